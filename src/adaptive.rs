@@ -0,0 +1,43 @@
+use serde_json::{Map, Value};
+
+use crate::log::Line;
+
+/// events looked at before the first one is written, to see which fields the logs have
+pub const SAMPLE_SIZE: usize = 50;
+
+/// the names loggers give each part of the default template, the first one found is used
+const LEVELS: [&str; 3] = ["level", "severity", "lvl"];
+const LOGGERS: [&str; 3] = ["logger", "logger_name", "service"];
+const MESSAGES: [&str; 2] = ["message", "msg"];
+const STACKS: [&str; 2] = ["stack", "stack_trace"];
+
+/**
+ * The pattern to pretty print with when none was given, made from the fields SAMPLE has: the time, the level and the
+ * logger or service when there are some, the message, then the stack on the lines after. Each field other than the
+ * time and message is only printed when an event has it, so events later on without one don't get gaps
+ */
+pub fn default_pattern(sample: &[Line]) -> String {
+  let seen = |names: &[&'static str]| names.iter().copied().find(|name| sample.iter().any(|line| has(&line.value, name)));
+
+  let mut pattern = String::new();
+
+  if seen(&["time"]).is_some() {
+    pattern.push_str("[%time]");
+  }
+
+  for name in [seen(&LEVELS), seen(&LOGGERS)].into_iter().flatten() {
+    pattern.push_str(&format!("%prefix/\\s/%{name}\\v/"));
+  }
+
+  let message = seen(&MESSAGES).unwrap_or(MESSAGES[0]);
+  pattern.push_str(&format!(" %{message}"));
+
+  let stack = seen(&STACKS).unwrap_or(STACKS[0]);
+  pattern.push_str(&format!(" %prefix/\\n/%{stack}\\v/"));
+
+  pattern.trim_start().to_string()
+}
+
+fn has(value: &Map<String, Value>, name: &str) -> bool {
+  value.get(name).is_some_and(|found| !found.is_null())
+}
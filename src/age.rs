@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use datetime::{LocalDate, LocalDateTime, LocalTime, Month};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::session::parse_duration;
+
+lazy_static! {
+  /// a date in a path, like app-2024-06-01.log, app.20240601.log or archive/2024/06/01/app.log
+  static ref PATH_DATE: Regex = Regex::new(r"(?:^|\D)((?:19|20)\d\d)[-_/]?(0[1-9]|1[0-2])[-_/]?(0[1-9]|[12]\d|3[01])(?:\D|$)").unwrap();
+}
+
+/**
+ * Leaves out sources that are too old to matter before they're opened, for --ignore-older-than DURATION. A file is
+ * too old if it was last changed before the cutoff, or if its path has a date in it and that whole day was before
+ * the cutoff, which still works for archives whose files were copied and so all look new
+ */
+#[derive(Debug)]
+pub struct AgeCutoff {
+  raw: String,
+  cutoff: SystemTime,
+  /// every source left out, by name
+  ignored: Vec<String>,
+}
+
+impl AgeCutoff {
+  pub fn parse(raw: &str) -> Result<AgeCutoff, String> {
+    let age = Duration::from_millis(parse_duration(raw, "--ignore-older-than")? as u64);
+
+    Ok(AgeCutoff {
+      raw: raw.trim().to_string(),
+      cutoff: SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH),
+      ignored: vec![],
+    })
+  }
+
+  /// why a source was left out, for --dry-run
+  pub fn reason(&self) -> String {
+    format!("last changed more than {} ago", self.raw)
+  }
+
+  /**
+   * Print how many sources were left out to stderr, if any were
+   */
+  pub fn report(&self) {
+    if !self.ignored.is_empty() {
+      eprintln!("Ignored {} sources {}", self.ignored.len(), self.reason());
+    }
+  }
+
+  pub fn ignored(&self) -> &[String] {
+    &self.ignored
+  }
+
+  /**
+   * Should this source be read? Remembers it if not
+   */
+  pub fn keep(&mut self, path: &Path) -> bool {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let too_old = modified.is_some_and(|modified| modified < self.cutoff)
+      || end_of_path_date(path).is_some_and(|end| end < self.cutoff);
+
+    if too_old {
+      self.ignored.push(path.to_str().unwrap_or("<invalid path>").to_string());
+    }
+
+    !too_old
+  }
+}
+
+/**
+ * The start of the day named in a path, if it has one. The last date wins, since files are usually named more exactly
+ * than the directories they're in
+ */
+pub fn path_date(path: &Path) -> Option<LocalDateTime> {
+  let raw = path.to_str()?;
+  let captures = PATH_DATE.captures_iter(raw).last()?;
+  let number = |index: usize| captures[index].parse::<i64>().ok();
+
+  let date = LocalDate::ymd(number(1)?, Month::from_one(number(2)? as i8).ok()?, number(3)? as i8).ok()?;
+
+  Some(LocalDateTime::new(date, LocalTime::midnight()))
+}
+
+/**
+ * The end of the day named in a path, if it has one
+ */
+fn end_of_path_date(path: &Path) -> Option<SystemTime> {
+  let start = path_date(path)?.to_instant().seconds();
+
+  Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(start).ok()? + 24 * 60 * 60))
+}
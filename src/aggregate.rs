@@ -0,0 +1,579 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use datetime::{ISO, LocalDateTime};
+use serde_json::{json, Map, Value};
+
+use crate::chunk::LogWriter;
+use crate::deterministic;
+use crate::heavy::{self, HeavyHitters};
+use crate::log::Line;
+use crate::pretty::PrettyDescriptor;
+use crate::width;
+
+/// the widest a histogram bar is drawn
+const BAR_WIDTH: u64 = 40;
+
+/// the most buckets of --stats hist the empty ones between are written for, past which only those with values are
+const MAX_FILLED: i64 = 1000;
+
+/**
+ * What --stats counts
+ */
+#[derive(Debug)]
+pub enum StatsMode {
+  Count,
+  /// every distinct combination of these fields, most common first
+  CountBy(Vec<String>),
+  /// like CountBy, but only the N most common
+  Top(usize, Vec<String>),
+  /// like Top, but estimated in a fixed amount of memory, for fields with too many values to count each one
+  Heavy(usize, Vec<String>),
+  /// events per bucket of a time field, every interval of milliseconds
+  Histogram(String, i64),
+  /// events per bucket of the values of a numeric field
+  Values(String, Buckets),
+}
+
+/**
+ * How the values of --stats hist are split into buckets
+ */
+#[derive(Debug, Clone, Copy)]
+pub enum Buckets {
+  /// every WIDTH, starting from 0
+  Linear(f64),
+  /// from each power of BASE to the next, so latencies of 2ms and 2s both get buckets that tell them apart. Values of
+  /// 0 and below have a bucket of their own
+  Exponential(f64),
+}
+
+impl Buckets {
+  /**
+   * Parse exp, exp:BASE or linear:WIDTH, optionally after buckets=
+   */
+  fn parse(raw: &str) -> Result<Buckets, String> {
+    let scheme = raw.strip_prefix("buckets=").unwrap_or(raw);
+
+    let (name, number) = match scheme.split_once(':') {
+      Some((name, number)) => (name, Some(number)),
+      None => (scheme, None),
+    };
+
+    let number = |what: &str| number.map(|number| number.parse::<f64>().ok()
+      .filter(|number| number.is_finite() && *number > 0.0)
+      .ok_or_else(|| format!("Argument --stats hist {name} {what} must be a number greater than 0, but was '{number}'"))).transpose();
+
+    match name {
+      "exp" => match number("BASE")?.unwrap_or(2.0) {
+        base if base > 1.0 => Ok(Buckets::Exponential(base)),
+        base => Err(format!("Argument --stats hist exp BASE must be more than 1, but was {base}")),
+      },
+      "linear" => Ok(Buckets::Linear(number("WIDTH")?.ok_or("Argument --stats hist linear must be followed by the WIDTH of each bucket, like linear:100")?)),
+      _ => Err(format!("Argument --stats hist buckets '{raw}' is not recognized. Use exp, exp:BASE or linear:WIDTH")),
+    }
+  }
+
+  fn describe(&self) -> String {
+    match self {
+      Buckets::Linear(width) => format!("buckets {width} wide"),
+      Buckets::Exponential(base) => format!("buckets from each power of {base} to the next"),
+    }
+  }
+
+  /// the bucket VALUE is in, or None for the one below every exponential bucket
+  fn index(&self, value: f64) -> Option<i64> {
+    match *self {
+      Buckets::Linear(width) => Some((value / width).floor() as i64),
+      Buckets::Exponential(_) if value <= 0.0 => None,
+      Buckets::Exponential(base) => {
+        let mut index = (value.ln() / base.ln()).floor() as i64;
+
+        // the logarithm can land just either side of a power
+        while self.start(index + 1) <= value {
+          index += 1;
+        }
+        while self.start(index) > value {
+          index -= 1;
+        }
+
+        Some(index)
+      }
+    }
+  }
+
+  fn start(&self, index: i64) -> f64 {
+    match *self {
+      Buckets::Linear(width) => index as f64 * width,
+      Buckets::Exponential(base) => base.powi(index as i32),
+    }
+  }
+}
+
+/**
+ * Counts events instead of writing them, and writes a table, or JSON, of the counts once every event has been read
+ */
+#[derive(Debug)]
+pub struct Stats {
+  mode: StatsMode,
+  /// write JSON instead of a table, from --json true
+  pub json: bool,
+  total: u64,
+  groups: HashMap<Vec<Option<String>>, u64>,
+  heavy: Option<HeavyHitters>,
+  buckets: BTreeMap<i64, u64>,
+  /// events left out of the histogram for not having a readable time in its field
+  untimed: u64,
+  /// events left out of --stats hist for not having a number in its field
+  not_numeric: u64,
+  /// events in --stats hist with a value of 0 or less, which have no exponential bucket
+  not_positive: u64,
+}
+
+impl StatsMode {
+  /**
+   * Read the mode and whatever it takes after it: count, count-by FIELDS, top N FIELDS, heavy N FIELDS, histogram
+   * FIELD INTERVAL or hist FIELD BUCKETS. FIELDS are comma separated and fields may start with % like they do in
+   * patterns
+   */
+  pub fn parse<Args: Iterator<Item=String>>(src: &mut Args) -> Result<StatsMode, String> {
+    let mode = src.next().ok_or("Argument --stats must be followed by count, count-by, top, heavy, histogram or hist")?;
+
+    let mut field = |what: &str| src.next().ok_or_else(|| format!("Argument --stats {mode} must be followed by {what}"));
+
+    let mode = match mode.as_str() {
+      "count" => StatsMode::Count,
+      "count-by" => StatsMode::CountBy(parse_fields(&field("FIELDS")?)?),
+      "top" | "heavy" => {
+        let count = field("a count N and then FIELDS")?;
+        let count = count.parse::<usize>().ok()
+          .filter(|count| *count > 0)
+          .ok_or_else(|| format!("Argument --stats {mode} must be followed by a count greater than 0, but was '{count}'"))?;
+
+        let fields = parse_fields(&field("FIELDS after N")?)?;
+
+        if mode == "top" { StatsMode::Top(count, fields) } else { StatsMode::Heavy(count, fields) }
+      }
+      "histogram" => {
+        let time_field = parse_fields(&field("a time FIELD and then an INTERVAL")?)?.remove(0);
+        let interval = field("an INTERVAL after the FIELD, like 5m")?;
+
+        StatsMode::Histogram(time_field, parse_interval(&interval)?)
+      }
+      "hist" => {
+        let field_name = parse_fields(&field("a numeric FIELD and then BUCKETS")?)?.remove(0);
+        let buckets = field("BUCKETS after the FIELD, like exp or linear:100")?;
+
+        StatsMode::Values(field_name, Buckets::parse(&buckets)?)
+      }
+      _ => return Err(format!("Argument --stats '{mode}' is not recognized. Valid options are count, count-by, top, heavy, histogram or hist")),
+    };
+
+    Ok(mode)
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      StatsMode::Count => "count of events".to_string(),
+      StatsMode::CountBy(fields) => format!("count by {}", fields.join(", ")),
+      StatsMode::Top(count, fields) => format!("top {count} by {}", fields.join(", ")),
+      StatsMode::Heavy(count, fields) => format!("about the top {count} by {}", fields.join(", ")),
+      StatsMode::Histogram(field, interval) => format!("histogram of {field} every {}", describe_interval(*interval)),
+      StatsMode::Values(field, buckets) => format!("histogram of {field} in {}", buckets.describe()),
+    }
+  }
+}
+
+fn parse_fields(raw: &str) -> Result<Vec<String>, String> {
+  let fields: Vec<String> = raw.split(',')
+    .map(|field| field.trim().trim_start_matches('%').to_string())
+    .collect();
+
+  if fields.iter().any(|field| field.is_empty()) {
+    return Err(format!("Argument --stats has an empty field name in '{raw}'"));
+  }
+
+  Ok(fields)
+}
+
+/**
+ * Parse an interval like 30s, 5m, 1h or 1d into milliseconds
+ */
+fn parse_interval(raw: &str) -> Result<i64, String> {
+  let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+  let (number, unit) = raw.split_at(split);
+
+  let number: i64 = number.parse().ok()
+    .filter(|number| *number > 0)
+    .ok_or_else(|| format!("Argument --stats histogram INTERVAL must be a number greater than 0 followed by s, m, h or d, but was '{raw}'"))?;
+
+  let unit = match unit {
+    "ms" => 1,
+    "s" => 1000,
+    "m" => 60 * 1000,
+    "h" => 60 * 60 * 1000,
+    "d" => 24 * 60 * 60 * 1000,
+    _ => return Err(format!("Argument --stats histogram INTERVAL must be a number followed by s, m, h or d, but was '{raw}'")),
+  };
+
+  Ok(number * unit)
+}
+
+fn describe_interval(ms: i64) -> String {
+  [("d", 86_400_000), ("h", 3_600_000), ("m", 60_000), ("s", 1000)].iter()
+    .find(|(_, unit)| ms % unit == 0)
+    .map_or(format!("{ms}ms"), |(name, unit)| format!("{}{name}", ms / unit))
+}
+
+impl Stats {
+  pub fn new(mode: StatsMode) -> Stats {
+    let heavy = match mode {
+      StatsMode::Heavy(count, _) => Some(HeavyHitters::new(count)),
+      _ => None,
+    };
+
+    Stats {
+      mode,
+      json: false,
+      total: 0,
+      groups: HashMap::new(),
+      heavy,
+      buckets: BTreeMap::new(),
+      untimed: 0,
+      not_numeric: 0,
+      not_positive: 0,
+    }
+  }
+
+  pub fn mode(&self) -> &StatsMode {
+    &self.mode
+  }
+
+  pub fn add(&mut self, line: &Line) {
+    self.total += 1;
+
+    match &self.mode {
+      StatsMode::Count => {}
+      StatsMode::CountBy(fields) | StatsMode::Top(_, fields) => {
+        *self.groups.entry(group_key(fields, line)).or_insert(0) += 1;
+      }
+      StatsMode::Heavy(_, fields) => {
+        let key = group_key(fields, line);
+
+        if let Some(heavy) = &mut self.heavy {
+          heavy.add(key);
+        }
+      }
+      StatsMode::Histogram(field, interval) => {
+        let time = if field == "time" {
+          line.time
+        } else {
+          line.value.get(field).and_then(Value::as_str).and_then(|time| LocalDateTime::from_str(time).ok())
+        };
+
+        match time {
+          Some(time) => {
+            let ms = millis(time);
+
+            *self.buckets.entry(ms - ms.rem_euclid(*interval)).or_insert(0) += 1;
+          }
+          None => self.untimed += 1,
+        }
+      }
+      StatsMode::Values(field, buckets) => {
+        let value = match line.value.get(field) {
+          Some(Value::Number(number)) => number.as_f64(),
+          Some(Value::String(text)) => text.trim().parse::<f64>().ok().filter(|number| number.is_finite()),
+          _ => None,
+        };
+
+        match value.map(|value| buckets.index(value)) {
+          Some(Some(index)) => *self.buckets.entry(index).or_insert(0) += 1,
+          Some(None) => self.not_positive += 1,
+          None => self.not_numeric += 1,
+        }
+      }
+    }
+  }
+
+  /**
+   * Write the counts so far, under a line with when they were taken, or as JSON marked as a snapshot
+   */
+  pub fn write_snapshot(&self, target: &mut Box<dyn LogWriter>) {
+    if self.json {
+      let mut snapshot = self.to_json();
+
+      if let Value::Object(snapshot) = &mut snapshot {
+        snapshot.insert("snapshot".to_string(), Value::Bool(true));
+      }
+
+      serde_json::to_writer(&mut *target, &snapshot).expect("Failed to write stats");
+      target.end_line();
+      return;
+    }
+
+    // with --deterministic nothing is written that would change on another run
+    let header = if deterministic::is_enabled() {
+      format!("Snapshot after {} events", self.total)
+    } else {
+      let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("System clock is before 1970").as_millis() as i64;
+
+      format!("Snapshot at {}, after {} events", from_millis(now).iso(), self.total)
+    };
+
+    target.write_all(header.as_bytes()).expect("Failed to write stats");
+    target.end_line();
+    self.write(target);
+    target.end_line();
+  }
+
+  pub fn write(&self, target: &mut Box<dyn LogWriter>) {
+    if self.json {
+      serde_json::to_writer(&mut *target, &self.to_json()).expect("Failed to write stats");
+      target.end_line();
+      return;
+    }
+
+    let rows = self.rows();
+
+    let widths: Vec<usize> = (0..rows[0].len())
+      .map(|column| rows.iter().map(|row| width::width(&row[column])).max().unwrap_or(0))
+      .collect();
+
+    for row in &rows {
+      let line: Vec<String> = row.iter().zip(&widths).enumerate()
+        .map(|(column, (cell, columns))| {
+          // counts read best on the right, everything else on the left
+          width::pad(cell, *columns, self.is_number_column(column, row.len()))
+        })
+        .collect();
+
+      target.write_all(line.join("  ").trim_end().as_bytes()).expect("Failed to write stats");
+      target.end_line();
+    }
+
+    if let Some(heavy) = &self.heavy {
+      let note = format!("Counts are estimated, and at most {} over", heavy.error());
+
+      target.write_all(note.as_bytes()).expect("Failed to write stats");
+      target.end_line();
+    }
+
+    if self.untimed > 0 {
+      let note = format!("{} events without a time were left out", self.untimed);
+
+      target.write_all(note.as_bytes()).expect("Failed to write stats");
+      target.end_line();
+    }
+
+    if let (StatsMode::Values(field, _), true) = (&self.mode, self.not_numeric > 0) {
+      let note = format!("{} events without a number in {field} were left out", self.not_numeric);
+
+      target.write_all(note.as_bytes()).expect("Failed to write stats");
+      target.end_line();
+    }
+  }
+
+  fn is_number_column(&self, column: usize, columns: usize) -> bool {
+    match self.mode {
+      StatsMode::Count => true,
+      StatsMode::CountBy(_) | StatsMode::Top(..) | StatsMode::Heavy(..) => column + 2 >= columns,
+      StatsMode::Histogram(..) => column == 1,
+      StatsMode::Values(..) => column == 1 || column == 2,
+    }
+  }
+
+  /// groups, most common first and then by value so the order never changes between runs
+  fn sorted_groups(&self) -> Vec<(&Vec<Option<String>>, u64)> {
+    if let Some(heavy) = &self.heavy {
+      return heavy.top();
+    }
+
+    let mut groups: Vec<_> = self.groups.iter().map(|(key, count)| (key, *count)).collect();
+    groups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    if let StatsMode::Top(count, _) = self.mode {
+      groups.truncate(count);
+    }
+
+    groups
+  }
+
+  /// the table, header first
+  fn rows(&self) -> Vec<Vec<String>> {
+    match &self.mode {
+      StatsMode::Count => vec![vec!["count".to_string()], vec![self.total.to_string()]],
+      StatsMode::CountBy(fields) | StatsMode::Top(_, fields) | StatsMode::Heavy(_, fields) => {
+        let mut header = fields.clone();
+        header.push("count".to_string());
+        header.push("percent".to_string());
+
+        let mut rows = vec![header];
+
+        for (key, count) in self.sorted_groups() {
+          let mut row: Vec<String> = key.iter().map(|value| value.clone().unwrap_or("-".to_string())).collect();
+          row.push(count.to_string());
+          row.push(format!("{:.1}%", count as f64 * 100.0 / self.total.max(1) as f64));
+
+          rows.push(row);
+        }
+
+        rows
+      }
+      StatsMode::Histogram(field, _) => {
+        let highest = self.buckets.values().copied().max().unwrap_or(1).max(1);
+        let mut rows = vec![vec![field.clone(), "count".to_string(), String::new()]];
+
+        for (start, count) in self.bucket_rows() {
+          let bar = "#".repeat((count * BAR_WIDTH).div_ceil(highest) as usize);
+
+          rows.push(vec![from_millis(start).iso().to_string(), count.to_string(), bar]);
+        }
+
+        rows
+      }
+      StatsMode::Values(field, buckets) => {
+        let value_rows = self.value_rows(*buckets);
+        let highest = value_rows.iter().map(|(_, _, count)| *count).max().unwrap_or(1).max(1);
+        let mut rows = vec![vec![field.clone(), "count".to_string(), "percent".to_string(), String::new()]];
+
+        for (from, to, count) in value_rows {
+          let range = match from {
+            Some(from) => format!("{from} to {to}"),
+            None => format!("{to} or less"),
+          };
+
+          let bar = "#".repeat((count * BAR_WIDTH).div_ceil(highest) as usize);
+          let percent = format!("{:.1}%", count as f64 * 100.0 / self.total.max(1) as f64);
+
+          rows.push(vec![range, count.to_string(), percent, bar]);
+        }
+
+        rows
+      }
+    }
+  }
+
+  /**
+   * (from, to, count) of every bucket from the first to the last with a value in it, including the empty ones between
+   * them. Buckets include their start but not their end, and the one for 0 or less of an exponential histogram has
+   * no start
+   */
+  fn value_rows(&self, buckets: Buckets) -> Vec<(Option<f64>, f64, u64)> {
+    let mut rows = vec![];
+
+    if self.not_positive > 0 {
+      rows.push((None, 0.0, self.not_positive));
+    }
+
+    let (Some(first), Some(last)) = (self.buckets.keys().next(), self.buckets.keys().last()) else {
+      return rows;
+    };
+
+    // a few values far from the rest would otherwise fill the table with empty buckets
+    let indexes: Vec<i64> = if last - first < MAX_FILLED { (*first..=*last).collect() } else { self.buckets.keys().copied().collect() };
+
+    for index in indexes {
+      rows.push((Some(buckets.start(index)), buckets.start(index + 1), self.buckets.get(&index).copied().unwrap_or(0)));
+    }
+
+    rows
+  }
+
+  /**
+   * Every bucket from the first to the last, including the empty ones between them, so gaps show up in the histogram
+   */
+  fn bucket_rows(&self) -> Vec<(i64, u64)> {
+    let StatsMode::Histogram(_, interval) = self.mode else {
+      return vec![];
+    };
+
+    let (Some(first), Some(last)) = (self.buckets.keys().next(), self.buckets.keys().last()) else {
+      return vec![];
+    };
+
+    (0..=(last - first) / interval)
+      .map(|index| first + index * interval)
+      .map(|start| (start, self.buckets.get(&start).copied().unwrap_or(0)))
+      .collect()
+  }
+
+  fn to_json(&self) -> Value {
+    match &self.mode {
+      StatsMode::Count => json!({ "count": self.total }),
+      StatsMode::CountBy(fields) | StatsMode::Top(_, fields) | StatsMode::Heavy(_, fields) => {
+        let groups: Vec<Value> = self.sorted_groups().into_iter()
+          .map(|(key, count)| {
+            let mut group: Map<String, Value> = fields.iter().zip(key)
+              .map(|(field, value)| (field.clone(), value.clone().map_or(Value::Null, Value::String)))
+              .collect();
+
+            group.insert("count".to_string(), json!(count));
+
+            Value::Object(group)
+          })
+          .collect();
+
+        match &self.heavy {
+          Some(heavy) => json!({ "total": self.total, "groups": groups, "max_overcount": heavy.error() }),
+          None => json!({ "total": self.total, "groups": groups }),
+        }
+      }
+      StatsMode::Histogram(field, interval) => {
+        let buckets: Vec<Value> = self.bucket_rows().into_iter()
+          .map(|(start, count)| json!({ "start": from_millis(start).iso().to_string(), "count": count }))
+          .collect();
+
+        json!({
+          "field": field,
+          "interval": describe_interval(*interval),
+          "total": self.total,
+          "untimed": self.untimed,
+          "buckets": buckets,
+        })
+      }
+      StatsMode::Values(field, scheme) => {
+        let buckets: Vec<Value> = self.value_rows(*scheme).into_iter()
+          .map(|(from, to, count)| json!({ "from": from, "to": to, "count": count }))
+          .collect();
+
+        let scheme = match scheme {
+          Buckets::Linear(width) => json!({ "linear": width }),
+          Buckets::Exponential(base) => json!({ "exp": base }),
+        };
+
+        json!({
+          "field": field,
+          "scheme": scheme,
+          "total": self.total,
+          "not_numeric": self.not_numeric,
+          "buckets": buckets,
+        })
+      }
+    }
+  }
+}
+
+/**
+ * The values of FIELDS in LINE, where message-template is the 'message' without what changes between events of the
+ * same kind
+ */
+fn group_key(fields: &[String], line: &Line) -> Vec<Option<String>> {
+  fields.iter()
+    .map(|field| if field == heavy::MESSAGE_TEMPLATE {
+      line.value.get("message").map(PrettyDescriptor::pretty_value).map(|message| heavy::template(&message))
+    } else {
+      line.value.get(field).map(PrettyDescriptor::pretty_value)
+    })
+    .collect()
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
+
+fn from_millis(ms: i64) -> LocalDateTime {
+  LocalDateTime::at_ms(ms.div_euclid(1000), ms.rem_euclid(1000) as i16)
+}
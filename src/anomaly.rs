@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use datetime::LocalDateTime;
+use serde_json::{json, Map, Value};
+
+use crate::pretty::PrettyDescriptor;
+use crate::session::{millis, parse_duration};
+
+pub const ANOMALY_FIELD: &str = "_anomaly";
+
+/// how quickly the expected rate follows the rate seen, as the weight of each new window
+const ALPHA: f64 = 0.1;
+
+/// windows a group must have been seen for before it can be flagged, so it isn't judged on a rate it hasn't learned
+const WARMUP: u64 = 5;
+
+/// most quiet windows folded into the expected rate at once, after which it has decayed to nothing anyway
+const MAX_QUIET: i64 = 200;
+
+/**
+ * Flags events that come while their group is far busier than usual, for --flag-anomalies rate. Events are grouped by
+ * the values of a few fields, 'level' and 'service' unless given, and counted in windows of their own time. Each group
+ * learns the rate it usually has as a moving average and deviation of its counts, and once a window's count goes over
+ * the usual rate by SENSITIVITY deviations, every event in that window from then on gets '_anomaly'. The deviation is
+ * never taken as less than the square root of the rate, the noise a steady stream has anyway, so a group that's
+ * always quiet isn't flagged for a handful of events.
+ *
+ * Events must come in time order, as they do when merged, and events without a time are left alone
+ */
+#[derive(Debug)]
+pub struct AnomalyDetector {
+  fields: Vec<String>,
+  window: String,
+  window_ms: i64,
+  sensitivity: f64,
+  groups: HashMap<String, Group>,
+  flagged: u64,
+  windows: u64,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+  /// the window being counted, as its start
+  start: i64,
+  count: u64,
+  /// windows seen before this one
+  seen: u64,
+  mean: f64,
+  variance: f64,
+  /// whether this window has been counted as anomalous yet
+  flagged: bool,
+}
+
+impl AnomalyDetector {
+  /**
+   * Parse rate[,by=FIELD+FIELD][,window=DURATION][,sensitivity=N], like rate,by=level+host,window=5m,sensitivity=4
+   */
+  pub fn parse(raw: &str) -> Result<AnomalyDetector, String> {
+    let mut parts = raw.split(',').map(str::trim);
+
+    match parts.next() {
+      Some("rate") => {}
+      _ => return Err(format!("Argument --flag-anomalies '{raw}' must start with the kind of anomaly, which can only be 'rate'")),
+    }
+
+    let mut detector = AnomalyDetector {
+      fields: vec!["level".to_string(), "service".to_string()],
+      window: "1m".to_string(),
+      window_ms: 60 * 1000,
+      sensitivity: 3.0,
+      groups: HashMap::new(),
+      flagged: 0,
+      windows: 0,
+    };
+
+    for part in parts {
+      match part.split_once('=') {
+        Some(("by", fields)) => {
+          detector.fields = fields.split('+').map(|field| field.trim().to_string()).collect();
+
+          if detector.fields.iter().any(String::is_empty) {
+            return Err(format!("Anomaly option 'by={fields}' must name fields separated by +, like by=level+host"));
+          }
+        }
+        Some(("window", window)) => {
+          detector.window = window.to_string();
+          detector.window_ms = parse_duration(window, "--flag-anomalies window")?;
+        }
+        Some(("sensitivity", sensitivity)) => {
+          detector.sensitivity = sensitivity.parse().ok()
+            .filter(|sensitivity: &f64| *sensitivity > 0.0)
+            .ok_or_else(|| format!("Anomaly sensitivity '{sensitivity}' must be a number greater than 0, like 3"))?;
+        }
+        _ => return Err(format!("Anomaly option '{part}' is not recognized. Valid options are by=FIELDS, window=DURATION and sensitivity=N")),
+      }
+    }
+
+    Ok(detector)
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{ANOMALY_FIELD} on windows of {} over the usual rate of each {} by {} deviations", self.window, self.fields.join(" and "), self.sensitivity)
+  }
+
+  pub fn apply(&mut self, values: &mut Map<String, Value>, time: Option<LocalDateTime>) {
+    let Some(time) = time else {
+      return;
+    };
+
+    let key = self.fields.iter()
+      .map(|field| values.get(field).map_or("-".to_string(), PrettyDescriptor::pretty_value))
+      .collect::<Vec<_>>()
+      .join(" ");
+
+    let now = millis(time);
+    let start = now - now.rem_euclid(self.window_ms);
+
+    let group = self.groups.entry(key.clone()).or_insert_with(|| Group { start, ..Group::default() });
+
+    if start > group.start {
+      group.advance(start, self.window_ms);
+    }
+
+    group.count += 1;
+
+    let expected = group.mean;
+    let deviation = group.variance.sqrt().max(group.mean.sqrt()).max(1.0);
+
+    if group.seen < WARMUP || (group.count as f64) <= expected + self.sensitivity * deviation {
+      return;
+    }
+
+    if !group.flagged {
+      group.flagged = true;
+      self.windows += 1;
+    }
+
+    self.flagged += 1;
+
+    values.insert(ANOMALY_FIELD.to_string(), json!({
+      "kind": "rate",
+      "group": key,
+      "count": group.count,
+      "expected": (expected * 10.0).round() / 10.0,
+    }));
+  }
+
+  /// how many events were flagged, and in how many windows
+  pub fn flagged(&self) -> (u64, u64) {
+    (self.flagged, self.windows)
+  }
+}
+
+impl Group {
+  /**
+   * Learn from the window that ended and any quiet ones after it, then start counting the window at 'start'
+   */
+  fn advance(&mut self, start: i64, window_ms: i64) {
+    let quiet = ((start - self.start) / window_ms - 1).min(MAX_QUIET);
+
+    self.learn(self.count as f64);
+
+    for _ in 0..quiet {
+      self.learn(0.0);
+    }
+
+    self.start = start;
+    self.count = 0;
+    self.flagged = false;
+  }
+
+  fn learn(&mut self, count: f64) {
+    if self.seen == 0 {
+      self.mean = count;
+    } else {
+      let diff = count - self.mean;
+
+      self.mean += ALPHA * diff;
+      self.variance = (1.0 - ALPHA) * (self.variance + ALPHA * diff * diff);
+    }
+
+    self.seen += 1;
+  }
+}
@@ -1,42 +1,500 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::str::FromStr;
+use std::time::Duration;
 
 use datetime::LocalDateTime;
-use glob::glob;
-
+use glob::{glob, Pattern};
+
+use crate::age::AgeCutoff;
+use crate::aggregate::{Stats, StatsMode};
+use crate::anomaly::AnomalyDetector;
+use crate::autotune::Autotune;
+use crate::cache::{self, CacheAdvice};
+use crate::catalog::{self, Catalog};
+use crate::checksum::Checksum;
 use crate::chunk::ChunkInfo;
-use crate::filter::FilterSet;
+use crate::clipboard::CLIPBOARD_OUTPUT;
+use crate::codec::{self, Codec, CompressionRoutes, SourceCodec};
+use crate::compaction;
+use crate::correlate::Correlator;
+use crate::crypt::FieldCrypto;
+use crate::deadline::Deadline;
+use crate::deterministic;
+use crate::escalate::Escalation;
+use crate::escape::JsonEscape;
+use crate::exists::OnExists;
+use crate::expect::ExpectCount;
+use crate::external::ExternalSort;
+use crate::filter::{FilterSet, ValueFilter};
+use crate::fingerprint::Fingerprint;
+use crate::flags;
+use crate::follow;
+use crate::frame::{Framing, FRAMES_FORMAT};
+use crate::functions;
+use crate::group::Grouping;
+use crate::html::{HtmlReport, HTML_FORMAT};
+use crate::ignore::IgnoreRules;
+use crate::indented::INDENTED_FORMAT;
+use crate::index;
+use crate::input::{InputFormat, TimeParser};
+use crate::integrity;
+use crate::levels::{self, LevelMap};
+use crate::link::Linker;
+use crate::log::Span;
 use crate::LogFile;
-use crate::pretty::PrettyDescriptor;
+use crate::manual;
+use crate::order::KeyOrder;
+use crate::partition::Partition;
+use crate::patterns::{self, RegexOptions};
+use crate::pipeline;
+use crate::plugin::{Plugin, PluginKind};
+use crate::preset::Preset;
+use crate::pretty::{PrettyDescriptor, PrettyTheme};
+use crate::project::Projection;
+use crate::quota::Quota;
+use crate::rate::RateLimit;
+use crate::raw::RawCopy;
+use crate::render;
+use crate::replay::Replay;
+use crate::retry::{self, RetryPolicy};
+use crate::route::Route;
+use crate::secrets::{SecretAction, SecretScanner};
+use crate::session::Sessionizer;
+use crate::shard::Shard;
+use crate::snapshot::SnapshotEvery;
+use crate::sort::SortKey;
+use crate::split::SplitInterval;
+use crate::summary::SummaryBy;
+use crate::table::Table;
+use crate::tenant::Tenant;
+use crate::timestamp::TimeOutput;
+use crate::trace::TraceIds;
 use crate::translate::Translation;
-
-const HELP: &str = r#"
-saw SOURCE_FILES
-  -h, --help [TOPIC]            Print help. If TOPIC is provided it will give more detail or list the topics
-  -v, --version                 Prints the version of saw
-  -p, --pretty [PATTERN]        Pretty print output as text instead of gzipped json PATTERN is optional and defines a pattern
-  -f, --filter PATTERN          Filter based on contents, PATTERN defines how and what to match on
-  -o, --output PATH             Instead of outputting to stdout, pipe results to a file directly
-  -c, --chunked [SIZE]          Requires --output option. Chunks output into multiple files based on size or number of lines
-  -r, --range MIN MAX           Filters logs to between the two given timestamps, (min is inclusive, max is exclusive)
-    --daily                     Tell saw that all lines in a single log file have the same date. This way saw can skip whole files that fall outside of range.
-  -t, --translate FIELD PATTERN Transform strings before printing them
-  -z, --zip true|false          Gzip output. Defaults to true if output is provided and false otherwise
-  -j, --json true|false         Output as JSON. Has defaults for all cases. Passing true while also providing pretty is illegal
+use crate::truncate::Truncation;
+use crate::upload::ChunkWorkers;
+use crate::verify::VerifyAction;
+use crate::watermark::Watermark;
+use crate::why::Why;
+use crate::zone::TimeZone;
+
+const HELP_NOTES: &str = r#"
+Remote files can be read with --ssh [user@]host:/path/to/*.log which lists and streams each matching file
+using the ssh command, so keys and config in ~/.ssh apply. Every event is given a '_host' field with the host name.
+--ssh can be passed more than once to merge logs from several machines. Only * and ? in the path are expanded by the
+remote shell, and a leading ~/ is the remote home directory; spaces and anything else are taken as they are. Files
+are listed while the arguments are read, but each one is only streamed once the merge starts reading it. Remote files
+are read to their end, so --ssh can't be used with --follow.
+
+Commands given with --command are run with sh, and every event they produce is tagged with '_command', '_pid',
+'_stream' (stdout or stderr) and '_cgroup' when it's available. How each command exited is printed once it's done.
+Each line a command writes to stderr becomes an event at the time it was written, with the line as its 'message'
+unless it's a JSON object, and is merged with stdout by that time, so a failure shows up next to what led to it.
+--command-with-stderr reads stderr as a log like stdout instead, for programs that write their events there. Most
+programs write plain text to stderr, so --passthrough-invalid is useful along with --command-with-stderr.
 
 Mutiple source files can be passed, and all are treated as globs.
 
+Every mistake in the arguments is listed together before anything is read, and saw exits with code 2.
+
 You can also pass "-" to read stdin as a source file, in a addition to any other sources.
 stdin must be plain text and cannot be gzipped.
 
-help TOPIC values are:
-  pretty    How pretty printing patterns work
-  filter    How filtering patterns work
-  range     How to use the range option
-  translate How to use the translate feature (it's like sed for json)
-  chunked   The syntax for chunked size limits
+"--output clipboard" puts the output on the system clipboard once saw is done instead of writing a file, using
+pbcopy, clip, wl-copy, xclip or xsel, whichever is available. It's meant for a few lines to paste into a ticket, so it
+stops at 1mb and says how many events were left off. Pass ./clipboard to write a file with that name.
+
+saw won't write over a file that's already there, so an old archive can't be lost by running the same command twice.
+--on-exists POLICY says what to do instead, for the --output file and every chunk or --split-by-time bucket:
+  error      stop before writing anything, the default
+  overwrite  replace the old file
+  append     add to the end of the old file, which also works for gzip and zstd
+  rename     move the old file aside to PATH.1, or the next free number, and write a new one
+With --chunked or --split-by-time, the error policy checks for old chunks or buckets before starting. Chunks written
+with --skip-unchanged are only replaced when they changed, so --on-exists can't be passed with it.
+
+--route "FILTER => PATH" writes events matching FILTER to PATH instead of the output, so events can be separated in
+one pass. FILTER is anything --filter takes, and routes are tried in the order given, with each event going to the
+first one it matches and to --output, or stdout, if it matches none. PATH is written in the same format and compressed
+the same way as --output would be, and follows --on-exists and --checksum too:
+  saw 'app/*.log' -o rest.log --route "%stack? => errors.log" --route "%level=DEBUG || %level=TRACE => debug.log"
+
+--checksum sha256 writes the checksum of every file written next to it, as PATH.sha256 for the --output file and each
+chunk or bucket, in the format `sha256sum -c PATH.sha256` checks. It's of the file as written, compressed if it is. A
+file that is appended to is checksummed whole once done. With --skip-unchanged the checksum of each chunk is kept in
+OUTPUT.manifest as well, and an unchanged chunk keeps the checksum file it already had.
+
+--deterministic makes what's written depend only on the events read, so the same sources give the same bytes on every
+run and machine, and an archive can be written again to check it against a copy. Gzip headers never hold a time or
+name, and keys are always written in order, so that already holds without it. On top of that, sources are merged
+in order of their names, so events at the same time come out the same whichever order the sources were given in,
+when files were changed is never used, so a first line that isn't JSON is left out with --passthrough-invalid and
+--max-open-files only goes by index and path, %now prints SOURCE_DATE_EPOCH, which must be set, and snapshots don't
+say when they were taken. Options whose output depends on when saw runs, like --follow and --deadline, can't be used,
+and neither can --encrypt-field, whose nonces are random, or a --time-format without a year, which is this year:
+  saw 'archive/2024-06-*.log.gz' --deterministic -o june.log.gz --checksum sha256
+
+--stdin-multiplex TAGS reads stdin as several sources sharing one pipe. Every line starts with its source's tag and
+a tab, and TAGS lists every tag to expect, like "web1,web2". Each tag is merged like a file of its own and its events
+are given a '_source' field. Lines with a tag that isn't listed are skipped with a warning. Lines of one tag wait in
+memory until the others catch up, so sources should be interleaved rather than sent one after another:
+  ssh gateway 'tag-and-cat /var/log/app' | saw --stdin-multiplex web1,web2
+
+Gzipped files with several members, like rotated logs that were appended to, are read to the end. If the last
+member is cut off or followed by garbage, saw warns and keeps every line before the damage.
+
+When nothing would change the events (no pretty, filter, range, translate or similar options) and the source
+files don't overlap in time, saw simply joins the files together byte for byte, without re-encoding them. This
+is much faster for gzipped archives, but means lines are written exactly as they were read.
+
+With --audit, once done saw prints how many events each translation ran on, changed and removed the field from,
+so that redactions can be checked after the fact. Translations run after --filter and --range, so events those
+drop are never counted.
+
+--deadline limits how long saw runs for, like "--deadline 10m". Once it passes, saw finishes reading the events
+with the same time as the last one read, closes the output properly and exits with code 75. When writing to --output a
+checkpoint is left next to it in OUTPUT.checkpoint, whose 'resume_from' time can be passed as the start of --range
+to pick up exactly where it stopped.
+
+--verify-sorted-output [fail|warn] checks that every event is written at the same time as the one before it or
+later, or earlier with --reverse, for archives that other tools binary search by time. With fail, the default, saw
+stops writing at the first event out of order, closes the output properly and exits with code 65, with warn it writes
+everything and says how many were out of order once done. Either way the first one is reported with where it came from:
+  saw 'archive/*.log.gz' -o merged.log.gz --verify-sorted-output
+
+--pretty-per FIELD VALUE=PATTERN picks a different pretty pattern for events whose FIELD is VALUE, and can be passed
+once for each VALUE. Events that don't match use the --pretty pattern, or the one given for the VALUE 'default':
+  saw app.log --pretty-per level 'ERROR=[%time] %message %rest%prefix/\n/%stack\v/' --pretty-per level 'default=%message'
+
+--rate-limit RATE[,burst=N][,drop] limits how quickly events are written, like "1000/s" or "50000/m". Up to N events
+(by default one second's worth) can be written at once before the limit kicks in. By default saw waits until each
+event may be written, with 'drop' it throws away the events over the limit instead and says how many once done.
+The slowest RATE allowed is 1/h.
+
+--quota "FILTER:N,FILTER:N" keeps at most N events matching each FILTER, written the same as for --filter, for a
+triage digest with some of everything rather than whatever came first. An event counts against the first FILTER it
+matches, events matching none are left out, and reading stops once every FILTER has its N. What was kept and left
+out of each is printed once done:
+  saw 'incident/*.log.gz' --quota "%level=ERROR:100,%level=WARN:20,%message=timeout:10"
+
+--escalate "FILTER => FIELD=VALUE[,FIELD=VALUE]" sets fields on every event matching FILTER, which is written the same
+as for --filter. It's meant for producers that log serious problems at the wrong level, so that --filter, --pretty-per,
+--summary-by and anything reading the output see the level they should have had. Rules run in the order given, after
+--trace-ids and before --filter, and each one sees what the rules before it set:
+  saw app.log --escalate "%message=OutOfMemory => level=FATAL" --escalate "%status=^5 => level=ERROR,alert=true"
+
+--stats counts the events that would have been written instead of writing them, and writes a table of the counts
+to the output once done, or JSON with --json true. It's much faster than piping the events to jq or sort | uniq:
+  count                     how many events there were
+  count-by FIELDS           how many had each value of the comma separated FIELDS, most common first
+  top N FIELDS              the same, but only the N most common
+  heavy N FIELDS            about the N most common, in a fixed amount of memory however many values there are
+  histogram FIELD INTERVAL  how many fell in each INTERVAL, like 30s, 5m or 1h, of a time FIELD
+  hist FIELD BUCKETS        how many had each range of values of a numeric FIELD, drawn as a chart
+  saw *.log.gz -f %level=ERROR --stats count-by %exceptionClass
+  saw *.log.gz --stats histogram %time 5m
+
+hist shows the shape of a distribution, like latencies, which an average hides. BUCKETS is exp for a bucket from each
+power of 2 to the next, exp:BASE for powers of another BASE like exp:10, or linear:WIDTH for buckets WIDTH wide from 0,
+and can be written buckets=exp. Values of 0 or less have an exponential bucket of their own. Numbers written as
+strings are read as numbers, and events without a number in FIELD are left out and counted:
+  saw 'gateway/*.log.gz' --stats hist %duration buckets=exp
+
+heavy is for fields with too many values to count each one, like user ids. Its counts are estimated with a count-min
+sketch, so they can be a little over but never under, and how far over they can be is written with them. FIELDS can
+include message-template, the 'message' with numbers, ids, hex and quoted strings replaced, to find which kinds of
+message are most common:
+  saw *.log.gz --stats heavy 20 message-template
+
+--snapshot-every EVERY writes what --stats has counted so far every so often, as well as once done, for sources that
+never end like --follow, where the report at the end never comes. EVERY is a number of events, like 1000, or an
+interval like 10s or 5m, which is checked as events arrive, since the counts can't change while none do. Each
+snapshot is a table under a line with when it was taken, or with --json true a line of JSON with "snapshot": true.
+With --group-by, the block being gathered is written at each snapshot, rather than waiting for a different FIELD:
+  saw 'services/*.log' --follow --stats top 10 %service --snapshot-every 30s
+
+--control-socket PATH listens on a unix socket at PATH while saw runs, so a long run or --follow can be managed
+without killing it. Each command is a line, answered with a line of JSON. pause stops reading, and writing with it,
+until resume. stats says how many events have been written, the time of the last one and whether saw is paused.
+stop finishes the events already read and ends as if the sources had, closing and finishing compressed output
+properly, where stopping saw with a signal would leave it cut off. The socket is removed once saw is done:
+  saw 'nightly/*.log.gz' -o merged.log.gz --control-socket /run/saw.sock &
+  echo pause | nc -U /run/saw.sock
+
+-F or --follow keeps reading like "tail -f" instead of stopping at the end of the sources. Plain files are watched for
+new lines, and the source globs are checked every couple of seconds for new files, which are read from the start.
+Events are still merged by time, so an event is held back for up to half a second while any file is quiet, in case
+that file writes an earlier one. A file that is truncated, or rotated away and replaced by a new one of the same name,
+is read again from the start. Gzipped files are read to the end but not followed, and the output is flushed after
+every event. Quote globs so saw sees them rather than the shell:
+  saw 'services/*/app.log' --follow
+
+--poll-interval DURATION, like 500ms or 2s, sets how long --follow waits before looking again once every file has been
+read to its end, 200ms by default. saw looks for new lines by reading again rather than asking the system to tell it
+about changes, so following works the same on network filesystems like NFS or SMB, where change notifications from
+other machines never arrive. Each look is a request to the server there, so a longer interval keeps the load down at
+the cost of lines showing up a little later:
+  saw '/mnt/nfs/services/*/app.log' --follow --poll-interval 2s
+
+--reload FILE changes what a --follow run keeps and how it prints it without starting over. FILE is JSON and each key
+it has replaces what the command line gave: "filter" a list of filters, "translate" a list of [TARGET_FIELD, PATTERN]
+pairs and "pretty" a pattern, which only applies if the output is pretty already. FILE is checked for changes at most
+once a second as events come in, and files are followed on from where they were. A FILE with a mistake is reported
+and the settings from before are kept:
+  echo '{"filter": ["%level=ERROR"], "pretty": "[%time] %message"}' > live.json
+  saw 'services/*/app.log' --follow --reload live.json
+
+--drop-older-than DURATION, like 10m, 2h or 1d, leaves out events more than DURATION older than the newest event read
+so far while following, so a backlog that's replayed, or a new file read from the start, doesn't flood alerts meant for
+what's happening now. Events are compared to each other rather than to the clock, so a source that's behind by a
+steady amount isn't dropped unless another source is ahead of it. Events without a time are always kept, and how many
+were dropped is printed once done:
+  saw 'services/*/app.log' 'backfill/*.log' --follow --drop-older-than 10m -f %level=ERROR
+
+--tail N writes only the last N events that would have been written, in time order, and --reverse writes every event
+newest first. Both work with filters and everything else, counting only the events that make it through. Plain files
+are read from the end a block at a time, so the tail of a huge file takes no longer than reading N events. When any
+source is gzipped or a pipe, --tail reads everything but only keeps the last N events, and --reverse on its own
+reads those sources whole before starting:
+  saw app.log --tail 1000 -f %level=ERROR
+  saw app.log --reverse --tail 20
+
+--sort-by FIELDS merges sources by the comma separated FIELDS instead of 'time', for producers that only promise
+their events are in order by a sequence number. Each field is compared in turn, so "time,seq" breaks ties between
+events with the same time. Numbers, including numbers written as strings, are compared as numbers, and 'time' as a
+time. Events missing a field sort before those that have it. Unless 'time' is one of the FIELDS, events without a
+valid time are kept, but are never in --range. Each source must already be in order by the FIELDS:
+  saw 'partition-*.log' --sort-by seq
+
+--external-sort DIR[,run=N] is for sources with no time order at all, like events shuffled by a queue, of any size.
+Events are read N at a time, 200000 unless it says otherwise, sorted by time and written to DIR as sorted runs, then
+every run is merged into one stream, so only N events are ever held in memory. Events with the same time stay in the
+order they were read, and those without one come first. Nothing is written until every event has been sorted. DIR
+keeps a manifest of the runs written so far, so a sort that's stopped partway through starts again from its last run
+when it's run again with the same sources and DIR, and everything in it is removed once the merge is done. DIR needs
+about as much free space as the sources uncompressed:
+  saw 'shuffled/*.log.gz' --external-sort /scratch/saw-sort,run=500000 -o sorted.log.gz
+
+--async-sources is for thousands of small files, like one per pod per hour. Files are listed without being opened,
+and once any with an index are skipped, the rest are all read at once on a handful of threads, holding only a few
+open at a time. Each is kept in memory whole, compressed if it was, so this is only for files that are small
+together. It needs saw built with the 'async' feature:
+  cargo install saw --features async
+  saw 'pods/*/2024-06-01T*.log.gz' --async-sources -f %level=ERROR
+
+--max-open-files N is for globs matching more files than can be open at once, which would otherwise stop saw when it
+runs out of file handles. Files are listed without being opened and at most N are open at a time, the rest opened as
+others finish, in the order they seem to start without reading them: by the time in their index if they have one,
+else a date in their path, else when they were last changed, which puts rotated files like app.log.2 before
+app.log.1. Files whose times overlap more than N deep can't all be merged in order, and a file opened too late to be
+is named on stderr. Sources that aren't files, like stdin, are always open and count toward N:
+  saw 'archive/**/*.log.gz' --max-open-files 256 -f %level=ERROR
+
+Files from different days are read on one thread per core with --daily. Inside a container with a CPU quota, like
+0.5 CPU in CI, that's one thread for each core the quota adds up to rather than each core of the machine, so the
+threads aren't left waiting on each other. --max-cpu N uses at most N, for a host shared with other work. With a
+memory limit, --async-sources only keeps as many files in memory as fit in half of it, and reads the rest as they're
+merged, so a big batch doesn't get saw stopped by the OOM killer. --dry-run shows the limits that were found:
+  saw 'archive/*.log.gz' --range 2024-06-01 2024-06-08 --daily --max-cpu 2
+
+--autotune SIZE reads the first SIZE of the local files, like 8mb, before the full run and measures what each event
+costs to read and parse, and how many events each filter keeps and how long it takes. Filters are then checked in the
+order that rejects the most for the least time, the read buffer is sized to the events, and with --daily it picks how
+many threads to read on, up to --max-cpu, and how far each can get ahead, going by how long the whole input should
+take. What it measured and picked is printed on stderr. The sample is read on its own, so nothing is taken from the run:
+  saw 'archive/*.log.gz' --range 2024-06-01T00:00:00 2024-06-08T00:00:00 --daily --autotune 8mb -f %level=ERROR
+
+--ignore-older-than DURATION leaves out every source that was last changed longer ago than DURATION, like 12h or 30d,
+without opening it, so a glob over a deep archive only reads what's recent. A source whose path has a date in it,
+like app-2024-06-01.log or 2024/06/01/app.log, is also left out once that whole day is older, even if the file
+itself was changed since. How many were left out is printed on stderr, and --dry-run lists them:
+  saw 'archive/**/*.log.gz' --ignore-older-than 7d -f %level=ERROR
+
+Local files are read from start to end, and the kernel is told so it can read further ahead. --page-cache says what
+happens to what's been read: with auto, the default, files of 1GB or more are dropped from the page cache as they're
+read, so a nightly run over hundreds of GB doesn't push everything else on the host out of memory. keep leaves every
+file cached, for files about to be read again, and drop drops every file. It's only advice, and only taken on Linux
+and FreeBSD:
+  saw 'archive/**/*.log.gz' --page-cache drop -o nightly.log.gz
+
+A read from a local file that fails the way network filesystems do for a moment, with EIO, ESTALE, ETIMEDOUT,
+ECONNRESET or EAGAIN, is tried again rather than ending the run. The file is opened again and read from the end of
+the last good read. --read-retries RETRIES[,backoff=DURATION] says how many times, 3 by default, and how long to wait
+before the first retry, 200ms by default, which doubles with each one after. --read-retries 0 never retries:
+  saw '/mnt/nfs/logs/*.log.gz' --read-retries 8,backoff=1s -o merged.log.gz
+
+--shard K/N[,key=FIELD] splits the sources between N saw processes, possibly on different machines, without them
+talking to each other. This one is number K, counted from 1. Each source is read by one shard picked by a hash of its
+name, so every process must be given the same list of sources by the same names. With key=FIELD every process reads
+every source instead and keeps the events whose FIELD hashes to it, which spreads the work more evenly when there are
+few large sources. Events without the FIELD are all kept by shard 1. Give each shard its own --output:
+  saw 'archive/*.log.gz' --shard 2/8 -o out/shard-2 -c 100mb --skip-unchanged
+
+--tenant NAME[,field=FIELD] makes an extract for one customer. Only events whose FIELD, 'tenant' unless given, is NAME
+are kept, and every other tenant is redacted from them as [REDACTED:tenant], both in a FIELD at any depth, like a
+nested 'target.tenant', and where one is named as a whole word in a string. Other tenants are learned from the FIELD
+of every event read, including those left out, so one first seen after events naming it were written can't be taken
+out of them. Those are listed on stderr once done, along with how many events were kept, left out and redacted:
+  saw 'archive/*.log.gz' --tenant acme,field=org_id -o extracts/acme.log.gz
+
+--partition-hash FIELD%N numbers every event from 0 to N-1 by the same hash of FIELD as --shard key=FIELD uses, and
+sets it as a '_partition' field, so events can be split evenly by a field with far too many values to list, like a
+user id. It's set before --filter, so it can be used there, in --pretty, --pretty-per, --group-by and --stats like
+any other field. Events without the FIELD get no '_partition':
+  saw app.log --partition-hash user_id%16 -f "%_partition>=8" --stats count-by _partition
+
+--sessionize by FIELD gap GAP numbers the sessions of each value of FIELD in a '_session' field. Events with the same
+FIELD are one session until one comes GAP or more after the one before it, like 90s, 30m, 2h or 1d, which starts the
+next. Sessions are numbered from 1 in the order they start, so no two keys share a number. It runs on the merged
+events in time order, after --filter and everything that changes events, so it can't be used with --reverse and
+sessions are made of the events that were kept. Events without the FIELD or a time get no '_session':
+  saw app.log --sessionize by user_id gap 30m --stats count-by _session
+
+--flag-anomalies rate[,by=FIELDS][,window=DURATION][,sensitivity=N] marks events that come while their group is far
+busier than usual, as a first place to look in a huge merge. Events are grouped by FIELDS joined with +, level+service
+unless given, and counted in windows of DURATION, 1m unless given. Each group learns its usual rate as it goes, and
+once a window has more than N deviations over it, 3 unless given, the events of that window from then on get an
+'_anomaly' field with the group, the count so far and the count expected. A group is only judged after five windows,
+and a lower N flags more. It runs right after --sessionize, so it sees the events that were kept:
+  saw 'incident/*.log.gz' --flag-anomalies rate,by=level+host,window=30s -o flagged.log.gz
+
+--correlate LEFT=RIGHT[,window=DURATION] pairs events that name the same thing by different fields, like a gateway's
+'id' and the 'request_id' a backend logs for it. LEFT and RIGHT are dotted paths like in --filter, and either side
+may come first. One event is written for each pair, at the time of the second, holding both as 'left' and 'right'
+and the milliseconds between them as '_gap_ms'. Events wait up to the window, 5m unless given, for their partner,
+and those that find none or have neither field are left out and counted on stderr once done. It runs after
+--sessionize, so everything else sees the events before they're paired:
+  saw gateway.log backend.log --correlate id=request_id,window=30s --output-format indented
+
+--link-by FIELD[,window=N] numbers every event written in a '_seq' field, counting from 0, and links each event with
+the FIELD to the events before and after it with the same value, in '_prev_seq' and '_next_seq', so a viewer can walk
+a request's chain without searching the whole output. Each event is held back until N more have been read, 10000
+unless given, to find the one after it, and one that comes later than that is only linked back. It runs last, after
+everything that leaves events out, so the numbers are those of the events as they're written:
+  saw 'services/*.log' --link-by request_id,window=50000 -o linked.log.gz
+
+--replay-speed SPEED writes events with the same gaps between them as their times, so old logs can be fed to
+something that expects live traffic. SPEED is a multiple like 10x or 0.5x, or 'realtime' for 1x. The first event is
+written straight away, and the output is flushed after every event. Events without a time are never held back:
+  saw incident.log.gz --replay-speed 10x --json true --zip false | staging-ingest
+
+--source-stats prints a table of every source once done: its size on disk and after decompressing, lines read,
+lines dropped as invalid, and the first and last event time. 'skew' is how far a source's last event is from the
+median of all sources, shown when it's over a minute, which usually points at a host with the wrong clock.
+'backwards' counts events earlier than the one before them in the same source, which come out of order when merged.
+
+--expect-count COUNT checks how many events were written once saw is done, and exits with code 65 if it's wrong, so
+scripts can tell an empty result from a successful one. COUNT is a bound like >0, >=10, <100, =5, or a range like
+10..100 where the end is left out, the same as --lines.
+
+Files that are searched again and again can be indexed ahead of time with "saw index", after which saw skips any
+file that can't have events in --range, or can't match a plain --filter or --filter-file on an indexed field,
+without reading it. Run "saw index --help" for more.
+
+Chunked archives written with --skip-unchanged can be cataloged with "saw merge-manifests", which merges the manifests
+of many runs into one catalog with the times of every chunk. --catalog CATALOG then reads only the chunks that can
+have events in --range, and the catalog lists chunks that overlap or are small enough to compact. Run
+"saw merge-manifests --help" for more.
+
+Directories of many small chunks, like the hourly files of frequent runs, can be rewritten into a few chunks per day
+with "saw compact DIR", which only deletes the old chunks once the new ones are checked to hold every event. Run
+"saw compact --help" for more.
+
+"saw man" prints this help as a man page, with every topic and subcommand, to be installed with the package:
+  saw man > /usr/local/share/man/man1/saw.1
+
+Jobs with many stages can be written down in a YAML file and run with "saw run PIPELINE", with any option given after
+it taking the place of the file's. Run "saw run --help" for more.
+
+Pretty patterns can be tried out with "saw render PATTERN", which renders them against a sample file or a few built
+in events, and can check the result against a snapshot in CI. Run "saw render --help" for more.
+
+Archives can be checked for corruption with "saw verify FILES", which reads each one through, checks that it isn't
+cut off and that its times are in order, and checks any checksum or manifest it was written with. It exits with code 1
+if anything is wrong, for cron. Run "saw verify --help" for more.
+
+--trace-ids looks for trace and span ids in a W3C 'traceparent' field or under common names like traceId, spanId,
+dd.trace_id and X-B3-TraceId, and copies them into 'trace_id' and 'span_id' as lower case hex padded to 32 and 16
+digits. --trace TRACE_ID does the same and only keeps events from that trace. Both run before --filter, so
+"%trace_id=" can be used to keep every event that has a trace.
+
+--decrypt-field FIELD decrypts a field that was encrypted with AES-GCM, and --encrypt-field FIELD encrypts one.
+Both can be passed more than once, and fields are decrypted before any are encrypted. Encrypted values are base64 of
+the 12 byte nonce followed by the ciphertext and tag. The 16 or 32 byte key is read from --field-key, which is
+env:NAME or file:PATH, or from the SAW_FIELD_KEY environment variable, as base64 or hex. Values that can't be
+decrypted are left alone and counted on stderr. This runs after --translate and before --fingerprint.
+
+Decrypted fields are meant to be read, so --decrypt-field only works with pretty or table output to the terminal,
+unless each field is encrypted again with --encrypt-field. --write-decrypted lets the plaintext go anywhere else too,
+like JSON, --output, --chunked, --split-by-time and --route files. Be careful with it: anything that can read those
+files can then read what the producer encrypted.
+
+--scan-secrets ACTION looks through every string of every event about to be written, nested ones too, for AWS
+access and secret keys, JWTs, bearer tokens, private keys, GitHub tokens and card numbers that pass the Luhn check.
+It runs after --decrypt-field and --encrypt-field, so it sees what will be written. ACTION is what to do with them:
+  report  Write events as they are, and list how many of each kind were found and where the first was on stderr
+  redact  Replace each one with [REDACTED:KIND], like [REDACTED:jwt], and list them the same way
+  fail    Leave out every event holding one, list them, and exit with code 65 once done
+  saw 'archive/*.log.gz' --scan-secrets redact -o clean.log.gz
+
+--truncate-field FIELD=SIZE cuts a field down to SIZE bytes, like 4kb or 512b, so one huge stack trace can't get an
+event rejected by a store that limits the size of a document. Strings are cut at the last whole character that fits,
+and objects and arrays over the limit are written as JSON and cut the same way. Each field cut short is listed in
+'_truncated' with the size it had, like {"_truncated":{"stack":18230}}. It can be passed more than once, and runs
+after --compact, so the limits apply to what will be written:
+  saw app.log --truncate-field stack=4kb --truncate-field request.body=1kb
+
+--normalize-time FORMAT rewrites 'time' in every event written into one FORMAT, whatever way each source wrote it,
+for loaders that only take one. FORMAT is epoch-millis or epoch for a number of milliseconds or seconds since the
+epoch, or rfc3339 for UTC like 2024-06-01T10:00:00.000Z. It's the time events were merged by, so it's read with
+--time-field and --time-format like always, and events without one are left as they are:
+  saw 'services/*.log' --time-format auto --normalize-time epoch-millis -o merged.log.gz
+
+--group-by FIELD gathers consecutive events with the same FIELD, like a request id, under a header with how many
+there were, how long they took from first to last and the worst level among them. Each event is pretty printed on
+a branch below it, and events without the FIELD are printed as usual. A block is held in memory until an event with
+a different FIELD comes along, so it works best with --keep-order-of-input or a --filter on a single request:
+  saw app.log --group-by request_id
+
+--collapse-repeats prints a pretty printed line that is exactly the same as the one before it only once, followed by
+"last message repeated N times" once a different line comes along, like syslog does. It only changes what's printed,
+so it's handy for scanning a noisy log on the terminal, and a --output file written without it keeps every event:
+  saw app.log --pretty '%level %message' --collapse-repeats
+
+--output-format html writes a single HTML file that can be opened in any browser and shared with people who don't
+use saw. It has a table of every event that can be searched and filtered by level, stacks that fold open, and the
+number of events per level with a histogram of events over time at the top:
+  saw app.log --range 2022-03-13T10:00 2022-03-13T11:00 --output-format html -o incident.html
+
+--output-format indented writes each event as JSON indented over as many lines as it needs, with a line of --- after
+each one, which is easier to read than one long line for events with big nested payloads:
+  saw app.log -f %level=ERROR --output-format indented
+
+--output-format frames writes each event as a 4 byte big endian length followed by that much JSON, with nothing
+between events, for spools and queues that are read by frame rather than by line. frames:le writes the length little
+endian and frames:msgpack writes MessagePack instead of JSON, the same way --input frames reads them back:
+  saw 'spool/*.bin' --input frames:msgpack -f %level=ERROR --output-format frames:msgpack -o errors.bin
+
+--escape-json-strings never|minimal|ascii decides which characters in JSON strings are written as \uXXXX. never,
+the default, only escapes what JSON needs and writes everything else as UTF-8. minimal also escapes DEL, the C1
+control characters and U+2028 and U+2029, which some readers take as the end of a line. ascii escapes everything that
+isn't ASCII, for consumers that can't read UTF-8 at all:
+  saw app.log --escape-json-strings ascii -o feed.log
+
+--table FIELDS[,every=N] prints the comma separated FIELDS as aligned columns under a header, which is easier to
+scan than a pattern for streams of similar events like access logs. Rows are written in batches of N (100 by default)
+so each batch can be sized to fit, and the header is written again whenever the column widths change:
+  saw access.log --table time,status,method,path,every=500
+
+Records written by --provenance are recognized by their '_saw' key and skipped when read back in.
+
+Lines kept by --passthrough-invalid are given the time of the line before them in the same file,
+or the file's modified time if they come first, so they stay next to the events they belong with.
+
+Warnings about invalid lines give both the line number and byte offset, which can be passed straight to
+--lines or --bytes to look at that part of the file again. Either end of the range can be left off, like "1000..".
+
 "#;
 
 const PRETTY_TOPIC: &str = r#"
@@ -47,12 +505,20 @@ Pretty patterns are simply % prefixed JSON keys, constants and functions.
 
 For example, the default pattern is "[%time] %message %prefix/\n/%stack\v/".
 
+That is the pattern saw starts from when none is given, by --pretty, --preset or SAW_PATTERN. It looks at the first
+50 events and adds what they have: the level, from 'level', 'severity' or 'lvl', and the logger, from 'logger',
+'logger_name' or 'service', after the time, each only printed for events that have it. 'msg' is printed in place of
+'message' and 'stack_trace' in place of 'stack' when the events only have those. With --follow or --replay nothing
+is held back to look at, so the pattern is used as it is.
+
 This prints the 'time' key surounded by square brackets, a space,
 then the value of the message key, then the value of the stack key prefixed with a newline.
 The \v seperates the %stack variable from the / because that's the syntax for a function.
 
 If a field is missing, like stack, then an empty string will be used instead.
 
+Keys can contain letters, numbers and underscores, so '_fingerprint' can be printed with "%_fingerprint".
+
 Excape characters are done with \.
 Escapable charcters are:
 t => tab
@@ -73,6 +539,42 @@ Existing functions so far are:
 %prefix/pattern to use as prefix/content pattern/
 %replace/base pattern/regex/regex replacement/
 %replaceAll/base pattern/regex/regex replacement/
+%except/comma separated keys/
+%env/NAME/
+%now/FORMAT/
+%pad/pattern/WIDTH/
+%padLeft/pattern/WIDTH/
+%truncate/pattern/WIDTH/
+%wrap/pattern/WIDTH/
+
+The special variable %rest prints every field that is not used anywhere else in the pattern
+as space separated key=value pairs. Values containing spaces, quotes or '=' are quoted.
+For example: "[%time] %message %rest" prints everything, with time and message up front.
+
+%except is like %rest except it prints every field but the ones listed, regardless of the rest of the pattern.
+For example: "%message %except/time,message,stack/"
+
+%pad, %padLeft, %truncate and %wrap fit what the pattern prints to WIDTH columns. %pad adds spaces after it and
+%padLeft before it, so columns line up, %truncate cuts it short with a … when it's wider, and %wrap breaks it into
+lines between words. Widths are counted the way a terminal shows the text, so Chinese, Japanese and Korean characters
+and most emoji count as two columns and combining accents as none, and nothing is ever cut partway through a
+character. The same counting lines up the columns of --table and --stats:
+  saw app.log -p "%pad/%level\v/5/ %padLeft/%user\v/12/ %truncate/%message\v/60/"
+
+%env prints the value of an environment variable, or nothing if it isn't set.
+%now prints the UTC time saw was started at, which is the same for every event. FORMAT is one of
+iso, date, time, epoch or epochMillis.
+For example: `saw -t processed_by "%env/HOSTNAME/ at %now/iso/"`
+
+Functions of your own are made of other patterns, and read from the JSON FILE given to --pattern-functions, or from
+the file SAW_PATTERN_FUNCTIONS names. Each key names a function, with either its pattern or its params and pattern:
+  {"stamp": "[%time] %level", "tagged": {"params": ["tag", "body"], "pattern": "<%tag> %body"}}
+
+They're called like any other function, in --pretty, --translate and everywhere else a pattern goes. A function
+without params is just %name/ and each param takes a pattern, so a variable that ends one needs \v before the /.
+Inside a function a param is whatever was passed for it, and any other variable is a field as usual.
+--pattern-functions can be passed more than once.
+For example: `saw app.log --pattern-functions team.json --pretty "%stamp/ %tagged/%service\v/%message\v/"`
 "#;
 
 const FILTER_TOPIC: &str = r#"
@@ -95,20 +597,85 @@ To test a field other than 'message', simply append a '%' prefixed key and an '=
 
 For example: "%stack=NullPointer" will match any stack field that contains the word "NullPointer"
 
-If a log does not contain a 'stack' field, it is automatically excluded. There is no way to
-apply a filter conditionally.
+If a log does not contain a 'stack' field, it is automatically excluded.
+
+Fields inside objects are reached with a dotted path, like "%context.userId=1234", and a number in the path picks
+from an array, like "%tags.0=beta". A field whose name really has a dot in it, like "dd.trace_id", is found first.
+
+Fields can be compared as numbers or times with >, >=, < and <=, like "%durationMs>500" or
+"%time>=2022-03-13T10:00:00". A number in a string compares as a number, a date alone means its midnight, and an
+event whose field isn't a number, or a time, is left out.
 
 Applying an empty filter works to confirm the field exists. For example: "%stack=" will print
-all events that have a stack, regardless of what they contain.
+all events that have a stack, regardless of what they contain. That only finds text, so "%stack?" finds events where
+the field holds anything but null, like an object or a number, and "!%stack?" finds the events without one.
 
 A filter can be negated like this: "%message!=something". This will return all events where the message
 does NOT contain the word "something".
 
 To apply multiple filters, simply pass --filter more than once. These filters are always ANDed together.
-There is currently no way to OR two filters. Multiple filters can touch the same or different fields.
+Multiple filters can touch the same or different fields.
 
 For example: `saw -f Controller -f %stack=NullPointer` -f %level!=DEBUG will find all messages that contain the word
 "Controller" and also have a stacktrace that contains the word "NullPointer" but who's level is NOT "DEBUG".
+
+A single --filter can also join filters with && and ||, negate them with ! and group them with parentheses:
+  saw app.log -f "(%level=ERROR || %level=WARN) && %context.userId=1234 && %durationMs>500"
+
+|| binds looser than &&, and ! binds tightest. Each filter in an expression needs its %FIELD, and its pattern runs
+until the next &&, || or unmatched ')', so patterns can still hold groups like "%message=(timeout|refused)". Unlike
+"%level!=DEBUG", which leaves out events without a level, "!%level=DEBUG" keeps them. A --filter is only read as an
+expression when it starts with ( or ! before a %FIELD, or holds && or ||, so a plain pattern like "(ERROR|WARN)" is
+still a regex on the message.
+
+When a field must be one of a long list of values, like thousands of user ids, put them in a file with one value per
+line and pass it with --filter-file, which takes the same %FIELD= or %FIELD!= before the path as --filter does:
+  saw app.log --filter-file %user_id=users.txt --filter-file %ip!=blocked.txt
+
+The field must equal one of the values exactly, or with != none of them, and events without the field are left out
+just like with --filter. Values are trimmed, and blank lines and lines starting with # are skipped. The values are
+kept in a hash set, so this stays fast no matter how many there are. Indexed files are skipped when their index
+shows they hold none of the values.
+
+--ignore-pack NAMES leaves out events that are known noise, from the comma separated packs NAMES, so the same health
+check filters don't have to be written again for every run. An event any rule of a pack matches is left out,
+whatever else it matches. The rules on each field are all checked in one pass, so a long pack costs little more than
+a single filter. The built in packs are:
+  kubernetes-probes     requests from kube-probe, by user_agent, userAgent, http.user_agent or in the message
+  aws-elb-healthchecks  requests from ELB-HealthChecker, the same way
+  grpc-health-checks    calls to grpc.health.v1.Health, by grpc.method, method or in the message
+  health-endpoints      requests for /healthz, /readyz, /livez, /health, /ready or /live, by path, url or http.path
+
+A team's own packs go in a JSON file named by SAW_IGNORE_PACKS, of each pack's name to its %FIELD=PATTERN rules,
+where PATTERN is a regex like for --filter. A pack with the name of a built in one replaces it:
+  {"our-noise": ["%logger=^com\\.acme\\.Heartbeat$", "%message=^cache refreshed"]}
+  saw app.log --ignore-pack kubernetes-probes,aws-elb-healthchecks,our-noise
+
+To see why a surprising event got through, pass --why. Each event written gets a '_matched_by' field listing every
+filter that accepted it, including each branch of an || that matched, so add %_matched_by to --pretty or use
+--json true to see it. --why drops also writes every event left out by --range, --trace, --shard, --tenant, --quota,
+--drop-older-than or --filter to stderr, with the stage and the filter that left it out:
+  saw app.log -f "%level=ERROR || %durationMs>500" --why drops -p "[%time] %message %_matched_by"
+
+With --why drops, indexes aren't used to skip whole files, so every dropped event is reported.
+
+Patterns can turn on regex flags for themselves, like "(?i)error" to ignore case, "(?m)^at " so ^ and $ match on
+each line, or "(?-u)\w+" to match only ASCII. Lookarounds like (?!...) aren't supported, use a negated filter instead.
+
+--regex-options KEY=VALUE,... sets these for every regex saw compiles, in filters, %replace and everywhere else:
+  size=SIZE            largest a compiled regex may be, like 50mb. Raise it when a large pattern is rejected
+  dfa=SIZE             most memory each regex may use for its cache while matching
+  unicode=true|false   false makes \w, \d and friends ASCII only, which is smaller and faster
+  multi-line=true|false
+  ignore-case=true|false
+For example: `saw -f '%path=^/api/(users|orders)/' --regex-options size=100mb,unicode=false`
+
+To check whether saw would keep one event without making a sample file, pass it to --explain-filter, or - to read it
+from stdin. Nothing is read from the sources. Instead the event is run through --range, --trace, --shard, --filter and
+everything else that can leave it out or change it, and each step is printed with what it did. saw exits with 0 if the
+event would be written and 1 if not:
+  saw -f "%level=ERROR" --range 2024-06-01T00:00:00 '*' --explain-filter '{"time":"2024-06-01T10:00:00","level":"WARN"}'
+  pbpaste | saw --preset logback -f %user=E455705 --explain-filter -
 "#;
 
 const RANGE_TOPIC: &str = r#"
@@ -130,6 +697,8 @@ You can however supply "*" as either the MIN or MAX to provide an open-ended tim
 Strictly speaking you can supply * for both MIN and MAX and this is equivalent to not providing a range at all.
 
 MIN is inclusive, MAX is exclusive.
+
+When used with --keep-order-of-input, events without a valid time are never in range.
 "#;
 
 const TRANSLATE_TOPIC: &str = r#"
@@ -146,6 +715,12 @@ PATTERN is a pattern in exactly the same form as used in --pretty
 
 Multiple translations can be applied by passing the argument more than once, and they will
 be applied in order.
+
+To only translate some events, use --translate-if with a FILTER in the same form as --filter:
+
+  saw --translate-if %service=billing card %replace/%card\v/.*/REDACTED/
+
+Events that don't match the FILTER are left untouched by that translation.
 "#;
 
 const CHUNKED_TOPIC: &str = r#"
@@ -170,293 +745,2182 @@ Options for this code are
   line based one will once it has proccessed that many lines. Note that "lines" means lines of INPUT,
   or in other words JSON objects, not lines of OUTPUT in the case of using the pretty printer.
 
+  A line limit and a byte limit can be combined by separating them with a comma. A new file is
+  created as soon as either limit is reached.
+
+  Byte sizes can have a fraction, like 1.5gb, and the code can be in any case and separated from
+  the number by spaces, like "10 MB". Limits must be greater than 0.
+
 Examples:
   Rollover every 20 kilobytes: `saw --output ex --chunked 20kb`
   Rollover every 1000 lines: `saw --output ex --chunked 1000ln`
+  Rollover every 1000 lines or 20 kilobytes, whichever comes first: `saw --output ex --chunked 1000ln,20kb`
+  Rollover every one and a half gigabytes: `saw --output ex --chunked "1.5 GB"`
+
+With --skip-unchanged, saw keeps a manifest of what went into each chunk in OUTPUT.manifest. When run again, a chunk
+that holds exactly the same lines as the one already there is thrown away and the old file is left untouched, so a
+daily job that re-runs over the same range only changes, and only has to upload, the chunks that are different.
+Each chunk is written to CHUNK.partial first and only moved into place if it changed.
+
+  Re-run a daily archive without touching unchanged chunks: `saw -o archive/app -c 100mb --skip-unchanged --range 2022-03-13 2022-03-14`
+
+--chunk-workers N[,retries=R] compresses each chunk on one of N other threads once it's written, while the next one
+is written, instead of compressing as it goes. Each chunk is written plain to CHUNK.partial and replaced by the
+compressed file once a worker is done with it. At most N chunks wait for a worker, after which writing waits too.
+
+--upload-chunk CMD runs CMD with sh for each chunk once it's compressed, with the chunk's path in SAW_CHUNK, so a slow
+upload holds up neither writing nor the other chunks. A command that fails is retried R times, 3 by default, waiting
+half a second and then twice as long each time. How many chunks were uploaded and failed is printed once done, and
+saw exits with 74 if any failed:
+  Upload each chunk with 4 at a time: `saw -o out/app -c 100mb --chunk-workers 4 --upload-chunk 'aws s3 cp "$SAW_CHUNK" s3://logs/'`
+
+--split-by-time INTERVAL splits by each event's own time instead, into a directory per day under --output. INTERVAL is
+a whole number of minutes, hours or days, like 15m, 1h or 1d, and anything under a day must divide a day evenly.
+Each bucket is named for its start, going as deep as the interval needs:
+  1d   out/2024/06/01.log.gz
+  1h   out/2024/06/01/13.log.gz
+  15m  out/2024/06/01/13/45.log.gz
+
+Events without a time go to out/untimed.log.gz. A file is kept open for each bucket being written to, up to 16, and
+the one used longest ago is closed when another is needed. A bucket that gets more events after being closed is
+appended to, which compressed files allow. Files from an earlier run are replaced the first time they're written.
+
+  Hourly files for a batch job: `saw app.log -o out --split-by-time 1h`
+
+Days and hours start in UTC, so a team in another zone gets days split at the wrong midnight. --timezone ZONE starts
+them on the wall clock of ZONE instead, for --split-by-time, --daily and --summary-by. ZONE is a name from the
+system's zoneinfo like Europe/Berlin, local for this machine's zone, an offset like +05:30, or UTC. Named zones follow
+daylight saving, so a bucket for the day the clocks change holds 23 or 25 hours of events, and an hour that happens
+twice goes to the same file. Event times are still written as they were read.
+
+  Daily files for a team in New York: `saw app.log -o out --split-by-time 1d --timezone America/New_York`
+"#;
+
+const PRESET_TOPIC: &str = r#"
+Usage:
+  saw --preset NAME
+
+Different logging libraries write JSON with different field names. A preset tells saw which
+library wrote the logs so it can rename fields to the ones saw expects ('time', 'message', 'level' and 'stack')
+as each event is read. Filters, translations and patterns all see the renamed fields.
+
+When a preset is given and --pretty is on without a pattern, the preset's own pattern is used instead of the default.
+
+Available presets:
+  pino      'msg' and 'err.stack' renamed, 'time' converted from epoch milliseconds
+  bunyan    'msg' and 'err.stack' renamed
+  logrus    'msg' renamed, 'error' renamed to 'stack'
+  logback   Logstash encoder layout, '@timestamp', 'stack_trace', 'thread_name' and 'logger_name' renamed
+
+Numeric levels are named as 10 => TRACE, 20 => DEBUG, 30 => INFO, 40 => WARN, 50 => ERROR, 60 => FATAL with or
+without a preset, unless --level-map says otherwise, for filters and pretty output only. Run "saw --help input" for
+more.
+"#;
+
+const COMPRESS_TOPIC: &str = r#"
+Usage:
+  saw --output PATH --compress DEST=CODEC
+
+By default all output is either gzipped or not based on --zip. --compress builds a small routing
+table instead, so that different destinations can use different compression.
+
+DEST is a glob matched against where the output is going:
+  "-" is stdout
+  the --output path when writing a single file
+  each chunk's path without its extension, like "out.3", when using --chunked
+  each bucket's path without its extension, like "out/2024/06/01/13", when using --split-by-time
+
+CODEC is one of
+  none: Plain text
+  gzip: Gzip, same as --zip true
+  zstd: Zstandard
+
+--compress can be passed more than once and the first route that matches wins. Any destination
+that doesn't match a route falls back to --zip.
+
+Chunks are given an extension to match their compression, ".log", ".log.gz" or ".log.zst".
+
+Examples:
+  Zstd compress chunks but leave the first one readable: `saw -o out -c 10mb --compress "out.0=none" --compress "out.*=zstd"`
 "#;
 
+const INPUT_TOPIC: &str = r#"
+Usage:
+  saw --input FORMAT [--time-field FIELD] [--time-format FORMAT]... [--time-locale LOCALE]
+
+Every line is read as a JSON object by default. --input reads other formats into the same fields, so filters,
+translations and patterns all work on them just the same:
+  json           One JSON object per line, the default
+  logfmt         key=value pairs separated by spaces, like `time=2024-06-01T13:00:00Z level=info msg="logged in"`.
+                 Values can be in double quotes with " and \ escapes, and a key without a value is true
+  regex:PATTERN  Each named group of PATTERN becomes a field, and lines it doesn't match are invalid
+  loki           A Grafana Loki query export, the JSON the query_range API returns, or a push payload with
+                 'streams'. Every entry becomes an event with its stream's labels and structured metadata as
+                 fields, and its line's own fields when the line is JSON, or else the line as 'message'. 'time' is
+                 Loki's timestamp. The whole export is read before any of it is written, so it can be put in order.
+                 Sources that aren't Loki exports are read as JSON lines, so exports merge with your own logs
+  frames         Each event is a 4 byte length followed by that many bytes of JSON, as spool files often hold them,
+                 so an event with newlines in it is still one event. frames:le reads the length little endian
+                 instead of big endian, and frames:msgpack reads MessagePack instead of JSON, as in
+                 frames:le:msgpack. A source cut off partway through a frame keeps every frame before it
+
+Lines that can't be read are reported and dropped, or kept with --passthrough-invalid, just like invalid JSON.
+
+--lenient-json reads lines from producers that write almost JSON, instead of dropping every one of them for a single
+odd field. NaN, Infinity and -Infinity are read as strings, integers too big for 64 bits are kept as strings with
+every digit rather than rounded to a float, and numbers with leading zeros or a + are read as the number they are,
+so 007 is 7. Anything inside a string is left alone. It applies to JSON and Loki sources, not logfmt or regex.
+
+--resync reads on past corrupt data in JSON lines, like a block of binary garbage or a write torn off partway
+through, instead of losing the record it's joined to. When a line can't be read, the first place a record starting
+with {" takes up the rest of it is where reading starts again, and every line before that is skipped. Each region
+that's skipped is reported once with the bytes it spans, rather than as every line in it:
+  saw crashed-node.log --resync
+
+Lines that aren't valid UTF-8 are reported and dropped like any other line that can't be read, and the rest of the
+source is still read. --debug-raw prints the bytes of every line that can't be read under its warning, like
+hexdump -C, to show what the parser actually saw, such as a byte order mark or a Latin-1 é:
+  00000000  ef bb bf 7b 22 74 69 6d  65 22 3a 22 32 30 32 34  |...{"time":"2024|
+
+--copy-raw SOURCE=PATH copies every line read from the sources whose path or file name matches the glob SOURCE to
+PATH, byte for byte after decompressing, including the ones that can't be read. It can be passed more than once,
+and the first SOURCE that matches wins, so a problem can be shared without the rest of the logs:
+  saw 'gateway/*.log.gz' --copy-raw 'gateway-3*=suspect.log' --debug-raw
+
+--parse-only FIELDS reads just the comma separated top level FIELDS from each JSON line, and skips over the rest
+without building them, which is most of the work of reading events with hundreds of fields only to throw most of
+them away. Fields that aren't read aren't written either, so list every one the output needs. 'time', --time-field,
+the fields of --sort-by and every field --filter, --filter-file, --ignore-pack, --translate, --pretty and --pretty-per
+look at are always read. Lines are still checked whole, so a line broken in a field that's skipped is invalid just the
+same. Other inputs are read whole and then cut down the same way:
+  saw 'wide/*.log.gz' --parse-only time,message,request_id -f %level=ERROR
+
+Sources that start like gzip are read as gzip and everything else as plain text, whatever their name. --codec
+SOURCE=CODEC says how to read the sources whose path or file name matches the glob SOURCE instead, for files that
+are misnamed or compressed some other way. CODEC is plain, gzip, zstd, zlib or deflate, where deflate has no header
+at all. It can be passed more than once, and the first SOURCE that matches wins:
+  saw 'exports/*.gz' --codec 'broken-*.gz=plain' --codec '*.z=zlib'
+
+--time-field FIELD reads the time from FIELD instead of 'time'. It's moved to 'time' once read, so the default
+pattern still shows it. --time-format says how it's written:
+  auto         The default. ISO8601 or RFC3339, with a T or a space between the date and time, a . or , before the
+               fraction and an optional offset like Z or +02:00. Numbers are epoch seconds, or milliseconds when
+               too large to be seconds
+  epoch        Seconds since 1970, as a number or a string, with or without a fraction
+  epochMillis  Milliseconds since 1970, as a number or a string
+  PATTERN      Pieces like strftime: %Y %m %d %H %M %S, %e for a day that may be padded with a space, %b or %B for a
+               month name like Jun or June, %a or %A for a day name that is skipped, %y for a two digit year from
+               1969 to 2068, %I and %p for a 12 hour clock with AM or PM, %f for a fraction of a second and %z for an
+               offset. Anything else must match exactly. Pieces left out are the start of their unit, except the year
+               which is this year
+
+--time-format can be passed more than once for sources that write times more than one way, and each is tried in
+turn until one reads the time. Month and day names are English unless --time-locale says otherwise, with one or more
+of en, de, es, fr, it, nl and pt separated by commas. Names match whatever their case, full or short, and with or
+without a . after them, so with --time-locale fr,de both "5 juin 2024" and "5 Juni 2024" read with "%d %b %Y".
+
+A numeric 'level' from the scale bunyan and pino use has a name, 10 => TRACE, 20 => DEBUG, 30 => INFO, 40 => WARN,
+50 => ERROR and 60 => FATAL, so "%level=ERROR" and the level colors work without a --preset. Other numbers are left
+alone. --level-map replaces the names with comma separated NUMBER=NAME pairs, for loggers with levels of their own,
+and --level-map none keeps every level a number:
+  saw app.log --level-map 10=TRACE,20=DEBUG,30=INFO,35=NOTICE,40=WARN,50=ERROR,60=FATAL
+
+The names are only used by filters and by pretty, table, html and summary output. JSON is written with the number the
+event had, so an archive reads the same as its sources, unless --rewrite-levels writes the name in its place:
+  saw app.log --rewrite-levels -o named.log.gz
+
+Times with an offset are moved to UTC so that logs from different zones merge in the right order. Without any of
+these options JSON is read exactly as before, where an offset is ignored. Indexes are only used for plain JSON.
+
+A single source is read straight through when nothing needs the time of its events, like --range, --summary-by or
+--split-by-time do. Each line still needs a valid 'time' to be kept, just as when merging, but common shapes of time
+are only checked rather than fully read. The plan from --dry-run says when this happens.
+
+Examples:
+  Logfmt with epoch milliseconds in 'ts': `saw app.log --input logfmt --time-field ts --time-format epochMillis`
+  Classic text logs: `saw app.log --input 'regex:^\[(?P<time>[^\]]+)\] (?P<level>\w+) (?P<message>.*)$'`
+  Apache access logs: `saw access.log --input 'regex:^(?P<ip>\S+) \S+ \S+ \[(?P<time>[^\]]+)\] "(?P<message>[^"]*)"' --time-format "%d/%b/%Y:%H:%M:%S %z"`
+  An appliance that changed formats: `saw fw.log --input logfmt --time-format "%d-%b-%y %I:%M:%S %p" --time-format auto`
+"#;
+
+const PLUGIN_TOPIC: &str = r#"
+Usage:
+  saw --plugin-filter CMD
+  saw --plugin-transform CMD
+
+Runs each event through a command of your own, in any language, for what --filter and --translate can't do.
+CMD is run once with sh and reads one JSON event per line on its stdin. For each event it reads it must write
+one answer per line to its stdout, in the same order:
+
+  --plugin-filter      true to keep the event, or false to leave it out
+  --plugin-transform   the event to write in its place as a JSON object, or null to leave it out
+
+Events are sent in batches of 256, or one at a time with --follow, so the command must answer each event
+as soon as it has read it instead of waiting for its input to end. In python that means flushing stdout
+after each answer. What it writes to stderr is passed through, and stdin is closed once there are no more events.
+
+Plugins run after every other stage that looks at one event at a time, so they see events as they'd be written,
+and before --tail. Pass either option more than once to run several commands, in the order given:
+
+  saw app.log --plugin-filter 'python3 is_suspicious.py' --plugin-transform './geoip --field client_ip'
+
+The time an event was sorted by is kept, even if a transform changes its 'time' field. A command that stops early
+or answers with something else stops saw.
+"#;
+
+/// (name, summary, text) of each topic for --help TOPIC
+const TOPICS: [(&str, &str, &str); 9] = [
+  ("pretty", "How pretty printing patterns work", PRETTY_TOPIC),
+  ("filter", "How filtering patterns work", FILTER_TOPIC),
+  ("range", "How to use the range option", RANGE_TOPIC),
+  ("translate", "How to use the translate feature (it's like sed for json)", TRANSLATE_TOPIC),
+  ("chunked", "The syntax for chunked size limits", CHUNKED_TOPIC),
+  ("preset", "The built in presets for common logging libraries", PRESET_TOPIC),
+  ("compress", "How to pick compression for each output", COMPRESS_TOPIC),
+  ("input", "How to read logfmt, plain text and other time formats", INPUT_TOPIC),
+  ("plugin", "How to filter and change events with your own commands", PLUGIN_TOPIC),
+];
+
 const DEFAULT_PRETTY: &str = "[%time] %message %prefix/\\n/%stack\\v/";
 
 pub struct Arguments {
   pub sources: Vec<LogFile>,
   pub pretty: Option<PrettyDescriptor>,
+  /// no pattern was given anywhere, so the default is made from the fields of the first events
+  pub adaptive_pretty: bool,
+  pub theme: Option<PrettyTheme>,
+  pub table: Option<Table>,
+  pub group: Option<Grouping>,
+  /// print identical consecutive pretty lines once, with a count of repeats
+  pub collapse_repeats: bool,
+  pub html: Option<HtmlReport>,
+  /// write each event as indented JSON followed by a separator line
+  pub indented: bool,
+  /// write each event as a length prefixed frame rather than a line
+  pub frames: Option<Framing>,
+  /// which characters in JSON strings are written as \uXXXX beyond what JSON needs
+  pub escape_json: Option<JsonEscape>,
   pub filter: Option<FilterSet>,
+  /// list the filters that accepted each event, and with drops what left out the rest
+  pub why: Option<Why>,
+  pub trace: Option<TraceIds>,
+  pub shard: Option<Shard>,
+  /// number each event by the hash of a field
+  pub partition: Option<Partition>,
+  pub escalations: Vec<Escalation>,
   pub output: Option<PathBuf>,
+  pub clipboard: bool,
   pub chunked: Option<ChunkInfo>,
+  pub skip_unchanged: bool,
+  /// compress and upload finished chunks on other threads
+  pub chunk_workers: Option<ChunkWorkers>,
+  pub split_by_time: Option<SplitInterval>,
+  /// events matching each route go to its file instead of the output
+  pub routes: Vec<Route>,
+  pub on_exists: OnExists,
+  /// write a checksum file next to every output file
+  pub checksum: Option<Checksum>,
   pub translations: Vec<Translation>,
+  pub crypto: Option<FieldCrypto>,
+  /// write decrypted fields to files and JSON too, not only to pretty or table output, from --write-decrypted
+  pub write_decrypted: bool,
+  /// look for secrets in what's written
+  pub secrets: Option<SecretScanner>,
+  pub fingerprint: Option<Fingerprint>,
+  /// keep only one tenant's events, with every other tenant redacted
+  pub tenant: Option<Tenant>,
+  /// external commands each event is run through, in the order given
+  pub plugins: Vec<Plugin>,
+  /// number the sessions of each key
+  pub sessionize: Option<Sessionizer>,
+  /// mark events that come while their group is far busier than usual
+  pub anomalies: Option<AnomalyDetector>,
+  /// pair events from two sides by different fields
+  pub correlate: Option<Correlator>,
+  /// number each event and link it to the ones before and after it with the same key
+  pub link_by: Option<Linker>,
+  pub compact: bool,
+  /// cut these fields down to a number of bytes
+  pub truncations: Vec<Truncation>,
+  /// how 'time' is written, whatever way it was read
+  pub normalize_time: Option<TimeOutput>,
+  pub preset: Option<&'static Preset>,
   pub range: (Option<LocalDateTime>, Option<LocalDateTime>),
   pub daily: bool,
-  pub zip: bool,
+  /// where days and hours start for --daily, --split-by-time and --summary-by
+  pub timezone: TimeZone,
+  pub keep_order: bool,
+  pub follow: bool,
+  /// how long --follow waits before looking for more lines, from --poll-interval
+  pub poll_interval: Option<Duration>,
+  /// replace the filters, translations and template whenever this file changes
+  pub reload: Option<PathBuf>,
+  /// drop events too far behind the newest one while following, from --drop-older-than
+  pub drop_older_than: Option<Watermark>,
+  /// only write the last N events, from --tail
+  pub tail: Option<usize>,
+  /// write the newest events first, from --reverse
+  pub reverse: bool,
+  /// every source pattern as given, so --follow can look for new files
+  pub globs: Vec<String>,
+  pub sort_by: Option<&'static SortKey>,
+  /// sort events that aren't in any order by spilling sorted runs to a directory, from --external-sort
+  pub external_sort: Option<ExternalSort>,
+  pub input: Option<&'static InputFormat>,
+  pub time: Option<&'static TimeParser>,
+  /// names for numeric levels, the standard ones of bunyan and pino unless --level-map says otherwise
+  pub levels: Option<&'static LevelMap>,
+  /// write the names of numeric levels into the events, rather than only filtering and printing with them
+  pub rewrite_levels: bool,
+  /// the only top level fields read from each event
+  pub projection: Option<&'static Projection>,
+  pub passthrough_invalid: bool,
+  /// accept the almost-JSON some producers write
+  pub lenient_json: bool,
+  /// read again from the next record after corrupt data
+  pub resync: bool,
+  /// print the bytes of lines that can't be read
+  pub debug_raw: bool,
+  /// copy every line read from some sources to a file as it was, from --copy-raw SOURCE=PATH
+  pub raw_copies: Vec<RawCopy>,
+  pub key_order: Option<KeyOrder>,
+  pub regex_options: Option<RegexOptions>,
+  /// sources read with a codec other than the one sniffed, from --codec SOURCE=CODEC
+  pub source_codecs: Vec<(Pattern, SourceCodec)>,
+  /// leave out sources older than this without opening them
+  pub ignore_older_than: Option<AgeCutoff>,
+  /// whether what's read is dropped from the page cache
+  pub page_cache: Option<CacheAdvice>,
+  /// how reads that fail for a moment are retried
+  pub read_retries: Option<RetryPolicy>,
+  pub lines: Span,
+  pub bytes: Span,
+  pub dry_run: bool,
+  /// check one event against every stage instead of reading the sources
+  pub explain_filter: Option<String>,
+  /// list local files without opening them, and read them all at once before merging
+  pub async_sources: bool,
+  /// the most files to have open at once, opening the rest as others finish
+  pub max_open_files: Option<usize>,
+  /// the most cores to run parallel stages on, below whatever the container allows
+  pub max_cpu: Option<usize>,
+  /// measure the start of the input and tune buffers, threads and filters to it
+  pub autotune: Option<Autotune>,
+  /// write the same bytes for the same sources on every run and machine
+  pub deterministic: bool,
+  pub percentiles: Vec<String>,
+  pub summary_by: Option<SummaryBy>,
+  pub stats: Option<Stats>,
+  /// write what --stats or --group-by have so far every so often, rather than only once done
+  pub snapshot_every: Option<SnapshotEvery>,
+  pub provenance: bool,
+  pub source_stats: bool,
+  pub audit: bool,
+  pub deadline: Option<Duration>,
+  /// check that events are written in time order, from --verify-sorted-output
+  pub verify_sorted: Option<VerifyAction>,
+  pub rate_limit: Option<RateLimit>,
+  /// at most so many events for each of a few filters
+  pub quota: Option<Quota>,
+  /// where to listen for pause, resume, stats and stop
+  pub control_socket: Option<PathBuf>,
+  pub replay: Option<Replay>,
+  pub expect_count: Option<ExpectCount>,
+  pub compression: CompressionRoutes,
 }
 
 impl Arguments {
   pub fn parse() -> Arguments {
+    Arguments::parse_from(env::args().skip(1).collect())
+  }
+
+  /**
+   * Read the arguments from a list that doesn't start with the program, like the one a pipeline file stands for
+   */
+  pub fn parse_from(raw: Vec<String>) -> Arguments {
     let mut init = Arguments {
       sources: vec![],
       pretty: None,
+      adaptive_pretty: false,
+      theme: None,
+      table: None,
+      group: None,
+      collapse_repeats: false,
+      html: None,
+      indented: false,
+      frames: None,
+      escape_json: None,
       filter: None,
+      why: None,
+      trace: None,
+      shard: None,
+      partition: None,
+      escalations: vec![],
       output: None,
+      clipboard: false,
       chunked: None,
+      skip_unchanged: false,
+      chunk_workers: None,
+      split_by_time: None,
+      routes: vec![],
+      on_exists: OnExists::default(),
+      checksum: None,
       translations: vec![],
+      crypto: None,
+      write_decrypted: false,
+      secrets: None,
+      fingerprint: None,
+      tenant: None,
+      plugins: vec![],
+      sessionize: None,
+      anomalies: None,
+      correlate: None,
+      link_by: None,
+      compact: false,
+      truncations: vec![],
+      normalize_time: None,
+      preset: None,
       range: (None, None),
       daily: false,
-      zip: false,
+      timezone: TimeZone::utc(),
+      keep_order: false,
+      follow: false,
+      poll_interval: None,
+      reload: None,
+      drop_older_than: None,
+      tail: None,
+      reverse: false,
+      globs: vec![],
+      sort_by: None,
+      external_sort: None,
+      input: None,
+      time: None,
+      levels: Some(LevelMap::standard()),
+      rewrite_levels: false,
+      projection: None,
+      passthrough_invalid: false,
+      lenient_json: false,
+      resync: false,
+      debug_raw: false,
+      raw_copies: vec![],
+      key_order: None,
+      regex_options: None,
+      source_codecs: vec![],
+      ignore_older_than: None,
+      page_cache: None,
+      read_retries: None,
+      lines: Span::default(),
+      bytes: Span::default(),
+      dry_run: false,
+      explain_filter: None,
+      async_sources: false,
+      max_open_files: None,
+      max_cpu: None,
+      autotune: None,
+      deterministic: false,
+      percentiles: vec![],
+      summary_by: None,
+      stats: None,
+      snapshot_every: None,
+      provenance: false,
+      source_stats: false,
+      audit: false,
+      deadline: None,
+      verify_sorted: None,
+      rate_limit: None,
+      quota: None,
+      control_socket: None,
+      replay: None,
+      expect_count: None,
+      compression: CompressionRoutes::new(vec![], Codec::Plain),
     };
 
     // have these flags been passed?
     let mut has_zip = false;
     let mut has_json = false;
+    let mut reads_stdin = false;
+    let mut has_on_exists = false;
+    let mut upload_chunk: Option<String> = None;
+    let mut time_field: Option<String> = None;
+    let mut time_formats: Vec<String> = vec![];
+    let mut time_locale: Option<String> = None;
+    let mut has_level_map = false;
+    let mut has_timezone = false;
+    let mut projection: Option<Projection> = None;
+    let mut ignore_packs: Vec<String> = vec![];
+    let mut has_pattern_functions = false;
+
+    // the default pattern depends on --preset which might come later, so it is loaded after all arguments are read
+    let mut default_pretty = false;
 
     // json is not on Arguments because the outer code can assume Pretty OR JSON
     let mut json = false;
 
-    let mut src = env::args().peekable();
+    // zip and the compression routes are combined into the single compression table after all arguments are read
+    let mut zip = false;
+    let mut compress_routes = vec![];
+
+    // the key is only loaded once all arguments are read, and only if a field needs it
+    let mut decrypt_fields: Vec<String> = vec![];
+    let mut encrypt_fields: Vec<String> = vec![];
+    let mut field_key: Option<String> = None;
+    let mut catalog: Option<PathBuf> = None;
+    // a --command's stderr is read as events at the time each line was written
+    let mut stamps_stderr = false;
+
+    // every mistake is kept and reported together once all arguments are read, so a long command line can be fixed in
+    // one go rather than one run at a time
+    let mut errors: Vec<String> = vec![];
+
+    // regex options apply to every pattern, including ones given before them, so they're found before anything is compiled,
+    // and so are pattern functions. --async-sources, --max-open-files, --codec, --page-cache and --ignore-older-than are found first too,
+    // since they decide how every source listed before them is opened, or whether it is, and --deterministic since it
+    // decides what %now prints
+    let mut scan = raw.iter().cloned();
+    while let Some(next) = scan.next() {
+      Arguments::collect(&mut errors, || {
+        if next == "--async-sources" {
+          if !cfg!(feature = "async") {
+            return Err("Option --async-sources needs saw built with the 'async' feature, like cargo install saw --features async".to_string());
+          }
+
+          if init.async_sources {
+            return Err("Cannot pass argument --async-sources twice!".to_string())
+          }
 
-    // the first argument is the program, always ignore that.
-    src.next();
+          init.async_sources = true;
+        }
 
-    while let Some(next) = src.next() {
-      if next.starts_with("-") {
-        match next.as_ref() {
-          "-" => {
-            init.sources.push(LogFile::from_stdin())
+        if next == "--regex-options" {
+          if init.regex_options.is_some() {
+            return Err("Cannot pass argument --regex-options twice!".to_string())
           }
-          "-h" | "--help" => {
-            if let Some(topic) = src.next() {
-              let message = match topic.as_ref() {
-                "pretty"    => PRETTY_TOPIC,
-                "filter"    => FILTER_TOPIC,
-                "range"     => RANGE_TOPIC,
-                "translate" => TRANSLATE_TOPIC,
-                "chunked"   => CHUNKED_TOPIC,
-                _           => HELP
-              };
 
-              println!("{}", message);
-              exit(0)
-            }
+          let raw = scan.next().ok_or("Argument --regex-options must be followed by KEY=VALUE options")?;
+          let options = RegexOptions::parse(&raw)?;
+
+          patterns::configure(options);
+          init.regex_options = Some(options);
+        }
+
+        if next == "--pattern-functions" {
+          let raw = scan.next().ok_or("Argument --pattern-functions must be followed by a FILE")?;
+
+          functions::load(Path::new(&raw))?;
+          has_pattern_functions = true;
+        }
+
+        if next == "--page-cache" {
+          if init.page_cache.is_some() {
+            return Err("Cannot pass argument --page-cache twice!".to_string())
+          }
+
+          let raw = scan.next().ok_or("Argument --page-cache must be followed by auto, keep or drop")?;
+          let advice = CacheAdvice::parse(&raw)?;
+
+          cache::set_advice(advice);
+          init.page_cache = Some(advice);
+        }
+
+        if next == "--deterministic" {
+          if init.deterministic {
+            return Err("Cannot pass argument --deterministic twice!".to_string())
+          }
+
+          deterministic::enable();
+          init.deterministic = true;
+        }
 
-            eprintln!("{}", HELP);
-            exit(0);
+        if next == "--max-open-files" {
+          if init.max_open_files.is_some() {
+            return Err("Cannot pass argument --max-open-files twice!".to_string())
           }
-          "-v" | "--version" => {
-            eprintln!(env!("CARGO_PKG_VERSION"));
-            exit(0);
+
+          let raw = scan.next().ok_or("Argument --max-open-files must be followed by a number of files")?;
+
+          init.max_open_files = Some(raw.parse().ok().filter(|max| *max > 0)
+            .ok_or_else(|| format!("Argument --max-open-files must be a number greater than 0, but was '{raw}'"))?);
+        }
+
+        if next == "--codec" {
+          let raw = scan.next().ok_or("Argument --codec must be followed by SOURCE=CODEC, like 'app.log.gz=plain'")?;
+
+          init.source_codecs.push(codec::parse_source_codec(&raw)?);
+        }
+
+        if next == "--ignore-older-than" {
+          if init.ignore_older_than.is_some() {
+            return Err("Cannot pass argument --ignore-older-than twice!".to_string())
           }
-          "-p" | "--pretty" => {
-            if init.pretty.is_some() {
-              panic!("Cannot pass argument --pretty twice!")
+
+          let raw = scan.next().ok_or("Argument --ignore-older-than must be followed by a DURATION like 30d")?;
+
+          init.ignore_older_than = Some(AgeCutoff::parse(&raw)?);
+        }
+
+        Ok(())
+      });
+    }
+
+    if !has_pattern_functions {
+      Arguments::collect(&mut errors, functions::load_default);
+    }
+
+    codec::force_source_codecs(init.source_codecs.clone());
+
+    let mut src = raw.into_iter().peekable();
+
+    while let Some(next) = src.next() {
+      // a mistyped flag would have its value read as a source, so that's passed over along with it
+      if next.starts_with('-') && next != "-" && flags::find(&next).is_none() {
+        errors.push(flags::unknown(&next));
+
+        while src.next_if(|value| !value.starts_with('-')).is_some() {}
+        continue;
+      }
+
+      Arguments::collect(&mut errors, || {
+        if next.starts_with("-") {
+          // every flag is resolved through the table, so short names become their long name here
+          let name = if next == "-" {
+            "-"
+          } else {
+            flags::find(&next)
+              .ok_or_else(|| flags::unknown(&next))?
+              .long
+          };
+
+          match name {
+            "-" => {
+              if reads_stdin {
+                return Err("Cannot read stdin twice, pass only one of - and --stdin-multiplex".to_string())
+              }
+
+              reads_stdin = true;
+              init.sources.push(LogFile::from_stdin())
+            }
+            "--help" => {
+              if let Some(topic) = src.next() {
+                let message = TOPICS.iter()
+                  .find(|(name, _, _)| *name == topic)
+                  .map_or(Arguments::help(), |(_, _, text)| text.to_string());
+
+                if !manual::page(&message) {
+                  println!("{}", message);
+                }
+
+                exit(0)
+              }
+
+              let help = Arguments::help();
+
+              if !manual::page(&help) {
+                eprintln!("{help}");
+              }
+
+              exit(0);
+            }
+            "--help-json" => {
+              println!("{}", serde_json::to_string_pretty(&flags::schema(&TOPICS)).expect("Failed to write help"));
+              exit(0);
             }
+            "--version" => {
+              eprintln!(env!("CARGO_PKG_VERSION"));
+              exit(0);
+            }
+            "--pretty" => {
+              if init.pretty.is_some() || default_pretty {
+                return Err("Cannot pass argument --pretty twice!".to_string())
+              }
 
-            if let Some(pattern) = src.peek() {
-              if pattern.starts_with('-') {
-                init.pretty = Some(Arguments::load_default_pattern());
+              if let Some(pattern) = src.peek() {
+                if pattern.starts_with('-') {
+                  default_pretty = true;
+                } else {
+                  init.pretty = Some(patterns::pretty(&src.next().unwrap())?);
+                }
               } else {
-                init.pretty = Some(PrettyDescriptor::parse(&src.next().unwrap()));
+                default_pretty = true;
               }
-            } else {
-              init.pretty = Some(Arguments::load_default_pattern());
             }
-          }
-          "-f" | "--filter" => {
-            let raw = src
-              .next()
-              .expect("Argument --filter must be followed by a pattern");
+            "--pretty-per" => {
+              let field = src.next().ok_or("Argument --pretty-per must be followed by a FIELD and then a VALUE=PATTERN argument")?;
+              let template = src.next().ok_or("Argument --pretty-per FIELD must be followed by a VALUE=PATTERN argument")?;
+
+              let theme = init.theme.get_or_insert_with(|| PrettyTheme::new(field.clone()));
 
-            let filter = FilterSet::parse(&raw);
+              if theme.field() != field {
+                return Err(format!("Argument --pretty-per can only pick templates by one field, but was given both '{}' and '{field}'", theme.field()));
+              }
 
-            if let Some(set) = &mut init.filter {
-              set.sets.push(filter);
-            } else {
-              init.filter = Some(FilterSet{ sets: vec![filter] });
+              theme.add(&template)?;
             }
-          }
-          "-o" | "--output" => {
-            if init.output.is_some() {
-              panic!("Cannot pass argument --filter twice!")
+            "--table" => {
+              if init.table.is_some() {
+                return Err("Cannot pass argument --table twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --table must be followed by comma separated FIELDS")?;
+
+              init.table = Some(Table::parse(&raw)?);
             }
+            "--output-format" => {
+              if init.html.is_some() || init.indented || init.frames.is_some() {
+                return Err("Cannot pass argument --output-format twice!".to_string())
+              }
 
-            init.output = Some(
-              src.next()
-                .expect("Argument --output must be followed by a file path")
-                .into(),
-            )
-          }
-          "-c" | "--chunked" => {
-            if init.chunked.is_some() {
-              panic!("Cannot pass argument --filter twice!")
+              let raw = src.next().ok_or("Argument --output-format must be followed by a FORMAT")?;
+
+              match raw.as_str() {
+                HTML_FORMAT => init.html = Some(HtmlReport::default()),
+                INDENTED_FORMAT => init.indented = true,
+                _ if Framing::parse(&raw).is_some() => init.frames = Framing::parse(&raw),
+                _ => return Err(format!("Argument --output-format '{raw}' is not recognized. The formats are '{HTML_FORMAT}', '{INDENTED_FORMAT}' and '{FRAMES_FORMAT}', use --pretty or --json for the others")),
+              }
             }
+            "--group-by" => {
+              if init.group.is_some() {
+                return Err("Cannot pass argument --group-by twice!".to_string())
+              }
 
-            let raw = src
-              .next()
-              .expect("Argument --chunked must be followed by a size descriptor");
+              let field = src.next().ok_or("Argument --group-by must be followed by a FIELD")?;
 
-            init.chunked = Some(ChunkInfo::parse(&raw))
-          }
-          "-z" | "--zip" => {
-            if has_zip {
-              panic!("Cannot pass argument --zip twice!")
+              init.group = Some(Grouping::new(field));
             }
+            "--escape-json-strings" => {
+              if init.escape_json.is_some() {
+                return Err("Cannot pass argument --escape-json-strings twice!".to_string())
+              }
 
-            has_zip = true;
+              let raw = src.next().ok_or("Argument --escape-json-strings must be followed by never, minimal or ascii")?;
 
-            let raw = src.next().expect("Argument --zip must be followed by 'true' or 'false'");
+              init.escape_json = Some(JsonEscape::parse(&raw)?);
+            }
+            "--collapse-repeats" => {
+              if init.collapse_repeats {
+                return Err("Cannot pass argument --collapse-repeats twice!".to_string())
+              }
 
-            let value = match raw.to_lowercase().as_str() {
-              "true" => true,
-              "false" => false,
-              _ => panic!("Argument --zip must be followed by 'true' or 'false'")
-            };
+              init.collapse_repeats = true;
+            }
+            "--filter" => {
+              let raw = src
+                .next()
+                .ok_or("Argument --filter must be followed by a pattern")?;
 
-            init.zip = value;
-          }
-          "-j" | "--json" => {
-            if has_json {
-              panic!("Cannot pass argument --json twice!")
+              let expression = FilterSet::parse_expression(&raw)?;
+
+              init.filter.get_or_insert_with(FilterSet::default).push(expression);
             }
+            "--filter-file" => {
+              let raw = src
+                .next()
+                .ok_or("Argument --filter-file must be followed by a [%FIELD=]PATH")?;
+
+              let filter = ValueFilter::parse(&raw)?;
 
-            has_json = true;
+              init.filter.get_or_insert_with(FilterSet::default).value_sets.push(filter);
+            }
+            "--ignore-pack" => {
+              let raw = src.next().ok_or("Argument --ignore-pack must be followed by comma separated NAMES of packs")?;
 
-            let raw = src.next().expect("Argument --json must be followed by 'true' or 'false'");
+              for name in raw.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                if ignore_packs.iter().any(|existing| existing == name) {
+                  return Err(format!("Cannot pass ignore pack '{name}' twice!"))
+                }
 
-            json = match raw.to_lowercase().as_str() {
-              "true" => true,
-              "false" => false,
-              _ => panic!("Argument --json must be followed by 'true' or 'false'")
-            };
-          }
-          "-r" | "--range" => {
-            if let (None, None) = init.range {} else {
-              panic!("Cannot pass argument --range twice!")
+                ignore_packs.push(name.to_string());
+              }
             }
+            "--why" => {
+              if init.why.is_some() {
+                return Err("Cannot pass argument --why twice!".to_string())
+              }
 
-            let raw_min = src.next().expect(
-              "Argument --range must be followed by a MIN and then MAX value",
-            );
-            let raw_max = src
-              .next()
-              .expect("Argument --range MIN must be followed by a MAX value");
+              // only 'drops' is taken, so a source right after --why is still a source
+              init.why = if src.next_if(|next| next == "drops").is_some() { Some(Why::Drops) } else { Some(Why::Matched) };
+            }
+            "--shard" => {
+              if init.shard.is_some() {
+                return Err("Cannot pass argument --shard twice!".to_string())
+              }
 
-            let range = match (raw_min.as_ref(), raw_max.as_ref()) {
-              ("*", "*") => (None, None),
-              ("*", raw_max) => {
-                let max = LocalDateTime::from_str(raw_max).expect(
-                  "Argument --range MAX must be a valid ISO8601 local date time",
-                );
+              let raw = src.next().ok_or("Argument --shard must be followed by K/N, like 2/8")?;
 
-                (None, Some(max))
+              init.shard = Some(Shard::parse(&raw)?);
+            }
+            "--tenant" => {
+              if init.tenant.is_some() {
+                return Err("Cannot pass argument --tenant twice!".to_string())
               }
-              (raw_min, "*") => {
-                let min = LocalDateTime::from_str(raw_min).expect(
-                  "Argument --range MIN must be a valid ISO8601 local date time",
-                );
 
-                (Some(min), None)
+              let raw = src.next().ok_or("Argument --tenant must be followed by a NAME, like acme or acme,field=org_id")?;
+
+              init.tenant = Some(Tenant::parse(&raw)?);
+            }
+            "--partition-hash" => {
+              if init.partition.is_some() {
+                return Err("Cannot pass argument --partition-hash twice!".to_string())
               }
-              (raw_min, raw_max) => {
-                let min = LocalDateTime::from_str(raw_min).expect(
-                  "Argument --range MIN must be a valid ISO8601 local date time",
-                );
-                let max = LocalDateTime::from_str(raw_max).expect(
-                  "Argument --range MAX must be a valid ISO8601 local date time",
-                );
 
-                (Some(min), Some(max))
+              let raw = src.next().ok_or("Argument --partition-hash must be followed by FIELD%N, like user_id%16")?;
+
+              init.partition = Some(Partition::parse(&raw)?);
+            }
+            "--trace-ids" | "--trace" => {
+              if init.trace.is_some() {
+                return Err("Cannot pass argument --trace or --trace-ids twice!".to_string())
               }
-            };
 
-            init.range = range
-          }
-          "--daily" => {
-            if init.daily {
-              panic!("Cannot pass argument --daily twice!")
+              if name == "--trace" {
+                let raw = src.next().ok_or("Argument --trace must be followed by a TRACE_ID")?;
+
+                init.trace = Some(TraceIds::only(&raw)?);
+              } else {
+                init.trace = Some(TraceIds::default());
+              }
             }
+            "--output" => {
+              if init.output.is_some() || init.clipboard {
+                return Err("Cannot pass argument --filter twice!".to_string())
+              }
 
-            init.daily = true;
-          }
-          "-t" | "--translate" => {
-            let output = src.next().expect("Argument --translate must be followed by a TARGET_FIELD and then a PATTERN argument");
-            let pattern = src.next().expect("Argument --translate TARGET_FIELD must be followed by a PATTERN argument");
+              let raw = src.next().ok_or("Argument --output must be followed by a file path")?;
 
-            let translation = Translation::parse(output, &pattern);
+              // a file really called clipboard can still be written as ./clipboard
+              if raw == CLIPBOARD_OUTPUT {
+                init.clipboard = true;
+              } else {
+                init.output = Some(raw.into());
+              }
+            }
+            "--on-exists" => {
+              if has_on_exists {
+                return Err("Cannot pass argument --on-exists twice!".to_string())
+              }
 
-            init.translations.push(translation);
-          }
-          _ => {
-            panic!("Unknown property '{next}'. Run saw with --help to see all known properties");
-          }
-        }
-      }
+              let raw = src
+                .next()
+                .ok_or("Argument --on-exists must be followed by one of error, overwrite, append or rename")?;
 
-      // must be a source
-      init.sources.append(&mut Arguments::read_path(&next));
-    }
+              init.on_exists = OnExists::parse(&raw)?;
+              has_on_exists = true;
+            }
+            "--checksum" => {
+              if init.checksum.is_some() {
+                return Err("Cannot pass argument --checksum twice!".to_string())
+              }
 
-    // a few remaining defaults and sanity checks
+              let raw = src.next().ok_or("Argument --checksum must be followed by sha256")?;
 
-    // chunked requires output
-    if init.chunked.is_some() && init.output.is_none() {
-      panic!("Option --chunked is only valid when option --output is specified!");
-    }
+              init.checksum = Some(Checksum::parse(&raw)?);
+            }
+            "--chunked" => {
+              if init.chunked.is_some() {
+                return Err("Cannot pass argument --filter twice!".to_string())
+              }
 
-    if has_json {
-      // if you passed the json flag
+              let raw = src
+                .next()
+                .ok_or("Argument --chunked must be followed by a size descriptor")?;
 
-      if json {
-        // if you passed true
+              init.chunked = Some(ChunkInfo::parse(&raw)?)
+            }
+            "--skip-unchanged" => {
+              if init.skip_unchanged {
+                return Err("Cannot pass argument --skip-unchanged twice!".to_string())
+              }
 
-        if init.pretty.is_some() {
-          // and pretty is on
-          panic!("Cannot pass both --pretty and --json true at the same time as these options conflict")
-        }
-      } else {
-        // if you specified json false, we need to default pretty if you did not
-        if init.pretty.is_none() {
-          init.pretty = Some(Arguments::load_default_pattern());
-        }
-      }
-    } else {
-      // if you did not specify json
+              init.skip_unchanged = true;
+            }
+            "--chunk-workers" => {
+              if init.chunk_workers.is_some() {
+                return Err("Cannot pass argument --chunk-workers twice!".to_string())
+              }
 
-      if init.output.is_none() {
-        // if you did not provide output
+              let raw = src.next().ok_or("Argument --chunk-workers must be followed by N[,retries=R]")?;
 
-        // if you did not specify pretty, default it on
-        if init.pretty.is_none() {
-          init.pretty = Some(Arguments::load_default_pattern());
-        }
-      }
-    }
+              init.chunk_workers = Some(ChunkWorkers::parse(&raw)?);
+            }
+            "--upload-chunk" => {
+              if upload_chunk.is_some() {
+                return Err("Cannot pass argument --upload-chunk twice!".to_string())
+              }
 
-    // if you did not specify zip
-    if !has_zip {
-      // set zip on if pretty it off
-      init.zip = init.pretty.is_none()
-    }
+              upload_chunk = Some(src.next().ok_or("Argument --upload-chunk must be followed by a command")?);
+            }
+            "--split-by-time" => {
+              if init.split_by_time.is_some() {
+                return Err("Cannot pass argument --split-by-time twice!".to_string())
+              }
 
-    // if you set daily but didn't provide a range
-    if init.daily && init.range == (None, None) {
-      panic!("Cannot pass the --daily flag without a range! Add a range or remove --daily")
-    }
+              let raw = src
+                .next()
+                .ok_or("Argument --split-by-time must be followed by an INTERVAL like 1h")?;
 
-    return init;
-  }
+              init.split_by_time = Some(SplitInterval::parse(&raw)?);
+            }
+            "--route" => {
+              let raw = src.next().ok_or("Argument --route must be followed by a rule like \"FILTER => PATH\"")?;
 
-  fn read_path(raw: &str) -> Vec<LogFile> {
-    glob(raw)
-      .expect(&format!(
-        "Source '{raw}' is not valid or directory could not be read"
-      ))
-      .map(|p| LogFile::from_file(&p.expect(&format!("Source '{raw}' is not valid or could not be read"))))
-      .collect()
-  }
+              init.routes.push(Route::parse(&raw)?);
+            }
+            "--zip" => {
+              if has_zip {
+                return Err("Cannot pass argument --zip twice!".to_string())
+              }
 
-  /**
-   * Either load up the default from an environment variable or take the default provided
-   */
-  fn load_default_pattern() -> PrettyDescriptor {
-    return env::var("SAW_PATTERN")
-      .map(| it | PrettyDescriptor::parse(&it))
-      .unwrap_or(PrettyDescriptor::parse(DEFAULT_PRETTY));
+              has_zip = true;
+
+              let raw = src.next().ok_or("Argument --zip must be followed by 'true' or 'false'")?;
+
+              let value = match raw.to_lowercase().as_str() {
+                "true" => true,
+                "false" => false,
+                _ => return Err("Argument --zip must be followed by 'true' or 'false'".to_string())
+              };
+
+              zip = value;
+            }
+            "--json" => {
+              if has_json {
+                return Err("Cannot pass argument --json twice!".to_string())
+              }
+
+              has_json = true;
+
+              let raw = src.next().ok_or("Argument --json must be followed by 'true' or 'false'")?;
+
+              json = match raw.to_lowercase().as_str() {
+                "true" => true,
+                "false" => false,
+                _ => return Err("Argument --json must be followed by 'true' or 'false'".to_string())
+              };
+            }
+            "--range" => {
+              if let (None, None) = init.range {} else {
+                return Err("Cannot pass argument --range twice!".to_string())
+              }
+
+              let raw_min = src.next().ok_or("Argument --range must be followed by a MIN and then MAX value")?;
+              let raw_max = src
+                .next()
+                .ok_or("Argument --range MIN must be followed by a MAX value")?;
+
+              let range = match (raw_min.as_ref(), raw_max.as_ref()) {
+                ("*", "*") => (None, None),
+                ("*", raw_max) => {
+                  let max = LocalDateTime::from_str(raw_max).map_err(|_| "Argument --range MAX must be a valid ISO8601 local date time")?;
+
+                  (None, Some(max))
+                }
+                (raw_min, "*") => {
+                  let min = LocalDateTime::from_str(raw_min).map_err(|_| "Argument --range MIN must be a valid ISO8601 local date time")?;
+
+                  (Some(min), None)
+                }
+                (raw_min, raw_max) => {
+                  let min = LocalDateTime::from_str(raw_min).map_err(|_| "Argument --range MIN must be a valid ISO8601 local date time")?;
+                  let max = LocalDateTime::from_str(raw_max).map_err(|_| "Argument --range MAX must be a valid ISO8601 local date time")?;
+
+                  (Some(min), Some(max))
+                }
+              };
+
+              init.range = range
+            }
+            "--preset" => {
+              if init.preset.is_some() {
+                return Err("Cannot pass argument --preset twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --preset must be followed by a preset name")?;
+
+              init.preset = Some(Preset::parse(&raw)?);
+            }
+            "--daily" => {
+              if init.daily {
+                return Err("Cannot pass argument --daily twice!".to_string())
+              }
+
+              init.daily = true;
+            }
+            "--timezone" => {
+              if has_timezone {
+                return Err("Cannot pass argument --timezone twice!".to_string())
+              }
+
+              has_timezone = true;
+
+              let raw = src.next().ok_or("Argument --timezone must be followed by a ZONE like Europe/Berlin, +02:00 or UTC")?;
+
+              init.timezone = TimeZone::parse(&raw)?;
+            }
+            "--compress" => {
+              let raw = src.next().ok_or("Argument --compress must be followed by DEST=CODEC")?;
+
+              compress_routes.push(CompressionRoutes::parse_route(&raw)?);
+            }
+            "--decrypt-field" => {
+              decrypt_fields.push(src.next().ok_or("Argument --decrypt-field must be followed by a FIELD")?);
+            }
+            "--encrypt-field" => {
+              encrypt_fields.push(src.next().ok_or("Argument --encrypt-field must be followed by a FIELD")?);
+            }
+            "--field-key" => {
+              if field_key.is_some() {
+                return Err("Cannot pass argument --field-key twice!".to_string())
+              }
+
+              field_key = Some(src.next().ok_or("Argument --field-key must be followed by env:NAME or file:PATH")?);
+            }
+            "--write-decrypted" => {
+              if init.write_decrypted {
+                return Err("Cannot pass argument --write-decrypted twice!".to_string())
+              }
+
+              init.write_decrypted = true;
+            }
+            "--scan-secrets" => {
+              if init.secrets.is_some() {
+                return Err("Cannot pass argument --scan-secrets twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --scan-secrets must be followed by report, redact or fail")?;
+
+              init.secrets = Some(SecretScanner::new(SecretAction::parse(&raw)?));
+            }
+            "--escalate" => {
+              let raw = src.next().ok_or("Argument --escalate must be followed by a rule like \"FILTER => FIELD=VALUE\"")?;
+
+              init.escalations.push(Escalation::parse(&raw)?);
+            }
+            "--fingerprint" => {
+              if init.fingerprint.is_some() {
+                return Err("Cannot pass argument --fingerprint twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --fingerprint must be followed by a comma separated list of fields")?;
+
+              init.fingerprint = Some(Fingerprint::parse(&raw)?);
+            }
+            "--plugin-filter" => {
+              let command = src.next().ok_or("Argument --plugin-filter must be followed by a command")?;
+
+              init.plugins.push(Plugin { kind: PluginKind::Filter, command });
+            }
+            "--plugin-transform" => {
+              let command = src.next().ok_or("Argument --plugin-transform must be followed by a command")?;
+
+              init.plugins.push(Plugin { kind: PluginKind::Transform, command });
+            }
+            "--sessionize" => {
+              if init.sessionize.is_some() {
+                return Err("Cannot pass argument --sessionize twice!".to_string())
+              }
+
+              let usage = "Argument --sessionize must be followed by 'by FIELD gap GAP', like by user_id gap 30m";
+
+              if src.next().as_deref() != Some("by") {
+                return Err(usage.to_string());
+              }
+
+              let field = src.next().ok_or(usage)?;
+
+              if src.next().as_deref() != Some("gap") {
+                return Err(usage.to_string());
+              }
+
+              let gap = src.next().ok_or(usage)?;
+
+              init.sessionize = Some(Sessionizer::new(field, &gap)?);
+            }
+            "--flag-anomalies" => {
+              if init.anomalies.is_some() {
+                return Err("Cannot pass argument --flag-anomalies twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --flag-anomalies must be followed by 'rate', like rate,window=5m")?;
+
+              init.anomalies = Some(AnomalyDetector::parse(&raw)?);
+            }
+            "--correlate" => {
+              if init.correlate.is_some() {
+                return Err("Cannot pass argument --correlate twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --correlate must be followed by LEFT=RIGHT, like id=request_id")?;
+
+              init.correlate = Some(Correlator::parse(&raw)?);
+            }
+            "--link-by" => {
+              if init.link_by.is_some() {
+                return Err("Cannot pass argument --link-by twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --link-by must be followed by a FIELD, like request_id")?;
+
+              init.link_by = Some(Linker::parse(&raw)?);
+            }
+            "--compact" => {
+              if init.compact {
+                return Err("Cannot pass argument --compact twice!".to_string())
+              }
+
+              init.compact = true;
+            }
+            "--truncate-field" => {
+              let raw = src.next().ok_or("Argument --truncate-field must be followed by FIELD=SIZE, like stack=4kb")?;
+
+              init.truncations.push(Truncation::parse(&raw)?);
+            }
+            "--normalize-time" => {
+              if init.normalize_time.is_some() {
+                return Err("Cannot pass argument --normalize-time twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --normalize-time must be followed by epoch-millis, epoch or rfc3339")?;
+
+              init.normalize_time = Some(TimeOutput::parse(&raw)?);
+            }
+            "--percentiles" => {
+              let field = src.next().ok_or("Argument --percentiles must be followed by a FIELD")?;
+
+              init.percentiles.push(field);
+            }
+            "--command" | "--command-with-stderr" => {
+              let cmd = src.next().ok_or_else(|| format!("Argument {next} must be followed by a command"))?;
+
+              stamps_stderr |= next == "--command";
+              init.sources.append(&mut LogFile::from_command(&cmd, next == "--command-with-stderr")?);
+            }
+            "--stdin-multiplex" => {
+              if reads_stdin {
+                return Err("Cannot read stdin twice, pass only one of - and --stdin-multiplex".to_string())
+              }
+
+              reads_stdin = true;
+
+              let raw = src.next().ok_or("Argument --stdin-multiplex must be followed by comma separated TAGS")?;
+
+              init.sources.append(&mut LogFile::from_multiplexed_stdin(&raw)?);
+            }
+            "--ssh" => {
+              let raw = src.next().ok_or("Argument --ssh must be followed by [user@]host:/path/to/logs")?;
+
+              init.sources.append(&mut LogFile::from_ssh(&raw)?);
+            }
+            "--stats" => {
+              if init.stats.is_some() {
+                return Err("Cannot pass argument --stats twice!".to_string())
+              }
+
+              init.stats = Some(Stats::new(StatsMode::parse(&mut src)?));
+            }
+            "--snapshot-every" => {
+              if init.snapshot_every.is_some() {
+                return Err("Cannot pass argument --snapshot-every twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --snapshot-every must be followed by a number of events or an interval like 10s")?;
+              init.snapshot_every = Some(SnapshotEvery::parse(&raw)?);
+            }
+            "--summary-by" => {
+              if init.summary_by.is_some() {
+                return Err("Cannot pass argument --summary-by twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --summary-by must be followed by 'day' or 'hour'")?;
+
+              init.summary_by = Some(SummaryBy::parse(&raw)?);
+            }
+            "--source-stats" => {
+              if init.source_stats {
+                return Err("Cannot pass argument --source-stats twice!".to_string())
+              }
+
+              init.source_stats = true;
+            }
+            "--provenance" => {
+              if init.provenance {
+                return Err("Cannot pass argument --provenance twice!".to_string())
+              }
+
+              init.provenance = true;
+            }
+            "--deadline" => {
+              if init.deadline.is_some() {
+                return Err("Cannot pass argument --deadline twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --deadline must be followed by a DURATION like 10m")?;
+
+              init.deadline = Some(Deadline::parse_budget(&raw)?);
+            }
+            "--verify-sorted-output" => {
+              if init.verify_sorted.is_some() {
+                return Err("Cannot pass argument --verify-sorted-output twice!".to_string())
+              }
+
+              // the action is optional, so a source right after it is still a source
+              let raw = src.next_if(|next| next == "fail" || next == "warn");
+
+              init.verify_sorted = Some(raw.map_or(Ok(VerifyAction::Fail), |raw| VerifyAction::parse(&raw))?);
+            }
+            "--control-socket" => {
+              if init.control_socket.is_some() {
+                return Err("Cannot pass argument --control-socket twice!".to_string())
+              }
+
+              init.control_socket = Some(src.next().ok_or("Argument --control-socket must be followed by a PATH")?.into());
+            }
+            "--quota" => {
+              if init.quota.is_some() {
+                return Err("Cannot pass argument --quota twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --quota must be followed by a comma separated list of FILTER:N")?;
+
+              init.quota = Some(Quota::parse(&raw)?);
+            }
+            "--rate-limit" => {
+              if init.rate_limit.is_some() {
+                return Err("Cannot pass argument --rate-limit twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --rate-limit must be followed by a RATE like 1000/s")?;
+
+              init.rate_limit = Some(RateLimit::parse(&raw)?);
+            }
+            "--replay-speed" => {
+              if init.replay.is_some() {
+                return Err("Cannot pass argument --replay-speed twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --replay-speed must be followed by a SPEED like 1x or 10x")?;
+
+              init.replay = Some(Replay::parse(&raw)?);
+            }
+            "--expect-count" => {
+              if init.expect_count.is_some() {
+                return Err("Cannot pass argument --expect-count twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --expect-count must be followed by a COUNT like >0")?;
+
+              init.expect_count = Some(ExpectCount::parse(&raw)?);
+            }
+            "--audit" => {
+              if init.audit {
+                return Err("Cannot pass argument --audit twice!".to_string())
+              }
+
+              init.audit = true;
+            }
+            "--dry-run" => {
+              if init.dry_run {
+                return Err("Cannot pass argument --dry-run twice!".to_string())
+              }
+
+              init.dry_run = true;
+            }
+            "--explain-filter" => {
+              if init.explain_filter.is_some() {
+                return Err("Cannot pass argument --explain-filter twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --explain-filter must be followed by a JSON event, or - to read one from stdin")?;
+
+              init.explain_filter = Some(raw);
+            }
+            "--regex-options" => {
+              // already read before any other argument
+              src.next();
+            }
+            "--async-sources" | "--deterministic" => {
+              // already read before any other argument
+            }
+            "--codec" | "--ignore-older-than" | "--max-open-files" | "--page-cache" | "--pattern-functions" => {
+              // already read before any other argument
+              src.next();
+            }
+            "--max-cpu" => {
+              if init.max_cpu.is_some() {
+                return Err("Cannot pass argument --max-cpu twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --max-cpu must be followed by a number of cores")?;
+
+              init.max_cpu = Some(raw.parse().ok().filter(|max| *max > 0)
+                .ok_or_else(|| format!("Argument --max-cpu must be a number greater than 0, but was '{raw}'"))?);
+            }
+            "--autotune" => {
+              if init.autotune.is_some() {
+                return Err("Cannot pass argument --autotune twice!".to_string())
+              }
+
+              init.autotune = Some(Autotune::parse(&src.next().ok_or("Argument --autotune must be followed by a SIZE to sample, like 8mb")?)?);
+            }
+            "--read-retries" => {
+              if init.read_retries.is_some() {
+                return Err("Cannot pass argument --read-retries twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --read-retries must be followed by RETRIES, like 5 or 5,backoff=1s")?;
+              let policy = RetryPolicy::parse(&raw)?;
+
+              retry::set_policy(policy);
+              init.read_retries = Some(policy);
+            }
+            "--catalog" => {
+              if catalog.is_some() {
+                return Err("Cannot pass argument --catalog twice!".to_string())
+              }
+
+              catalog = Some(src.next().ok_or("Argument --catalog must be followed by a CATALOG written by saw merge-manifests")?.into());
+            }
+            "--key-order" => {
+              if init.key_order.is_some() {
+                return Err("Cannot pass argument --key-order twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --key-order must be followed by a comma separated list of keys")?;
+
+              init.key_order = Some(KeyOrder::parse(&raw)?);
+            }
+            "--lines" => {
+              let raw = src.next().ok_or("Argument --lines must be followed by a range like 1000..2000")?;
+
+              init.lines = Span::parse(&raw)?;
+            }
+            "--bytes" => {
+              let raw = src.next().ok_or("Argument --bytes must be followed by a range like 1000..2000")?;
+
+              init.bytes = Span::parse(&raw)?;
+            }
+            "--passthrough-invalid" => {
+              if init.passthrough_invalid {
+                return Err("Cannot pass argument --passthrough-invalid twice!".to_string())
+              }
+
+              init.passthrough_invalid = true;
+            }
+            "--lenient-json" => {
+              if init.lenient_json {
+                return Err("Cannot pass argument --lenient-json twice!".to_string())
+              }
+
+              init.lenient_json = true;
+            }
+            "--resync" => {
+              if init.resync {
+                return Err("Cannot pass argument --resync twice!".to_string())
+              }
+
+              init.resync = true;
+            }
+            "--debug-raw" => {
+              if init.debug_raw {
+                return Err("Cannot pass argument --debug-raw twice!".to_string())
+              }
+
+              init.debug_raw = true;
+            }
+            "--copy-raw" => {
+              let raw = src.next().ok_or("Argument --copy-raw must be followed by SOURCE=PATH, like 'app.log=raw.log'")?;
+
+              init.raw_copies.push(RawCopy::parse(&raw)?);
+            }
+            "--keep-order-of-input" => {
+              if init.keep_order {
+                return Err("Cannot pass argument --keep-order-of-input twice!".to_string())
+              }
+
+              init.keep_order = true;
+            }
+            "--follow" => {
+              if init.follow {
+                return Err("Cannot pass argument --follow twice!".to_string())
+              }
+
+              init.follow = true;
+            }
+            "--poll-interval" => {
+              if init.poll_interval.is_some() {
+                return Err("Cannot pass argument --poll-interval twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --poll-interval must be followed by a DURATION like 500ms")?;
+
+              init.poll_interval = Some(follow::parse_poll(&raw)?);
+            }
+            "--reload" => {
+              if init.reload.is_some() {
+                return Err("Cannot pass argument --reload twice!".to_string())
+              }
+
+              init.reload = Some(src.next().ok_or("Argument --reload must be followed by a FILE")?.into());
+            }
+            "--drop-older-than" => {
+              if init.drop_older_than.is_some() {
+                return Err("Cannot pass argument --drop-older-than twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --drop-older-than must be followed by a DURATION like 10m")?;
+
+              init.drop_older_than = Some(Watermark::parse(&raw)?);
+            }
+            "--tail" => {
+              if init.tail.is_some() {
+                return Err("Cannot pass argument --tail twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --tail must be followed by a number of events")?;
+
+              init.tail = Some(raw.parse().ok().filter(|count| *count > 0)
+                .ok_or_else(|| format!("Argument --tail must be followed by a number greater than 0, but was '{raw}'"))?);
+            }
+            "--reverse" => {
+              if init.reverse {
+                return Err("Cannot pass argument --reverse twice!".to_string())
+              }
+
+              init.reverse = true;
+            }
+            "--sort-by" => {
+              if init.sort_by.is_some() {
+                return Err("Cannot pass argument --sort-by twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --sort-by must be followed by comma separated FIELDS")?;
+
+              // read by every source for as long as saw runs
+              init.sort_by = Some(Box::leak(Box::new(SortKey::parse(&raw)?)));
+            }
+            "--external-sort" => {
+              if init.external_sort.is_some() {
+                return Err("Cannot pass argument --external-sort twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --external-sort must be followed by a DIR to write sorted runs to")?;
+
+              init.external_sort = Some(ExternalSort::parse(&raw)?);
+            }
+            "--input" => {
+              if init.input.is_some() {
+                return Err("Cannot pass argument --input twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --input must be followed by json, logfmt or regex:PATTERN")?;
+
+              init.input = Some(Box::leak(Box::new(InputFormat::parse(&raw)?)));
+            }
+            "--time-field" => {
+              if time_field.is_some() {
+                return Err("Cannot pass argument --time-field twice!".to_string())
+              }
+
+              time_field = Some(src.next().ok_or("Argument --time-field must be followed by a FIELD")?);
+            }
+            "--time-format" => {
+              time_formats.push(src.next().ok_or("Argument --time-format must be followed by auto, epoch, epochMillis or a pattern")?);
+            }
+            "--time-locale" => {
+              if time_locale.is_some() {
+                return Err("Cannot pass argument --time-locale twice!".to_string())
+              }
+
+              time_locale = Some(src.next().ok_or("Argument --time-locale must be followed by a LOCALE, or several separated by commas like en,de")?);
+            }
+            "--level-map" => {
+              if has_level_map {
+                return Err("Cannot pass argument --level-map twice!".to_string())
+              }
+
+              has_level_map = true;
+
+              let raw = src.next().ok_or("Argument --level-map must be followed by NUMBER=NAME pairs like 30=INFO,35=NOTICE, or none")?;
+
+              // used by every filter and source for as long as saw runs
+              init.levels = LevelMap::parse(&raw)?.map(|levels| &*Box::leak(Box::new(levels)));
+              levels::use_names(init.levels);
+            }
+            "--rewrite-levels" => {
+              if init.rewrite_levels {
+                return Err("Cannot pass argument --rewrite-levels twice!".to_string())
+              }
+
+              init.rewrite_levels = true;
+            }
+            "--parse-only" => {
+              if projection.is_some() {
+                return Err("Cannot pass argument --parse-only twice!".to_string())
+              }
+
+              let raw = src.next().ok_or("Argument --parse-only must be followed by a comma separated list of fields, like time,level,message")?;
+
+              projection = Some(Projection::parse(&raw)?);
+            }
+            "--translate" => {
+              let output = src.next().ok_or("Argument --translate must be followed by a TARGET_FIELD and then a PATTERN argument")?;
+              let pattern = src.next().ok_or("Argument --translate TARGET_FIELD must be followed by a PATTERN argument")?;
+
+              let translation = Translation::parse(output, &pattern)?;
+
+              init.translations.push(translation);
+            }
+            "--translate-if" => {
+              let filter = src.next().ok_or("Argument --translate-if must be followed by a FILTER, a TARGET_FIELD and then a PATTERN argument")?;
+              let output = src.next().ok_or("Argument --translate-if FILTER must be followed by a TARGET_FIELD and then a PATTERN argument")?;
+              let pattern = src.next().ok_or("Argument --translate-if FILTER TARGET_FIELD must be followed by a PATTERN argument")?;
+
+              let translation = Translation::parse_if(&filter, output, &pattern)?;
+
+              init.translations.push(translation);
+            }
+            _ => {
+              return Err(format!("Property '{next}' is listed but not handled, this is a bug in saw"));
+            }
+          }
+        }
+
+        // must be a source
+        let unopened = init.async_sources || init.max_open_files.is_some();
+        init.sources.append(&mut Arguments::read_path(&next, unopened, init.ignore_older_than.as_mut())?);
+        init.globs.push(next);
+
+        Ok(())
+      });
+    }
+
+    // the chunks are only known once --range is
+    if let Some(catalog) = catalog {
+      Arguments::collect(&mut errors, || {
+        for path in Catalog::load(&catalog)?.sources(init.range) {
+          if init.ignore_older_than.as_mut().is_some_and(|age| !age.keep(&path)) {
+            continue;
+          }
+
+          let unopened = init.async_sources || init.max_open_files.is_some();
+          init.sources.push(if unopened { LogFile::unopened(&path) } else { LogFile::from_file(&path) });
+        }
+
+        Ok(())
+      });
+    }
+
+    // a few remaining defaults and sanity checks
+
+    if !ignore_packs.is_empty() {
+      Arguments::collect(&mut errors, || {
+        init.filter.get_or_insert_with(FilterSet::default).ignore = Some(IgnoreRules::load(&ignore_packs)?);
+
+        Ok(())
+      });
+    }
+
+    if let Some(filter) = &mut init.filter {
+      filter.optimize();
+    }
+
+    if let Some(why) = init.why {
+      let drops_any = init.range != (None, None) || init.trace.is_some() || init.shard.is_some() || init.tenant.is_some() || init.quota.is_some()
+        || init.drop_older_than.is_some();
+
+      if init.filter.is_none() && !(why.drops() && drops_any) {
+        errors.push("Option --why explains --filter, and --why drops also --range, --trace, --shard, --tenant, --quota and --drop-older-than, so it needs one of them".to_string());
+      }
+    }
+
+    if !decrypt_fields.is_empty() || !encrypt_fields.is_empty() {
+      Arguments::collect(&mut errors, || {
+        init.crypto = Some(FieldCrypto::new(field_key.as_deref(), decrypt_fields, encrypt_fields)?);
+
+        Ok(())
+      });
+    } else if field_key.is_some() {
+      errors.push("Option --field-key is only valid with --decrypt-field or --encrypt-field!".to_string());
+    }
+
+    // only what was on the command line is named in errors, not the pretty output other options turn on
+    let pretty_given = init.pretty.is_some() || default_pretty;
+    let pretty_per_given = init.theme.is_some();
+    let json_given = has_json && json;
+
+    // a 'default' template stands in for --pretty, and any template at all means the output is pretty
+    if let Some(theme) = &mut init.theme {
+      if let Some(default) = theme.take_default() {
+        if init.pretty.is_some() {
+          errors.push("Cannot pass both --pretty PATTERN and a 'default' template for --pretty-per, as both set the pattern for everything else".to_string());
+        }
+
+        init.pretty = Some(default);
+      } else if init.pretty.is_none() {
+        default_pretty = true;
+      }
+    }
+
+    if init.table.is_some() {
+      if init.pretty.is_some() || default_pretty {
+        errors.push("Cannot pass both --table and --pretty or --pretty-per, as both decide how each event is printed".to_string());
+      }
+
+      if has_json && json {
+        errors.push("Cannot pass both --table and --json true at the same time as these options conflict".to_string());
+      }
+
+      // the table is written in place of json or pretty, so no default pattern should be loaded below
+      has_json = true;
+      json = true;
+    }
+
+    if let Some(stats) = &mut init.stats {
+      if init.pretty.is_some() || default_pretty || init.table.is_some() || init.html.is_some() || init.indented || init.frames.is_some() || init.group.is_some() {
+        errors.push("Cannot pass --stats with --pretty, --pretty-per, --table, --group-by or --output-format, as events aren't printed with --stats".to_string());
+      }
+
+      if init.provenance || init.key_order.is_some() || init.chunked.is_some() || init.split_by_time.is_some() {
+        errors.push("Cannot pass --stats with --provenance, --key-order, --chunked or --split-by-time, as events aren't written with --stats".to_string());
+      }
+
+      stats.json = has_json && json;
+
+      // the counts are written in place of the events, so no default pattern should be loaded below
+      has_json = true;
+      json = true;
+    }
+
+    if init.snapshot_every.is_some() && init.stats.is_none() && init.group.is_none() {
+      errors.push("Option --snapshot-every only applies to --stats and --group-by".to_string());
+    }
+
+    if init.html.is_some() {
+      if init.pretty.is_some() || default_pretty || init.table.is_some() || init.group.is_some() {
+        errors.push("Cannot pass --output-format html with --pretty, --pretty-per, --table or --group-by, as they all decide how each event is printed".to_string());
+      }
+
+      if has_json && json {
+        errors.push("Cannot pass both --output-format html and --json true at the same time as these options conflict".to_string());
+      }
+
+      if init.provenance || init.key_order.is_some() {
+        errors.push("Options --provenance and --key-order only apply to JSON output and can't be used with --output-format html".to_string());
+      }
+
+      // the report is written in place of json or pretty, so no default pattern should be loaded below
+      has_json = true;
+      json = true;
+    }
+
+    if init.indented {
+      if init.pretty.is_some() || default_pretty || init.table.is_some() || init.group.is_some() {
+        errors.push("Cannot pass --output-format indented with --pretty, --pretty-per, --table or --group-by, as they all decide how each event is printed".to_string());
+      }
+
+      if has_json && json {
+        errors.push("Cannot pass both --output-format indented and --json true, which writes each event on one line".to_string());
+      }
+
+      if init.provenance || init.key_order.is_some() {
+        errors.push("Options --provenance and --key-order only apply to JSON written one event per line and can't be used with --output-format indented".to_string());
+      }
+
+      // the events are written in place of json or pretty, so no default pattern should be loaded below
+      has_json = true;
+      json = true;
+    }
+
+    if let Some(framing) = init.frames {
+      if init.pretty.is_some() || default_pretty || init.table.is_some() || init.group.is_some() {
+        errors.push("Cannot pass --output-format frames with --pretty, --pretty-per, --table or --group-by, as they all decide how each event is printed".to_string());
+      }
+
+      if has_json && json {
+        errors.push("Cannot pass both --output-format frames and --json true, which writes each event on one line".to_string());
+      }
+
+      // each of these writes lines, or changes bytes after the length of their frame is written
+      if init.provenance || init.key_order.is_some() || init.escape_json.is_some() || init.clipboard {
+        errors.push(format!("Options --provenance, --key-order, --escape-json-strings and --output clipboard can't be used with --output-format {}", framing.describe()));
+      }
+
+      // the events are written in place of json or pretty, so no default pattern should be loaded below
+      has_json = true;
+      json = true;
+    }
+
+    // groups are drawn as text, so they need a pretty pattern even when writing to a file
+    if init.group.is_some() {
+      if init.table.is_some() {
+        errors.push("Cannot pass both --group-by and --table, as both decide how each event is printed".to_string());
+      }
+
+      if json_given {
+        errors.push("Option --group-by needs pretty output, but --json true was given".to_string());
+      }
+
+      if init.pretty.is_none() {
+        default_pretty = true;
+      }
+    }
+
+    if default_pretty {
+      Arguments::collect(&mut errors, || {
+        init.pretty = Some(Arguments::load_default_pattern(init.preset)?);
+
+        Ok(())
+      });
+      init.adaptive_pretty = Arguments::adapts_pattern(init.preset);
+    }
+
+    if init.collapse_repeats {
+      if init.pretty.is_none() || init.table.is_some() || init.html.is_some() || init.stats.is_some() {
+        errors.push("Option --collapse-repeats only applies to pretty printed events, and can't be used with --json, --table, --stats or --output-format".to_string());
+      }
+
+      if init.group.is_some() {
+        errors.push("Cannot pass --collapse-repeats with --group-by, as a group already prints its events together".to_string());
+      }
+    }
+
+    if init.chunked.is_some() && init.clipboard {
+      errors.push("Option --chunked cannot be used with --output clipboard!".to_string());
+    }
+
+    if init.skip_unchanged && init.chunked.is_none() {
+      errors.push("Option --skip-unchanged is only valid with --chunked!".to_string());
+    }
+
+    let is_partial = init.lines.start.is_some() || init.lines.end.is_some() || init.bytes.start.is_some() || init.bytes.end.is_some();
+
+    if init.input.is_some_and(InputFormat::is_document) && (init.follow || is_partial) {
+      errors.push("Cannot pass --input loki with --follow, --lines or --bytes, as an export is read whole rather than by line".to_string());
+    }
+
+    if matches!(init.input, Some(InputFormat::Frames(_))) && (init.follow || is_partial) {
+      errors.push("Cannot pass --input frames with --follow, --lines or --bytes, as frames are read whole rather than by line".to_string());
+    }
+
+    // times in text logs are rarely ISO8601 exactly, so any other input reads them the same way --time-format auto does
+    if init.input.is_some() || time_field.is_some() || !time_formats.is_empty() || time_locale.is_some() {
+      Arguments::collect(&mut errors, || {
+        init.time = Some(Box::leak(Box::new(TimeParser::new(time_field.clone(), time_formats, time_locale)?)));
+
+        Ok(())
+      });
+    }
+
+    if has_on_exists && init.output.is_none() {
+      errors.push("Option --on-exists is only valid when option --output is specified!".to_string());
+    }
+
+    if has_on_exists && init.skip_unchanged {
+      errors.push("Cannot pass both --on-exists and --skip-unchanged, which decides for itself which chunks to replace".to_string());
+    }
+
+    if init.checksum.is_some() && init.output.is_none() {
+      errors.push("Option --checksum is only valid when option --output is specified!".to_string());
+    }
+
+    // chunked requires output
+    if init.chunked.is_some() && init.output.is_none() {
+      errors.push("Option --chunked is only valid when option --output is specified!".to_string());
+    }
+
+    if let Some(upload) = upload_chunk {
+      init.chunk_workers.get_or_insert_with(ChunkWorkers::default).upload = Some(upload);
+    }
+
+    if init.chunk_workers.is_some() {
+      if init.chunked.is_none() {
+        errors.push("Options --chunk-workers and --upload-chunk are only valid with --chunked!".to_string());
+      }
+
+      if init.skip_unchanged {
+        errors.push("Cannot pass --chunk-workers or --upload-chunk with --skip-unchanged, which compares chunks as they're written".to_string());
+      }
+    }
+
+    if !init.routes.is_empty() {
+      // these write one whole document, or look at lines after they're written, which can't be spread over files
+      if init.table.is_some() || init.html.is_some() || init.group.is_some() || init.provenance || init.stats.is_some() || init.collapse_repeats {
+        errors.push("Cannot pass --route with --table, --group-by, --provenance, --stats, --collapse-repeats or --output-format html".to_string());
+      }
+
+      if init.clipboard {
+        errors.push("Option --route cannot be used with --output clipboard!".to_string());
+      }
+
+      for (index, route) in init.routes.iter().enumerate() {
+        if init.routes[..index].iter().any(|other| other.path == route.path) || init.output.as_ref() == Some(&route.path) {
+          errors.push(format!("Route '{route}' writes to '{}', which something else already writes to", route.path.display()));
+        }
+      }
+    }
+
+    if init.split_by_time.is_some() {
+      if init.output.is_none() {
+        errors.push("Option --split-by-time is only valid when option --output is specified as the directory to split into!".to_string());
+      }
+
+      if init.chunked.is_some() {
+        errors.push("Cannot pass both --split-by-time and --chunked at the same time as these options conflict".to_string());
+      }
+
+      // these write one whole document, which can't be spread over several files
+      if init.table.is_some() || init.html.is_some() || init.group.is_some() || init.provenance {
+        errors.push("Cannot pass --split-by-time with --table, --group-by, --provenance or --output-format html".to_string());
+      }
+    }
+
+    if has_json {
+      // if you passed the json flag
+
+      if json {
+        // if you passed true
+
+        if pretty_given || pretty_per_given {
+          // and pretty is on
+          let given = if pretty_given { "--pretty" } else { "--pretty-per" };
+
+          errors.push(format!("Cannot pass both {given} and --json true at the same time as these options conflict"));
+        }
+      } else {
+        // if you specified json false, we need to default pretty if you did not
+        if init.pretty.is_none() {
+          Arguments::collect(&mut errors, || {
+            init.pretty = Some(Arguments::load_default_pattern(init.preset)?);
+
+            Ok(())
+          });
+          init.adaptive_pretty = Arguments::adapts_pattern(init.preset);
+        }
+      }
+    } else {
+      // if you did not specify json
+
+      if init.output.is_none() {
+        // if you did not provide output
+
+        // if you did not specify pretty, default it on
+        if init.pretty.is_none() {
+          Arguments::collect(&mut errors, || {
+            init.pretty = Some(Arguments::load_default_pattern(init.preset)?);
+
+            Ok(())
+          });
+          init.adaptive_pretty = Arguments::adapts_pattern(init.preset);
+        }
+      }
+    }
+
+    if let Some(mut projection) = projection {
+      // the fields events are merged by have to be read, whatever else is left out, and so do the ones filters,
+      // translations and patterns look at, or they would never find anything
+      let merged = time_field.into_iter().chain(init.sort_by.iter().flat_map(|sort_by| sort_by.fields()).cloned());
+      let filtered = init.filter.iter().flat_map(FilterSet::keys).map(str::to_string);
+      let translated = init.translations.iter().flat_map(Translation::variables);
+      let printed = init.pretty.iter().flat_map(PrettyDescriptor::variables).chain(init.theme.iter().flat_map(PrettyTheme::variables));
+
+      for field in merged.chain(filtered).chain(translated).chain(printed) {
+        projection.include(&field);
+      }
+
+      // read by every source for as long as saw runs
+      init.projection = Some(Box::leak(Box::new(projection)));
+    }
+
+    // if you did not specify zip
+    if !has_zip {
+      // set zip on if pretty it off
+      zip = init.pretty.is_none() && init.table.is_none() && init.html.is_none() && !init.indented && init.stats.is_none()
+    }
+
+    init.compression = CompressionRoutes::new(compress_routes, if zip { Codec::Gzip } else { Codec::Plain });
+
+    // chunked output picks its own extensions, but a single output file and route files are named by the user
+    let single = init.output.iter().filter(|_| init.chunked.is_none() && init.split_by_time.is_none());
+
+    for output in single.chain(init.routes.iter().map(|route| &route.path)) {
+      let codec = init.compression.codec_for(output.to_str().unwrap_or(""));
+      let extension = output.extension().and_then(|ext| ext.to_str());
+
+      match (codec.extension(), extension) {
+        (Some(expected), Some(actual)) if expected == actual => {}
+        (Some(expected), _) => {
+          eprintln!("Warning: output '{}' will be compressed with {codec:?} but does not end in '.{expected}'", output.display());
+        }
+        (None, Some(actual @ ("gz" | "zst"))) => {
+          eprintln!("Warning: output '{}' ends in '.{actual}' but will not be compressed. Pass --zip true or --compress for compressed output", output.display());
+        }
+        (None, _) => {}
+      }
+    }
+
+    if init.crypto.as_ref().is_some_and(FieldCrypto::writes_plaintext) && !init.write_decrypted {
+      let displayed = (init.pretty.is_some() || init.table.is_some())
+        && init.output.is_none()
+        && init.chunked.is_none()
+        && init.split_by_time.is_none()
+        && init.routes.is_empty();
+
+      if !displayed {
+        errors.push("Option --decrypt-field only decrypts for pretty or table output to the terminal, as otherwise the plaintext would be written out. Pass --write-decrypted to write it anyway".to_string());
+      }
+    }
+
+    if init.write_decrypted && !init.crypto.as_ref().is_some_and(FieldCrypto::writes_plaintext) {
+      errors.push("Option --write-decrypted is only valid with --decrypt-field!".to_string());
+    }
+
+    if init.key_order.is_some() && init.pretty.is_some() {
+      if pretty_given || pretty_per_given {
+        let given = if pretty_given { "--pretty" } else { "--pretty-per" };
+
+        errors.push(format!("Option --key-order only applies to JSON output and can't be used with {given}"));
+      } else {
+        errors.push("Option --key-order only applies to JSON output, but the output is pretty. Pass --json true or --output".to_string());
+      }
+    }
+
+    if init.escape_json.is_some() {
+      let json_output = init.pretty.is_none() && init.table.is_none() && init.html.is_none() && init.group.is_none()
+        && init.stats.as_ref().is_none_or(|stats| stats.json);
+
+      if !json_output {
+        errors.push("Option --escape-json-strings only applies to JSON output, like --json true or --output FILE, and can't be used with --pretty, --table, --group-by, --output-format html or --stats as a table".to_string());
+      }
+    }
+
+    // if you set daily but didn't provide a range
+    if init.daily && init.range == (None, None) {
+      errors.push("Cannot pass the --daily flag without a range! Add a range or remove --daily".to_string());
+    }
+
+    if init.sort_by.is_some() && init.keep_order {
+      errors.push("Cannot pass both --sort-by and --keep-order-of-input, as both decide the order events are written in".to_string());
+    }
+
+    // daily looks at the time of the first line, which might not exist when order is kept
+    if init.daily && init.keep_order {
+      errors.push("Cannot pass the --daily flag with --keep-order-of-input!".to_string());
+    }
+
+    if init.daily && init.tenant.is_some() {
+      errors.push("Cannot pass the --daily flag with --tenant, as days are read on their own threads and tenants seen on one couldn't be redacted from another".to_string());
+    }
+
+    if init.daily && init.sort_by.is_some() {
+      errors.push("Cannot pass the --daily flag with --sort-by!".to_string());
+    }
+
+    if init.external_sort.is_some() && (init.sort_by.is_some() || init.keep_order) {
+      errors.push("Cannot pass --external-sort with --sort-by or --keep-order-of-input, as it sorts events by time".to_string());
+    }
+
+    if init.external_sort.is_some() && (init.follow || init.daily) {
+      errors.push("Cannot pass --external-sort with --follow or --daily, as nothing is written until every event has been read and sorted".to_string());
+    }
+
+    if init.external_sort.is_some() && (init.reverse || init.tail.is_some()) {
+      errors.push("Cannot pass --external-sort with --reverse or --tail, as sources out of order can't be read from the end".to_string());
+    }
+
+    if has_timezone && !init.daily && init.split_by_time.is_none() && init.summary_by.is_none() {
+      errors.push("Option --timezone only decides where days and hours start for --daily, --split-by-time and --summary-by, so pass one of them too".to_string());
+    }
+
+    if init.follow && (init.daily || init.keep_order) {
+      errors.push("Cannot pass --follow with --daily or --keep-order-of-input, as followed files never end".to_string());
+    }
+
+    if init.lenient_json && matches!(init.input, Some(InputFormat::Logfmt | InputFormat::Regex(_))) {
+      errors.push("Option --lenient-json only applies to JSON, and can't be used with --input logfmt or regex".to_string());
+    }
+
+    if init.resync && init.input.is_some_and(|input| !matches!(input, InputFormat::Json)) {
+      errors.push("Option --resync only applies to JSON lines, and can't be used with any other --input".to_string());
+    }
+
+    if init.reload.is_some() && !init.follow {
+      errors.push("Option --reload is only valid with --follow!".to_string());
+    }
+
+    if init.poll_interval.is_some() && !init.follow {
+      errors.push("Option --poll-interval is only valid with --follow!".to_string());
+    }
+
+    if init.drop_older_than.is_some() && !init.follow {
+      errors.push("Option --drop-older-than is only valid with --follow!".to_string());
+    }
+
+    if init.reload.is_some() && init.audit {
+      errors.push("Cannot pass --reload with --audit, as the translations it counts can be replaced".to_string());
+    }
+
+    if init.sessionize.is_some() && init.reverse {
+      errors.push("Cannot pass --sessionize with --reverse, as sessions are found going forward in time".to_string());
+    }
+
+    if init.anomalies.is_some() && init.reverse {
+      errors.push("Cannot pass --flag-anomalies with --reverse, as the usual rate is learned going forward in time".to_string());
+    }
+
+    if init.correlate.is_some() && init.reverse {
+      errors.push("Cannot pass --correlate with --reverse, as events wait for their partner going forward in time".to_string());
+    }
+
+    if init.link_by.is_some() && (init.follow || init.replay.is_some()) {
+      errors.push("Cannot pass --link-by with --follow or --replay-speed, as each event is held back until the ones after it are read".to_string());
+    }
+
+    if init.explain_filter.is_some() && (init.dry_run || init.follow) {
+      errors.push("Cannot pass --explain-filter with --dry-run or --follow, as it checks one event without reading the sources".to_string());
+    }
+
+    if init.follow && init.sources.iter().any(LogFile::is_remote) {
+      errors.push("Cannot pass --follow with --ssh, as remote files are read once to their end".to_string());
+    }
+
+    if init.follow && init.async_sources {
+      errors.push("Cannot pass --follow with --async-sources, which reads each file whole before merging".to_string());
+    }
+
+    if init.deterministic {
+      if init.follow || init.deadline.is_some() || init.ignore_older_than.is_some() || init.rate_limit.as_ref().is_some_and(RateLimit::drops) {
+        errors.push("Cannot pass --deterministic with --follow, --deadline, --ignore-older-than or --rate-limit with drop, as what they write depends on when saw runs".to_string());
+      }
+
+      if init.crypto.as_ref().is_some_and(FieldCrypto::encrypts) {
+        errors.push("Cannot pass --encrypt-field with --deterministic, as each value is encrypted with a random nonce".to_string());
+      }
+
+      if init.time.is_some_and(TimeParser::guesses_year) {
+        errors.push("Cannot pass a --time-format without %Y or %y with --deterministic, as the year would be the one saw runs in".to_string());
+      }
+
+      if matches!(init.snapshot_every, Some(SnapshotEvery::Interval(_))) {
+        errors.push("Option --snapshot-every can only be a number of events with --deterministic, not an interval".to_string());
+      }
+
+      if stamps_stderr {
+        errors.push("Cannot pass --command with --deterministic, as its stderr is read with the time each line was written. Use --command-with-stderr".to_string());
+      }
+
+      // ties between sources go to the first, so they're put in an order that doesn't depend on how they were given
+      if !init.keep_order {
+        init.sources.sort_by(|left, right| left.name().cmp(right.name()));
+      }
+    }
+
+    if init.max_open_files.is_some() && (init.follow || init.reverse || init.tail.is_some() || init.async_sources) {
+      errors.push("Cannot pass --max-open-files with --follow, --reverse, --tail or --async-sources, which all need every file open from the start".to_string());
+    }
+
+    if init.follow && init.html.is_some() {
+      errors.push("Cannot pass --follow with --output-format html, as the report is only finished once saw is".to_string());
+    }
+
+    if init.tail.is_some() || init.reverse {
+      if init.follow || init.daily || init.keep_order {
+        errors.push("Cannot pass --tail or --reverse with --follow, --daily or --keep-order-of-input".to_string());
+      }
+
+      // both count lines and bytes from the start of each source
+      if init.lines.start.is_some() || init.lines.end.is_some() || init.bytes.start.is_some() || init.bytes.end.is_some() {
+        errors.push("Cannot pass --tail or --reverse with --lines or --bytes".to_string());
+      }
+    }
+
+    // both expect time to only go forwards
+    if init.reverse && (init.deadline.is_some() || init.replay.is_some()) {
+      errors.push("Cannot pass --reverse with --deadline or --replay-speed".to_string());
+    }
+
+    // without time order there is nothing to verify
+    if init.verify_sorted.is_some() && (init.keep_order || init.sort_by.is_some()) {
+      errors.push("Cannot pass --verify-sorted-output with --keep-order-of-input or --sort-by, as events aren't written in time order".to_string());
+    }
+
+    match errors.len() {
+      0 => {}
+      1 => {
+        eprintln!("{}", errors[0]);
+        exit(2);
+      }
+      count => {
+        eprintln!("Found {count} problems with the arguments:\n  {}", errors.join("\n  "));
+        exit(2);
+      }
+    }
+
+    init
+  }
+
+  /**
+   * Read part of the arguments, keeping its error as one of the errors instead of stopping there
+   */
+  fn collect<F: FnOnce() -> Result<(), String>>(errors: &mut Vec<String>, part: F) {
+    if let Err(err) = part() {
+      errors.push(err);
+    }
+  }
+
+  /**
+   * The man page, from the same flags and topics as --help
+   */
+  pub fn manual() -> String {
+    let commands = [
+      ("compact", compaction::COMPACT_HELP),
+      ("index", index::INDEX_HELP),
+      ("merge-manifests", catalog::CATALOG_HELP),
+      ("render", render::RENDER_HELP),
+      ("run", pipeline::PIPELINE_HELP),
+      ("verify", integrity::VERIFY_HELP),
+    ];
+
+    manual::man_page(HELP_NOTES, &TOPICS, &commands)
+  }
+
+  fn help() -> String {
+    let topics: String = TOPICS.iter()
+      .map(|(name, summary, _)| format!("  {name:<9} {summary}\n"))
+      .collect();
+
+    format!("\nsaw SOURCE_FILES\n{}{HELP_NOTES}help TOPIC values are:\n{topics}", flags::usage())
+  }
+
+  fn read_path(raw: &str, unopened: bool, mut age: Option<&mut AgeCutoff>) -> Result<Vec<LogFile>, String> {
+    let paths = glob(raw)
+      .map_err(|_| format!("Source '{raw}' is not valid or directory could not be read"))?
+      .map(|p| p.map_err(|_| format!("Source '{raw}' is not valid or could not be read")))
+      .collect::<Result<Vec<_>, String>>()?;
+
+    let sources = paths.into_iter()
+      .filter(|path| age.as_mut().is_none_or(|age| age.keep(path)))
+      .map(|path| if unopened { LogFile::unopened(&path) } else { LogFile::from_file(&path) })
+      .collect();
+
+    Ok(sources)
+  }
+
+  /**
+   * Whether the default pattern is made from the fields of the first events, only when neither a preset nor SAW_PATTERN gives one
+   */
+  fn adapts_pattern(preset: Option<&'static Preset>) -> bool {
+    preset.is_none() && env::var("SAW_PATTERN").is_err()
+  }
+
+  /**
+   * Use the preset's pattern if there is one, otherwise either load up the default from an environment variable or take the default provided
+   */
+  fn load_default_pattern(preset: Option<&'static Preset>) -> Result<PrettyDescriptor, String> {
+    if let Some(preset) = preset {
+      return patterns::pretty(preset.pattern);
+    }
+
+    env::var("SAW_PATTERN")
+      .map(| it | patterns::pretty(&it))
+      .unwrap_or_else(|_| patterns::pretty(DEFAULT_PRETTY))
   }
 }
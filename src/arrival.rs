@@ -0,0 +1,156 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread::spawn;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use datetime::{ISO, LocalDateTime};
+use serde_json::{Map, Value};
+
+/// how much of a pipe is read at once by Drained
+const CHUNK: usize = 64 * 1024;
+
+/**
+ * The stderr of a --command, where each line becomes an event at the time it was written, so failures are merged
+ * with whatever the command logged around them. Plain text is put in 'message', and a JSON object is kept as it is,
+ * only given a time if it doesn't have one.
+ *
+ * Lines are read on their own thread as soon as they're written, since the merge only reads a source when it needs
+ * its next event and by then the time would be when saw got to it rather than when the command wrote it. They're held
+ * for as long as it takes the merge to get to them, so the command is never stopped waiting on saw. When none are
+ * waiting a read fails with WouldBlock rather than waiting for one, since any line written later will be later than
+ * now, so the merge can go on with the other sources.
+ */
+pub struct ArrivalReader {
+  lines: Receiver<Vec<u8>>,
+  current: Vec<u8>,
+  position: usize,
+}
+
+impl ArrivalReader {
+  pub fn new<Pipe: 'static + Read + Send>(stderr: Pipe) -> ArrivalReader {
+    let (sender, receiver) = channel();
+
+    spawn(move || {
+      let mut stderr = BufReader::new(stderr);
+      let mut raw = Vec::new();
+
+      loop {
+        raw.clear();
+
+        match stderr.read_until(b'\n', &mut raw) {
+          Ok(0) | Err(_) => return,
+          Ok(_) => if sender.send(stamp(&raw)).is_err() {
+            return;
+          }
+        }
+      }
+    });
+
+    ArrivalReader { lines: receiver, current: Vec::new(), position: 0 }
+  }
+}
+
+impl Read for ArrivalReader {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.position >= self.current.len() {
+      match self.lines.try_recv() {
+        Ok(next) => {
+          self.current = next;
+          self.position = 0;
+        }
+        Err(TryRecvError::Empty) => return Err(ErrorKind::WouldBlock.into()),
+        Err(TryRecvError::Disconnected) => return Ok(0),
+      }
+    }
+
+    let count = buf.len().min(self.current.len() - self.position);
+    buf[..count].copy_from_slice(&self.current[self.position..self.position + count]);
+    self.position += count;
+
+    Ok(count)
+  }
+}
+
+/**
+ * A pipe read on its own thread as fast as it's written, for --command-with-stderr, where the merge reads stdout and
+ * stderr as two sources. Whichever one the merge isn't reading is held in memory until it is, so the command never
+ * stops on a full pipe while saw waits for the other one
+ */
+pub struct Drained {
+  chunks: Receiver<Vec<u8>>,
+  current: Vec<u8>,
+  position: usize,
+}
+
+impl Drained {
+  pub fn new<Pipe: 'static + Read + Send>(mut pipe: Pipe) -> Drained {
+    let (sender, receiver) = channel();
+
+    spawn(move || {
+      let mut chunk = vec![0u8; CHUNK];
+
+      loop {
+        match pipe.read(&mut chunk) {
+          Ok(0) => return,
+          Ok(read) => if sender.send(chunk[..read].to_vec()).is_err() {
+            return;
+          }
+          Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+          Err(_) => return,
+        }
+      }
+    });
+
+    Drained { chunks: receiver, current: Vec::new(), position: 0 }
+  }
+}
+
+impl Read for Drained {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.position >= self.current.len() {
+      match self.chunks.recv() {
+        Ok(next) => {
+          self.current = next;
+          self.position = 0;
+        }
+        Err(_) => return Ok(0),
+      }
+    }
+
+    let count = buf.len().min(self.current.len() - self.position);
+    buf[..count].copy_from_slice(&self.current[self.position..self.position + count]);
+    self.position += count;
+
+    Ok(count)
+  }
+}
+
+/// RAW as a line of JSON with the time it arrived
+fn stamp(raw: &[u8]) -> Vec<u8> {
+  let line = String::from_utf8_lossy(raw);
+  let line = line.trim_end_matches(['\n', '\r']);
+
+  let now = now();
+
+  let mut event = match serde_json::from_str(line) {
+    Ok(Value::Object(event)) => event,
+    _ => {
+      let mut event = Map::new();
+      event.insert("message".to_string(), Value::String(line.to_string()));
+      event
+    }
+  };
+
+  event.entry("time").or_insert_with(|| Value::String(format!("{}Z", now.iso())));
+
+  let mut stamped = serde_json::to_vec(&event).expect("Failed to write stderr event");
+  stamped.push(b'\n');
+  stamped
+}
+
+/// the time a line arriving now is stamped with
+pub fn now() -> LocalDateTime {
+  let millis = SystemTime::now().duration_since(UNIX_EPOCH).expect("System clock is before 1970").as_millis() as i64;
+
+  LocalDateTime::at_ms(millis.div_euclid(1000), millis.rem_euclid(1000) as i16)
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::translate::Translation;
+
+/**
+ * How often a single translation ran and what it did. Shared with the translation itself,
+ * which may be running on another thread with --daily
+ */
+#[derive(Debug, Default)]
+pub struct RuleCounts {
+  /// events the translation ran on, after any --translate-if condition
+  applied: AtomicU64,
+  /// events where the field ended up with a different value than it started with
+  changed: AtomicU64,
+  /// events where a field that existed was removed by a blank result
+  removed: AtomicU64,
+}
+
+impl RuleCounts {
+  pub fn record(&self, changed: bool, removed: bool) {
+    self.applied.fetch_add(1, Ordering::Relaxed);
+
+    if changed {
+      self.changed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if removed {
+      self.removed.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+}
+
+/**
+ * The record of which translations touched which fields, printed to stderr once done so that
+ * redactions can be checked after the fact
+ */
+#[derive(Debug)]
+pub struct Audit {
+  rules: Vec<(String, Arc<RuleCounts>)>,
+}
+
+impl Audit {
+  pub fn new(translations: &[Translation]) -> Audit {
+    Audit {
+      rules: translations.iter().map(|trans| (trans.to_string(), trans.counts())).collect(),
+    }
+  }
+
+  pub fn print(&self) {
+    if self.rules.is_empty() {
+      eprintln!("audit: no translations were given");
+    }
+
+    for (rule, counts) in &self.rules {
+      eprintln!(
+        "audit: {rule}: applied={} changed={} removed={}",
+        counts.applied.load(Ordering::Relaxed),
+        counts.changed.load(Ordering::Relaxed),
+        counts.removed.load(Ordering::Relaxed),
+      );
+    }
+  }
+}
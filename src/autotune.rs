@@ -0,0 +1,259 @@
+use std::fs::{self, File};
+use std::io::{BufRead, Read, Seek};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, Value};
+
+use crate::args::Arguments;
+use crate::chunk::BYTE_SUFFIXES;
+use crate::codec::{self, SourceCodec};
+use crate::limits;
+use crate::parallel::LINE_BUFFER;
+
+/// the bounds of the read buffer picked, the smallest for plain files and for compressed ones
+const MIN_READ_BUFFER: usize = 16 * 1024;
+const MIN_COMPRESSED_READ_BUFFER: usize = 64 * 1024;
+const MAX_READ_BUFFER: usize = 1024 * 1024;
+
+/// lines each read should hold, so a buffer isn't refilled for every few lines
+const LINES_PER_READ: usize = 128;
+
+/// how much work a --daily thread should have before another one is worth starting
+const WORK_PER_THREAD: Duration = Duration::from_millis(250);
+
+/// how much of what they've finished --daily threads hold, in bytes of events
+const WORKER_BYTES: usize = 4 * 1024 * 1024;
+
+/**
+ * Measures the first SIZE of the input before the full run, from --autotune SIZE, and picks the read buffer, the
+ * order filters are checked in, and with --daily how many threads to read on and how far each can get ahead. Only
+ * local files are sampled, read on their own so nothing is taken from the run, and what was picked is printed on
+ * stderr.
+ */
+#[derive(Debug)]
+pub struct Autotune {
+  raw: String,
+  sample: u64,
+}
+
+/**
+ * What was read from the start of the sources
+ */
+#[derive(Default)]
+struct Sample {
+  events: Vec<Map<String, Value>>,
+  files: usize,
+  /// how much of the files was read, as stored and once decompressed
+  stored: u64,
+  bytes: u64,
+  lines: u64,
+  compressed: bool,
+  reading: Duration,
+  parsing: Duration,
+}
+
+/**
+ * Counts the bytes read from under a decompressor, to tell how much the files shrink by
+ */
+struct Counted {
+  src: File,
+  count: Arc<AtomicU64>,
+}
+
+impl Read for Counted {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let read = self.src.read(buf)?;
+    self.count.fetch_add(read as u64, Ordering::Relaxed);
+    Ok(read)
+  }
+}
+
+impl Autotune {
+  pub fn parse(raw: &str) -> Result<Autotune, String> {
+    let lower = raw.trim().to_lowercase();
+    let split = lower.find(|c: char| !c.is_ascii_digit()).unwrap_or(lower.len());
+    let (number, suffix) = lower.split_at(split);
+
+    let multiplier = BYTE_SUFFIXES.iter()
+      .find(|(key, _)| *key == suffix)
+      .map(|(_, multiplier)| *multiplier as u64)
+      .ok_or_else(|| format!("Argument --autotune '{raw}' must be a size ending in b, kb, mb or gb, like 8mb"))?;
+
+    let sample = number.parse::<u64>().ok()
+      .and_then(|number| number.checked_mul(multiplier))
+      .filter(|sample| *sample > 0)
+      .ok_or_else(|| format!("Argument --autotune '{raw}' must be a size greater than 0, like 8mb"))?;
+
+    Ok(Autotune { raw: raw.trim().to_string(), sample })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("measuring the first {} of the local files before picking buffers, threads and the order of filters", self.raw)
+  }
+
+  /**
+   * Sample the sources of ARGS and tune the run to them. Returns how many lines each --daily thread can get ahead
+   */
+  pub fn tune(&self, args: &mut Arguments) -> usize {
+    let start = Instant::now();
+    let sample = self.read_sample(args);
+
+    if sample.lines == 0 {
+      eprintln!("Autotune: no local files to sample, running with the defaults");
+      return LINE_BUFFER;
+    }
+
+    let lines = sample.lines as f64;
+    let average = (sample.bytes as f64 / lines).ceil().max(1.0) as usize;
+
+    let mut report = vec![format!(
+      "sampled {} events, {} from {} files, in {}ms: {:.0}ns to read and {:.0}ns to parse each",
+      sample.lines,
+      describe_bytes(sample.bytes),
+      sample.files,
+      start.elapsed().as_millis(),
+      sample.reading.as_nanos() as f64 / lines,
+      sample.parsing.as_nanos() as f64 / lines,
+    )];
+
+    // big enough for a good run of lines each read, and for a decompressor to work on whole blocks
+    let least = if sample.compressed { MIN_COMPRESSED_READ_BUFFER } else { MIN_READ_BUFFER };
+    let read_buffer = (average * LINES_PER_READ).next_power_of_two().clamp(least, MAX_READ_BUFFER);
+
+    codec::set_read_buffer(read_buffer);
+    report.push(format!("read buffer {} for events of {average} bytes on average", describe_bytes(read_buffer as u64)));
+
+    // the files were opened while the arguments were read, so they're opened again to be read with the new buffer
+    for source in args.sources.iter_mut().filter(|source| source.path().is_some() && !source.is_unopened()) {
+      source.open();
+    }
+
+    let mut filtering = 0.0;
+
+    if let Some(filter) = args.filter.as_mut() {
+      let measured = filter.tune(&sample.events);
+
+      // each filter is only checked on what the ones before it kept
+      let mut reaching = 1.0;
+
+      for found in &measured {
+        report.push(format!("filter {}: {:.1}% kept, {:.0}ns each", found.filter, found.kept * 100.0, found.nanos));
+
+        filtering += reaching * found.nanos;
+        reaching *= found.kept;
+      }
+    }
+
+    let mut line_buffer = LINE_BUFFER;
+
+    if args.daily {
+      let stored: u64 = args.sources.iter()
+        .filter_map(|source| source.path())
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+
+      // the whole input goes by how much the sample shrank and what its events cost
+      let ratio = sample.bytes as f64 / sample.stored.max(1) as f64;
+      let per_event = (sample.reading + sample.parsing).as_nanos() as f64 / lines + filtering;
+      let work = stored as f64 * ratio / average as f64 * per_event;
+
+      let most = limits::threads(args.max_cpu);
+      let threads = ((work / WORK_PER_THREAD.as_nanos() as f64).ceil() as usize).clamp(1, most);
+
+      args.max_cpu = Some(threads);
+      line_buffer = (WORKER_BYTES / average).clamp(256, 65536);
+
+      report.push(format!(
+        "{threads} of {most} threads for about {:.1}s of work in {}, each up to {line_buffer} lines ahead",
+        work / 1e9,
+        describe_bytes(stored),
+      ));
+    }
+
+    eprintln!("Autotune: {}", report.join("\n  "));
+
+    line_buffer
+  }
+
+  /**
+   * Read events from the local files in the order given, until SIZE of them has been read once decompressed
+   */
+  fn read_sample(&self, args: &Arguments) -> Sample {
+    let mut sample = Sample::default();
+
+    for path in args.sources.iter().filter_map(|source| source.path()) {
+      if sample.bytes >= self.sample {
+        break;
+      }
+
+      let Ok(mut file) = File::open(path) else {
+        continue;
+      };
+
+      let codec = sample_codec(path, &mut file);
+      let count = Arc::new(AtomicU64::new(0));
+      let mut src = codec.decode(Counted { src: file, count: count.clone() });
+
+      sample.files += 1;
+      sample.compressed |= codec != SourceCodec::Plain;
+
+      let mut raw = vec![];
+
+      while sample.bytes < self.sample {
+        raw.clear();
+
+        let start = Instant::now();
+        let read = src.read_until(b'\n', &mut raw).unwrap_or(0);
+        sample.reading += start.elapsed();
+
+        if read == 0 {
+          break;
+        }
+
+        sample.bytes += read as u64;
+        sample.lines += 1;
+
+        let start = Instant::now();
+        let parsed = serde_json::from_slice::<Map<String, Value>>(&raw);
+        sample.parsing += start.elapsed();
+
+        if let Ok(event) = parsed {
+          sample.events.push(event);
+        }
+      }
+
+      sample.stored += count.load(Ordering::Relaxed);
+    }
+
+    sample
+  }
+}
+
+/**
+ * How a sampled file is read, from --codec or its first two bytes like any source, but without warning about its
+ * name again
+ */
+fn sample_codec(path: &Path, file: &mut File) -> SourceCodec {
+  if let Some(codec) = codec::forced_source_codec(path) {
+    return codec;
+  }
+
+  let mut start = [0u8; 2];
+  let read = file.read(&mut start).unwrap_or(0);
+  let _ = file.rewind();
+
+  if start[..read] == [31, 139] { SourceCodec::Gzip } else { SourceCodec::Plain }
+}
+
+fn describe_bytes(bytes: u64) -> String {
+  match bytes {
+    bytes if bytes >= 1024 * 1024 * 1024 => format!("{:.1}gb", bytes as f64 / (1024.0 * 1024.0 * 1024.0)),
+    bytes if bytes >= 1024 * 1024 => format!("{:.1}mb", bytes as f64 / (1024.0 * 1024.0)),
+    bytes if bytes >= 1024 => format!("{:.1}kb", bytes as f64 / 1024.0),
+    bytes => format!("{bytes}b"),
+  }
+}
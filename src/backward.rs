@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+
+/// how much of the file is read at a time, walking back from the end
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/**
+ * Reads the lines of a plain file from the last to the first, a block at a time from the end, so only as much of the
+ * file is read as there are lines wanted. Memory stays at about one block, plus the longest line.
+ */
+pub struct BackwardReader {
+  file: File,
+  /// everything before this offset is still to be read
+  position: u64,
+  /// read from the file but not yet returned, always starting at position
+  pending: Vec<u8>,
+}
+
+impl BackwardReader {
+  pub fn new(mut file: File) -> io::Result<BackwardReader> {
+    let position = file.seek(SeekFrom::End(0))?;
+
+    Ok(BackwardReader { file, position, pending: vec![] })
+  }
+
+  /**
+   * Read the line before the last one returned into raw, with its newline. Returns how many bytes it was and the
+   * offset it started at, or 0 bytes once the start of the file is reached
+   */
+  pub fn read_line(&mut self, raw: &mut String) -> io::Result<(usize, u64)> {
+    loop {
+      // the newline at the very end belongs to the last line, it doesn't start a new one
+      let search_end = self.pending.len() - usize::from(self.pending.last() == Some(&b'\n'));
+
+      let start = match self.pending[..search_end].iter().rposition(|byte| *byte == b'\n') {
+        Some(newline) => newline + 1,
+        None if self.position == 0 => 0,
+        None => {
+          self.read_block()?;
+          continue;
+        }
+      };
+
+      let line = self.pending.split_off(start);
+      let offset = self.position + start as u64;
+
+      if line.is_empty() {
+        return Ok((0, offset));
+      }
+
+      let text = String::from_utf8(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+      raw.push_str(&text);
+
+      return Ok((text.len(), offset));
+    }
+  }
+
+  fn read_block(&mut self) -> io::Result<()> {
+    let size = BLOCK_SIZE.min(self.position);
+    self.position -= size;
+
+    let mut block = vec![0u8; size as usize];
+    self.file.seek(SeekFrom::Start(self.position))?;
+    self.file.read_exact(&mut block)?;
+
+    block.extend_from_slice(&mem::take(&mut self.pending));
+    self.pending = block;
+
+    Ok(())
+  }
+}
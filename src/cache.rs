@@ -0,0 +1,127 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::retry;
+
+/// files at least this big are dropped from the page cache as they're read with --page-cache auto
+const LARGE_FILE: u64 = 1 << 30;
+
+/// how much is read between asking for what's been read to be dropped, so it's done in big cheap steps
+const DROP_EVERY: u64 = 64 << 20;
+
+static ADVICE: RwLock<CacheAdvice> = RwLock::new(CacheAdvice::Auto);
+
+/**
+ * What the kernel is told about the page cache of the files read, from --page-cache. Every file is read from start to
+ * end, so it's always told they're read sequentially and can read ahead further. Dropping what's been read keeps a
+ * batch job over hundreds of GB from pushing everything else running on the host out of the cache
+ */
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CacheAdvice {
+  /// drop what's been read only for files of a GB or more
+  Auto,
+  /// leave what's been read cached, for files that will be read again soon
+  Keep,
+  /// drop what's been read from every file
+  Drop,
+}
+
+impl CacheAdvice {
+  pub fn parse(raw: &str) -> Result<CacheAdvice, String> {
+    match raw {
+      "auto" => Ok(CacheAdvice::Auto),
+      "keep" => Ok(CacheAdvice::Keep),
+      "drop" => Ok(CacheAdvice::Drop),
+      _ => Err(format!("Argument --page-cache '{raw}' is not recognized. Use auto, keep or drop")),
+    }
+  }
+
+  pub fn describe(&self) -> &'static str {
+    match self {
+      CacheAdvice::Auto => "dropped after reading files of 1GB or more",
+      CacheAdvice::Keep => "kept after reading",
+      CacheAdvice::Drop => "dropped after reading every file",
+    }
+  }
+}
+
+/**
+ * Set the advice for every file opened after this, including those --follow finds later. Must be called before any
+ * source is opened
+ */
+pub fn set_advice(advice: CacheAdvice) {
+  *ADVICE.write().expect("Page cache advice was poisoned") = advice;
+}
+
+/**
+ * A local file read from start to end, which drops what it has read from the page cache as it goes if it should, and
+ * retries reads that fail for a moment
+ */
+pub struct AdvisedFile {
+  file: File,
+  /// to open the file again if a read fails
+  path: PathBuf,
+  id: Option<(u64, u64)>,
+  drops: bool,
+  read: u64,
+  /// everything before this has already been dropped
+  dropped: u64,
+}
+
+impl AdvisedFile {
+  pub fn new(file: File, path: &Path) -> AdvisedFile {
+    let advice = *ADVICE.read().expect("Page cache advice was poisoned");
+    let size = file.metadata().map_or(0, |metadata| metadata.len());
+
+    let drops = match advice {
+      CacheAdvice::Auto => size >= LARGE_FILE,
+      CacheAdvice::Keep => false,
+      CacheAdvice::Drop => true,
+    };
+
+    advise_sequential(&file);
+
+    let id = retry::identity(&file);
+
+    AdvisedFile { file, path: path.to_path_buf(), id, drops, read: 0, dropped: 0 }
+  }
+}
+
+impl Read for AdvisedFile {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read = retry::read(&mut self.file, &self.path, self.id, self.read, buf)?;
+    self.read += read as u64;
+
+    // at the end everything left is dropped, since there won't be another read to do it
+    if self.drops && (self.read - self.dropped >= DROP_EVERY || (read == 0 && self.read > self.dropped)) {
+      advise_dont_need(&self.file, self.dropped, self.read - self.dropped);
+      self.dropped = self.read;
+    }
+
+    Ok(read)
+  }
+}
+
+// the advice is only a hint, so where there's no posix_fadvise or the kernel won't take it, files are read the same
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn advise_sequential(file: &File) {
+  use std::os::unix::io::AsRawFd;
+
+  unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL) };
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn advise_dont_need(file: &File, offset: u64, len: u64) {
+  use std::os::unix::io::AsRawFd;
+
+  unsafe { libc::posix_fadvise(file.as_raw_fd(), offset as libc::off_t, len as libc::off_t, libc::POSIX_FADV_DONTNEED) };
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn advise_sequential(_file: &File) {}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn advise_dont_need(_file: &File, _offset: u64, _len: u64) {}
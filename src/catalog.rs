@@ -0,0 +1,350 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use datetime::{ISO, LocalDateTime};
+use glob::glob;
+use serde_json::{json, Map, Value};
+
+use crate::chunk::{ChunkInfo, ChunkUnit};
+
+const CATALOG_VERSION: u64 = 1;
+
+/// chunks this small or smaller, next to each other in one manifest, are suggested for compacting by default
+const DEFAULT_SMALL: u64 = 1024 * 1024;
+
+pub const CATALOG_HELP: &str = r#"
+Usage:
+  saw merge-manifests --output CATALOG [--small SIZE] MANIFESTS
+
+Merges the OUTPUT.manifest files written by --chunked with --skip-unchanged into one CATALOG of every chunk in an
+archive, with the first and last time, size and checksum of each. An existing CATALOG is updated rather than replaced:
+the chunks of a manifest given again take the place of the ones it had before, and chunks whose file is gone are left
+out. Run it after each run that writes chunks to keep the catalog up to date.
+
+Later runs given --catalog CATALOG read only the chunks that can have events in --range, without opening the rest:
+  saw merge-manifests -o archive/app.catalog 'archive/*.manifest'
+  saw --catalog archive/app.catalog --range 2024-06-01T00:00:00 2024-06-02T00:00:00 -f %level=ERROR
+
+Chunks from different manifests whose times overlap are listed, since merging them means reading both. Two or more
+chunks of one manifest next to each other that are each SIZE or smaller, 1mb by default, are suggested for compacting
+into one. Chunks written before manifests kept their times are always read, and listed so they can be written again.
+"#;
+
+/**
+ * One chunk in a catalog
+ */
+#[derive(Debug)]
+struct Entry {
+  /// relative to the catalog's own directory, unless it's outside of it
+  path: PathBuf,
+  /// the manifest that listed it, the same way
+  manifest: PathBuf,
+  first: Option<LocalDateTime>,
+  last: Option<LocalDateTime>,
+  /// of the lines in it, before compression
+  bytes: u64,
+  sha256: Option<String>,
+}
+
+/**
+ * Every chunk of an archive from many runs' manifests, oldest first, from saw merge-manifests
+ */
+#[derive(Debug)]
+pub struct Catalog {
+  dir: PathBuf,
+  chunks: Vec<Entry>,
+}
+
+impl Catalog {
+  /**
+   * Load a catalog written by saw merge-manifests
+   */
+  pub fn load(path: &Path) -> Result<Catalog, String> {
+    let raw = fs::read_to_string(path).map_err(|_| format!("Failed to read catalog '{}'", path.display()))?;
+
+    Catalog::parse(path, &raw).ok_or_else(|| format!("Catalog '{}' is not valid, write it again with saw merge-manifests", path.display()))
+  }
+
+  fn empty(path: &Path) -> Catalog {
+    Catalog { dir: Catalog::dir_of(path), chunks: vec![] }
+  }
+
+  fn dir_of(path: &Path) -> PathBuf {
+    path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf()
+  }
+
+  fn parse(path: &Path, raw: &str) -> Option<Catalog> {
+    let catalog: Value = serde_json::from_str(raw).ok()?;
+
+    if catalog.get("version")?.as_u64()? != CATALOG_VERSION {
+      return None;
+    }
+
+    let chunks = catalog.get("chunks")?.as_array()?.iter()
+      .map(|chunk| {
+        let time = |key: &str| chunk.get(key)?.as_str().and_then(|time| LocalDateTime::from_str(time).ok());
+
+        Some(Entry {
+          path: chunk.get("path")?.as_str()?.into(),
+          manifest: chunk.get("manifest")?.as_str()?.into(),
+          first: time("first"),
+          last: time("last"),
+          bytes: chunk.get("bytes")?.as_u64()?,
+          sha256: chunk.get("sha256").and_then(Value::as_str).map(str::to_string),
+        })
+      })
+      .collect::<Option<Vec<Entry>>>()?;
+
+    Some(Catalog { dir: Catalog::dir_of(path), chunks })
+  }
+
+  /**
+   * Every chunk that could have an event in the range, oldest first. Chunks without times are always included, unless
+   * they're empty
+   */
+  pub fn sources(&self, range: (Option<LocalDateTime>, Option<LocalDateTime>)) -> Vec<PathBuf> {
+    let (min, max) = range;
+
+    self.chunks.iter()
+      .filter(|chunk| match (chunk.first, chunk.last) {
+        (Some(first), Some(last)) => min.is_none_or(|min| last >= min) && max.is_none_or(|max| first < max),
+        _ => chunk.bytes > 0,
+      })
+      .map(|chunk| self.dir.join(&chunk.path))
+      .collect()
+  }
+
+  /// a path as the catalog keeps it, relative to its directory when it's inside it
+  fn relative(&self, path: &Path) -> PathBuf {
+    let absolute = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let full = absolute(path);
+    let dir = absolute(&self.dir);
+
+    full.strip_prefix(&dir).map(Path::to_path_buf).unwrap_or(full)
+  }
+
+  /**
+   * Replace every chunk from this manifest with what it lists now. Returns how many chunks it lists
+   */
+  fn merge(&mut self, manifest: &Path) -> usize {
+    let raw = fs::read_to_string(manifest).unwrap_or_else(|_| panic!("Failed to read manifest '{}'", manifest.display()));
+
+    let Ok(Value::Object(listed)) = serde_json::from_str::<Value>(&raw) else {
+      panic!("Manifest '{}' is not valid", manifest.display());
+    };
+
+    let key = self.relative(manifest);
+    let dir = Catalog::dir_of(manifest);
+
+    self.chunks.retain(|chunk| chunk.manifest != key);
+
+    for (name, chunk) in &listed {
+      let time = |key: &str| chunk.get(key)?.as_str().and_then(|time| LocalDateTime::from_str(time).ok());
+
+      self.chunks.push(Entry {
+        path: self.relative(&dir.join(name)),
+        manifest: key.clone(),
+        first: time("first"),
+        last: time("last"),
+        bytes: chunk.get("bytes").and_then(Value::as_u64).unwrap_or(0),
+        sha256: chunk.get("sha256").and_then(Value::as_str).map(str::to_string),
+      });
+    }
+
+    listed.len()
+  }
+
+  /**
+   * Leave out chunks whose file is gone, and put the rest in order. Returns how many were left out
+   */
+  fn tidy(&mut self) -> usize {
+    let before = self.chunks.len();
+    let dir = self.dir.clone();
+
+    self.chunks.retain(|chunk| dir.join(&chunk.path).exists());
+
+    // chunks without times last, so the ones that can be skipped read in order
+    self.chunks.sort_by(|a, b| (a.first.is_none(), a.first, &a.path).cmp(&(b.first.is_none(), b.first, &b.path)));
+
+    before - self.chunks.len()
+  }
+
+  fn write(&self, path: &Path) {
+    let time = |time: Option<LocalDateTime>| time.map(|time| time.iso().to_string());
+
+    let chunks: Vec<Value> = self.chunks.iter()
+      .map(|chunk| {
+        let mut entry = Map::new();
+
+        entry.insert("path".to_string(), json!(chunk.path.to_string_lossy()));
+        entry.insert("manifest".to_string(), json!(chunk.manifest.to_string_lossy()));
+        entry.insert("first".to_string(), json!(time(chunk.first)));
+        entry.insert("last".to_string(), json!(time(chunk.last)));
+        entry.insert("bytes".to_string(), json!(chunk.bytes));
+
+        if let Some(sha256) = &chunk.sha256 {
+          entry.insert("sha256".to_string(), json!(sha256));
+        }
+
+        Value::Object(entry)
+      })
+      .collect();
+
+    let catalog = json!({ "version": CATALOG_VERSION, "chunks": chunks });
+
+    fs::write(path, catalog.to_string() + "\n").unwrap_or_else(|_| panic!("Failed to write catalog '{}'", path.display()));
+  }
+
+  /**
+   * Pairs of chunks from different manifests whose times overlap
+   */
+  fn overlaps(&self) -> Vec<(&Entry, &Entry)> {
+    let timed: Vec<(&Entry, LocalDateTime, LocalDateTime)> = self.chunks.iter()
+      .filter_map(|chunk| Some((chunk, chunk.first?, chunk.last?)))
+      .collect();
+
+    let mut found = vec![];
+
+    // sorted by first, so only the chunks starting before this one ends can overlap it
+    for (index, (chunk, _, last)) in timed.iter().enumerate() {
+      for (other, _, _) in timed[index + 1..].iter().take_while(|(_, other_first, _)| other_first < last) {
+        if chunk.manifest != other.manifest {
+          found.push((*chunk, *other));
+        }
+      }
+    }
+
+    found
+  }
+
+  /**
+   * Runs of two or more small chunks next to each other in the same manifest
+   */
+  fn small_runs(&self, small: u64) -> Vec<Vec<&Entry>> {
+    let mut manifests: Vec<&PathBuf> = self.chunks.iter().map(|chunk| &chunk.manifest).collect();
+    manifests.sort();
+    manifests.dedup();
+
+    let mut runs = vec![];
+
+    for manifest in manifests {
+      let mut run: Vec<&Entry> = vec![];
+
+      for chunk in self.chunks.iter().filter(|chunk| &chunk.manifest == manifest && chunk.first.is_some()) {
+        if chunk.bytes <= small {
+          run.push(chunk);
+        } else if !run.is_empty() {
+          runs.push(std::mem::take(&mut run));
+        }
+      }
+
+      runs.push(run);
+    }
+
+    runs.retain(|run| run.len() > 1);
+    runs
+  }
+}
+
+fn span(chunk: &Entry) -> String {
+  let time = |time: Option<LocalDateTime>| time.map_or("-".to_string(), |time| time.iso().to_string());
+
+  format!("{} to {}", time(chunk.first), time(chunk.last))
+}
+
+/**
+ * saw merge-manifests --output CATALOG [--small SIZE] MANIFESTS
+ */
+pub fn run<Args: Iterator<Item=String>>(mut src: Args) {
+  let mut output: Option<PathBuf> = None;
+  let mut small = DEFAULT_SMALL;
+  let mut manifests: Vec<PathBuf> = vec![];
+
+  while let Some(next) = src.next() {
+    match next.as_ref() {
+      "-h" | "--help" => {
+        eprintln!("{CATALOG_HELP}");
+        return;
+      }
+      "-o" | "--output" => {
+        output = Some(src.next().expect("Argument --output must be followed by a CATALOG path").into());
+      }
+      "--small" => {
+        let raw = src.next().expect("Argument --small must be followed by a SIZE like 1mb");
+
+        small = ChunkInfo::parse(&raw).unwrap_or_else(|err| panic!("{err}")).limits.iter()
+          .find(|limit| limit.unit == ChunkUnit::Bytes)
+          .map(|limit| limit.value as u64)
+          .unwrap_or_else(|| panic!("Argument --small '{raw}' must be a size in bytes, like 512kb or 1mb"));
+      }
+      _ if next.starts_with('-') => {
+        panic!("Unknown property '{next}' for saw merge-manifests. Run saw merge-manifests --help to see all known properties");
+      }
+      _ => {
+        let paths = glob(&next).unwrap_or_else(|_| panic!("Manifest '{next}' is not valid or directory could not be read"));
+
+        for path in paths {
+          manifests.push(path.unwrap_or_else(|_| panic!("Manifest '{next}' is not valid or could not be read")));
+        }
+      }
+    }
+  }
+
+  let output = output.expect("saw merge-manifests needs --output CATALOG. Run saw merge-manifests --help for more information");
+
+  if manifests.is_empty() {
+    panic!("saw merge-manifests needs at least one manifest. Run saw merge-manifests --help for more information");
+  }
+
+  let mut catalog = if output.exists() { Catalog::load(&output).unwrap_or_else(|err| panic!("{err}")) } else { Catalog::empty(&output) };
+
+  let listed: usize = manifests.iter().map(|manifest| catalog.merge(manifest)).sum();
+  let gone = catalog.tidy();
+
+  catalog.write(&output);
+
+  eprintln!(
+    "Cataloged {} chunks in '{}', {listed} from {} manifests, left out {gone} whose file is gone",
+    catalog.chunks.len(),
+    output.display(),
+    manifests.len(),
+  );
+
+  // the last chunk of a run is often empty, which has no times but nothing to read either
+  let untimed: Vec<&Entry> = catalog.chunks.iter().filter(|chunk| chunk.first.is_none() && chunk.bytes > 0).collect();
+
+  if !untimed.is_empty() {
+    eprintln!("{} chunks have no times and are always read, write them again to catalog their times:", untimed.len());
+
+    for chunk in untimed {
+      eprintln!("  {}", chunk.path.display());
+    }
+  }
+
+  let overlaps = catalog.overlaps();
+
+  if !overlaps.is_empty() {
+    eprintln!("{} pairs of chunks from different manifests overlap:", overlaps.len());
+
+    for (a, b) in overlaps {
+      eprintln!("  {} ({}) and {} ({})", a.path.display(), span(a), b.path.display(), span(b));
+    }
+  }
+
+  for run in catalog.small_runs(small) {
+    let bytes: u64 = run.iter().map(|chunk| chunk.bytes).sum();
+    let paths: Vec<String> = run.iter().map(|chunk| chunk.path.display().to_string()).collect();
+
+    eprintln!(
+      "Suggest compacting {} chunks of {} bytes or less from '{}', {bytes} bytes together, from {} to {}:\n  {}",
+      run.len(),
+      small,
+      run[0].manifest.display(),
+      run[0].first.map_or("-".to_string(), |time| time.iso().to_string()),
+      run[run.len() - 1].last.map_or("-".to_string(), |time| time.iso().to_string()),
+      paths.join(" "),
+    );
+  }
+}
@@ -0,0 +1,129 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+/**
+ * Which checksum --checksum writes next to each output file. Its name is the sidecar's extension, like out.log.sha256
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Checksum {
+  Sha256,
+}
+
+impl Checksum {
+  pub fn parse(raw: &str) -> Result<Checksum, String> {
+    match raw.trim().to_lowercase().as_str() {
+      "sha256" => Ok(Checksum::Sha256),
+      _ => Err(format!("Argument --checksum '{raw}' is not recognized. Valid options are sha256")),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Checksum::Sha256 => "sha256",
+    }
+  }
+
+  /// where the checksum of a file is written, PATH.sha256
+  pub fn sidecar(&self, path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".");
+    sidecar.push(self.name());
+
+    PathBuf::from(sidecar)
+  }
+
+  /**
+   * Hash a whole file as it is on disk
+   */
+  pub fn hash_file(&self, path: &Path) -> String {
+    let mut file = File::open(path).unwrap_or_else(|_| panic!("Failed to open file '{}' to checksum it", path.display()));
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut file, &mut hasher).unwrap_or_else(|_| panic!("Failed to read file '{}' to checksum it", path.display()));
+
+    format!("{:x}", hasher.finalize())
+  }
+
+  /**
+   * Write the checksum of a file next to it, as sha256sum writes it, so `sha256sum -c PATH.sha256` checks it
+   */
+  pub fn write(&self, path: &Path, digest: &str) {
+    let sidecar = self.sidecar(path);
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+
+    fs::write(&sidecar, format!("{digest}  {name}\n"))
+      .unwrap_or_else(|_| panic!("Failed to write checksum '{}'", sidecar.display()));
+  }
+
+  /**
+   * Read and remove the checksum written next to a file, for a file that is about to be moved or thrown away
+   */
+  pub fn take(&self, path: &Path) -> Option<String> {
+    let sidecar = self.sidecar(path);
+    let raw = fs::read_to_string(&sidecar).ok()?;
+
+    fs::remove_file(&sidecar).unwrap_or_else(|_| panic!("Failed to remove '{}'", sidecar.display()));
+
+    raw.split_whitespace().next().map(str::to_string)
+  }
+}
+
+/**
+ * A file that output is written to. With --checksum, everything written to it is hashed on the way, and the checksum
+ * is written next to it once it's closed. A file that already held something, like one appended to, is hashed again
+ * from the start instead, since the checksum must cover all of it
+ */
+pub struct OutputFile {
+  file: File,
+  path: PathBuf,
+  checksum: Option<(Checksum, Sha256)>,
+  appended: bool,
+}
+
+impl OutputFile {
+  pub fn new(file: File, path: &Path, checksum: Option<Checksum>) -> OutputFile {
+    let appended = checksum.is_some() && file.metadata().is_ok_and(|metadata| metadata.len() > 0);
+
+    OutputFile {
+      file,
+      path: path.to_path_buf(),
+      checksum: checksum.map(|checksum| (checksum, Sha256::new())),
+      appended,
+    }
+  }
+}
+
+impl Write for OutputFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let written = self.file.write(buf)?;
+
+    if let Some((_, hasher)) = &mut self.checksum {
+      hasher.update(&buf[..written]);
+    }
+
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+impl Drop for OutputFile {
+  fn drop(&mut self) {
+    let Some((checksum, hasher)) = self.checksum.take() else {
+      return;
+    };
+
+    let digest = if self.appended {
+      checksum.hash_file(&self.path)
+    } else {
+      format!("{:x}", hasher.finalize())
+    };
+
+    checksum.write(&self.path, &digest);
+  }
+}
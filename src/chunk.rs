@@ -1,23 +1,40 @@
-use std::fs::File;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use flate2::Compression;
+use datetime::LocalDateTime;
 use flate2::write::GzEncoder;
-
+use serde_json::{Map, Value};
+
+use crate::checksum::{Checksum, OutputFile};
+use crate::codec::{Codec, CompressionRoutes};
+use crate::dedup::ChunkDedup;
+use crate::exists::OnExists;
+use crate::fingerprint::{fnv_extend, FNV_OFFSET};
+use crate::upload::ChunkQueue;
+
+/**
+ * One or more limits, a new chunk is started as soon as any one of them is reached
+ */
 #[derive(Debug)]
 pub struct ChunkInfo {
+  pub limits: Vec<ChunkLimit>,
+}
+
+#[derive(Debug)]
+pub struct ChunkLimit {
   pub value: usize,
   pub unit: ChunkUnit,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ChunkUnit {
   Bytes,
   Lines,
 }
 
-const BYTE_SUFFIXES: [(&str, usize); 4] = [
+pub const BYTE_SUFFIXES: [(&str, usize); 4] = [
   ("b", 1),
   ("kb", 1024),
   ("mb", 1024 * 1024),
@@ -27,54 +44,98 @@ const BYTE_SUFFIXES: [(&str, usize); 4] = [
 const LINE_SUFFIX: &str = "ln";
 
 impl ChunkInfo {
-  pub fn parse(raw: &str) -> ChunkInfo {
-    let mut src = raw.chars().peekable();
-    let mut number = String::new();
+  pub fn parse(raw: &str) -> Result<ChunkInfo, String> {
+    let limits: Vec<ChunkLimit> = raw.split(',').map(ChunkLimit::parse).collect::<Result<_, _>>()?;
+
+    let lines = limits.iter().filter(|limit| limit.unit == ChunkUnit::Lines).count();
+    let bytes = limits.iter().filter(|limit| limit.unit == ChunkUnit::Bytes).count();
 
-    while let Some('0'..='9') = src.peek() {
-      number.push(src.next().unwrap());
+    if lines > 1 || bytes > 1 {
+      return Err(format!("Invalid chunk pattern {raw}, at most one line limit and one byte limit can be given"));
     }
 
-    let mut suffix = String::new();
+    Ok(ChunkInfo { limits })
+  }
 
-    while let Some('a'..='z') = src.peek() {
-      suffix.push(src.next().unwrap());
-    }
+  fn is_full(&self, lines: usize, bytes: usize) -> bool {
+    self.limits.iter().any(|limit| {
+      match limit.unit {
+        ChunkUnit::Lines => lines >= limit.value,
+        ChunkUnit::Bytes => bytes >= limit.value,
+      }
+    })
+  }
+}
 
-    if let Some(_) = src.next() {
-      panic!("Invalid chunk pattern {raw}");
+impl fmt::Display for ChunkLimit {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.unit {
+      ChunkUnit::Lines => write!(f, "{} lines", self.value),
+      ChunkUnit::Bytes => write!(f, "{} bytes", self.value),
     }
+  }
+}
+
+impl ChunkLimit {
+  /**
+   * Parse a number and a unit like 20kb, 1.5gb or 1000ln. Space between them, quotes around the whole thing and the
+   * case of the unit are all ignored, so "10 MB" works too
+   */
+  fn parse(raw: &str) -> Result<ChunkLimit, String> {
+    let trimmed = raw.trim().trim_matches(|c| c == '"' || c == '\'').trim();
+
+    let split = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(split);
+    let suffix = suffix.trim().to_lowercase();
 
-    let raw_value: usize = number
+    let raw_value: f64 = number
       .parse()
-      .expect(&format!("Chunk number {number} is not a valid number"));
+      .ok()
+      .filter(|value: &f64| value.is_finite())
+      .ok_or_else(|| format!("Chunk number '{number}' in '{raw}' is not a valid number"))?;
+
+    if raw_value <= 0.0 {
+      return Err(format!("Chunk limit '{raw}' must be greater than 0"));
+    }
 
     if suffix == LINE_SUFFIX {
-      return ChunkInfo {
-        value: raw_value,
+      if raw_value.fract() != 0.0 {
+        return Err(format!("Chunk limit '{raw}' must be a whole number of lines"));
+      }
+
+      return Ok(ChunkLimit {
+        value: raw_value as usize,
         unit: ChunkUnit::Lines,
-      };
+      });
     }
 
     for (key, multiplier) in BYTE_SUFFIXES {
       if suffix == key {
-        let value = raw_value.checked_mul(multiplier)
-          .expect(&format!("Chunk value {raw} is too large! Try trimming the value down to something more reasonable (the max unsigned value your arch can represent)"));
+        let value = raw_value * multiplier as f64;
 
-        return ChunkInfo {
-          value,
+        if value >= usize::MAX as f64 {
+          return Err(format!("Chunk value {raw} is too large! Try trimming the value down to something more reasonable (the max unsigned value your arch can represent)"));
+        }
+
+        // anything under a byte would still be a file per line
+        if value < 1.0 {
+          return Err(format!("Chunk limit '{raw}' must be at least 1 byte"));
+        }
+
+        return Ok(ChunkLimit {
+          value: value as usize,
           unit: ChunkUnit::Bytes,
-        };
+        });
       }
     }
 
     let all_suffixes: Vec<String> = BYTE_SUFFIXES.iter().map(|(s, _)| s.to_string()).collect();
 
-    panic!(
+    Err(format!(
       "Chunk suffix {suffix} is not recognized. Valid options are {}, {}",
       LINE_SUFFIX,
       all_suffixes.join(", ")
-    )
+    ))
   }
 }
 
@@ -93,22 +154,53 @@ impl Write for NoOpWriter {
 pub struct ChunkedWriter {
   base_path: PathBuf,
   chunk_info: ChunkInfo,
-  zipped: bool,
+  compression: CompressionRoutes,
+  on_exists: OnExists,
+  checksum: Option<Checksum>,
   chunk_index: usize,
-  written: usize,
+  lines: usize,
+  bytes: usize,
   inner: Box<dyn Write>,
+  /// with --skip-unchanged, the chunk being written and the hash of what has gone into it so far
+  dedup: Option<ChunkDedup>,
+  current: Option<PathBuf>,
+  hash: u64,
+  chunk_bytes: u64,
+  /// the first and last time of the events in the chunk, kept in the manifest for saw merge-manifests
+  span: Option<(LocalDateTime, LocalDateTime)>,
+  /// with --chunk-workers or --upload-chunk, where chunks are compressed once they're written, and the chunk being
+  /// written plain until then
+  queue: Option<ChunkQueue>,
+  pending: Option<(PathBuf, PathBuf, Codec)>,
 }
 
 impl ChunkedWriter {
 
-  pub fn new(base_path: PathBuf, chunk_info: ChunkInfo, zipped: bool) -> ChunkedWriter {
+  pub fn new(base_path: PathBuf, chunk_info: ChunkInfo, compression: CompressionRoutes, skip_unchanged: bool, on_exists: OnExists, checksum: Option<Checksum>, queue: Option<ChunkQueue>) -> ChunkedWriter {
+    let dedup = if skip_unchanged { Some(ChunkDedup::load(&base_path, checksum)) } else { None };
+
+    // --skip-unchanged replaces old chunks itself, and only when they changed
+    if dedup.is_none() {
+      on_exists.check(ChunkedWriter::existing_chunks(&base_path));
+    }
+
     let mut res = ChunkedWriter {
       base_path,
       chunk_info,
-      zipped,
+      compression,
+      on_exists,
+      checksum,
       chunk_index: 0,
-      written: 0,
+      lines: 0,
+      bytes: 0,
       inner: Box::new(NoOpWriter{}), // just a placeholder, we update it instantly
+      dedup,
+      current: None,
+      hash: FNV_OFFSET,
+      chunk_bytes: 0,
+      span: None,
+      queue,
+      pending: None,
     };
 
     // this fills inner with an actual valid value
@@ -119,21 +211,93 @@ impl ChunkedWriter {
 
   fn next_chunk(&mut self) {
     let index = self.chunk_index;
-    let ext = if self.zipped { ".log.gz" } else { ".log" };
 
     let base_file_name = self.base_path.file_name().unwrap().to_str().unwrap();
-    let file_name = base_file_name.to_owned() + "." + &index.to_string() + ext;
+    let chunk_name = base_file_name.to_owned() + "." + &index.to_string();
+
+    // routes are matched against the chunk without its extensions, since the extension depends on the route
+    let codec = self.compression.codec_for(self.base_path.with_file_name(&chunk_name).to_str().unwrap_or(""));
+    let file_name = match codec.extension() {
+      Some(ext) => chunk_name + ".log." + ext,
+      None => chunk_name + ".log",
+    };
 
     let file_path = self.base_path.with_file_name(file_name);
 
+    // drop the old chunk first so that it is completely written before the next one is started
+    self.inner = Box::new(NoOpWriter{});
+    self.finish_chunk();
+
     self.chunk_index += 1;
-    let file = BufWriter::new(File::create(file_path).expect(&format!("Failed to create file '{}.{}'", self.base_path.to_str().unwrap_or("<invalid>"), self.chunk_index)));
 
-    if self.zipped {
-      self.inner = Box::new(GzEncoder::new(file, Compression::best()))
+    // a worker compresses the chunk once it's written, so until then it's written plain next to where it goes
+    if self.queue.is_some() {
+      let staging = ChunkDedup::staging_path(&file_path);
+      let file = File::create(&staging).unwrap_or_else(|_| panic!("Failed to create file '{}'", staging.display()));
+
+      self.inner = Box::new(BufWriter::new(file));
+      self.pending = Some((staging, file_path, codec));
+      return;
+    }
+
+    // with --skip-unchanged, a chunk only takes the place of the old one once it's known to be different
+    let create_path = if self.dedup.is_some() { ChunkDedup::staging_path(&file_path) } else { file_path.clone() };
+    self.current = Some(file_path);
+
+    let file = if self.dedup.is_some() {
+      File::create(&create_path).unwrap_or_else(|_| panic!("Failed to create file '{}'", create_path.display()))
     } else {
-      self.inner = Box::new(file)
+      self.on_exists.create(&create_path)
+    };
+
+    self.inner = codec.wrap(BufWriter::new(OutputFile::new(file, &create_path, self.checksum)));
+  }
+
+  /// files from an earlier run that look like chunks of this output, like out.3.log.gz
+  fn existing_chunks(base_path: &Path) -> Vec<PathBuf> {
+    let Some(base_name) = base_path.file_name().and_then(|name| name.to_str()) else {
+      return vec![];
+    };
+
+    let parent = base_path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let prefix = format!("{base_name}.");
+
+    let Ok(entries) = fs::read_dir(parent) else {
+      return vec![];
+    };
+
+    entries.flatten()
+      .map(|entry| entry.path())
+      .filter(|path| {
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+
+        name.strip_prefix(&prefix)
+          .and_then(|rest| rest.split_once('.'))
+          .is_some_and(|(index, ext)| !index.is_empty() && index.bytes().all(|c| c.is_ascii_digit()) && ext.starts_with("log"))
+      })
+      .collect()
+  }
+
+  fn finish_chunk(&mut self) {
+    if let (Some(queue), Some((staging, target, codec))) = (&self.queue, self.pending.take()) {
+      queue.submit(staging, target, codec);
     }
+
+    if let (Some(dedup), Some(current)) = (&mut self.dedup, self.current.take()) {
+      dedup.finish(&current, self.chunk_bytes, self.hash, self.span);
+    }
+
+    self.hash = FNV_OFFSET;
+    self.chunk_bytes = 0;
+    self.span = None;
+  }
+}
+
+impl Drop for ChunkedWriter {
+  fn drop(&mut self) {
+    // the last chunk must be completely written before it can be compared
+    self.inner = Box::new(NoOpWriter{});
+    self.finish_chunk();
   }
 }
 
@@ -141,10 +305,13 @@ impl Write for ChunkedWriter {
 
   fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
     let written = self.inner.write(buf);
+    let count = *written.as_ref().expect("Failed to write to file");
+
+    self.bytes += count;
 
-    // if we're counting bytes then all the whole size, else we're counting lines
-    if let ChunkUnit::Bytes = self.chunk_info.unit {
-      self.written += written.as_ref().expect("Failed to write to file");
+    if self.dedup.is_some() {
+      self.hash = fnv_extend(self.hash, buf[..count].iter().copied());
+      self.chunk_bytes += count as u64;
     }
 
     written
@@ -156,27 +323,47 @@ impl Write for ChunkedWriter {
 }
 
 pub trait LogWriter: Write {
+  /**
+   * Called with each event before it's written, for writers that pick where an event goes by what's in it
+   */
+  fn route_event(&mut self, _values: &Map<String, Value>) {}
+
+  /**
+   * Called with the time of each event before it's written, for writers that pick where an event goes by its time
+   */
+  fn start_event(&mut self, _time: Option<LocalDateTime>) {}
+
   fn end_line(&mut self) {
-    self.write(b"\n").expect("Failed to write to file");
+    self.write_all(b"\n").expect("Failed to write to file");
+    self.end_frame();
   }
+
+  /**
+   * Called after each event that isn't ended by a newline, like a length prefixed frame, and after every line
+   */
+  fn end_frame(&mut self) {}
 }
 
 impl LogWriter for ChunkedWriter {
 
-  fn end_line(&mut self) {
-    self.write(b"\n").expect("Failed to write to file");
-
-    if let ChunkUnit::Lines = self.chunk_info.unit {
-      self.written += 1;
+  fn start_event(&mut self, time: Option<LocalDateTime>) {
+    if let (Some(_), Some(time)) = (&self.dedup, time) {
+      self.span = Some(self.span.map_or((time, time), |(first, last)| (first.min(time), last.max(time))));
     }
+  }
+
+  fn end_frame(&mut self) {
+    self.lines += 1;
 
-    if self.written >= self.chunk_info.value {
+    if self.chunk_info.is_full(self.lines, self.bytes) {
       self.next_chunk();
-      self.written = 0;
+      self.lines = 0;
+      self.bytes = 0;
     }
   }
 }
 
 impl <Inner: Write> LogWriter for GzEncoder<Inner> {}
+impl <Inner: Write> LogWriter for zstd::stream::AutoFinishEncoder<'_, Inner> {}
 impl <Inner: Write> LogWriter for BufWriter<Inner> {}
 
@@ -0,0 +1,107 @@
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::chunk::LogWriter;
+
+/// the value of --output that writes to the clipboard instead of a file
+pub const CLIPBOARD_OUTPUT: &str = "clipboard";
+
+/// clipboards aren't meant for whole archives, anything past this is left off at a line boundary
+const CLIPBOARD_LIMIT: usize = 1024 * 1024;
+
+/**
+ * Holds everything written and puts it on the system clipboard once done, using whichever of the usual clipboard
+ * commands this platform has
+ */
+#[derive(Default)]
+pub struct ClipboardWriter {
+  buffer: Vec<u8>,
+  /// length of the buffer at the end of the last whole event
+  line_end: usize,
+  lines: u64,
+  /// events left off for being over the limit
+  dropped: u64,
+}
+
+impl ClipboardWriter {
+  /// commands to try, in order, as (program, args)
+  fn backends() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+      vec![("pbcopy", &[])]
+    } else if cfg!(target_os = "windows") {
+      vec![("clip", &[])]
+    } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+      vec![("wl-copy", &[]), ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])]
+    } else {
+      vec![("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"]), ("wl-copy", &[])]
+    }
+  }
+
+  fn copy(&self, content: &[u8]) -> Result<&'static str, String> {
+    for (program, args) in ClipboardWriter::backends() {
+      // not installed, try the next one
+      let Ok(mut child) = Command::new(program).args(args).stdin(Stdio::piped()).spawn() else {
+        continue;
+      };
+
+      if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(content).map_err(|err| format!("Failed to write to {program}: {err}"))?;
+      }
+
+      let status = child.wait().map_err(|err| format!("Failed to wait on {program}: {err}"))?;
+
+      return if status.success() { Ok(program) } else { Err(format!("{program} exited with {status}")) };
+    }
+
+    let names: Vec<&str> = ClipboardWriter::backends().iter().map(|(program, _)| *program).collect();
+
+    Err(format!("No clipboard command was found, install one of {}", names.join(", ")))
+  }
+}
+
+impl Write for ClipboardWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    // once a line has been dropped everything after it is too, so the clipboard never has a gap in the middle
+    if self.dropped == 0 {
+      self.buffer.extend_from_slice(buf);
+    }
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+impl LogWriter for ClipboardWriter {
+  fn end_line(&mut self) {
+    self.write_all(b"\n").expect("Failed to write to clipboard");
+
+    if self.dropped > 0 {
+      self.dropped += 1;
+    } else if self.buffer.len() > CLIPBOARD_LIMIT {
+      self.buffer.truncate(self.line_end);
+      self.dropped = 1;
+    } else {
+      self.line_end = self.buffer.len();
+      self.lines += 1;
+    }
+  }
+}
+
+impl Drop for ClipboardWriter {
+  fn drop(&mut self) {
+    let content = &self.buffer[..self.line_end];
+
+    match self.copy(content) {
+      Ok(program) => eprintln!("Copied {} events ({} bytes) to the clipboard with {program}", self.lines, content.len()),
+      Err(err) => eprintln!("Failed to copy to the clipboard: {err}"),
+    }
+
+    if self.dropped > 0 {
+      eprintln!("Left off the last {} events, which were over the clipboard limit of {CLIPBOARD_LIMIT} bytes", self.dropped);
+    }
+  }
+}
@@ -0,0 +1,188 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use flate2::{Compression, GzBuilder};
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use glob::Pattern;
+use lazy_static::lazy_static;
+
+use crate::chunk::LogWriter;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+  Plain,
+  Gzip,
+  Zstd,
+}
+
+const ZSTD_LEVEL: i32 = 19;
+
+impl Codec {
+  pub fn parse(raw: &str) -> Result<Codec, String> {
+    match raw.to_lowercase().as_str() {
+      "none" | "plain" => Ok(Codec::Plain),
+      "gzip" | "gz" => Ok(Codec::Gzip),
+      "zstd" | "zst" => Ok(Codec::Zstd),
+      _ => Err(format!("Compression '{raw}' is not recognized. Valid options are none, gzip, zstd")),
+    }
+  }
+
+  /**
+   * The file extension this codec is expected to have, if any
+   */
+  pub fn extension(&self) -> Option<&'static str> {
+    match self {
+      Codec::Plain => None,
+      Codec::Gzip => Some("gz"),
+      Codec::Zstd => Some("zst"),
+    }
+  }
+
+  pub fn wrap<Writer: 'static + Write + LogWriter>(&self, src: Writer) -> Box<dyn LogWriter> {
+    match self {
+      Codec::Plain => Box::new(src),
+      // no name or time in the header, so the same lines always compress to the same bytes
+      Codec::Gzip => Box::new(GzBuilder::new().mtime(0).write(src, Compression::best())),
+      Codec::Zstd => {
+        let encoder = zstd::Encoder::new(src, ZSTD_LEVEL).expect("Failed to start zstd compression");
+
+        Box::new(encoder.auto_finish())
+      }
+    }
+  }
+}
+
+/**
+ * Picks the compression for each place output is written, the first matching route wins
+ */
+#[derive(Debug)]
+pub struct CompressionRoutes {
+  routes: Vec<(Pattern, Codec)>,
+  default: Codec,
+}
+
+impl CompressionRoutes {
+  pub fn new(routes: Vec<(Pattern, Codec)>, default: Codec) -> CompressionRoutes {
+    CompressionRoutes { routes, default }
+  }
+
+  /**
+   * Parse a route in the form GLOB=CODEC
+   */
+  pub fn parse_route(raw: &str) -> Result<(Pattern, Codec), String> {
+    let (glob, codec) = raw.rsplit_once('=')
+      .ok_or_else(|| format!("Compression route '{raw}' must be in the form DESTINATION=CODEC"))?;
+
+    let pattern = Pattern::new(glob).map_err(|_| format!("Compression route destination '{glob}' is not a valid glob"))?;
+
+    Ok((pattern, Codec::parse(codec)?))
+  }
+
+  pub fn codec_for(&self, destination: &str) -> Codec {
+    self.routes.iter()
+      .find(|(pattern, _)| pattern.matches(destination))
+      .map_or(self.default, |(_, codec)| *codec)
+  }
+}
+
+/// how much of a source is read at once, the standard library's own default unless --autotune picks another
+pub const DEFAULT_READ_BUFFER: usize = 8 * 1024;
+
+static READ_BUFFER: AtomicUsize = AtomicUsize::new(DEFAULT_READ_BUFFER);
+
+lazy_static! {
+  static ref SOURCE_CODECS: RwLock<Vec<(Pattern, SourceCodec)>> = RwLock::new(vec![]);
+}
+
+/**
+ * How a source is decompressed as it's read. Sources are read as gzip when they start like one and as plain text
+ * otherwise, unless --codec says what they are
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceCodec {
+  Plain,
+  Gzip,
+  Zstd,
+  /// deflate with the two byte zlib header
+  Zlib,
+  /// deflate without any header
+  Deflate,
+}
+
+impl SourceCodec {
+  pub fn parse(raw: &str) -> Result<SourceCodec, String> {
+    match raw.to_lowercase().as_str() {
+      "none" | "plain" => Ok(SourceCodec::Plain),
+      "gzip" | "gz" => Ok(SourceCodec::Gzip),
+      "zstd" | "zst" => Ok(SourceCodec::Zstd),
+      "zlib" => Ok(SourceCodec::Zlib),
+      "deflate" => Ok(SourceCodec::Deflate),
+      _ => Err(format!("Codec '{raw}' is not recognized. Valid options are plain, gzip, zstd, zlib, deflate")),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      SourceCodec::Plain => "plain",
+      SourceCodec::Gzip => "gzip",
+      SourceCodec::Zstd => "zstd",
+      SourceCodec::Zlib => "zlib",
+      SourceCodec::Deflate => "deflate",
+    }
+  }
+
+  pub fn decode<Reader: 'static + Read + Send>(&self, src: Reader) -> Box<dyn BufRead + Send> {
+    match self {
+      SourceCodec::Plain => Box::new(BufReader::with_capacity(read_buffer(), src)),
+      SourceCodec::Gzip => Box::new(BufReader::with_capacity(read_buffer(), MultiGzDecoder::new(src))),
+      SourceCodec::Zstd => Box::new(BufReader::with_capacity(read_buffer(), zstd::Decoder::new(src).expect("Failed to start zstd decompression"))),
+      SourceCodec::Zlib => Box::new(BufReader::with_capacity(read_buffer(), ZlibDecoder::new(src))),
+      SourceCodec::Deflate => Box::new(BufReader::with_capacity(read_buffer(), DeflateDecoder::new(src))),
+    }
+  }
+}
+
+/**
+ * Parse an override in the form SOURCE=CODEC, where SOURCE is a glob
+ */
+pub fn parse_source_codec(raw: &str) -> Result<(Pattern, SourceCodec), String> {
+  let (glob, codec) = raw.rsplit_once('=')
+    .ok_or_else(|| format!("Codec '{raw}' must be in the form SOURCE=CODEC, like 'app.log.gz=plain'"))?;
+
+  let pattern = Pattern::new(glob).map_err(|_| format!("Codec source '{glob}' is not a valid glob"))?;
+
+  Ok((pattern, SourceCodec::parse(codec)?))
+}
+
+/**
+ * Set the codec overrides for every source opened after this, including those --follow finds later. Must be called
+ * before any source is opened
+ */
+pub fn force_source_codecs(overrides: Vec<(Pattern, SourceCodec)>) {
+  *SOURCE_CODECS.write().expect("Codec overrides were poisoned") = overrides;
+}
+
+/**
+ * Read every source opened from now on BYTES at a time. Must be called before any source is opened
+ */
+pub fn set_read_buffer(bytes: usize) {
+  READ_BUFFER.store(bytes, Ordering::Relaxed);
+}
+
+pub fn read_buffer() -> usize {
+  READ_BUFFER.load(Ordering::Relaxed)
+}
+
+/**
+ * The codec --codec forces for a source, matched against its path as given or just its file name. The first wins
+ */
+pub fn forced_source_codec(path: &Path) -> Option<SourceCodec> {
+  let overrides = SOURCE_CODECS.read().expect("Codec overrides were poisoned");
+  let name = path.file_name().map(Path::new);
+
+  overrides.iter()
+    .find(|(pattern, _)| pattern.matches_path(path) || name.is_some_and(|name| pattern.matches_path(name)))
+    .map(|(_, codec)| *codec)
+}
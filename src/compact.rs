@@ -0,0 +1,26 @@
+use serde_json::{Map, Value};
+
+/**
+ * Remove every null, empty string, empty array and empty object, at any depth. Anything left empty by that is
+ * removed too, so {"a": {"b": null}} becomes {}.
+ */
+pub fn compact(values: &mut Map<String, Value>) {
+  values.retain(|_, value| !compact_value(value));
+}
+
+/// compact a value in place, returning true if nothing is left of it
+fn compact_value(value: &mut Value) -> bool {
+  match value {
+    Value::Null => true,
+    Value::String(str) => str.is_empty(),
+    Value::Array(arr) => {
+      arr.retain_mut(|item| !compact_value(item));
+      arr.is_empty()
+    }
+    Value::Object(obj) => {
+      compact(obj);
+      obj.is_empty()
+    }
+    Value::Bool(_) | Value::Number(_) => false,
+  }
+}
@@ -0,0 +1,473 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use datetime::{DatePiece, ISO, LocalDateTime};
+use glob::glob;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+use crate::checksum::Checksum;
+use crate::chunk::{ChunkInfo, ChunkUnit, LogWriter};
+use crate::codec::Codec;
+use crate::dedup::ChunkDedup;
+use crate::index::SourceIndex;
+use crate::integrity::{self, Checked};
+use crate::log::{Aggregator, LogFile, ReadOptions};
+use crate::preset::Preset;
+
+/// compacted chunks are started again once they reach this many bytes before compression, by default
+const DEFAULT_TARGET: u64 = 256 * 1024 * 1024;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+lazy_static! {
+  /// a chunk this writes, DIR/YYYY/MM/DD.N.log with its compression's extension, relative to DIR
+  static ref COMPACTED: Regex = Regex::new(r"^(\d{4})/(\d{2})/(\d{2})\.\d+\.log(\.gz|\.zst)?$").unwrap();
+}
+
+pub const COMPACT_HELP: &str = r#"
+Usage:
+  saw compact [--target SIZE] [--compress CODEC] [--dry-run] DIR
+
+Rewrites the small chunks in DIR, like the hourly files of frequent runs, into a few chunks per day, so later runs open
+a handful of files instead of thousands. Every file under DIR whose name ends in .log, .log.gz or .log.zst is read,
+and its events are merged by time into DIR/YYYY/MM/DD.0.log.gz for the UTC day they're from, going on to DD.1.log.gz
+and so on whenever one reaches SIZE before compression, 256mb by default. CODEC is gzip, zstd or none, gzip by default.
+A day whose compacted chunks get no new events is left as it is, and one that does is written again with them merged in.
+
+Nothing is deleted until every new chunk has been read back and holds as many events as the chunks it replaces had
+lines. Only then are the old chunks removed, along with their index and checksum. Each day gets a DD.manifest like the
+ones --skip-unchanged writes, for saw verify and saw merge-manifests, and other manifests in DIR stop listing the
+chunks that are gone. The new chunks of a day are indexed with the fields of any index the old ones had, and given a
+checksum if any of the old ones had one.
+
+A chunk with lines that aren't JSON or events without a time, that's cut off, or whose times go backwards can't be
+compacted without losing or reordering events, so saw stops before changing anything and names it. --dry-run prints
+what would be compacted without writing anything:
+  saw compact --dry-run archive/
+  saw compact --target 1gb --compress zstd archive/
+"#;
+
+/**
+ * One chunk under DIR, as read through before compacting
+ */
+struct Chunk {
+  path: PathBuf,
+  checked: Checked,
+  /// the day it holds, if it was written by an earlier compaction
+  compacted: Option<i64>,
+}
+
+impl Chunk {
+  /// every day it has events on, counted from 1970-01-01
+  fn days(&self) -> Option<(i64, i64)> {
+    Some((day_of(self.checked.first?), day_of(self.checked.last?)))
+  }
+
+  fn overlaps(&self, day: i64) -> bool {
+    match self.compacted {
+      Some(compacted) => compacted == day,
+      None => self.days().is_some_and(|(first, last)| first <= day && day <= last),
+    }
+  }
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
+
+fn day_of(time: LocalDateTime) -> i64 {
+  millis(time).div_euclid(DAY_MS)
+}
+
+fn day_name(day: i64) -> String {
+  LocalDateTime::at(day * DAY_MS / 1000).date().iso().to_string()
+}
+
+/// DIR/YYYY/MM/DD, which the chunks and manifest of a day are named from
+fn day_base(dir: &Path, day: i64) -> PathBuf {
+  let date = LocalDateTime::at(day * DAY_MS / 1000).date();
+
+  dir.join(format!("{:04}", date.year()))
+    .join(format!("{:02}", date.month().months_from_january() + 1))
+    .join(format!("{:02}", date.day()))
+}
+
+fn with_suffix(base: &Path, suffix: &str) -> PathBuf {
+  let mut path = base.as_os_str().to_owned();
+  path.push(suffix);
+
+  PathBuf::from(path)
+}
+
+/**
+ * Writes the events of one day, starting a new chunk whenever one reaches the target size. Each chunk is written to
+ * its staging path, and only moved into place once every day has been written and checked
+ */
+struct DayWriter {
+  base: PathBuf,
+  codec: Codec,
+  target: u64,
+  bytes: u64,
+  out: Option<Box<dyn LogWriter>>,
+  /// where each chunk written so far goes once it's checked
+  chunks: Vec<PathBuf>,
+}
+
+impl DayWriter {
+  fn new(base: PathBuf, codec: Codec, target: u64) -> DayWriter {
+    DayWriter { base, codec, target, bytes: 0, out: None, chunks: vec![] }
+  }
+
+  fn write(&mut self, value: &Map<String, Value>) {
+    if self.out.is_none() || self.bytes >= self.target {
+      self.next_chunk();
+    }
+
+    let mut line = serde_json::to_vec(value).expect("Failed to write line");
+    line.push(b'\n');
+
+    self.bytes += line.len() as u64;
+
+    self.out.as_mut().unwrap().write_all(&line).expect("Failed to write compacted chunk");
+  }
+
+  fn next_chunk(&mut self) {
+    // dropped first, so the chunk before is completely written
+    self.out = None;
+    self.bytes = 0;
+
+    let extension = self.codec.extension().map_or(String::new(), |ext| format!(".{ext}"));
+    let target = with_suffix(&self.base, &format!(".{}.log{extension}", self.chunks.len()));
+    let staging = ChunkDedup::staging_path(&target);
+
+    if let Some(parent) = staging.parent() {
+      fs::create_dir_all(parent).unwrap_or_else(|_| panic!("Failed to create directory '{}'", parent.display()));
+    }
+
+    let file = File::create(&staging).unwrap_or_else(|_| panic!("Failed to create file '{}'", staging.display()));
+
+    self.out = Some(self.codec.wrap(BufWriter::new(file)));
+    self.chunks.push(target);
+  }
+
+  fn finish(mut self) -> Vec<PathBuf> {
+    self.out = None;
+    self.chunks
+  }
+}
+
+/**
+ * saw compact [--target SIZE] [--compress CODEC] [--dry-run] DIR
+ */
+pub fn run<Args: Iterator<Item=String>>(mut src: Args) {
+  let mut target = DEFAULT_TARGET;
+  let mut codec = Codec::Gzip;
+  let mut dry_run = false;
+  let mut dir: Option<PathBuf> = None;
+
+  while let Some(next) = src.next() {
+    match next.as_ref() {
+      "-h" | "--help" => {
+        eprintln!("{COMPACT_HELP}");
+        return;
+      }
+      "--target" => {
+        let raw = src.next().expect("Argument --target must be followed by a SIZE like 256mb");
+
+        target = ChunkInfo::parse(&raw).unwrap_or_else(|err| panic!("{err}")).limits.iter()
+          .find(|limit| limit.unit == ChunkUnit::Bytes)
+          .map(|limit| limit.value as u64)
+          .unwrap_or_else(|| panic!("Argument --target '{raw}' must be a size in bytes, like 256mb or 1gb"));
+      }
+      "--compress" => {
+        codec = Codec::parse(&src.next().expect("Argument --compress must be followed by gzip, zstd or none")).unwrap_or_else(|err| panic!("{err}"));
+      }
+      "--dry-run" => dry_run = true,
+      _ if next.starts_with('-') => {
+        panic!("Unknown property '{next}' for saw compact. Run saw compact --help to see all known properties");
+      }
+      _ if dir.is_some() => panic!("saw compact takes one DIR, but was given '{next}' as well"),
+      _ => dir = Some(next.into()),
+    }
+  }
+
+  let dir = dir.expect("saw compact needs a DIR. Run saw compact --help for more information");
+
+  if !dir.is_dir() {
+    panic!("'{}' is not a directory", dir.display());
+  }
+
+  let chunks = read_chunks(&dir);
+
+  let refused: Vec<String> = chunks.iter().filter_map(refusal).collect();
+
+  if !refused.is_empty() {
+    panic!("Can't compact '{}' without losing or reordering events, nothing was changed:\n  {}", dir.display(), refused.join("\n  "));
+  }
+
+  // only days new chunks have events on are written, along with what's already compacted for them
+  let mut days: BTreeSet<i64> = BTreeSet::new();
+
+  for (first, last) in chunks.iter().filter(|chunk| chunk.compacted.is_none()).filter_map(Chunk::days) {
+    days.extend(first..=last);
+  }
+
+  // empty chunks, like the last one of a run that ended right as it was started, are only removed
+  let empty: Vec<&Chunk> = chunks.iter().filter(|chunk| chunk.compacted.is_none() && chunk.checked.lines == 0).collect();
+
+  let used: Vec<&Chunk> = chunks.iter()
+    .filter(|chunk| chunk.checked.lines > 0 && days.iter().any(|day| chunk.overlaps(*day)))
+    .collect();
+
+  if days.is_empty() && empty.is_empty() {
+    eprintln!("Nothing to compact in '{}'", dir.display());
+    return;
+  }
+
+  if dry_run {
+    for day in &days {
+      let day_chunks: Vec<&&Chunk> = used.iter().filter(|chunk| chunk.overlaps(*day)).collect();
+      let bytes: u64 = day_chunks.iter().map(|chunk| chunk.checked.bytes).sum();
+
+      println!("{}: {} chunks, {bytes} bytes, into {}", day_name(*day), day_chunks.len(), with_suffix(&day_base(&dir, *day), ".N.log").display());
+    }
+
+    println!("Would compact {} chunks into {} days, and remove {} empty chunks", used.len(), days.len(), empty.len());
+    return;
+  }
+
+  let mut written: BTreeMap<i64, Vec<PathBuf>> = BTreeMap::new();
+  let mut events = 0u64;
+
+  for day in &days {
+    let sources: Vec<LogFile> = used.iter()
+      .filter(|chunk| chunk.overlaps(*day))
+      .map(|chunk| LogFile::from_file(&chunk.path))
+      .collect();
+
+    let mut writer = DayWriter::new(day_base(&dir, *day), codec, target);
+
+    // a chunk from around midnight is read for each day it has events on, and gives each only its own
+    for line in Aggregator::new(sources, ReadOptions::default()).filter(|line| line.time.is_some_and(|time| day_of(time) == *day)) {
+      writer.write(&line.value);
+      events += 1;
+    }
+
+    written.insert(*day, writer.finish());
+  }
+
+  check_written(&dir, &written, &used, events);
+
+  // every new chunk is checked, so now they can take the place of the old ones
+  let targets: BTreeSet<PathBuf> = written.values().flatten().cloned().collect();
+
+  for target in &targets {
+    let staging = ChunkDedup::staging_path(target);
+
+    fs::rename(&staging, target).unwrap_or_else(|_| panic!("Failed to move '{}' to '{}'", staging.display(), target.display()));
+  }
+
+  let old: Vec<&Chunk> = used.iter().chain(&empty).copied().collect();
+
+  for (day, chunks) in &written {
+    let day_old: Vec<&Chunk> = old.iter().filter(|chunk| chunk.overlaps(*day)).copied().collect();
+
+    finish_day(&day_base(&dir, *day), chunks, &day_old);
+  }
+
+  let mut removed: Vec<PathBuf> = vec![];
+
+  for chunk in old.iter().filter(|chunk| !targets.contains(&chunk.path)) {
+    fs::remove_file(&chunk.path).unwrap_or_else(|_| panic!("Failed to remove '{}'", chunk.path.display()));
+
+    for sidecar in [SourceIndex::path_for(&chunk.path), Checksum::Sha256.sidecar(&chunk.path)] {
+      if sidecar.exists() {
+        fs::remove_file(&sidecar).unwrap_or_else(|_| panic!("Failed to remove '{}'", sidecar.display()));
+      }
+    }
+
+    removed.push(chunk.path.clone());
+  }
+
+  let manifests = forget_removed(&dir, &removed, &written);
+
+  eprintln!(
+    "Compacted {} chunks into {} for {} days, {events} events, and updated {manifests} other manifests",
+    old.len(),
+    targets.len(),
+    days.len(),
+  );
+}
+
+/**
+ * Every chunk under DIR, read through to find its times and check that it can be compacted
+ */
+fn read_chunks(dir: &Path) -> Vec<Chunk> {
+  let pattern = dir.join("**").join("*");
+  let paths = glob(&pattern.to_string_lossy()).unwrap_or_else(|_| panic!("Directory '{}' is not valid", dir.display()));
+
+  paths
+    .map(|path| path.unwrap_or_else(|_| panic!("Directory '{}' could not be read", dir.display())))
+    .filter(|path| path.is_file())
+    .filter(|path| {
+      let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+
+      [".log", ".log.gz", ".log.zst"].iter().any(|ext| name.ends_with(ext))
+    })
+    .map(|path| {
+      let relative = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+
+      let compacted = COMPACTED.captures(&relative).and_then(|captures| {
+        let number = |index: usize| captures[index].parse::<i64>().ok();
+        let time = format!("{:04}-{:02}-{:02}T00:00:00", number(1)?, number(2)?, number(3)?);
+
+        time.parse::<LocalDateTime>().ok().map(day_of)
+      });
+
+      Chunk { checked: integrity::read(&path), path, compacted }
+    })
+    .collect()
+}
+
+/// why a chunk can't be compacted, if it can't
+fn refusal(chunk: &Chunk) -> Option<String> {
+  let checked = &chunk.checked;
+
+  let problem = if !checked.problems.is_empty() {
+    checked.problems.join("; ")
+  } else if checked.invalid > 0 {
+    format!("{} lines are not JSON", checked.invalid)
+  } else if checked.lines > 0 && checked.first.is_none() {
+    "its events have no time".to_string()
+  } else {
+    return None;
+  };
+
+  Some(format!("{}: {problem}", chunk.path.display()))
+}
+
+/**
+ * Read back every chunk written, and check that together they hold every line of the chunks they were made from.
+ * Anything wrong throws the new chunks away, leaving the old ones as they were
+ */
+fn check_written(dir: &Path, written: &BTreeMap<i64, Vec<PathBuf>>, used: &[&Chunk], events: u64) {
+  let expected: u64 = used.iter().map(|chunk| chunk.checked.lines).sum();
+
+  let mut problems: Vec<String> = vec![];
+  let mut lines = 0;
+
+  for target in written.values().flatten() {
+    let checked = integrity::read(&ChunkDedup::staging_path(target));
+
+    lines += checked.lines;
+
+    if !checked.problems.is_empty() {
+      problems.push(format!("{}: {}", target.display(), checked.problems.join("; ")));
+    }
+  }
+
+  if events != expected || lines != expected {
+    problems.push(format!("the old chunks have {expected} lines, but {events} events were read from them and the new chunks have {lines}"));
+  }
+
+  if problems.is_empty() {
+    return;
+  }
+
+  for target in written.values().flatten() {
+    let _ = fs::remove_file(ChunkDedup::staging_path(target));
+  }
+
+  panic!("Compacting '{}' went wrong, so the old chunks were kept and nothing was changed:\n  {}", dir.display(), problems.join("\n  "));
+}
+
+/**
+ * Write the manifest for a day's new chunks, and give them an index and checksum if the old chunks had them
+ */
+fn finish_day(base: &Path, chunks: &[PathBuf], old: &[&Chunk]) {
+  let checksum = old.iter().any(|chunk| Checksum::Sha256.sidecar(&chunk.path).exists()).then_some(Checksum::Sha256);
+
+  let settings: Vec<(Vec<String>, Option<String>)> = old.iter().filter_map(|chunk| SourceIndex::settings(&chunk.path)).collect();
+
+  let mut manifest = Map::new();
+
+  for chunk in chunks {
+    let checked = integrity::read(chunk);
+    let name = chunk.file_name().and_then(|name| name.to_str()).unwrap_or("").to_string();
+
+    let mut entry = json!({ "bytes": checked.bytes, "hash": format!("{:016x}", checked.hash) });
+
+    if let (Some(first), Some(last)) = (checked.first, checked.last) {
+      entry["first"] = Value::String(first.iso().to_string());
+      entry["last"] = Value::String(last.iso().to_string());
+    }
+
+    if let Some(checksum) = checksum {
+      let digest = checksum.hash_file(chunk);
+
+      checksum.write(chunk, &digest);
+      entry[checksum.name()] = Value::String(digest);
+    }
+
+    manifest.insert(name, entry);
+
+    if !settings.is_empty() {
+      let mut fields: Vec<String> = settings.iter().flat_map(|(fields, _)| fields.clone()).collect();
+      fields.sort();
+      fields.dedup();
+
+      let preset = settings.iter().find_map(|(_, preset)| preset.as_deref()).map(|name| Preset::parse(name).unwrap_or_else(|err| panic!("{err}")));
+
+      SourceIndex::write_for(chunk, &fields, preset);
+    }
+  }
+
+  let path = with_suffix(base, ".manifest");
+
+  fs::write(&path, Value::Object(manifest).to_string() + "\n").unwrap_or_else(|_| panic!("Failed to write manifest '{}'", path.display()));
+}
+
+/**
+ * Leave the chunks that were removed out of every other manifest under DIR, removing any left listing nothing.
+ * Returns how many were changed
+ */
+fn forget_removed(dir: &Path, removed: &[PathBuf], written: &BTreeMap<i64, Vec<PathBuf>>) -> usize {
+  let ours: BTreeSet<PathBuf> = written.keys().map(|day| with_suffix(&day_base(dir, *day), ".manifest")).collect();
+
+  let pattern = dir.join("**").join("*.manifest");
+  let paths = glob(&pattern.to_string_lossy()).unwrap_or_else(|_| panic!("Directory '{}' is not valid", dir.display()));
+
+  let mut changed = 0;
+
+  for manifest in paths.flatten().filter(|path| !ours.contains(path)) {
+    let Ok(Value::Object(mut chunks)) = fs::read_to_string(&manifest).map_err(|_| ()).and_then(|raw| serde_json::from_str(&raw).map_err(|_| ())) else {
+      eprintln!("Warning: manifest '{}' is not valid, so it was left as it is", manifest.display());
+      continue;
+    };
+
+    let parent = manifest.parent().unwrap_or(Path::new(""));
+    let before = chunks.len();
+
+    chunks.retain(|name, _| !removed.contains(&parent.join(name)));
+
+    if chunks.len() == before {
+      continue;
+    }
+
+    changed += 1;
+
+    if chunks.is_empty() {
+      fs::remove_file(&manifest).unwrap_or_else(|_| panic!("Failed to remove manifest '{}'", manifest.display()));
+    } else {
+      fs::write(&manifest, Value::Object(chunks).to_string() + "\n").unwrap_or_else(|_| panic!("Failed to write manifest '{}'", manifest.display()));
+    }
+  }
+
+  changed
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, copy, stdout, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use datetime::LocalDateTime;
+use flate2::read::MultiGzDecoder;
+use serde_json::{Map, Value};
+
+use crate::args::Arguments;
+use crate::cache::AdvisedFile;
+use crate::checksum::OutputFile;
+use crate::codec::{Codec, SourceCodec};
+use crate::escape::JsonEscape;
+use crate::levels::LevelMap;
+use crate::provenance::PROVENANCE_KEY;
+use crate::shard::Shard;
+
+/**
+ * If nothing would change any line, and the sources don't overlap in time, then merging them is the same as
+ * putting one file after another. Gzip members can be joined without decompressing and recompressing them,
+ * which is by far the slowest part of a plain merge.
+ *
+ * Returns true if the output was written this way, false if the normal pipeline must be used instead.
+ */
+pub fn try_concat(args: &Arguments) -> bool {
+  let nothing_to_do = args.pretty.is_none()
+    && args.table.is_none()
+    && args.html.is_none()
+    && !args.indented
+    && args.frames.is_none()
+    && args.filter.is_none()
+    && args.trace.is_none()
+    && args.escalations.is_empty()
+    && !args.shard.as_ref().is_some_and(Shard::by_key)
+    && args.partition.is_none()
+    && args.sessionize.is_none()
+    && args.anomalies.is_none()
+    && args.correlate.is_none()
+    && args.link_by.is_none()
+    && args.external_sort.is_none()
+    && args.translations.is_empty()
+    && args.crypto.is_none()
+    && args.secrets.is_none()
+    && args.fingerprint.is_none()
+    && args.tenant.is_none()
+    && args.plugins.is_empty()
+    && !args.compact
+    && args.truncations.is_empty()
+    && args.normalize_time.is_none()
+    && args.preset.is_none()
+    && args.percentiles.is_empty()
+    && args.summary_by.is_none()
+    && args.stats.is_none()
+    && !args.provenance
+    && !args.source_stats
+    && args.deadline.is_none()
+    && args.rate_limit.is_none()
+    && args.quota.is_none()
+    && args.control_socket.is_none()
+    && args.replay.is_none()
+    && args.expect_count.is_none()
+    && args.key_order.is_none()
+    && args.escape_json.is_none_or(|escape| escape == JsonEscape::Never)
+    && args.lines.start.is_none() && args.lines.end.is_none()
+    && args.bytes.start.is_none() && args.bytes.end.is_none()
+    && args.chunked.is_none()
+    && args.split_by_time.is_none()
+    && args.routes.is_empty()
+    && !args.clipboard
+    && args.sort_by.is_none()
+    && args.input.is_none()
+    && !args.lenient_json
+    && !args.resync
+    && args.projection.is_none()
+    && !args.debug_raw
+    && args.raw_copies.is_empty()
+    && args.time.is_none()
+    // numeric levels are only looked for while checking the times, which keeping the order skips
+    && (!args.keep_order || !args.rewrite_levels || args.levels.is_none())
+    && !args.follow
+    && args.tail.is_none()
+    && !args.reverse
+    && args.range == (None, None);
+
+  if !nothing_to_do || args.sources.is_empty() {
+    return false;
+  }
+
+  let destination = args.output.as_ref().map_or("-", |output| output.to_str().unwrap_or(""));
+  let gzip = match args.compression.codec_for(destination) {
+    Codec::Gzip => true,
+    Codec::Plain => false,
+    Codec::Zstd => return false,
+  };
+
+  let mut files: Vec<(PathBuf, Option<(LocalDateTime, LocalDateTime)>)> = vec![];
+
+  for source in &args.sources {
+    match source.path() {
+      Some(path) if source.codec() == if gzip { SourceCodec::Gzip } else { SourceCodec::Plain } => files.push((path.clone(), None)),
+      _ => return false,
+    }
+  }
+
+  let levels = args.levels.filter(|_| args.rewrite_levels);
+
+  // with keep order the files go one after another anyway, otherwise check that they really don't overlap
+  if args.keep_order {
+    if !files.iter().all(|(path, _)| check_lines(path, gzip, levels, |_| true)) {
+      return false;
+    }
+  } else {
+    for (path, span) in &mut files {
+      match time_span(path, gzip, levels) {
+        Some(found) => *span = Some(found),
+        None => return false,
+      }
+    }
+
+    files.sort_by_key(|(_, span)| span.unwrap().0);
+
+    let overlaps = files.windows(2).any(|pair| pair[0].1.unwrap().1 > pair[1].1.unwrap().0);
+
+    if overlaps {
+      return false;
+    }
+  }
+
+  let mut target: Box<dyn Write> = match &args.output {
+    Some(output) => Box::new(OutputFile::new(args.on_exists.create(output), output, args.checksum)),
+    None => Box::new(stdout()),
+  };
+
+  for (path, _) in &files {
+    let file = File::open(path).unwrap_or_else(|_| panic!("Failed to open file {}", path.display()));
+
+    copy(&mut AdvisedFile::new(file, path), &mut target).expect("Failed to write to output");
+  }
+
+  target.flush().expect("Failed to write to output");
+
+  true
+}
+
+/**
+ * Find the first and last time in a file, or None if any line has no time, is out of order, or fails check_lines
+ */
+fn time_span(path: &PathBuf, gzip: bool, levels: Option<&LevelMap>) -> Option<(LocalDateTime, LocalDateTime)> {
+  let mut span: Option<(LocalDateTime, LocalDateTime)> = None;
+
+  let valid = check_lines(path, gzip, levels, |time| {
+    let Some(time) = time else {
+      return false;
+    };
+
+    span = match span {
+      None => Some((time, time)),
+      // lines must already be in order, otherwise the merge would reorder them
+      Some((_, last)) if time < last => return false,
+      Some((first, _)) => Some((first, time)),
+    };
+
+    true
+  });
+
+  span.filter(|_| valid)
+}
+
+/**
+ * Check that a normal merge would write every line of a file exactly as it is, passing the time of each to EACH until
+ * it returns false. Lines that aren't JSON would be dropped, JSON that isn't written the way saw writes it would be
+ * reformatted, and numeric levels would be named by --rewrite-levels
+ */
+fn check_lines(path: &PathBuf, gzip: bool, levels: Option<&LevelMap>, mut each: impl FnMut(Option<LocalDateTime>) -> bool) -> bool {
+  let Ok(file) = File::open(path) else {
+    return false;
+  };
+
+  let reader: Box<dyn BufRead> = if gzip {
+    Box::new(BufReader::new(MultiGzDecoder::new(file)))
+  } else {
+    Box::new(BufReader::new(file))
+  };
+
+  for raw in reader.lines() {
+    let Ok(raw) = raw else {
+      return false;
+    };
+
+    let Ok(body) = serde_json::from_str::<Map<String, Value>>(&raw) else {
+      return false;
+    };
+
+    if body.contains_key(PROVENANCE_KEY) || serde_json::to_string(&body).ok().as_deref() != Some(raw.as_str()) {
+      return false;
+    }
+
+    if levels.is_some_and(|levels| levels.name(&body).is_some()) {
+      return false;
+    }
+
+    let time = body.get("time")
+      .and_then(|time| time.as_str())
+      .and_then(|time| LocalDateTime::from_str(time).ok());
+
+    if !each(time) {
+      return false;
+    }
+  }
+
+  true
+}
@@ -0,0 +1,165 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use datetime::{ISO, LocalDateTime};
+use serde_json::{json, Value};
+
+use crate::log::Line;
+
+/**
+ * Lets another process pause, resume, query or stop saw while it runs, through a unix socket from
+ * --control-socket PATH, so orchestration can manage a long run without killing it or stopping it with signals
+ * halfway through writing compressed output. Each command is a line, answered with a line of JSON:
+ *   pause   stop reading until resumed, and so writing, since nothing reads ahead of what's written
+ *   resume  carry on
+ *   stats   how many events have been written, the time of the last one and whether saw is paused
+ *   stop    finish the events already read and end as if the sources had, closing the output properly
+ */
+pub struct Control {
+  path: PathBuf,
+  state: Mutex<State>,
+  changed: Condvar,
+  /// set once stop is asked for, and read by --follow, which would otherwise wait for more lines forever
+  stopping: Arc<AtomicBool>,
+  started: Instant,
+}
+
+#[derive(Default)]
+struct State {
+  paused: bool,
+  written: u64,
+  last_time: Option<LocalDateTime>,
+}
+
+impl Control {
+  /**
+   * Start listening on the socket, replacing one left behind by a saw that's no longer running
+   */
+  pub fn listen(path: &Path) -> Arc<Control> {
+    let control = Arc::new(Control {
+      path: path.to_path_buf(),
+      state: Mutex::new(State::default()),
+      changed: Condvar::new(),
+      stopping: Arc::new(AtomicBool::new(false)),
+      started: Instant::now(),
+    });
+
+    listen(control.clone(), path);
+
+    control
+  }
+
+  pub fn describe(path: &Path) -> String {
+    format!("pause, resume, stats and stop on {}", path.display())
+  }
+
+  pub fn stopping(&self) -> Arc<AtomicBool> {
+    self.stopping.clone()
+  }
+
+  /**
+   * Should another line be read? This is asked for every line read, before anything can leave it out, so a run whose
+   * filters leave out everything can still be paused and stopped. Waits while paused, and returns false once stop was
+   * asked for. Once this returns false it must not be called again
+   */
+  pub fn allow(&self) -> bool {
+    let mut state = self.state.lock().expect("Control state was poisoned");
+
+    while state.paused && !self.stopping.load(Ordering::Relaxed) {
+      state = self.changed.wait(state).expect("Control state was poisoned");
+    }
+
+    !self.stopping.load(Ordering::Relaxed)
+  }
+
+  /**
+   * Count a line that's about to be written, for stats
+   */
+  pub fn written(&self, line: &Line) {
+    let mut state = self.state.lock().expect("Control state was poisoned");
+
+    state.written += 1;
+    state.last_time = line.time.or(state.last_time);
+  }
+
+  /**
+   * Remove the socket once done, so nothing tries to talk to a saw that has finished
+   */
+  pub fn close(&self) {
+    let _ = std::fs::remove_file(&self.path);
+  }
+
+  fn answer(&self, command: &str) -> Value {
+    match command {
+      "pause" | "resume" => {
+        self.state.lock().expect("Control state was poisoned").paused = command == "pause";
+        self.changed.notify_all();
+        json!({ "ok": true, "paused": command == "pause" })
+      }
+      "stats" => {
+        let state = self.state.lock().expect("Control state was poisoned");
+
+        json!({
+          "ok": true,
+          "paused": state.paused,
+          "stopping": self.stopping.load(Ordering::Relaxed),
+          "written": state.written,
+          "last_time": state.last_time.map(|time| time.iso().to_string()),
+          "uptime_s": self.started.elapsed().as_secs(),
+        })
+      }
+      "stop" => {
+        self.stopping.store(true, Ordering::Relaxed);
+        self.changed.notify_all();
+        json!({ "ok": true, "stopping": true })
+      }
+      other => json!({ "ok": false, "error": format!("Command '{other}' is not recognized. Use pause, resume, stats or stop") }),
+    }
+  }
+}
+
+#[cfg(unix)]
+fn listen(control: Arc<Control>, path: &Path) {
+  use std::os::unix::net::{UnixListener, UnixStream};
+
+  if path.exists() {
+    if UnixStream::connect(path).is_ok() {
+      panic!("Control socket '{}' is already in use by another process", path.display());
+    }
+
+    // nothing is listening, so it was left behind
+    std::fs::remove_file(path).unwrap_or_else(|err| panic!("Failed to replace control socket '{}': {err}", path.display()));
+  }
+
+  let listener = UnixListener::bind(path).unwrap_or_else(|err| panic!("Failed to listen on control socket '{}': {err}", path.display()));
+
+  thread::spawn(move || {
+    for stream in listener.incoming().flatten() {
+      let control = control.clone();
+
+      // each client gets its own thread, so one left connected doesn't shut the others out
+      thread::spawn(move || {
+        let Ok(mut writer) = stream.try_clone() else {
+          return;
+        };
+
+        for command in BufReader::new(stream).lines().map_while(Result::ok) {
+          let answer = control.answer(command.trim());
+
+          if writeln!(writer, "{answer}").is_err() {
+            break;
+          }
+        }
+      });
+    }
+  });
+}
+
+#[cfg(not(unix))]
+fn listen(_control: Arc<Control>, _path: &Path) {
+  panic!("Option --control-socket needs unix sockets, which this platform doesn't have");
+}
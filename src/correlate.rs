@@ -0,0 +1,213 @@
+use std::collections::{HashMap, VecDeque};
+
+use datetime::{ISO, LocalDateTime};
+use serde_json::{Map, Value};
+
+use crate::filter::lookup;
+use crate::log::Line;
+use crate::pretty::PrettyDescriptor;
+use crate::session::{millis, parse_duration};
+
+/// how long an event waits for its partner unless window=DURATION is given
+const DEFAULT_WINDOW: &str = "5m";
+
+/// most events waiting at once, past this the oldest are given up on even inside the window
+const MAX_PENDING: usize = 100_000;
+
+/**
+ * Pairs events from two sides that name the same thing by different fields, for --correlate LEFT=RIGHT, like a
+ * gateway's 'id' and a backend's 'request_id'. Each side is a dotted path. An event whose RIGHT matches the LEFT of
+ * one waiting, or whose LEFT matches the RIGHT of one waiting, completes a pair, so either may come first. One
+ * event is written for each pair, at the time of the second, with both under 'left' and 'right' and the
+ * milliseconds between them in '_gap_ms'.
+ *
+ * Events wait at most the window for their partner, by the time of the events read rather than the clock, so only
+ * so many are ever held. Those that find none are left out, as are events with neither field, and both are counted
+ * once done.
+ */
+#[derive(Debug)]
+pub struct Correlator {
+  left: String,
+  right: String,
+  window: String,
+  window_ms: i64,
+  /// each waiting event by a number that is never reused, so it can be found from either index
+  pending: HashMap<u64, (i64, Map<String, Value>)>,
+  /// the numbers of waiting events, oldest first, to give up on them in order
+  order: VecDeque<u64>,
+  by_left: HashMap<String, VecDeque<u64>>,
+  by_right: HashMap<String, VecDeque<u64>>,
+  next_id: u64,
+  latest: i64,
+  pairs: u64,
+  unmatched: u64,
+  unrelated: u64,
+}
+
+impl Correlator {
+  /**
+   * Parse LEFT=RIGHT[,window=DURATION], for example id=request_id,window=30s
+   */
+  pub fn parse(raw: &str) -> Result<Correlator, String> {
+    let mut parts = raw.split(',');
+
+    let (left, right) = parts.next().unwrap_or("").split_once('=')
+      .map(|(left, right)| (left.trim(), right.trim()))
+      .filter(|(left, right)| !left.is_empty() && !right.is_empty())
+      .ok_or_else(|| format!("Argument --correlate '{raw}' must be in the form LEFT=RIGHT[,window=DURATION], like id=request_id"))?;
+
+    let mut window = DEFAULT_WINDOW.to_string();
+
+    for part in parts {
+      window = part.trim().strip_prefix("window=")
+        .ok_or_else(|| format!("Correlate option '{part}' is not recognized. The only option is window=DURATION"))?
+        .to_string();
+    }
+
+    Ok(Correlator {
+      left: left.to_string(),
+      right: right.to_string(),
+      window_ms: parse_duration(&window, "--correlate window")?,
+      window,
+      pending: HashMap::new(),
+      order: VecDeque::new(),
+      by_left: HashMap::new(),
+      by_right: HashMap::new(),
+      next_id: 0,
+      latest: i64::MIN,
+      pairs: 0,
+      unmatched: 0,
+      unrelated: 0,
+    })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("'{}' with '{}', within {}", self.left, self.right, self.window)
+  }
+
+  /**
+   * Take an event, and return the pair it completes if it does
+   */
+  pub fn apply(&mut self, mut line: Line) -> Option<Line> {
+    let left_key = lookup(&line.value, &self.left).map(PrettyDescriptor::pretty_value);
+    let right_key = lookup(&line.value, &self.right).map(PrettyDescriptor::pretty_value);
+
+    let Some(time) = line.time.filter(|_| left_key.is_some() || right_key.is_some()) else {
+      self.unrelated += 1;
+      return None;
+    };
+
+    let now = millis(time);
+    self.latest = self.latest.max(now);
+    self.expire();
+
+    if let Some((waited, waiting)) = right_key.as_ref().and_then(|key| Correlator::take(&mut self.pending, &mut self.by_left, key)) {
+      line.value = self.pair(waiting, line.value, now - waited, time);
+      return Some(line);
+    }
+
+    if let Some((waited, waiting)) = left_key.as_ref().and_then(|key| Correlator::take(&mut self.pending, &mut self.by_right, key)) {
+      line.value = self.pair(line.value, waiting, waited - now, time);
+      return Some(line);
+    }
+
+    // it waits on both sides it has, until one of them is matched
+    let id = self.next_id;
+    self.next_id += 1;
+
+    if let Some(key) = left_key {
+      self.by_left.entry(key).or_default().push_back(id);
+    }
+
+    if let Some(key) = right_key {
+      self.by_right.entry(key).or_default().push_back(id);
+    }
+
+    self.pending.insert(id, (now, line.value));
+    self.order.push_back(id);
+
+    None
+  }
+
+  /**
+   * Print how many pairs were made and how many events had no partner to stderr
+   */
+  pub fn report(&self) {
+    let unmatched = self.unmatched + self.pending.len() as u64;
+
+    eprintln!(
+      "Correlated {} pairs of '{}' and '{}'. {unmatched} events found no partner within {}, and {} had neither field",
+      self.pairs, self.left, self.right, self.window, self.unrelated,
+    );
+  }
+
+  /**
+   * The oldest event still waiting with this key in the index, skipping those already paired by their other side
+   */
+  fn take(pending: &mut HashMap<u64, (i64, Map<String, Value>)>, index: &mut HashMap<String, VecDeque<u64>>, key: &str) -> Option<(i64, Map<String, Value>)> {
+    let ids = index.get_mut(key)?;
+
+    let found = loop {
+      match ids.pop_front() {
+        Some(id) => if let Some(waiting) = pending.remove(&id) {
+          break Some(waiting);
+        },
+        None => break None,
+      }
+    };
+
+    if ids.is_empty() {
+      index.remove(key);
+    }
+
+    found
+  }
+
+  fn pair(&mut self, left: Map<String, Value>, right: Map<String, Value>, gap_ms: i64, time: LocalDateTime) -> Map<String, Value> {
+    self.pairs += 1;
+
+    let mut pair = Map::new();
+    pair.insert("time".to_string(), Value::String(time.iso().to_string()));
+    pair.insert("left".to_string(), Value::Object(left));
+    pair.insert("right".to_string(), Value::Object(right));
+    pair.insert("_gap_ms".to_string(), Value::from(gap_ms));
+    pair
+  }
+
+  /**
+   * Give up on every event that has waited longer than the window, or the oldest when too many are waiting
+   */
+  fn expire(&mut self) {
+    let cutoff = self.latest.saturating_sub(self.window_ms);
+
+    while let Some(id) = self.order.front().copied() {
+      let expired = match self.pending.get(&id) {
+        // already paired
+        None => true,
+        Some((time, _)) => *time < cutoff || self.pending.len() > MAX_PENDING,
+      };
+
+      if !expired {
+        break;
+      }
+
+      self.order.pop_front();
+
+      if self.pending.remove(&id).is_some() {
+        self.unmatched += 1;
+      }
+    }
+
+    // the indexes only hold numbers, which are skipped once their event is gone, so they're tidied now and then
+    if self.by_left.len() + self.by_right.len() > 2 * MAX_PENDING {
+      let pending = &self.pending;
+
+      for index in [&mut self.by_left, &mut self.by_right] {
+        index.retain(|_, ids| {
+          ids.retain(|id| pending.contains_key(id));
+          !ids.is_empty()
+        });
+      }
+    }
+  }
+}
@@ -0,0 +1,189 @@
+use std::env;
+use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Map, Value};
+
+/// read when --field-key isn't given
+const KEY_VARIABLE: &str = "SAW_FIELD_KEY";
+
+/// AES-GCM nonces are 96 bits, and are stored in front of the ciphertext
+const NONCE_SIZE: usize = 12;
+
+enum Cipher {
+  Aes128(Box<Aes128Gcm>),
+  Aes256(Box<Aes256Gcm>),
+}
+
+impl Cipher {
+  fn from_key(key: &[u8]) -> Option<Cipher> {
+    match key.len() {
+      16 => Some(Cipher::Aes128(Box::new(Aes128Gcm::new_from_slice(key).ok()?))),
+      32 => Some(Cipher::Aes256(Box::new(Aes256Gcm::new_from_slice(key).ok()?))),
+      _ => None,
+    }
+  }
+
+  fn encrypt(&self, plain: &[u8]) -> Vec<u8> {
+    // both sizes use the same nonce type, so either can make it
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let sealed = match self {
+      Cipher::Aes128(cipher) => cipher.encrypt(&nonce, plain),
+      Cipher::Aes256(cipher) => cipher.encrypt(&nonce, plain),
+    }.expect("Failed to encrypt field");
+
+    nonce.iter().copied().chain(sealed).collect()
+  }
+
+  fn decrypt(&self, sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_SIZE {
+      return None;
+    }
+
+    let (nonce, body) = sealed.split_at(NONCE_SIZE);
+    let nonce = Nonce::from_slice(nonce);
+
+    match self {
+      Cipher::Aes128(cipher) => cipher.decrypt(nonce, body).ok(),
+      Cipher::Aes256(cipher) => cipher.decrypt(nonce, body).ok(),
+    }
+  }
+}
+
+/**
+ * Decrypts and encrypts the values of chosen fields with AES-GCM. Encrypted values are base64 of the 12 byte nonce
+ * followed by the ciphertext and tag, which is what most AES-GCM libraries produce.
+ */
+pub struct FieldCrypto {
+  cipher: Cipher,
+  decrypt: Vec<String>,
+  encrypt: Vec<String>,
+  /// values that couldn't be decrypted. Shared so it can be read after the stage has moved to another thread
+  failures: Arc<AtomicU64>,
+}
+
+impl FieldCrypto {
+  /**
+   * Load the key from SOURCE, which is env:NAME, file:PATH or a path. Without one the key is read from SAW_FIELD_KEY.
+   * Keys are 16 or 32 bytes, given as base64 or hex, or as raw bytes in a file.
+   */
+  pub fn new(source: Option<&str>, decrypt: Vec<String>, encrypt: Vec<String>) -> Result<FieldCrypto, String> {
+    let raw: Vec<u8> = match source {
+      Some(source) if source.starts_with("env:") => {
+        let name = &source[4..];
+
+        env::var(name).map_err(|_| format!("Environment variable '{name}' for --field-key is not set"))?.into_bytes()
+      }
+      Some(source) => {
+        let path = source.strip_prefix("file:").unwrap_or(source);
+
+        fs::read(path).map_err(|_| format!("Failed to read --field-key file '{path}'"))?
+      }
+      None => env::var(KEY_VARIABLE)
+        .map_err(|_| format!("--decrypt-field and --encrypt-field need a key from --field-key or the {KEY_VARIABLE} environment variable"))?
+        .into_bytes(),
+    };
+
+    // text keys are tried first, since a 32 character hex key would otherwise pass as 32 raw bytes
+    let cipher = decode_key(&raw).and_then(|key| Cipher::from_key(&key))
+      .or_else(|| Cipher::from_key(&raw))
+      .ok_or("The key for --decrypt-field and --encrypt-field must be 16 or 32 bytes, given as base64, hex or raw bytes")?;
+
+    Ok(FieldCrypto {
+      cipher,
+      decrypt,
+      encrypt,
+      failures: Arc::default(),
+    })
+  }
+
+  /**
+   * Are any fields decrypted and left that way, rather than encrypted again under the new key?
+   */
+  pub fn writes_plaintext(&self) -> bool {
+    self.decrypt.iter().any(|field| !self.encrypt.contains(field))
+  }
+
+  /**
+   * Encrypting uses a random nonce, so the same value is written differently every time
+   */
+  pub fn encrypts(&self) -> bool {
+    !self.encrypt.is_empty()
+  }
+
+  pub fn failures(&self) -> Arc<AtomicU64> {
+    self.failures.clone()
+  }
+
+  pub fn describe(&self) -> String {
+    let mut parts = vec![];
+
+    if !self.decrypt.is_empty() {
+      parts.push(format!("decrypt {}", self.decrypt.join(", ")));
+    }
+
+    if !self.encrypt.is_empty() {
+      parts.push(format!("encrypt {}", self.encrypt.join(", ")));
+    }
+
+    parts.join("; ")
+  }
+
+  /**
+   * Decrypt fields first, so a field can be decrypted and then encrypted again under a new key by a later run.
+   * Values that can't be decrypted are left as they were and counted.
+   */
+  pub fn apply(&self, values: &mut Map<String, Value>) {
+    for field in &self.decrypt {
+      let Some(Value::String(sealed)) = values.get(field) else {
+        continue;
+      };
+
+      let plain = STANDARD.decode(sealed.trim()).ok()
+        .and_then(|sealed| self.cipher.decrypt(&sealed))
+        .and_then(|plain| String::from_utf8(plain).ok());
+
+      match plain {
+        Some(plain) => {
+          values.insert(field.clone(), Value::String(plain));
+        }
+        None => {
+          self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+      }
+    }
+
+    for field in &self.encrypt {
+      let plain = match values.get(field) {
+        None | Some(Value::Null) => continue,
+        Some(Value::String(plain)) => plain.clone(),
+        // anything else is encrypted as its JSON
+        Some(value) => value.to_string(),
+      };
+
+      let sealed = STANDARD.encode(self.cipher.encrypt(plain.as_bytes()));
+
+      values.insert(field.clone(), Value::String(sealed));
+    }
+  }
+}
+
+fn decode_key(raw: &[u8]) -> Option<Vec<u8>> {
+  let text = std::str::from_utf8(raw).ok()?.trim();
+
+  let is_hex = text.len() % 2 == 0 && text.bytes().all(|byte| byte.is_ascii_hexdigit());
+
+  if is_hex {
+    return (0..text.len()).step_by(2)
+      .map(|start| u8::from_str_radix(&text[start..start + 2], 16).ok())
+      .collect();
+  }
+
+  STANDARD.decode(text).ok()
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use datetime::{ISO, LocalDateTime};
+use serde_json::json;
+
+use crate::log::Line;
+
+/// the exit code used when the deadline stopped saw early, EX_TEMPFAIL from sysexits.h
+pub const DEADLINE_EXIT_CODE: i32 = 75;
+
+/**
+ * Stops reading once a wall clock budget runs out, remembering where to pick up again. This is checked for every event
+ * read, before anything can leave it out, so a run whose filters leave out everything still stops in time.
+ *
+ * Events are merged in time order, so once the deadline passes saw finishes every event with the same time as the
+ * last one read and then stops. Passing the time of the first event left out as the start of --range resumes
+ * exactly where this run ended, without gaps or duplicates.
+ */
+#[derive(Debug)]
+pub struct Deadline {
+  end: Instant,
+  events: u64,
+  last: Option<LocalDateTime>,
+  expired: bool,
+  resume_from: Option<LocalDateTime>,
+}
+
+impl Deadline {
+  pub fn new(budget: Duration) -> Deadline {
+    Deadline {
+      end: Instant::now() + budget,
+      events: 0,
+      last: None,
+      expired: false,
+      resume_from: None,
+    }
+  }
+
+  /**
+   * Parse a duration like 90s, 10m or 2h. A plain number is in seconds
+   */
+  pub fn parse_budget(raw: &str) -> Result<Duration, String> {
+    let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(split);
+
+    let number: u64 = number.parse()
+      .map_err(|_| format!("Argument --deadline must be a number followed by ms, s, m or h, but was '{raw}'"))?;
+
+    match unit {
+      "ms" => Ok(Duration::from_millis(number)),
+      "" | "s" => Ok(Duration::from_secs(number)),
+      "m" => Ok(Duration::from_secs(number * 60)),
+      "h" => Ok(Duration::from_secs(number * 60 * 60)),
+      _ => Err(format!("Argument --deadline must be a number followed by ms, s, m or h, but was '{raw}'")),
+    }
+  }
+
+  pub fn expired(&self) -> bool {
+    self.expired
+  }
+
+  /**
+   * Should this line still be read? Once this returns false it must not be called again
+   */
+  pub fn allow(&mut self, line: &Line) -> bool {
+    let same_time = line.time.is_some() && line.time == self.last;
+
+    if same_time || Instant::now() < self.end {
+      self.events += 1;
+      self.last = line.time;
+      return true;
+    }
+
+    self.expired = true;
+    self.resume_from = line.time;
+    false
+  }
+
+  /**
+   * Tell the user how far saw got, and if writing to a file leave a checkpoint next to it
+   */
+  pub fn report(&self, output: Option<&Path>) {
+    let resume_from = self.resume_from.map(|time| time.iso().to_string());
+
+    if let Some(output) = output {
+      let mut path = output.as_os_str().to_owned();
+      path.push(".checkpoint");
+
+      let checkpoint = json!({
+        "events": self.events,
+        "last": self.last.map(|time| time.iso().to_string()),
+        "resume_from": resume_from,
+      });
+
+      fs::write(&path, checkpoint.to_string() + "\n").expect("Failed to write deadline checkpoint");
+    }
+
+    eprintln!("Deadline reached after reading {} events, output is incomplete", self.events);
+
+    match resume_from {
+      Some(time) => eprintln!("Resume by passing --range {time} with the same MAX as before"),
+      None => eprintln!("The next event has no time, so there is no --range to resume from"),
+    }
+  }
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use datetime::{ISO, LocalDateTime};
+use serde_json::{json, Map, Value};
+
+use crate::checksum::Checksum;
+
+/**
+ * Remembers what each chunk held the last time it was written, in OUTPUT.manifest next to the chunks. A chunk that
+ * comes out the same as last time is thrown away instead of replacing the old file, so the file is left untouched
+ * and anything syncing the directory sees nothing new.
+ *
+ * Chunks are compared by the length and FNV-1a hash of the lines written to them, before compression. With --checksum
+ * the checksum of each chunk as written is kept in the manifest too, and moved into place along with the chunk. The
+ * first and last time in each chunk are kept as well, for saw merge-manifests to catalog.
+ */
+#[derive(Debug)]
+pub struct ChunkDedup {
+  manifest: PathBuf,
+  /// file name of each chunk, to its 'bytes', 'hash', 'first' and 'last', and its checksum with --checksum
+  chunks: Map<String, Value>,
+  checksum: Option<Checksum>,
+  written: u64,
+  skipped: u64,
+}
+
+impl ChunkDedup {
+  pub fn load(base_path: &Path, checksum: Option<Checksum>) -> ChunkDedup {
+    let mut manifest = base_path.as_os_str().to_owned();
+    manifest.push(".manifest");
+    let manifest = PathBuf::from(manifest);
+
+    let chunks = match fs::read_to_string(&manifest) {
+      Ok(raw) => match serde_json::from_str(&raw) {
+        Ok(Value::Object(chunks)) => chunks,
+        _ => {
+          eprintln!("Manifest '{}' is not valid, every chunk will be written", manifest.display());
+          Map::new()
+        }
+      },
+      Err(_) => Map::new(),
+    };
+
+    ChunkDedup { manifest, chunks, checksum, written: 0, skipped: 0 }
+  }
+
+  /**
+   * Where a chunk is written until it's known whether it changed
+   */
+  pub fn staging_path(target: &Path) -> PathBuf {
+    let mut staging = target.as_os_str().to_owned();
+    staging.push(".partial");
+
+    PathBuf::from(staging)
+  }
+
+  /**
+   * Keep the chunk that was just written to its staging path if it's new or changed, or throw it away if the one
+   * already at the target holds the same lines
+   */
+  pub fn finish(&mut self, target: &Path, bytes: u64, hash: u64, span: Option<(LocalDateTime, LocalDateTime)>) {
+    let staging = ChunkDedup::staging_path(target);
+    let name = target.file_name().and_then(|name| name.to_str()).unwrap_or("").to_string();
+    let mut entry = json!({ "bytes": bytes, "hash": format!("{hash:016x}") });
+
+    if let Some((first, last)) = span {
+      entry["first"] = Value::String(first.iso().to_string());
+      entry["last"] = Value::String(last.iso().to_string());
+    }
+    let digest = self.checksum.and_then(|checksum| checksum.take(&staging));
+
+    if target.exists() && self.chunks.get(&name).is_some_and(|old| same_lines(old, &entry)) {
+      fs::remove_file(&staging).unwrap_or_else(|_| panic!("Failed to remove '{}'", staging.display()));
+      self.skipped += 1;
+
+      // the same lines have the same times, but a manifest from before they were kept has none
+      if let Some(old) = self.chunks.get_mut(&name).and_then(Value::as_object_mut) {
+        for key in ["first", "last"] {
+          if let Some(time) = entry.get(key) {
+            old.insert(key.to_string(), time.clone());
+          }
+        }
+      }
+
+      // a chunk kept from a run without --checksum has none yet
+      if let Some(checksum) = self.checksum.filter(|checksum| !checksum.sidecar(target).exists()) {
+        let digest = checksum.hash_file(target);
+
+        checksum.write(target, &digest);
+        self.chunks[&name][checksum.name()] = Value::String(digest);
+      }
+    } else {
+      fs::rename(&staging, target).unwrap_or_else(|_| panic!("Failed to move '{}' to '{}'", staging.display(), target.display()));
+
+      if let (Some(checksum), Some(digest)) = (self.checksum, digest) {
+        checksum.write(target, &digest);
+        entry[checksum.name()] = Value::String(digest);
+      }
+
+      self.chunks.insert(name, entry);
+      self.written += 1;
+    }
+  }
+}
+
+/// whether two manifest entries are for the same lines, whatever else they hold
+fn same_lines(old: &Value, new: &Value) -> bool {
+  old.get("bytes") == new.get("bytes") && old.get("hash") == new.get("hash")
+}
+
+impl Drop for ChunkDedup {
+  fn drop(&mut self) {
+    let manifest = Value::Object(self.chunks.clone());
+
+    fs::write(&self.manifest, manifest.to_string() + "\n")
+      .unwrap_or_else(|_| panic!("Failed to write manifest '{}'", self.manifest.display()));
+
+    eprintln!("Wrote {} chunks, skipped {} that were unchanged since the last run", self.written, self.skipped);
+  }
+}
@@ -0,0 +1,37 @@
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// the reproducible builds convention for the time to use in place of now, in seconds since 1970
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Make everything written depend only on the events read, from --deterministic, so the same sources always give the
+ * same bytes on any machine. Must be called before any pattern is read or source opened
+ */
+pub fn enable() {
+  DETERMINISTIC.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+  DETERMINISTIC.load(Ordering::Relaxed)
+}
+
+/**
+ * The time %now prints, in milliseconds since 1970. With --deterministic that's SOURCE_DATE_EPOCH, so a run can be
+ * repeated exactly, and otherwise None for the clock
+ */
+pub fn fixed_now() -> Option<i64> {
+  if !is_enabled() {
+    return None;
+  }
+
+  let raw = env::var(SOURCE_DATE_EPOCH)
+    .expect("Pattern function %now can only be used with --deterministic when SOURCE_DATE_EPOCH is set to the time to print");
+
+  let seconds: i64 = raw.trim().parse()
+    .unwrap_or_else(|_| panic!("SOURCE_DATE_EPOCH must be a number of seconds since 1970, but was '{raw}'"));
+
+  Some(seconds * 1000)
+}
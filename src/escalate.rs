@@ -0,0 +1,60 @@
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::filter::{Filter, FilterSet};
+
+/// separates a rule's filter from what it sets
+const ARROW: &str = "=>";
+
+/**
+ * A rule that sets fields on events matching a filter, like raising the level of events that were logged at the
+ * wrong one, from --escalate "FILTER => FIELD=VALUE[,FIELD=VALUE]"
+ */
+#[derive(Debug)]
+pub struct Escalation {
+  condition: Filter,
+  set: Vec<(String, Value)>,
+}
+
+impl fmt::Display for Escalation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let set: Vec<String> = self.set.iter().map(|(field, value)| format!("{field}={value}")).collect();
+
+    write!(f, "{} => {}", self.condition, set.join(", "))
+  }
+}
+
+impl Escalation {
+  pub fn parse(raw: &str) -> Result<Escalation, String> {
+    let (filter, set) = raw.split_once(ARROW)
+      .ok_or_else(|| format!("Escalation rule '{raw}' must be in the form \"FILTER => FIELD=VALUE\""))?;
+
+    let condition = FilterSet::parse(filter.trim())?;
+
+    let set: Vec<(String, Value)> = set.split(',')
+      .map(|part| {
+        let (field, value) = part.trim().split_once('=')
+          .ok_or_else(|| format!("Escalation rule '{raw}' must set fields like FIELD=VALUE, not '{}'", part.trim()))?;
+
+        if field.trim().is_empty() {
+          return Err(format!("Escalation rule '{raw}' sets a field without a name"));
+        }
+
+        Ok((field.trim().to_string(), Value::String(value.trim().to_string())))
+      })
+      .collect::<Result<_, String>>()?;
+
+    Ok(Escalation { condition, set })
+  }
+
+  pub fn apply(&self, values: &mut Map<String, Value>) {
+    if !self.condition.matches(values) {
+      return;
+    }
+
+    for (field, value) in &self.set {
+      values.insert(field.clone(), value.clone());
+    }
+  }
+}
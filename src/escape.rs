@@ -0,0 +1,121 @@
+use std::io::Write;
+
+use datetime::LocalDateTime;
+use serde_json::{Map, Value};
+
+use crate::chunk::LogWriter;
+
+/**
+ * How much of a JSON string is escaped beyond what JSON needs, from --escape-json-strings
+ */
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JsonEscape {
+  /// only quotes, backslashes and control characters, with everything else written as UTF-8
+  Never,
+  /// also DEL, the C1 controls and the line and paragraph separators, which some readers take as the end of a line
+  Minimal,
+  /// everything that isn't ASCII, so the output is plain ASCII
+  Ascii,
+}
+
+impl JsonEscape {
+  pub fn parse(raw: &str) -> Result<JsonEscape, String> {
+    match raw {
+      "never" => Ok(JsonEscape::Never),
+      "minimal" => Ok(JsonEscape::Minimal),
+      "ascii" => Ok(JsonEscape::Ascii),
+      _ => Err(format!("Argument --escape-json-strings '{raw}' is not recognized. Use never, minimal or ascii")),
+    }
+  }
+
+  pub fn describe(&self) -> &'static str {
+    match self {
+      JsonEscape::Never => "only what JSON needs",
+      JsonEscape::Minimal => "also DEL, C1 controls and line separators",
+      JsonEscape::Ascii => "everything that isn't ASCII",
+    }
+  }
+
+  fn escapes(&self, c: char) -> bool {
+    match self {
+      JsonEscape::Never => false,
+      JsonEscape::Minimal => matches!(c, '\u{7f}'..='\u{9f}' | '\u{2028}' | '\u{2029}'),
+      JsonEscape::Ascii => !c.is_ascii(),
+    }
+  }
+}
+
+/**
+ * Escapes characters in JSON as it's written, for --escape-json-strings. Outside of strings JSON is only ever ASCII,
+ * so any character that needs escaping is inside one and can be written as \uXXXX where it is, with a surrogate pair
+ * for those past the first plane
+ */
+pub struct EscapeWriter {
+  inner: Box<dyn LogWriter>,
+  escape: JsonEscape,
+  /// the start of a character split between writes
+  partial: Vec<u8>,
+}
+
+impl EscapeWriter {
+  pub fn new(inner: Box<dyn LogWriter>, escape: JsonEscape) -> EscapeWriter {
+    EscapeWriter { inner, escape, partial: vec![] }
+  }
+
+  fn write_text(&mut self, text: &str) -> std::io::Result<()> {
+    let mut copied = 0;
+
+    for (index, c) in text.char_indices() {
+      if !self.escape.escapes(c) {
+        continue;
+      }
+
+      self.inner.write_all(&text.as_bytes()[copied..index])?;
+
+      for unit in c.encode_utf16(&mut [0; 2]) {
+        write!(self.inner, "\\u{unit:04x}")?;
+      }
+
+      copied = index + c.len_utf8();
+    }
+
+    self.inner.write_all(&text.as_bytes()[copied..])
+  }
+}
+
+impl Write for EscapeWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let mut bytes = std::mem::take(&mut self.partial);
+    bytes.extend_from_slice(buf);
+
+    let valid = match std::str::from_utf8(&bytes) {
+      Ok(_) => bytes.len(),
+      // cut short in the middle of a character, which the next write finishes
+      Err(error) if error.error_len().is_none() => error.valid_up_to(),
+      Err(_) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "JSON written was not UTF-8")),
+    };
+
+    self.write_text(std::str::from_utf8(&bytes[..valid]).expect("Checked above"))?;
+    self.partial = bytes[valid..].to_vec();
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+impl LogWriter for EscapeWriter {
+  fn route_event(&mut self, values: &Map<String, Value>) {
+    self.inner.route_event(values);
+  }
+
+  fn start_event(&mut self, time: Option<LocalDateTime>) {
+    self.inner.start_event(time);
+  }
+
+  fn end_line(&mut self) {
+    self.inner.end_line();
+  }
+}
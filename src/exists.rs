@@ -0,0 +1,93 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/**
+ * What to do when a file saw is about to write already exists, from --on-exists. Refusing is the default, since
+ * truncating last week's merged archive can't be undone
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OnExists {
+  #[default]
+  Error,
+  Overwrite,
+  Append,
+  /// move the old file aside to PATH.N and write a new one
+  Rename,
+}
+
+const NAMES: [(&str, OnExists); 4] = [
+  ("error", OnExists::Error),
+  ("overwrite", OnExists::Overwrite),
+  ("append", OnExists::Append),
+  ("rename", OnExists::Rename),
+];
+
+impl OnExists {
+  pub fn parse(raw: &str) -> Result<OnExists, String> {
+    NAMES.iter()
+      .find(|(name, _)| name.eq_ignore_ascii_case(raw.trim()))
+      .map(|(_, policy)| *policy)
+      .ok_or_else(|| {
+        let names: Vec<&str> = NAMES.iter().map(|(name, _)| *name).collect();
+
+        format!("Argument --on-exists '{raw}' is not recognized. Valid options are {}", names.join(", "))
+      })
+  }
+
+  pub fn name(&self) -> &'static str {
+    NAMES.iter().find(|(_, policy)| policy == self).map_or("error", |(name, _)| name)
+  }
+
+  /**
+   * Open a file to write output to, following this policy if it's already there
+   */
+  pub fn create(&self, path: &Path) -> File {
+    if path.exists() {
+      match self {
+        OnExists::Error => panic!(
+          "Output '{}' already exists! Pass --on-exists overwrite, append or rename to write it anyway",
+          path.display(),
+        ),
+        OnExists::Overwrite => {}
+        OnExists::Append => {
+          return OpenOptions::new().append(true).open(path).unwrap_or_else(|_| panic!("Failed to open file '{}'", path.display()));
+        }
+        OnExists::Rename => {
+          let aside = OnExists::free_name(path);
+
+          fs::rename(path, &aside).unwrap_or_else(|_| panic!("Failed to move '{}' to '{}'", path.display(), aside.display()));
+          eprintln!("Moved existing '{}' to '{}'", path.display(), aside.display());
+        }
+      }
+    }
+
+    File::create(path).unwrap_or_else(|_| panic!("Failed to create file '{}'", path.display()))
+  }
+
+  /**
+   * With the error policy, fail before anything is written if any of these already exist, rather than part way
+   * through writing the rest
+   */
+  pub fn check(&self, paths: impl IntoIterator<Item=PathBuf>) {
+    if *self != OnExists::Error {
+      return;
+    }
+
+    if let Some(found) = paths.into_iter().find(|path| path.exists()) {
+      panic!("Output '{}' already exists! Pass --on-exists overwrite, append or rename to write it anyway", found.display());
+    }
+  }
+
+  /// the first of PATH.1, PATH.2 and so on that isn't taken
+  fn free_name(path: &Path) -> PathBuf {
+    (1..)
+      .map(|number| {
+        let mut aside = path.as_os_str().to_owned();
+        aside.push(format!(".{number}"));
+
+        PathBuf::from(aside)
+      })
+      .find(|aside| !aside.exists())
+      .unwrap()
+  }
+}
@@ -0,0 +1,64 @@
+/// the exit code used when --expect-count isn't met, EX_DATAERR from sysexits.h
+pub const EXPECT_EXIT_CODE: i32 = 65;
+
+/**
+ * Bounds on how many events must be written, checked once everything is done
+ */
+#[derive(Debug)]
+pub struct ExpectCount {
+  raw: String,
+  /// inclusive
+  min: Option<u64>,
+  /// inclusive
+  max: Option<u64>,
+}
+
+impl ExpectCount {
+  /**
+   * Parse a bound like >0, >=10, <100, <=100, =5, 5 or a range like 10..100, where either end can be left off
+   */
+  pub fn parse(raw: &str) -> Result<ExpectCount, String> {
+    let trimmed = raw.trim();
+
+    let number = |text: &str| -> Result<u64, String> {
+      text.trim().parse()
+        .map_err(|_| format!("Argument --expect-count '{raw}' must be a count like >0, <=100, =5 or 10..100"))
+    };
+
+    let (min, max) = if let Some((low, high)) = trimmed.split_once("..") {
+      let low = if low.is_empty() { None } else { Some(number(low)?) };
+      // the same as --lines, the end is exclusive
+      let high = if high.is_empty() { None } else { Some(number(high)?.checked_sub(1).ok_or_else(|| format!("Argument --expect-count '{raw}' can never be met"))?) };
+
+      (low, high)
+    } else if let Some(rest) = trimmed.strip_prefix(">=") {
+      (Some(number(rest)?), None)
+    } else if let Some(rest) = trimmed.strip_prefix("<=") {
+      (None, Some(number(rest)?))
+    } else if let Some(rest) = trimmed.strip_prefix('>') {
+      (Some(number(rest)?.checked_add(1).ok_or_else(|| format!("Argument --expect-count '{raw}' can never be met"))?), None)
+    } else if let Some(rest) = trimmed.strip_prefix('<') {
+      (None, Some(number(rest)?.checked_sub(1).ok_or_else(|| format!("Argument --expect-count '{raw}' can never be met"))?))
+    } else {
+      let exact = number(trimmed.strip_prefix('=').unwrap_or(trimmed))?;
+
+      (Some(exact), Some(exact))
+    };
+
+    if let (Some(min), Some(max)) = (min, max) {
+      if min > max {
+        return Err(format!("Argument --expect-count '{raw}' can never be met"));
+      }
+    }
+
+    Ok(ExpectCount { raw: trimmed.to_string(), min, max })
+  }
+
+  pub fn describe(&self) -> &str {
+    &self.raw
+  }
+
+  pub fn is_met(&self, count: u64) -> bool {
+    self.min.is_none_or(|min| count >= min) && self.max.is_none_or(|max| count <= max)
+  }
+}
@@ -0,0 +1,238 @@
+use std::io::{BufReader, Cursor, stdin};
+
+use datetime::ISO;
+use serde_json::{Map, Value};
+
+use crate::args::Arguments;
+use crate::compact;
+use crate::log::{Aggregator, LogFile, ReadOptions};
+use crate::why::MATCHED_BY_KEY;
+
+/**
+ * Run one event through every stage that could leave it out or change it, for --explain-filter EVENT, and print what
+ * each one did. The event is read like a line of a source, so --preset, --input and --time-format apply to it too.
+ * Returns true if the event would be written
+ */
+pub fn explain(mut args: Arguments, raw: &str) -> bool {
+  let text = if raw == "-" {
+    stdin().lines().map_while(Result::ok).find(|line| !line.trim().is_empty()).unwrap_or_default()
+  } else {
+    raw.to_string()
+  };
+
+  println!("Event: {}", text.trim());
+
+  // read without needing a time, so a missing one can be explained rather than only warned about
+  let options = ReadOptions {
+    preset: args.preset,
+    levels: args.levels.filter(|_| args.rewrite_levels),
+    projection: args.projection,
+    keep_order: true,
+    input: args.input,
+    time: args.time,
+    passthrough_invalid: args.passthrough_invalid,
+    lenient_json: args.lenient_json,
+    resync: args.resync,
+    ..ReadOptions::default()
+  };
+
+  let source = LogFile::from_reader(Box::new(BufReader::new(Cursor::new(text))), "<explained event>");
+
+  let Some(mut line) = Aggregator::new(vec![source], options).next() else {
+    return verdict(Some(("reading", "not a valid event")));
+  };
+
+  step("time", &line.time.map_or("none".to_string(), |time| time.iso().to_string()));
+
+  let needs_time = !args.keep_order && args.sort_by.is_none_or(|sort_by| sort_by.uses_time());
+
+  if line.time.is_none() && needs_time {
+    return verdict(Some(("reading", "invalid or missing 'time' field")));
+  }
+
+  if args.range != (None, None) {
+    let (min, max) = args.range;
+    let in_range = line.time.is_some_and(|time| min.is_none_or(|min| time >= min) && max.is_none_or(|max| time < max));
+
+    if !in_range {
+      return verdict(Some(("--range", if line.time.is_some() { "outside the range" } else { "no time" })));
+    }
+
+    step("--range", "kept, in range");
+  }
+
+  if let Some(trace) = &args.trace {
+    let before = line.value.clone();
+
+    if !trace.apply(&mut line.value) {
+      return verdict(Some(("--trace", "another trace")));
+    }
+
+    step("--trace", &format!("kept{}", changes(&before, &line.value)));
+  }
+
+  for escalation in &args.escalations {
+    let before = line.value.clone();
+    escalation.apply(&mut line.value);
+
+    step("--escalate", &format!("{escalation}{}", changes(&before, &line.value)));
+  }
+
+  if let Some(shard) = &args.shard {
+    if !shard.by_key() {
+      step("--shard", "by source, so every event of an owned source is kept");
+    } else if shard.owns_event(&line.value) {
+      step("--shard", "kept, owned by this shard");
+    } else {
+      return verdict(Some(("--shard", "another shard")));
+    }
+  }
+
+  if let Some(tenant) = &mut args.tenant {
+    let before = line.value.clone();
+
+    if !tenant.apply(&mut line.value) {
+      return verdict(Some(("--tenant", "another tenant")));
+    }
+
+    step("--tenant", &format!("kept{}", changes(&before, &line.value)));
+  }
+
+  if let Some(partition) = &args.partition {
+    let before = line.value.clone();
+    partition.apply(&mut line.value);
+
+    step("--partition-hash", &format!("applied{}", changes(&before, &line.value)));
+  }
+
+  if let Some(filter) = &args.filter {
+    match filter.explain(&line.value) {
+      Ok(reasons) => {
+        step("--filter", &format!("kept, matched by {}", reasons.join(", ")));
+
+        if args.why.is_some() {
+          line.value.insert(MATCHED_BY_KEY.to_string(), Value::Array(reasons.into_iter().map(Value::String).collect()));
+        }
+      }
+      Err(reason) => return verdict(Some(("--filter", &format!("{reason} did not match")))),
+    }
+  }
+
+  for translation in &args.translations {
+    let before = line.value.clone();
+    translation.translate(&mut line.value);
+
+    step("--translate", &format!("{translation}{}", changes(&before, &line.value)));
+  }
+
+  if let Some(crypto) = &args.crypto {
+    let before = line.value.clone();
+    crypto.apply(&mut line.value);
+
+    step("--field-key", &format!("{}{}", crypto.describe(), changes(&before, &line.value)));
+  }
+
+  if let Some(secrets) = &args.secrets {
+    let before = line.value.clone();
+
+    if !secrets.apply(&mut line) {
+      return verdict(Some(("--scan-secrets", "holds a secret")));
+    }
+
+    let found = secrets.findings().total();
+    let outcome = if found > 0 { format!("found {found} secrets") } else { "none found".to_string() };
+
+    step("--scan-secrets", &format!("{outcome}{}", changes(&before, &line.value)));
+  }
+
+  if let Some(fingerprint) = &args.fingerprint {
+    let before = line.value.clone();
+    fingerprint.apply(&mut line.value);
+
+    step("--fingerprint", &format!("applied{}", changes(&before, &line.value)));
+  }
+
+  if args.compact {
+    let before = line.value.clone();
+    compact::compact(&mut line.value);
+
+    step("--compact", &format!("applied{}", changes(&before, &line.value)));
+  }
+
+  for truncation in &args.truncations {
+    let before = line.value.clone();
+    truncation.apply(&mut line.value);
+
+    step("--truncate-field", &format!("applied{}", changes(&before, &line.value)));
+  }
+
+  for plugin in &args.plugins {
+    let before = line.value.clone();
+
+    let Some(kept) = plugin.start().apply(vec![line], false).pop() else {
+      return verdict(Some((plugin.flag(), &format!("left out by '{}'", plugin.command))));
+    };
+
+    line = kept;
+    step(plugin.flag(), &format!("kept{}", changes(&before, &line.value)));
+  }
+
+  if let Some(normalize) = args.normalize_time {
+    let before = line.value.clone();
+    normalize.apply(&mut line.value, line.time);
+
+    step("--normalize-time", &format!("applied{}", changes(&before, &line.value)));
+  }
+
+  if !args.routes.is_empty() {
+    match args.routes.iter().find(|route| route.matches(&line.value)) {
+      Some(route) => step("--route", &format!("to {}", route.path.display())),
+      None => step("--route", "matched none, to the output"),
+    }
+  }
+
+  println!("Written: {}", Value::Object(line.value));
+
+  verdict(None)
+}
+
+fn step(stage: &str, outcome: &str) {
+  println!("  {stage:<16} {outcome}");
+}
+
+/**
+ * Print the verdict, with the stage that left the event out and why if one did
+ */
+fn verdict(dropped: Option<(&str, &str)>) -> bool {
+  match dropped {
+    Some((stage, reason)) => {
+      step(stage, &format!("dropped, {reason}"));
+      println!("Verdict: dropped by {stage}");
+      false
+    }
+    None => {
+      println!("Verdict: kept");
+      true
+    }
+  }
+}
+
+/**
+ * The fields a stage added, changed or removed, like ", changed level, added _partition", or nothing
+ */
+fn changes(before: &Map<String, Value>, after: &Map<String, Value>) -> String {
+  let mut changed = vec![];
+
+  for (key, value) in after {
+    match before.get(key) {
+      None => changed.push(format!("added {key}")),
+      Some(old) if old != value => changed.push(format!("changed {key}")),
+      _ => {}
+    }
+  }
+
+  changed.extend(before.keys().filter(|key| !after.contains_key(*key)).map(|key| format!("removed {key}")));
+
+  changed.iter().map(|change| format!(", {change}")).collect()
+}
+
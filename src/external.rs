@@ -0,0 +1,349 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use datetime::LocalDateTime;
+use serde_json::{json, Map, Value};
+
+use crate::log::{FileSource, Line};
+
+/// events sorted in memory at a time by default, before they're written out as a run
+const DEFAULT_RUN: usize = 200_000;
+
+/// what's been sorted so far, rewritten after every run so a stopped sort picks up from the last one
+const MANIFEST: &str = "manifest.json";
+
+/**
+ * Sorts events that aren't in any time order, from --external-sort DIR[,run=N], without holding them all in memory.
+ * The first phase reads N events at a time, sorts them by time and writes each batch to DIR as a sorted run. The second
+ * merges every run at once, reading one event ahead in each. Events with the same time stay in the order they were
+ * read, and events without a time come first.
+ *
+ * DIR keeps a manifest of the runs written so far, so a sort that's stopped partway through, by a crash or ^C, starts
+ * again from its last run when run with the same sources and DIR. Everything saw wrote to DIR is removed once every
+ * event has been merged.
+ */
+#[derive(Debug)]
+pub struct ExternalSort {
+  dir: PathBuf,
+  run: usize,
+}
+
+impl ExternalSort {
+  /**
+   * Parse DIR[,run=N], like /scratch/sort,run=500000
+   */
+  pub fn parse(raw: &str) -> Result<ExternalSort, String> {
+    let mut parts = raw.split(',');
+
+    let dir = parts.next()
+      .filter(|dir| !dir.is_empty())
+      .ok_or_else(|| format!("Argument --external-sort '{raw}' must start with the DIR to write sorted runs to"))?;
+
+    let mut sort = ExternalSort { dir: PathBuf::from(dir), run: DEFAULT_RUN };
+
+    for part in parts {
+      match part.trim().split_once('=') {
+        Some(("run", run)) => {
+          sort.run = run.parse().ok()
+            .filter(|run| *run > 0)
+            .ok_or_else(|| format!("Sort run must be a number of events greater than 0, but was '{run}'"))?;
+        }
+        _ => return Err(format!("External sort option '{part}' is not recognized. The only option is run=N")),
+      }
+    }
+
+    Ok(sort)
+  }
+
+  pub fn describe(&self) -> String {
+    format!("by time in runs of {} events, written to '{}'", self.run, self.dir.display())
+  }
+}
+
+/**
+ * The runs written to DIR so far, for the sources named INPUTS. A run is only added once it's written whole, so
+ * anything in DIR past the last one is left from a sort that stopped partway through it
+ */
+struct Manifest {
+  inputs: Vec<String>,
+  /// how many events each run holds
+  runs: Vec<usize>,
+  /// every event has been read, so only the merge is left
+  complete: bool,
+}
+
+impl Manifest {
+  /// the manifest in DIR for INPUTS, or an empty one when there isn't one or it's for other sources
+  fn load(dir: &Path, inputs: &[String]) -> Manifest {
+    let empty = Manifest { inputs: inputs.to_vec(), runs: vec![], complete: false };
+    let path = dir.join(MANIFEST);
+
+    let Ok(raw) = fs::read_to_string(&path) else {
+      return empty;
+    };
+
+    let Ok(manifest) = serde_json::from_str::<Value>(&raw) else {
+      eprintln!("Warning: sort manifest '{}' is not valid JSON, sorting from the start", path.display());
+      return empty;
+    };
+
+    let found: Option<Vec<String>> = manifest["inputs"].as_array()
+      .and_then(|inputs| inputs.iter().map(|input| input.as_str().map(str::to_string)).collect());
+
+    let runs: Option<Vec<usize>> = manifest["runs"].as_array()
+      .and_then(|runs| runs.iter().map(|run| run.as_u64().map(|run| run as usize)).collect());
+
+    match (found, runs) {
+      (Some(found), Some(runs)) if found == inputs => Manifest {
+        inputs: found,
+        runs,
+        complete: manifest["complete"].as_bool().unwrap_or(false),
+      },
+      _ => {
+        eprintln!("Warning: sort manifest '{}' is for other sources, sorting from the start", path.display());
+        empty
+      }
+    }
+  }
+
+  /// written beside and then moved over the old one, so a crash never leaves half a manifest
+  fn save(&self, dir: &Path) {
+    let path = dir.join(MANIFEST);
+    let staging = dir.join(format!("{MANIFEST}.tmp"));
+
+    let manifest = json!({ "inputs": self.inputs, "runs": self.runs, "complete": self.complete });
+
+    fs::write(&staging, manifest.to_string()).unwrap_or_else(|_| panic!("Failed to write sort manifest '{}'", staging.display()));
+    fs::rename(&staging, &path).unwrap_or_else(|_| panic!("Failed to replace sort manifest '{}'", path.display()));
+  }
+
+  fn events(&self) -> usize {
+    self.runs.iter().sum()
+  }
+}
+
+fn run_path(dir: &Path, index: usize) -> PathBuf {
+  dir.join(format!("run-{index:06}.jsonl"))
+}
+
+/**
+ * Write LINES, already sorted, as one run. Each event is written with its time, as milliseconds so it isn't parsed
+ * again, and where it was read from
+ */
+fn write_run(path: &Path, lines: &[Line]) {
+  let file = File::create(path).unwrap_or_else(|_| panic!("Failed to create sort run '{}'", path.display()));
+  let mut out = BufWriter::new(file);
+
+  for line in lines {
+    let time = line.time.map_or(Value::Null, |time| Value::from(millis(time)));
+    let record = json!({ "time": time, "file": &*line.src.file, "line": line.src.line, "event": line.value });
+
+    serde_json::to_writer(&mut out, &record)
+      .and_then(|_| out.write_all(b"\n").map_err(serde_json::Error::io))
+      .unwrap_or_else(|err| panic!("Failed to write sort run '{}': {err}", path.display()));
+  }
+
+  out.flush().unwrap_or_else(|err| panic!("Failed to write sort run '{}': {err}", path.display()));
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
+
+/**
+ * Reads one run back in the order it was written
+ */
+struct RunReader {
+  path: PathBuf,
+  src: BufReader<File>,
+  /// the name of the last source read, shared by the lines after it that came from the same one
+  file: Arc<str>,
+}
+
+impl RunReader {
+  fn open(path: PathBuf) -> RunReader {
+    let file = File::open(&path).unwrap_or_else(|_| panic!("Failed to open sort run '{}'", path.display()));
+
+    RunReader { path, src: BufReader::new(file), file: Arc::from("") }
+  }
+
+  fn next(&mut self) -> Option<Line> {
+    let mut raw = String::new();
+
+    let read = self.src.read_line(&mut raw)
+      .unwrap_or_else(|err| panic!("Failed to read sort run '{}': {err}", self.path.display()));
+
+    if read == 0 {
+      return None;
+    }
+
+    let record: Map<String, Value> = serde_json::from_str(&raw)
+      .unwrap_or_else(|err| panic!("Sort run '{}' is corrupt, remove it and sort again: {err}", self.path.display()));
+
+    let file = record["file"].as_str().unwrap_or_default();
+
+    if *self.file != *file {
+      self.file = Arc::from(file);
+    }
+
+    let time = record["time"].as_i64()
+      .map(|ms| LocalDateTime::at_ms(ms.div_euclid(1000), ms.rem_euclid(1000) as i16));
+
+    let value = match record.get("event") {
+      Some(Value::Object(value)) => value.clone(),
+      _ => panic!("Sort run '{}' is corrupt, remove it and sort again", self.path.display()),
+    };
+
+    Some(Line { value, time, src: FileSource { file: self.file.clone(), line: record["line"].as_u64().unwrap_or(0) } })
+  }
+}
+
+/**
+ * Merges the runs in DIR, taking the earliest next event of any of them, and the one from the earliest run when they
+ * have the same time, so events with the same time stay in the order they were read
+ */
+struct Merge {
+  runs: Vec<RunReader>,
+  heads: Vec<Option<Line>>,
+  queue: BinaryHeap<Reverse<(Option<LocalDateTime>, usize)>>,
+}
+
+impl Merge {
+  fn open(dir: &Path, count: usize) -> Merge {
+    let mut runs: Vec<RunReader> = (0..count).map(|index| RunReader::open(run_path(dir, index))).collect();
+    let heads: Vec<Option<Line>> = runs.iter_mut().map(RunReader::next).collect();
+
+    let queue = heads.iter()
+      .enumerate()
+      .filter_map(|(index, head)| head.as_ref().map(|line| Reverse((line.time, index))))
+      .collect();
+
+    Merge { runs, heads, queue }
+  }
+
+  fn next(&mut self) -> Option<Line> {
+    let Reverse((_, index)) = self.queue.pop()?;
+
+    let next = self.runs[index].next();
+
+    if let Some(line) = &next {
+      self.queue.push(Reverse((line.time, index)));
+    }
+
+    std::mem::replace(&mut self.heads[index], next)
+  }
+}
+
+/**
+ * Sorts every event of SRC by time. Nothing is written until every event has been read and sorted into runs
+ */
+pub struct ExternalSortStage {
+  src: Box<dyn Iterator<Item=Line>>,
+  sort: ExternalSort,
+  /// the names of the sources, so a manifest left by a sort of other sources isn't picked up
+  inputs: Vec<String>,
+  merge: Option<Merge>,
+  done: bool,
+}
+
+impl ExternalSortStage {
+  pub fn new(src: Box<dyn Iterator<Item=Line>>, sort: ExternalSort, inputs: Vec<String>) -> ExternalSortStage {
+    ExternalSortStage { src, sort, inputs, merge: None, done: false }
+  }
+
+  /**
+   * Read and sort SRC into runs, starting after the last run in the manifest, and return how many runs there are
+   */
+  fn spill(&mut self) -> usize {
+    let dir = self.sort.dir.clone();
+
+    fs::create_dir_all(&dir).unwrap_or_else(|_| panic!("Failed to create sort directory '{}'", dir.display()));
+
+    let mut manifest = Manifest::load(&dir, &self.inputs);
+
+    if manifest.complete {
+      eprintln!("Resuming external sort in '{}': all {} runs were already sorted", dir.display(), manifest.runs.len());
+      return manifest.runs.len();
+    }
+
+    // the events in those runs are read again, as nothing else says where the sources were, but not sorted again
+    let skip = manifest.events();
+
+    if skip > 0 {
+      eprintln!("Resuming external sort in '{}' after {} runs of {skip} events", dir.display(), manifest.runs.len());
+
+      if self.src.by_ref().take(skip).count() < skip {
+        panic!("Sources have fewer events than the sort manifest in '{}' says were read, remove it and sort again", dir.display());
+      }
+    }
+
+    loop {
+      let mut lines: Vec<Line> = self.src.by_ref().take(self.sort.run).collect();
+
+      if lines.is_empty() {
+        break;
+      }
+
+      // stable, so events with the same time stay in the order they were read
+      lines.sort_by_key(|line| line.time);
+
+      write_run(&run_path(&dir, manifest.runs.len()), &lines);
+
+      manifest.runs.push(lines.len());
+      manifest.save(&dir);
+    }
+
+    manifest.complete = true;
+    manifest.save(&dir);
+
+    manifest.runs.len()
+  }
+
+  /// remove the runs and manifest, and DIR too when nothing else is in it
+  fn clean_up(&self, runs: usize) {
+    let dir = &self.sort.dir;
+
+    for index in 0..runs {
+      let _ = fs::remove_file(run_path(dir, index));
+    }
+
+    let _ = fs::remove_file(dir.join(MANIFEST));
+    let _ = fs::remove_dir(dir);
+  }
+}
+
+impl Iterator for ExternalSortStage {
+  type Item = Line;
+
+  fn next(&mut self) -> Option<Line> {
+    if self.done {
+      return None;
+    }
+
+    if self.merge.is_none() {
+      let runs = self.spill();
+      self.merge = Some(Merge::open(&self.sort.dir, runs));
+    }
+
+    let merge = self.merge.as_mut()?;
+
+    match merge.next() {
+      Some(line) => Some(line),
+      None => {
+        let runs = merge.runs.len();
+
+        self.merge = None;
+        self.done = true;
+        self.clean_up(runs);
+
+        None
+      }
+    }
+  }
+}
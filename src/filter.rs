@@ -1,9 +1,32 @@
-use regex::Regex;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::str::FromStr;
+use std::time::Instant;
+
+use datetime::{ISO, LocalDate, LocalDateTime, LocalTime};
+use regex::{Regex, RegexSet};
 use serde_json::{Map, Value};
 
-#[derive(Debug)]
+use crate::ignore::IgnoreRules;
+use crate::levels;
+use crate::patterns;
+
+#[derive(Debug, Default)]
 pub struct FilterSet {
+  /// single filters, all ANDed together
   pub sets: Vec<Filter>,
+  /// filters combined with ||, ! or parentheses, which are checked after every single filter
+  pub expressions: Vec<Expression>,
+  /// fields that must, or must not, be exactly one of a list of values, from --filter-file
+  pub value_sets: Vec<ValueFilter>,
+  /// regex filters on the same field, checked together in one pass. Filled by optimize
+  combined: Vec<CombinedFilter>,
+  /// events that are known noise, from --ignore-pack, which are dropped whatever else matches
+  pub ignore: Option<IgnoreRules>,
 }
 
 #[derive(Debug)]
@@ -11,43 +34,747 @@ pub struct Filter {
   key: String,
   inverse: bool,
   pattern: Regex,
+  kind: FilterKind,
+}
+
+/**
+ * How a filter's pattern is checked, cheapest first
+ */
+#[derive(Debug, PartialEq)]
+enum FilterKind {
+  /// an empty pattern, which only needs the field to exist
+  Exists,
+  /// %FIELD?, which needs the field to be there with any value but null, not only a string
+  Present,
+  /// a pattern without any special characters, which is a plain substring search
+  Literal,
+  /// %FIELD>VALUE and the like, comparing the field as a number or a time
+  Compare(Comparison, Bound),
+  Regex,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Comparison {
+  Less,
+  LessOrEqual,
+  Greater,
+  GreaterOrEqual,
+}
+
+/**
+ * What a field is compared to. Fields are read the same way as the bound, so a number in a string still compares as
+ * a number
+ */
+#[derive(Debug, PartialEq)]
+enum Bound {
+  Number(f64),
+  Time(LocalDateTime),
+}
+
+/**
+ * Filters joined with && and ||, negated with ! and grouped with parentheses, from a single --filter
+ */
+#[derive(Debug)]
+pub enum Expression {
+  Filter(Filter),
+  Not(Box<Expression>),
+  All(Vec<Expression>),
+  Any(Vec<Expression>),
+}
+
+/**
+ * Checks a field against every value listed in a file at once, which stays fast with thousands of values where an
+ * alternation regex would not
+ */
+#[derive(Debug)]
+pub struct ValueFilter {
+  key: String,
+  inverse: bool,
+  path: String,
+  values: HashSet<String>,
+}
+
+/**
+ * How much of a sample a filter kept, and how long it took on each event, for --autotune
+ */
+#[derive(Debug)]
+pub struct Measured {
+  pub filter: String,
+  /// the share of events kept, from 0 to 1
+  pub kept: f64,
+  pub nanos: f64,
+}
+
+impl Measured {
+  /// filters ANDed together are fastest checked lowest rank first: what each one costs over the share it rejects
+  fn rank(&self) -> f64 {
+    self.nanos / (1.0 - self.kept)
+  }
+}
+
+#[derive(Debug)]
+struct CombinedFilter {
+  key: String,
+  set: RegexSet,
+}
+
+impl fmt::Display for Filter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let FilterKind::Compare(comparison, bound) = &self.kind {
+      return write!(f, "%{}{}{bound}", self.key, comparison.symbol());
+    }
+
+    if self.kind == FilterKind::Present {
+      return write!(f, "%{}?", self.key);
+    }
+
+    let op = if self.inverse { "!=" } else { "=" };
+
+    write!(f, "%{}{op}{}", self.key, self.pattern)
+  }
+}
+
+impl fmt::Display for Bound {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Bound::Number(number) => write!(f, "{number}"),
+      Bound::Time(time) => write!(f, "{}", time.iso()),
+    }
+  }
+}
+
+impl fmt::Display for Expression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // anything joined is wrapped when inside something else, so the grouping reads the way it was parsed
+    let nested = |inner: &Expression| match inner {
+      Expression::All(_) | Expression::Any(_) => format!("({inner})"),
+      _ => inner.to_string(),
+    };
+
+    match self {
+      Expression::Filter(filter) => write!(f, "{filter}"),
+      Expression::Not(inner) => write!(f, "!{}", nested(inner)),
+      Expression::All(items) => write!(f, "{}", items.iter().map(nested).collect::<Vec<_>>().join(" && ")),
+      Expression::Any(items) => write!(f, "{}", items.iter().map(nested).collect::<Vec<_>>().join(" || ")),
+    }
+  }
+}
+
+impl fmt::Display for ValueFilter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let op = if self.inverse { "!=" } else { "=" };
+
+    write!(f, "%{}{op}one of {} values in {}", self.key, self.values.len(), self.path)
+  }
 }
 
 lazy_static! {
-  static ref PATTERN: Regex = Regex::new(r"^(%(\w+)(!)?=)?(.*)$").unwrap();
+  static ref PATTERN: Regex = Regex::new(r"^(%([\w.]+)(!)?=)?(.*)$").unwrap();
+  static ref CLAUSE: Regex = Regex::new(r"^(%([\w.]+)(!=|=|>=|<=|>|<|\?$))?(.*)$").unwrap();
 }
 
-impl FilterSet {
+/**
+ * Find a field by its dotted path, like context.userId. A field whose name has dots in it wins over a nested one, and
+ * a number in the path picks from an array
+ */
+pub fn lookup<'a>(line: &'a Map<String, Value>, key: &str) -> Option<&'a Value> {
+  if let Some(value) = line.get(key) {
+    return Some(value);
+  }
+
+  let mut parts = key.split('.');
+  let mut value = line.get(parts.next()?)?;
+
+  for part in parts {
+    value = match value {
+      Value::Object(inner) => inner.get(part)?,
+      Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+      _ => return None,
+    };
+  }
+
+  Some(value)
+}
+
+/**
+ * The value of KEY a filter checks, where a numeric 'level' is its name, since events keep the number they were
+ * written with
+ */
+fn field<'a>(line: &'a Map<String, Value>, key: &str) -> Option<Cow<'a, Value>> {
+  if key == "level" {
+    if let Some(name) = levels::named(line) {
+      return Some(Cow::Owned(Value::String(name.to_string())));
+    }
+  }
 
+  lookup(line, key).map(Cow::Borrowed)
+}
+
+impl Filter {
+  pub fn key(&self) -> &str {
+    &self.key
+  }
+
+  pub fn matches(&self, line: &Map<String, Value>) -> bool {
+    let Some(value) = field(line, &self.key) else {
+      return false;
+    };
+    let value = value.as_ref();
+
+    if self.kind == FilterKind::Present {
+      return !value.is_null();
+    }
+
+    if let FilterKind::Compare(comparison, bound) = &self.kind {
+      return bound.compare(value).is_some_and(|order| comparison.accepts(order));
+    }
+
+    if let Value::String(base) = value {
+      let found = match self.kind {
+        FilterKind::Exists => true,
+        FilterKind::Literal => base.contains(self.pattern.as_str()),
+        _ => self.pattern.is_match(base),
+      };
+
+      found ^ self.inverse
+    } else {
+      false
+    }
+  }
+
+  /// lower is cheaper to check
+  fn cost(&self) -> u8 {
+    match self.kind {
+      FilterKind::Exists | FilterKind::Present => 0,
+      FilterKind::Literal => 1,
+      FilterKind::Compare(..) => 2,
+      FilterKind::Regex => 3,
+    }
+  }
+}
+
+impl Comparison {
+  fn parse(op: &str) -> Option<Comparison> {
+    match op {
+      "<" => Some(Comparison::Less),
+      "<=" => Some(Comparison::LessOrEqual),
+      ">" => Some(Comparison::Greater),
+      ">=" => Some(Comparison::GreaterOrEqual),
+      _ => None,
+    }
+  }
+
+  fn symbol(&self) -> &'static str {
+    match self {
+      Comparison::Less => "<",
+      Comparison::LessOrEqual => "<=",
+      Comparison::Greater => ">",
+      Comparison::GreaterOrEqual => ">=",
+    }
+  }
+
+  fn accepts(&self, order: Ordering) -> bool {
+    match self {
+      Comparison::Less => order == Ordering::Less,
+      Comparison::LessOrEqual => order != Ordering::Greater,
+      Comparison::Greater => order == Ordering::Greater,
+      Comparison::GreaterOrEqual => order != Ordering::Less,
+    }
+  }
+}
+
+impl Bound {
+  /**
+   * A number, or else an ISO8601 date time or date, where a date is its midnight
+   */
+  fn parse(raw: &str) -> Option<Bound> {
+    let raw = raw.trim();
+
+    if let Some(number) = raw.parse::<f64>().ok().filter(|number| number.is_finite()) {
+      return Some(Bound::Number(number));
+    }
+
+    LocalDateTime::from_str(raw).ok()
+      .or_else(|| LocalDate::from_str(raw).ok().map(|date| LocalDateTime::new(date, LocalTime::midnight())))
+      .map(Bound::Time)
+  }
+
+  /**
+   * How the field compares to this bound, or None if it isn't the same kind of value
+   */
+  fn compare(&self, value: &Value) -> Option<Ordering> {
+    match self {
+      Bound::Number(bound) => {
+        let number = match value {
+          Value::Number(number) => number.as_f64()?,
+          Value::String(text) => text.trim().parse::<f64>().ok()?,
+          _ => return None,
+        };
+
+        number.partial_cmp(bound)
+      }
+      Bound::Time(bound) => {
+        let time = LocalDateTime::from_str(value.as_str()?).ok()?;
+
+        Some(time.cmp(bound))
+      }
+    }
+  }
+}
+
+impl Expression {
   pub fn matches(&self, line: &Map<String, Value>) -> bool {
-    self.sets.iter().fold(true, | sum, next | {
-      if sum {
-        if let Some(value) = line.get(&next.key) {
-          if let Some(base) = value.as_str() {
-            next.pattern.is_match(base) ^ next.inverse
-          } else {
-            false
-          }
-        } else {
-          false
+    match self {
+      Expression::Filter(filter) => filter.matches(line),
+      Expression::Not(inner) => !inner.matches(line),
+      Expression::All(items) => items.iter().all(|item| item.matches(line)),
+      Expression::Any(items) => items.iter().any(|item| item.matches(line)),
+    }
+  }
+
+  /**
+   * Every single filter that made this expression match, or None if it didn't. Every branch of an || that matched is
+   * listed, not only the first, and a ! is listed whole since nothing inside it matched
+   */
+  fn explain(&self, line: &Map<String, Value>) -> Option<Vec<String>> {
+    match self {
+      Expression::Filter(filter) => filter.matches(line).then(|| vec![filter.to_string()]),
+      Expression::Not(inner) => (!inner.matches(line)).then(|| vec![self.to_string()]),
+      Expression::All(items) => {
+        let reasons: Option<Vec<Vec<String>>> = items.iter().map(|item| item.explain(line)).collect();
+
+        reasons.map(|reasons| reasons.concat())
+      }
+      Expression::Any(items) => {
+        let reasons: Vec<String> = items.iter().filter_map(|item| item.explain(line)).flatten().collect();
+
+        if reasons.is_empty() { None } else { Some(reasons) }
+      }
+    }
+  }
+
+  fn keys<'a>(&'a self, keys: &mut Vec<&'a str>) {
+    match self {
+      Expression::Filter(filter) => keys.push(&filter.key),
+      Expression::Not(inner) => inner.keys(keys),
+      Expression::All(items) | Expression::Any(items) => items.iter().for_each(|item| item.keys(keys)),
+    }
+  }
+}
+
+/**
+ * Recursive descent over a --filter expression. || binds loosest, then &&, then !
+ */
+struct ExpressionParser<'a> {
+  raw: &'a str,
+  rest: &'a str,
+}
+
+impl<'a> ExpressionParser<'a> {
+  fn fail(&self, problem: &str) -> String {
+    format!("Filter expression {} {problem}. Run saw --help filter for more information", self.raw)
+  }
+
+  fn eat(&mut self, token: &str) -> bool {
+    self.rest = self.rest.trim_start();
+
+    if let Some(rest) = self.rest.strip_prefix(token) {
+      self.rest = rest;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn any(&mut self) -> Result<Expression, String> {
+    let mut items = vec![self.all()?];
+
+    while self.eat("||") {
+      items.push(self.all()?);
+    }
+
+    Ok(if items.len() == 1 { items.pop().unwrap() } else { Expression::Any(items) })
+  }
+
+  fn all(&mut self) -> Result<Expression, String> {
+    let mut items = vec![self.unary()?];
+
+    while self.eat("&&") {
+      items.push(self.unary()?);
+    }
+
+    Ok(if items.len() == 1 { items.pop().unwrap() } else { Expression::All(items) })
+  }
+
+  fn unary(&mut self) -> Result<Expression, String> {
+    if self.eat("!") {
+      return Ok(Expression::Not(Box::new(self.unary()?)));
+    }
+
+    if self.eat("(") {
+      let inner = self.any()?;
+
+      if !self.eat(")") {
+        return Err(self.fail("is missing a closing ')'"));
+      }
+
+      return Ok(inner);
+    }
+
+    self.filter()
+  }
+
+  /**
+   * A single filter runs until a && or || or a ')' it didn't open itself, so patterns can still have groups in them
+   */
+  fn filter(&mut self) -> Result<Expression, String> {
+    self.rest = self.rest.trim_start();
+
+    let mut depth = 0;
+    let mut escaped = false;
+    let mut end = self.rest.len();
+
+    for (index, next) in self.rest.char_indices() {
+      if escaped {
+        escaped = false;
+        continue;
+      }
+
+      let rest = &self.rest[index..];
+
+      match next {
+        '\\' => escaped = true,
+        '(' => depth += 1,
+        ')' if depth == 0 => {
+          end = index;
+          break;
+        }
+        ')' => depth -= 1,
+        _ if depth == 0 && (rest.starts_with("&&") || rest.starts_with("||")) => {
+          end = index;
+          break;
         }
-      } else {
-        false
+        _ => {}
       }
-    })
+    }
+
+    let base = self.rest[..end].trim_end();
+
+    if base.is_empty() {
+      return Err(self.fail(&format!("is missing a filter before '{}'", self.rest)));
+    }
+
+    self.rest = &self.rest[end..];
+
+    Ok(Expression::Filter(FilterSet::parse(base)?))
   }
+}
 
-  pub fn parse(base: &str) -> Filter {
-    let captures = PATTERN.captures(base).expect(&format!("Filter input {base} does not match valid pattern. Run saw --help filter for more information"));
+impl ValueFilter {
+  /**
+   * Parse [%FIELD=|%FIELD!=]PATH and read one value per line from PATH. Blank lines and lines starting with # are
+   * skipped, and values are trimmed
+   */
+  pub fn parse(base: &str) -> Result<ValueFilter, String> {
+    let captures = PATTERN.captures(base).ok_or_else(|| format!("Filter file {base} does not match valid pattern. Run saw --help filter for more information"))?;
 
     let key = captures.get(2).map_or("message", |m| m.as_str()).to_owned();
     let inverse = captures.get(3).is_some();
-    let body = captures.get(4).expect(&format!("Filter input {base} does not match valid pattern. Run saw --help filter for more information"))
+    let path = captures.get(4).map_or("", |m| m.as_str()).to_owned();
+
+    let raw = fs::read_to_string(&path).map_err(|_| format!("Failed to read filter file '{path}'"))?;
+
+    let values: HashSet<String> = raw.lines()
+      .map(str::trim)
+      .filter(|value| !value.is_empty() && !value.starts_with('#'))
+      .map(String::from)
+      .collect();
+
+    if values.is_empty() && !inverse {
+      eprintln!("Warning: filter file '{path}' has no values, so no event can match it");
+    }
+
+    Ok(ValueFilter { key, inverse, path, values })
+  }
+
+  pub fn matches(&self, line: &Map<String, Value>) -> bool {
+    let found = match field(line, &self.key).as_deref() {
+      Some(Value::String(base)) => self.values.contains(base),
+      Some(Value::Number(num)) => self.values.contains(&num.to_string()),
+      _ => return false,
+    };
+
+    found ^ self.inverse
+  }
+}
+
+/**
+ * Check each of ITEMS on every event of SAMPLE, and sort them lowest rank first. The sort is stable, so filters that
+ * measured the same keep the order optimize gave them
+ */
+fn by_rank<T>(
+  items: Vec<T>,
+  sample: &[Map<String, Value>],
+  name: impl Fn(&T) -> String,
+  check: impl Fn(&T, &Map<String, Value>) -> bool,
+) -> (Vec<T>, Vec<Measured>) {
+  let count = sample.len().max(1) as f64;
+
+  let mut measured: Vec<(T, Measured)> = items.into_iter()
+    .map(|item| {
+      let start = Instant::now();
+      let kept = sample.iter().filter(|line| check(&item, line)).count();
+      let nanos = start.elapsed().as_nanos() as f64 / count;
+
+      let measured = Measured { filter: name(&item), kept: kept as f64 / count, nanos };
+      (item, measured)
+    })
+    .collect();
+
+  measured.sort_by(|(_, a), (_, b)| a.rank().total_cmp(&b.rank()));
+  measured.into_iter().unzip()
+}
+
+impl CombinedFilter {
+  fn describe(&self) -> String {
+    self.set.patterns().iter().map(|pattern| format!("%{}={pattern}", self.key)).collect::<Vec<_>>().join(" and ")
+  }
+
+  fn matches(&self, line: &Map<String, Value>) -> bool {
+    if let Some(Value::String(base)) = field(line, &self.key).as_deref() {
+      self.set.matches(base).iter().count() == self.set.len()
+    } else {
+      false
+    }
+  }
+
+  /**
+   * Each pattern as the filter it came from, or the first one that didn't match
+   */
+  fn explain(&self, line: &Map<String, Value>) -> Result<Vec<String>, String> {
+    let describe = |pattern: &String| format!("%{}={pattern}", self.key);
+
+    let Some(value) = field(line, &self.key) else {
+      return Err(describe(&self.set.patterns()[0]));
+    };
+
+    let Value::String(base) = value.as_ref() else {
+      return Err(describe(&self.set.patterns()[0]));
+    };
+
+    let found = self.set.matches(base);
+
+    match self.set.patterns().iter().enumerate().find(|(index, _)| !found.matched(*index)) {
+      Some((_, missed)) => Err(describe(missed)),
+      None => Ok(self.set.patterns().iter().map(describe).collect()),
+    }
+  }
+}
+
+impl FilterSet {
+
+  /**
+   * Add a parsed --filter. Anything only ANDed at the top is split into single filters, so they can be reordered,
+   * combined and checked against an index like any other
+   */
+  pub fn push(&mut self, expression: Expression) {
+    match expression {
+      Expression::Filter(filter) => self.sets.push(filter),
+      Expression::All(items) => items.into_iter().for_each(|item| self.push(item)),
+      other => self.expressions.push(other),
+    }
+  }
+
+  pub fn matches(&self, line: &Map<String, Value>) -> bool {
+    // a hash lookup is cheaper than any pattern, so value sets go first
+    self.value_sets.iter().all(|next| next.matches(line))
+      && self.sets.iter().all(|next| next.matches(line))
+      && self.combined.iter().all(|next| next.matches(line))
+      && self.expressions.iter().all(|next| next.matches(line))
+      && self.ignore.as_ref().is_none_or(|ignore| !ignore.matches(line))
+  }
+
+  /**
+   * Like matches, but says why: every filter that accepted the event, or the first one that rejected it, for --why
+   */
+  pub fn explain(&self, line: &Map<String, Value>) -> Result<Vec<String>, String> {
+    let mut reasons = vec![];
+
+    for next in &self.value_sets {
+      if !next.matches(line) {
+        return Err(next.to_string());
+      }
+
+      reasons.push(next.to_string());
+    }
+
+    for next in &self.sets {
+      if !next.matches(line) {
+        return Err(next.to_string());
+      }
+
+      reasons.push(next.to_string());
+    }
+
+    for next in &self.combined {
+      reasons.extend(next.explain(line)?);
+    }
+
+    for next in &self.expressions {
+      reasons.extend(next.explain(line).ok_or_else(|| next.to_string())?);
+    }
+
+    if let Some(rule) = self.ignore.as_ref().and_then(|ignore| ignore.ignored_by(line)) {
+      return Err(format!("--ignore-pack {rule}"));
+    }
+
+    Ok(reasons)
+  }
+
+  /**
+   * Every field and plain substring that a matching event must contain, for checking against an index
+   */
+  pub fn literals(&self) -> impl Iterator<Item=(&str, &str)> {
+    self.sets.iter()
+      .filter(|filter| filter.kind == FilterKind::Literal && !filter.inverse)
+      .map(|filter| (filter.key.as_str(), filter.pattern.as_str()))
+  }
+
+  /**
+   * Every field that a matching event must hold one of a list of values in, for checking against an index
+   */
+  pub fn allowed(&self) -> impl Iterator<Item=(&str, &HashSet<String>)> {
+    self.value_sets.iter()
+      .filter(|filter| !filter.inverse)
+      .map(|filter| (filter.key.as_str(), &filter.values))
+  }
+
+  /**
+   * Filters are all ANDed together, so their order doesn't change the result. Put the cheap ones first so that
+   * most lines are rejected before any regex runs, and check regexes on the same field together.
+   */
+  /**
+   * Every field any filter looks at, so --parse-only can read them along with the fields it was given
+   */
+  pub fn keys(&self) -> Vec<&str> {
+    let mut keys: Vec<&str> = self.value_sets.iter().map(|filter| filter.key.as_str())
+      .chain(self.sets.iter().map(|filter| filter.key.as_str()))
+      .chain(self.combined.iter().map(|filter| filter.key.as_str()))
+      .chain(self.ignore.iter().flat_map(IgnoreRules::keys))
+      .collect();
+
+    self.expressions.iter().for_each(|expression| expression.keys(&mut keys));
+
+    keys
+  }
+
+  pub fn optimize(&mut self) {
+    self.sets.sort_by_key(Filter::cost);
+
+    let mut keys: Vec<String> = self.sets.iter()
+      .filter(|filter| filter.kind == FilterKind::Regex && !filter.inverse)
+      .map(|filter| filter.key.clone())
+      .collect();
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+      let is_combinable = |filter: &Filter| filter.kind == FilterKind::Regex && !filter.inverse && filter.key == key;
+
+      if self.sets.iter().filter(|filter| is_combinable(filter)).count() < 2 {
+        continue;
+      }
+
+      let patterns: Vec<&str> = self.sets.iter()
+        .filter(|filter| is_combinable(filter))
+        .map(|filter| filter.pattern.as_str())
+        .collect();
+
+      // each pattern already compiled on its own, so only a set too large for the limits fails, and is left uncombined
+      let Ok(set) = patterns::regex_set(&patterns, "Filters") else {
+        continue;
+      };
+
+      self.sets.retain(|filter| !is_combinable(filter));
+      self.combined.push(CombinedFilter { key, set });
+    }
+  }
+
+  /**
+   * Time every filter on SAMPLE, and order each kind of filter so the ones that reject the most for what they cost
+   * are checked first, for --autotune. Filters that keep everything go last. Returns what was measured, in the order
+   * the filters are now checked
+   */
+  pub fn tune(&mut self, sample: &[Map<String, Value>]) -> Vec<Measured> {
+    let (value_sets, mut measured) = by_rank(mem::take(&mut self.value_sets), sample, ValueFilter::to_string, ValueFilter::matches);
+    let (sets, found) = by_rank(mem::take(&mut self.sets), sample, Filter::to_string, Filter::matches);
+    measured.extend(found);
+    let (combined, found) = by_rank(mem::take(&mut self.combined), sample, CombinedFilter::describe, CombinedFilter::matches);
+    measured.extend(found);
+    let (expressions, found) = by_rank(mem::take(&mut self.expressions), sample, Expression::to_string, Expression::matches);
+    measured.extend(found);
+
+    self.value_sets = value_sets;
+    self.sets = sets;
+    self.combined = combined;
+    self.expressions = expressions;
+
+    measured
+  }
+
+  /**
+   * Parse a whole --filter. Only input starting with ( or ! before a %FIELD, or holding && or ||, is an expression,
+   * so a plain pattern like (ERROR|WARN) is still a regex on the message
+   */
+  pub fn parse_expression(raw: &str) -> Result<Expression, String> {
+    let opening = raw.trim_start();
+    let grouped = opening.starts_with(['(', '!']) && opening.trim_start_matches(['(', '!', ' ']).starts_with('%');
+
+    if !grouped && !raw.contains("&&") && !raw.contains("||") {
+      return Ok(Expression::Filter(FilterSet::parse(raw)?));
+    }
+
+    let mut parser = ExpressionParser { raw, rest: raw };
+    let expression = parser.any()?;
+
+    if !parser.rest.trim().is_empty() {
+      return Err(parser.fail(&format!("has an unexpected '{}'", parser.rest.trim())));
+    }
+
+    Ok(expression)
+  }
+
+  pub fn parse(base: &str) -> Result<Filter, String> {
+    let captures = CLAUSE.captures(base).ok_or_else(|| format!("Filter input {base} does not match valid pattern. Run saw --help filter for more information"))?;
+
+    let key = captures.get(2).map_or("message", |m| m.as_str()).to_owned();
+    let op = captures.get(3).map_or("=", |m| m.as_str());
+    let inverse = op == "!=";
+    let body = captures.get(4).ok_or_else(|| format!("Filter input {base} does not match valid pattern. Run saw --help filter for more information"))?
       .as_str();
 
-    let pattern = Regex::new(body).expect(&format!("Filter is not a valid regex according to https://github.com/rust-lang/regex"));
+    if let Some(comparison) = Comparison::parse(op) {
+      let bound = Bound::parse(body)
+        .ok_or_else(|| format!("Filter input {base} must compare %{key} to a number or an ISO8601 date time"))?;
+
+      // never run, the bound is what's compared
+      let pattern = Regex::new(&regex::escape(body)).unwrap();
+
+      return Ok(Filter { key, inverse, pattern, kind: FilterKind::Compare(comparison, bound) });
+    }
+
+    let pattern = patterns::regex(body, "Filter")?;
 
+    let kind = if op == "?" {
+      FilterKind::Present
+    } else if body.is_empty() {
+      FilterKind::Exists
+    } else if regex::escape(body) == body && patterns::options().literals_are_plain() {
+      FilterKind::Literal
+    } else {
+      FilterKind::Regex
+    };
 
-    Filter{key, inverse, pattern}
+    Ok(Filter{key, inverse, pattern, kind})
   }
 }
@@ -0,0 +1,64 @@
+use serde_json::{Map, Value};
+
+const FINGERPRINT_FIELD: &str = "_fingerprint";
+
+// FNV-1a, chosen because it is tiny and, unlike the std hasher, guaranteed to be the same across versions and machines
+pub const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/**
+ * Hash bytes with FNV-1a, for anything that must hash the same on every run and every machine
+ */
+pub fn fnv<Bytes: IntoIterator<Item=u8>>(bytes: Bytes) -> u64 {
+  fnv_extend(FNV_OFFSET, bytes)
+}
+
+/**
+ * Continue a hash from fnv with more bytes, for content that arrives a piece at a time
+ */
+pub fn fnv_extend<Bytes: IntoIterator<Item=u8>>(mut hash: u64, bytes: Bytes) -> u64 {
+  for byte in bytes {
+    hash ^= byte as u64;
+    hash = hash.wrapping_mul(FNV_PRIME);
+  }
+
+  hash
+}
+
+#[derive(Debug)]
+pub struct Fingerprint {
+  fields: Vec<String>,
+}
+
+impl Fingerprint {
+  pub fn parse(raw: &str) -> Result<Fingerprint, String> {
+    let fields: Vec<String> = raw.split(',')
+      .map(|field| field.trim().to_owned())
+      .collect();
+
+    if fields.iter().any(|field| field.is_empty()) {
+      return Err(format!("Argument --fingerprint contains an empty field name in '{raw}'"));
+    }
+
+    Ok(Fingerprint { fields })
+  }
+
+  /**
+   * Hash the selected fields and store the result as '_fingerprint'. Missing fields hash the same as null.
+   */
+  pub fn apply(&self, values: &mut Map<String, Value>) {
+    let mut bytes = Vec::new();
+
+    for field in &self.fields {
+      let value = values.get(field).unwrap_or(&Value::Null);
+      let encoded = serde_json::to_string(value).expect("Failed to encode field for fingerprint");
+
+      // the separator keeps ["ab", "c"] and ["a", "bc"] from colliding
+      bytes.extend(field.bytes().chain([0u8]).chain(encoded.bytes()).chain([0u8]));
+    }
+
+    let hash = fnv(bytes);
+
+    values.insert(FINGERPRINT_FIELD.to_string(), Value::String(format!("{hash:016x}")));
+  }
+}
@@ -0,0 +1,1246 @@
+use serde_json::{json, Value};
+
+/**
+ * One command line flag. Parsing resolves every argument through this table, and both --help and --help-json
+ * are generated from it, so a flag can't be accepted without also being documented.
+ */
+#[derive(Debug)]
+pub struct Flag {
+  pub short: Option<&'static str>,
+  pub long: &'static str,
+  /// names of the values that follow the flag, as shown in --help
+  pub values: &'static [&'static str],
+  /// what kind of value the flag takes, 'none' for plain switches
+  pub kind: &'static str,
+  /// true if the values may be left off, like --pretty without a pattern
+  pub optional: bool,
+  pub repeatable: bool,
+  pub default: Option<&'static str>,
+  pub help: &'static str,
+}
+
+pub const FLAGS: &[Flag] = &[
+  Flag {
+    short: Some("-h"),
+    long: "--help",
+    values: &["TOPIC"],
+    kind: "topic",
+    optional: true,
+    repeatable: false,
+    default: None,
+    help: "Print help. If TOPIC is provided it will give more detail or list the topics",
+  },
+  Flag {
+    short: None,
+    long: "--help-json",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Print every flag and help topic as JSON, for tools that wrap saw",
+  },
+  Flag {
+    short: Some("-v"),
+    long: "--version",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Prints the version of saw",
+  },
+  Flag {
+    short: Some("-p"),
+    long: "--pretty",
+    values: &["PATTERN"],
+    kind: "pattern",
+    optional: true,
+    repeatable: false,
+    default: Some("[%time] %message %prefix/\\n/%stack\\v/, with the level and logger when the first events have them"),
+    help: "Pretty print output as text instead of gzipped json PATTERN is optional and defines a pattern",
+  },
+  Flag {
+    short: None,
+    long: "--pretty-per",
+    values: &["FIELD", "VALUE=PATTERN"],
+    kind: "template",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Pretty print events whose FIELD is VALUE with PATTERN instead. A VALUE of 'default' replaces the --pretty pattern",
+  },
+  Flag {
+    short: None,
+    long: "--table",
+    values: &["FIELDS[,every=N]"],
+    kind: "table",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Print the comma separated FIELDS as aligned columns, resized every N lines",
+  },
+  Flag {
+    short: None,
+    long: "--output-format",
+    values: &["FORMAT"],
+    kind: "format",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write the output in another FORMAT. 'html' writes a searchable report with stats, for sharing, 'indented' writes each event as indented JSON, and 'frames' writes each one after its length",
+  },
+  Flag {
+    short: None,
+    long: "--group-by",
+    values: &["FIELD"],
+    kind: "field",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Pretty print consecutive events with the same FIELD as a block under a header with their count, duration and worst level",
+  },
+  Flag {
+    short: None,
+    long: "--collapse-repeats",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Print a pretty line that's the same as the one before only once, followed by how many times it was repeated",
+  },
+  Flag {
+    short: Some("-f"),
+    long: "--filter",
+    values: &["PATTERN"],
+    kind: "filter",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Filter based on contents, PATTERN defines how and what to match on",
+  },
+  Flag {
+    short: None,
+    long: "--shard",
+    values: &["K/N[,key=FIELD]"],
+    kind: "shard",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Only do shard K of N of the work, split by source or with key=FIELD by the hash of FIELD",
+  },
+  Flag {
+    short: None,
+    long: "--tenant",
+    values: &["NAME[,field=FIELD]"],
+    kind: "tenant",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Keep only the events of tenant NAME, by 'tenant' or FIELD, with every other tenant redacted from them",
+  },  Flag {
+    short: None,
+    long: "--partition-hash",
+    values: &["FIELD%N"],
+    kind: "partition",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Set _partition to a number from 0 to N-1 by the hash of FIELD, for splitting evenly by a field with many values",
+  },
+  Flag {
+    short: None,
+    long: "--sessionize",
+    values: &["by", "FIELD", "gap", "GAP"],
+    kind: "session",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Number the sessions of each FIELD in _session, starting a new one after GAP without events, like by user_id gap 30m",
+  },
+  Flag {
+    short: None,
+    long: "--flag-anomalies",
+    values: &["rate[,by=FIELDS][,window=DURATION][,sensitivity=N]"],
+    kind: "anomaly",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Add _anomaly to events that come while their level and service are far busier than usual",
+  },
+  Flag {
+    short: None,
+    long: "--correlate",
+    values: &["LEFT=RIGHT[,window=DURATION]"],
+    kind: "correlate",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write one event for each pair of events whose LEFT and RIGHT fields match within DURATION, 5m by default",
+  },
+  Flag {
+    short: None,
+    long: "--link-by",
+    values: &["FIELD[,window=N]"],
+    kind: "link",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Number events in _seq and link those with the same FIELD in _prev_seq and _next_seq, looking N events ahead",
+  },
+
+  Flag {
+    short: None,
+    long: "--escalate",
+    values: &["FILTER => FIELD=VALUE"],
+    kind: "rule",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Set fields, like level=FATAL, on events matching FILTER before any --filter runs",
+  },
+  Flag {
+    short: None,
+    long: "--filter-file",
+    values: &["[%FIELD=]PATH"],
+    kind: "filter",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Keep events whose FIELD is exactly one of the values listed in PATH, one per line. Use %FIELD!= to drop them instead",
+  },
+  Flag {
+    short: None,
+    long: "--ignore-pack",
+    values: &["NAMES"],
+    kind: "packs",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Leave out the events known as noise by the comma separated packs NAMES, like kubernetes-probes,aws-elb-healthchecks",
+  },  Flag {
+    short: None,
+    long: "--why",
+    values: &["drops"],
+    kind: "enum",
+    optional: true,
+    repeatable: false,
+    default: None,
+    help: "Add a _matched_by field listing the filters that accepted each event. With drops, note every event left out on stderr",
+  },
+
+  Flag {
+    short: Some("-o"),
+    long: "--output",
+    values: &["PATH"],
+    kind: "path",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Instead of outputting to stdout, pipe results to a file directly, or to the system clipboard with 'clipboard'",
+  },
+  Flag {
+    short: None,
+    long: "--on-exists",
+    values: &["POLICY"],
+    kind: "enum",
+    optional: false,
+    repeatable: false,
+    default: Some("error"),
+    help: "Requires --output option. What to do when an output file already exists: error, overwrite, append or rename",
+  },
+  Flag {
+    short: None,
+    long: "--checksum",
+    values: &["ALGORITHM"],
+    kind: "enum",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Requires --output option. Write the sha256 of every output file and chunk next to it, as PATH.sha256",
+  },
+  Flag {
+    short: None,
+    long: "--deterministic",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write the same bytes for the same sources on every run, leaving out anything that depends on when or where saw runs",
+  },
+  Flag {
+    short: Some("-c"),
+    long: "--chunked",
+    values: &["SIZE"],
+    kind: "size",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Requires --output option. Chunks output into multiple files based on size or number of lines",
+  },
+  Flag {
+    short: None,
+    long: "--skip-unchanged",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Requires --chunked option. Leave chunks that are the same as the last run's untouched, using OUTPUT.manifest",
+  },
+  Flag {
+    short: None,
+    long: "--chunk-workers",
+    values: &["N[,retries=R]"],
+    kind: "workers",
+    optional: false,
+    repeatable: false,
+    default: Some("1,retries=3 with --upload-chunk"),
+    help: "Requires --chunked option. Compress and upload finished chunks on N threads while the next one is written",
+  },
+  Flag {
+    short: None,
+    long: "--upload-chunk",
+    values: &["CMD"],
+    kind: "command",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Requires --chunked option. Run CMD with sh for each finished chunk, with its path in SAW_CHUNK, retrying if it fails",
+  },
+  Flag {
+    short: None,
+    long: "--split-by-time",
+    values: &["INTERVAL"],
+    kind: "duration",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Requires --output option. Write events into OUTPUT/YYYY/MM/DD/HH.log files by their own time, every INTERVAL like 1h",
+  },
+  Flag {
+    short: None,
+    long: "--route",
+    values: &["FILTER => PATH"],
+    kind: "rule",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Write events matching FILTER to PATH instead of the output. The first matching route wins",
+  },
+  Flag {
+    short: Some("-r"),
+    long: "--range",
+    values: &["MIN", "MAX"],
+    kind: "time",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Filters logs to between the two given timestamps, (min is inclusive, max is exclusive)",
+  },
+  Flag {
+    short: None,
+    long: "--daily",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Tell saw that all lines in a single log file have the same date. This way saw can skip whole files that fall outside of range, and read files from different days in parallel.",
+  },
+  Flag {
+    short: None,
+    long: "--timezone",
+    values: &["ZONE"],
+    kind: "zone",
+    optional: false,
+    repeatable: false,
+    default: Some("UTC"),
+    help: "Start days and hours on the wall clock of ZONE, like Europe/Berlin or +05:30, for --daily, --split-by-time and --summary-by",
+  },  Flag {
+    short: None,
+    long: "--async-sources",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Read every file at once on a few threads before merging, for thousands of small files. Needs the async feature",
+  },
+  Flag {
+    short: None,
+    long: "--max-open-files",
+    values: &["N"],
+    kind: "count",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Keep at most N files open at once, opening the rest in the order they seem to start as others finish",
+  },
+  Flag {
+    short: None,
+    long: "--max-cpu",
+    values: &["N"],
+    kind: "count",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Run on at most N cores, even if the machine or the container's CPU quota allows more",
+  },
+  Flag {
+    short: None,
+    long: "--autotune",
+    values: &["SIZE"],
+    kind: "size",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Measure the first SIZE of the local files, then pick the read buffer, filter order and --daily threads",
+  },
+  Flag {
+    short: None,
+    long: "--codec",
+    values: &["SOURCE=CODEC"],
+    kind: "route",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Read sources matching the glob SOURCE as plain, gzip, zstd, zlib or deflate instead of going by their first bytes",
+  },
+  Flag {
+    short: None,
+    long: "--page-cache",
+    values: &["ADVICE"],
+    kind: "enum",
+    optional: false,
+    repeatable: false,
+    default: Some("auto"),
+    help: "Whether what's read from local files is dropped from the page cache: auto for files of 1GB or more, keep or drop",
+  },
+  Flag {
+    short: None,
+    long: "--read-retries",
+    values: &["RETRIES[,backoff=DURATION]"],
+    kind: "retry",
+    optional: false,
+    repeatable: false,
+    default: Some("3,backoff=200ms"),
+    help: "Retry reads from local files that fail for a moment, like EIO or ESTALE over NFS, from the last good byte",
+  },
+  Flag {
+    short: None,
+    long: "--catalog",
+    values: &["CATALOG"],
+    kind: "path",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Read the chunks in a catalog from saw merge-manifests that can have events in --range",
+  },
+  Flag {
+    short: None,
+    long: "--ignore-older-than",
+    values: &["DURATION"],
+    kind: "duration",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Leave out sources last changed, or dated in their path, longer ago than DURATION, like 30d, without opening them",
+  },
+
+  Flag {
+    short: Some("-t"),
+    long: "--translate",
+    values: &["FIELD", "PATTERN"],
+    kind: "translation",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Transform strings before printing them",
+  },
+  Flag {
+    short: None,
+    long: "--translate-if",
+    values: &["FILTER", "FIELD", "PATTERN"],
+    kind: "translation",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Like --translate, but only for events matching FILTER",
+  },
+  Flag {
+    short: None,
+    long: "--trace-ids",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Copy trace and span ids from traceparent or common field names into 'trace_id' and 'span_id'",
+  },
+  Flag {
+    short: None,
+    long: "--trace",
+    values: &["TRACE_ID"],
+    kind: "trace",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Like --trace-ids, but only keep events from the trace TRACE_ID",
+  },
+  Flag {
+    short: None,
+    long: "--decrypt-field",
+    values: &["FIELD"],
+    kind: "field",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Decrypt the AES-GCM encrypted FIELD using the key from --field-key. Can be passed more than once",
+  },
+  Flag {
+    short: None,
+    long: "--encrypt-field",
+    values: &["FIELD"],
+    kind: "field",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Encrypt FIELD with AES-GCM using the key from --field-key. Can be passed more than once",
+  },
+  Flag {
+    short: None,
+    long: "--field-key",
+    values: &["env:NAME|file:PATH"],
+    kind: "key",
+    optional: false,
+    repeatable: false,
+    default: Some("the SAW_FIELD_KEY environment variable"),
+    help: "Where to read the 16 or 32 byte key for --decrypt-field and --encrypt-field, as base64 or hex",
+  },
+  Flag {
+    short: None,
+    long: "--write-decrypted",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Requires --decrypt-field option. Write decrypted fields to JSON and files too, not only to pretty or table output",
+  },
+  Flag {
+    short: None,
+    long: "--scan-secrets",
+    values: &["report|redact|fail"],
+    kind: "enum",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Look for keys, tokens and card numbers in what's written, and report them, redact them or leave them out and fail",
+  },
+
+  Flag {
+    short: None,
+    long: "--fingerprint",
+    values: &["FIELDS"],
+    kind: "fields",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Add a '_fingerprint' field holding a stable hash of the comma separated FIELDS",
+  },
+  Flag {
+    short: None,
+    long: "--compact",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Remove null, empty string, empty array and empty object fields at any depth before writing",
+  },
+  Flag {
+    short: None,
+    long: "--truncate-field",
+    values: &["FIELD=SIZE"],
+    kind: "size",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Cut FIELD down to SIZE bytes, listing its original size in '_truncated'",
+  },
+  Flag {
+    short: None,
+    long: "--normalize-time",
+    values: &["FORMAT"],
+    kind: "enum",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write 'time' as epoch-millis, epoch or rfc3339 in every event, whatever format it was read in",
+  },
+  Flag {
+    short: None,
+    long: "--plugin-filter",
+    values: &["CMD"],
+    kind: "command",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Keep only the events CMD answers true for, sending it one JSON event per line. See --help plugin",
+  },
+  Flag {
+    short: None,
+    long: "--plugin-transform",
+    values: &["CMD"],
+    kind: "command",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Write the JSON event CMD answers with in place of each event, or leave it out for null. See --help plugin",
+  },
+  Flag {
+    short: None,
+    long: "--percentiles",
+    values: &["FIELD"],
+    kind: "field",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Estimate percentiles of the numeric FIELD and print them to stderr once done. Can be passed more than once",
+  },
+  Flag {
+    short: None,
+    long: "--stats",
+    values: &["MODE", "ARGS"],
+    kind: "stats",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write counts instead of events: count, count-by FIELDS, top N FIELDS, heavy N FIELDS, histogram FIELD INTERVAL or hist FIELD BUCKETS",
+  },
+  Flag {
+    short: None,
+    long: "--snapshot-every",
+    values: &["EVERY"],
+    kind: "interval",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write what --stats or --group-by have so far every EVERY events, or every interval like 10s, as well as once done",
+  },
+  Flag {
+    short: None,
+    long: "--summary-by",
+    values: &["day|hour"],
+    kind: "enum",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Once done, print a table of how many events of each level were written per day or hour to stderr",
+  },
+  Flag {
+    short: None,
+    long: "--source-stats",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Once done, print bytes, lines, errors, time span and clock skew of each source to stderr",
+  },
+  Flag {
+    short: None,
+    long: "--provenance",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Start and end the output with records describing saw's version, command line, sources, time span and event count",
+  },
+  Flag {
+    short: None,
+    long: "--deadline",
+    values: &["DURATION"],
+    kind: "duration",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Stop writing after DURATION (like 90s, 10m or 2h), leave a checkpoint and exit with code 75",
+  },
+  Flag {
+    short: None,
+    long: "--verify-sorted-output",
+    values: &["fail|warn"],
+    kind: "enum",
+    optional: true,
+    repeatable: false,
+    default: Some("fail"),
+    help: "Check that events are written in time order, and stop with code 65 or only warn at an event out of order",
+  },
+  Flag {
+    short: None,
+    long: "--control-socket",
+    values: &["PATH"],
+    kind: "path",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Listen on a unix socket at PATH for pause, resume, stats and stop, each answered with a line of JSON",
+  },
+  Flag {
+    short: None,
+    long: "--quota",
+    values: &["FILTER:N[,FILTER:N]"],
+    kind: "quota",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Keep at most N events matching each FILTER, for a balanced sample, and stop once every FILTER has its N",
+  },
+  Flag {
+    short: None,
+    long: "--rate-limit",
+    values: &["RATE[,burst=N][,drop]"],
+    kind: "rate",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write no more than RATE events, like 1000/s, waiting or with 'drop' throwing away the rest",
+  },
+  Flag {
+    short: None,
+    long: "--replay-speed",
+    values: &["SPEED"],
+    kind: "speed",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write events paced by their times, at SPEED like 1x, 10x or realtime",
+  },
+  Flag {
+    short: None,
+    long: "--expect-count",
+    values: &["COUNT"],
+    kind: "count",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Exit with code 65 if the number of events written isn't COUNT, like >0, <=100, =5 or 10..100",
+  },
+  Flag {
+    short: None,
+    long: "--audit",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Once done, print how many events each translation applied to, changed and removed to stderr",
+  },
+  Flag {
+    short: None,
+    long: "--dry-run",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Check all the arguments, open all sources and print what would be done, without reading or writing any logs",
+  },
+  Flag {
+    short: None,
+    long: "--explain-filter",
+    values: &["EVENT"],
+    kind: "json",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Print whether one JSON event, or one read from stdin with -, would be written, and what each step did to it",
+  },
+  Flag {
+    short: Some("-z"),
+    long: "--zip",
+    values: &["true|false"],
+    kind: "bool",
+    optional: false,
+    repeatable: false,
+    default: Some("true if --output is provided and --pretty is not"),
+    help: "Gzip output. Defaults to true if output is provided and false otherwise",
+  },
+  Flag {
+    short: None,
+    long: "--compress",
+    values: &["DEST=CODEC"],
+    kind: "route",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Pick the compression (none, gzip or zstd) for outputs matching the glob DEST, overriding --zip",
+  },
+  Flag {
+    short: Some("-j"),
+    long: "--json",
+    values: &["true|false"],
+    kind: "bool",
+    optional: false,
+    repeatable: false,
+    default: Some("true if --output is provided and --pretty is not"),
+    help: "Output as JSON. Has defaults for all cases. Passing true while also providing pretty is illegal",
+  },
+  Flag {
+    short: None,
+    long: "--command",
+    values: &["CMD"],
+    kind: "command",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Run CMD with sh and read its stdout as a source, and each line of its stderr as an event at the time it was written. Can be passed more than once",
+  },
+  Flag {
+    short: None,
+    long: "--command-with-stderr",
+    values: &["CMD"],
+    kind: "command",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Like --command, but read its stderr as a log like stdout, rather than each line as an event",
+  },
+  Flag {
+    short: None,
+    long: "--stdin-multiplex",
+    values: &["TAGS"],
+    kind: "tags",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Read stdin as one source per comma separated tag, where every line is TAG, a tab and then the line",
+  },
+  Flag {
+    short: None,
+    long: "--ssh",
+    values: &["[USER@]HOST:PATH"],
+    kind: "remote",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Read files matching the glob PATH on a remote HOST over ssh. Can be passed more than once",
+  },
+  Flag {
+    short: None,
+    long: "--keep-order-of-input",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Read sources one after another in the order given instead of merging them by time. The 'time' field becomes optional",
+  },
+  Flag {
+    short: Some("-F"),
+    long: "--follow",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Keep watching the sources for new lines and new files, like tail -f",
+  },
+  Flag {
+    short: None,
+    long: "--poll-interval",
+    values: &["DURATION"],
+    kind: "duration",
+    optional: false,
+    repeatable: false,
+    default: Some("200ms"),
+    help: "Requires --follow option. How long to wait before looking for more lines, longer on network filesystems like NFS",
+  },
+  Flag {
+    short: None,
+    long: "--reload",
+    values: &["FILE"],
+    kind: "path",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Requires --follow option. Swap in the filters, translations and pretty template from a JSON FILE whenever it changes",
+  },
+  Flag {
+    short: None,
+    long: "--drop-older-than",
+    values: &["DURATION"],
+    kind: "duration",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Requires --follow option. Leave out events more than DURATION, like 10m, older than the newest event read so far",
+  },
+  Flag {
+    short: None,
+    long: "--input",
+    values: &["FORMAT"],
+    kind: "input",
+    optional: false,
+    repeatable: false,
+    default: Some("json"),
+    help: "Read lines as json, logfmt or regex:PATTERN with named groups, or read a Loki export or length prefixed frames. Run saw --help input for more",
+  },
+  Flag {
+    short: None,
+    long: "--time-field",
+    values: &["FIELD"],
+    kind: "field",
+    optional: false,
+    repeatable: false,
+    default: Some("time"),
+    help: "Read each event's time from FIELD, which is moved to 'time'",
+  },
+  Flag {
+    short: None,
+    long: "--time-format",
+    values: &["FORMAT"],
+    kind: "format",
+    optional: false,
+    repeatable: true,
+    default: Some("auto"),
+    help: "How times are written: auto, epoch, epochMillis or a pattern like \"%d/%b/%Y:%H:%M:%S %z\", each tried in turn",
+  },
+  Flag {
+    short: None,
+    long: "--time-locale",
+    values: &["LOCALE"],
+    kind: "locale",
+    optional: false,
+    repeatable: false,
+    default: Some("en"),
+    help: "Read month and day names in these languages: en, de, es, fr, it, nl or pt, separated by commas",
+  },
+  Flag {
+    short: None,
+    long: "--level-map",
+    values: &["MAP"],
+    kind: "levels",
+    optional: false,
+    repeatable: false,
+    default: Some("10=TRACE,20=DEBUG,30=INFO,40=WARN,50=ERROR,60=FATAL"),
+    help: "Name numeric levels with comma separated NUMBER=NAME pairs, or none to keep them numbers",
+  },
+  Flag {
+    short: None,
+    long: "--rewrite-levels",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write the names of numeric levels into the events, not only use them for filters and pretty output",
+  },
+  Flag {
+    short: None,
+    long: "--tail",
+    values: &["N"],
+    kind: "count",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Only write the last N events, reading plain files from the end",
+  },
+  Flag {
+    short: None,
+    long: "--reverse",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Write the newest events first",
+  },
+  Flag {
+    short: None,
+    long: "--sort-by",
+    values: &["FIELDS"],
+    kind: "fields",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Merge sources by the comma separated FIELDS, like seq or time,seq, instead of by time",
+  },
+  Flag {
+    short: None,
+    long: "--external-sort",
+    values: &["DIR[,run=N]"],
+    kind: "sort",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Sort sources with no time order by writing sorted runs of N events to DIR and merging them, picking up from the last run if stopped",
+  },
+  Flag {
+    short: None,
+    long: "--passthrough-invalid",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Instead of dropping lines that aren't JSON, turn them into events with the raw line as 'message' and '_unparsed' set to true",
+  },
+  Flag {
+    short: None,
+    long: "--lenient-json",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Accept NaN, Infinity, integers over 64 bits and leading zeros in JSON, keeping the odd ones as strings",
+  },
+  Flag {
+    short: None,
+    long: "--resync",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Skip corrupt data in JSON lines to the next record that can be read, reporting the bytes skipped",
+  },
+  Flag {
+    short: None,
+    long: "--parse-only",
+    values: &["FIELDS"],
+    kind: "fields",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Read only these comma separated top level fields of each event and skip the rest, along with time and any field filters and patterns use",
+  },
+  Flag {
+    short: None,
+    long: "--debug-raw",
+    values: &[],
+    kind: "none",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Print the bytes of every line that can't be read under its warning, like hexdump -C",
+  },
+  Flag {
+    short: None,
+    long: "--copy-raw",
+    values: &["SOURCE=PATH"],
+    kind: "route",
+    optional: false,
+    repeatable: true,
+    default: None,
+    help: "Copy every line read from sources matching the glob SOURCE to PATH, byte for byte",
+  },
+  Flag {
+    short: None,
+    long: "--regex-options",
+    values: &["KEY=VALUE,..."],
+    kind: "options",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Set size, dfa, unicode, multi-line and ignore-case for every regex. Run saw --help filter for more",
+  },
+  Flag {
+    short: None,
+    long: "--pattern-functions",
+    values: &["FILE"],
+    kind: "path",
+    optional: false,
+    repeatable: true,
+    default: Some("the SAW_PATTERN_FUNCTIONS environment variable"),
+    help: "Read functions made of other patterns from the JSON FILE, to call in any pattern. Run saw --help pretty for more",
+  },
+  Flag {
+    short: None,
+    long: "--escape-json-strings",
+    values: &["MODE"],
+    kind: "enum",
+    optional: false,
+    repeatable: false,
+    default: Some("never"),
+    help: "Which characters in JSON strings to write as \\uXXXX: never beyond what JSON needs, minimal or ascii",
+  },
+  Flag {
+    short: None,
+    long: "--key-order",
+    values: &["KEYS"],
+    kind: "fields",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "When writing JSON, put the comma separated KEYS first in the given order, followed by the rest alphabetically",
+  },
+  Flag {
+    short: None,
+    long: "--lines",
+    values: &["START..END"],
+    kind: "span",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Only read lines START (inclusive) to END (exclusive) of each source, counting from 0 as saw's warnings do",
+  },
+  Flag {
+    short: None,
+    long: "--bytes",
+    values: &["START..END"],
+    kind: "span",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Only read lines that start between byte offsets START and END of each source, after decompressing",
+  },
+  Flag {
+    short: None,
+    long: "--preset",
+    values: &["NAME"],
+    kind: "enum",
+    optional: false,
+    repeatable: false,
+    default: None,
+    help: "Read logs written by a well known library (pino, bunyan, logrus, logback) and pretty print them to match",
+  },
+];
+
+/**
+ * Look up a flag by either its short or long name
+ */
+pub fn find(name: &str) -> Option<&'static Flag> {
+  FLAGS.iter().find(|flag| flag.long == name || flag.short == Some(name))
+}
+
+/**
+ * What to say about a flag that doesn't exist, with the one that was probably meant
+ */
+pub fn unknown(name: &str) -> String {
+  match suggest(name) {
+    Some(flag) => format!("Unknown property '{name}', did you mean {}? Run saw with --help to see all known properties", flag.long),
+    None => format!("Unknown property '{name}'. Run saw with --help to see all known properties"),
+  }
+}
+
+/**
+ * The flag most likely meant by a name that isn't one, for a "did you mean" hint. Only flags a typo or two away count,
+ * or ones the name is the start of
+ */
+pub fn suggest(name: &str) -> Option<&'static Flag> {
+  let name = name.trim_start_matches('-');
+
+  if name.is_empty() {
+    return None;
+  }
+
+  let limit = 1 + name.len() / 4;
+
+  FLAGS.iter()
+    .map(|flag| {
+      let long = flag.long.trim_start_matches('-');
+      let distance = if name.len() >= 3 && long.starts_with(name) { 1 } else { distance(name, long) };
+
+      (flag, distance)
+    })
+    .filter(|(_, distance)| *distance <= limit)
+    .min_by_key(|(_, distance)| *distance)
+    .map(|(flag, _)| flag)
+}
+
+/**
+ * How many characters must be added, removed or changed to turn one name into the other
+ */
+fn distance(left: &str, right: &str) -> usize {
+  let right: Vec<char> = right.chars().collect();
+  let mut previous: Vec<usize> = (0..=right.len()).collect();
+
+  for (i, left) in left.chars().enumerate() {
+    let mut current = vec![i + 1];
+
+    for (j, right) in right.iter().enumerate() {
+      let changed = previous[j] + if left == *right { 0 } else { 1 };
+
+      current.push(changed.min(previous[j + 1] + 1).min(current[j] + 1));
+    }
+
+    previous = current;
+  }
+
+  previous[right.len()]
+}
+
+impl Flag {
+  fn usage(&self) -> String {
+    let names = match self.short {
+      Some(short) => format!("  {short}, {}", self.long),
+      None => format!("    {}", self.long),
+    };
+
+    let values = self.values.join(" ");
+
+    let usage = if values.is_empty() {
+      names
+    } else if self.optional {
+      format!("{names} [{values}]")
+    } else {
+      format!("{names} {values}")
+    };
+
+    if usage.len() <= 31 {
+      format!("{usage:<31} {}", self.help)
+    } else {
+      format!("{usage}  {}", self.help)
+    }
+  }
+}
+
+/**
+ * The list of flags shown at the top of --help
+ */
+pub fn usage() -> String {
+  FLAGS.iter()
+    .map(|flag| flag.usage() + "\n")
+    .collect()
+}
+
+/**
+ * Everything --help knows, as JSON. topics are (name, summary, text)
+ */
+pub fn schema(topics: &[(&str, &str, &str)]) -> Value {
+  let flags: Vec<Value> = FLAGS.iter()
+    .map(|flag| json!({
+      "long": flag.long,
+      "short": flag.short,
+      "values": flag.values,
+      "type": flag.kind,
+      "optional": flag.optional,
+      "repeatable": flag.repeatable,
+      "default": flag.default,
+      "help": flag.help,
+    }))
+    .collect();
+
+  let topics: Vec<Value> = topics.iter()
+    .map(|(name, summary, text)| json!({
+      "name": name,
+      "summary": summary,
+      "text": text.trim(),
+    }))
+    .collect();
+
+  json!({
+    "name": "saw",
+    "version": env!("CARGO_PKG_VERSION"),
+    "usage": "saw SOURCE_FILES",
+    "flags": flags,
+    "topics": topics,
+  })
+}
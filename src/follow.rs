@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use glob::glob;
+
+use crate::log::{Line, LogFile, ReadOptions};
+
+/// how long to wait before looking for more lines when every source has been read to its end, unless --poll-interval
+/// says otherwise
+const POLL: Duration = Duration::from_millis(200);
+
+/// how long a quiet source may hold back events newer than its last one, in case it writes an earlier one
+const GRACE: Duration = Duration::from_millis(500);
+
+/// how often the source globs are checked for new files
+const RESCAN: Duration = Duration::from_secs(2);
+
+/**
+ * Merges sources by time like the Aggregator, but never finishes. Plain local files are read to their end and then
+ * watched for more lines, and the source globs are checked again every so often for new files.
+ *
+ * A source that is quiet might still write an event older than the ones waiting in the others, so events newer than
+ * the last one a quiet source wrote are held back until it has been quiet for GRACE. After that they're all written
+ * without waiting, until the source writes again.
+ */
+pub struct Follower {
+  logs: Vec<LogFile>,
+  options: ReadOptions,
+  globs: Vec<String>,
+  /// every file found so far, so a rescan only adds new ones
+  known: HashSet<PathBuf>,
+  last_scan: Instant,
+  /// how long to wait before looking for more lines, from --poll-interval
+  poll: Duration,
+  /// set from --control-socket to end following, which otherwise only ends once every source has
+  stopping: Option<Arc<AtomicBool>>,
+}
+
+impl Follower {
+  pub fn new(mut logs: Vec<LogFile>, options: ReadOptions, globs: Vec<String>) -> Follower {
+    let known = logs.iter().filter_map(|log| log.path().cloned()).collect();
+
+    for log in &mut logs {
+      log.start(options);
+    }
+
+    logs.retain(|log| !log.is_completed());
+
+    Follower { logs, options, globs, known, last_scan: Instant::now(), poll: POLL, stopping: None }
+  }
+
+  /**
+   * End once the flag is set, even while waiting for more lines
+   */
+  pub fn stop_on(mut self, stopping: Arc<AtomicBool>) -> Follower {
+    self.stopping = Some(stopping);
+    self
+  }
+
+  /**
+   * Wait POLL between looks for more lines instead of the default
+   */
+  pub fn poll_every(mut self, poll: Duration) -> Follower {
+    self.poll = poll;
+    self
+  }
+
+  fn rescan(&mut self) {
+    self.last_scan = Instant::now();
+
+    for pattern in &self.globs {
+      let Ok(paths) = glob(pattern) else {
+        continue;
+      };
+
+      for path in paths.flatten() {
+        if !path.is_file() || !self.known.insert(path.clone()) {
+          continue;
+        }
+
+        eprintln!("Following new file '{}'", path.display());
+
+        let mut log = LogFile::from_file(&path);
+        log.start(self.options);
+
+        if !log.is_completed() {
+          self.logs.push(log);
+        }
+      }
+    }
+  }
+}
+
+impl Iterator for Follower {
+  type Item = Line;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      if self.stopping.as_ref().is_some_and(|stopping| stopping.load(Ordering::Relaxed)) {
+        return None;
+      }
+
+      for log in self.logs.iter_mut().filter(|log| log.is_waiting()) {
+        log.advance();
+      }
+
+      self.logs.retain(|log| !log.is_completed());
+
+      if self.last_scan.elapsed() >= RESCAN {
+        self.rescan();
+      }
+
+      // nothing left to read and nowhere for more to come from
+      if self.logs.is_empty() && self.globs.is_empty() {
+        return None;
+      }
+
+      let oldest = self.logs.iter()
+        .enumerate()
+        .filter(|(_, log)| !log.is_waiting())
+        .min_by(|(_, left), (_, right)| left.order(right))
+        .map(|(index, log)| (index, log.next.as_ref().and_then(|line| line.time)));
+
+      if let Some((index, time)) = oldest {
+        let held = self.logs.iter().any(|log| {
+          let quiet_for = log.waited();
+          // merging by anything but time, a quiet source could write an earlier event whatever its last one was
+          let behind = match (time, log.last_time()) {
+            (Some(time), Some(last)) if self.options.sort_by.is_none() => last < time,
+            _ => true,
+          };
+
+          quiet_for.is_some_and(|quiet_for| quiet_for < GRACE) && behind
+        });
+
+        if !held {
+          let oldest = &mut self.logs[index];
+          let line = oldest.take();
+          oldest.advance();
+
+          return Some(line);
+        }
+      }
+
+      sleep(self.poll);
+    }
+  }
+}
+
+/**
+ * Parse a --poll-interval like 500ms or 2s
+ */
+pub fn parse_poll(raw: &str) -> Result<Duration, String> {
+  let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+  let (number, unit) = raw.split_at(split);
+
+  let number: u64 = number.parse().ok()
+    .filter(|number| *number > 0)
+    .ok_or_else(|| format!("Argument --poll-interval must be a whole number greater than 0 followed by ms or s, but was '{raw}'"))?;
+
+  match unit {
+    "ms" => Ok(Duration::from_millis(number)),
+    "s" => Ok(Duration::from_secs(number)),
+    _ => Err(format!("Argument --poll-interval must be a whole number greater than 0 followed by ms or s, but was '{raw}'")),
+  }
+}
@@ -0,0 +1,171 @@
+use std::io::{self, BufRead, Read};
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+
+use crate::chunk::LogWriter;
+use crate::msgpack;
+
+/// the value of --input and --output-format that reads or writes length prefixed frames
+pub const FRAMES_FORMAT: &str = "frames";
+
+/// a length over this is far more likely to be a prefix read with the wrong byte order than a real event
+const MAX_FRAME: u32 = 64 * 1024 * 1024;
+
+/**
+ * Events that are each a 4 byte length followed by that many bytes of JSON or MessagePack, from --input frames or
+ * --output-format frames, as spool files and queues often hold them. Nothing is split on newlines, so an event
+ * written with newlines in it is still one event
+ */
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Framing {
+  little_endian: bool,
+  msgpack: bool,
+}
+
+impl Framing {
+  /**
+   * Parse frames[:be|le][:json|msgpack], or None for any other format. Lengths are big endian and frames hold JSON
+   * unless it says otherwise
+   */
+  pub fn parse(raw: &str) -> Option<Framing> {
+    let mut parts = raw.split(':');
+
+    if parts.next() != Some(FRAMES_FORMAT) {
+      return None;
+    }
+
+    let mut framing = Framing { little_endian: false, msgpack: false };
+
+    for part in parts {
+      match part {
+        "be" => framing.little_endian = false,
+        "le" => framing.little_endian = true,
+        "json" => framing.msgpack = false,
+        "msgpack" => framing.msgpack = true,
+        _ => panic!("Frame option '{part}' in '{raw}' is not recognized. Use be or le for the byte order of the length, and json or msgpack for what's in each frame"),
+      }
+    }
+
+    Some(framing)
+  }
+
+  pub fn describe(&self) -> String {
+    format!(
+      "{} in frames after a {} endian length",
+      if self.msgpack { "MessagePack" } else { "JSON" },
+      if self.little_endian { "little" } else { "big" },
+    )
+  }
+
+  /**
+   * Write an event as one frame, with no newline after it
+   */
+  pub fn write(&self, value: &Map<String, Value>, target: &mut Box<dyn LogWriter>) {
+    let mut payload = vec![];
+
+    if self.msgpack {
+      msgpack::encode(value, &mut payload);
+    } else {
+      serde_json::to_writer(&mut payload, value).expect("Failed to write line");
+    }
+
+    let len = u32::try_from(payload.len()).expect("Cannot write an event of more than 4GB as a frame");
+    let prefix = if self.little_endian { len.to_le_bytes() } else { len.to_be_bytes() };
+
+    target.write_all(&prefix).expect("Failed to write line");
+    target.write_all(&payload).expect("Failed to write line");
+    target.end_frame();
+  }
+}
+
+/**
+ * Reads frames from a source as JSON lines, so everything after opening reads them like any other source, with each
+ * frame a line. Newlines in JSON can only be between values, so they're turned into spaces rather than decoding it
+ */
+pub struct FrameReader {
+  src: Box<dyn BufRead + Send>,
+  framing: Framing,
+  name: Arc<str>,
+  /// the line made from the last frame, and how much of it has been read
+  line: Vec<u8>,
+  read: usize,
+}
+
+impl FrameReader {
+  pub fn new(src: Box<dyn BufRead + Send>, framing: Framing, name: Arc<str>) -> FrameReader {
+    FrameReader { src, framing, name, line: vec![], read: 0 }
+  }
+
+  /**
+   * Read the next frame into a line, false at the end of the source. A source cut off partway through a frame keeps
+   * every frame before it, like a log cut off partway through a line
+   */
+  fn next_frame(&mut self) -> io::Result<bool> {
+    let mut prefix = [0u8; 4];
+
+    if !self.read_full(&mut prefix, true)? {
+      return Ok(false);
+    }
+
+    let len = if self.framing.little_endian { u32::from_le_bytes(prefix) } else { u32::from_be_bytes(prefix) };
+
+    if len > MAX_FRAME {
+      panic!(
+        "Frame in file {} is {len} bytes long, which is more likely a length read with the wrong byte order. Try --input {}",
+        self.name, if self.framing.little_endian { "frames:be" } else { "frames:le" },
+      );
+    }
+
+    let mut payload = vec![0u8; len as usize];
+
+    if !self.read_full(&mut payload, false)? {
+      return Ok(false);
+    }
+
+    self.line.clear();
+    self.read = 0;
+
+    match self.framing.msgpack.then(|| msgpack::decode(&payload)) {
+      Some(Some(value)) => serde_json::to_writer(&mut self.line, &value).expect("Failed to write line"),
+      // a frame that can't be decoded is still a line, so it's reported as invalid like any other
+      _ => self.line.extend(payload.iter().map(|byte| if matches!(byte, b'\n' | b'\r') { b' ' } else { *byte })),
+    }
+
+    self.line.push(b'\n');
+
+    Ok(true)
+  }
+
+  /// fill BUF, false if the source ends first, warning unless it ends right before a frame, AT_START
+  fn read_full(&mut self, buf: &mut [u8], at_start: bool) -> io::Result<bool> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+      match self.src.read(&mut buf[filled..])? {
+        0 if filled == 0 && at_start => return Ok(false),
+        0 => {
+          eprintln!("Warning: file '{}' ends partway through a frame, skipping the rest of it", self.name);
+          return Ok(false);
+        }
+        read => filled += read,
+      }
+    }
+
+    Ok(true)
+  }
+}
+
+impl Read for FrameReader {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.read == self.line.len() && !self.next_frame()? {
+      return Ok(0);
+    }
+
+    let read = buf.len().min(self.line.len() - self.read);
+    buf[..read].copy_from_slice(&self.line[self.read..self.read + read]);
+    self.read += read;
+
+    Ok(read)
+  }
+}
@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+
+use serde_json::Value;
+
+use crate::pretty::PrettyDescriptor;
+
+/// where functions are read from when --pattern-functions isn't given
+const FUNCTIONS_VARIABLE: &str = "SAW_PATTERN_FUNCTIONS";
+
+/// names patterns already give a meaning to, which a function can't take
+const BUILT_IN: [&str; 11] = ["prefix", "replace", "replaceAll", "except", "env", "now", "rest", "pad", "padLeft", "truncate", "wrap"];
+
+/// most calls that can be inside one another, which only a function that calls itself ever reaches
+const MAX_DEPTH: usize = 32;
+
+lazy_static! {
+  static ref FUNCTIONS: RwLock<Vec<(String, PatternFunction)>> = RwLock::new(vec![]);
+}
+
+thread_local! {
+  /// the arguments of each function whose pattern is being parsed, innermost last
+  static SCOPES: RefCell<Vec<Vec<(String, PrettyDescriptor)>>> = const { RefCell::new(vec![]) };
+}
+
+/**
+ * A function for patterns that is made of other patterns, from --pattern-functions FILE. FILE is JSON, and each key
+ * names a function, with either its pattern or its parameters and pattern:
+ *   {"stamp": "[%time] %level", "tagged": {"params": ["tag", "body"], "pattern": "<%tag> %body"}}
+ *
+ * which can then be used in any pattern, like "%stamp/ %message" or "%tagged/%service\v/%message\v/". Inside the
+ * pattern a parameter is whatever pattern was passed for it, and every other variable is a field like anywhere else.
+ */
+#[derive(Debug, Clone)]
+pub struct PatternFunction {
+  params: Vec<String>,
+  pattern: String,
+}
+
+/**
+ * Read every function in FILE, so patterns parsed after this can call them
+ */
+pub fn load(path: &Path) -> Result<(), String> {
+  let raw = fs::read_to_string(path).map_err(|err| format!("Failed to read pattern functions {}: {err}", path.display()))?;
+  let config: Value = serde_json::from_str(&raw).map_err(|err| format!("Pattern functions {} are not valid JSON: {err}", path.display()))?;
+
+  let Value::Object(config) = config else {
+    return Err(format!("Pattern functions {} must be a JSON object of NAME to PATTERN", path.display()));
+  };
+
+  let mut functions = FUNCTIONS.write().expect("Pattern functions were poisoned");
+
+  for (name, value) in config {
+    let function = match value {
+      Value::String(pattern) => PatternFunction { params: vec![], pattern },
+      Value::Object(definition) => {
+        let params = match definition.get("params") {
+          None => vec![],
+          Some(Value::Array(params)) => params.iter()
+            .map(|param| param.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("Pattern function '{name}' must list its params as strings"))?,
+          Some(_) => return Err(format!("Pattern function '{name}' must list its params as strings")),
+        };
+
+        let pattern = definition.get("pattern")
+          .and_then(Value::as_str)
+          .ok_or_else(|| format!("Pattern function '{name}' must have a 'pattern'"))?
+          .to_string();
+
+        PatternFunction { params, pattern }
+      }
+      _ => return Err(format!("Pattern function '{name}' must be a pattern, or an object with 'params' and a 'pattern'")),
+    };
+
+    if !is_identifier(&name) || BUILT_IN.contains(&name.as_str()) {
+      return Err(format!("Pattern function '{name}' must be named with letters, numbers and underscores, and not be one of {}", BUILT_IN.join(", ")));
+    }
+
+    if functions.iter().any(|(existing, _)| *existing == name) {
+      return Err(format!("Pattern function '{name}' is defined twice"));
+    }
+
+    for (index, param) in function.params.iter().enumerate() {
+      if !is_identifier(param) || param == "rest" || function.params[..index].contains(param) {
+        return Err(format!("Param '{param}' of pattern function '{name}' must be named with letters, numbers and underscores, and only once"));
+      }
+    }
+
+    functions.push((name, function));
+  }
+
+  Ok(())
+}
+
+/**
+ * Read the functions in the file SAW_PATTERN_FUNCTIONS names, if it's set
+ */
+pub fn load_default() -> Result<(), String> {
+  match env::var(FUNCTIONS_VARIABLE) {
+    Ok(path) => load(Path::new(&path)),
+    Err(_) => Ok(()),
+  }
+}
+
+pub fn find(name: &str) -> Option<PatternFunction> {
+  FUNCTIONS.read().expect("Pattern functions were poisoned")
+    .iter()
+    .find(|(existing, _)| existing == name)
+    .map(|(_, function)| function.clone())
+}
+
+/**
+ * Every function that was loaded, like "stamp, tagged(tag, body)", or None if there aren't any
+ */
+pub fn describe() -> Option<String> {
+  let functions = FUNCTIONS.read().expect("Pattern functions were poisoned");
+
+  if functions.is_empty() {
+    return None;
+  }
+
+  let described = functions.iter()
+    .map(|(name, function)| if function.params.is_empty() {
+      name.clone()
+    } else {
+      format!("{name}({})", function.params.join(", "))
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  Some(described)
+}
+
+impl PatternFunction {
+  pub fn params(&self) -> usize {
+    self.params.len()
+  }
+
+  /**
+   * Parse the pattern of this function with its params standing for these arguments
+   */
+  pub fn call(&self, name: &str, arguments: Vec<PrettyDescriptor>) -> Result<PrettyDescriptor, String> {
+    let depth = SCOPES.with(|scopes| scopes.borrow().len());
+
+    if depth >= MAX_DEPTH {
+      return Err(format!("Pattern function '{name}' calls itself, directly or through other functions"));
+    }
+
+    let scope = self.params.iter().cloned().zip(arguments).collect();
+    SCOPES.with(|scopes| scopes.borrow_mut().push(scope));
+
+    // the scope is left however parsing ends, since a mistake in one argument is reported with the others
+    let _leave = Leave;
+
+    PrettyDescriptor::parse(&self.pattern)
+  }
+}
+
+/**
+ * The pattern passed for a param of the function being parsed, if NAME is one
+ */
+pub fn argument(name: &str) -> Option<PrettyDescriptor> {
+  SCOPES.with(|scopes| {
+    scopes.borrow().last()?
+      .iter()
+      .find(|(param, _)| param == name)
+      .map(|(_, argument)| argument.clone())
+  })
+}
+
+struct Leave;
+
+impl Drop for Leave {
+  fn drop(&mut self) {
+    SCOPES.with(|scopes| scopes.borrow_mut().pop());
+  }
+}
+
+fn is_identifier(name: &str) -> bool {
+  !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,126 @@
+use datetime::LocalDateTime;
+use serde_json::{Map, Value};
+
+use crate::chunk::LogWriter;
+use crate::pretty::PrettyDescriptor;
+
+/// levels from least to most severe, compared ignoring case
+const SEVERITY: [&[&str]; 6] = [
+  &["trace"],
+  &["debug"],
+  &["info"],
+  &["warn", "warning"],
+  &["error", "err"],
+  &["fatal", "critical", "crit", "panic"],
+];
+
+/**
+ * Gathers consecutive events that share a value of one field, like a request id, and writes them as a block under a
+ * header with how many there were, how long they took and the worst level among them. Events without the field end
+ * the current block and are written as they are.
+ */
+#[derive(Debug)]
+pub struct Grouping {
+  field: String,
+  key: Option<String>,
+  /// (rendered event, time, level) of every event in the current block
+  events: Vec<(String, Option<LocalDateTime>, Option<String>)>,
+}
+
+impl Grouping {
+  pub fn new(field: String) -> Grouping {
+    Grouping { field, key: None, events: vec![] }
+  }
+
+  pub fn field(&self) -> &str {
+    &self.field
+  }
+
+  pub fn add(&mut self, values: &Map<String, Value>, time: Option<LocalDateTime>, rendered: String, target: &mut Box<dyn LogWriter>) {
+    let key = values.get(&self.field).map(PrettyDescriptor::pretty_value).filter(|key| !key.is_empty());
+
+    if key != self.key {
+      self.flush(target);
+      self.key = key;
+    }
+
+    if self.key.is_some() {
+      let level = values.get("level").map(PrettyDescriptor::pretty_value);
+
+      self.events.push((rendered, time, level));
+    } else {
+      target.write_all(rendered.as_bytes()).expect("Failed to write");
+      target.end_line();
+    }
+  }
+
+  /**
+   * Write the current block, if there is one
+   */
+  pub fn flush(&mut self, target: &mut Box<dyn LogWriter>) {
+    let Some(key) = &self.key else {
+      return;
+    };
+
+    if self.events.is_empty() {
+      return;
+    }
+
+    let mut header = format!("{}={key}  {} event{}", self.field, self.events.len(), if self.events.len() == 1 { "" } else { "s" });
+
+    let first = self.events.iter().filter_map(|(_, time, _)| *time).min();
+    let last = self.events.iter().filter_map(|(_, time, _)| *time).max();
+
+    if let (Some(first), Some(last)) = (first, last) {
+      header.push_str(&format!(" over {}", describe_duration(millis(last) - millis(first))));
+    }
+
+    let worst = self.events.iter()
+      .filter_map(|(_, _, level)| level.as_ref())
+      .filter_map(|level| severity(level).map(|rank| (rank, level)))
+      .max_by_key(|(rank, _)| *rank);
+
+    if let Some((_, level)) = worst {
+      header.push_str(&format!(", worst {level}"));
+    }
+
+    target.write_all(header.as_bytes()).expect("Failed to write");
+    target.end_line();
+
+    let count = self.events.len();
+
+    for (index, (rendered, _, _)) in self.events.drain(..).enumerate() {
+      let is_last = index + 1 == count;
+      let (branch, stem) = if is_last { ("  └─ ", "     ") } else { ("  ├─ ", "  │  ") };
+
+      // an event that prints over several lines, like one with a stack, stays inside its branch
+      for (number, line) in rendered.trim_end_matches('\n').split('\n').enumerate() {
+        let prefix = if number == 0 { branch } else { stem };
+
+        target.write_all(prefix.as_bytes()).expect("Failed to write");
+        target.write_all(line.as_bytes()).expect("Failed to write");
+        target.end_line();
+      }
+    }
+  }
+}
+
+fn severity(level: &str) -> Option<usize> {
+  let level = level.to_lowercase();
+
+  SEVERITY.iter().position(|names| names.contains(&level.as_str()))
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
+
+fn describe_duration(ms: i64) -> String {
+  match ms {
+    0..=999 => format!("{ms}ms"),
+    1000..=59_999 => format!("{:.3}s", ms as f64 / 1000.0),
+    _ => format!("{}m{:02}s", ms / 60_000, (ms % 60_000) / 1000),
+  }
+}
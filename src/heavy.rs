@@ -0,0 +1,144 @@
+use std::collections::{BTreeSet, HashMap};
+
+use regex::{Captures, Regex};
+
+use crate::fingerprint::{fnv, fnv_extend};
+
+/// counters in each row of the sketch. A count is over by at most e / WIDTH of every event, almost always
+const WIDTH: usize = 2048;
+
+/// rows of the sketch, each hashing differently, so a count is only wrong when it collides in every one
+const DEPTH: usize = 4;
+
+/// values kept as candidates for each one that is asked for, so a value that is common late still gets its place
+const CANDIDATES_PER_RESULT: usize = 4;
+
+/// the field that stands for 'message' with everything that changes between events of the same kind taken out
+pub const MESSAGE_TEMPLATE: &str = "message-template";
+
+lazy_static! {
+  static ref UUID: Regex = Regex::new(r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b").unwrap();
+  static ref HEX: Regex = Regex::new(r"\b(?:0x[0-9a-fA-F]+|[0-9a-fA-F]{8,})\b").unwrap();
+  static ref QUOTED: Regex = Regex::new(r#""[^"]*"|'[^']*'"#).unwrap();
+  static ref NUMBER: Regex = Regex::new(r"\d+(?:[.:]\d+)*").unwrap();
+}
+
+/**
+ * The N most common values of some fields, found with a count-min sketch instead of a count of every value, for
+ * fields with too many distinct values to count them all, like user ids or messages. Memory stays the same however
+ * many values there are: the sketch estimates how often each value was seen, and only the values estimated to be
+ * the most common are remembered, so counts are never under and only over by a little, and a value that is only
+ * common right at the end can be missed when it's close to the last place.
+ */
+#[derive(Debug)]
+pub struct HeavyHitters {
+  count: usize,
+  sketch: Vec<[u64; WIDTH]>,
+  total: u64,
+  candidates: HashMap<Vec<Option<String>>, u64>,
+  /// the candidates by their count, least common first, to know which to drop for a more common one
+  ranked: BTreeSet<(u64, Vec<Option<String>>)>,
+}
+
+impl HeavyHitters {
+  pub fn new(count: usize) -> HeavyHitters {
+    HeavyHitters {
+      count,
+      sketch: vec![[0; WIDTH]; DEPTH],
+      total: 0,
+      candidates: HashMap::new(),
+      ranked: BTreeSet::new(),
+    }
+  }
+
+  pub fn add(&mut self, key: Vec<Option<String>>) {
+    self.total += 1;
+
+    let cells = cells(&key);
+    let lowest = cells.iter().enumerate().map(|(row, cell)| self.sketch[row][*cell]).min().unwrap_or(0);
+
+    // only the cells at the lowest count go up, since the others already count other values too
+    for (row, cell) in cells.iter().enumerate() {
+      if self.sketch[row][*cell] == lowest {
+        self.sketch[row][*cell] += 1;
+      }
+    }
+
+    let estimate = lowest + 1;
+
+    if let Some(previous) = self.candidates.get_mut(&key) {
+      self.ranked.remove(&(*previous, key.clone()));
+      *previous = estimate;
+      self.ranked.insert((estimate, key));
+      return;
+    }
+
+    if self.candidates.len() >= self.count * CANDIDATES_PER_RESULT {
+      match self.ranked.first() {
+        Some((least, _)) if *least < estimate => {
+          let (_, dropped) = self.ranked.pop_first().expect("Heavy hitter candidates were empty");
+          self.candidates.remove(&dropped);
+        }
+        _ => return,
+      }
+    }
+
+    self.candidates.insert(key.clone(), estimate);
+    self.ranked.insert((estimate, key));
+  }
+
+  /// the N most common, then by value so the order never changes between runs
+  pub fn top(&self) -> Vec<(&Vec<Option<String>>, u64)> {
+    let mut top: Vec<_> = self.candidates.iter().map(|(key, count)| (key, *count)).collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    top.truncate(self.count);
+    top
+  }
+
+  /// the most any count can be over, almost always
+  pub fn error(&self) -> u64 {
+    (self.total as f64 * std::f64::consts::E / WIDTH as f64).ceil() as u64
+  }
+}
+
+/// the cell KEY counts in, in each row
+fn cells(key: &[Option<String>]) -> [usize; DEPTH] {
+  let hash = key.iter().fold(fnv([]), |hash, value| match value {
+    Some(value) => fnv_extend(fnv_extend(hash, [1]), value.bytes().chain([0])),
+    None => fnv_extend(hash, [2]),
+  });
+
+  // two hashes are enough to make as many as needed. The low bits of fnv only depend on the low bits before them, so
+  // the cell is taken from the high bits, and the halves of the hash are swapped so each row mixes them differently
+  let step = hash.rotate_left(32) | 1;
+
+  let mut cells = [0; DEPTH];
+
+  for (row, cell) in cells.iter_mut().enumerate() {
+    *cell = ((hash.wrapping_add(step.wrapping_mul(row as u64)) >> 32) % WIDTH as u64) as usize;
+  }
+
+  cells
+}
+
+/**
+ * MESSAGE with the parts that change between events of the same kind replaced, so "took 32ms for 'bob'" and
+ * "took 7ms for 'alice'" are both "took <num>ms for <str>". Ids, hex, quoted strings and numbers, including times
+ * and addresses like 10.0.0.1, are replaced
+ */
+pub fn template(message: &str) -> String {
+  let message = UUID.replace_all(message, "<id>");
+  let message = QUOTED.replace_all(&message, "<str>");
+  let message = HEX.replace_all(&message, |found: &Captures| {
+    let found = &found[0];
+
+    // all digits is a number, and no digits at all is a word that happens to be made of a to f
+    if found.starts_with("0x") || (found.bytes().any(|c| c.is_ascii_digit()) && found.bytes().any(|c| c.is_ascii_alphabetic())) {
+      "<hex>".to_string()
+    } else {
+      found.to_string()
+    }
+  });
+
+  NUMBER.replace_all(&message, "<num>").into_owned()
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::env;
+
+use datetime::{ISO, LocalDateTime};
+use serde_json::{Map, Value};
+
+use crate::chunk::LogWriter;
+use crate::pretty::PrettyDescriptor;
+
+/// the value of --output-format that writes an HTML report
+pub const HTML_FORMAT: &str = "html";
+
+/// bars in the histogram of events over time
+const HISTOGRAM_BARS: usize = 48;
+
+/// fields given their own column, everything else is listed together
+const COLUMNS: [&str; 3] = ["time", "level", "message"];
+
+const STACK_FIELD: &str = "stack";
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 1em; color: #222; }
+h1 { font-size: 1.3em; }
+#stats { display: flex; gap: 2em; align-items: flex-end; margin-bottom: 1em; }
+#stats table td { padding: 0 .5em; }
+#histogram { display: flex; align-items: flex-end; height: 80px; gap: 1px; }
+#histogram div { background: #4a7ab5; width: 8px; min-height: 1px; }
+#controls { margin-bottom: .5em; }
+#events { border-collapse: collapse; width: 100%; font-family: monospace; font-size: .9em; }
+#events th { text-align: left; position: sticky; top: 0; background: #eee; }
+#events td { border-top: 1px solid #ddd; padding: 2px 6px; vertical-align: top; }
+.time { white-space: nowrap; }
+.level-WARN, .level-WARNING { background: #fff6d6; }
+.level-ERROR, .level-FATAL { background: #fde2e2; }
+.fields { color: #666; }
+details pre { margin: 0; white-space: pre-wrap; }
+"#;
+
+const SCRIPT: &str = r#"
+const search = document.getElementById('search');
+const level = document.getElementById('level');
+const rows = Array.from(document.querySelectorAll('#events tbody tr'));
+const shown = document.getElementById('shown');
+
+function update() {
+  const text = search.value.toLowerCase();
+  let count = 0;
+  for (const row of rows) {
+    const visible = (!level.value || row.dataset.level === level.value) && (!text || row.textContent.toLowerCase().includes(text));
+    row.style.display = visible ? '' : 'none';
+    if (visible) count++;
+  }
+  shown.textContent = count;
+}
+
+search.addEventListener('input', update);
+level.addEventListener('change', update);
+update();
+"#;
+
+/**
+ * Writes events as a single HTML file that can be opened anywhere, with a table that can be searched and filtered by
+ * level, stacks folded away, and counts per level and a histogram of events over time at the top. Rows are written
+ * as they come, and the stats are written at the end and moved into place when the page loads.
+ */
+#[derive(Debug, Default)]
+pub struct HtmlReport {
+  events: u64,
+  levels: BTreeMap<String, u64>,
+  /// time of every event, in milliseconds, for the histogram
+  times: Vec<i64>,
+  first: Option<LocalDateTime>,
+  last: Option<LocalDateTime>,
+}
+
+impl HtmlReport {
+  pub fn write_header(&self, target: &mut Box<dyn LogWriter>) {
+    let command: Vec<String> = env::args().collect();
+
+    let header = format!(
+      "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>saw report</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+      <h1>saw report</h1>\n<p><code>{}</code></p>\n<div id=\"stats\"></div>\n\
+      <div id=\"controls\"><input id=\"search\" type=\"search\" placeholder=\"Search\"> <select id=\"level\"><option value=\"\">All levels</option></select> \
+      <span id=\"shown\"></span> shown</div>\n\
+      <table id=\"events\">\n<thead><tr><th>time</th><th>level</th><th>message</th><th>fields</th></tr></thead>\n<tbody>",
+      escape(&command.join(" ")),
+    );
+
+    target.write_all(header.as_bytes()).expect("Failed to write");
+    target.end_line();
+  }
+
+  pub fn add(&mut self, values: &Map<String, Value>, time: Option<LocalDateTime>, target: &mut Box<dyn LogWriter>) {
+    let text = |field: &str| values.get(field).map(PrettyDescriptor::pretty_value).unwrap_or_default();
+
+    let level = text("level");
+
+    self.events += 1;
+    *self.levels.entry(level.clone()).or_insert(0) += 1;
+
+    if let Some(time) = time {
+      self.times.push(millis(time));
+      self.first = Some(self.first.map_or(time, |first| first.min(time)));
+      self.last = Some(self.last.map_or(time, |last| last.max(time)));
+    }
+
+    let fields: Vec<String> = values.iter()
+      .filter(|(key, _)| !COLUMNS.contains(&key.as_str()) && key.as_str() != STACK_FIELD)
+      .map(|(key, value)| format!("{}={}", escape(key), escape(&PrettyDescriptor::pretty_value(value))))
+      .collect();
+
+    let stack = match values.get(STACK_FIELD) {
+      Some(stack) => format!("<details><summary>stack</summary><pre>{}</pre></details>", escape(&PrettyDescriptor::pretty_value(stack))),
+      None => String::new(),
+    };
+
+    let row = format!(
+      "<tr class=\"level-{level}\" data-level=\"{level}\"><td class=\"time\">{}</td><td>{level}</td><td>{}{stack}</td><td class=\"fields\">{}</td></tr>",
+      escape(&text("time")),
+      escape(&text("message")),
+      fields.join(" "),
+      level = escape(&level),
+    );
+
+    target.write_all(row.as_bytes()).expect("Failed to write");
+    target.end_line();
+  }
+
+  pub fn write_footer(&self, target: &mut Box<dyn LogWriter>) {
+    let levels: String = self.levels.iter()
+      .map(|(level, count)| format!("<tr><td>{}</td><td>{count}</td></tr>", if level.is_empty() { "-".to_string() } else { escape(level) }))
+      .collect();
+
+    let options: String = self.levels.keys()
+      .filter(|level| !level.is_empty())
+      .map(|level| format!("<option>{}</option>", escape(level)))
+      .collect();
+
+    let span = match (self.first, self.last) {
+      (Some(first), Some(last)) => format!("{} to {}", first.iso(), last.iso()),
+      _ => "-".to_string(),
+    };
+
+    let stats = format!(
+      "<div id=\"stats-content\"><table><tr><td>events</td><td>{}</td></tr><tr><td>time</td><td>{span}</td></tr>{levels}</table></div>\
+      <div id=\"histogram\" title=\"events over time\">{}</div>",
+      self.events,
+      self.histogram(),
+    );
+
+    // the stats are only known once every row has been written, so they are placed at the top once the page loads
+    let footer = format!(
+      "</tbody>\n</table>\n<template id=\"stats-template\">{stats}</template>\n<script>\n\
+      document.getElementById('stats').append(document.getElementById('stats-template').content);\n\
+      document.getElementById('level').insertAdjacentHTML('beforeend', '{}');\n{SCRIPT}</script>\n</body>\n</html>",
+      options.replace('\\', "\\\\").replace('\'', "\\'"),
+    );
+
+    target.write_all(footer.as_bytes()).expect("Failed to write");
+    target.end_line();
+  }
+
+  fn histogram(&self) -> String {
+    let (Some(first), Some(last)) = (self.first, self.last) else {
+      return String::new();
+    };
+
+    let start = millis(first);
+    let width = (millis(last) - start) / HISTOGRAM_BARS as i64 + 1;
+    let mut bars = [0u64; HISTOGRAM_BARS];
+
+    for time in &self.times {
+      bars[(((time - start) / width) as usize).min(HISTOGRAM_BARS - 1)] += 1;
+    }
+
+    let highest = bars.iter().copied().max().unwrap_or(1).max(1);
+
+    bars.iter()
+      .map(|count| format!("<div style=\"height: {}%\" title=\"{count}\"></div>", count * 100 / highest))
+      .collect()
+  }
+}
+
+fn escape(raw: &str) -> String {
+  raw.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&#39;")
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
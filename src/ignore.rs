@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+use regex::RegexSet;
+use serde_json::{Map, Value};
+
+use crate::filter::lookup;
+use crate::patterns;
+
+/// the JSON file of the packs a team maintains, read along with the built in ones
+const PACKS_VARIABLE: &str = "SAW_IGNORE_PACKS";
+
+/**
+ * A named list of events that are known noise, like health checks, each a %FIELD=PATTERN that drops the events it
+ * matches
+ */
+struct Pack {
+  name: &'static str,
+  rules: &'static [&'static str],
+}
+
+const PACKS: [Pack; 4] = [
+  Pack {
+    name: "kubernetes-probes",
+    rules: &[
+      "%user_agent=^kube-probe/",
+      "%userAgent=^kube-probe/",
+      "%http.user_agent=^kube-probe/",
+      "%message=kube-probe/",
+    ],
+  },
+  Pack {
+    name: "aws-elb-healthchecks",
+    rules: &[
+      "%user_agent=^ELB-HealthChecker/",
+      "%userAgent=^ELB-HealthChecker/",
+      "%http.user_agent=^ELB-HealthChecker/",
+      "%message=ELB-HealthChecker/",
+    ],
+  },
+  Pack {
+    name: "grpc-health-checks",
+    rules: &[
+      "%grpc.method=^/?grpc\\.health\\.v1\\.Health/",
+      "%method=^/?grpc\\.health\\.v1\\.Health/",
+      "%message=grpc\\.health\\.v1\\.Health/(Check|Watch)",
+    ],
+  },
+  Pack {
+    name: "health-endpoints",
+    rules: &[
+      "%path=^/(healthz|readyz|livez|health|ready|live)(/|\\?|$)",
+      "%url=^/(healthz|readyz|livez|health|ready|live)(/|\\?|$)",
+      "%http.path=^/(healthz|readyz|livez|health|ready|live)(/|\\?|$)",
+    ],
+  },
+];
+
+/**
+ * Drops events that any rule of the --ignore-pack packs matches. The patterns on each field are all checked in one
+ * pass of a RegexSet, so dozens of rules cost about as much as one
+ */
+#[derive(Debug)]
+pub struct IgnoreRules {
+  packs: Vec<String>,
+  /// each field, the patterns for it, and the pack each of them came from
+  fields: Vec<(String, RegexSet, Vec<String>)>,
+}
+
+impl IgnoreRules {
+  /**
+   * Build the rules of the packs NAMES, from the built in packs and the file SAW_IGNORE_PACKS names, where a pack
+   * with the name of a built in one takes its place
+   */
+  pub fn load(names: &[String]) -> Result<IgnoreRules, String> {
+    let configured = load_configured()?;
+    let mut by_field: BTreeMap<String, (Vec<String>, Vec<String>)> = BTreeMap::new();
+
+    for name in names {
+      let rules: Vec<String> = match configured.get(name) {
+        Some(rules) => rules.clone(),
+        None => PACKS.iter()
+          .find(|pack| pack.name == name)
+          .map(|pack| pack.rules.iter().map(|rule| rule.to_string()).collect())
+          .ok_or_else(|| {
+            let mut known: Vec<&str> = PACKS.iter().map(|pack| pack.name).chain(configured.keys().map(String::as_str)).collect();
+            known.sort();
+            known.dedup();
+
+            format!("Ignore pack '{name}' is not recognized. Known packs are {}", known.join(", "))
+          })?,
+      };
+
+      for rule in rules {
+        let (field, pattern) = rule.strip_prefix('%')
+          .and_then(|rule| rule.split_once('='))
+          .ok_or_else(|| format!("Rule '{rule}' of ignore pack '{name}' must be written %FIELD=PATTERN"))?;
+
+        let (patterns, packs) = by_field.entry(field.to_string()).or_default();
+        patterns.push(pattern.to_string());
+        packs.push(name.clone());
+      }
+    }
+
+    let fields = by_field.into_iter()
+      .map(|(field, (found, packs))| {
+        let found: Vec<&str> = found.iter().map(String::as_str).collect();
+
+        Ok((field, patterns::regex_set(&found, "Ignore rules")?, packs))
+      })
+      .collect::<Result<_, String>>()?;
+
+    Ok(IgnoreRules { packs: names.to_vec(), fields })
+  }
+
+  /// every field a rule looks at
+  pub fn keys(&self) -> impl Iterator<Item=&str> {
+    self.fields.iter().map(|(field, _, _)| field.as_str())
+  }
+
+  pub fn describe(&self) -> String {
+    let rules: usize = self.fields.iter().map(|(_, set, _)| set.len()).sum();
+
+    format!("{}, {rules} rules on {} fields", self.packs.join(", "), self.fields.len())
+  }
+
+  /**
+   * The rule that makes LINE noise, as the pack and filter it came from, or None if it's kept
+   */
+  pub fn ignored_by(&self, line: &Map<String, Value>) -> Option<String> {
+    self.fields.iter().find_map(|(field, set, packs)| {
+      let Some(Value::String(value)) = lookup(line, field) else {
+        return None;
+      };
+
+      let index = set.matches(value).into_iter().next()?;
+
+      Some(format!("{} %{field}={}", packs[index], set.patterns()[index]))
+    })
+  }
+
+  pub fn matches(&self, line: &Map<String, Value>) -> bool {
+    self.fields.iter().any(|(field, set, _)| matches!(lookup(line, field), Some(Value::String(value)) if set.is_match(value)))
+  }
+}
+
+/**
+ * The packs in the file SAW_IGNORE_PACKS names, a JSON object of each pack's name to its list of rules:
+ *   {"our-noise": ["%logger=^com\\.acme\\.Heartbeat$", "%message=^cache refreshed"]}
+ */
+fn load_configured() -> Result<BTreeMap<String, Vec<String>>, String> {
+  let Ok(path) = env::var(PACKS_VARIABLE) else {
+    return Ok(BTreeMap::new());
+  };
+
+  let raw = fs::read_to_string(&path).map_err(|err| format!("Failed to read ignore packs {path}: {err}"))?;
+  let config: Value = serde_json::from_str(&raw).map_err(|err| format!("Ignore packs {path} are not valid JSON: {err}"))?;
+
+  let Value::Object(config) = config else {
+    return Err(format!("Ignore packs {path} must be a JSON object of NAME to a list of %FIELD=PATTERN rules"));
+  };
+
+  config.into_iter()
+    .map(|(name, rules)| {
+      let rules = rules.as_array()
+        .and_then(|rules| rules.iter().map(|rule| rule.as_str().map(str::to_string)).collect::<Option<Vec<_>>>())
+        .ok_or_else(|| format!("Ignore pack '{name}' in {path} must be a list of %FIELD=PATTERN rules"))?;
+
+      Ok((name, rules))
+    })
+    .collect()
+}
@@ -0,0 +1,19 @@
+use serde_json::{Map, Value};
+
+use crate::chunk::LogWriter;
+
+/// the value of --output-format that writes each event as indented JSON
+pub const INDENTED_FORMAT: &str = "indented";
+
+/// the line written after every event, so where one ends is clear however deeply it's nested
+const SEPARATOR: &[u8] = b"---";
+
+/**
+ * Write an event as JSON indented over as many lines as it needs, followed by a separator line
+ */
+pub fn write(value: &Map<String, Value>, target: &mut Box<dyn LogWriter>) {
+  serde_json::to_writer_pretty(&mut *target, value).expect("Failed to write line");
+  target.write_all(b"\n").expect("Failed to write line");
+  target.write_all(SEPARATOR).expect("Failed to write line");
+  target.end_line();
+}
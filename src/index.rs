@@ -0,0 +1,405 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+use datetime::{ISO, LocalDateTime};
+use glob::glob;
+use serde_json::{json, Map, Value};
+
+use crate::filter::{self, FilterSet};
+use crate::fingerprint::fnv;
+use crate::levels::{self, LevelMap};
+use crate::log::{Aggregator, LogFile, ReadOptions};
+use crate::preset::Preset;
+
+/// appended to a log file's name to find its index
+const INDEX_EXTENSION: &str = ".sawidx";
+/// 3 indexes numbers too, which older indexes read as missing
+const INDEX_VERSION: u64 = 3;
+
+/// how often a bloom filter may claim to hold something it doesn't
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// bloom filters hold every run of this many bytes in a field's values, so substrings can be looked up too
+const GRAM: usize = 3;
+
+pub const INDEX_HELP: &str = r#"
+Usage:
+  saw index [--fields FIELDS] [--preset NAME] SOURCE_FILES
+
+Reads each source file and writes an index next to it, named like 'app.log.gz.sawidx'. The index holds the
+time span of the file, how many events it has and, for each of the comma separated FIELDS, a bloom filter
+of the values seen.
+
+Later runs use the index to skip files that can't have anything in --range without opening them. Files are
+also skipped when a plain --filter on one of the indexed FIELDS, like '%request_id=abc123', is looking for
+something the bloom filter shows the file doesn't have. Values shorter than 3 characters can't be looked up.
+An index is ignored if the file has changed since it was written, or if it was written with a different --preset.
+"#;
+
+/**
+ * A fixed size set that can say something definitely isn't in it, but only that something probably is
+ */
+#[derive(Debug)]
+pub struct Bloom {
+  bits: Vec<u64>,
+  hashes: u32,
+}
+
+impl Bloom {
+  fn with_capacity(items: usize) -> Bloom {
+    let items = items.max(1) as f64;
+    let ln2 = std::f64::consts::LN_2;
+
+    let size = (-items * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil().max(64.0) as usize;
+    let hashes = ((size as f64 / items) * ln2).round().clamp(1.0, 16.0) as u32;
+
+    Bloom {
+      bits: vec![0; size.div_ceil(64)],
+      hashes,
+    }
+  }
+
+  /// every bit to set or check for an item, using two halves of one hash to make the rest
+  fn positions(&self, item: &[u8]) -> impl Iterator<Item=usize> {
+    let hash = fnv(item.iter().copied());
+    let first = hash & 0xffffffff;
+    let second = (hash >> 32) | 1;
+    let size = (self.bits.len() * 64) as u64;
+
+    (0..self.hashes as u64).map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % size) as usize)
+  }
+
+  fn insert(&mut self, item: &[u8]) {
+    for position in self.positions(item).collect::<Vec<usize>>() {
+      self.bits[position / 64] |= 1 << (position % 64);
+    }
+  }
+
+  fn contains(&self, item: &[u8]) -> bool {
+    self.positions(item).all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+  }
+
+  fn to_json(&self) -> Value {
+    let hex: String = self.bits.iter().map(|word| format!("{word:016x}")).collect();
+
+    json!({ "hashes": self.hashes, "bits": hex })
+  }
+
+  fn from_json(value: &Value) -> Option<Bloom> {
+    let hashes = value.get("hashes")?.as_u64()? as u32;
+    let hex = value.get("bits")?.as_str()?;
+
+    let bits = (0..hex.len()).step_by(16)
+      .map(|start| u64::from_str_radix(hex.get(start..start + 16)?, 16).ok())
+      .collect::<Option<Vec<u64>>>()?;
+
+    if bits.is_empty() || hashes == 0 {
+      return None;
+    }
+
+    Some(Bloom { bits, hashes })
+  }
+}
+
+/**
+ * What is known about one log file without reading it
+ */
+#[derive(Debug)]
+pub struct SourceIndex {
+  pub events: u64,
+  pub first: Option<LocalDateTime>,
+  pub last: Option<LocalDateTime>,
+  pub fields: BTreeMap<String, Bloom>,
+}
+
+impl SourceIndex {
+  pub fn path_for(source: &Path) -> PathBuf {
+    let mut path = source.as_os_str().to_owned();
+    path.push(INDEX_EXTENSION);
+    path.into()
+  }
+
+  /// size and modified time, used to tell if the file changed after being indexed
+  fn stamp(source: &Path) -> Option<(u64, u64)> {
+    let metadata = source.metadata().ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+    Some((metadata.len(), modified))
+  }
+
+  fn build(source: &Path, fields: &[String], preset: Option<&'static Preset>) -> SourceIndex {
+    // built with the standard level names, so "%level=ERROR" can skip files from bunyan and pino too
+    let options = ReadOptions { preset, levels: Some(LevelMap::standard()), ..ReadOptions::default() };
+    let mut grams: Vec<HashSet<[u8; GRAM]>> = fields.iter().map(|_| HashSet::new()).collect();
+
+    let mut index = SourceIndex {
+      events: 0,
+      first: None,
+      last: None,
+      fields: BTreeMap::new(),
+    };
+
+    for line in Aggregator::new(vec![LogFile::from_file(source)], options) {
+      index.events += 1;
+
+      if let Some(time) = line.time {
+        index.first = Some(index.first.map_or(time, |first| first.min(time)));
+        index.last = Some(index.last.map_or(time, |last| last.max(time)));
+      }
+
+      for (field, seen) in fields.iter().zip(grams.iter_mut()) {
+        // numbers as --filter-file reads them, so a list of ids can skip files too
+        let value = match filter::lookup(&line.value, field) {
+          Some(Value::String(value)) => Cow::Borrowed(value.as_str()),
+          Some(Value::Number(number)) => Cow::Owned(number.to_string()),
+          _ => continue,
+        };
+
+        for gram in value.as_bytes().windows(GRAM) {
+          seen.insert(gram.try_into().unwrap());
+        }
+      }
+    }
+
+    for (field, seen) in fields.iter().zip(grams) {
+      let mut bloom = Bloom::with_capacity(seen.len());
+
+      for gram in &seen {
+        bloom.insert(gram);
+      }
+
+      index.fields.insert(field.clone(), bloom);
+    }
+
+    index
+  }
+
+  fn write(&self, source: &Path, preset: Option<&'static Preset>) {
+    let (size, modified) = SourceIndex::stamp(source)
+      .unwrap_or_else(|| panic!("Failed to read metadata of {}", source.display()));
+
+    let fields: Map<String, Value> = self.fields.iter()
+      .map(|(field, bloom)| (field.clone(), bloom.to_json()))
+      .collect();
+
+    let index = json!({
+      "version": INDEX_VERSION,
+      "size": size,
+      "modified": modified,
+      "preset": preset.map(|preset| preset.name),
+      "events": self.events,
+      "first": self.first.map(|time| time.iso().to_string()),
+      "last": self.last.map(|time| time.iso().to_string()),
+      "fields": fields,
+    });
+
+    let path = SourceIndex::path_for(source);
+    fs::write(&path, index.to_string() + "\n").unwrap_or_else(|_| panic!("Failed to write index {}", path.display()));
+  }
+
+  /**
+   * Load the index for a file, if there is one and it still describes the file as it is now
+   */
+  pub fn load(source: &Path, preset: Option<&'static Preset>) -> Option<SourceIndex> {
+    let raw = fs::read_to_string(SourceIndex::path_for(source)).ok()?;
+    let index: Value = serde_json::from_str(&raw).ok()?;
+
+    let (size, modified) = SourceIndex::stamp(source)?;
+
+    let fresh = index.get("version")?.as_u64()? == INDEX_VERSION
+      && index.get("size")?.as_u64()? == size
+      && index.get("modified")?.as_u64()? == modified
+      && index.get("preset")?.as_str() == preset.map(|preset| preset.name);
+
+    if !fresh {
+      return None;
+    }
+
+    let time = |key: &str| index.get(key)?.as_str().and_then(|time| LocalDateTime::from_str(time).ok());
+
+    let fields = index.get("fields")?.as_object()?.iter()
+      .map(|(field, bloom)| Some((field.clone(), Bloom::from_json(bloom)?)))
+      .collect::<Option<BTreeMap<String, Bloom>>>()?;
+
+    Some(SourceIndex {
+      events: index.get("events")?.as_u64()?,
+      first: time("first"),
+      last: time("last"),
+      fields,
+    })
+  }
+
+  /**
+   * The fields and preset an index was written with, whether or not it still describes its file, so a file made
+   * from that one can be indexed the same way
+   */
+  pub fn settings(source: &Path) -> Option<(Vec<String>, Option<String>)> {
+    let raw = fs::read_to_string(SourceIndex::path_for(source)).ok()?;
+    let index: Value = serde_json::from_str(&raw).ok()?;
+
+    let fields = index.get("fields")?.as_object()?.keys().cloned().collect();
+    let preset = index.get("preset").and_then(Value::as_str).map(str::to_string);
+
+    Some((fields, preset))
+  }
+
+  /**
+   * Index a file and write the index next to it, as saw index does
+   */
+  pub fn write_for(source: &Path, fields: &[String], preset: Option<&'static Preset>) {
+    SourceIndex::build(source, fields, preset).write(source, preset);
+  }
+
+  /**
+   * Could any event in this file be inside the range? Only false if the index proves there can't be
+   */
+  pub fn might_overlap(&self, range: (Option<LocalDateTime>, Option<LocalDateTime>)) -> bool {
+    let (Some(first), Some(last)) = (self.first, self.last) else {
+      // no event had a time, and --range drops every event without one
+      return false;
+    };
+
+    let (min, max) = range;
+
+    min.is_none_or(|min| last >= min) && max.is_none_or(|max| first < max)
+  }
+
+  /**
+   * Could any event in this file have a field containing this value? Only false if the field was indexed
+   * and its bloom filter is missing a piece of the value
+   */
+  pub fn might_contain(&self, field: &str, value: &str) -> bool {
+    let Some(bloom) = self.fields.get(field) else {
+      return true;
+    };
+
+    value.as_bytes().windows(GRAM).all(|gram| bloom.contains(gram))
+  }
+}
+
+/**
+ * Why this source's index shows it can't hold anything that would be written, if it does
+ */
+pub fn skip_reason(
+  source: &LogFile,
+  range: (Option<LocalDateTime>, Option<LocalDateTime>),
+  filter: Option<&FilterSet>,
+  options: ReadOptions,
+) -> Option<&'static str> {
+  let has_literals = filter.is_some_and(|filter| filter.literals().next().is_some() || filter.allowed().next().is_some());
+
+  // lines kept by --passthrough-invalid, --lenient-json or --resync were never counted by the index, and it was built reading
+  // JSON times with the standard level names, which filters only compare against when those are the names in use
+  let custom_levels = levels::in_use().is_none_or(|levels| !levels.is_standard());
+
+  if (range == (None, None) && !has_literals) || options.passthrough_invalid || options.lenient_json || options.resync || options.input.is_some() || options.time.is_some() || custom_levels {
+    return None;
+  }
+
+  let index = SourceIndex::load(source.path()?, options.preset)?;
+
+  if range != (None, None) && !index.might_overlap(range) {
+    return Some("nothing in range");
+  }
+
+  if filter.is_some_and(|filter| filter.literals().any(|(field, value)| !index.might_contain(field, value))) {
+    return Some("nothing matching the filter");
+  }
+
+  if filter.is_some_and(|filter| filter.allowed().any(|(field, values)| !values.iter().any(|value| index.might_contain(field, value)))) {
+    return Some("none of the values in the filter file");
+  }
+
+  None
+}
+
+pub fn can_skip(
+  source: &LogFile,
+  range: (Option<LocalDateTime>, Option<LocalDateTime>),
+  filter: Option<&FilterSet>,
+  options: ReadOptions,
+) -> bool {
+  skip_reason(source, range, filter, options).is_some()
+}
+
+/**
+ * Drop any source that can be skipped, without opening it further
+ */
+pub fn prune(
+  sources: Vec<LogFile>,
+  range: (Option<LocalDateTime>, Option<LocalDateTime>),
+  filter: Option<&FilterSet>,
+  options: ReadOptions,
+) -> Vec<LogFile> {
+  sources.into_iter()
+    .filter(|source| !can_skip(source, range, filter, options))
+    .collect()
+}
+
+/**
+ * saw index [--fields FIELDS] [--preset NAME] SOURCE_FILES
+ */
+pub fn run<Args: Iterator<Item=String>>(mut src: Args) {
+  let mut fields: Vec<String> = vec![];
+  let mut preset = None;
+  let mut sources: Vec<PathBuf> = vec![];
+
+  while let Some(next) = src.next() {
+    match next.as_ref() {
+      "-h" | "--help" => {
+        eprintln!("{INDEX_HELP}");
+        return;
+      }
+      "--fields" => {
+        let raw = src.next().expect("Argument --fields must be followed by comma separated FIELDS");
+
+        fields = raw.split(',').map(|field| field.trim().to_string()).collect();
+
+        if fields.iter().any(|field| field.is_empty()) {
+          panic!("Argument --fields contains an empty field name in '{raw}'");
+        }
+      }
+      "--preset" => {
+        let raw = src.next().expect("Argument --preset must be followed by a NAME");
+
+        preset = Some(Preset::parse(&raw).unwrap_or_else(|err| panic!("{err}")));
+      }
+      _ if next.starts_with('-') => {
+        panic!("Unknown property '{next}' for saw index. Run saw index --help to see all known properties");
+      }
+      _ => {
+        let paths = glob(&next).unwrap_or_else(|_| panic!("Source '{next}' is not valid or directory could not be read"));
+
+        for path in paths {
+          let path = path.unwrap_or_else(|_| panic!("Source '{next}' is not valid or could not be read"));
+
+          // indexing the indexes would only get in the way of a glob like 'logs/*'
+          if !path.to_string_lossy().ends_with(INDEX_EXTENSION) {
+            sources.push(path);
+          }
+        }
+      }
+    }
+  }
+
+  if sources.is_empty() {
+    panic!("saw index needs at least one source file. Run saw index --help for more information");
+  }
+
+  for source in sources {
+    let index = SourceIndex::build(&source, &fields, preset);
+    index.write(&source, preset);
+
+    eprintln!(
+      "Indexed {}: {} events from {} to {}",
+      source.display(),
+      index.events,
+      index.first.map_or("-".to_string(), |time| time.iso().to_string()),
+      index.last.map_or("-".to_string(), |time| time.iso().to_string()),
+    );
+  }
+}
@@ -0,0 +1,427 @@
+use std::str::FromStr;
+
+use datetime::{DatePiece, ISO, LocalDate, LocalDateTime, LocalTime, Month};
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+
+use crate::frame::Framing;
+use crate::locale::{DEFAULT_LOCALES, Names};
+use crate::patterns;
+
+/**
+ * How each line of a source is turned into an event, from --input
+ */
+#[derive(Debug)]
+pub enum InputFormat {
+  Json,
+  /// key=value pairs separated by spaces, with values optionally in double quotes
+  Logfmt,
+  /// a regex whose named groups become the event's fields
+  Regex(Regex),
+  /// a Loki query export, which is one document rather than lines, so it's decoded into JSON lines when opened
+  Loki,
+  /// length prefixed frames rather than lines, which are decoded into JSON lines as they're read
+  Frames(Framing),
+}
+
+impl InputFormat {
+  /**
+   * Parse json, logfmt, loki, frames[:be|le][:json|msgpack] or regex:PATTERN
+   */
+  pub fn parse(raw: &str) -> Result<InputFormat, String> {
+    if let Some(framing) = Framing::parse(raw) {
+      return Ok(InputFormat::Frames(framing));
+    }
+
+    if let Some(pattern) = raw.strip_prefix("regex:") {
+      let regex = patterns::regex(pattern, "Input")?;
+
+      if !regex.capture_names().any(|name| name.is_some()) {
+        return Err(format!("Input pattern '{pattern}' has no named groups like (?P<message>.*), so it can't set any fields"));
+      }
+
+      return Ok(InputFormat::Regex(regex));
+    }
+
+    match raw {
+      "json" => Ok(InputFormat::Json),
+      "logfmt" => Ok(InputFormat::Logfmt),
+      "loki" => Ok(InputFormat::Loki),
+      _ => Err(format!("Input format '{raw}' is not recognized. Valid options are json, logfmt, loki, frames, regex:PATTERN")),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      InputFormat::Json => "JSON",
+      InputFormat::Logfmt => "logfmt",
+      InputFormat::Regex(_) => "input pattern",
+      InputFormat::Loki => "Loki export",
+      InputFormat::Frames(_) => "frame",
+    }
+  }
+
+  /**
+   * Is the whole source one document, which has to be read before any line of it can be?
+   */
+  pub fn is_document(&self) -> bool {
+    matches!(self, InputFormat::Loki)
+  }
+
+  /**
+   * Is the source read as something other than lines, so where each event is in it can't be found from its lines?
+   */
+  pub fn is_decoded(&self) -> bool {
+    matches!(self, InputFormat::Loki | InputFormat::Frames(_))
+  }
+
+  /**
+   * The event in a line, or None if the line isn't in this format
+   */
+  pub fn read(&self, raw: &str) -> Option<Map<String, Value>> {
+    match self {
+      InputFormat::Json | InputFormat::Loki | InputFormat::Frames(_) => match serde_json::from_str(raw) {
+        Ok(Value::Object(map)) => Some(map),
+        _ => None,
+      },
+      InputFormat::Logfmt => read_logfmt(raw.trim_end_matches(['\r', '\n'])),
+      InputFormat::Regex(regex) => {
+        let line = raw.trim_end_matches(['\r', '\n']);
+        let captures = regex.captures(line)?;
+
+        Some(from_captures(regex, &captures))
+      }
+    }
+  }
+}
+
+fn from_captures(regex: &Regex, captures: &Captures) -> Map<String, Value> {
+  regex.capture_names()
+    .flatten()
+    .filter_map(|name| Some((name.to_string(), Value::String(captures.name(name)?.as_str().to_string()))))
+    .collect()
+}
+
+/**
+ * Read logfmt like `time=2024-06-01T13:00:00Z level=info msg="user logged in" admin`. A key without a value is true,
+ * and a line without any key=value pair isn't logfmt at all
+ */
+fn read_logfmt(line: &str) -> Option<Map<String, Value>> {
+  let mut values = Map::new();
+  let mut has_pair = false;
+  let mut chars = line.chars().peekable();
+
+  loop {
+    while chars.next_if(|next| next.is_whitespace()).is_some() {}
+
+    let mut key = String::new();
+    while let Some(next) = chars.next_if(|next| !next.is_whitespace() && *next != '=') {
+      key.push(next);
+    }
+
+    if key.is_empty() {
+      // a stray '=' with no key before it
+      if chars.next().is_none() {
+        break;
+      }
+
+      return None;
+    }
+
+    if chars.next_if_eq(&'=').is_none() {
+      values.insert(key, Value::Bool(true));
+      continue;
+    }
+
+    let mut value = String::new();
+
+    if chars.next_if_eq(&'"').is_some() {
+      loop {
+        match chars.next()? {
+          '"' => break,
+          '\\' => match chars.next()? {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            other => value.push(other),
+          },
+          other => value.push(other),
+        }
+      }
+    } else {
+      while let Some(next) = chars.next_if(|next| !next.is_whitespace()) {
+        value.push(next);
+      }
+    }
+
+    has_pair = true;
+    values.insert(key, Value::String(value));
+  }
+
+  if has_pair { Some(values) } else { None }
+}
+
+/**
+ * How an event's time is found and read, from --time-field, --time-format and --time-locale. Each format is tried in
+ * turn until one reads the time, for sources that write it more than one way. The time is always written back as an
+ * ISO8601 'time' field, so everything after reading sees the same thing it would for a JSON log
+ */
+#[derive(Debug)]
+pub struct TimeParser {
+  field: String,
+  formats: Vec<TimeFormat>,
+  /// the month and day names that %b and %a match
+  names: Names,
+}
+
+#[derive(Debug)]
+enum TimeFormat {
+  /// ISO8601 or RFC3339 with a space or T, a . or , before the fraction, and an offset, or epoch seconds or
+  /// milliseconds for numbers
+  Auto,
+  Epoch,
+  EpochMillis,
+  Pattern(TimePattern),
+}
+
+/**
+ * A strftime style pattern like "%d/%b/%Y:%H:%M:%S %z", turned into a regex with a group for each piece
+ */
+#[derive(Debug)]
+struct TimePattern {
+  raw: String,
+  regex: Regex,
+  /// the piece each group of the regex holds, in order
+  pieces: Vec<char>,
+}
+
+lazy_static! {
+  static ref DATE_TIME: Regex = Regex::new(
+    r"^(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2})(?::(\d{2})(?:[.,](\d+))?)?\s*(Z|z|[+-]\d{2}:?\d{2})?$"
+  ).unwrap();
+}
+
+impl TimeParser {
+  pub fn new(field: Option<String>, formats: Vec<String>, locales: Option<String>) -> Result<TimeParser, String> {
+    let names = Names::parse(locales.as_deref().unwrap_or(DEFAULT_LOCALES))?;
+
+    let mut formats: Vec<TimeFormat> = formats.iter().map(|format| match format.as_str() {
+      "auto" => Ok(TimeFormat::Auto),
+      "epoch" => Ok(TimeFormat::Epoch),
+      "epochMillis" => Ok(TimeFormat::EpochMillis),
+      pattern if pattern.contains('%') => Ok(TimeFormat::Pattern(TimePattern::parse(pattern, &names)?)),
+      other => Err(format!("Time format '{other}' is not recognized. Valid options are auto, epoch, epochMillis or a pattern like %Y-%m-%d %H:%M:%S")),
+    }).collect::<Result<_, _>>()?;
+
+    if formats.is_empty() {
+      formats.push(TimeFormat::Auto);
+    }
+
+    Ok(TimeParser { field: field.unwrap_or("time".to_string()), formats, names })
+  }
+
+  pub fn describe(&self) -> String {
+    let formats: Vec<&str> = self.formats.iter().map(|format| match format {
+      TimeFormat::Auto => "ISO8601, RFC3339 or epoch",
+      TimeFormat::Epoch => "epoch seconds",
+      TimeFormat::EpochMillis => "epoch milliseconds",
+      TimeFormat::Pattern(pattern) => &pattern.raw,
+    }).collect();
+
+    let names = if self.formats.iter().any(|format| matches!(format, TimeFormat::Pattern(_))) {
+      format!(", with names in {}", self.names.describe())
+    } else {
+      String::new()
+    };
+
+    format!("'{}' read as {}{names}", self.field, formats.join(", or else "))
+  }
+
+  /**
+   * Does any pattern leave out the year, which is then taken to be this year?
+   */
+  pub fn guesses_year(&self) -> bool {
+    self.formats.iter().any(|format| match format {
+      TimeFormat::Pattern(pattern) => !pattern.pieces.iter().any(|piece| matches!(piece, 'Y' | 'y')),
+      _ => false,
+    })
+  }
+
+  /**
+   * Replace the time field with an ISO8601 'time', moving it there if it had another name. A time that can't be read
+   * is left where it is, so the event is reported as missing one
+   */
+  pub fn normalize(&self, values: &mut Map<String, Value>) {
+    let Some(time) = values.get(&self.field).and_then(|value| self.formats.iter().find_map(|format| self.read(format, value))) else {
+      return;
+    };
+
+    if self.field != "time" {
+      values.remove(&self.field);
+    }
+
+    values.insert("time".to_string(), Value::String(time.iso().to_string()));
+  }
+
+  fn read(&self, format: &TimeFormat, value: &Value) -> Option<LocalDateTime> {
+    match (format, value) {
+      (TimeFormat::Auto, Value::String(text)) => read_date_time(text.trim()),
+      // anything past the year 5138 in seconds is taken to be milliseconds
+      (TimeFormat::Auto, Value::Number(number)) if number.as_f64()?.abs() >= 1e11 => from_millis(number.as_f64()?),
+      (TimeFormat::Auto | TimeFormat::Epoch, Value::Number(number)) => from_millis(number.as_f64()? * 1000.0),
+      (TimeFormat::Epoch, Value::String(text)) => from_millis(text.trim().parse::<f64>().ok()? * 1000.0),
+      (TimeFormat::EpochMillis, Value::Number(number)) => from_millis(number.as_f64()?),
+      (TimeFormat::EpochMillis, Value::String(text)) => from_millis(text.trim().parse::<f64>().ok()?),
+      (TimeFormat::Pattern(pattern), Value::String(text)) => pattern.read(text.trim(), &self.names),
+      _ => None,
+    }
+  }
+}
+
+fn from_millis(millis: f64) -> Option<LocalDateTime> {
+  if !millis.is_finite() {
+    return None;
+  }
+
+  let millis = millis.round() as i64;
+
+  Some(LocalDateTime::at_ms(millis.div_euclid(1000), millis.rem_euclid(1000) as i16))
+}
+
+/**
+ * Read a date time with an optional offset, which is taken away so every time is UTC
+ */
+fn read_date_time(text: &str) -> Option<LocalDateTime> {
+  let Some(captures) = DATE_TIME.captures(text) else {
+    return LocalDateTime::from_str(text).ok();
+  };
+
+  let number = |index: usize| captures.get(index).map_or(Some(0), |found| found.as_str().parse::<i64>().ok());
+
+  let date = LocalDate::ymd(number(1)?, Month::from_one(number(2)? as i8).ok()?, number(3)? as i8).ok()?;
+  let time = LocalTime::hms_ms(number(4)? as i8, number(5)? as i8, number(6)? as i8, fraction_millis(captures.get(7).map(|found| found.as_str())))
+    .ok()?;
+
+  let offset = captures.get(8).map_or(Some(0), |found| offset_seconds(found.as_str()))?;
+
+  Some(LocalDateTime::new(date, time).add_seconds(-offset))
+}
+
+fn fraction_millis(fraction: Option<&str>) -> i16 {
+  let Some(fraction) = fraction else {
+    return 0;
+  };
+
+  let digits: String = fraction.chars().chain("000".chars()).take(3).collect();
+
+  digits.parse().unwrap_or(0)
+}
+
+/// Z, +02:00 or -0530 as seconds east of UTC
+fn offset_seconds(raw: &str) -> Option<i64> {
+  if raw.eq_ignore_ascii_case("z") {
+    return Some(0);
+  }
+
+  let sign = if raw.starts_with('-') { -1 } else { 1 };
+  let digits: String = raw[1..].chars().filter(char::is_ascii_digit).collect();
+
+  if digits.len() != 4 {
+    return None;
+  }
+
+  let hours: i64 = digits[..2].parse().ok()?;
+  let minutes: i64 = digits[2..].parse().ok()?;
+
+  Some(sign * (hours * 3600 + minutes * 60))
+}
+
+impl TimePattern {
+  fn parse(raw: &str, names: &Names) -> Result<TimePattern, String> {
+    let mut regex = String::from("^");
+    let mut pieces = vec![];
+    let mut chars = raw.chars();
+
+    while let Some(next) = chars.next() {
+      if next != '%' {
+        regex.push_str(&regex::escape(&next.to_string()));
+        continue;
+      }
+
+      let piece = chars.next().ok_or_else(|| format!("Time format '{raw}' ends with a lone %"))?;
+
+      let group = match piece {
+        'Y' => r"(\d{4})".to_string(),
+        'y' | 'm' | 'd' | 'H' | 'I' | 'M' | 'S' => r"(\d{1,2})".to_string(),
+        'e' => r"\s?(\d{1,2})".to_string(),
+        'f' => r"(\d+)".to_string(),
+        'b' | 'B' => names.month_pattern(),
+        'a' | 'A' => names.day_pattern(),
+        'p' => r"((?i:am|pm|a\.m\.|p\.m\.))".to_string(),
+        'z' => r"(Z|z|[+-]\d{2}:?\d{2})".to_string(),
+        '%' => {
+          regex.push('%');
+          continue;
+        }
+        other => return Err(format!("Time format '{raw}' has an unknown piece %{other}. Valid pieces are %Y %y %m %d %e %b %B %a %A %H %I %p %M %S %f %z")),
+      };
+
+      regex.push_str(&group);
+      pieces.push(match piece {
+        'e' => 'd',
+        'B' => 'b',
+        'A' => 'a',
+        other => other,
+      });
+    }
+
+    regex.push('$');
+
+    Ok(TimePattern { raw: raw.to_string(), regex: Regex::new(&regex).unwrap(), pieces })
+  }
+
+  /**
+   * Pieces left out of the pattern are the start of their unit, except the year, which is this year, as in syslog.
+   * Two digit years are from 1969 to 2068, as in POSIX, and the day's name is only checked to be a name
+   */
+  fn read(&self, text: &str, names: &Names) -> Option<LocalDateTime> {
+    let captures = self.regex.captures(text)?;
+
+    let mut year = LocalDateTime::now().year();
+    let (mut month, mut day, mut hour, mut minute, mut second, mut millis, mut offset) = (1, 1, 0, 0, 0, 0, 0);
+    let mut afternoon = None;
+
+    for (piece, found) in self.pieces.iter().zip(captures.iter().skip(1)) {
+      let found = found?.as_str();
+      let number = || found.parse::<i64>().ok();
+
+      match piece {
+        'Y' => year = number()?,
+        'y' => year = number()? + if number()? < 69 { 2000 } else { 1900 },
+        'm' => month = number()? as i8,
+        'd' => day = number()? as i8,
+        'H' | 'I' => hour = number()? as i8,
+        'M' => minute = number()? as i8,
+        'S' => second = number()? as i8,
+        'f' => millis = fraction_millis(Some(found)),
+        'b' => month = names.month(found)?,
+        'p' => afternoon = Some(found.starts_with(['p', 'P'])),
+        'z' => offset = offset_seconds(found)?,
+        _ => {}
+      }
+    }
+
+    if let Some(afternoon) = afternoon {
+      if !(1..=12).contains(&hour) {
+        return None;
+      }
+
+      hour = hour % 12 + if afternoon { 12 } else { 0 };
+    }
+
+    let date = LocalDate::ymd(year, Month::from_one(month).ok()?, day).ok()?;
+    let time = LocalTime::hms_ms(hour, minute, second, millis).ok()?;
+
+    Some(LocalDateTime::new(date, time).add_seconds(-offset))
+  }
+}
@@ -0,0 +1,305 @@
+use std::fs::File;
+use std::io::{BufRead, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::str::FromStr;
+
+use datetime::{ISO, LocalDateTime};
+use glob::glob;
+use serde_json::{Map, Value};
+
+use crate::checksum::Checksum;
+use crate::codec::SourceCodec;
+use crate::fingerprint::{fnv_extend, FNV_OFFSET};
+use crate::project::Projection;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+pub const VERIFY_HELP: &str = r#"
+Usage:
+  saw verify [--manifest MANIFEST] [--require-checksum] [--quiet] FILES
+
+Reads each of FILES through to the end, decompressing it if it's gzipped or zstd, and checks that it's whole: that
+it decompresses without error, that its first and last lines are JSON, since a file cut off part way through ends
+in half a line, and that the times of its events never go backwards. Lines in the middle that aren't JSON are
+counted, since --passthrough-invalid writes them, but don't fail a file.
+
+A checksum written next to a file by --checksum, like app.log.gz.sha256, is checked whenever there is one, and with
+--require-checksum a file without one fails. --manifest MANIFEST checks every chunk listed in an OUTPUT.manifest
+written by --skip-unchanged as well, against the length and hash of its lines and its checksum, and a chunk that is
+listed but missing fails. --manifest can be given more than once and MANIFEST can be a glob.
+
+Each file gets a line saying OK or FAIL and why, and saw exits with code 1 if any failed, so it can be run from cron
+and only be heard from when something is wrong with --quiet, which leaves out the files that are fine:
+  saw verify --quiet --manifest 'archive/*.manifest' 'archive/*.log.gz'
+"#;
+
+/**
+ * What a file is expected to hold, from a manifest
+ */
+#[derive(Debug)]
+struct Expected {
+  manifest: PathBuf,
+  bytes: Option<u64>,
+  hash: Option<String>,
+  sha256: Option<String>,
+}
+
+/**
+ * What reading a file through found
+ */
+#[derive(Debug, Default)]
+pub struct Checked {
+  pub lines: u64,
+  pub bytes: u64,
+  pub hash: u64,
+  pub invalid: u64,
+  pub first: Option<LocalDateTime>,
+  pub last: Option<LocalDateTime>,
+  pub problems: Vec<String>,
+}
+
+/**
+ * saw verify [--manifest MANIFEST] [--require-checksum] [--quiet] FILES
+ */
+pub fn run<Args: Iterator<Item=String>>(mut src: Args) {
+  let mut files: Vec<(PathBuf, Option<Expected>)> = vec![];
+  let mut require_checksum = false;
+  let mut quiet = false;
+
+  while let Some(next) = src.next() {
+    match next.as_ref() {
+      "-h" | "--help" => {
+        eprintln!("{VERIFY_HELP}");
+        return;
+      }
+      "--manifest" => {
+        let raw = src.next().expect("Argument --manifest must be followed by a MANIFEST");
+
+        for manifest in expand(&raw, "Manifest") {
+          files.extend(read_manifest(&manifest));
+        }
+      }
+      "--require-checksum" => require_checksum = true,
+      "-q" | "--quiet" => quiet = true,
+      _ if next.starts_with('-') => {
+        panic!("Unknown property '{next}' for saw verify. Run saw verify --help to see all known properties");
+      }
+      _ => files.extend(expand(&next, "File").into_iter().map(|path| (path, None))),
+    }
+  }
+
+  if files.is_empty() {
+    panic!("saw verify needs at least one file. Run saw verify --help for more information");
+  }
+
+  // a file given on its own and in a manifest is only checked once, against the manifest
+  let mut checked: Vec<(PathBuf, Option<Expected>)> = vec![];
+
+  for (path, expected) in files {
+    match checked.iter_mut().find(|(existing, _)| *existing == path) {
+      Some((_, existing)) => if expected.is_some() {
+        *existing = expected;
+      },
+      None => checked.push((path, expected)),
+    }
+  }
+
+  let mut failed = 0;
+
+  for (path, expected) in &checked {
+    let problems = verify(path, expected.as_ref(), require_checksum);
+
+    match problems {
+      Ok(summary) if !quiet => println!("OK    {}  {summary}", path.display()),
+      Ok(_) => {}
+      Err(problems) => {
+        failed += 1;
+        println!("FAIL  {}  {}", path.display(), problems.join("; "));
+      }
+    }
+  }
+
+  if !quiet || failed > 0 {
+    println!("Verified {} files, {failed} failed", checked.len());
+  }
+
+  if failed > 0 {
+    exit(1);
+  }
+}
+
+fn expand(raw: &str, what: &str) -> Vec<PathBuf> {
+  let paths = glob(raw).unwrap_or_else(|_| panic!("{what} '{raw}' is not valid or directory could not be read"));
+  let paths: Vec<PathBuf> = paths.map(|path| path.unwrap_or_else(|_| panic!("{what} '{raw}' is not valid or could not be read"))).collect();
+
+  if paths.is_empty() {
+    panic!("{what} '{raw}' did not match anything");
+  }
+
+  paths
+}
+
+/**
+ * Every chunk a manifest lists, next to the manifest, with what it should hold
+ */
+fn read_manifest(manifest: &Path) -> Vec<(PathBuf, Option<Expected>)> {
+  let raw = std::fs::read_to_string(manifest).unwrap_or_else(|_| panic!("Failed to read manifest '{}'", manifest.display()));
+
+  let Ok(Value::Object(chunks)) = serde_json::from_str::<Value>(&raw) else {
+    panic!("Manifest '{}' is not valid, it must be written by --skip-unchanged", manifest.display());
+  };
+
+  let parent = manifest.parent().unwrap_or(Path::new(""));
+
+  chunks.into_iter()
+    .map(|(name, entry)| {
+      let expected = Expected {
+        manifest: manifest.to_path_buf(),
+        bytes: entry.get("bytes").and_then(Value::as_u64),
+        hash: entry.get("hash").and_then(Value::as_str).map(str::to_string),
+        sha256: entry.get("sha256").and_then(Value::as_str).map(str::to_string),
+      };
+
+      (parent.join(name), Some(expected))
+    })
+    .collect()
+}
+
+/**
+ * Check one file, with a summary of it if it's fine, or everything wrong with it
+ */
+fn verify(path: &Path, expected: Option<&Expected>, require_checksum: bool) -> Result<String, Vec<String>> {
+  if !path.exists() {
+    let listed = expected.map_or(String::new(), |expected| format!(", but is listed in '{}'", expected.manifest.display()));
+
+    return Err(vec![format!("is missing{listed}")]);
+  }
+
+  let checked = read(path);
+  let mut problems = checked.problems;
+
+  if let Some(expected) = expected {
+    if expected.bytes.is_some_and(|bytes| bytes != checked.bytes) || expected.hash.as_ref().is_some_and(|hash| *hash != format!("{:016x}", checked.hash)) {
+      problems.push(format!("its lines are not the ones '{}' lists", expected.manifest.display()));
+    }
+  }
+
+  let checksum = Checksum::Sha256;
+  let sidecar = checksum.sidecar(path);
+  let written = std::fs::read_to_string(&sidecar).ok()
+    .and_then(|raw| raw.split_whitespace().next().map(str::to_string))
+    .or_else(|| expected.and_then(|expected| expected.sha256.clone()));
+
+  match written {
+    Some(written) if checksum.hash_file(path) != written => {
+      problems.push(format!("its {} checksum does not match", checksum.name()));
+    }
+    Some(_) => {}
+    None if require_checksum => problems.push(format!("it has no {} checksum", checksum.name())),
+    None => {}
+  }
+
+  if !problems.is_empty() {
+    return Err(problems);
+  }
+
+  let span = match (checked.first, checked.last) {
+    (Some(first), Some(last)) => format!(", {} to {}", first.iso(), last.iso()),
+    _ => String::new(),
+  };
+
+  let invalid = if checked.invalid > 0 { format!(", {} not JSON", checked.invalid) } else { String::new() };
+
+  Ok(format!("{} lines{invalid}{span}", checked.lines))
+}
+
+/**
+ * Read a file through to the end, hashing its lines the way manifests do and checking each one
+ */
+pub fn read(path: &Path) -> Checked {
+  let mut checked = Checked { hash: FNV_OFFSET, ..Checked::default() };
+
+  let mut file = match File::open(path) {
+    Ok(file) => file,
+    Err(err) => {
+      checked.problems.push(format!("can't be opened: {err}"));
+      return checked;
+    }
+  };
+
+  let mut start = [0u8; 4];
+  let read = file.read(&mut start).unwrap_or(0);
+
+  let codec = if start[..read].starts_with(&GZIP_MAGIC) {
+    SourceCodec::Gzip
+  } else if start[..read] == ZSTD_MAGIC {
+    SourceCodec::Zstd
+  } else {
+    SourceCodec::Plain
+  };
+
+  if file.rewind().is_err() {
+    checked.problems.push("can't be read from the start".to_string());
+    return checked;
+  }
+
+  let times = Projection::parse("time").expect("A projection of time must always parse");
+  let mut src = codec.decode(file);
+  let mut raw = Vec::new();
+  let mut last_valid = true;
+
+  loop {
+    raw.clear();
+
+    match src.read_until(b'\n', &mut raw) {
+      Ok(0) => break,
+      Ok(_) => {}
+      Err(err) => {
+        checked.problems.push(format!("can't be {} after line {}: {err}", if codec == SourceCodec::Plain { "read" } else { "decompressed" }, checked.lines));
+        return checked;
+      }
+    }
+
+    checked.lines += 1;
+    checked.bytes += raw.len() as u64;
+    checked.hash = fnv_extend(checked.hash, raw.iter().copied());
+
+    let line = String::from_utf8_lossy(&raw);
+    let event = times.read(line.trim_end_matches(['\n', '\r']));
+
+    last_valid = event.is_some();
+
+    let Some(event) = event else {
+      if checked.lines == 1 {
+        checked.problems.push("its first line is not JSON".to_string());
+      }
+
+      checked.invalid += 1;
+      continue;
+    };
+
+    if let Some(time) = time_of(&event) {
+      if checked.last.is_some_and(|last| time < last) {
+        if checked.problems.iter().all(|problem| !problem.starts_with("times go backwards")) {
+          checked.problems.push(format!("times go backwards at line {}", checked.lines));
+        }
+      } else {
+        checked.last = Some(time);
+      }
+
+      checked.first = checked.first.or(Some(time));
+    }
+  }
+
+  if !last_valid && checked.lines > 1 {
+    checked.problems.push("its last line is not JSON, it may have been cut off".to_string());
+  }
+
+  checked
+}
+
+fn time_of(event: &Map<String, Value>) -> Option<LocalDateTime> {
+  event.get("time").and_then(Value::as_str).and_then(|time| LocalDateTime::from_str(time).ok())
+}
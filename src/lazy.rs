@@ -0,0 +1,55 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use datetime::LocalDateTime;
+
+use crate::age;
+use crate::deterministic;
+use crate::index::SourceIndex;
+use crate::log::LogFile;
+use crate::preset::Preset;
+
+/**
+ * Put files that haven't been opened yet in the order they're likely to start, for --max-open-files, so they can be
+ * opened a few at a time as the ones before them finish. Nothing is read to find out: a file's index says exactly
+ * when it starts, otherwise a date in its path is the day it starts, otherwise when it was last changed is close
+ * enough to put rotated files like app.log.2 and app.log.1 in order. Files without any of them go last, in the
+ * order they were given. With --deterministic, when files were changed isn't used
+ */
+pub fn order(files: Vec<LogFile>, preset: Option<&'static Preset>) -> Vec<LogFile> {
+  // found once each, since sorting would look at every file many times
+  let mut keyed: Vec<(Option<LocalDateTime>, LogFile)> = files.into_iter()
+    .map(|file| (file.path().and_then(|path| likely_start(path, preset)), file))
+    .collect();
+
+  // a stable sort, so files that seem to start at the same time stay in the order they were given
+  keyed.sort_by(|(left, _), (right, _)| match (left, right) {
+    (Some(left), Some(right)) => left.cmp(right),
+    (Some(_), None) => Ordering::Less,
+    (None, Some(_)) => Ordering::Greater,
+    (None, None) => Ordering::Equal,
+  });
+
+  keyed.into_iter().map(|(_, file)| file).collect()
+}
+
+fn likely_start(path: &Path, preset: Option<&'static Preset>) -> Option<LocalDateTime> {
+  if let Some(first) = SourceIndex::load(path, preset).and_then(|index| index.first) {
+    return Some(first);
+  }
+
+  if let Some(date) = age::path_date(path) {
+    return Some(date);
+  }
+
+  // a copy of the file would have been changed at another time
+  if deterministic::is_enabled() {
+    return None;
+  }
+
+  let modified = path.metadata().ok()?.modified().ok()?;
+  let millis = modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64;
+
+  Some(LocalDateTime::at_ms(millis.div_euclid(1000), millis.rem_euclid(1000) as i16))
+}
@@ -0,0 +1,125 @@
+use serde_json::{Map, Value};
+
+/**
+ * Read a JSON object from a producer that doesn't quite write JSON, for --lenient-json. NaN and Infinity, with or
+ * without a sign, become strings, as do integers too big for 64 bits, which would otherwise lose digits as a float.
+ * Numbers with leading zeros or a leading + are read as the number they are.
+ */
+pub fn read(raw: &str) -> Option<Map<String, Value>> {
+  match serde_json::from_str(&repair(raw)) {
+    Ok(Value::Object(map)) => Some(map),
+    _ => None,
+  }
+}
+
+/**
+ * Rewrite every bare word or number outside of a string that JSON wouldn't accept as it is. Strings are passed over
+ * whole, so nothing inside one is ever changed
+ */
+fn repair(raw: &str) -> String {
+  let bytes = raw.as_bytes();
+  let mut repaired = String::with_capacity(raw.len());
+  let mut copied = 0;
+  let mut index = 0;
+
+  while index < bytes.len() {
+    match bytes[index] {
+      b'"' => {
+        index += 1;
+
+        while index < bytes.len() && bytes[index] != b'"' {
+          // an escape is two bytes, so an escaped quote doesn't end the string
+          index += if bytes[index] == b'\\' { 2 } else { 1 };
+        }
+
+        index += 1;
+      }
+      byte if is_word(byte) => {
+        let start = index;
+
+        while index < bytes.len() && is_word(bytes[index]) {
+          index += 1;
+        }
+
+        if let Some(fixed) = fix(&raw[start..index]) {
+          repaired.push_str(&raw[copied..start]);
+          repaired.push_str(&fixed);
+          copied = index;
+        }
+      }
+      _ => index += 1,
+    }
+  }
+
+  repaired.push_str(&raw[copied..]);
+  repaired
+}
+
+fn is_word(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'+' | b'.')
+}
+
+/**
+ * What to write in place of a word, or None if it's fine as it is
+ */
+fn fix(word: &str) -> Option<String> {
+  let unsigned = word.strip_prefix('+').unwrap_or(word);
+
+  match unsigned {
+    "NaN" | "-NaN" => return Some("\"NaN\"".to_string()),
+    "Infinity" => return Some("\"Infinity\"".to_string()),
+    "-Infinity" => return Some("\"-Infinity\"".to_string()),
+    _ => {}
+  }
+
+  let (sign, digits) = match unsigned.strip_prefix('-') {
+    Some(rest) => ("-", rest),
+    None => ("", unsigned),
+  };
+
+  let integer_end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+  let (integer, rest) = digits.split_at(integer_end);
+
+  if integer.is_empty() || !is_fraction_and_exponent(rest) {
+    return None;
+  }
+
+  let integer = match integer.trim_start_matches('0') {
+    "" => "0",
+    trimmed => trimmed,
+  };
+
+  let number = format!("{sign}{integer}{rest}");
+
+  // a whole number that doesn't fit in 64 bits would be read as a float, so it's kept exactly as a string instead
+  if rest.is_empty() && number.parse::<i64>().is_err() && number.parse::<u64>().is_err() {
+    return Some(format!("\"{number}\""));
+  }
+
+  if number == word { None } else { Some(number) }
+}
+
+/// whether what follows the digits of a number is an optional .DIGITS and then an optional e+DIGITS
+fn is_fraction_and_exponent(rest: &str) -> bool {
+  let rest = match rest.strip_prefix('.') {
+    Some(fraction) => {
+      let end = fraction.find(|c: char| !c.is_ascii_digit()).unwrap_or(fraction.len());
+
+      if end == 0 {
+        return false;
+      }
+
+      &fraction[end..]
+    }
+    None => rest,
+  };
+
+  match rest.strip_prefix(['e', 'E']) {
+    Some(exponent) => {
+      let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+
+      !exponent.is_empty() && exponent.bytes().all(|byte| byte.is_ascii_digit())
+    }
+    None => rest.is_empty(),
+  }
+}
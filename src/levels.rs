@@ -0,0 +1,120 @@
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+
+/// the scale bunyan and pino both use, and most loggers that copied them
+const STANDARD: [(u64, &str); 6] = [
+  (10, "TRACE"),
+  (20, "DEBUG"),
+  (30, "INFO"),
+  (40, "WARN"),
+  (50, "ERROR"),
+  (60, "FATAL"),
+];
+
+lazy_static! {
+  static ref STANDARD_MAP: LevelMap = LevelMap { names: STANDARD.iter().map(|(level, name)| (*level, name.to_string())).collect() };
+
+  /// the names filters and pretty output use, set once from --level-map before anything is read
+  static ref IN_USE: RwLock<Option<&'static LevelMap>> = RwLock::new(Some(&*STANDARD_MAP));
+}
+
+pub fn use_names(levels: Option<&'static LevelMap>) {
+  *IN_USE.write().unwrap() = levels;
+}
+
+pub fn in_use() -> Option<&'static LevelMap> {
+  *IN_USE.read().unwrap()
+}
+
+/**
+ * The name of a numeric 'level' in VALUES under the names in use, if it has one
+ */
+pub fn named(values: &Map<String, Value>) -> Option<&'static str> {
+  in_use()?.name(values)
+}
+
+/**
+ * Replace a numeric 'level' with its name under the names in use, for an event about to be printed for people
+ */
+pub fn name_level(values: &mut Map<String, Value>) {
+  if let Some(levels) = in_use() {
+    levels.apply(values);
+  }
+}
+
+/**
+ * Names for numeric 'level' values, so events from bunyan and pino can be filtered with "%level=ERROR" and colored
+ * like any other. The standard scale is used unless --level-map replaces it or turns it off. Events keep the number
+ * they were written with unless --rewrite-levels asks for the name, and numbers not in the map are left as they are
+ */
+#[derive(Debug, PartialEq)]
+pub struct LevelMap {
+  names: Vec<(u64, String)>,
+}
+
+impl LevelMap {
+  pub fn standard() -> &'static LevelMap {
+    &STANDARD_MAP
+  }
+
+  pub fn is_standard(&self) -> bool {
+    self == LevelMap::standard()
+  }
+
+  /**
+   * Parse comma separated NUMBER=NAME pairs like '10=TRACE,35=NOTICE', or 'none' to leave numbers alone
+   */
+  pub fn parse(raw: &str) -> Result<Option<LevelMap>, String> {
+    if raw == "none" {
+      return Ok(None);
+    }
+
+    let names = raw.split(',')
+      .map(|pair| {
+        let (level, name) = pair.split_once('=')
+          .map(|(level, name)| (level.trim(), name.trim()))
+          .filter(|(_, name)| !name.is_empty())
+          .ok_or_else(|| format!("Level '{pair}' of --level-map must be in the form NUMBER=NAME, like 30=INFO"))?;
+
+        let level = level.parse::<u64>().map_err(|_| format!("Level '{level}' of --level-map is not a whole number"))?;
+
+        Ok((level, name.to_string()))
+      })
+      .collect::<Result<Vec<_>, String>>()?;
+
+    for (index, (level, _)) in names.iter().enumerate() {
+      if names[..index].iter().any(|(other, _)| other == level) {
+        return Err(format!("Level {level} is named twice in --level-map"));
+      }
+    }
+
+    Ok(Some(LevelMap { names }))
+  }
+
+  pub fn describe(&self) -> String {
+    self.names.iter()
+      .map(|(level, name)| format!("{level} => {name}"))
+      .collect::<Vec<_>>()
+      .join(", ")
+  }
+
+  /**
+   * Replace a numeric 'level' with its name, if it has one
+   */
+  pub fn apply(&self, values: &mut Map<String, Value>) {
+    if let Some(name) = self.name(values) {
+      values.insert("level".to_string(), Value::String(name.to_string()));
+    }
+  }
+
+  /**
+   * The name a numeric 'level' would be given, if it has one
+   */
+  pub fn name(&self, values: &Map<String, Value>) -> Option<&str> {
+    let level = values.get("level").and_then(Value::as_u64)?;
+
+    self.names.iter().find(|(num, _)| *num == level).map(|(_, name)| name.as_str())
+  }
+}
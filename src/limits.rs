@@ -0,0 +1,91 @@
+use std::fs;
+use std::thread::available_parallelism;
+
+/// cgroup v2 puts the limits of the container saw is in here, and v1 in a directory for each controller
+const CGROUP_V2: &str = "/sys/fs/cgroup";
+const CGROUP_V1_CPU: &str = "/sys/fs/cgroup/cpu";
+const CGROUP_V1_MEMORY: &str = "/sys/fs/cgroup/memory";
+
+/// cgroup v1 has no way to say there's no memory limit, so it gives the largest page aligned number instead
+const V1_UNLIMITED: u64 = 1 << 60;
+
+/**
+ * How many threads saw runs its parallel stages on: one for each core it can use, fewer if the container it's in
+ * only gets part of the machine's cores, and no more than --max-cpu. A quota of half a core still gets one thread,
+ * since nothing runs without one
+ */
+pub fn threads(max_cpu: Option<usize>) -> usize {
+  let cores = available_parallelism().map_or(1, |count| count.get());
+  let quota = cpu_quota().map_or(cores, |quota| quota.ceil().max(1.0) as usize);
+
+  cores.min(quota).min(max_cpu.unwrap_or(usize::MAX))
+}
+
+/**
+ * The cores the cgroup saw is in can use, like 0.5 for half of one, or None if it isn't limited. Only Linux has
+ * cgroups, so it's always None everywhere else
+ */
+pub fn cpu_quota() -> Option<f64> {
+  // cgroup v2 writes "QUOTA PERIOD" in microseconds, with "max" for the quota when there isn't one
+  if let Ok(raw) = fs::read_to_string(format!("{CGROUP_V2}/cpu.max")) {
+    let (quota, period) = raw.trim().split_once(' ')?;
+
+    return quota_of(quota.parse().ok()?, period.parse().ok()?);
+  }
+
+  // v1 uses -1 when there isn't one
+  let quota = read_number(&format!("{CGROUP_V1_CPU}/cpu.cfs_quota_us"))?;
+  let period = read_number(&format!("{CGROUP_V1_CPU}/cpu.cfs_period_us"))?;
+
+  quota_of(quota.parse().ok()?, period.parse().ok()?)
+}
+
+fn quota_of(quota: f64, period: f64) -> Option<f64> {
+  if quota > 0.0 && period > 0.0 { Some(quota / period) } else { None }
+}
+
+/**
+ * The most memory the cgroup saw is in can use before the OOM killer stops it, in bytes, or None if it isn't limited
+ */
+pub fn memory_limit() -> Option<u64> {
+  let raw = read_number(&format!("{CGROUP_V2}/memory.max"))
+    .or_else(|| read_number(&format!("{CGROUP_V1_MEMORY}/memory.limit_in_bytes")))?;
+
+  // "max" in v2
+  raw.parse().ok().filter(|limit| *limit < V1_UNLIMITED)
+}
+
+fn read_number(path: &str) -> Option<String> {
+  fs::read_to_string(path).ok().map(|raw| raw.trim().to_string())
+}
+
+/**
+ * The limits saw is running under, or None when it has the whole machine
+ */
+pub fn describe(max_cpu: Option<usize>) -> Option<String> {
+  let mut limits = vec![];
+
+  if let Some(quota) = cpu_quota() {
+    limits.push(format!("a CPU quota of {quota:.2} cores"));
+  }
+
+  if let Some(max) = max_cpu {
+    limits.push(format!("--max-cpu {max}"));
+  }
+
+  let memory = memory_limit().map(|limit| format!("memory limited to {}MB", limit >> 20));
+
+  if limits.is_empty() && memory.is_none() {
+    return None;
+  }
+
+  let count = threads(max_cpu);
+  let threads = format!("{count} {}", if count == 1 { "thread" } else { "threads" });
+
+  let threads = if limits.is_empty() { threads } else { format!("{threads}, from {}", limits.join(" and ")) };
+
+  Some(match memory {
+    Some(memory) => format!("{threads}, {memory}"),
+    None => threads,
+  })
+}
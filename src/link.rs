@@ -0,0 +1,135 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde_json::Value;
+
+use crate::log::Line;
+use crate::pretty::PrettyDescriptor;
+
+pub const SEQ_FIELD: &str = "_seq";
+pub const PREV_FIELD: &str = "_prev_seq";
+pub const NEXT_FIELD: &str = "_next_seq";
+
+/// events held back by default, waiting to see if another with their key comes
+const DEFAULT_WINDOW: usize = 10_000;
+
+/// how many keys are remembered before the ones not seen for the longest are forgotten
+const PRUNE_AT: usize = 100_000;
+
+/**
+ * Links the events that share a key, from --link-by FIELD[,window=N], so a viewer can walk a request or a user's chain
+ * of events without searching the whole output for each one. Every event is numbered in '_seq', counting from 0 in
+ * the order they're written, and an event with the FIELD gets the number of the one before it with the same value in
+ * '_prev_seq' and of the one after it in '_next_seq'.
+ *
+ * An event is held back until N more have come after it, so it can be given the next one of its key. A next event
+ * that comes later than that isn't linked from it, though it's still linked back.
+ */
+#[derive(Debug)]
+pub struct Linker {
+  field: String,
+  window: usize,
+  seq: u64,
+  /// each key, to the number of its last event
+  last: HashMap<String, u64>,
+}
+
+impl Linker {
+  /**
+   * Parse FIELD[,window=N], like request_id,window=50000
+   */
+  pub fn parse(raw: &str) -> Result<Linker, String> {
+    let mut parts = raw.split(',').map(str::trim);
+
+    let field = parts.next()
+      .map(|field| field.trim_start_matches('%'))
+      .filter(|field| !field.is_empty())
+      .ok_or_else(|| format!("Argument --link-by '{raw}' must start with the FIELD to link events by"))?;
+
+    let mut linker = Linker { field: field.to_string(), window: DEFAULT_WINDOW, seq: 0, last: HashMap::new() };
+
+    for part in parts {
+      match part.split_once('=') {
+        Some(("window", window)) => {
+          linker.window = window.parse().ok()
+            .filter(|window| *window > 0)
+            .ok_or_else(|| format!("Link window must be a number of events greater than 0, but was '{window}'"))?;
+        }
+        _ => return Err(format!("Link option '{part}' is not recognized. The only option is window=N")),
+      }
+    }
+
+    Ok(linker)
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{SEQ_FIELD}, {PREV_FIELD} and {NEXT_FIELD} by '{}', looking {} events ahead", self.field, self.window)
+  }
+
+  /// forget the keys whose last event was longest ago, so the keys of a long run don't all stay in memory
+  fn prune(&mut self) {
+    let mut seqs: Vec<u64> = self.last.values().copied().collect();
+    let (_, cutoff, _) = seqs.select_nth_unstable(PRUNE_AT / 2);
+    let cutoff = *cutoff;
+
+    self.last.retain(|_, seq| *seq >= cutoff);
+  }
+}
+
+/**
+ * Numbers and links the events from SRC, holding each back until the window after it has been read
+ */
+pub struct LinkStage {
+  src: Box<dyn Iterator<Item=Line>>,
+  linker: Linker,
+  /// events read but not written yet, the first of them numbered first_seq
+  waiting: VecDeque<Line>,
+  first_seq: u64,
+}
+
+impl LinkStage {
+  pub fn new(src: Box<dyn Iterator<Item=Line>>, linker: Linker) -> LinkStage {
+    LinkStage { src, linker, waiting: VecDeque::new(), first_seq: 0 }
+  }
+
+  fn add(&mut self, mut line: Line) {
+    let seq = self.linker.seq;
+    self.linker.seq += 1;
+
+    let key = line.value.get(&self.linker.field).filter(|value| !value.is_null()).map(PrettyDescriptor::pretty_value);
+
+    if let Some(key) = key {
+      if self.linker.last.len() >= PRUNE_AT {
+        self.linker.prune();
+      }
+
+      if let Some(prev) = self.linker.last.insert(key, seq) {
+        line.value.insert(PREV_FIELD.to_string(), Value::from(prev));
+
+        if let Some(waiting) = prev.checked_sub(self.first_seq).and_then(|index| self.waiting.get_mut(index as usize)) {
+          waiting.value.insert(NEXT_FIELD.to_string(), Value::from(seq));
+        }
+      }
+    }
+
+    line.value.insert(SEQ_FIELD.to_string(), Value::from(seq));
+    self.waiting.push_back(line);
+  }
+}
+
+impl Iterator for LinkStage {
+  type Item = Line;
+
+  fn next(&mut self) -> Option<Line> {
+    while self.waiting.len() <= self.linker.window {
+      match self.src.next() {
+        Some(line) => self.add(line),
+        None => break,
+      }
+    }
+
+    let line = self.waiting.pop_front()?;
+    self.first_seq += 1;
+
+    Some(line)
+  }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+/**
+ * The names of months and days in one language, all lowercase, for reading times like "3. März 2024" or
+ * "mer. 5 juin 2024" with --time-locale. Days are only matched so they can be skipped, since the date says which day
+ * it was
+ */
+#[derive(Debug)]
+pub struct Locale {
+  name: &'static str,
+  /// the names of each month, from January, full and then short
+  months: [&'static [&'static str]; 12],
+  days: &'static [&'static str],
+}
+
+/// the locale used unless --time-locale is given
+pub const DEFAULT_LOCALES: &str = "en";
+
+const LOCALES: [Locale; 7] = [
+  Locale {
+    name: "en",
+    months: [
+      &["january", "jan"], &["february", "feb"], &["march", "mar"], &["april", "apr"], &["may"], &["june", "jun"],
+      &["july", "jul"], &["august", "aug"], &["september", "sept", "sep"], &["october", "oct"], &["november", "nov"],
+      &["december", "dec"],
+    ],
+    days: &[
+      "monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday",
+      "mon", "tue", "tues", "wed", "thu", "thur", "thurs", "fri", "sat", "sun",
+    ],
+  },
+  Locale {
+    name: "de",
+    months: [
+      &["januar", "jan"], &["februar", "feb"], &["märz", "mär", "mrz"], &["april", "apr"], &["mai"], &["juni", "jun"],
+      &["juli", "jul"], &["august", "aug"], &["september", "sept", "sep"], &["oktober", "okt"], &["november", "nov"],
+      &["dezember", "dez"],
+    ],
+    days: &["montag", "dienstag", "mittwoch", "donnerstag", "freitag", "samstag", "sonntag", "mo", "di", "mi", "do", "fr", "sa", "so"],
+  },
+  Locale {
+    name: "es",
+    months: [
+      &["enero", "ene"], &["febrero", "feb"], &["marzo", "mar"], &["abril", "abr"], &["mayo", "may"], &["junio", "jun"],
+      &["julio", "jul"], &["agosto", "ago"], &["septiembre", "setiembre", "sept", "sep", "set"], &["octubre", "oct"],
+      &["noviembre", "nov"], &["diciembre", "dic"],
+    ],
+    days: &["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo", "lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+  },
+  Locale {
+    name: "fr",
+    months: [
+      &["janvier", "janv"], &["février", "févr", "fév"], &["mars"], &["avril", "avr"], &["mai"], &["juin"],
+      &["juillet", "juil"], &["août"], &["septembre", "sept"], &["octobre", "oct"], &["novembre", "nov"],
+      &["décembre", "déc"],
+    ],
+    days: &["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche", "lun", "mar", "mer", "jeu", "ven", "sam", "dim"],
+  },
+  Locale {
+    name: "it",
+    months: [
+      &["gennaio", "gen"], &["febbraio", "feb"], &["marzo", "mar"], &["aprile", "apr"], &["maggio", "mag"], &["giugno", "giu"],
+      &["luglio", "lug"], &["agosto", "ago"], &["settembre", "set"], &["ottobre", "ott"], &["novembre", "nov"],
+      &["dicembre", "dic"],
+    ],
+    days: &["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica", "lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+  },
+  Locale {
+    name: "nl",
+    months: [
+      &["januari", "jan"], &["februari", "feb"], &["maart", "mrt"], &["april", "apr"], &["mei"], &["juni", "jun"],
+      &["juli", "jul"], &["augustus", "aug"], &["september", "sep"], &["oktober", "okt"], &["november", "nov"],
+      &["december", "dec"],
+    ],
+    days: &["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag", "ma", "di", "wo", "do", "vr", "za", "zo"],
+  },
+  Locale {
+    name: "pt",
+    months: [
+      &["janeiro", "jan"], &["fevereiro", "fev"], &["março", "mar"], &["abril", "abr"], &["maio", "mai"], &["junho", "jun"],
+      &["julho", "jul"], &["agosto", "ago"], &["setembro", "set"], &["outubro", "out"], &["novembro", "nov"],
+      &["dezembro", "dez"],
+    ],
+    days: &[
+      "segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado", "domingo",
+      "seg", "ter", "qua", "qui", "sex", "sáb", "dom",
+    ],
+  },
+];
+
+/**
+ * Month and day names from every locale in a list like en,de, for --time-locale
+ */
+#[derive(Debug)]
+pub struct Names {
+  locales: Vec<&'static str>,
+  /// each month name to its number, from 1
+  months: HashMap<&'static str, i8>,
+  days: Vec<&'static str>,
+}
+
+impl Names {
+  pub fn parse(raw: &str) -> Result<Names, String> {
+    let mut names = Names { locales: vec![], months: HashMap::new(), days: vec![] };
+
+    for name in raw.split(',').map(str::trim) {
+      let locale = LOCALES.iter().find(|locale| locale.name.eq_ignore_ascii_case(name)).ok_or_else(|| {
+        let known: Vec<&str> = LOCALES.iter().map(|locale| locale.name).collect();
+        format!("Locale '{name}' is not recognized. Valid locales are {}", known.join(", "))
+      })?;
+
+      for (index, month) in locale.months.iter().enumerate() {
+        for month_name in month.iter() {
+          if let Some(other) = names.months.insert(month_name, index as i8 + 1).filter(|other| *other != index as i8 + 1) {
+            return Err(format!("Locale '{}' reads '{month_name}' as month {}, but an earlier locale reads it as month {other}. Pass only one of them", locale.name, index + 1))
+          }
+        }
+      }
+
+      names.days.extend(locale.days);
+      names.locales.push(locale.name);
+    }
+
+    Ok(names)
+  }
+
+  pub fn describe(&self) -> String {
+    self.locales.join(", ")
+  }
+
+  /// a regex for any month name, ignoring case and allowing a . after a short one
+  pub fn month_pattern(&self) -> String {
+    Names::pattern(self.months.keys().copied().collect())
+  }
+
+  /// a regex for any day name, ignoring case and allowing a . after a short one
+  pub fn day_pattern(&self) -> String {
+    Names::pattern(self.days.clone())
+  }
+
+  pub fn month(&self, found: &str) -> Option<i8> {
+    self.months.get(found.trim_end_matches('.').to_lowercase().as_str()).copied()
+  }
+
+  fn pattern(mut names: Vec<&'static str>) -> String {
+    // longest first, so a short name that starts a longer one doesn't stop the match early
+    names.sort_by(|a, b| b.chars().count().cmp(&a.chars().count()).then(a.cmp(b)));
+    names.dedup();
+
+    let alternatives: Vec<String> = names.iter().map(|name| regex::escape(name)).collect();
+
+    format!(r"((?i:{})\.?)", alternatives.join("|"))
+  }
+}
@@ -1,79 +1,620 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Seek, stdin};
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::fs::{File, Metadata};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, stdin};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::mem;
+use std::sync::{Arc, Mutex};
 use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
-use datetime::LocalDateTime;
-use flate2::read::GzDecoder;
+use datetime::{ISO, LocalDateTime};
 use serde_json::{Map, Value};
 
+use crate::arrival::{self, ArrivalReader, Drained};
+use crate::backward::BackwardReader;
+use crate::cache::AdvisedFile;
+use crate::codec::{self, SourceCodec};
+use crate::deterministic;
+use crate::frame::FrameReader;
+use crate::input::{InputFormat, TimeParser};
+use crate::lazy;
+use crate::lenient;
+use crate::levels::LevelMap;
+use crate::loki;
+use crate::multiplex;
+use crate::preset::Preset;
+use crate::project::Projection;
+use crate::provenance::PROVENANCE_KEY;
+use crate::raw::{self, RawSink};
+use crate::sort::{SortKey, SortValue};
+use crate::stats::SourceStats;
+use crate::stream;
+use crate::zone::TimeZone;
+
+/// how long the merge sleeps when every source it could write from has nothing to read yet
+const WAIT: Duration = Duration::from_millis(10);
+
 pub struct FileSource {
-  pub file: String,
+  /// shared by every line of the source, rather than copied for each one
+  pub file: Arc<str>,
   pub line: u64,
 }
 
 pub struct Line {
   pub value: Map<String, Value>,
-  pub time: LocalDateTime,
+  /// always present unless reading with keep_order or untimed
+  pub time: Option<LocalDateTime>,
   pub src: FileSource,
 }
 
+/**
+ * Settings that change how every line of every source is read
+ */
+#[derive(Clone, Copy, Default)]
+pub struct ReadOptions {
+  pub preset: Option<&'static Preset>,
+  /// names for numeric levels, from --level-map, or the standard ones of bunyan and pino
+  pub levels: Option<&'static LevelMap>,
+  /// read each source to the end before moving on to the next, in the order given, rather than merging on 'time'
+  pub keep_order: bool,
+  /// wrap lines that aren't JSON into events instead of dropping them
+  pub passthrough_invalid: bool,
+  /// only read lines with these numbers, counted from 0 in each source
+  pub lines: Span,
+  /// only read lines starting at these byte offsets in each source, after decompressing
+  pub bytes: Span,
+  /// merge by these fields instead of 'time'. Leaked when the arguments are read so the options can stay Copy
+  pub sort_by: Option<&'static SortKey>,
+  /// wait for more lines at the end of plain local files instead of finishing, from --follow
+  pub follow: bool,
+  /// read every source from the end and merge newest first, for --reverse and --tail
+  pub reverse: bool,
+  /// how lines are read when they aren't JSON, from --input. Leaked like sort_by
+  pub input: Option<&'static InputFormat>,
+  /// where the time is and how it's written, from --time-field and --time-format
+  pub time: Option<&'static TimeParser>,
+  /// accept NaN, Infinity, huge integers and leading zeros in JSON, from --lenient-json
+  pub lenient_json: bool,
+  /// read again from the next record after corrupt data, rather than dropping whatever it's joined to, from --resync
+  pub resync: bool,
+  /// print the bytes of every line that can't be read, from --debug-raw
+  pub debug_raw: bool,
+  /// the only fields read from each event, from --parse-only. Leaked like sort_by
+  pub projection: Option<&'static Projection>,
+  /// only check that each line has a valid 'time' without keeping it, for a single source nothing needs the times of
+  pub untimed: bool,
+}
+
+/**
+ * A range of positions in a source, start is inclusive and end is exclusive
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Span {
+  pub start: Option<u64>,
+  pub end: Option<u64>,
+}
+
+impl Span {
+  /**
+   * Parse a range like 1000..2000, 1000.. or ..2000
+   */
+  pub fn parse(raw: &str) -> Result<Span, String> {
+    let (start, end) = raw.split_once("..")
+      .ok_or_else(|| format!("Range '{raw}' must be in the form START..END, where either can be left off"))?;
+
+    let parse_bound = |bound: &str| -> Result<Option<u64>, String> {
+      if bound.is_empty() {
+        Ok(None)
+      } else {
+        bound.parse().map(Some).map_err(|_| format!("Range bound '{bound}' is not a valid number"))
+      }
+    };
+
+    Ok(Span {
+      start: parse_bound(start)?,
+      end: parse_bound(end)?,
+    })
+  }
+
+  fn is_before(&self, position: u64) -> bool {
+    self.start.is_some_and(|start| position < start)
+  }
+
+  fn is_after(&self, position: u64) -> bool {
+    self.end.is_some_and(|end| position >= end)
+  }
+}
+
 pub struct LogFile {
-  src: Box<dyn BufRead>,
-  name: String,
+  src: Box<dyn BufRead + Send>,
+  name: Arc<str>,
   line: u64,
+  /// byte offset of the next line
+  offset: u64,
+  options: ReadOptions,
+  /// extra fields added to every line read from this source
+  tags: Map<String, Value>,
+  /// only set for local files
+  path: Option<PathBuf>,
+  codec: SourceCodec,
+  /// from --async-sources, nothing has been read yet and load must be called before it can be
+  is_unopened: bool,
+  /// time of the last valid line, used to place lines that aren't JSON
+  last_time: Option<LocalDateTime>,
+  /// the process producing this source, if any, so it can be waited on once it's done
+  child: Option<Child>,
+  /// print how the process exited even when it succeeded
+  report_exit: bool,
+  /// [user@]host and path of a file from --ssh, which isn't streamed until it's started
+  remote: Option<(String, String)>,
+  /// only kept with --source-stats
+  stats: Option<Arc<Mutex<SourceStats>>>,
+  /// where every line read is copied to, from --copy-raw
+  raw_copy: Option<RawSink>,
+  /// the bytes of the line just read, when they weren't UTF-8
+  not_utf8: Option<Vec<u8>>,
+  /// with --resync, the line and byte the corrupt data being skipped starts at
+  corrupt: Option<(u64, u64)>,
 
   is_completed: bool,
   pub next: Option<Line>,
+  /// sort key of next, only with --sort-by
+  next_key: Vec<SortValue>,
+  /// the line being read, kept between lines so its allocation can be reused
+  buffer: String,
+  /// with --follow, the start of a line whose end hasn't been written yet
+  partial: String,
+  /// with --follow, the end of the file was reached and there may be more later
+  is_waiting: bool,
+  /// with --follow, when this source last started waiting, so a quiet source only holds back others for a while
+  waiting_since: Option<Instant>,
+  /// identifies the file that was opened, to notice when another takes its place
+  file_id: Option<u64>,
+  /// with --reverse or --tail, where lines are read from instead of src, last first. Line numbers then count from
+  /// the end, but byte offsets are still from the start
+  backward: Option<Backward>,
+}
+
+enum Backward {
+  /// a plain local file, read a block at a time from the end
+  File(BackwardReader),
+  /// anything else can only be read forwards, so it's read whole first. (line, offset) in the order they were read
+  Buffered(Vec<(String, u64)>),
 }
 
 const GZIP_MAGIC: [u8; 2] = [31u8, 139u8];
 
+/**
+ * How to read a file, from --codec or else going by whether its first two bytes are gzip's
+ */
+fn pick_codec(name: &str, path: &Path, start: &[u8]) -> SourceCodec {
+  if let Some(codec) = codec::forced_source_codec(path) {
+    return codec;
+  }
+
+  if check_gzip(name, path, start) { SourceCodec::Gzip } else { SourceCodec::Plain }
+}
+
+/**
+ * Is a file gzipped, going by its first two bytes? Warns when its name says otherwise
+ */
+fn check_gzip(name: &str, path: &Path, start: &[u8]) -> bool {
+  let is_gzip = start == GZIP_MAGIC;
+  let has_gz_extension = path.extension().is_some_and(|ext| ext == "gz");
+
+  if is_gzip && !has_gz_extension {
+    eprintln!("Warning: file '{name}' is gzipped but does not end in '.gz', reading it as gzip anyway");
+  } else if !is_gzip && has_gz_extension {
+    eprintln!("Warning: file '{name}' ends in '.gz' but is not gzipped, reading it as plain text. Pass --codec to say how to read it");
+  }
+
+  is_gzip
+}
+
 impl LogFile {
 
-  pub fn from_file(path: &PathBuf) -> LogFile {
-    let name = path.to_str().unwrap_or("<invalid path>").to_string();
-    let mut file = File::open(path).unwrap_or_else(|_| panic!("Failed to open file {name}"));
+  pub fn from_file(path: &Path) -> LogFile {
+    let mut log = LogFile::unopened(path);
+    log.open();
+    log
+  }
+
+  /**
+   * Open an unopened local file to read it from the start, going by its first two bytes for whether it's gzipped
+   */
+  pub fn open(&mut self) {
+    let path = self.path.clone().expect("Only local files can be opened");
+    let name = self.name.to_string();
+
+    let mut file = File::open(&path).unwrap_or_else(|_| panic!("Failed to open file {name}"));
     let mut gzip_check = [0u8; 2];
     let read = file
       .read(&mut gzip_check)
       .unwrap_or_else(|_| panic!("Failed to open file {name}"));
     file.rewind().expect("Failed to rewind file!");
 
-    let src: Box<dyn BufRead> = if read != 2 || GZIP_MAGIC != gzip_check {
-      // not gzip
-      Box::new(BufReader::new(file))
+    self.codec = pick_codec(&name, &path, &gzip_check[..read]);
+    self.file_id = file.metadata().ok().and_then(|metadata| file_id(&metadata));
+    self.src = self.codec.decode(AdvisedFile::new(file, &path));
+    self.is_unopened = false;
+  }
+
+  /**
+   * A local file that isn't opened until its contents are given with load, so thousands of them can be listed
+   * without holding a file open for each. Until then it's taken to be gzipped if it ends in .gz, unless --codec says
+   */
+  pub fn unopened(path: &Path) -> LogFile {
+    let name = path.to_str().unwrap_or("<invalid path>").to_string();
+
+    let mut log = LogFile::new(Box::new(io::empty()), name);
+    log.path = Some(path.to_path_buf());
+    log.codec = codec::forced_source_codec(path).unwrap_or(if path.extension().is_some_and(|ext| ext == "gz") {
+      SourceCodec::Gzip
     } else {
-      Box::new(BufReader::new(GzDecoder::new(file)))
+      SourceCodec::Plain
+    });
+    log.is_unopened = true;
+    log
+  }
+
+  /**
+   * Read an unopened file from its whole contents, which are kept compressed if they were
+   */
+  #[cfg_attr(not(feature = "async"), allow(dead_code))]
+  pub fn load(&mut self, contents: Vec<u8>) {
+    let path = self.path.clone().expect("Only local files can be loaded");
+
+    self.codec = pick_codec(&self.name, &path, &contents[..contents.len().min(2)]);
+    self.is_unopened = false;
+
+    self.src = match self.codec {
+      SourceCodec::Plain => Box::new(Cursor::new(contents)),
+      codec => codec.decode(Cursor::new(contents)),
     };
+  }
 
-    LogFile {
-      src,
-      name,
-      line: 0,
-      is_completed: false,
-      next: None,
-    }
+  pub fn is_unopened(&self) -> bool {
+    self.is_unopened
   }
 
   pub fn from_stdin() -> LogFile {
-    let src = Box::new(BufReader::new(stdin()));
+    LogFile::new(Box::new(BufReader::new(stdin())), "<stdin>".to_string())
+  }
+
+  /**
+   * Read lines from anything else, like text built into saw
+   */
+  pub fn from_reader(src: Box<dyn BufRead + Send>, name: &str) -> LogFile {
+    LogFile::new(src, name.to_string())
+  }
 
+  /**
+   * Read stdin as several sources at once, where each line is 'TAG<tab>LINE' and TAGS lists every tag to expect.
+   * Each tag becomes its own source, tagged with '_source', so they are merged like separate files would be.
+   */
+  pub fn from_multiplexed_stdin(raw: &str) -> Result<Vec<LogFile>, String> {
+    let tags: Vec<String> = raw.split(',').map(|tag| tag.trim().to_string()).collect();
+
+    if tags.iter().any(|tag| tag.is_empty()) {
+      return Err(format!("Argument --stdin-multiplex contains an empty tag in '{raw}'"));
+    }
+
+    let logs = multiplex::split(Box::new(BufReader::new(stdin())), "<stdin>", &tags)
+      .into_iter()
+      .zip(&tags)
+      .map(|(reader, tag)| {
+        let mut log = LogFile::new(Box::new(BufReader::new(reader)), format!("<stdin>:{tag}"));
+        log.tags.insert("_source".to_string(), Value::String(tag.clone()));
+        log
+      })
+      .collect();
+
+    Ok(logs)
+  }
+
+  /**
+   * Expand a remote glob in the form [user@]host:path over ssh, and stream each matching file back once it's read.
+   * Every line is tagged with '_host' so merged logs from many machines can be told apart.
+   */
+  pub fn from_ssh(raw: &str) -> Result<Vec<LogFile>, String> {
+    let (target, path) = raw.split_once(':')
+      .ok_or_else(|| format!("Argument --ssh '{raw}' must be in the form [user@]host:/path/to/logs"))?;
+    let host = target.rsplit('@').next().unwrap_or(target).to_string();
+
+    let listing = Command::new("ssh")
+      .args([target, "ls", "-1d", "--", &remote_glob(path)])
+      .stderr(Stdio::inherit())
+      .output()
+      .map_err(|_| "Failed to run ssh, is it installed and on the PATH?".to_string())?;
+
+    if !listing.status.success() {
+      return Err(format!("Failed to list remote files for '{raw}'"));
+    }
+
+    let logs = String::from_utf8_lossy(&listing.stdout)
+      .lines()
+      .filter(|file| !file.is_empty())
+      .map(|file| {
+        let mut log = LogFile::new(Box::new(io::empty()), format!("{target}:{file}"));
+        log.tags.insert("_host".to_string(), Value::String(host.clone()));
+        log.remote = Some((target.to_string(), file.to_string()));
+        log
+      })
+      .collect();
+
+    Ok(logs)
+  }
+
+  /**
+   * Was this source given with --ssh?
+   */
+  pub fn is_remote(&self) -> bool {
+    self.remote.is_some()
+  }
+
+  /**
+   * Start streaming a remote file over ssh
+   */
+  fn open_remote(&mut self, target: &str, file: &str) {
+    // gzip -f passes plain text through untouched, so this reads both
+    let mut child = Command::new("ssh")
+      .args([target, "gzip", "-cdf", "--", &shell_quote(file)])
+      .stdin(Stdio::null())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::inherit())
+      .spawn()
+      .unwrap_or_else(|_| panic!("Failed to run ssh to read {}", self.name));
+
+    let stdout = child.stdout.take().expect("Failed to read from ssh");
+    self.src = Box::new(BufReader::new(stdout));
+    self.child = Some(child);
+  }
+
+  /**
+   * Run CMD with sh and read its stdout, and its stderr as a second source. Each line of stderr is an event at the
+   * time it was written, unless WITH_STDERR, where stderr is read as a log of its own like stdout and so both pipes
+   * are drained on their own threads, as the merge may wait on one while the command fills the other.
+   * Every line is tagged with the command, its pid, which stream it came from and, where available, its cgroup.
+   */
+  pub fn from_command(cmd: &str, with_stderr: bool) -> Result<Vec<LogFile>, String> {
+    let mut child = Command::new("sh")
+      .args(["-c", cmd])
+      .stdin(Stdio::null())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .map_err(|_| format!("Failed to run command '{cmd}'"))?;
+
+    let pid = child.id();
+
+    let mut tags = Map::new();
+    tags.insert("_command".to_string(), Value::String(cmd.to_string()));
+    tags.insert("_pid".to_string(), Value::from(pid));
+
+    // linux only, the first line is enough to tell which service or container the process ran in
+    if let Ok(cgroup) = std::fs::read_to_string(format!("/proc/{pid}/cgroup")) {
+      if let Some(first) = cgroup.lines().next() {
+        tags.insert("_cgroup".to_string(), Value::String(first.to_string()));
+      }
+    }
+
+    let mut logs = vec![];
+
+    if let Some(stderr) = child.stderr.take() {
+      let stderr: Box<dyn BufRead + Send> = if with_stderr {
+        Box::new(BufReader::new(Drained::new(stderr)))
+      } else {
+        Box::new(BufReader::new(ArrivalReader::new(stderr)))
+      };
+
+      let mut log = LogFile::new(stderr, format!("{cmd} (stderr)"));
+      log.tags = tags.clone();
+      log.tags.insert("_stream".to_string(), Value::String("stderr".to_string()));
+      logs.push(log);
+    }
+
+    let stdout = child.stdout.take().expect("Failed to read from command");
+    let stdout: Box<dyn BufRead + Send> = if with_stderr { Box::new(BufReader::new(Drained::new(stdout))) } else { Box::new(BufReader::new(stdout)) };
+    let mut log = LogFile::new(stdout, cmd.to_string());
+    log.tags = tags;
+    log.tags.insert("_stream".to_string(), Value::String("stdout".to_string()));
+    log.child = Some(child);
+    log.report_exit = true;
+    logs.push(log);
+
+    Ok(logs)
+  }
+
+  fn new(src: Box<dyn BufRead + Send>, name: String) -> LogFile {
     LogFile {
       src,
-      name: "<stdin>".to_string(),
+      name: name.into(),
       line: 0,
+      offset: 0,
+      options: ReadOptions::default(),
+      tags: Map::new(),
+      path: None,
+      codec: SourceCodec::Plain,
+      is_unopened: false,
+      last_time: None,
+      child: None,
+      report_exit: false,
+      remote: None,
+      stats: None,
+      raw_copy: None,
+      not_utf8: None,
+      corrupt: None,
       is_completed: false,
+      next_key: vec![],
+      buffer: String::new(),
+      partial: String::new(),
+      is_waiting: false,
+      waiting_since: None,
+      file_id: None,
+      backward: None,
       next: None,
     }
   }
 
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn path(&self) -> Option<&PathBuf> {
+    self.path.as_ref()
+  }
+
+  pub fn codec(&self) -> SourceCodec {
+    self.codec
+  }
+
+  pub fn is_compressed(&self) -> bool {
+    self.codec != SourceCodec::Plain
+  }
+
+  /**
+   * Start counting what is read from this source, returning the counts to be printed once done
+   */
+  pub fn track_stats(&mut self) -> Arc<Mutex<SourceStats>> {
+    self.stats.get_or_insert_with(|| SourceStats::new(&self.name, self.path.as_ref())).clone()
+  }
+
+  /**
+   * Copy every line read from now on to the sink, byte for byte
+   */
+  pub fn copy_raw_to(&mut self, sink: RawSink) {
+    self.raw_copy = Some(sink);
+  }
+
+  fn record<F: FnOnce(&mut SourceStats)>(&self, update: F) {
+    if let Some(stats) = &self.stats {
+      update(&mut stats.lock().expect("Source stats were poisoned"));
+    }
+  }
+
+  /**
+   * Apply the options and read the first line, returning false if there wasn't one
+   */
+  pub fn start(&mut self, options: ReadOptions) -> bool {
+    if self.is_unopened {
+      panic!("Attempt to read file {} before it was loaded!", self.name);
+    }
+
+    if let Some((target, file)) = self.remote.take() {
+      self.open_remote(&target, &file);
+    }
+
+    self.options = options;
+
+    // events are only in order once the whole document is decoded, and then they're read like any JSON lines
+    if options.input.is_some_and(InputFormat::is_document) {
+      let mut raw = vec![];
+      self.src.read_to_end(&mut raw).unwrap_or_else(|err| panic!("Failed to read file {}: {err}", self.name));
+
+      let decoded = match loki::decode(&raw, &self.name) {
+        Some(events) => events.join("\n").into_bytes(),
+        None => raw,
+      };
+
+      self.src = Box::new(Cursor::new(decoded));
+    }
+
+    if let Some(InputFormat::Frames(framing)) = options.input {
+      let src = mem::replace(&mut self.src, Box::new(io::empty()));
+      self.src = Box::new(BufReader::new(FrameReader::new(src, *framing, self.name.clone())));
+    }
+
+    if options.reverse {
+      self.backward = Some(self.open_backward());
+    }
+
+    self.advance()
+  }
+
+  fn open_backward(&mut self) -> Backward {
+    let is_decoded = self.options.input.is_some_and(InputFormat::is_decoded);
+
+    if let (Some(path), false, false) = (&self.path, self.is_compressed(), is_decoded) {
+      let file = File::open(path).unwrap_or_else(|_| panic!("Failed to open file {}", self.name));
+
+      return Backward::File(BackwardReader::new(file).unwrap_or_else(|err| panic!("Failed to read file {}: {err}", self.name)));
+    }
+
+    let mut lines = vec![];
+    let mut offset = 0;
+
+    loop {
+      let mut line = String::new();
+
+      match self.src.read_line(&mut line) {
+        Ok(0) => break,
+        Ok(read) => {
+          lines.push((line, offset));
+          offset += read as u64;
+        }
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => sleep(WAIT),
+        Err(err) if self.is_compressed() => {
+          eprintln!("Warning: file '{}' has truncated or damaged {} data at byte {offset}, skipping the rest of it: {err}", self.name, self.codec.name());
+          break;
+        }
+        Err(_) => panic!("Failed to read line from file {}", self.name),
+      }
+    }
+
+    Backward::Buffered(lines)
+  }
+
+  pub fn is_completed(&self) -> bool {
+    self.is_completed
+  }
+
+  /**
+   * With --follow, has this source reached the end of what has been written so far?
+   */
+  pub fn is_waiting(&self) -> bool {
+    self.is_waiting
+  }
+
+  /**
+   * With --follow, how long this source has been waiting for more lines, if it is
+   */
+  pub fn waited(&self) -> Option<Duration> {
+    self.waiting_since.map(|since| since.elapsed())
+  }
+
+  /**
+   * Time of the last line read, which nothing read from this source later should be before
+   */
+  pub fn last_time(&self) -> Option<LocalDateTime> {
+    self.last_time
+  }
+
+  /**
+   * Only plain local files are followed. Gzipped files are finished once written, and pipes end when their writer does
+   */
+  fn is_followed(&self) -> bool {
+    self.options.follow && self.path.is_some() && !self.is_compressed()
+  }
+
   pub fn time(&self) -> LocalDateTime {
     if self.is_completed {
       panic!("Attempt to peek at a completed LogFile!")
     }
 
-    self.next.as_ref().unwrap().time
+    self.next.as_ref().unwrap().time.expect("Attempt to merge a line without a time!")
+  }
+
+  /**
+   * Which of two sources' next lines should be written first, by time or by --sort-by
+   */
+  pub fn order(&self, other: &LogFile) -> Ordering {
+    if self.options.sort_by.is_some() {
+      return self.next_key.cmp(&other.next_key);
+    }
+
+    self.time().cmp(&other.time())
   }
 
   /**
@@ -99,45 +640,229 @@ impl LogFile {
     // do this until do_advance returns true
     while !self.do_advance() {}
 
+    if !self.is_waiting {
+      self.waiting_since = None;
+    } else if self.waiting_since.is_none() {
+      self.waiting_since = Some(Instant::now());
+    }
+
     // do_advance will set this flag
     !self.is_completed
   }
 
   // returns true if a value was successfully read, false if something went wrong with the line.
   fn do_advance(&mut self) -> bool {
-    let mut raw = String::new();
-    let read = self.src
-      .read_line(&mut raw)
-      .unwrap_or_else(|_| panic!("Failed to read line from file {}", self.name));
+    // most lines are about the same length, so the buffer rarely needs to grow after the first few
+    let mut raw = mem::take(&mut self.buffer);
+    raw.clear();
+
+    let advanced = self.read_next(&mut raw);
+
+    self.buffer = raw;
+    advanced
+  }
+
+  fn read_next(&mut self, raw: &mut String) -> bool {
+    self.is_waiting = false;
+
+    let read = match &mut self.backward {
+      Some(Backward::File(reader)) => reader.read_line(raw).map(|(read, offset)| {
+        self.offset = offset;
+        read
+      }),
+      Some(Backward::Buffered(lines)) => Ok(lines.pop().map_or(0, |(line, offset)| {
+        raw.push_str(&line);
+        self.offset = offset;
+        line.len()
+      })),
+      None => self.read_bytes(raw),
+    };
+
+    let read = match read {
+      Ok(read) => read,
+      // a source with nothing to read yet, like the stderr of a --command, is asked again once another has been read
+      Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+        self.is_waiting = true;
+        return true;
+      }
+      // rotated files are often appended to or cut off mid write, so keep everything before the damage
+      Err(err) if self.is_compressed() => {
+        eprintln!(
+          "Warning: file '{}' has truncated or damaged {} data at line {} (byte {}), skipping the rest of it: {err}",
+          self.name, self.codec.name(), self.line, self.offset,
+        );
+
+        self.is_completed = true;
+        return true;
+      }
+      Err(_) => panic!("Failed to read line from file {}", self.name),
+    };
+
+    // a line is only read once its newline has been written, until then what there is of it is held back
+    let read = if self.is_followed() {
+      if !raw.ends_with('\n') {
+        if read == 0 && self.partial.is_empty() {
+          self.reopen_if_replaced();
+        }
+
+        self.partial.push_str(raw);
+        self.is_waiting = true;
+        return true;
+      }
+
+      if !self.partial.is_empty() {
+        raw.insert_str(0, &mem::take(&mut self.partial));
+      }
+
+      raw.len()
+    } else {
+      read
+    };
+
     let file = self.name.clone();
     let line = self.line;
+    let offset = self.offset;
     self.line += 1;
+    self.offset += read as u64;
+
+    if read > 0 {
+      self.record(|stats| {
+        stats.lines += 1;
+        stats.bytes += read as u64;
+      });
+    }
 
     if read == 0 {
       // EOF
+      self.end_corrupt(self.offset);
+
+      if let Some(mut child) = self.child.take() {
+        let status = child.wait().unwrap_or_else(|_| panic!("Failed to wait on process for {}", self.name));
+
+        if self.report_exit {
+          eprintln!("Command '{}' (pid {}) exited with {status}", self.name, child.id());
+        } else if !status.success() {
+          eprintln!("Process reading '{}' exited with {status}", self.name);
+        }
+      }
+
       self.is_completed = true;
       return true;
     }
 
-    let body = match serde_json::from_str(&raw) {
-      Ok(Value::Object(map)) => map,
+    // nothing after the end of the selected lines or bytes is needed, so stop reading the source early
+    if self.options.lines.is_after(line) || self.options.bytes.is_after(offset) {
+      if let Some(mut child) = self.child.take() {
+        // it's fine if the process already finished on its own
+        let _ = child.kill();
+        let _ = child.wait();
+      }
+
+      self.is_completed = true;
+      return true;
+    }
+
+    let not_utf8 = self.not_utf8.take();
+
+    if self.options.lines.is_before(line) || self.options.bytes.is_before(offset) {
+      return false;
+    }
+
+    let bytes = not_utf8.as_deref().unwrap_or(raw.as_bytes());
+
+    if let Some(sink) = &self.raw_copy {
+      raw::copy(sink, bytes);
+    }
+
+    let input = self.options.input.unwrap_or(&InputFormat::Json);
+
+    // the line was read lossily so it can be kept with --passthrough-invalid, but it's never an event as it is
+    let read = if not_utf8.is_some() { None } else { self.read_event(input, raw) };
+
+    // where a record was found after corrupt data at the start of the line
+    let mut skipped = 0;
+
+    let read = read.or_else(|| {
+      let (start, map) = self.resync(bytes)?;
+      skipped = start;
+      Some(map)
+    });
+
+    if skipped > 0 {
+      self.corrupt.get_or_insert((line, offset));
+    }
+
+    let mut body = match read {
+      Some(map) => map,
+      // there's nothing worth keeping from a blank line
+      _ if self.options.passthrough_invalid && raw.trim().is_empty() => return false,
+      _ if self.options.passthrough_invalid => self.wrap_invalid(raw),
+      // reported all at once when the next record is found
+      _ if self.options.resync => {
+        self.corrupt.get_or_insert((line, offset));
+        return false;
+      }
       _ => {
-        eprintln!("Invalid JSON in file '{file}' at line {line}");
+        let what = if not_utf8.is_some() { "UTF-8" } else { input.name() };
+
+        eprintln!("Invalid {what} in file '{file}' at line {line} (byte {offset})");
+        self.dump_raw(bytes);
+        self.record(|stats| stats.errors += 1);
         return false;
       }
     };
 
+    self.end_corrupt(offset + skipped as u64);
+
+    // saw's own header and footer records describe a previous run, they aren't events
+    if body.contains_key(PROVENANCE_KEY) {
+      return false;
+    }
+
+    for (key, value) in &self.tags {
+      body.insert(key.clone(), value.clone());
+    }
+
+    if let Some(preset) = self.options.preset {
+      preset.normalize(&mut body);
+    }
+
+    if let Some(levels) = self.options.levels {
+      levels.apply(&mut body);
+    }
+
+    if let Some(time) = self.options.time {
+      time.normalize(&mut body);
+    }
+
     let time = match &body.get("time") // pluck time out
       .and_then(|time| time.as_str()) // convert it to a string
+      .filter(|_| !self.options.untimed) // nothing will look at the time, so it only has to be valid
       .and_then(|time| LocalDateTime::from_str(time).ok()) // convert to type
     {
-      Some(time) => *time,
+      Some(time) => Some(*time),
+      None if self.options.keep_order => None,
+      None if self.options.untimed && body.get("time").and_then(Value::as_str).is_some_and(stream::is_valid_time) => None,
+      // merging by other fields doesn't need a time
+      None if self.options.sort_by.is_some_and(|sort_by| !sort_by.uses_time()) => None,
       None => {
-        eprintln!("Invalid or missing 'time' field in JSON from file '{file}' at line {line}");
+        eprintln!("Invalid or missing 'time' field in {} from file '{file}' at line {line} (byte {offset})", input.name());
+        self.dump_raw(raw.as_bytes());
+        self.record(|stats| stats.errors += 1);
         return false;
       }
     };
 
+    if time.is_some() {
+      self.last_time = time;
+    }
+
+    self.record(|stats| stats.add_event(time));
+
+    if let Some(sort_by) = self.options.sort_by {
+      self.next_key = sort_by.key(&body, time);
+    }
+
     let src = FileSource { file, line };
 
     self.next = Some(Line {
@@ -147,50 +872,344 @@ impl LogFile {
     });
 
     // successfully read a value
-    return true;
+    true
+  }
+}
+
+impl LogFile {
+  /**
+   * With --follow, start again from the beginning of the file if it was truncated, or if it was rotated and another
+   * file now has its name. A file that was deleted is waited for until one with the same name shows up.
+   */
+  fn reopen_if_replaced(&mut self) {
+    let Some(path) = &self.path else {
+      return;
+    };
+
+    let Ok(metadata) = path.metadata() else {
+      return;
+    };
+
+    let replaced = file_id(&metadata) != self.file_id;
+    let truncated = metadata.len() < self.offset;
+
+    if !replaced && !truncated {
+      return;
+    }
+
+    let Ok(file) = File::open(path) else {
+      return;
+    };
+
+    eprintln!("File '{}' was {}, reading it again from the start", self.name, if replaced { "replaced" } else { "truncated" });
+
+    self.file_id = file.metadata().ok().and_then(|metadata| file_id(&metadata));
+    self.src = Box::new(BufReader::with_capacity(codec::read_buffer(), AdvisedFile::new(file, path)));
+    self.line = 0;
+    self.offset = 0;
+  }
+
+  /**
+   * Read the next line forwards. Bytes that aren't UTF-8 don't fail the whole source, the line is read lossily and
+   * its bytes are kept so it can be reported as it was
+   */
+  fn read_bytes(&mut self, raw: &mut String) -> io::Result<usize> {
+    let mut bytes = mem::take(raw).into_bytes();
+    let read = self.src.read_until(b'\n', &mut bytes)?;
+
+    match String::from_utf8(bytes) {
+      Ok(text) => {
+        *raw = text;
+        self.not_utf8 = None;
+      }
+      Err(err) => {
+        *raw = String::from_utf8_lossy(err.as_bytes()).into_owned();
+        self.not_utf8 = Some(err.into_bytes());
+      }
+    }
+
+    Ok(read)
+  }
+
+  fn read_event(&self, input: &InputFormat, raw: &str) -> Option<Map<String, Value>> {
+    match (input, self.options.projection) {
+      (InputFormat::Json | InputFormat::Loki | InputFormat::Frames(_), projection) if self.options.lenient_json => lenient::read(raw)
+        .map(|map| retain(map, projection)),
+      (InputFormat::Json | InputFormat::Loki | InputFormat::Frames(_), Some(projection)) => projection.read(raw),
+      (_, projection) => input.read(raw).map(|map| retain(map, projection)),
+    }
+  }
+
+  /**
+   * Find a record after corrupt data at the start of a line, for --resync. Garbage without a newline, or a write torn
+   * off partway through, ends up joined to the start of the next record, so the first place a JSON object starts
+   * where the rest of the line can be read as one is taken as where the corruption ends
+   */
+  fn resync(&self, bytes: &[u8]) -> Option<(usize, Map<String, Value>)> {
+    if !self.options.resync {
+      return None;
+    }
+
+    bytes.windows(2)
+      .enumerate()
+      .skip(1)
+      .filter(|(_, pair)| *pair == b"{\"")
+      .find_map(|(start, _)| {
+        let rest = std::str::from_utf8(&bytes[start..]).ok()?;
+
+        Some((start, self.read_event(&InputFormat::Json, rest)?))
+      })
+  }
+
+  /**
+   * Report the corrupt data being skipped, if there is any, now that the record after it starts at END
+   */
+  fn end_corrupt(&mut self, end: u64) {
+    if let Some((line, start)) = self.corrupt.take() {
+      eprintln!("Skipped {} bytes of corrupt data in file '{}' from line {line}, bytes {start} to {end}", end - start, self.name);
+      self.record(|stats| stats.errors += 1);
+    }
+  }
+
+  fn dump_raw(&self, bytes: &[u8]) {
+    if self.options.debug_raw {
+      eprint!("{}", raw::hexdump(bytes));
+    }
+  }
+
+  /**
+   * Turn a line that isn't JSON into an event, using the time of the line before it or,
+   * if it's the first line, the time the file was last modified. With --deterministic a first line has no time,
+   * since a copy of the file would have another
+   */
+  fn wrap_invalid(&self, raw: &str) -> Map<String, Value> {
+    let mut body = Map::new();
+
+    body.insert("message".to_string(), Value::String(raw.trim_end_matches(['\r', '\n']).to_string()));
+    body.insert("_unparsed".to_string(), Value::Bool(true));
+
+    let time = self.last_time.or_else(|| {
+      if deterministic::is_enabled() {
+        return None;
+      }
+
+      let modified = self.path.as_ref()?.metadata().ok()?.modified().ok()?;
+      let millis = modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64;
+
+      Some(LocalDateTime::at_ms(millis.div_euclid(1000), millis.rem_euclid(1000) as i16))
+    });
+
+    if let Some(time) = time {
+      body.insert("time".to_string(), Value::String(time.iso().to_string()));
+    }
+
+    body
   }
 }
 
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<u64> {
+  use std::os::unix::fs::MetadataExt;
+
+  Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<u64> {
+  None
+}
+
+/**
+ * Drop the fields --parse-only leaves out from an event that had to be read whole, like lenient JSON or logfmt
+ */
+fn retain(mut values: Map<String, Value>, projection: Option<&Projection>) -> Map<String, Value> {
+  if let Some(projection) = projection {
+    projection.retain(&mut values);
+  }
+
+  values
+}
+
+/**
+ * RAW in single quotes, so a remote shell takes it as it is
+ */
+fn shell_quote(raw: &str) -> String {
+  format!("'{}'", raw.replace('\'', "'\\''"))
+}
+
+/**
+ * A remote path for --ssh quoted so that only * and ? are expanded by the remote shell, and a leading ~/ is the remote
+ * home directory. Spaces and anything else the shell would act on are taken as they are
+ */
+fn remote_glob(path: &str) -> String {
+  let (home, rest) = match path.strip_prefix("~/") {
+    Some(rest) => ("~/", rest),
+    None => ("", path),
+  };
+
+  let mut quoted = home.to_string();
+  let mut literal = String::new();
+
+  for c in rest.chars() {
+    if c == '*' || c == '?' {
+      if !literal.is_empty() {
+        quoted.push_str(&shell_quote(&literal));
+        literal.clear();
+      }
+
+      quoted.push(c);
+    } else {
+      literal.push(c);
+    }
+  }
+
+  if !literal.is_empty() {
+    quoted.push_str(&shell_quote(&literal));
+  }
+
+  quoted
+}
+
 pub struct Aggregator {
   logs: Vec<LogFile>,
+  keep_order: bool,
+  /// take the newest line first, from sources being read backwards
+  reverse: bool,
+  /// with --max-open-files, the files that haven't been opened yet
+  pending: Option<Box<Pending>>,
+}
+
+struct Pending {
+  /// the next one to open last
+  files: Vec<LogFile>,
+  max_open: usize,
+  options: ReadOptions,
+  /// time of the last line taken, to notice a file opened late that starts before it
+  last: Option<LocalDateTime>,
 }
 
 impl Aggregator {
-  pub fn new(mut logs: Vec<LogFile>) -> Aggregator {
+  pub fn new(mut logs: Vec<LogFile>, options: ReadOptions) -> Aggregator {
     // load up initial values and remove any that are empty
     logs.iter_mut().for_each(|log| {
-      log.advance();
+      log.start(options);
     });
 
     // keep only those that are not completed
     logs.retain(|log| !log.is_completed);
 
-    // sort them most oldest first
-    logs.sort_unstable_by_key(|log| log.time());
+    // a single source has nothing to be merged with, so it's read straight through
+    let keep_order = options.keep_order || logs.len() < 2;
+
+    // sort them most oldest first, unless we've been asked to keep them as they were given. Those with nothing to
+    // read yet go last
+    if !keep_order {
+      logs.sort_by(|left, right| match (left.is_waiting, right.is_waiting) {
+        (false, false) => left.order(right),
+        (left, right) => left.cmp(&right),
+      });
+    }
+
+    Aggregator { logs, keep_order, reverse: options.reverse, pending: None }
+  }
+
+  /**
+   * Like new, but with at most MAX_OPEN files open at once, for --max-open-files. Files are opened in the order they
+   * seem to start, each as one before it finishes. Sources that aren't files are always open and count toward it
+   */
+  pub fn lazy(logs: Vec<LogFile>, options: ReadOptions, max_open: usize) -> Aggregator {
+    let keep_order = options.keep_order || logs.len() < 2;
+    let (unopened, opened): (Vec<LogFile>, Vec<LogFile>) = logs.into_iter().partition(LogFile::is_unopened);
+
+    // the order they were given is kept with --keep-order-of-input
+    let mut files = if keep_order { unopened } else { lazy::order(unopened, options.preset) };
+    files.reverse();
 
-    Aggregator { logs }
+    let pending = Pending { files, max_open, options, last: None };
+    let mut agg = Aggregator { keep_order, pending: Some(Box::new(pending)), ..Aggregator::new(opened, options) };
+    agg.open_pending();
+    agg
   }
 
   /**
-   * Skip any file that doesn't contain values in the range
+   * Open files waiting for a turn while there's room, or the next one when none are left open
+   */
+  fn open_pending(&mut self) {
+    let Some(pending) = &mut self.pending else {
+      return;
+    };
+
+    while self.logs.len() < pending.max_open || self.logs.is_empty() {
+      let Some(mut log) = pending.files.pop() else {
+        break;
+      };
+
+      log.open();
+
+      if !log.start(pending.options) {
+        continue;
+      }
+
+      let early = pending.options.sort_by.is_none() && !self.keep_order
+        && pending.last.is_some_and(|last| log.next.as_ref().and_then(|line| line.time).is_some_and(|time| time < last));
+
+      if early {
+        eprintln!(
+          "Warning: file '{}' starts before events that were already written, so it isn't merged in order with them. Pass a higher --max-open-files to fix this",
+          log.name(),
+        );
+      }
+
+      self.logs.push(log);
+    }
+
+    if pending.files.is_empty() {
+      self.pending = None;
+    }
+  }
+
+  /**
+   * If every file starts on a different day then, assuming each file only holds a single day as --daily promises,
+   * none of them overlap and each can be read on its own. Returns one Aggregator per file, oldest first,
+   * or gives this one back untouched if that isn't possible.
+   */
+  pub fn split_by_day(self, zone: &TimeZone) -> Result<Vec<Aggregator>, Aggregator> {
+    if self.logs.len() < 2 || self.keep_order || self.logs.iter().any(LogFile::is_waiting) {
+      return Err(self);
+    }
+
+    // logs are already sorted by time, so matching days would be next to each other
+    let distinct = self.logs.windows(2).all(|pair| zone.to_local(pair[0].time()).date() != zone.to_local(pair[1].time()).date());
+
+    if !distinct {
+      return Err(self);
+    }
+
+    Ok(self.logs.into_iter().map(|log| Aggregator { logs: vec![log], keep_order: false, reverse: false, pending: None }).collect())
+  }
+
+  /**
+   * Skip any file that doesn't contain values in the range, with days starting in the zone given
   **/
-  pub fn filter_daily(&mut self, src: (Option<LocalDateTime>, Option<LocalDateTime>)) {
+  pub fn filter_daily(&mut self, src: (Option<LocalDateTime>, Option<LocalDateTime>), zone: &TimeZone) {
+    let day = |time: LocalDateTime| zone.to_local(time).date();
+
     match src {
       (None, None) => panic!("This case should have been prevented by the args parser"),
       (Some(min), None) => {
-        let range = min.date()..;
+        let range = day(min)..;
 
-        self.logs.retain(|log| range.contains(&log.time().date()));
+        self.logs.retain(|log| log.is_waiting || range.contains(&day(log.time())));
       }
       (None, Some(max)) => {
-        let range = ..=max.date();
+        let range = ..=day(max);
 
-        self.logs.retain(|log| range.contains(&log.time().date()));
+        self.logs.retain(|log| log.is_waiting || range.contains(&day(log.time())));
       }
       (Some(min), Some(max)) => {
-        let range = min.date()..=max.date();
+        let range = day(min)..=day(max);
 
-        self.logs.retain(|log| range.contains(&log.time().date()));
+        self.logs.retain(|log| log.is_waiting || range.contains(&day(log.time())));
       }
     }
   }
@@ -200,16 +1219,65 @@ impl Iterator for Aggregator {
   type Item = Line;
 
   fn next(&mut self) -> Option<Self::Item> {
-    if self.logs.is_empty() {
-      return None;
+    self.open_pending();
+
+    loop {
+      // sources that had nothing to read, like the stderr of a --command, are asked again
+      for log in self.logs.iter_mut().filter(|log| log.is_waiting) {
+        log.advance();
+      }
+
+      self.logs.retain(|log| !log.is_completed);
+
+      if self.logs.is_empty() {
+        return None;
+      }
+
+      match self.next_ready() {
+        Some(line) => return Some(line),
+        None => sleep(WAIT),
+      }
     }
+  }
+}
 
-    let (min_index, min) = self
-      .logs
-      .iter_mut()
-      .enumerate()
-      .min_by(|(_, l), (_, r)| l.time().cmp(&r.time()))
-      .unwrap();
+impl Aggregator {
+  /**
+   * The next line, or None while it can't be told yet. A source that's waiting only ever gets lines stamped with when
+   * they arrived, so once the oldest line of the others is no later than now it can't be beaten
+   */
+  fn next_ready(&mut self) -> Option<Line> {
+    if self.keep_order {
+      let first = &mut self.logs[0];
+
+      if first.is_waiting {
+        return None;
+      }
+
+      let result = first.take();
+
+      if !first.advance() {
+        self.logs.remove(0);
+      }
+
+      return Some(result);
+    }
+
+    let waiting = self.logs.iter().any(LogFile::is_waiting);
+
+    // ties go to the first source forwards and so the last one backwards, which max_by picks, so reversing the
+    // output gives exactly the order it would have had when read forwards
+    let ready = self.logs.iter_mut().enumerate().filter(|(_, log)| !log.is_waiting);
+
+    let (min_index, min) = if self.reverse {
+      ready.max_by(|(_, l), (_, r)| l.order(r))?
+    } else {
+      ready.min_by(|(_, l), (_, r)| l.order(r))?
+    };
+
+    if waiting && (min.options.sort_by.is_some() || min.time() > arrival::now()) {
+      return None;
+    }
 
     let result = min.take();
 
@@ -218,6 +1286,10 @@ impl Iterator for Aggregator {
       self.logs.remove(min_index);
     }
 
+    if let Some(pending) = &mut self.pending {
+      pending.last = result.time;
+    }
+
     Some(result)
   }
 }
@@ -0,0 +1,106 @@
+use datetime::{ISO, LocalDateTime};
+use serde_json::{Map, Value};
+
+/**
+ * Turn a Grafana Loki export into one JSON event per line, oldest first. Reads query responses saved from the API,
+ * like {"data":{"result":[{"stream":{...},"values":[["NANOS","LINE"],...]}]}}, as well as their bare 'data' and push
+ * payloads with 'streams'. Several documents one after another, like saved pages, are all read.
+ *
+ * Each event has the stream's labels, then any structured metadata, then the fields of the line itself if it's a JSON
+ * object or else the line as 'message'. 'time' is always Loki's timestamp, since that is what the export is ordered by.
+ *
+ * None if the source doesn't start with a Loki export, so it can be read as JSON lines, like the files it's merged with
+ */
+pub fn decode(raw: &[u8], name: &str) -> Option<Vec<String>> {
+  let mut documents = serde_json::Deserializer::from_slice(raw).into_iter::<Value>().peekable();
+
+  if !documents.peek()?.as_ref().is_ok_and(|first| streams(first).is_some()) {
+    return None;
+  }
+
+  let mut events: Vec<(i128, Map<String, Value>)> = vec![];
+
+  for document in documents {
+    let document = document.unwrap_or_else(|err| panic!("Invalid Loki export in file '{name}': {err}"));
+
+    let streams = streams(&document)
+      .unwrap_or_else(|| panic!("Invalid Loki export in file '{name}': expected streams under 'data.result', 'result' or 'streams'"));
+
+    for stream in streams {
+      read_stream(stream, name, &mut events);
+    }
+  }
+
+  // streams are merged, and the API returns the newest first by default
+  events.sort_by_key(|(nanos, _)| *nanos);
+
+  Some(events.into_iter()
+    .map(|(_, event)| Value::Object(event).to_string())
+    .collect())
+}
+
+fn streams(document: &Value) -> Option<&Vec<Value>> {
+  let data = document.get("data").unwrap_or(document);
+
+  if let Some(kind) = data.get("resultType").and_then(Value::as_str) {
+    if kind != "streams" {
+      panic!("Loki export holds '{kind}' results, only log 'streams' can be read");
+    }
+  }
+
+  data.get("result").or_else(|| data.get("streams"))?.as_array()
+}
+
+fn read_stream(stream: &Value, name: &str, events: &mut Vec<(i128, Map<String, Value>)>) {
+  let labels = stream.get("stream").and_then(Value::as_object).cloned().unwrap_or_default();
+
+  let Some(values) = stream.get("values").and_then(Value::as_array) else {
+    return;
+  };
+
+  for entry in values {
+    let Some((nanos, line, metadata)) = read_entry(entry) else {
+      eprintln!("Invalid Loki entry in file '{name}': {entry}");
+      continue;
+    };
+
+    let mut event = labels.clone();
+
+    if let Some(metadata) = metadata {
+      event.extend(metadata.iter().map(|(key, value)| (key.clone(), value.clone())));
+    }
+
+    match serde_json::from_str(line) {
+      Ok(Value::Object(fields)) => event.extend(fields),
+      _ => {
+        event.insert("message".to_string(), Value::String(line.to_string()));
+      }
+    }
+
+    let millis = nanos.div_euclid(1_000_000) as i64;
+    let time = LocalDateTime::at_ms(millis.div_euclid(1000), millis.rem_euclid(1000) as i16);
+
+    event.insert("time".to_string(), Value::String(time.iso().to_string()));
+
+    events.push((nanos, event));
+  }
+}
+
+/// (nanoseconds since the epoch, line, structured metadata) of one entry
+type Entry<'a> = (i128, &'a str, Option<&'a Map<String, Value>>);
+
+/// [NANOS, LINE] or [NANOS, LINE, {METADATA}], where NANOS is a string of nanoseconds since the epoch
+fn read_entry(entry: &Value) -> Option<Entry<'_>> {
+  let parts = entry.as_array()?;
+
+  let nanos = match parts.first()? {
+    Value::String(raw) => raw.parse().ok()?,
+    Value::Number(raw) => raw.as_i64()? as i128,
+    _ => return None,
+  };
+
+  let line = parts.get(1)?.as_str()?;
+  let metadata = parts.get(2).and_then(Value::as_object);
+
+  Some((nanos, line, metadata))
+}
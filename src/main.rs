@@ -2,83 +2,818 @@ extern crate core;
 #[macro_use]
 extern crate lazy_static;
 
-use std::fs::File;
-use std::io::{BufWriter, stdout, Write};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::env;
+use std::io::{BufWriter, stdout};
 use std::path::PathBuf;
+use std::process::exit;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
 use datetime::LocalDateTime;
-use flate2::Compression;
-use flate2::write::GzEncoder;
+use serde_json::Value;
 
 use args::Arguments;
 
+use crate::anomaly::AnomalyDetector;
+use crate::audit::Audit;
+use crate::checksum::{Checksum, OutputFile};
 use crate::chunk::{ChunkedWriter, ChunkInfo, LogWriter};
+use crate::clipboard::ClipboardWriter;
+use crate::codec::CompressionRoutes;
+use crate::control::Control;
+use crate::correlate::Correlator;
+use crate::crypt::FieldCrypto;
+use crate::deadline::{Deadline, DEADLINE_EXIT_CODE};
+use crate::escalate::Escalation;
+use crate::escape::{EscapeWriter, JsonEscape};
+use crate::exists::OnExists;
+use crate::expect::EXPECT_EXIT_CODE;
+use crate::external::{ExternalSort, ExternalSortStage};
 use crate::filter::FilterSet;
-use crate::log::{Aggregator, Line, LogFile};
-use crate::pretty::PrettyDescriptor;
+use crate::fingerprint::Fingerprint;
+use crate::follow::Follower;
+use crate::frame::Framing;
+use crate::group::Grouping;
+use crate::html::HtmlReport;
+use crate::link::{Linker, LinkStage};
+use crate::log::{Aggregator, Line, LogFile, ReadOptions};
+use crate::order::KeyOrder;
+use crate::parallel::{LINE_BUFFER, ParallelReader, Stages};
+use crate::partition::Partition;
+use crate::plugin::{Plugin, PluginStage};
+use crate::pretty::{PrettyDescriptor, PrettyTheme};
+use crate::provenance::Provenance;
+use crate::quota::Quota;
+use crate::rate::RateLimit;
+use crate::raw::RawSink;
+use crate::reload::LiveConfig;
+use crate::repeat::RepeatWriter;
+use crate::replay::{LiveWriter, Replay};
+use crate::route::{Route, RouteWriter};
+use crate::secrets::{SECRETS_EXIT_CODE, SecretAction, SecretScanner};
+use crate::session::Sessionizer;
+use crate::shard::Shard;
+use crate::sketch::Sketch;
+use crate::snapshot::Snapshots;
+use crate::split::{SplitInterval, SplitWriter};
+use crate::summary::Summary;
+use crate::table::Table;
+use crate::tenant::Tenant;
+use crate::timestamp::TimeOutput;
+use crate::trace::TraceIds;
 use crate::translate::Translation;
+use crate::truncate::Truncation;
+use crate::upload::{ChunkWorkers, CHUNK_EXIT_CODE};
+use crate::verify::{SortCheck, UNSORTED_EXIT_CODE};
+use crate::watermark::Watermark;
+use crate::why::Why;
+use crate::zone::TimeZone;
 
+mod adaptive;
+mod age;
+mod aggregate;
+mod anomaly;
 mod args;
+mod arrival;
+mod audit;
+mod autotune;
+mod backward;
+mod cache;
+mod catalog;
+mod checksum;
 mod chunk;
+mod clipboard;
+mod codec;
+mod compact;
+mod compaction;
+mod concat;
+mod control;
+mod correlate;
+mod crypt;
+mod deadline;
+mod dedup;
+mod deterministic;
+mod escalate;
+mod escape;
+mod exists;
+mod expect;
+mod explain;
+mod external;
 mod filter;
+mod fingerprint;
+mod flags;
+mod follow;
+mod frame;
+mod functions;
+mod group;
+mod heavy;
+mod html;
+mod ignore;
+mod indented;
+mod index;
+mod input;
+mod integrity;
+mod lazy;
+mod lenient;
+mod levels;
+mod limits;
+mod link;
+mod locale;
 mod log;
+mod loki;
+mod manual;
+mod msgpack;
+mod multiplex;
+mod order;
+mod parallel;
+mod partition;
+mod patterns;
+mod pipeline;
+mod plan;
+mod plugin;
+#[cfg(feature = "async")]
+mod prefetch;
+mod preset;
 mod pretty;
+mod project;
+mod provenance;
+mod quota;
+mod rate;
+mod raw;
+mod reload;
+mod render;
+mod repeat;
+mod replay;
+mod retry;
+mod route;
+mod secrets;
+mod session;
+mod shard;
+mod sketch;
+mod snapshot;
+mod sort;
+mod split;
+mod stats;
+mod stream;
+mod summary;
+mod table;
+mod tenant;
+mod timestamp;
+mod trace;
 mod translate;
+mod truncate;
+mod upload;
 mod utils;
+mod verify;
+mod watermark;
+mod why;
+mod width;
+mod zone;
 
 fn main() {
-  let args = Arguments::parse();
+  if env::args().nth(1).is_some_and(|command| command == "compact") {
+    compaction::run(env::args().skip(2));
+    return;
+  }
+
+  if env::args().nth(1).is_some_and(|command| command == "index") {
+    index::run(env::args().skip(2));
+    return;
+  }
+
+  if env::args().nth(1).is_some_and(|command| command == "man") {
+    print!("{}", Arguments::manual());
+    return;
+  }
+
+  if env::args().nth(1).is_some_and(|command| command == "merge-manifests") {
+    catalog::run(env::args().skip(2));
+    return;
+  }
+
+  if env::args().nth(1).is_some_and(|command| command == "render") {
+    render::run(env::args().skip(2));
+    return;
+  }
+
+  if env::args().nth(1).is_some_and(|command| command == "verify") {
+    integrity::run(env::args().skip(2));
+    return;
+  }
+
+  let mut args = if env::args().nth(1).is_some_and(|command| command == "run") {
+    match pipeline::arguments(env::args().skip(2)) {
+      Some(raw) => Arguments::parse_from(raw),
+      None => return,
+    }
+  } else {
+    Arguments::parse()
+  };
+
+  if args.dry_run {
+    let ok = plan::print_plan(&args);
+    exit(if ok { 0 } else { 1 });
+  }
+
+  if let Some(event) = args.explain_filter.take() {
+    let kept = explain::explain(args, &event);
+    exit(if kept { 0 } else { 1 });
+  }
+
+  if let Some(age) = &args.ignore_older_than {
+    age.report();
+  }
+
+  if let Some(shard) = &args.shard {
+    args.sources.retain(|source| shard.owns_source(source.name()));
+  }
+
+  if concat::try_concat(&args) {
+    return;
+  }
+
+  // before anything else reads the sources or the filters, so they're all tuned
+  let line_buffer = match args.autotune.take() {
+    Some(autotune) => autotune.tune(&mut args),
+    None => LINE_BUFFER,
+  };
+
+  let source_names: Vec<String> = args.sources.iter().map(|source| source.name().to_string()).collect();
+
+  let options = ReadOptions {
+    preset: args.preset,
+    levels: args.levels.filter(|_| args.rewrite_levels),
+    projection: args.projection,
+    keep_order: args.keep_order,
+    sort_by: args.sort_by,
+    follow: args.follow,
+    reverse: false,
+    input: args.input,
+    time: args.time,
+    passthrough_invalid: args.passthrough_invalid,
+    lenient_json: args.lenient_json,
+    resync: args.resync,
+    debug_raw: args.debug_raw,
+    lines: args.lines,
+    bytes: args.bytes,
+    untimed: stream::can_stream(&args),
+  };
 
-  let mut agg = Aggregator::new(args.sources);
+  // the counts are shared with each translation, so they can be read after the translations have been moved away
+  let audit = if args.audit { Some(Audit::new(&args.translations)) } else { None };
+  let decrypt_failures = args.crypto.as_ref().map(|crypto| crypto.failures());
+  let secret_findings = args.secrets.as_ref().map(|secrets| (secrets.action(), secrets.findings()));
+  let truncated: Vec<_> = args.truncations.iter().map(|truncation| (truncation.field().to_string(), truncation.truncated())).collect();
 
-  if args.daily {
-    agg.filter_daily(args.range);
+  // the filters, translations and template --reload can replace. Taken before the indexes are used, since files can't
+  // be skipped for a filter that may change
+  // listening starts before anything is read, so a run can be paused or stopped from the very start
+  let control = args.control_socket.as_deref().map(Control::listen);
+
+  let live = args.reload.take().map(|path| {
+    let translations = std::mem::take(&mut args.translations);
+
+    Rc::new(RefCell::new(LiveConfig::new(path, args.filter.take(), translations, args.pretty.clone())))
+  });
+
+  // sources with an up to date index can be skipped if they can't have anything in range or matching the filter
+  // with --why drops every dropped event is reported, so nothing is skipped without being read
+  let drops = args.why.is_some_and(|why| why.drops());
+  // the index only knows fields as they were written, so it can't be checked against a filter on fields a stage
+  // before it may have changed
+  let prune_filter = if rewrites_before_filter(&args) { None } else { args.filter.as_ref() };
+  let mut sources = if drops { args.sources } else { index::prune(args.sources, args.range, prune_filter, options) };
+
+  // only what's left after pruning needs reading
+  #[cfg(feature = "async")]
+  if args.async_sources {
+    prefetch::load(&mut sources);
+  }
+
+  let raw_sinks: Vec<RawSink> = args.raw_copies.iter().map(|copy| copy.open()).collect();
+
+  for source in sources.iter_mut() {
+    if let Some(index) = args.raw_copies.iter().position(|copy| copy.matches(source.name())) {
+      source.copy_raw_to(raw_sinks[index].clone());
+    }
   }
 
-  let ranged = do_range(agg, args.range);
-  let filtered = do_filter(ranged, args.filter);
-  let translated = do_translate(filtered, args.translations);
-  let writer = handle_output(args.output, args.chunked, args.zip);
-  do_pretty(translated, args.pretty, writer);
+  // plain files can be read from the end, so --tail only reads as far back as it needs to. Anything else is read
+  // forwards for --tail, keeping only the last events, and only read whole and backwards for --reverse on its own
+  let all_plain = sources.iter().all(|source| source.path().is_some() && !source.is_compressed());
+  let read_backward = (args.tail.is_some() && all_plain) || (args.reverse && args.tail.is_none());
+  let options = ReadOptions { reverse: read_backward, ..options };
+
+  let source_stats: Vec<_> = if args.source_stats {
+    sources.iter_mut().map(|source| source.track_stats()).collect()
+  } else {
+    vec![]
+  };
+
+  let split: Result<Vec<Aggregator>, Box<dyn Iterator<Item=Line>>> = if args.follow {
+    let mut follower = Follower::new(sources, options, args.globs);
+
+    if let Some(poll) = args.poll_interval {
+      follower = follower.poll_every(poll);
+    }
+
+    match &control {
+      Some(control) => Err(Box::new(follower.stop_on(control.stopping()))),
+      None => Err(Box::new(follower)),
+    }
+  } else {
+    // the files that aren't open yet can't be told apart by day
+    let daily = args.daily && args.max_open_files.is_none();
+
+    let mut agg = match args.max_open_files {
+      Some(max) => Aggregator::lazy(sources, options, max),
+      None => Aggregator::new(sources, options),
+    };
+
+    if daily {
+      agg.filter_daily(args.range, &args.timezone);
+    }
+
+    // with --daily, files from different days can't overlap and so can be read in parallel, except that --deadline
+    // has to see events in the order they're read to know where to resume from, and --control-socket has to be able
+    // to stop reading
+    if daily && args.deadline.is_none() && control.is_none() {
+      agg.split_by_day(&args.timezone).map_err(|agg| Box::new(agg) as Box<dyn Iterator<Item=Line>>)
+    } else {
+      Err(Box::new(agg))
+    }
+  };
+
+  let deadline = args.deadline.map(|budget| Rc::new(RefCell::new(Deadline::new(budget))));
+  let tenant = args.tenant.map(|tenant| Rc::new(RefCell::new(tenant)));
+  let watermark = args.drop_older_than.map(|watermark| Rc::new(RefCell::new(watermark)));
+
+  let fingerprinted = match split {
+    Ok(days) => {
+      let stages = Stages {
+        range: args.range,
+        trace: args.trace,
+        escalations: args.escalations,
+        shard: args.shard,
+        partition: args.partition,
+        filter: args.filter,
+        why: args.why,
+        translations: args.translations,
+        crypto: args.crypto,
+        secrets: args.secrets,
+        fingerprint: args.fingerprint,
+        compact: args.compact,
+        truncations: args.truncations,
+      };
+
+      Box::new(ParallelReader::new(days, stages, limits::threads(args.max_cpu), line_buffer))
+    }
+    Err(merged) => {
+      let merged = do_external_sort(merged, args.external_sort, source_names.clone());
+      // checked for every event read, so a filter that leaves everything out still runs out of time
+      let merged = do_deadline(merged, deadline.clone());
+      let merged = do_control(merged, control.clone());
+      let merged = do_watermark(merged, watermark.clone(), drops);
+      let ranged = do_range(merged, args.range, drops);
+      let traced = do_trace(ranged, args.trace, drops);
+      let escalated = do_escalate(traced, args.escalations);
+      let sharded = do_shard(escalated, args.shard, drops);
+      let owned = do_tenant(sharded, tenant.clone(), drops);
+      let partitioned = do_partition(owned, args.partition);
+      let translated = match &live {
+        Some(live) => do_live(partitioned, live.clone(), args.why),
+        None => {
+          let filtered = do_filter(partitioned, args.filter, args.why);
+          do_translate(filtered, args.translations)
+        }
+      };
+      let crypted = do_crypto(translated, args.crypto);
+      let scanned = do_secrets(crypted, args.secrets);
+      let fingerprinted = do_fingerprint(scanned, args.fingerprint);
+      let compacted = do_compact(fingerprinted, args.compact);
+      do_truncate(compacted, args.truncations)
+    }
+  };
+
+  // events are answered one at a time when following, so nothing waits on a batch that may take minutes to fill
+  let batch = if args.follow { 1 } else { plugin::BATCH_SIZE };
+  let fingerprinted = do_plugins(fingerprinted, args.plugins, batch, drops);
+
+  let quota = args.quota.map(|quota| Rc::new(RefCell::new(quota)));
+  let fingerprinted = do_quota(fingerprinted, quota.clone(), drops);
+
+  let fingerprinted = do_tail(fingerprinted, args.tail, read_backward, args.reverse);
+
+  let sessionized = do_sessionize(fingerprinted, args.sessionize);
+
+  let anomalies = args.anomalies.map(|detector| Rc::new(RefCell::new(detector)));
+  let sessionized = do_anomalies(sessionized, anomalies.clone());
+
+  let correlator = args.correlate.map(|correlate| Rc::new(RefCell::new(correlate)));
+  let correlated = do_correlate(sessionized, correlator.clone());
+
+  let replaying = args.replay.is_some();
+  let replayed = do_replay(correlated, args.replay);
+
+  let rate_limit = args.rate_limit.map(|limit| Rc::new(RefCell::new(limit)));
+  let throttled = do_rate_limit(replayed, rate_limit.clone());
+
+  let sort_check = args.verify_sorted.map(|action| Rc::new(RefCell::new(SortCheck::new(action, args.reverse))));
+  let throttled = do_verify_sorted(throttled, sort_check.clone());
+  let throttled = do_link(throttled, args.link_by);
+
+  let sketches: Rc<RefCell<Vec<(String, Sketch)>>> = Rc::new(RefCell::new(
+    args.percentiles.into_iter().map(|field| (field, Sketch::new())).collect()
+  ));
+  let sketched = do_percentiles(throttled, sketches.clone());
+
+  let summary = args.summary_by.map(|by| Rc::new(RefCell::new(Summary::new(by, args.timezone.clone()))));
+  let summarized = do_summary(sketched, summary.clone());
+
+  let summarized = do_normalize_time(summarized, args.normalize_time);
+
+  let provenance = if args.provenance { Some(Rc::new(RefCell::new(Provenance::default()))) } else { None };
+  let tracked = do_provenance(summarized, provenance.clone());
+
+  let controlled = do_control_stats(tracked, control.clone());
+
+  let written = Rc::new(Cell::new(0u64));
+  let counted = do_count(controlled, args.expect_count.is_some(), written.clone());
+
+  // following or replaying, the first events can't be held back to look at
+  let (counted, adapted) = if args.adaptive_pretty && !replaying && !args.follow && args.stats.is_none() {
+    do_adapt_pattern(counted)
+  } else {
+    (counted, None)
+  };
+
+  let checkpoint = args.output.clone();
+  let chunk_outcome = args.chunk_workers.as_ref().map(ChunkWorkers::outcome);
+  let destination = Destination {
+    output: args.output,
+    clipboard: args.clipboard,
+    chunked: args.chunked,
+    skip_unchanged: args.skip_unchanged,
+    chunk_workers: args.chunk_workers,
+    split: args.split_by_time.map(|interval| (interval, args.timezone)),
+    routes: args.routes,
+    on_exists: args.on_exists,
+    checksum: args.checksum,
+  };
+
+  let mut writer = handle_output(destination, args.compression);
+
+  if args.collapse_repeats {
+    writer = Box::new(RepeatWriter::new(writer));
+  }
+
+  if let Some(escape) = args.escape_json.filter(|escape| *escape != JsonEscape::Never) {
+    writer = Box::new(EscapeWriter::new(writer, escape));
+  }
+
+  if replaying || args.follow {
+    writer = Box::new(LiveWriter(writer));
+  }
+
+  if provenance.is_some() {
+    Provenance::write_header(&mut writer, &source_names);
+  }
+
+  let format = Format {
+    pretty: adapted.or(args.pretty),
+    theme: args.theme,
+    table: args.table,
+    html: args.html,
+    indented: args.indented,
+    frames: args.frames,
+    group: args.group,
+    key_order: args.key_order,
+    live: live.clone(),
+    snapshots: args.snapshot_every.map(Snapshots::new),
+  };
+
+  if let Some(mut stats) = args.stats {
+    let mut snapshots = args.snapshot_every.map(Snapshots::new);
+
+    counted.for_each(|line| {
+      stats.add(&line);
+
+      if snapshots.as_mut().is_some_and(Snapshots::due) {
+        stats.write_snapshot(&mut writer);
+      }
+    });
+    stats.write(&mut writer);
+  } else {
+    do_pretty(counted, format, &mut writer);
+  }
+
+  // flushed here rather than when the sources are dropped, since saw may exit before they are
+  raw_sinks.iter().for_each(raw::flush);
+
+  if let Some(control) = &control {
+    control.close();
+  }
+
+  if let Some(provenance) = provenance {
+    provenance.borrow().write_footer(&mut writer);
+  }
+
+  print_percentiles(&sketches.borrow());
+
+  if let Some(summary) = summary {
+    summary.borrow().print();
+  }
+
+  if args.source_stats {
+    stats::print(&source_stats);
+  }
+
+  if let Some(quota) = quota {
+    quota.borrow().report();
+  }
+
+  if let Some(anomalies) = anomalies {
+    let (flagged, windows) = anomalies.borrow().flagged();
+
+    if flagged > 0 {
+      eprintln!("Flagged {flagged} events in {windows} unusually busy windows with --flag-anomalies");
+    }
+  }
+
+  if let Some(watermark) = watermark {
+    let dropped = watermark.borrow().dropped();
+
+    if dropped > 0 {
+      eprintln!("Dropped {dropped} events that were behind --drop-older-than");
+    }
+  }
+
+  if let Some(rate_limit) = rate_limit {
+    let dropped = rate_limit.borrow().dropped();
+
+    if dropped > 0 {
+      eprintln!("Dropped {dropped} events that were over --rate-limit");
+    }
+  }
+
+  if let Some(failures) = decrypt_failures {
+    let failures = failures.load(Ordering::Relaxed);
+
+    if failures > 0 {
+      eprintln!("Failed to decrypt {failures} values of --decrypt-field, they were left as they were");
+    }
+  }
+
+  for (field, truncated) in truncated {
+    let truncated = truncated.load(Ordering::Relaxed);
+
+    if truncated > 0 {
+      eprintln!("Truncated {truncated} values of '{field}' to fit --truncate-field");
+    }
+  }
+
+  if let Some(audit) = audit {
+    audit.print();
+  }
+
+  if let Some(tenant) = tenant {
+    tenant.borrow().report();
+  }
+
+  if let Some(correlator) = correlator {
+    correlator.borrow().report();
+  }
+
+  if let Some((action, findings)) = secret_findings {
+    findings.print(action);
+
+    if action == SecretAction::Fail && findings.total() > 0 {
+      drop(writer);
+      exit(SECRETS_EXIT_CODE);
+    }
+  }
+
+  if let Some(check) = sort_check {
+    if check.borrow().report() {
+      drop(writer);
+      exit(UNSORTED_EXIT_CODE);
+    }
+  }
+
+  if let Some(deadline) = deadline {
+    let deadline = deadline.borrow();
+
+    if deadline.expired() {
+      // exit skips destructors, and the writer must be dropped to flush and finish compressing
+      drop(writer);
+      deadline.report(checkpoint.as_deref());
+      exit(DEADLINE_EXIT_CODE);
+    }
+  }
+
+  if let Some(expect) = args.expect_count {
+    let count = written.get();
+
+    if !expect.is_met(count) {
+      eprintln!("Expected {} events to be written, but {count} were", expect.describe());
+      drop(writer);
+      exit(EXPECT_EXIT_CODE);
+    }
+  }
+
+  if let Some(outcome) = chunk_outcome {
+    // the workers are only done with the last chunks once the writer is dropped
+    drop(writer);
+
+    if outcome.failed() > 0 {
+      exit(CHUNK_EXIT_CODE);
+    }
+  }
 }
 
 fn do_filter<Iter: 'static + Iterator<Item=Line>>(
   src: Iter,
   maybe_pattern: Option<FilterSet>,
+  maybe_why: Option<Why>,
 ) -> Box<dyn Iterator<Item=Line>> {
-  if let Some(filter) = maybe_pattern {
-    Box::new(src.filter(move |row| {
+  match (maybe_pattern, maybe_why) {
+    (Some(filter), Some(why)) => Box::new(src.filter_map(move |row| why::filter(&filter, why, row))),
+    (Some(filter), None) => Box::new(src.filter(move |row| {
       filter.matches(&row.value)
-    }))
-  } else {
-    Box::new(src)
+    })),
+    (None, _) => Box::new(src),
   }
 }
 
 fn do_range<Iter: 'static + Iterator<Item=Line>>(
   src: Iter,
   maybe_range: (Option<LocalDateTime>, Option<LocalDateTime>),
+  drops: bool,
 ) -> Box<dyn Iterator<Item=Line>> {
+  if drops && maybe_range != (None, None) {
+    let (min, max) = maybe_range;
+
+    return Box::new(src.filter(move |line| {
+      let in_range = line.time.is_some_and(|time| min.is_none_or(|min| time >= min) && max.is_none_or(|max| time < max));
+
+      if !in_range {
+        why::dropped("--range", if line.time.is_some() { "outside the range" } else { "no time" }, line);
+      }
+
+      in_range
+    }));
+  }
+
   match maybe_range {
     (None, None) => Box::new(src),
     (Some(min), None) => {
       let range = min..;
 
-      Box::new(src.filter(move |line| range.contains(&line.time)))
+      Box::new(src.filter(move |line| line.time.is_some_and(|time| range.contains(&time))))
     }
     (None, Some(max)) => {
       let range = ..max;
 
-      Box::new(src.filter(move |line| range.contains(&line.time)))
+      Box::new(src.filter(move |line| line.time.is_some_and(|time| range.contains(&time))))
     }
     (Some(min), Some(max)) => {
       let range = min..max;
 
-      Box::new(src.filter(move |line| range.contains(&line.time)))
+      Box::new(src.filter(move |line| line.time.is_some_and(|time| range.contains(&time))))
+    }
+  }
+}
+
+/**
+ * Can a stage that runs before --filter add or change the fields it checks?
+ */
+fn rewrites_before_filter(args: &Arguments) -> bool {
+  args.trace.is_some() || !args.escalations.is_empty() || args.tenant.is_some() || args.partition.is_some()
+}
+
+fn do_trace<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_trace: Option<TraceIds>,
+  drops: bool,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(trace) = maybe_trace {
+    Box::new(src.filter_map(move |mut line| {
+      if trace.apply(&mut line.value) {
+        return Some(line);
+      }
+
+      if drops {
+        why::dropped("--trace", "another trace", &line);
+      }
+
+      None
+    }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_escalate<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  escalations: Vec<Escalation>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if escalations.is_empty() {
+    return Box::new(src);
+  }
+
+  Box::new(src.map(move |mut line| {
+    for escalation in &escalations {
+      escalation.apply(&mut line.value);
     }
+
+    line
+  }))
+}
+
+fn do_shard<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_shard: Option<Shard>,
+  drops: bool,
+) -> Box<dyn Iterator<Item=Line>> {
+  match maybe_shard {
+    Some(shard) if shard.by_key() => Box::new(src.filter(move |line| {
+      let owned = shard.owns_event(&line.value);
+
+      if !owned && drops {
+        why::dropped("--shard", "another shard", line);
+      }
+
+      owned
+    })),
+    _ => Box::new(src),
   }
 }
 
+fn do_tenant<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_tenant: Option<Rc<RefCell<Tenant>>>,
+  drops: bool,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(tenant) = maybe_tenant {
+    Box::new(src.filter_map(move |mut line| {
+      if tenant.borrow_mut().apply(&mut line.value) {
+        return Some(line);
+      }
+
+      if drops {
+        why::dropped("--tenant", "another tenant", &line);
+      }
+
+      None
+    }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_partition<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_partition: Option<Partition>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(partition) = maybe_partition {
+    Box::new(src.map(move |mut line| {
+      partition.apply(&mut line.value);
+      line
+    }))
+  } else {
+    Box::new(src)
+  }
+}
+
+/**
+ * --filter and then --translate, with whatever --reload last read
+ */
+fn do_live<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  live: Rc<RefCell<LiveConfig>>,
+  maybe_why: Option<Why>,
+) -> Box<dyn Iterator<Item=Line>> {
+  Box::new(src.filter_map(move |line| {
+    let mut live = live.borrow_mut();
+    live.refresh();
+
+    let mut line = match (&live.filter, maybe_why) {
+      (Some(filter), Some(why)) => why::filter(filter, why, line)?,
+      (Some(filter), None) if !filter.matches(&line.value) => return None,
+      _ => line,
+    };
+
+    for trans in &live.translations {
+      trans.translate(&mut line.value);
+    }
+
+    Some(line)
+  }))
+}
+
 fn do_translate<Iter: 'static + Iterator<Item=Line>>(
   src: Iter,
   translations: Vec<Translation>,
@@ -87,50 +822,579 @@ fn do_translate<Iter: 'static + Iterator<Item=Line>>(
     return Box::new(src);
   }
 
-  return Box::new(src.map(move |mut line| {
+  Box::new(src.map(move |mut line| {
     for trans in &translations {
       trans.translate(&mut line.value);
     }
 
     line
-  }));
+  }))
 }
 
-fn handle_output(maybe_output: Option<PathBuf>, chunked: Option<ChunkInfo>, zipped: bool) -> Box<dyn LogWriter> {
-  if let Some(output) = maybe_output {
-    if let Some(chunk_info) = chunked {
-      Box::new(ChunkedWriter::new(output, chunk_info, zipped))
-    } else {
-      let target = File::create(output).expect("Could not create output file");
+fn do_crypto<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_crypto: Option<FieldCrypto>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(crypto) = maybe_crypto {
+    Box::new(src.map(move |mut line| {
+      crypto.apply(&mut line.value);
+      line
+    }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_secrets<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_secrets: Option<SecretScanner>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(secrets) = maybe_secrets {
+    Box::new(src.filter_map(move |mut line| if secrets.apply(&mut line) { Some(line) } else { None }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_fingerprint<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_fingerprint: Option<Fingerprint>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(fingerprint) = maybe_fingerprint {
+    Box::new(src.map(move |mut line| {
+      fingerprint.apply(&mut line.value);
+      line
+    }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_compact<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  compact: bool,
+) -> Box<dyn Iterator<Item=Line>> {
+  if compact {
+    Box::new(src.map(|mut line| {
+      compact::compact(&mut line.value);
+      line
+    }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_truncate<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  truncations: Vec<Truncation>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if truncations.is_empty() {
+    Box::new(src)
+  } else {
+    Box::new(src.map(move |mut line| {
+      for truncation in &truncations {
+        truncation.apply(&mut line.value);
+      }
+
+      line
+    }))
+  }
+}
+
+fn do_plugins(
+  src: Box<dyn Iterator<Item=Line>>,
+  plugins: Vec<Plugin>,
+  batch: usize,
+  drops: bool,
+) -> Box<dyn Iterator<Item=Line>> {
+  plugins.iter().fold(src, |src, plugin| Box::new(PluginStage::new(src, plugin, batch, drops)))
+}
+
+fn do_sessionize<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_sessionizer: Option<Sessionizer>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(mut sessionizer) = maybe_sessionizer {
+    Box::new(src.map(move |mut line| {
+      sessionizer.apply(&mut line.value, line.time);
+      line
+    }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_anomalies<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_detector: Option<Rc<RefCell<AnomalyDetector>>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(detector) = maybe_detector {
+    Box::new(src.map(move |mut line| {
+      detector.borrow_mut().apply(&mut line.value, line.time);
+      line
+    }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_correlate<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_correlator: Option<Rc<RefCell<Correlator>>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(correlator) = maybe_correlator {
+    Box::new(src.filter_map(move |line| correlator.borrow_mut().apply(line)))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_external_sort(
+  src: Box<dyn Iterator<Item=Line>>,
+  maybe_sort: Option<ExternalSort>,
+  inputs: Vec<String>,
+) -> Box<dyn Iterator<Item=Line>> {
+  match maybe_sort {
+    Some(sort) => Box::new(ExternalSortStage::new(src, sort, inputs)),
+    None => src,
+  }
+}
+
+fn do_link(
+  src: Box<dyn Iterator<Item=Line>>,
+  maybe_linker: Option<Linker>,
+) -> Box<dyn Iterator<Item=Line>> {
+  match maybe_linker {
+    Some(linker) => Box::new(LinkStage::new(src, linker)),
+    None => src,
+  }
+}
+
+fn do_deadline<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_deadline: Option<Rc<RefCell<Deadline>>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(deadline) = maybe_deadline {
+    Box::new(src.take_while(move |line| deadline.borrow_mut().allow(line)))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_control<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_control: Option<Arc<Control>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(control) = maybe_control {
+    Box::new(src.take_while(move |_| control.allow()))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_control_stats<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_control: Option<Arc<Control>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(control) = maybe_control {
+    Box::new(src.inspect(move |line| control.written(line)))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_verify_sorted<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_check: Option<Rc<RefCell<SortCheck>>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(check) = maybe_check {
+    Box::new(src.take_while(move |line| check.borrow_mut().allow(line)))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_replay<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_replay: Option<Replay>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(mut replay) = maybe_replay {
+    Box::new(src.inspect(move |line| replay.pace(line.time)))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_quota<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_quota: Option<Rc<RefCell<Quota>>>,
+  drops: bool,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(quota) = maybe_quota {
+    let reading = quota.clone();
 
-      handle_zip(BufWriter::new(target), zipped)
+    Box::new(src
+      .take_while(move |_| !reading.borrow().full())
+      .filter(move |line| {
+        if quota.borrow_mut().admit(&line.value) {
+          return true;
+        }
+
+        if drops {
+          why::dropped("--quota", "no bucket or its bucket is full", line);
+        }
+
+        false
+      }))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_watermark(
+  src: Box<dyn Iterator<Item=Line>>,
+  maybe_watermark: Option<Rc<RefCell<Watermark>>>,
+  drops: bool,
+) -> Box<dyn Iterator<Item=Line>> {
+  let Some(watermark) = maybe_watermark else {
+    return src;
+  };
+
+  Box::new(src.filter(move |line| {
+    let mut watermark = watermark.borrow_mut();
+
+    if watermark.admit(line) {
+      return true;
     }
+
+    if drops {
+      why::dropped("--drop-older-than", &watermark.reason(), line);
+    }
+
+    false
+  }))
+}
+
+fn do_rate_limit<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_limit: Option<Rc<RefCell<RateLimit>>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(limit) = maybe_limit {
+    Box::new(src.filter(move |_| limit.borrow_mut().admit()))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_percentiles<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  sketches: Rc<RefCell<Vec<(String, Sketch)>>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if sketches.borrow().is_empty() {
+    return Box::new(src);
+  }
+
+  Box::new(src.inspect(move |line| {
+    for (field, sketch) in sketches.borrow_mut().iter_mut() {
+      let value = match line.value.get(field) {
+        Some(Value::Number(num)) => num.as_f64(),
+        Some(Value::String(str)) => str.trim().parse().ok(),
+        _ => None,
+      };
+
+      if let Some(value) = value {
+        sketch.add(value);
+      }
+    }
+  }))
+}
+
+fn print_percentiles(sketches: &[(String, Sketch)]) {
+  for (field, sketch) in sketches {
+    let describe = |q: f64| sketch.quantile(q).map_or("-".to_string(), |value| format!("{value:.3}"));
+
+    eprintln!(
+      "{field}: count={} min={} p50={} p90={} p95={} p99={} max={}",
+      sketch.count(),
+      describe(0.0),
+      describe(0.5),
+      describe(0.9),
+      describe(0.95),
+      describe(0.99),
+      describe(1.0),
+    );
+  }
+}
+
+/**
+ * Keep only the last count events. Read backwards they're the first ones, and otherwise only the last count seen are
+ * held on to. Either way they're written in time order, or newest first with --reverse
+ */
+fn do_tail<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_tail: Option<usize>,
+  read_backward: bool,
+  reverse: bool,
+) -> Box<dyn Iterator<Item=Line>> {
+  let Some(count) = maybe_tail else {
+    return Box::new(src);
+  };
+
+  if read_backward {
+    let mut last: Vec<Line> = src.take(count).collect();
+
+    if !reverse {
+      last.reverse();
+    }
+
+    return Box::new(last.into_iter());
+  }
+
+  let mut last = VecDeque::with_capacity(count);
+
+  for line in src {
+    if last.len() == count {
+      last.pop_front();
+    }
+
+    last.push_back(line);
+  }
+
+  if reverse {
+    Box::new(last.into_iter().rev())
+  } else {
+    Box::new(last.into_iter())
+  }
+}
+
+fn do_summary<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_summary: Option<Rc<RefCell<Summary>>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(summary) = maybe_summary {
+    Box::new(src.inspect(move |line| summary.borrow_mut().add(line)))
+  } else {
+    Box::new(src)
+  }
+}
+
+fn do_normalize_time<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_normalize: Option<TimeOutput>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(normalize) = maybe_normalize {
+    Box::new(src.map(move |mut line| {
+      normalize.apply(&mut line.value, line.time);
+      line
+    }))
   } else {
-    handle_zip(BufWriter::new(stdout()), zipped)
+    Box::new(src)
   }
 }
 
-fn handle_zip<Writer: 'static + Write + LogWriter>(src: Writer, zip: bool) -> Box<dyn LogWriter> {
-  if zip {
-    Box::new(GzEncoder::new(src, Compression::best()))
+fn do_provenance<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  maybe_provenance: Option<Rc<RefCell<Provenance>>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if let Some(provenance) = maybe_provenance {
+    Box::new(src.inspect(move |line| provenance.borrow_mut().add(line)))
   } else {
     Box::new(src)
   }
 }
 
+fn do_count<Iter: 'static + Iterator<Item=Line>>(
+  src: Iter,
+  enabled: bool,
+  count: Rc<Cell<u64>>,
+) -> Box<dyn Iterator<Item=Line>> {
+  if enabled {
+    Box::new(src.inspect(move |_| count.set(count.get() + 1)))
+  } else {
+    Box::new(src)
+  }
+}
+
+/**
+ * Everything that decides where output is written, besides how it's compressed
+ */
+struct Destination {
+  output: Option<PathBuf>,
+  clipboard: bool,
+  chunked: Option<ChunkInfo>,
+  skip_unchanged: bool,
+  chunk_workers: Option<ChunkWorkers>,
+  split: Option<(SplitInterval, TimeZone)>,
+  routes: Vec<Route>,
+  on_exists: OnExists,
+  checksum: Option<Checksum>,
+}
+
+fn handle_output(
+  destination: Destination,
+  compression: CompressionRoutes,
+) -> Box<dyn LogWriter> {
+  let Destination {
+    output: maybe_output,
+    clipboard,
+    chunked,
+    skip_unchanged,
+    chunk_workers,
+    split,
+    routes,
+    on_exists,
+    checksum,
+  } = destination;
+
+  let routes = route::open(routes, &compression, on_exists, checksum);
+
+  let writer: Box<dyn LogWriter> = if clipboard {
+    // whatever is copied is meant to be pasted, so it's never compressed
+    Box::new(ClipboardWriter::default())
+  } else if let Some(output) = maybe_output {
+    if let Some((interval, zone)) = split {
+      Box::new(SplitWriter::new(output, interval, zone, compression, on_exists, checksum))
+    } else if let Some(chunk_info) = chunked {
+      let queue = chunk_workers.map(|workers| workers.start(on_exists, checksum));
+
+      Box::new(ChunkedWriter::new(output, chunk_info, compression, skip_unchanged, on_exists, checksum, queue))
+    } else {
+      let codec = compression.codec_for(output.to_str().unwrap_or(""));
+      let target = on_exists.create(&output);
+
+      codec.wrap(BufWriter::new(OutputFile::new(target, &output, checksum)))
+    }
+  } else {
+    compression.codec_for("-").wrap(BufWriter::new(stdout()))
+  };
+
+  if routes.is_empty() {
+    writer
+  } else {
+    Box::new(RouteWriter::new(writer, routes))
+  }
+}
+
+/**
+ * Look at the first events to make the default pattern from the fields they have, then write them along with the rest
+ */
+fn do_adapt_pattern(mut src: Box<dyn Iterator<Item=Line>>) -> (Box<dyn Iterator<Item=Line>>, Option<PrettyDescriptor>) {
+  let sample: Vec<Line> = src.by_ref().take(adaptive::SAMPLE_SIZE).collect();
+  let pattern = patterns::pretty(&adaptive::default_pattern(&sample)).expect("The adaptive pattern must always parse");
+
+  (Box::new(sample.into_iter().chain(src)), Some(pattern))
+}
+
+/**
+ * Everything that decides how each event is written, at most one of which is used besides a theme or grouping
+ */
+struct Format {
+  pretty: Option<PrettyDescriptor>,
+  theme: Option<PrettyTheme>,
+  table: Option<Table>,
+  html: Option<HtmlReport>,
+  indented: bool,
+  frames: Option<Framing>,
+  group: Option<Grouping>,
+  key_order: Option<KeyOrder>,
+  /// with --reload, where the pretty template may have been replaced
+  live: Option<Rc<RefCell<LiveConfig>>>,
+  /// with --group-by, when to write the block being gathered without waiting for it to end
+  snapshots: Option<Snapshots>,
+}
+
+/**
+ * LINE with a numeric level replaced by its name, for output people read. JSON keeps the number it was written with
+ */
+fn named(mut line: Line) -> Line {
+  levels::name_level(&mut line.value);
+  line
+}
+
+/**
+ * Print with the template, or the one --reload has replaced it with
+ */
+fn with_template(live: &Option<Rc<RefCell<LiveConfig>>>, pretty: &PrettyDescriptor, print: impl FnOnce(&PrettyDescriptor)) {
+  match live {
+    Some(live) => print(live.borrow().pretty.as_ref().unwrap_or(pretty)),
+    None => print(pretty),
+  }
+}
+
 fn do_pretty<Iter: 'static + Iterator<Item=Line>>(
   src: Iter,
-  maybe_pretty: Option<PrettyDescriptor>,
-  mut target: Box<dyn LogWriter>,
+  format: Format,
+  target: &mut Box<dyn LogWriter>,
 ) {
-  if let Some(pretty) = maybe_pretty {
-    src.for_each(move |line| {
-      pretty.print(&line.value, &mut target);
+  let Format {
+    pretty: maybe_pretty,
+    theme: maybe_theme,
+    table: maybe_table,
+    html: maybe_html,
+    indented,
+    frames: maybe_frames,
+    group: maybe_group,
+    key_order: maybe_order,
+    live: maybe_live,
+    snapshots: mut maybe_snapshots,
+  } = format;
+
+  if let Some(mut table) = maybe_table {
+    src.map(named).for_each(|line| table.add(&line.value, target));
+    table.flush(target);
+  } else if let Some(mut html) = maybe_html {
+    html.write_header(target);
+    src.map(named).for_each(|line| html.add(&line.value, line.time, target));
+    html.write_footer(target);
+  } else if let (Some(pretty), Some(mut group)) = (&maybe_pretty, maybe_group) {
+    src.map(named).for_each(|line| with_template(&maybe_live, pretty, |pretty| {
+      let pattern = maybe_theme.as_ref().map_or(pretty, |theme| theme.select(&line.value, pretty));
+
+      group.add(&line.value, line.time, pattern.print_to_string(&line.value), target);
+
+      if maybe_snapshots.as_mut().is_some_and(Snapshots::due) {
+        group.flush(target);
+      }
+    }));
+    group.flush(target);
+  } else if let (Some(pretty), Some(theme)) = (&maybe_pretty, &maybe_theme) {
+    src.map(named).for_each(|line| {
+      target.route_event(&line.value);
+      target.start_event(line.time);
+      with_template(&maybe_live, pretty, |pretty| theme.select(&line.value, pretty).print(&line.value, target));
+      target.end_line();
+    })
+  } else if let Some(pretty) = maybe_pretty {
+    src.map(named).for_each(|line| {
+      target.route_event(&line.value);
+      target.start_event(line.time);
+      with_template(&maybe_live, &pretty, |pretty| pretty.print(&line.value, target));
+      target.end_line();
+    })
+  } else if indented {
+    src.for_each(|line| {
+      target.route_event(&line.value);
+      target.start_event(line.time);
+      indented::write(&line.value, target);
+    })
+  } else if let Some(framing) = maybe_frames {
+    src.for_each(|line| {
+      target.route_event(&line.value);
+      target.start_event(line.time);
+      framing.write(&line.value, target);
+    })
+  } else if let Some(order) = maybe_order {
+    src.for_each(|line| {
+      target.route_event(&line.value);
+      target.start_event(line.time);
+      order.write(&line.value, target);
       target.end_line();
     })
   } else {
-    src.for_each(move |line| {
-      serde_json::to_writer(&mut target, &line.value).expect("Failed to write line");
+    src.for_each(|line| {
+      target.route_event(&line.value);
+      target.start_event(line.time);
+      serde_json::to_writer(&mut *target, &line.value).expect("Failed to write line");
       target.end_line();
     })
   }
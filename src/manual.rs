@@ -0,0 +1,117 @@
+use std::env;
+use std::io::{IsTerminal, stdout, Write};
+use std::process::{Command, Stdio};
+
+use crate::flags::FLAGS;
+
+/// used when $PAGER isn't set
+const DEFAULT_PAGER: &str = "less -R";
+
+/**
+ * The man page for saw, in troff, built from the same flags and topics as --help so the two can't disagree.
+ * commands are (name, text) of each subcommand's own help
+ */
+pub fn man_page(notes: &str, topics: &[(&str, &str, &str)], commands: &[(&str, &str)]) -> String {
+  let mut page = String::new();
+
+  page.push_str(&format!(".TH SAW 1 \"\" \"saw {}\" \"User Commands\"\n", env!("CARGO_PKG_VERSION")));
+  page.push_str(".SH NAME\nsaw \\- merge, filter and pretty print JSON logs\n");
+  page.push_str(".SH SYNOPSIS\n.B saw\n[\\fIOPTIONS\\fR] \\fISOURCE_FILES\\fR...\n");
+
+  page.push_str(".SH DESCRIPTION\n");
+  page.push_str(&to_troff(notes));
+
+  page.push_str(".SH OPTIONS\n");
+  for flag in FLAGS {
+    let mut names = match flag.short {
+      Some(short) => format!("\\fB{}\\fR, \\fB{}\\fR", escape(short), escape(flag.long)),
+      None => format!("\\fB{}\\fR", escape(flag.long)),
+    };
+
+    if !flag.values.is_empty() {
+      let values = format!("\\fI{}\\fR", escape(&flag.values.join(" ")));
+
+      names.push(' ');
+      names.push_str(&if flag.optional { format!("[{values}]") } else { values });
+    }
+
+    page.push_str(&format!(".TP\n{names}\n{}\n", escape(flag.help)));
+
+    if let Some(default) = flag.default {
+      page.push_str(&format!("Defaults to \\fB{}\\fR.\n", escape(default)));
+    }
+  }
+
+  for (name, text) in commands {
+    page.push_str(&format!(".SH \"SAW {}\"\n", name.to_uppercase()));
+    page.push_str(&to_troff(text));
+  }
+
+  for (name, summary, text) in topics {
+    page.push_str(&format!(".SH \"{}\"\n{}\n.PP\n", name.to_uppercase(), escape(summary)));
+    page.push_str(&to_troff(text));
+  }
+
+  page
+}
+
+/**
+ * Turn help text into troff. Blank lines start a new paragraph and indented lines, which are usage lines and examples,
+ * are kept exactly as they are
+ */
+fn to_troff(text: &str) -> String {
+  let mut out = String::new();
+  let mut verbatim = false;
+
+  for line in text.trim_matches('\n').lines() {
+    let indented = line.starts_with(' ');
+
+    if indented != verbatim {
+      out.push_str(if indented { ".RS\n.nf\n" } else { ".fi\n.RE\n" });
+      verbatim = indented;
+    }
+
+    if line.trim().is_empty() {
+      out.push_str(if verbatim { "\n" } else { ".PP\n" });
+    } else if verbatim {
+      out.push_str(&format!("\\&{}\n", escape(line.trim_start())));
+    } else {
+      out.push_str(&format!("\\&{}\n", escape(line)));
+    }
+  }
+
+  if verbatim {
+    out.push_str(".fi\n.RE\n");
+  }
+
+  out
+}
+
+fn escape(raw: &str) -> String {
+  raw.replace('\\', "\\e").replace('-', "\\-")
+}
+
+/**
+ * Show long help through $PAGER when writing to a terminal. Returns false if it wasn't shown, so it can be printed as
+ * usual instead
+ */
+pub fn page(text: &str) -> bool {
+  if !stdout().is_terminal() {
+    return false;
+  }
+
+  let pager = env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()).unwrap_or(DEFAULT_PAGER.to_string());
+
+  Command::new("sh")
+    .arg("-c")
+    .arg(&pager)
+    .stdin(Stdio::piped())
+    .spawn()
+    .ok()
+    .and_then(|mut child| {
+      // the pager may be closed before reading everything, which is fine
+      let _ = child.stdin.take()?.write_all(text.as_bytes());
+      child.wait().ok()
+    })
+    .is_some_and(|status| status.success())
+}
@@ -0,0 +1,266 @@
+use datetime::{ISO, LocalDateTime};
+use serde_json::{Map, Number, Value};
+
+use crate::pretty::PrettyDescriptor;
+
+/// the extension type MessagePack gives timestamps
+const TIMESTAMP_EXT: i8 = -1;
+
+/**
+ * Decode one MessagePack value into JSON, or None if it isn't one or there's anything after it. Keys that aren't
+ * strings are written as text, binary is a string when it's UTF-8 and an array of bytes when it's not, and timestamps
+ * are ISO8601 like any other time. Other extensions have no JSON form, so they're null
+ */
+pub fn decode(bytes: &[u8]) -> Option<Value> {
+  let mut reader = Reader { bytes, pos: 0 };
+  let value = reader.value()?;
+
+  if reader.pos == bytes.len() { Some(value) } else { None }
+}
+
+/**
+ * Encode an event as a MessagePack map, using the smallest form of each value
+ */
+pub fn encode(value: &Map<String, Value>, out: &mut Vec<u8>) {
+  write_header(out, value.len(), 0x80, 16, [0xde, 0xdf]);
+
+  for (key, value) in value {
+    write_str(out, key);
+    write_value(out, value);
+  }
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl <'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+    let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len())?;
+    let taken = &self.bytes[self.pos..end];
+    self.pos = end;
+
+    Some(taken)
+  }
+
+  fn uint(&mut self, len: usize) -> Option<u64> {
+    Some(self.take(len)?.iter().fold(0u64, |acc, byte| acc << 8 | *byte as u64))
+  }
+
+  fn int(&mut self, len: usize) -> Option<i64> {
+    let bits = (8 - len) * 8;
+
+    // shifted up and back down to carry the sign
+    Some(((self.uint(len)? << bits) as i64) >> bits)
+  }
+
+  fn value(&mut self) -> Option<Value> {
+    let marker = *self.take(1)?.first()?;
+
+    let value = match marker {
+      0x00..=0x7f => Value::from(marker),
+      0x80..=0x8f => self.map((marker & 0x0f) as usize)?,
+      0x90..=0x9f => self.array((marker & 0x0f) as usize)?,
+      0xa0..=0xbf => self.str((marker & 0x1f) as usize)?,
+      0xc0 => Value::Null,
+      0xc2 => Value::Bool(false),
+      0xc3 => Value::Bool(true),
+      0xc4 => { let len = self.uint(1)?; self.bin(len as usize)? }
+      0xc5 => { let len = self.uint(2)?; self.bin(len as usize)? }
+      0xc6 => { let len = self.uint(4)?; self.bin(len as usize)? }
+      0xc7 => { let len = self.uint(1)?; self.ext(len as usize)? }
+      0xc8 => { let len = self.uint(2)?; self.ext(len as usize)? }
+      0xc9 => { let len = self.uint(4)?; self.ext(len as usize)? }
+      0xca => float(f32::from_bits(self.uint(4)? as u32) as f64),
+      0xcb => float(f64::from_bits(self.uint(8)?)),
+      0xcc => Value::from(self.uint(1)?),
+      0xcd => Value::from(self.uint(2)?),
+      0xce => Value::from(self.uint(4)?),
+      0xcf => Value::from(self.uint(8)?),
+      0xd0 => Value::from(self.int(1)?),
+      0xd1 => Value::from(self.int(2)?),
+      0xd2 => Value::from(self.int(4)?),
+      0xd3 => Value::from(self.int(8)?),
+      0xd4 => self.ext(1)?,
+      0xd5 => self.ext(2)?,
+      0xd6 => self.ext(4)?,
+      0xd7 => self.ext(8)?,
+      0xd8 => self.ext(16)?,
+      0xd9 => { let len = self.uint(1)?; self.str(len as usize)? }
+      0xda => { let len = self.uint(2)?; self.str(len as usize)? }
+      0xdb => { let len = self.uint(4)?; self.str(len as usize)? }
+      0xdc => { let len = self.uint(2)?; self.array(len as usize)? }
+      0xdd => { let len = self.uint(4)?; self.array(len as usize)? }
+      0xde => { let len = self.uint(2)?; self.map(len as usize)? }
+      0xdf => { let len = self.uint(4)?; self.map(len as usize)? }
+      0xe0..=0xff => Value::from(marker as i8),
+      // 0xc1 is never used
+      _ => return None,
+    };
+
+    Some(value)
+  }
+
+  fn str(&mut self, len: usize) -> Option<Value> {
+    Some(Value::String(std::str::from_utf8(self.take(len)?).ok()?.to_string()))
+  }
+
+  fn bin(&mut self, len: usize) -> Option<Value> {
+    let bytes = self.take(len)?;
+
+    Some(match std::str::from_utf8(bytes) {
+      Ok(text) => Value::String(text.to_string()),
+      Err(_) => Value::Array(bytes.iter().map(|byte| Value::from(*byte)).collect()),
+    })
+  }
+
+  fn array(&mut self, len: usize) -> Option<Value> {
+    // the length comes from the data, so it's only trusted as far as there are bytes for it
+    let mut values = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+
+    for _ in 0..len {
+      values.push(self.value()?);
+    }
+
+    Some(Value::Array(values))
+  }
+
+  fn map(&mut self, len: usize) -> Option<Value> {
+    let mut values = Map::new();
+
+    for _ in 0..len {
+      let key = match self.value()? {
+        Value::String(key) => key,
+        other => PrettyDescriptor::pretty_value(&other),
+      };
+
+      values.insert(key, self.value()?);
+    }
+
+    Some(Value::Object(values))
+  }
+
+  fn ext(&mut self, len: usize) -> Option<Value> {
+    let kind = self.int(1)? as i8;
+    let data = self.take(len)?;
+
+    if kind != TIMESTAMP_EXT {
+      return Some(Value::Null);
+    }
+
+    let mut data = Reader { bytes: data, pos: 0 };
+
+    let (seconds, nanos) = match len {
+      4 => (data.uint(4)? as i64, 0),
+      8 => {
+        let packed = data.uint(8)?;
+        ((packed & 0x3_ffff_ffff) as i64, packed >> 34)
+      }
+      12 => {
+        let nanos = data.uint(4)?;
+        (data.int(8)?, nanos)
+      }
+      _ => return None,
+    };
+
+    let time = LocalDateTime::at_ms(seconds, (nanos / 1_000_000) as i16);
+
+    Some(Value::String(time.iso().to_string()))
+  }
+}
+
+/// NaN and infinity have no JSON form
+fn float(number: f64) -> Value {
+  Number::from_f64(number).map_or(Value::Null, Value::Number)
+}
+
+/**
+ * Write the marker for a string, array or map of LEN, which is FIX with the length in it when it's under FIX_LIMIT, or
+ * else one of WIDE followed by the length in 16 or 32 bits
+ */
+fn write_header(out: &mut Vec<u8>, len: usize, fix: u8, fix_limit: usize, wide: [u8; 2]) {
+  if len < fix_limit {
+    out.push(fix | len as u8);
+  } else if len <= u16::MAX as usize {
+    out.push(wide[0]);
+    out.extend_from_slice(&(len as u16).to_be_bytes());
+  } else {
+    let len = u32::try_from(len).expect("Cannot write a value with more than 4GB of items as MessagePack");
+
+    out.push(wide[1]);
+    out.extend_from_slice(&len.to_be_bytes());
+  }
+}
+
+fn write_str(out: &mut Vec<u8>, text: &str) {
+  if text.len() < 32 {
+    out.push(0xa0 | text.len() as u8);
+  } else if text.len() <= u8::MAX as usize {
+    out.push(0xd9);
+    out.push(text.len() as u8);
+  } else {
+    write_header(out, text.len(), 0, 0, [0xda, 0xdb]);
+  }
+
+  out.extend_from_slice(text.as_bytes());
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+  match value {
+    Value::Null => out.push(0xc0),
+    Value::Bool(false) => out.push(0xc2),
+    Value::Bool(true) => out.push(0xc3),
+    Value::Number(number) => {
+      if let Some(unsigned) = number.as_u64() {
+        write_uint(out, unsigned);
+      } else if let Some(signed) = number.as_i64() {
+        write_int(out, signed);
+      } else {
+        out.push(0xcb);
+        out.extend_from_slice(&number.as_f64().unwrap_or(0.0).to_be_bytes());
+      }
+    }
+    Value::String(text) => write_str(out, text),
+    Value::Array(values) => {
+      write_header(out, values.len(), 0x90, 16, [0xdc, 0xdd]);
+      values.iter().for_each(|value| write_value(out, value));
+    }
+    Value::Object(values) => encode(values, out),
+  }
+}
+
+fn write_uint(out: &mut Vec<u8>, number: u64) {
+  if number < 0x80 {
+    out.push(number as u8);
+  } else if number <= u8::MAX as u64 {
+    out.extend_from_slice(&[0xcc, number as u8]);
+  } else if number <= u16::MAX as u64 {
+    out.push(0xcd);
+    out.extend_from_slice(&(number as u16).to_be_bytes());
+  } else if number <= u32::MAX as u64 {
+    out.push(0xce);
+    out.extend_from_slice(&(number as u32).to_be_bytes());
+  } else {
+    out.push(0xcf);
+    out.extend_from_slice(&number.to_be_bytes());
+  }
+}
+
+/// only called for negative numbers, since anything else is written unsigned
+fn write_int(out: &mut Vec<u8>, number: i64) {
+  if number >= -32 {
+    out.push(number as i8 as u8);
+  } else if number >= i8::MIN as i64 {
+    out.extend_from_slice(&[0xd0, number as i8 as u8]);
+  } else if number >= i16::MIN as i64 {
+    out.push(0xd1);
+    out.extend_from_slice(&(number as i16).to_be_bytes());
+  } else if number >= i32::MIN as i64 {
+    out.push(0xd2);
+    out.extend_from_slice(&(number as i32).to_be_bytes());
+  } else {
+    out.push(0xd3);
+    out.extend_from_slice(&number.to_be_bytes());
+  }
+}
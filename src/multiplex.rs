@@ -0,0 +1,110 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Read};
+use std::sync::{Arc, Mutex};
+
+/// separates a line's source tag from the line itself
+const TAG_SEPARATOR: u8 = b'\t';
+
+/**
+ * Splits one stream where every line is 'TAG<tab>LINE' into a queue of lines per tag. Lines are only read when some
+ * tag needs its next one, so a tag that falls behind makes the others wait in memory until it catches up.
+ */
+struct Demux {
+  input: Box<dyn BufRead + Send>,
+  name: String,
+  queues: HashMap<String, VecDeque<Vec<u8>>>,
+  /// line number of the next line of input, for warnings
+  line: u64,
+  done: bool,
+}
+
+impl Demux {
+  fn next_for(&mut self, tag: &str) -> Option<Vec<u8>> {
+    loop {
+      if let Some(found) = self.queues.get_mut(tag).and_then(VecDeque::pop_front) {
+        return Some(found);
+      }
+
+      if self.done {
+        return None;
+      }
+
+      let mut raw = Vec::new();
+      let read = self.input.read_until(b'\n', &mut raw)
+        .unwrap_or_else(|_| panic!("Failed to read line from {}", self.name));
+      let line = self.line;
+      self.line += 1;
+
+      if read == 0 {
+        self.done = true;
+        continue;
+      }
+
+      let Some(split) = raw.iter().position(|byte| *byte == TAG_SEPARATOR) else {
+        eprintln!("Line {line} of {} has no tag before a tab, skipping it", self.name);
+        continue;
+      };
+
+      let found = String::from_utf8_lossy(&raw[..split]).into_owned();
+
+      match self.queues.get_mut(&found) {
+        Some(queue) => queue.push_back(raw[split + 1..].to_vec()),
+        None => eprintln!("Line {line} of {} has unknown tag '{found}', skipping it", self.name),
+      }
+    }
+  }
+}
+
+/**
+ * The lines of one tag, read as if they were a file of their own
+ */
+pub struct TagReader {
+  demux: Arc<Mutex<Demux>>,
+  tag: String,
+  current: Vec<u8>,
+  position: usize,
+}
+
+impl Read for TagReader {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    if self.position >= self.current.len() {
+      let next = self.demux.lock().expect("Multiplexed input was poisoned").next_for(&self.tag);
+
+      match next {
+        Some(next) => {
+          self.current = next;
+          self.position = 0;
+        }
+        None => return Ok(0),
+      }
+    }
+
+    let count = buf.len().min(self.current.len() - self.position);
+    buf[..count].copy_from_slice(&self.current[self.position..self.position + count]);
+    self.position += count;
+
+    Ok(count)
+  }
+}
+
+/**
+ * One reader for each of the tags, all sharing the same input
+ */
+pub fn split(input: Box<dyn BufRead + Send>, name: &str, tags: &[String]) -> Vec<TagReader> {
+  let demux = Arc::new(Mutex::new(Demux {
+    input,
+    name: name.to_string(),
+    queues: tags.iter().map(|tag| (tag.clone(), VecDeque::new())).collect(),
+    line: 0,
+    done: false,
+  }));
+
+  tags.iter()
+    .map(|tag| TagReader {
+      demux: demux.clone(),
+      tag: tag.clone(),
+      current: Vec::new(),
+      position: 0,
+    })
+    .collect()
+}
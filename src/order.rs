@@ -0,0 +1,45 @@
+use std::io::Write;
+
+use serde_json::{Map, Value};
+
+/**
+ * Writes JSON objects with some keys always first, in the order given, and every other key after them alphabetically
+ */
+#[derive(Debug)]
+pub struct KeyOrder {
+  keys: Vec<String>,
+}
+
+impl KeyOrder {
+  pub fn parse(raw: &str) -> Result<KeyOrder, String> {
+    let keys: Vec<String> = raw.split(',')
+      .map(|key| key.trim().to_owned())
+      .collect();
+
+    if keys.iter().any(|key| key.is_empty()) {
+      return Err(format!("Argument --key-order contains an empty key in '{raw}'"));
+    }
+
+    Ok(KeyOrder { keys })
+  }
+
+  pub fn write<Writer: Write>(&self, values: &Map<String, Value>, target: &mut Writer) {
+    let first = self.keys.iter().filter_map(|key| values.get_key_value(key));
+    // the map is already sorted, so the rest are alphabetical
+    let rest = values.iter().filter(|(key, _)| !self.keys.contains(key));
+
+    target.write_all(b"{").expect("Failed to write line");
+
+    for (index, (key, value)) in first.chain(rest).enumerate() {
+      if index > 0 {
+        target.write_all(b",").expect("Failed to write line");
+      }
+
+      serde_json::to_writer(&mut *target, key).expect("Failed to write line");
+      target.write_all(b":").expect("Failed to write line");
+      serde_json::to_writer(&mut *target, value).expect("Failed to write line");
+    }
+
+    target.write_all(b"}").expect("Failed to write line");
+  }
+}
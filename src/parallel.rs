@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, sync_channel};
+use std::thread::{JoinHandle, spawn};
+
+use datetime::LocalDateTime;
+
+use crate::compact::compact;
+use crate::crypt::FieldCrypto;
+use crate::escalate::Escalation;
+use crate::filter::FilterSet;
+use crate::fingerprint::Fingerprint;
+use crate::log::{Aggregator, Line};
+use crate::partition::Partition;
+use crate::secrets::SecretScanner;
+use crate::shard::Shard;
+use crate::trace::TraceIds;
+use crate::translate::Translation;
+use crate::truncate::Truncation;
+use crate::why::{self, Why};
+
+// how many finished lines each worker can get ahead of the writer, unless --autotune picks another
+pub const LINE_BUFFER: usize = 4096;
+
+/**
+ * The stages that only look at one line at a time, and so can run on any thread
+ */
+pub struct Stages {
+  pub range: (Option<LocalDateTime>, Option<LocalDateTime>),
+  pub trace: Option<TraceIds>,
+  pub escalations: Vec<Escalation>,
+  pub shard: Option<Shard>,
+  pub partition: Option<Partition>,
+  pub filter: Option<FilterSet>,
+  pub why: Option<Why>,
+  pub translations: Vec<Translation>,
+  pub crypto: Option<FieldCrypto>,
+  pub secrets: Option<SecretScanner>,
+  pub fingerprint: Option<Fingerprint>,
+  pub compact: bool,
+  pub truncations: Vec<Truncation>,
+}
+
+impl Stages {
+  fn process(&self, mut line: Line) -> Option<Line> {
+    let drops = self.why.is_some_and(|why| why.drops());
+
+    let in_range = match self.range {
+      (None, None) => true,
+      (min, max) => line.time.is_some_and(|time| {
+        min.is_none_or(|min| time >= min) && max.is_none_or(|max| time < max)
+      }),
+    };
+
+    if !in_range {
+      if drops {
+        why::dropped("--range", if line.time.is_some() { "outside the range" } else { "no time" }, &line);
+      }
+
+      return None;
+    }
+
+    if let Some(trace) = &self.trace {
+      if !trace.apply(&mut line.value) {
+        if drops {
+          why::dropped("--trace", "another trace", &line);
+        }
+
+        return None;
+      }
+    }
+
+    for escalation in &self.escalations {
+      escalation.apply(&mut line.value);
+    }
+
+    if let Some(shard) = &self.shard {
+      if !shard.owns_event(&line.value) {
+        if drops {
+          why::dropped("--shard", "another shard", &line);
+        }
+
+        return None;
+      }
+    }
+
+    if let Some(partition) = &self.partition {
+      partition.apply(&mut line.value);
+    }
+
+    if let Some(filter) = &self.filter {
+      line = match self.why {
+        Some(why) => why::filter(filter, why, line)?,
+        None if filter.matches(&line.value) => line,
+        None => return None,
+      };
+    }
+
+    for trans in &self.translations {
+      trans.translate(&mut line.value);
+    }
+
+    if let Some(crypto) = &self.crypto {
+      crypto.apply(&mut line.value);
+    }
+
+    if let Some(secrets) = &self.secrets {
+      if !secrets.apply(&mut line) {
+        return None;
+      }
+    }
+
+    if let Some(fingerprint) = &self.fingerprint {
+      fingerprint.apply(&mut line.value);
+    }
+
+    if self.compact {
+      compact(&mut line.value);
+    }
+
+    for truncation in &self.truncations {
+      truncation.apply(&mut line.value);
+    }
+
+    Some(line)
+  }
+}
+
+/**
+ * Reads sources that don't overlap in time on separate threads, then yields their lines one source after another.
+ * Only as many sources as there are WORKERS are read at once, and each can only get a little ahead of the writer.
+ */
+pub struct ParallelReader {
+  stages: Arc<Stages>,
+  /// how many lines each worker can get ahead
+  buffer: usize,
+  pending: VecDeque<Aggregator>,
+  running: VecDeque<(JoinHandle<()>, Receiver<Line>)>,
+}
+
+impl ParallelReader {
+  pub fn new(sources: Vec<Aggregator>, stages: Stages, workers: usize, buffer: usize) -> ParallelReader {
+    let mut reader = ParallelReader {
+      stages: Arc::new(stages),
+      buffer,
+      pending: sources.into(),
+      running: VecDeque::new(),
+    };
+
+    for _ in 0..workers {
+      reader.start_next();
+    }
+
+    reader
+  }
+
+  fn start_next(&mut self) {
+    if let Some(source) = self.pending.pop_front() {
+      let stages = self.stages.clone();
+      let (sender, receiver) = sync_channel(self.buffer);
+
+      let handle = spawn(move || {
+        for line in source.filter_map(|line| stages.process(line)) {
+          // the reader only goes away if the program is ending anyway
+          if sender.send(line).is_err() {
+            return;
+          }
+        }
+      });
+
+      self.running.push_back((handle, receiver));
+    }
+  }
+}
+
+impl Iterator for ParallelReader {
+  type Item = Line;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let (_, receiver) = self.running.front()?;
+
+      if let Ok(line) = receiver.recv() {
+        return Some(line);
+      }
+
+      // this source is done, move on to the next one and start another in its place
+      let (handle, _) = self.running.pop_front().unwrap();
+
+      if handle.join().is_err() {
+        panic!("Failed to read source file");
+      }
+
+      self.start_next();
+    }
+  }
+}
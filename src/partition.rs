@@ -0,0 +1,56 @@
+use serde_json::{Map, Value};
+
+use crate::fingerprint::fnv;
+use crate::pretty::PrettyDescriptor;
+
+pub const PARTITION_FIELD: &str = "_partition";
+
+/**
+ * Numbers each event from 0 to N-1 by the hash of one field, from --partition-hash FIELD%N, so events can be split
+ * evenly by a field with far too many values to list. The hash is the one --shard key=FIELD uses, so partition K-1
+ * holds exactly the events shard K/N would keep
+ */
+#[derive(Debug)]
+pub struct Partition {
+  field: String,
+  count: u64,
+}
+
+impl Partition {
+  /**
+   * Parse FIELD%N, like user_id%16
+   */
+  pub fn parse(raw: &str) -> Result<Partition, String> {
+    let (field, count) = raw.rsplit_once('%')
+      .ok_or_else(|| format!("Partition '{raw}' must be in the form FIELD%N, like user_id%16"))?;
+
+    let field = field.trim().trim_start_matches('%');
+
+    if field.is_empty() {
+      return Err(format!("Partition '{raw}' must have a FIELD before the %"));
+    }
+
+    let count: u64 = count.trim().parse().ok()
+      .filter(|count| *count > 0)
+      .ok_or_else(|| format!("Partition '{raw}' must have a number of partitions greater than 0 after the %"))?;
+
+    Ok(Partition { field: field.to_string(), count })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{PARTITION_FIELD} from 0 to {} by the hash of '{}'", self.count - 1, self.field)
+  }
+
+  /**
+   * Set the event's partition. Events without the field are left without one, so they can be told apart
+   */
+  pub fn apply(&self, values: &mut Map<String, Value>) {
+    let Some(value) = values.get(&self.field) else {
+      return;
+    };
+
+    let partition = fnv(PrettyDescriptor::pretty_value(value).bytes()) % self.count;
+
+    values.insert(PARTITION_FIELD.to_string(), Value::from(partition));
+  }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+
+use crate::chunk::BYTE_SUFFIXES;
+use crate::pretty::PrettyDescriptor;
+
+lazy_static! {
+  static ref REGEXES: Mutex<HashMap<String, Regex>> = Mutex::new(HashMap::new());
+  static ref PRETTY: Mutex<HashMap<String, PrettyDescriptor>> = Mutex::new(HashMap::new());
+  static ref OPTIONS: RwLock<RegexOptions> = RwLock::new(RegexOptions::default());
+}
+
+/**
+ * Settings for every regex saw compiles, from --regex-options
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegexOptions {
+  /// largest a compiled regex may be, in bytes
+  size_limit: Option<usize>,
+  /// most memory the lazy DFA may use per regex, in bytes
+  dfa_size_limit: Option<usize>,
+  unicode: bool,
+  /// ^ and $ match at the start and end of each line, not just the whole value
+  multi_line: bool,
+  case_insensitive: bool,
+}
+
+impl Default for RegexOptions {
+  fn default() -> Self {
+    RegexOptions {
+      size_limit: None,
+      dfa_size_limit: None,
+      unicode: true,
+      multi_line: false,
+      case_insensitive: false,
+    }
+  }
+}
+
+impl RegexOptions {
+  /**
+   * Parse comma separated KEY=VALUE options, like size=50mb,unicode=false
+   */
+  pub fn parse(raw: &str) -> Result<RegexOptions, String> {
+    let mut options = RegexOptions::default();
+
+    for part in raw.split(',').map(str::trim) {
+      let (key, value) = part.split_once('=')
+        .ok_or_else(|| format!("Regex option '{part}' must be in the form KEY=VALUE"))?;
+
+      let flag = || match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("Regex option '{key}' must be true or false, not '{value}'")),
+      };
+
+      match key {
+        "size" => options.size_limit = Some(RegexOptions::parse_size(value)?),
+        "dfa" => options.dfa_size_limit = Some(RegexOptions::parse_size(value)?),
+        "unicode" => options.unicode = flag()?,
+        "multi-line" => options.multi_line = flag()?,
+        "ignore-case" => options.case_insensitive = flag()?,
+        _ => return Err(format!("Regex option '{key}' is not recognized. Valid options are size, dfa, unicode, multi-line and ignore-case")),
+      }
+    }
+
+    Ok(options)
+  }
+
+  fn parse_size(raw: &str) -> Result<usize, String> {
+    let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+    let (number, suffix) = raw.split_at(split);
+
+    let number: usize = number.parse().map_err(|_| format!("Regex size '{raw}' must start with a number"))?;
+    let multiplier = BYTE_SUFFIXES.iter()
+      .find(|(key, _)| *key == suffix || suffix.is_empty() && *key == "b")
+      .map(|(_, multiplier)| *multiplier)
+      .ok_or_else(|| format!("Regex size '{raw}' must end in b, kb, mb or gb"))?;
+
+    number.checked_mul(multiplier).ok_or_else(|| format!("Regex size '{raw}' is too large"))
+  }
+
+  pub fn describe(&self) -> String {
+    let mut parts = vec![];
+
+    if let Some(size) = self.size_limit {
+      parts.push(format!("size={size}b"));
+    }
+
+    if let Some(size) = self.dfa_size_limit {
+      parts.push(format!("dfa={size}b"));
+    }
+
+    parts.push(format!("unicode={}", self.unicode));
+    parts.push(format!("multi-line={}", self.multi_line));
+    parts.push(format!("ignore-case={}", self.case_insensitive));
+
+    parts.join(", ")
+  }
+
+  /**
+   * Does a pattern without special characters still match as a plain substring?
+   */
+  pub fn literals_are_plain(&self) -> bool {
+    !self.case_insensitive
+  }
+
+  /**
+   * The largest a compiled regex may be, if one was given
+   */
+  pub fn size_limit(&self) -> Option<usize> {
+    self.size_limit
+  }
+}
+
+/**
+ * Set the options for every regex compiled after this. Must be called before any are compiled
+ */
+pub fn configure(options: RegexOptions) {
+  *OPTIONS.write().expect("Regex options were poisoned") = options;
+  REGEXES.lock().expect("Pattern cache was poisoned").clear();
+  PRETTY.lock().expect("Pattern cache was poisoned").clear();
+}
+
+pub fn options() -> RegexOptions {
+  *OPTIONS.read().expect("Regex options were poisoned")
+}
+
+/**
+ * Compile a regex, or reuse the one already compiled from the same pattern. Every pattern is compiled while the
+ * arguments are read, so a bad one stops saw before any source is read. 'what' names the option in the error.
+ */
+pub fn regex(pattern: &str, what: &str) -> Result<Regex, String> {
+  let mut cache = REGEXES.lock().expect("Pattern cache was poisoned");
+
+  if let Some(found) = cache.get(pattern) {
+    // cloning shares the compiled program
+    return Ok(found.clone());
+  }
+
+  let options = options();
+  let mut builder = RegexBuilder::new(pattern);
+
+  builder
+    .unicode(options.unicode)
+    .multi_line(options.multi_line)
+    .case_insensitive(options.case_insensitive);
+
+  if let Some(size) = options.size_limit {
+    builder.size_limit(size);
+  }
+
+  if let Some(size) = options.dfa_size_limit {
+    builder.dfa_size_limit(size);
+  }
+
+  let compiled = builder.build()
+    .map_err(|err| format!("{what} '{pattern}' is not a valid regex according to https://github.com/rust-lang/regex\n{err}"))?;
+
+  cache.insert(pattern.to_string(), compiled.clone());
+  Ok(compiled)
+}
+
+/**
+ * Compile several regexes to be checked together, with the same options as any other
+ */
+pub fn regex_set(patterns: &[&str], what: &str) -> Result<RegexSet, String> {
+  let options = options();
+  let mut builder = RegexSetBuilder::new(patterns);
+
+  builder
+    .unicode(options.unicode)
+    .multi_line(options.multi_line)
+    .case_insensitive(options.case_insensitive);
+
+  if let Some(size) = options.size_limit {
+    builder.size_limit(size);
+  }
+
+  if let Some(size) = options.dfa_size_limit {
+    builder.dfa_size_limit(size);
+  }
+
+  builder.build()
+    .map_err(|err| format!("{what} are not valid regexes according to https://github.com/rust-lang/regex\n{err}"))
+}
+
+/**
+ * Parse a pretty pattern, or copy the one already parsed from the same text, like a preset's pattern that is also
+ * used for --translate
+ */
+pub fn pretty(pattern: &str) -> Result<PrettyDescriptor, String> {
+  if let Some(found) = PRETTY.lock().expect("Pattern cache was poisoned").get(pattern) {
+    return Ok(found.clone());
+  }
+
+  // not held while parsing, which may compile regexes for %replace
+  let parsed = PrettyDescriptor::parse(pattern)?;
+
+  PRETTY.lock().expect("Pattern cache was poisoned").insert(pattern.to_string(), parsed.clone());
+  Ok(parsed)
+}
@@ -0,0 +1,453 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::flags;
+
+pub const PIPELINE_HELP: &str = r#"
+Usage:
+  saw run PIPELINE [SOURCES...] [OPTIONS...]
+
+Runs the job described by the PIPELINE file, for jobs with too many stages to read comfortably as flags. It's YAML,
+or JSON when it ends in .json, with up to four sections. Each key is the long name of an option without its --:
+  sources  the files to read, one or a list, the same as listed on the command line
+  options  how sources are read and anything else that isn't a stage, like preset, time-field or daily
+  stages   what happens to each event, as a list run in the order given, so repeated ones like translate keep theirs
+  output   where and how events are written, like output, split-by-time or pretty
+
+A value is given as it would be on the command line. Options without a value take true, or false to leave them out.
+Options with more than one value, like range or translate, take a list of them, and repeatable options with one
+value can take a list to be given once for each:
+
+  sources: ["gateway/*.log.gz", "backend/*.log.gz"]
+  options:
+    preset: pino
+    range: [2024-06-01T00:00:00, 2024-06-02T00:00:00]
+  stages:
+    - filter: "%level=ERROR|WARN"
+    - escalate: "%message=timeout => level=ERROR"
+    - translate: [summary, "%level %message"]
+    - compact: true
+  output:
+    output: out/incident
+    split-by-time: 1h
+
+Sources and options given after PIPELINE override it. Sources replace every source of the file, and an option
+replaces every time the file gives it, so one file can serve many runs. Sources must come before the first option:
+  saw run incident.yaml --range 2024-06-03T00:00:00 2024-06-04T00:00:00 --output out/june-3
+
+Only the parts of YAML a pipeline needs are read: mappings and lists, both by indentation, lists in [brackets],
+quoted and plain text, true, false and # comments. Text holding ': ' or ' #' must be quoted.
+"#;
+
+/// the sections of a pipeline, in the order their options are given
+const SECTIONS: [&str; 4] = ["sources", "options", "stages", "output"];
+
+/**
+ * The command line a pipeline file stands for, with the sources and options after it applied over the top. None if
+ * help was asked for instead
+ */
+pub fn arguments<Args: Iterator<Item=String>>(mut src: Args) -> Option<Vec<String>> {
+  let path = src.next().expect("saw run must be followed by a PIPELINE file. Run saw run --help for more information");
+
+  if path == "-h" || path == "--help" {
+    eprintln!("{PIPELINE_HELP}");
+    return None;
+  }
+
+  let raw = fs::read_to_string(&path).unwrap_or_else(|err| panic!("Failed to read pipeline {path}: {err}"));
+
+  let spec = if Path::new(&path).extension().is_some_and(|ext| ext == "json") {
+    serde_json::from_str(&raw).unwrap_or_else(|err| panic!("Invalid JSON in pipeline {path}: {err}"))
+  } else {
+    yaml::parse(&raw).unwrap_or_else(|err| panic!("Invalid YAML in pipeline {path}: {err}"))
+  };
+
+  let (sources, options) = from_spec(&spec, &path);
+  let (override_sources, overrides) = group(src);
+
+  let mut args = if override_sources.is_empty() { sources } else { override_sources };
+
+  for (flag, values) in options {
+    if !overrides.iter().any(|(overridden, _)| *overridden == flag) {
+      args.push(flag.to_string());
+      args.extend(values);
+    }
+  }
+
+  for (flag, values) in overrides {
+    args.push(flag.to_string());
+    args.extend(values);
+  }
+
+  Some(args)
+}
+
+/**
+ * The sources and each option with its values from a pipeline, in the order they're given
+ */
+fn from_spec(spec: &Value, path: &str) -> (Vec<String>, Vec<(&'static str, Vec<String>)>) {
+  let Value::Object(sections) = spec else {
+    panic!("Pipeline {path} must be a mapping of {}", SECTIONS.join(", "));
+  };
+
+  if let Some(unknown) = sections.keys().find(|key| !SECTIONS.contains(&key.as_str())) {
+    panic!("Pipeline {path} has an unknown section '{unknown}'. Valid sections are {}", SECTIONS.join(", "));
+  }
+
+  let sources = match sections.get("sources") {
+    None | Some(Value::Null) => vec![],
+    Some(Value::Array(sources)) => sources.iter().map(|source| scalar(source, "sources", path)).collect(),
+    Some(source) => vec![scalar(source, "sources", path)],
+  };
+
+  let mut options = vec![];
+
+  for section in ["options", "stages", "output"] {
+    let entries: Vec<(&String, &Value)> = match sections.get(section) {
+      None | Some(Value::Null) => vec![],
+      Some(Value::Object(entries)) if section != "stages" => entries.iter().collect(),
+      Some(Value::Array(stages)) if section == "stages" => stages.iter()
+        .map(|stage| match stage {
+          Value::Object(stage) if stage.len() == 1 => stage.iter().next().unwrap(),
+          _ => panic!("Each stage in pipeline {path} must be a single option, like '- filter: \"%level=ERROR\"'"),
+        })
+        .collect(),
+      Some(_) if section == "stages" => panic!("Section 'stages' of pipeline {path} must be a list"),
+      Some(_) => panic!("Section '{section}' of pipeline {path} must be a mapping of options"),
+    };
+
+    for (key, value) in entries {
+      options.extend(option(key, value, path));
+    }
+  }
+
+  (sources, options)
+}
+
+/**
+ * The flag a key stands for, given once or more with its values
+ */
+fn option(key: &str, value: &Value, path: &str) -> Vec<(&'static str, Vec<String>)> {
+  let flag = flags::find(&format!("--{key}"))
+    .filter(|flag| flag.long != "--help" && flag.long != "--help-json")
+    .unwrap_or_else(|| {
+      let hint = flags::suggest(key).map_or(".".to_string(), |flag| format!(", did you mean '{}'?", &flag.long[2..]));
+
+      panic!("Pipeline {path} has an unknown option '{key}'{hint} Run saw --help to see all known options")
+    });
+
+  if flag.values.is_empty() {
+    return match value {
+      Value::Bool(true) => vec![(flag.long, vec![])],
+      Value::Bool(false) | Value::Null => vec![],
+      _ => panic!("Option '{key}' in pipeline {path} doesn't take a value, so it can only be true or false"),
+    };
+  }
+
+  let given = |values: &Value| -> Vec<String> {
+    match values {
+      Value::Array(values) => values.iter().map(|value| scalar(value, key, path)).collect(),
+      value => vec![scalar(value, key, path)],
+    }
+  };
+
+  match value {
+    // a list of lists gives the option once for each
+    Value::Array(each) if each.iter().all(Value::is_array) && !each.is_empty() => each.iter().map(|values| (flag.long, given(values))).collect(),
+    Value::Array(each) if flag.values.len() == 1 && flag.repeatable => each.iter().map(|value| (flag.long, given(value))).collect(),
+    value => vec![(flag.long, given(value))],
+  }
+}
+
+fn scalar(value: &Value, key: &str, path: &str) -> String {
+  match value {
+    Value::String(text) => text.clone(),
+    Value::Bool(_) | Value::Number(_) => value.to_string(),
+    _ => panic!("Option '{key}' in pipeline {path} must be given as text, a number or true or false"),
+  }
+}
+
+/**
+ * Split what comes after the pipeline into the sources before the first option, and each option with the values
+ * after it
+ */
+fn group<Args: Iterator<Item=String>>(src: Args) -> (Vec<String>, Vec<(&'static str, Vec<String>)>) {
+  let mut sources = vec![];
+  let mut options: Vec<(&'static str, Vec<String>)> = vec![];
+
+  for arg in src {
+    let is_flag = arg.starts_with('-') && arg != "-";
+
+    if is_flag {
+      let flag = flags::find(&arg).unwrap_or_else(|| panic!("{}", flags::unknown(&arg)));
+
+      options.push((flag.long, vec![]));
+    } else if let Some((_, values)) = options.last_mut() {
+      values.push(arg);
+    } else {
+      sources.push(arg);
+    }
+  }
+
+  (sources, options)
+}
+
+/**
+ * Just enough YAML for a pipeline: block mappings and lists by indentation, flow lists, quoted and plain scalars,
+ * booleans, null and comments
+ */
+mod yaml {
+  use serde_json::{Map, Value};
+
+  struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+  }
+
+  pub fn parse(raw: &str) -> Result<Value, String> {
+    let lines: Vec<Line> = raw.lines().enumerate()
+      .filter(|(_, line)| line.trim() != "---")
+      .map(|(index, line)| (index + 1, strip_comment(line)))
+      .filter(|(_, line)| !line.trim().is_empty())
+      .map(|(number, line)| Ok(Line { number, indent: indent(line, number)?, text: line.trim() }))
+      .collect::<Result<_, String>>()?;
+
+    if lines.is_empty() {
+      return Ok(Value::Object(Map::new()));
+    }
+
+    let mut at = 0;
+    let value = block(&lines, &mut at, lines[0].indent, None)?;
+
+    match lines.get(at) {
+      Some(line) => Err(format!("line {} is indented less than the lines before it", line.number)),
+      None => Ok(value),
+    }
+  }
+
+  fn indent(line: &str, number: usize) -> Result<usize, String> {
+    let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+
+    if line[..indent].contains('\t') {
+      return Err(format!("line {number} is indented with a tab, which YAML doesn't allow"));
+    }
+
+    Ok(indent)
+  }
+
+  /// everything before a # that starts a comment, which is one at the start or after a space outside quotes
+  fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut previous = ' ';
+
+    for (index, c) in line.char_indices() {
+      match quote {
+        Some(open) if c == open => quote = None,
+        Some(_) => {}
+        None if c == '"' || c == '\'' => quote = Some(c),
+        None if c == '#' && previous.is_whitespace() => return &line[..index],
+        None => {}
+      }
+
+      previous = c;
+    }
+
+    line
+  }
+
+  /**
+   * A mapping or list whose lines are all at this indent. 'first' replaces the text of the first line, for a mapping
+   * that starts on the same line as the '- ' of a list
+   */
+  fn block(lines: &[Line], at: &mut usize, indent: usize, first: Option<&str>) -> Result<Value, String> {
+    let text = first.unwrap_or(lines[*at].text);
+
+    if text == "-" || text.starts_with("- ") {
+      list(lines, at, indent)
+    } else {
+      mapping(lines, at, indent, first)
+    }
+  }
+
+  fn list(lines: &[Line], at: &mut usize, indent: usize) -> Result<Value, String> {
+    let mut items = vec![];
+
+    while let Some(line) = lines.get(*at).filter(|line| line.indent == indent && (line.text == "-" || line.text.starts_with("- "))) {
+      let rest = line.text[1..].trim_start();
+      let offset = line.text.len() - rest.len();
+
+      if rest.is_empty() {
+        *at += 1;
+        items.push(nested(lines, at, indent)?);
+      } else if key_value(rest).is_some() {
+        // a mapping that starts on the line of its '- ', with the rest of its keys lined up under the first
+        items.push(block(lines, at, indent + offset, Some(rest))?);
+      } else {
+        *at += 1;
+        items.push(scalar(rest, line.number)?);
+      }
+    }
+
+    Ok(Value::Array(items))
+  }
+
+  fn mapping(lines: &[Line], at: &mut usize, indent: usize, mut first: Option<&str>) -> Result<Value, String> {
+    let mut entries = Map::new();
+
+    while let Some(line) = lines.get(*at) {
+      let text = match first.take() {
+        Some(text) => text,
+        None if line.indent == indent => line.text,
+        None => break,
+      };
+
+      let (key, value) = key_value(text).ok_or(format!("line {} should be 'key: value'", line.number))?;
+      let key = match scalar(key, line.number)? {
+        Value::String(key) => key,
+        other => other.to_string(),
+      };
+
+      *at += 1;
+
+      let value = if value.is_empty() {
+        // a list under a key may start at the same indent as the key
+        match lines.get(*at) {
+          Some(next) if next.indent == indent && (next.text == "-" || next.text.starts_with("- ")) => list(lines, at, indent)?,
+          _ => nested(lines, at, indent)?,
+        }
+      } else {
+        scalar(value, line.number)?
+      };
+
+      if entries.insert(key.clone(), value).is_some() {
+        return Err(format!("line {} repeats the key '{key}'", line.number));
+      }
+    }
+
+    Ok(Value::Object(entries))
+  }
+
+  /// the block indented under a line, or null if there isn't one
+  fn nested(lines: &[Line], at: &mut usize, indent: usize) -> Result<Value, String> {
+    match lines.get(*at) {
+      Some(next) if next.indent > indent => block(lines, at, next.indent, None),
+      _ => Ok(Value::Null),
+    }
+  }
+
+  /// split 'key: value' at the first ': ' or trailing ':' outside quotes
+  fn key_value(text: &str) -> Option<(&str, &str)> {
+    let mut quote: Option<char> = None;
+
+    for (index, c) in text.char_indices() {
+      match quote {
+        Some(open) if c == open => quote = None,
+        Some(_) => {}
+        None if (c == '"' || c == '\'') && index == 0 => quote = Some(c),
+        None if c == ':' && text[index + 1..].chars().next().is_none_or(char::is_whitespace) => {
+          return Some((text[..index].trim(), text[index + 1..].trim()));
+        }
+        None => {}
+      }
+    }
+
+    None
+  }
+
+  fn scalar(text: &str, number: usize) -> Result<Value, String> {
+    let text = text.trim();
+
+    if let Some(inner) = text.strip_prefix('[') {
+      let inner = inner.strip_suffix(']').ok_or(format!("line {number} has a list without its closing ]"))?;
+
+      return split_flow(inner, number)?.into_iter()
+        .map(|item| scalar(item, number))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::Array);
+    }
+
+    if text.starts_with('{') {
+      return Err(format!("line {number} uses {{ }}, which pipelines don't support. Write the mapping on its own lines"));
+    }
+
+    if let Some(inner) = text.strip_prefix('"') {
+      let inner = inner.strip_suffix('"').filter(|_| text.len() > 1).ok_or(format!("line {number} has text without its closing \""))?;
+
+      return unescape(inner).map(Value::String).ok_or(format!("line {number} has an unknown escape in \"{inner}\""));
+    }
+
+    if let Some(inner) = text.strip_prefix('\'') {
+      let inner = inner.strip_suffix('\'').filter(|_| text.len() > 1).ok_or(format!("line {number} has text without its closing '"))?;
+
+      return Ok(Value::String(inner.replace("''", "'")));
+    }
+
+    Ok(match text {
+      "true" | "True" | "TRUE" => Value::Bool(true),
+      "false" | "False" | "FALSE" => Value::Bool(false),
+      "null" | "Null" | "NULL" | "~" | "" => Value::Null,
+      _ => Value::String(text.to_string()),
+    })
+  }
+
+  /// the items of a [flow, list], split at commas outside quotes
+  fn split_flow(inner: &str, number: usize) -> Result<Vec<&str>, String> {
+    let mut items = vec![];
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (index, c) in inner.char_indices() {
+      match quote {
+        Some(open) if c == open && !inner[..index].ends_with('\\') => quote = None,
+        Some(_) => {}
+        None if c == '"' || c == '\'' => quote = Some(c),
+        None if c == '[' => return Err(format!("line {number} has a list inside a [list], which pipelines don't support")),
+        None if c == ',' => {
+          items.push(&inner[start..index]);
+          start = index + 1;
+        }
+        None => {}
+      }
+    }
+
+    let last = &inner[start..];
+
+    if !last.trim().is_empty() || !items.is_empty() {
+      items.push(last);
+    }
+
+    Ok(items)
+  }
+
+  fn unescape(inner: &str) -> Option<String> {
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+      if c != '\\' {
+        text.push(c);
+        continue;
+      }
+
+      text.push(match chars.next()? {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '\\' => '\\',
+        '"' => '"',
+        '/' => '/',
+        'u' => {
+          let hex: String = chars.by_ref().take(4).collect();
+          char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+        }
+        _ => return None,
+      });
+    }
+
+    Some(text)
+  }
+}
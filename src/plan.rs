@@ -0,0 +1,414 @@
+use std::path::Path;
+
+use datetime::{ISO, LocalDateTime};
+
+use crate::adaptive;
+use crate::args::Arguments;
+use crate::control::Control;
+use crate::exists::OnExists;
+use crate::functions;
+use crate::index;
+use crate::input::InputFormat;
+use crate::limits;
+use crate::log::ReadOptions;
+use crate::stream;
+use crate::verify::SortCheck;
+use crate::why::Why;
+
+/**
+ * Print everything saw would do with these arguments, without reading any lines.
+ * Returns false if any problems were found that would make the real run fail.
+ */
+pub fn print_plan(args: &Arguments) -> bool {
+  let mut problems: Vec<String> = vec![];
+
+  println!("Sources:");
+  if args.sources.is_empty() {
+    problems.push("No source files matched".to_string());
+  }
+  let options = ReadOptions {
+    preset: args.preset,
+    levels: args.levels.filter(|_| args.rewrite_levels),
+    projection: args.projection,
+    passthrough_invalid: args.passthrough_invalid,
+    lenient_json: args.lenient_json,
+    resync: args.resync,
+    input: args.input,
+    time: args.time,
+    ..ReadOptions::default()
+  };
+  for source in &args.sources {
+    if args.shard.as_ref().is_some_and(|shard| !shard.owns_source(source.name())) {
+      println!("  {} (skipped, belongs to another shard)", source.name());
+    } else if let Some(reason) = index::skip_reason(source, args.range, args.filter.as_ref(), options)
+      .filter(|_| !args.why.is_some_and(|why| why.drops())) {
+      println!("  {} (skipped, its index shows {reason})", source.name());
+    } else {
+      println!("  {}", source.name());
+    }
+  }
+
+  if let Some(age) = &args.ignore_older_than {
+    for ignored in age.ignored() {
+      println!("  {ignored} (skipped, {})", age.reason());
+    }
+  }
+
+  if args.async_sources {
+    println!("Reading: every file at once, before merging");
+  }
+  if args.deterministic {
+    println!("Deterministic: sources merged in order of their names, nothing written depends on when saw runs");
+  }
+  if let Some(max) = args.max_open_files {
+    println!("Reading: at most {max} files open at once, in the order they seem to start");
+  }
+  if let Some(limits) = limits::describe(args.max_cpu) {
+    println!("Limits: {limits}");
+  }
+  if let Some(autotune) = &args.autotune {
+    println!("Autotune: {}", autotune.describe());
+  }
+  if let Some(InputFormat::Frames(framing)) = args.input {
+    println!("Input: {}", framing.describe());
+  } else if let Some(input) = args.input {
+    println!("Input: {}", input.name());
+  }
+
+  if let Some(projection) = args.projection {
+    println!("Fields: {}", projection.describe());
+  }
+
+  if args.lenient_json {
+    println!("Lenient JSON: NaN, Infinity and integers over 64 bits read as strings");
+  }
+
+  if args.resync {
+    println!("Corrupt data: skipped to the next record that can be read, and reported once");
+  }
+
+  if args.debug_raw {
+    println!("Invalid lines: reported with their bytes");
+  }
+
+  for copy in &args.raw_copies {
+    println!("Raw copy: {}", copy.describe());
+  }
+
+  if let Some(time) = args.time {
+    println!("Time: {}", time.describe());
+  }
+
+  if let Some(preset) = args.preset {
+    println!("Preset: {}", preset.name);
+  }
+
+  match args.levels {
+    Some(levels) if args.rewrite_levels => println!("Levels: {}, written into the events", levels.describe()),
+    Some(levels) => println!("Levels: {}, for filters and pretty output", levels.describe()),
+    None => println!("Levels: numbers kept as they are"),
+  }
+
+  if args.keep_order {
+    println!("Order: as given");
+  } else if args.reverse {
+    println!("Order: newest first");
+  } else if stream::can_stream(args) {
+    println!("Order: as read, from a single source whose times aren't needed");
+  } else if let Some(sort) = &args.external_sort {
+    println!("Order: sorted {}", sort.describe());
+  } else if let Some(sort_by) = args.sort_by {
+    println!("Order: merged by {}", sort_by.describe());
+  } else {
+    println!("Order: merged by time");
+  }
+
+  if args.follow {
+    println!("Follow: watching for new lines and files");
+
+    if let Some(interval) = args.poll_interval {
+      println!("Poll: looking for more lines every {}ms", interval.as_millis());
+    }
+  }
+
+  if let Some(reload) = &args.reload {
+    println!("Reload: filters, translations and template from '{}' when it changes", reload.display());
+  }
+  if let Some(watermark) = &args.drop_older_than {
+    println!("Watermark: dropping {}", watermark.describe());
+  }
+
+  if let Some(tail) = args.tail {
+    println!("Tail: only the last {tail} events");
+  }
+
+  println!("Range: {} to {}{}", describe_time(args.range.0), describe_time(args.range.1), if args.daily { " (daily)" } else { "" });
+
+  if let Some(shard) = &args.shard {
+    println!("Shard: {}", shard.describe());
+  }
+  if let Some(tenant) = &args.tenant {
+    println!("Tenant: {}", tenant.describe());
+  }
+  if let Some(partition) = &args.partition {
+    println!("Partition: {}", partition.describe());
+  }
+  if let Some(sessionize) = &args.sessionize {
+    println!("Sessions: {}", sessionize.describe());
+  }
+  if let Some(link) = &args.link_by {
+    println!("Links: {}", link.describe());
+  }
+  if let Some(anomalies) = &args.anomalies {
+    println!("Anomalies: {}", anomalies.describe());
+  }
+  if let Some(correlate) = &args.correlate {
+    println!("Correlate: {}", correlate.describe());
+  }
+
+  if let Some(trace) = &args.trace {
+    println!("Trace: {}", trace.describe());
+  }
+
+  if !args.escalations.is_empty() {
+    println!("Escalations:");
+    for escalation in &args.escalations {
+      println!("  {escalation}");
+    }
+  }
+
+  if let Some(advice) = args.page_cache {
+    println!("Page cache: {}", advice.describe());
+  }
+
+  if let Some(policy) = args.read_retries {
+    println!("Read retries: {}", policy.describe());
+  }
+
+  for (pattern, codec) in &args.source_codecs {
+    println!("Codec: '{pattern}' read as {}", codec.name());
+  }
+
+  if let Some(options) = &args.regex_options {
+    println!("Regex options: {}", options.describe());
+  }
+
+  if let Some(functions) = functions::describe() {
+    println!("Pattern functions: {functions}");
+  }
+
+  if let Some(filter) = &args.filter {
+    println!("Filters:");
+    for next in &filter.value_sets {
+      println!("  {next}");
+    }
+    for next in &filter.sets {
+      println!("  {next}");
+    }
+    for next in &filter.expressions {
+      println!("  {next}");
+    }
+    if let Some(ignore) = &filter.ignore {
+      println!("  none known as noise by {}", ignore.describe());
+    }
+  }
+  match args.why {
+    Some(Why::Matched) => println!("Why: list the filters that accepted each event in _matched_by"),
+    Some(Why::Drops) => println!("Why: list the filters that accepted each event in _matched_by, and note every dropped event on stderr"),
+    None => {}
+  }
+
+  if !args.translations.is_empty() {
+    println!("Translations:");
+    for trans in &args.translations {
+      println!("  {trans}");
+    }
+  }
+
+  if let Some(deadline) = args.deadline {
+    println!("Deadline: {}s", deadline.as_secs_f64());
+  }
+
+  if let Some(action) = args.verify_sorted {
+    println!("Verify order: {}", SortCheck::new(action, args.reverse).describe());
+  }
+
+  if let Some(replay) = &args.replay {
+    println!("Replay: {}", replay.describe());
+  }
+
+  if let Some(path) = &args.control_socket {
+    println!("Control: {}", Control::describe(path));
+  }
+
+  if let Some(quota) = &args.quota {
+    println!("Quota: {}", quota.describe());
+  }
+
+  if let Some(rate_limit) = &args.rate_limit {
+    println!("Rate limit: {}", rate_limit.describe());
+  }
+
+  if let Some(expect) = &args.expect_count {
+    println!("Expect: {} events written", expect.describe());
+  }
+
+  if args.audit {
+    println!("Audit: translation counts printed to stderr");
+  }
+
+  if let Some(crypto) = &args.crypto {
+    println!("Fields: {}", crypto.describe());
+
+    if args.write_decrypted {
+      println!("Decrypted: written to every output, not only pretty or table output");
+    }
+  }
+  if let Some(secrets) = &args.secrets {
+    println!("Secrets: {}", secrets.action().describe());
+  }
+
+  if args.fingerprint.is_some() {
+    println!("Fingerprint: _fingerprint");
+  }
+
+  if args.compact {
+    println!("Compact: empty and null fields removed");
+  }
+
+  for truncation in &args.truncations {
+    println!("Truncate: {}", truncation.describe());
+  }
+
+  if let Some(normalize) = args.normalize_time {
+    println!("Time written as: {}", normalize.describe());
+  }
+
+  for plugin in &args.plugins {
+    println!("Plugin: {}", plugin.describe());
+  }
+
+  if !args.percentiles.is_empty() {
+    println!("Percentiles: {}", args.percentiles.join(", "));
+  }
+
+  if args.source_stats {
+    println!("Source stats: printed to stderr");
+  }
+
+  if let Some(by) = args.summary_by {
+    println!("Summary: by {by:?}");
+  }
+
+  if !args.timezone.is_utc() {
+    println!("Time zone: days and hours start in {}", args.timezone.describe());
+  }
+
+  if let Some(stats) = &args.stats {
+    println!("Format: {} as {}", stats.mode().describe(), if stats.json { "JSON" } else { "a table" });
+  } else if let Some(table) = &args.table {
+    println!("Format: table of {}", table.describe());
+  } else if args.html.is_some() {
+    println!("Format: html report");
+  } else if args.indented {
+    println!("Format: indented json");
+  } else if let Some(framing) = &args.frames {
+    println!("Format: {}", framing.describe());
+  } else {
+    match &args.theme {
+      Some(theme) => println!("Format: pretty, picked by '{}'", theme.field()),
+      None if args.adaptive_pretty && !args.follow && args.replay.is_none() => println!("Format: pretty, with the fields of the first {} events", adaptive::SAMPLE_SIZE),
+      None => println!("Format: {}", if args.pretty.is_some() { "pretty" } else { "json" }),
+    }
+
+    if let Some(group) = &args.group {
+      println!("  grouped by '{}'", group.field());
+    }
+
+    if args.collapse_repeats {
+      println!("  with repeated lines collapsed");
+    }
+  }
+
+  if let Some(every) = &args.snapshot_every {
+    println!("  with a snapshot of what's so far {}", every.describe());
+  }
+
+  if let Some(escape) = args.escape_json {
+    println!("  with JSON strings escaping {}", escape.describe());
+  }
+
+  match &args.output {
+    Some(output) => {
+      let name = output.to_str().unwrap_or("<invalid path>");
+
+      if let Some(interval) = &args.split_by_time {
+        println!("Output: {name}/YYYY/MM/DD/... split every {interval} by event time ({:?})", args.compression.codec_for(name));
+      } else if let Some(chunked) = &args.chunked {
+        let limits: Vec<String> = chunked.limits.iter().map(|limit| limit.to_string()).collect();
+
+        println!("Output: {name}.N chunked every {}", limits.join(" or "));
+
+        if args.skip_unchanged {
+          println!("  chunks that match {name}.manifest are left untouched");
+        }
+
+        if let Some(workers) = &args.chunk_workers {
+          println!("  chunks compressed by {}", workers.describe());
+        }
+      } else {
+        println!("Output: {name} ({:?})", args.compression.codec_for(name));
+
+        if output.exists() {
+          match args.on_exists {
+            OnExists::Error => problems.push(format!("Output '{name}' already exists, pass --on-exists to write it anyway")),
+            OnExists::Overwrite => println!("  '{name}' already exists and will be overwritten"),
+            OnExists::Append => println!("  '{name}' already exists and will be appended to"),
+            OnExists::Rename => println!("  '{name}' already exists and will be moved aside"),
+          }
+        }
+      }
+
+      if !args.skip_unchanged && (args.chunked.is_some() || args.split_by_time.is_some()) {
+        println!("  existing files: {}", args.on_exists.name());
+      }
+
+      if let Some(checksum) = &args.checksum {
+        println!("  a {} checksum next to every file written", checksum.name());
+      }
+
+      let parent = output.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+
+      if !parent.is_dir() {
+        problems.push(format!("Output directory '{}' does not exist", parent.display()));
+      }
+    }
+    None if args.clipboard => println!("Output: clipboard"),
+    None => println!("Output: stdout ({:?})", args.compression.codec_for("-")),
+  }
+
+  for route in &args.routes {
+    let name = route.path.to_str().unwrap_or("<invalid path>");
+
+    println!("Route: {route} ({:?})", args.compression.codec_for(name));
+
+    if route.path.exists() && args.on_exists == OnExists::Error {
+      problems.push(format!("Route output '{name}' already exists, pass --on-exists to write it anyway"));
+    }
+  }
+
+  if problems.is_empty() {
+    println!("No problems found");
+  } else {
+    println!("Problems:");
+    for problem in &problems {
+      println!("  {problem}");
+    }
+  }
+
+  problems.is_empty()
+}
+
+fn describe_time(time: Option<LocalDateTime>) -> String {
+  time.map_or("*".to_string(), |time| time.iso().to_string())
+}
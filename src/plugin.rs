@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread::{spawn, JoinHandle};
+
+use serde_json::Value;
+
+use crate::log::Line;
+use crate::why;
+
+/// how many events are sent to a plugin before waiting for its answers, unless following
+pub const BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PluginKind {
+  /// answers true to keep each event or false to leave it out
+  Filter,
+  /// answers each event with the event to write in its place, or null to leave it out
+  Transform,
+}
+
+/**
+ * An external command from --plugin-filter CMD or --plugin-transform CMD. It's run with sh, reads one JSON event per
+ * line on stdin and writes one answer per line on stdout, in the same order. Events are sent in batches, so it
+ * must answer each event as soon as it has read it rather than waiting for stdin to end
+ */
+#[derive(Debug, Clone)]
+pub struct Plugin {
+  pub kind: PluginKind,
+  pub command: String,
+}
+
+impl Plugin {
+  pub fn flag(&self) -> &'static str {
+    match self.kind {
+      PluginKind::Filter => "--plugin-filter",
+      PluginKind::Transform => "--plugin-transform",
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    match self.kind {
+      PluginKind::Filter => format!("filter with '{}'", self.command),
+      PluginKind::Transform => format!("transform with '{}'", self.command),
+    }
+  }
+
+  pub fn start(&self) -> PluginProcess {
+    let mut child = Command::new("sh")
+      .args(["-c", &self.command])
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::inherit())
+      .spawn()
+      .unwrap_or_else(|_| panic!("Failed to run plugin '{}'", self.command));
+
+    let stdin = child.stdin.take().expect("Failed to write to plugin");
+    let stdout = child.stdout.take().expect("Failed to read from plugin");
+
+    // events are written on their own thread, so a plugin that answers before reading the whole batch never has
+    // both sides waiting on a full pipe
+    let (sender, receiver) = sync_channel::<Vec<String>>(1);
+    let writer = spawn(move || {
+      let mut stdin = BufWriter::new(stdin);
+
+      for batch in receiver {
+        for event in batch {
+          if stdin.write_all(event.as_bytes()).and_then(|_| stdin.write_all(b"\n")).is_err() {
+            return;
+          }
+        }
+
+        if stdin.flush().is_err() {
+          return;
+        }
+      }
+    });
+
+    PluginProcess {
+      plugin: self.clone(),
+      child,
+      sender: Some(sender),
+      writer: Some(writer),
+      answers: BufReader::new(stdout).lines(),
+    }
+  }
+}
+
+/**
+ * A plugin that is running
+ */
+pub struct PluginProcess {
+  plugin: Plugin,
+  child: Child,
+  sender: Option<SyncSender<Vec<String>>>,
+  writer: Option<JoinHandle<()>>,
+  answers: Lines<BufReader<ChildStdout>>,
+}
+
+impl PluginProcess {
+  /**
+   * Send a batch of events and read back an answer for each, returning the events to keep with whatever changes
+   * the plugin made
+   */
+  pub fn apply(&mut self, lines: Vec<Line>, drops: bool) -> Vec<Line> {
+    let events = lines.iter().map(|line| serde_json::to_string(&line.value).unwrap_or_default()).collect();
+
+    if let Some(sender) = &self.sender {
+      // a plugin that has exited is reported below, when its answers run out
+      let _ = sender.send(events);
+    }
+
+    let mut kept = Vec::with_capacity(lines.len());
+
+    for mut line in lines {
+      let command = &self.plugin.command;
+
+      let answer = match self.answers.next() {
+        Some(Ok(answer)) => answer,
+        _ => panic!("Plugin '{command}' stopped before answering every event"),
+      };
+
+      let keep = match (self.plugin.kind, answer.trim()) {
+        (PluginKind::Filter, "true") => true,
+        (PluginKind::Filter, "false") => false,
+        (PluginKind::Filter, other) => panic!("Plugin '{command}' answered '{other}', but a filter must answer true or false"),
+        (PluginKind::Transform, other) => match serde_json::from_str(other) {
+          Ok(Value::Object(value)) => {
+            line.value = value;
+            true
+          }
+          Ok(Value::Null) => false,
+          _ => panic!("Plugin '{command}' answered '{other}', but a transform must answer a JSON object or null"),
+        },
+      };
+
+      if keep {
+        kept.push(line);
+      } else if drops {
+        why::dropped(self.plugin.flag(), &format!("left out by '{command}'"), &line);
+      }
+    }
+
+    kept
+  }
+}
+
+impl Drop for PluginProcess {
+  fn drop(&mut self) {
+    // closing stdin tells the plugin there are no more events
+    self.sender.take();
+
+    if let Some(writer) = self.writer.take() {
+      let _ = writer.join();
+    }
+
+    let _ = self.child.wait();
+  }
+}
+
+/**
+ * Runs each event through a plugin, a batch at a time
+ */
+pub struct PluginStage {
+  src: Box<dyn Iterator<Item=Line>>,
+  process: PluginProcess,
+  batch: usize,
+  drops: bool,
+  pending: VecDeque<Line>,
+}
+
+impl PluginStage {
+  pub fn new(src: Box<dyn Iterator<Item=Line>>, plugin: &Plugin, batch: usize, drops: bool) -> PluginStage {
+    PluginStage { src, process: plugin.start(), batch, drops, pending: VecDeque::new() }
+  }
+}
+
+impl Iterator for PluginStage {
+  type Item = Line;
+
+  fn next(&mut self) -> Option<Line> {
+    loop {
+      if let Some(line) = self.pending.pop_front() {
+        return Some(line);
+      }
+
+      let lines: Vec<Line> = self.src.by_ref().take(self.batch).collect();
+
+      if lines.is_empty() {
+        return None;
+      }
+
+      self.pending.extend(self.process.apply(lines, self.drops));
+    }
+  }
+}
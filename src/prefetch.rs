@@ -0,0 +1,77 @@
+use tokio::runtime::Builder;
+use tokio::task::JoinSet;
+
+use crate::limits;
+use crate::log::LogFile;
+
+/// threads reading files at once. tokio reads files on its blocking pool, so this also bounds how many are open
+const READ_THREADS: usize = 8;
+
+/**
+ * Read every unopened source whole and at once, for --async-sources. Each read is a task rather than a thread, so
+ * thousands of small files take a handful of threads and only as many open files as there are threads reading them.
+ * The contents are kept in memory, compressed if they were, and merged exactly like any other source. In a container
+ * with a memory limit, only as many as fit in half of it are read this way, and the rest are opened like usual.
+ */
+pub fn load(sources: &mut [LogFile]) {
+  let mut budget = limits::memory_limit().map_or(u64::MAX, |limit| limit / 2);
+  let mut too_big = vec![];
+
+  let pending: Vec<_> = sources.iter()
+    .enumerate()
+    .filter(|(_, source)| source.is_unopened())
+    .filter_map(|(index, source)| Some((index, source.path()?.clone())))
+    .filter(|(index, path)| {
+      let size = path.metadata().map_or(0, |metadata| metadata.len());
+
+      if size > budget {
+        too_big.push(*index);
+        return false;
+      }
+
+      budget -= size;
+      true
+    })
+    .collect();
+
+  if !too_big.is_empty() {
+    eprintln!("Warning: {} files don't fit in half of the memory saw is limited to, so they're read as they're merged instead", too_big.len());
+
+    for index in too_big {
+      sources[index].open();
+    }
+  }
+
+  if pending.is_empty() {
+    return;
+  }
+
+  let runtime = Builder::new_multi_thread()
+    .worker_threads(1)
+    .max_blocking_threads(READ_THREADS)
+    .build()
+    .expect("Failed to start reading sources");
+
+  let contents = runtime.block_on(async move {
+    let mut tasks = JoinSet::new();
+
+    for (index, path) in pending {
+      tasks.spawn(async move { (index, tokio::fs::read(&path).await) });
+    }
+
+    let mut contents = vec![];
+
+    while let Some(done) = tasks.join_next().await {
+      contents.push(done.expect("Failed to read source file"));
+    }
+
+    contents
+  });
+
+  for (index, read) in contents {
+    let source = &mut sources[index];
+    let bytes = read.unwrap_or_else(|err| panic!("Failed to read file {}: {err}", source.name()));
+
+    source.load(bytes);
+  }
+}
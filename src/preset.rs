@@ -0,0 +1,89 @@
+use datetime::{ISO, LocalDateTime};
+use serde_json::{Map, Value};
+
+/**
+ * A built in description of a well known logging library's JSON layout.
+ *
+ * Presets normalize each event as it is read so that the rest of saw can rely on the usual
+ * 'time', 'message', 'level' and 'stack' fields.
+ */
+#[derive(Debug)]
+pub struct Preset {
+  pub name: &'static str,
+  /// true if 'time' is written as a number of milliseconds since the unix epoch
+  epoch_millis: bool,
+  /// (from, to) pairs, 'from' may be a dotted path into nested objects
+  renames: &'static [(&'static str, &'static str)],
+  pub pattern: &'static str,
+}
+
+const PRESETS: [Preset; 4] = [
+  Preset {
+    name: "pino",
+    epoch_millis: true,
+    renames: &[("msg", "message"), ("err.stack", "stack")],
+    pattern: "[%time] %level %message %prefix/\\n/%stack\\v/",
+  },
+  Preset {
+    name: "bunyan",
+    epoch_millis: false,
+    renames: &[("msg", "message"), ("err.stack", "stack")],
+    pattern: "[%time] %level %name: %message %prefix/\\n/%stack\\v/",
+  },
+  Preset {
+    name: "logrus",
+    epoch_millis: false,
+    renames: &[("msg", "message"), ("error", "stack")],
+    pattern: "[%time] %level %message %prefix/\\n/%stack\\v/",
+  },
+  Preset {
+    name: "logback",
+    epoch_millis: false,
+    renames: &[("@timestamp", "time"), ("stack_trace", "stack"), ("thread_name", "thread"), ("logger_name", "logger")],
+    pattern: "[%time] %level [%thread] %logger - %message %prefix/\\n/%stack\\v/",
+  },
+];
+
+impl Preset {
+  pub fn parse(raw: &str) -> Result<&'static Preset, String> {
+    PRESETS.iter()
+      .find(|preset| preset.name == raw)
+      .ok_or_else(|| {
+        let names: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+
+        format!("Unknown preset '{raw}'. Valid options are {}", names.join(", "))
+      })
+  }
+
+  pub fn normalize(&self, values: &mut Map<String, Value>) {
+    for (from, to) in self.renames {
+      if let Some(value) = Preset::take_path(values, from) {
+        values.insert(to.to_string(), value);
+      }
+    }
+
+    if self.epoch_millis {
+      if let Some(millis) = values.get("time").and_then(Value::as_i64) {
+        let time = LocalDateTime::at_ms(millis.div_euclid(1000), millis.rem_euclid(1000) as i16);
+
+        values.insert("time".to_string(), Value::String(time.iso().to_string()));
+      }
+    }
+  }
+
+  /**
+   * Remove and return the value found at a dotted path like 'err.stack'
+   */
+  fn take_path(values: &mut Map<String, Value>, path: &str) -> Option<Value> {
+    match path.split_once('.') {
+      Some((head, rest)) => {
+        if let Some(Value::Object(inner)) = values.get_mut(head) {
+          Preset::take_path(inner, rest)
+        } else {
+          None
+        }
+      }
+      None => values.remove(path),
+    }
+  }
+}
@@ -1,13 +1,20 @@
 use std::collections::HashMap;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::io::Write;
 use std::iter::Peekable;
 use std::str::Chars;
 use std::vec::IntoIter;
+use datetime::{ISO, LocalDateTime};
 use regex::Regex;
 
 use serde_json::{Map, Value};
 
+use crate::deterministic;
+use crate::functions;
+use crate::patterns;
 use crate::utils::ExtraIter;
+use crate::width;
 
 #[derive(Debug, Clone)]
 pub struct PrettyDescriptor {
@@ -27,7 +34,29 @@ enum PrettyFragment {
     regex: Regex,
     replacement: String,
     global: bool,
-  }
+  },
+  /// every field not in exclude, as key=value pairs. The exclude of %except is only what it lists, while %rest also
+  /// leaves out every field the pattern prints
+  Rest {
+    exclude: Vec<String>,
+    listed: bool,
+  },
+  /// a pattern function with its arguments filled in, or the argument a param of one stands for
+  Group(PrettyDescriptor),
+  /// base printed to fit a number of columns, by how wide it looks rather than how many bytes or chars it has
+  Fit {
+    base: PrettyDescriptor,
+    columns: usize,
+    fit: Fit,
+  },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Fit {
+  Pad,
+  PadLeft,
+  Truncate,
+  Wrap,
 }
 
 #[derive(Debug, Clone)]
@@ -59,56 +88,104 @@ pattern looks like this:
 
 if the variable is missing, an empty string will be used
  */
-
 impl PrettyDescriptor {
-  pub fn parse(pattern: &str) -> PrettyDescriptor {
-    let tokens = PrettyDescriptor::lex(pattern);
+  pub fn parse(pattern: &str) -> Result<PrettyDescriptor, String> {
+    let tokens = PrettyDescriptor::lex(pattern)?;
     let mut src = tokens.into_iter().peekable();
 
     let mut fragments = Vec::new();
 
-    while let Some(frag) = PrettyDescriptor::parse_expression(&mut src) {
+    while let Some(frag) = PrettyDescriptor::parse_expression(&mut src)? {
       fragments.push(frag);
     }
 
-    PrettyDescriptor { fragments }
+    let mut result = PrettyDescriptor { fragments };
+
+    // %rest needs to know every variable used anywhere in the pattern, so it can only be filled in once all of it is parsed
+    let mut referenced = Vec::new();
+    result.collect_variables(&mut referenced);
+    result.exclude_from_rest(&referenced);
+
+    Ok(result)
+  }
+
+  /**
+   * Every field the pattern prints by name, not counting %rest
+   */
+  pub fn variables(&self) -> Vec<String> {
+    let mut names = Vec::new();
+    self.collect_variables(&mut names);
+    names
+  }
+
+  fn collect_variables(&self, names: &mut Vec<String>) {
+    for frag in &self.fragments {
+      match frag {
+        PrettyFragment::Literal(_) | PrettyFragment::Rest { .. } => {}
+        PrettyFragment::Variable(name) => names.push(name.clone()),
+        PrettyFragment::Prefix { prefix, base } => {
+          prefix.collect_variables(names);
+          base.collect_variables(names);
+        }
+        PrettyFragment::Replace { base, .. } | PrettyFragment::Group(base) | PrettyFragment::Fit { base, .. } => base.collect_variables(names),
+      }
+    }
+  }
+
+  fn exclude_from_rest(&mut self, names: &[String]) {
+    for frag in &mut self.fragments {
+      match frag {
+        PrettyFragment::Literal(_) | PrettyFragment::Variable(_) => {}
+        PrettyFragment::Rest { exclude, listed: false } => exclude.extend_from_slice(names),
+        PrettyFragment::Rest { listed: true, .. } => {}
+        PrettyFragment::Prefix { prefix, base } => {
+          prefix.exclude_from_rest(names);
+          base.exclude_from_rest(names);
+        }
+        PrettyFragment::Replace { base, .. } | PrettyFragment::Group(base) | PrettyFragment::Fit { base, .. } => base.exclude_from_rest(names),
+      }
+    }
   }
 
-  fn parse_expression(src: &mut Peekable<IntoIter<PrettyToken>>) -> Option<PrettyFragment> {
+  fn parse_expression(src: &mut Peekable<IntoIter<PrettyToken>>) -> Result<Option<PrettyFragment>, String> {
     if let Some(next) = src.next() {
       let ans = match next {
         PrettyToken::Literal(lit) => PrettyFragment::Literal(lit),
         PrettyToken::Variable(name) => {
           if let Some(PrettyToken::Slash) = src.peek() {
             src.next();
-            PrettyDescriptor::parse_function(src, &name)
+            PrettyDescriptor::parse_function(src, &name)?
+          } else if name == "rest" {
+            PrettyFragment::Rest { exclude: Vec::new(), listed: false }
+          } else if let Some(argument) = functions::argument(&name) {
+            PrettyFragment::Group(argument)
           } else {
             PrettyFragment::Variable(name)
           }
         }
-        PrettyToken::Slash => panic!("Unexpected '/' found in pattern! Did you mean to escape it?"),
+        PrettyToken::Slash => return Err("Unexpected '/' found in pattern! Did you mean to escape it?".to_string()),
       };
 
-      Some(ans)
+      Ok(Some(ans))
     } else {
-      None
+      Ok(None)
     }
   }
 
-  fn parse_function(src: &mut Peekable<IntoIter<PrettyToken>>, name: &str) -> PrettyFragment {
-    match name {
+  fn parse_function(src: &mut Peekable<IntoIter<PrettyToken>>, name: &str) -> Result<PrettyFragment, String> {
+    let fragment = match name {
       "prefix" => {
-        let prefix = PrettyDescriptor::parse_pattern_argument(src);
-        let base = PrettyDescriptor::parse_pattern_argument(src);
+        let prefix = PrettyDescriptor::parse_pattern_argument(src)?;
+        let base = PrettyDescriptor::parse_pattern_argument(src)?;
 
         PrettyFragment::Prefix { prefix, base }
       }
       "replace" | "replaceAll" => {
-        let base = PrettyDescriptor::parse_pattern_argument(src);
-        let regex_pattern = PrettyDescriptor::parse_literal_argument(src);
-        let replacement = PrettyDescriptor::parse_literal_argument(src);
+        let base = PrettyDescriptor::parse_pattern_argument(src)?;
+        let regex_pattern = PrettyDescriptor::parse_literal_argument(src)?;
+        let replacement = PrettyDescriptor::parse_literal_argument(src)?;
 
-        let regex = Regex::new(&regex_pattern).expect("%regex pattern is invalid!");
+        let regex = patterns::regex(&regex_pattern, &format!("%{name} pattern"))?;
 
         PrettyFragment::Replace {
           base,
@@ -117,43 +194,105 @@ impl PrettyDescriptor {
           global: name == "replaceAll"
         }
       }
-      _ => panic!("Unknown function call in pattern! '{name}' is not a known function, see `saw --help pretty` for list of functions")
+      "pad" | "padLeft" | "truncate" | "wrap" => {
+        let base = PrettyDescriptor::parse_pattern_argument(src)?;
+        let raw = PrettyDescriptor::parse_literal_argument(src)?;
+
+        let columns = raw.trim().parse()
+          .map_err(|_| format!("Width of %{name} must be a whole number of columns, but was '{raw}'"))?;
+
+        let fit = match name {
+          "pad" => Fit::Pad,
+          "padLeft" => Fit::PadLeft,
+          "truncate" => Fit::Truncate,
+          _ => Fit::Wrap,
+        };
+
+        PrettyFragment::Fit { base, columns, fit }
+      }
+      // env and now are constant for the whole run, so they're resolved once here
+      "env" => {
+        let name = PrettyDescriptor::parse_literal_argument(src)?;
+
+        PrettyFragment::Literal(env::var(&name).unwrap_or_default())
+      }
+      "now" => {
+        let format = PrettyDescriptor::parse_literal_argument(src)?;
+
+        PrettyFragment::Literal(PrettyDescriptor::format_now(&format)?)
+      }
+      "except" => {
+        let fields = PrettyDescriptor::parse_literal_argument(src)?;
+
+        PrettyFragment::Rest {
+          exclude: fields.split(',').map(|field| field.trim().to_owned()).collect(),
+          listed: true,
+        }
+      }
+      _ => {
+        let function = functions::find(name)
+          .ok_or_else(|| format!("Unknown function call in pattern! '{name}' is not a known function, see `saw --help pretty` for list of functions"))?;
+
+        let arguments = (0..function.params()).map(|_| PrettyDescriptor::parse_pattern_argument(src)).collect::<Result<_, _>>()?;
+
+        PrettyFragment::Group(function.call(name, arguments)?)
+      }
+    };
+
+    Ok(fragment)
+  }
+
+  fn format_now(format: &str) -> Result<String, String> {
+    lazy_static! {
+      // LocalDateTime::now gets the milliseconds wrong, so go through the std clock instead
+      static ref NOW_MILLIS: i64 = deterministic::fixed_now()
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).expect("System clock is before 1970").as_millis() as i64);
+      static ref NOW: LocalDateTime = LocalDateTime::at_ms(NOW_MILLIS.div_euclid(1000), NOW_MILLIS.rem_euclid(1000) as i16);
+    }
+
+    match format {
+      "iso" => Ok(NOW.iso().to_string()),
+      "date" => Ok(NOW.date().iso().to_string()),
+      "time" => Ok(NOW.time().iso().to_string()),
+      "epoch" => Ok(NOW_MILLIS.div_euclid(1000).to_string()),
+      "epochMillis" => Ok(NOW_MILLIS.to_string()),
+      _ => Err(format!("Unknown %now format '{format}', valid options are iso, date, time, epoch, epochMillis")),
     }
   }
 
-  fn parse_pattern_argument(src: &mut Peekable<IntoIter<PrettyToken>>) -> PrettyDescriptor {
+  fn parse_pattern_argument(src: &mut Peekable<IntoIter<PrettyToken>>) -> Result<PrettyDescriptor, String> {
     let mut fragments = Vec::<PrettyFragment>::new();
 
     loop {
-      if let PrettyToken::Slash = src.peek().expect("Pattern contains unterminated function call") {
+      if let PrettyToken::Slash = src.peek().ok_or("Pattern contains unterminated function call")? {
         src.next();
-        return PrettyDescriptor{fragments};
+        return Ok(PrettyDescriptor{fragments});
       } else {
-        if let Some(frag) = PrettyDescriptor::parse_expression(src) {
+        if let Some(frag) = PrettyDescriptor::parse_expression(src)? {
           fragments.push(frag)
         } else {
-          panic!("Pattern contains unterminated function call")
+          return Err("Pattern contains unterminated function call".to_string())
         }
       }
     }
   }
 
-  fn parse_literal_argument(src: &mut Peekable<IntoIter<PrettyToken>>) -> String {
+  fn parse_literal_argument(src: &mut Peekable<IntoIter<PrettyToken>>) -> Result<String, String> {
     let regex_pattern = if let Some(PrettyToken::Literal(lit)) = src.next() {
       lit
     } else {
-      panic!("Expected string argument to function")
+      return Err("Expected string argument to function".to_string())
     };
 
     if let Some(PrettyToken::Slash) = src.next() {
     } else {
-      panic!("Function ended unexpectedly");
+      return Err("Function ended unexpectedly".to_string());
     }
 
-    return regex_pattern;
+    Ok(regex_pattern)
   }
 
-  fn lex(pattern: &str) -> Vec<PrettyToken> {
+  fn lex(pattern: &str) -> Result<Vec<PrettyToken>, String> {
     let mut tokens: Vec<PrettyToken> = vec![];
 
     let mut src = pattern.chars().peekable();
@@ -172,48 +311,50 @@ impl PrettyDescriptor {
         },
         _ => {
           let mut literal = String::new();
-          PrettyDescriptor::lex_literal(&mut src, &mut literal);
+          PrettyDescriptor::lex_literal(&mut src, &mut literal)?;
           tokens.push(PrettyToken::Literal(literal));
         }
       }
     }
 
-    return tokens;
+    Ok(tokens)
   }
 
   fn lex_identifier(src: &mut Peekable<Chars>, name: &mut String) {
-    while let Some(next @ ('a'..='z' | 'A'..='Z')) = src.peek() {
-      name.push(next.clone());
+    while let Some(next @ ('a'..='z' | 'A'..='Z' | '0'..='9' | '_')) = src.peek() {
+      name.push(*next);
       src.next();
     }
   }
 
-  fn lex_literal(src: &mut Peekable<Chars>, name: &mut String) {
+  fn lex_literal(src: &mut Peekable<Chars>, name: &mut String) -> Result<(), String> {
     while let Some(next) = src.peek() {
       match next {
         '\\' => {
           src.next(); // discard the slash
-          let follow = src.next().expect("Pattern cannot end with an unmatched '\\' character.");
+          let follow = src.next().ok_or("Pattern cannot end with an unmatched '\\' character.")?;
 
           if follow == 'v' {
-            return;
+            return Ok(());
           }
 
-          let found = ESCAPE_MAP.get(&follow).expect(&format!("Pattern contained unknown and invalid escape sequence '{follow}'"));
-          name.push(found.clone());
+          let found = ESCAPE_MAP.get(&follow).ok_or_else(|| format!("Pattern contained unknown and invalid escape sequence '{follow}'"))?;
+          name.push(*found);
         }
         '%' | '/' => {
-          return;
+          return Ok(());
         }
         _ => {
-          name.push(next.clone());
+          name.push(*next);
           src.next();
         }
       }
     }
+
+    Ok(())
   }
 
-  pub fn print<Writer: Write>(&self, values: &Map<String, Value>, target: &mut Writer) -> () {
+  pub fn print<Writer: Write>(&self, values: &Map<String, Value>, target: &mut Writer) {
     for frag in &self.fragments {
       match &frag {
         PrettyFragment::Literal(lit) => {
@@ -244,6 +385,35 @@ impl PrettyDescriptor {
 
           target.write_all(replaced.as_bytes()).expect("Failed to write")
         }
+        PrettyFragment::Rest { exclude, .. } => {
+          let rest = values.iter()
+            .filter(|(key, _)| !exclude.contains(key))
+            .join(" ", |(key, value)| {
+              let pretty = PrettyDescriptor::pretty_value(value);
+
+              // quote anything that would make the pairs ambiguous to read
+              if pretty.is_empty() || pretty.contains(|c: char| c.is_whitespace() || c == '=' || c == '"') {
+                format!("{key}={pretty:?}")
+              } else {
+                format!("{key}={pretty}")
+              }
+            });
+
+          target.write_all(rest.as_bytes()).expect("Failed to write")
+        }
+        PrettyFragment::Group(pattern) => pattern.print(values, target),
+        PrettyFragment::Fit { base, columns, fit } => {
+          let content = base.print_to_string(values);
+
+          let fitted = match fit {
+            Fit::Pad => width::pad(&content, *columns, false),
+            Fit::PadLeft => width::pad(&content, *columns, true),
+            Fit::Truncate => width::truncate(&content, *columns),
+            Fit::Wrap => width::wrap(&content, *columns),
+          };
+
+          target.write_all(fitted.as_bytes()).expect("Failed to write")
+        }
       };
     }
   }
@@ -257,14 +427,14 @@ impl PrettyDescriptor {
     String::from_utf8(out).unwrap()
   }
 
-  fn pretty_value(value: &Value) -> String {
+  pub fn pretty_value(value: &Value) -> String {
     match value {
       Value::String(str) => str.to_string(),
       Value::Number(num) => num.to_string(),
       Value::Null => "".to_string(),
       Value::Bool(b) => if *b { "true".to_string() } else { "false".to_string() },
       Value::Array(arr) => {
-        arr.iter().join(", ", |v| PrettyDescriptor::pretty_value(v))
+        arr.iter().join(", ", PrettyDescriptor::pretty_value)
       }
       Value::Object(obj) => {
         obj.iter().join(", ", | (k, v) | {
@@ -276,3 +446,73 @@ impl PrettyDescriptor {
 
 
 }
+
+/**
+ * A set of patterns picked between by the value of a single field, like a detailed one for ERROR and a short one for
+ * everything else. Events whose field matches none of them use the usual --pretty pattern.
+ */
+#[derive(Debug, Clone)]
+pub struct PrettyTheme {
+  field: String,
+  templates: Vec<(String, PrettyDescriptor)>,
+  default: Option<PrettyDescriptor>,
+}
+
+impl PrettyTheme {
+  pub fn new(field: String) -> PrettyTheme {
+    PrettyTheme {
+      field,
+      templates: vec![],
+      default: None,
+    }
+  }
+
+  pub fn field(&self) -> &str {
+    &self.field
+  }
+
+  /**
+   * Add a template in the form VALUE=PATTERN, where a VALUE of 'default' is used when nothing else matches
+   */
+  pub fn add(&mut self, raw: &str) -> Result<(), String> {
+    let (value, pattern) = raw.split_once('=')
+      .ok_or_else(|| format!("Template '{raw}' for --pretty-per must be in the form VALUE=PATTERN"))?;
+
+    let pattern = patterns::pretty(pattern)?;
+
+    if value == "default" {
+      self.default = Some(pattern);
+    } else {
+      self.templates.push((value.to_string(), pattern));
+    }
+
+    Ok(())
+  }
+
+  /**
+   * The field templates are picked by, and every field any template prints
+   */
+  pub fn variables(&self) -> Vec<String> {
+    let mut names = vec![self.field.clone()];
+
+    for (_, template) in &self.templates {
+      template.collect_variables(&mut names);
+    }
+
+    names
+  }
+
+  /**
+   * The 'default' template, which takes the place of the --pretty pattern
+   */
+  pub fn take_default(&mut self) -> Option<PrettyDescriptor> {
+    self.default.take()
+  }
+
+  pub fn select<'a>(&'a self, values: &Map<String, Value>, fallback: &'a PrettyDescriptor) -> &'a PrettyDescriptor {
+    let value = values.get(&self.field).map(PrettyDescriptor::pretty_value);
+
+    value.and_then(|value| self.templates.iter().find(|(expected, _)| *expected == value))
+      .map_or(fallback, |(_, pattern)| pattern)
+  }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde_json::{Map, Value};
+
+use crate::provenance::PROVENANCE_KEY;
+
+/**
+ * Only the top level fields of each event that are needed, from --parse-only FIELDS. JSON lines are scanned for these
+ * and everything else is skipped over without being built, which is most of the work of reading wide events that are
+ * mostly thrown away. The rest are never read, so they aren't written either. 'time' is always read, and so is saw's
+ * own provenance record, so the events still merge and earlier headers are still skipped
+ */
+#[derive(Debug)]
+pub struct Projection {
+  raw: String,
+  fields: HashSet<String>,
+}
+
+impl Projection {
+  pub fn parse(raw: &str) -> Result<Projection, String> {
+    let mut fields: HashSet<String> = raw.split(',')
+      .map(str::trim)
+      .filter(|field| !field.is_empty())
+      .map(str::to_string)
+      .collect();
+
+    if fields.is_empty() {
+      return Err("Argument --parse-only must be followed by a comma separated list of fields, like time,level,message".to_string());
+    }
+
+    fields.insert("time".to_string());
+    fields.insert(PROVENANCE_KEY.to_string());
+
+    Ok(Projection { raw: raw.to_string(), fields })
+  }
+
+  /**
+   * Read this field too, for one saw needs whatever was asked for, like --time-field. A dotted path reads the whole
+   * top level field it starts in
+   */
+  pub fn include(&mut self, field: &str) {
+    let top = field.split('.').next().unwrap_or(field);
+
+    self.fields.insert(top.to_string());
+  }
+
+  pub fn describe(&self) -> String {
+    format!("only {} read from each event", self.raw)
+  }
+
+  /**
+   * Read just the fields wanted from a line of JSON, or None if it isn't a JSON object. Fields that are skipped are
+   * still checked, so a line that is broken anywhere is just as invalid as when it's read whole
+   */
+  pub fn read(&self, raw: &str) -> Option<Map<String, Value>> {
+    let mut deserializer = serde_json::Deserializer::from_str(raw);
+    let map = self.deserialize(&mut deserializer).ok()?;
+
+    deserializer.end().ok()?;
+
+    Some(map)
+  }
+
+  /**
+   * Drop the fields that aren't wanted from an event that was read some other way, like logfmt
+   */
+  pub fn retain(&self, values: &mut Map<String, Value>) {
+    values.retain(|key, _| self.fields.contains(key));
+  }
+}
+
+impl<'de> DeserializeSeed<'de> for &Projection {
+  type Value = Map<String, Value>;
+
+  fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+    deserializer.deserialize_map(self)
+  }
+}
+
+impl<'de> Visitor<'de> for &Projection {
+  type Value = Map<String, Value>;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("a JSON object")
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+    let mut values = Map::new();
+
+    while let Some(key) = access.next_key::<String>()? {
+      if self.fields.contains(&key) {
+        values.insert(key, access.next_value()?);
+      } else {
+        access.next_value::<IgnoredAny>()?;
+      }
+    }
+
+    Ok(values)
+  }
+}
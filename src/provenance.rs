@@ -0,0 +1,58 @@
+use std::env;
+
+use datetime::{ISO, LocalDateTime};
+use serde_json::{json, Value};
+
+use crate::chunk::LogWriter;
+use crate::log::Line;
+
+/// the key that marks a record as saw's own metadata rather than an event
+pub const PROVENANCE_KEY: &str = "_saw";
+
+/**
+ * Tracks what was written, so the output can end with a record describing it
+ */
+#[derive(Debug, Default)]
+pub struct Provenance {
+  events: u64,
+  first: Option<LocalDateTime>,
+  last: Option<LocalDateTime>,
+}
+
+impl Provenance {
+  pub fn add(&mut self, line: &Line) {
+    self.events += 1;
+
+    if let Some(time) = line.time {
+      self.first = Some(self.first.map_or(time, |first| first.min(time)));
+      self.last = Some(self.last.map_or(time, |last| last.max(time)));
+    }
+  }
+
+  pub fn write_header(target: &mut Box<dyn LogWriter>, sources: &[String]) {
+    let header = json!({
+      PROVENANCE_KEY: "header",
+      "version": env!("CARGO_PKG_VERSION"),
+      "command": env::args().collect::<Vec<String>>(),
+      "sources": sources,
+    });
+
+    Provenance::write_record(target, &header);
+  }
+
+  pub fn write_footer(&self, target: &mut Box<dyn LogWriter>) {
+    let footer = json!({
+      PROVENANCE_KEY: "footer",
+      "events": self.events,
+      "first": self.first.map(|time| time.iso().to_string()),
+      "last": self.last.map(|time| time.iso().to_string()),
+    });
+
+    Provenance::write_record(target, &footer);
+  }
+
+  fn write_record(target: &mut Box<dyn LogWriter>, record: &Value) {
+    serde_json::to_writer(&mut *target, record).expect("Failed to write line");
+    target.end_line();
+  }
+}
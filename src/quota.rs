@@ -0,0 +1,115 @@
+use serde_json::{Map, Value};
+
+use crate::filter::{Expression, FilterSet};
+
+/**
+ * A budget of events for each of a few filters, for --quota "FILTER:N,FILTER:N", so a triage digest gets some of
+ * everything asked for rather than whatever happens to come first. An event goes to the first bucket whose filter
+ * it matches, and is kept while that bucket has room. Events matching no bucket are left out, and once every bucket
+ * is full nothing more is read.
+ */
+#[derive(Debug)]
+pub struct Quota {
+  buckets: Vec<Bucket>,
+  /// events that matched no bucket
+  unmatched: u64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+  raw: String,
+  filter: Expression,
+  limit: u64,
+  kept: u64,
+  /// events that matched once the bucket was already full
+  over: u64,
+}
+
+impl Quota {
+  /**
+   * Parse a comma separated list of FILTER:N. Filters may hold commas themselves, so a piece only ends a bucket
+   * when it ends in :N
+   */
+  pub fn parse(raw: &str) -> Result<Quota, String> {
+    let mut buckets = vec![];
+    let mut pending = String::new();
+
+    for piece in raw.split(',') {
+      if !pending.is_empty() {
+        pending.push(',');
+      }
+
+      pending.push_str(piece);
+
+      let Some((filter, limit)) = pending.rsplit_once(':') else {
+        continue;
+      };
+
+      let Ok(limit) = limit.trim().parse::<u64>() else {
+        continue;
+      };
+
+      if filter.trim().is_empty() {
+        return Err(format!("Argument --quota '{raw}' has a bucket of {limit} without a FILTER"));
+      }
+
+      buckets.push(Bucket {
+        raw: filter.trim().to_string(),
+        filter: FilterSet::parse_expression(filter.trim())?,
+        limit,
+        kept: 0,
+        over: 0,
+      });
+
+      pending.clear();
+    }
+
+    if !pending.trim().is_empty() || buckets.is_empty() {
+      return Err(format!("Argument --quota '{raw}' must be a comma separated list of FILTER:N, like \"%level=ERROR:100,%level=WARN:20\""));
+    }
+
+    Ok(Quota { buckets, unmatched: 0 })
+  }
+
+  pub fn describe(&self) -> String {
+    let buckets: Vec<String> = self.buckets.iter().map(|bucket| format!("{} of {}", bucket.limit, bucket.raw)).collect();
+
+    buckets.join(", ")
+  }
+
+  /**
+   * Should this event be kept? Counts it against the first bucket it matches
+   */
+  pub fn admit(&mut self, values: &Map<String, Value>) -> bool {
+    let Some(bucket) = self.buckets.iter_mut().find(|bucket| bucket.filter.matches(values)) else {
+      self.unmatched += 1;
+      return false;
+    };
+
+    if bucket.kept >= bucket.limit {
+      bucket.over += 1;
+      return false;
+    }
+
+    bucket.kept += 1;
+    true
+  }
+
+  /// true once no more events can be kept, so there's no need to read any more
+  pub fn full(&self) -> bool {
+    self.buckets.iter().all(|bucket| bucket.kept >= bucket.limit)
+  }
+
+  /**
+   * Print how many events each bucket kept and left out to stderr
+   */
+  pub fn report(&self) {
+    let stopped = if self.full() { ", and stopped reading once every bucket was full" } else { "" };
+
+    eprintln!("Quota kept {} events, left out {} that matched no bucket{stopped}", self.buckets.iter().map(|bucket| bucket.kept).sum::<u64>(), self.unmatched);
+
+    for bucket in &self.buckets {
+      eprintln!("  {}: kept {} of {}, left out {} more", bucket.raw, bucket.kept, bucket.limit, bucket.over);
+    }
+  }
+}
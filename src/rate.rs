@@ -0,0 +1,133 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// the slowest rate allowed, one event an hour, so the wait for a token is never more than an hour
+const SLOWEST: f64 = 1.0 / 3600.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RatePolicy {
+  /// wait until the event can be written
+  Block,
+  /// throw away events that come too quickly
+  Drop,
+}
+
+/**
+ * A token bucket limiting how quickly events are written. Up to 'burst' events can be written at once, after which
+ * they are let through at 'per_second'.
+ */
+#[derive(Debug)]
+pub struct RateLimit {
+  raw: String,
+  per_second: f64,
+  burst: f64,
+  policy: RatePolicy,
+  tokens: f64,
+  last: Instant,
+  dropped: u64,
+}
+
+impl RateLimit {
+  /**
+   * Parse a limit in the form RATE/UNIT[,burst=N][,drop|block], for example 1000/s,burst=5000,drop
+   */
+  pub fn parse(raw: &str) -> Result<RateLimit, String> {
+    let mut parts = raw.split(',');
+
+    let rate = parts.next().unwrap_or("");
+    let (count, unit) = rate.split_once('/').unwrap_or((rate, "s"));
+
+    let count: f64 = count.trim().parse()
+      .map_err(|_| format!("Rate limit '{raw}' must start with a number of events like 1000/s"))?;
+
+    let per_second = match unit {
+      "s" => count,
+      "m" => count / 60.0,
+      "h" => count / 3600.0,
+      _ => return Err(format!("Rate limit '{raw}' must be per s, m or h")),
+    };
+
+    // NaN fails every comparison, so it has to be ruled out on its own
+    if !per_second.is_finite() || per_second < SLOWEST {
+      return Err(format!("Rate limit '{raw}' must be a number of events that allows at least 1/h through"));
+    }
+
+    let mut burst = per_second.max(1.0);
+    let mut policy = RatePolicy::Block;
+
+    for part in parts {
+      match part.trim() {
+        "drop" => policy = RatePolicy::Drop,
+        "block" => policy = RatePolicy::Block,
+        option => {
+          let size = option.strip_prefix("burst=")
+            .ok_or_else(|| format!("Rate limit option '{option}' is not recognized. Valid options are burst=N, drop and block"))?;
+
+          burst = size.parse()
+            .map_err(|_| format!("Rate limit burst '{size}' must be a number"))?;
+
+          if !burst.is_finite() || burst < 1.0 {
+            return Err("Rate limit burst must be a number that's at least 1".to_string());
+          }
+        }
+      }
+    }
+
+    Ok(RateLimit {
+      raw: raw.to_string(),
+      per_second,
+      burst,
+      policy,
+      tokens: burst,
+      last: Instant::now(),
+      dropped: 0,
+    })
+  }
+
+  /**
+   * Take a token for the next event, returning false if it should be dropped
+   */
+  pub fn admit(&mut self) -> bool {
+    self.refill();
+
+    if self.tokens < 1.0 {
+      match self.policy {
+        RatePolicy::Drop => {
+          self.dropped += 1;
+          return false;
+        }
+        RatePolicy::Block => {
+          let wait = Duration::try_from_secs_f64((1.0 - self.tokens) / self.per_second)
+            .unwrap_or(Duration::from_secs_f64(1.0 / SLOWEST));
+
+          sleep(wait);
+          self.refill();
+        }
+      }
+    }
+
+    self.tokens -= 1.0;
+    true
+  }
+
+  fn refill(&mut self) {
+    let now = Instant::now();
+    let elapsed = now.duration_since(self.last).as_secs_f64();
+
+    self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+    self.last = now;
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{}, burst of {}, {:?} when over", self.raw, self.burst, self.policy)
+  }
+
+  /// whether events over the limit are thrown away, which depends on how fast they're read
+  pub fn drops(&self) -> bool {
+    self.policy == RatePolicy::Drop
+  }
+
+  pub fn dropped(&self) -> u64 {
+    self.dropped
+  }
+}
@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use glob::Pattern;
+
+/// most bytes of a line shown by --debug-raw, the rest are only counted
+const DUMP_LIMIT: usize = 512;
+
+/// bytes on each row of a hexdump
+const ROW: usize = 16;
+
+/// where the lines of a source are copied to, shared by every source copied to the same file
+pub type RawSink = Arc<Mutex<BufWriter<File>>>;
+
+/**
+ * Copies every line read from the sources matching a glob to a file, byte for byte as the parser saw them, for
+ * --copy-raw SOURCE=PATH. Compressed sources are copied after decompressing, and lines that fail to parse are copied
+ * too, so the copy can be passed around to reproduce a problem without the rest of the logs
+ */
+#[derive(Debug)]
+pub struct RawCopy {
+  pattern: Pattern,
+  path: PathBuf,
+}
+
+impl RawCopy {
+  pub fn parse(raw: &str) -> Result<RawCopy, String> {
+    let (glob, path) = raw.split_once('=')
+      .filter(|(glob, path)| !glob.is_empty() && !path.is_empty())
+      .ok_or_else(|| format!("Argument --copy-raw '{raw}' must be in the form SOURCE=PATH, like 'app.log=raw.log'"))?;
+
+    let pattern = Pattern::new(glob).map_err(|_| format!("Copy source '{glob}' is not a valid glob"))?;
+
+    Ok(RawCopy { pattern, path: PathBuf::from(path) })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("lines of '{}' copied to {}", self.pattern, self.path.display())
+  }
+
+  /**
+   * Does this copy take the source with this name? Matched against the name as given or just its file name
+   */
+  pub fn matches(&self, name: &str) -> bool {
+    let path = Path::new(name);
+    let file_name = path.file_name().map(Path::new);
+
+    self.pattern.matches_path(path) || file_name.is_some_and(|file_name| self.pattern.matches_path(file_name))
+  }
+
+  pub fn open(&self) -> RawSink {
+    let file = File::create(&self.path).unwrap_or_else(|err| panic!("Failed to create --copy-raw file {}: {err}", self.path.display()));
+
+    Arc::new(Mutex::new(BufWriter::new(file)))
+  }
+}
+
+/**
+ * Write what was read to a copy, exactly as it was
+ */
+pub fn copy(sink: &RawSink, bytes: &[u8]) {
+  sink.lock().expect("Raw copy was poisoned").write_all(bytes).expect("Failed to write --copy-raw file");
+}
+
+pub fn flush(sink: &RawSink) {
+  sink.lock().expect("Raw copy was poisoned").flush().expect("Failed to write --copy-raw file");
+}
+
+/**
+ * The bytes of a line as offset, hex and printable ASCII, like hexdump -C, for --debug-raw
+ */
+pub fn hexdump(bytes: &[u8]) -> String {
+  let mut dump = String::new();
+
+  for (row, chunk) in bytes[..bytes.len().min(DUMP_LIMIT)].chunks(ROW).enumerate() {
+    dump.push_str(&format!("  {:08x} ", row * ROW));
+
+    for index in 0..ROW {
+      if index == ROW / 2 {
+        dump.push(' ');
+      }
+
+      match chunk.get(index) {
+        Some(byte) => dump.push_str(&format!(" {byte:02x}")),
+        None => dump.push_str("   "),
+      }
+    }
+
+    let text: String = chunk.iter().map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' }).collect();
+    dump.push_str(&format!("  |{text}|\n"));
+  }
+
+  if bytes.len() > DUMP_LIMIT {
+    dump.push_str(&format!("  and {} more bytes\n", bytes.len() - DUMP_LIMIT));
+  }
+
+  dump
+}
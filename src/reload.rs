@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde_json::Value;
+
+use crate::filter::FilterSet;
+use crate::patterns;
+use crate::pretty::PrettyDescriptor;
+use crate::translate::Translation;
+
+/// how often the file is looked at, at most. It's only looked at when an event comes in, since that's when it matters
+const CHECK_EVERY: Duration = Duration::from_secs(1);
+
+/**
+ * The filters, translations and pretty template of a --follow run, which --reload FILE swaps for what FILE says
+ * whenever it changes. FILE is JSON, and each key it has replaces what the command line gave:
+ *   {"filter": ["%level=ERROR", "%durationMs>500"], "translate": [["user", "%user_id"]], "pretty": "[%time] %message"}
+ *
+ * A file that can't be read or has a mistake in it is reported and the settings from before are kept, so a typo
+ * doesn't end a capture that has been running for hours.
+ */
+#[derive(Debug)]
+pub struct LiveConfig {
+  path: PathBuf,
+  checked: Option<Instant>,
+  modified: Option<SystemTime>,
+  pub filter: Option<FilterSet>,
+  pub translations: Vec<Translation>,
+  pub pretty: Option<PrettyDescriptor>,
+}
+
+/// what a reload read from the file, with None for each key it didn't have
+type Settings = (Option<Option<FilterSet>>, Option<Vec<Translation>>, Option<PrettyDescriptor>);
+
+impl LiveConfig {
+  pub fn new(path: PathBuf, filter: Option<FilterSet>, translations: Vec<Translation>, pretty: Option<PrettyDescriptor>) -> LiveConfig {
+    let mut live = LiveConfig { path, checked: None, modified: None, filter, translations, pretty };
+
+    live.refresh();
+    live
+  }
+
+  /**
+   * Load the file again if it has changed since it was last loaded
+   */
+  pub fn refresh(&mut self) {
+    if self.checked.is_some_and(|checked| checked.elapsed() < CHECK_EVERY) {
+      return;
+    }
+
+    self.checked = Some(Instant::now());
+
+    let Ok(modified) = fs::metadata(&self.path).and_then(|metadata| metadata.modified()) else {
+      return;
+    };
+
+    if self.modified == Some(modified) {
+      return;
+    }
+
+    self.modified = Some(modified);
+
+    match self.read() {
+      Ok((filter, translations, pretty)) => {
+        if let Some(mut filter) = filter {
+          // --ignore-pack is only ever given on the command line, so the noise stays ignored whatever FILE says
+          if let Some(ignore) = self.filter.as_mut().and_then(|old| old.ignore.take()) {
+            filter.get_or_insert_with(FilterSet::default).ignore = Some(ignore);
+          }
+
+          self.filter = filter;
+        }
+
+        if let Some(translations) = translations {
+          self.translations = translations;
+        }
+
+        if pretty.is_some() {
+          self.pretty = pretty;
+        }
+
+        eprintln!("Reloaded '{}'", self.path.display());
+      }
+      Err(err) => eprintln!("Failed to reload '{}', keeping the settings from before: {err}", self.path.display()),
+    }
+  }
+
+  fn read(&self) -> Result<Settings, String> {
+    let raw = fs::read_to_string(&self.path).map_err(|err| err.to_string())?;
+    let config: Value = serde_json::from_str(&raw).map_err(|err| err.to_string())?;
+
+    let filter = match config.get("filter") {
+      None => None,
+      Some(Value::Array(filters)) => {
+        let mut set: Option<FilterSet> = None;
+
+        for raw in filters {
+          let raw = raw.as_str().ok_or("'filter' must be a list of filters")?;
+
+          set.get_or_insert_with(FilterSet::default).push(FilterSet::parse_expression(raw)?);
+        }
+
+        if let Some(set) = &mut set {
+          set.optimize();
+        }
+
+        Some(set)
+      }
+      Some(_) => return Err("'filter' must be a list of filters".to_string()),
+    };
+
+    let translations = match config.get("translate") {
+      None => None,
+      Some(Value::Array(translations)) => Some(translations.iter()
+        .map(|translation| match translation.as_array().map(Vec::as_slice) {
+          Some([Value::String(target), Value::String(pattern)]) => Translation::parse(target.clone(), pattern),
+          _ => Err("'translate' must be a list of [TARGET_FIELD, PATTERN] pairs".to_string()),
+        })
+        .collect::<Result<Vec<Translation>, String>>()?),
+      Some(_) => return Err("'translate' must be a list of [TARGET_FIELD, PATTERN] pairs".to_string()),
+    };
+
+    let pretty = match config.get("pretty") {
+      None => None,
+      Some(Value::String(pattern)) => Some(patterns::pretty(pattern)?),
+      Some(_) => return Err("'pretty' must be a pattern".to_string()),
+    };
+
+    Ok((filter, translations, pretty))
+  }
+}
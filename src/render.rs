@@ -0,0 +1,171 @@
+use std::fs;
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use serde_json::{Map, Value};
+
+use crate::functions;
+use crate::levels::LevelMap;
+use crate::log::{Aggregator, LogFile, ReadOptions};
+use crate::patterns;
+use crate::preset::Preset;
+use crate::pretty::{PrettyDescriptor, PrettyTheme};
+
+pub const RENDER_HELP: &str = r#"
+Usage:
+  saw render [--sample FILE] [--preset NAME] [--pretty-per FIELD VALUE=PATTERN] [--pattern-functions FILE] [--check SNAPSHOT] PATTERN
+
+Renders PATTERN, in the same form as --pretty, against every event in the sample FILE and prints the result. Without
+--sample a few built in events are used, with an INFO, a WARN and an ERROR with a stack. --pretty-per can be given
+like it is for saw, and a 'default' template takes the place of PATTERN. --pattern-functions reads functions for the
+patterns like it does for saw, wherever it is given.
+
+With --check the output is compared to the SNAPSHOT file instead of printed. saw exits with code 1 and shows the first
+line that differs if they don't match, so templates can be checked in CI. Write a snapshot with:
+  saw render --sample sample.log '[%time] %message' > sample.snap
+"#;
+
+/// used when no --sample is given
+const BUILT_IN_SAMPLE: &str = r#"{"time": "2022-03-13T10:00:00.000", "level": "INFO", "thread": "main", "logger": "com.example.App", "message": "Application started", "user": "E455705"}
+{"time": "2022-03-13T10:01:00.000", "level": "WARN", "thread": "worker-1", "logger": "com.example.Pool", "message": "Pool is 90% full", "size": 90}
+{"time": "2022-03-13T10:02:00.000", "level": "ERROR", "thread": "worker-2", "logger": "com.example.Handler", "message": "Request failed", "stack": "java.lang.NullPointerException\n\tat com.example.Handler.handle(Handler.java:42)"}
+"#;
+
+/**
+ * saw render [--sample FILE] [--preset NAME] [--pretty-per FIELD VALUE=PATTERN] [--pattern-functions FILE] [--check SNAPSHOT] PATTERN
+ */
+pub fn run<Args: Iterator<Item=String>>(src: Args) {
+  let mut sample: Option<PathBuf> = None;
+  let mut preset = None;
+  let mut theme: Option<PrettyTheme> = None;
+  let mut check: Option<PathBuf> = None;
+  let mut pattern: Option<PrettyDescriptor> = None;
+
+  // functions have to be known before any pattern that calls them is parsed
+  let raw: Vec<String> = src.collect();
+  let files: Vec<&String> = raw.windows(2).filter(|pair| pair[0] == "--pattern-functions").map(|pair| &pair[1]).collect();
+
+  if files.is_empty() {
+    functions::load_default().unwrap_or_else(|err| panic!("{err}"));
+  }
+
+  for file in files {
+    functions::load(Path::new(file)).unwrap_or_else(|err| panic!("{err}"));
+  }
+
+  let mut src = raw.iter().cloned();
+
+  while let Some(next) = src.next() {
+    match next.as_ref() {
+      "-h" | "--help" => {
+        eprintln!("{RENDER_HELP}");
+        return;
+      }
+      "--sample" => {
+        sample = Some(src.next().expect("Argument --sample must be followed by a FILE").into());
+      }
+      "--preset" => {
+        let raw = src.next().expect("Argument --preset must be followed by a NAME");
+
+        preset = Some(Preset::parse(&raw).unwrap_or_else(|err| panic!("{err}")));
+      }
+      "--pretty-per" => {
+        let field = src.next().expect("Argument --pretty-per must be followed by a FIELD and then a VALUE=PATTERN argument");
+        let template = src.next().expect("Argument --pretty-per FIELD must be followed by a VALUE=PATTERN argument");
+
+        let theme = theme.get_or_insert_with(|| PrettyTheme::new(field.clone()));
+
+        if theme.field() != field {
+          panic!("Argument --pretty-per can only pick templates by one field, but was given both '{}' and '{field}'", theme.field());
+        }
+
+        theme.add(&template).unwrap_or_else(|err| panic!("{err}"));
+      }
+      "--pattern-functions" => {
+        src.next().expect("Argument --pattern-functions must be followed by a FILE");
+      }
+      "--check" => {
+        check = Some(src.next().expect("Argument --check must be followed by a SNAPSHOT file").into());
+      }
+      _ if next.starts_with('-') => {
+        panic!("Unknown property '{next}' for saw render. Run saw render --help to see all known properties");
+      }
+      _ => {
+        if pattern.is_some() {
+          panic!("saw render takes only one PATTERN, but was given another: '{next}'");
+        }
+
+        pattern = Some(patterns::pretty(&next).unwrap_or_else(|err| panic!("{err}")));
+      }
+    }
+  }
+
+  let default = theme.as_mut().and_then(PrettyTheme::take_default);
+
+  let pattern = match (pattern, default) {
+    (Some(_), Some(_)) => panic!("Cannot pass both a PATTERN and a 'default' template for --pretty-per, as both set the pattern for everything else"),
+    (Some(pattern), None) | (None, Some(pattern)) => pattern,
+    (None, None) => panic!("saw render needs a PATTERN to render. Run saw render --help for more information"),
+  };
+
+  let source = match &sample {
+    Some(path) => LogFile::from_file(path),
+    None => LogFile::from_reader(Box::new(BufReader::new(Cursor::new(BUILT_IN_SAMPLE))), "<built in sample>"),
+  };
+
+  // events are rendered in the order they're written, even without a time, just like a template sees them
+  let options = ReadOptions { preset, levels: Some(LevelMap::standard()), keep_order: true, ..ReadOptions::default() };
+
+  let mut rendered = String::new();
+
+  for line in Aggregator::new(vec![source], options) {
+    rendered.push_str(&render(&line.value, &pattern, theme.as_ref()));
+    rendered.push('\n');
+  }
+
+  match check {
+    None => print!("{rendered}"),
+    Some(snapshot) => {
+      let expected = fs::read_to_string(&snapshot)
+        .unwrap_or_else(|_| panic!("Failed to read snapshot {}", snapshot.display()));
+
+      if let Some(difference) = first_difference(&expected, &rendered) {
+        eprintln!("Rendered output does not match snapshot {}\n{difference}", snapshot.display());
+        exit(1);
+      }
+
+      eprintln!("Rendered output matches snapshot {}", snapshot.display());
+    }
+  }
+}
+
+fn render(values: &Map<String, Value>, pattern: &PrettyDescriptor, theme: Option<&PrettyTheme>) -> String {
+  match theme {
+    Some(theme) => theme.select(values, pattern).print_to_string(values),
+    None => pattern.print_to_string(values),
+  }
+}
+
+/**
+ * Describe the first line that differs between the snapshot and the output, or None if they match
+ */
+fn first_difference(expected: &str, actual: &str) -> Option<String> {
+  let mut expected_lines = expected.lines();
+  let mut actual_lines = actual.lines();
+  let mut number = 1;
+
+  loop {
+    match (expected_lines.next(), actual_lines.next()) {
+      (None, None) => return None,
+      (expected, actual) if expected != actual => {
+        return Some(format!(
+          "line {number}:\n  expected: {}\n  actual:   {}",
+          expected.unwrap_or("<end of snapshot>"),
+          actual.unwrap_or("<end of output>"),
+        ));
+      }
+      _ => number += 1,
+    }
+  }
+}
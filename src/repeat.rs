@@ -0,0 +1,85 @@
+use std::io::Write;
+
+use datetime::LocalDateTime;
+
+use crate::chunk::LogWriter;
+
+/**
+ * Prints a line that is exactly the same as the one before it only once, for --collapse-repeats, and then the
+ * number of times it was repeated once a different line comes or the output ends, like syslog does:
+ *   [2024-06-01T10:00:00] connection refused
+ *   last message repeated 41 times
+ *
+ * This only looks at what's printed, so events that differ in a field the pattern leaves out are collapsed too
+ */
+pub struct RepeatWriter {
+  inner: Box<dyn LogWriter>,
+  /// the line being written, until its end shows whether it's a repeat
+  line: Vec<u8>,
+  time: Option<LocalDateTime>,
+  last: Option<Vec<u8>>,
+  /// the time of the last repeat, to write the count under
+  last_time: Option<LocalDateTime>,
+  repeats: u64,
+}
+
+impl RepeatWriter {
+  pub fn new(inner: Box<dyn LogWriter>) -> RepeatWriter {
+    RepeatWriter { inner, line: vec![], time: None, last: None, last_time: None, repeats: 0 }
+  }
+
+  fn write_repeats(&mut self) {
+    if self.repeats == 0 {
+      return;
+    }
+
+    let plural = if self.repeats == 1 { "" } else { "s" };
+
+    self.inner.start_event(self.last_time);
+    write!(self.inner, "last message repeated {} time{plural}", self.repeats).expect("Failed to write line");
+    self.inner.end_line();
+    self.repeats = 0;
+  }
+}
+
+impl Write for RepeatWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.line.extend_from_slice(buf);
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+impl LogWriter for RepeatWriter {
+  fn start_event(&mut self, time: Option<LocalDateTime>) {
+    self.time = time;
+  }
+
+  fn end_line(&mut self) {
+    let line = std::mem::take(&mut self.line);
+
+    if self.last.as_ref() == Some(&line) {
+      self.repeats += 1;
+      self.last_time = self.time;
+      return;
+    }
+
+    self.write_repeats();
+
+    self.inner.start_event(self.time);
+    self.inner.write_all(&line).expect("Failed to write line");
+    self.inner.end_line();
+
+    self.last = Some(line);
+  }
+}
+
+impl Drop for RepeatWriter {
+  fn drop(&mut self) {
+    self.write_repeats();
+  }
+}
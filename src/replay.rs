@@ -0,0 +1,112 @@
+use std::io::Write;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use datetime::LocalDateTime;
+use serde_json::{Map, Value};
+
+use crate::chunk::LogWriter;
+
+/**
+ * Paces events by their times, so a file of old events is written with the same gaps between them as when they
+ * happened, or scaled by 'speed'. The first event is written straight away and the rest wait for their turn.
+ */
+#[derive(Debug)]
+pub struct Replay {
+  raw: String,
+  speed: f64,
+  /// when the first event was written, and its time in milliseconds
+  start: Option<(Instant, i64)>,
+}
+
+impl Replay {
+  /**
+   * Parse a speed like 1x, 10x, 0.5x or realtime, which is the same as 1x
+   */
+  pub fn parse(raw: &str) -> Result<Replay, String> {
+    let speed: f64 = match raw {
+      "realtime" => 1.0,
+      _ => raw.strip_suffix('x').unwrap_or(raw).parse()
+        .map_err(|_| format!("Replay speed '{raw}' must be a multiple like 1x, 10x or 0.5x, or realtime"))?,
+    };
+
+    if !speed.is_finite() || speed <= 0.0 {
+      return Err(format!("Replay speed '{raw}' must be greater than 0"));
+    }
+
+    Ok(Replay { raw: raw.to_string(), speed, start: None })
+  }
+
+  /**
+   * Wait until the event with this time is due. Events without a time, or earlier than one already written, aren't held
+   */
+  pub fn pace(&mut self, time: Option<LocalDateTime>) {
+    let Some(time) = time else {
+      return;
+    };
+
+    let millis = millis(time);
+
+    let Some((started, first)) = self.start else {
+      self.start = Some((Instant::now(), millis));
+      return;
+    };
+
+    let due = started + Duration::from_secs_f64((millis - first).max(0) as f64 / 1000.0 / self.speed);
+    let now = Instant::now();
+
+    if due > now {
+      sleep(due - now);
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    if self.speed == 1.0 {
+      format!("{}, as fast as the events happened", self.raw)
+    } else {
+      format!("{}, {} times as fast as the events happened", self.raw, self.speed)
+    }
+  }
+}
+
+/**
+ * Flushes after every event, so paced events reach whatever is reading them when they're due rather than whenever
+ * the buffer fills
+ */
+pub struct LiveWriter(pub Box<dyn LogWriter>);
+
+impl Write for LiveWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.0.write(buf)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.0.flush()
+  }
+}
+
+impl LogWriter for LiveWriter {
+  fn route_event(&mut self, values: &Map<String, Value>) {
+    self.0.route_event(values);
+  }
+
+  fn start_event(&mut self, time: Option<LocalDateTime>) {
+    self.0.start_event(time);
+  }
+
+  fn end_line(&mut self) {
+    self.0.end_line();
+    self.0.flush().expect("Failed to flush output");
+  }
+
+  fn end_frame(&mut self) {
+    self.0.end_frame();
+    self.0.flush().expect("Failed to flush output");
+  }
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
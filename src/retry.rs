@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+
+static POLICY: RwLock<RetryPolicy> = RwLock::new(RetryPolicy::DEFAULT);
+
+/**
+ * How often a read from a local file is tried again when it fails the way network filesystems do for a moment, like
+ * EIO or ESTALE from NFS, from --read-retries. The file is opened again and read from the end of the last good read,
+ * after waiting the backoff, which doubles with each retry. Only once every retry has failed is the read an error
+ */
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RetryPolicy {
+  retries: u32,
+  backoff: Duration,
+}
+
+impl RetryPolicy {
+  const DEFAULT: RetryPolicy = RetryPolicy { retries: 3, backoff: Duration::from_millis(200) };
+
+  /**
+   * Parse RETRIES[,backoff=DURATION], like 5,backoff=1s. 0 never retries
+   */
+  pub fn parse(raw: &str) -> Result<RetryPolicy, String> {
+    let mut parts = raw.split(',').map(str::trim);
+
+    let retries = parts.next()
+      .and_then(|retries| retries.parse().ok())
+      .ok_or_else(|| format!("Argument --read-retries '{raw}' must start with how many times to retry, like 5"))?;
+
+    let mut policy = RetryPolicy { retries, ..RetryPolicy::DEFAULT };
+
+    for part in parts {
+      match part.split_once('=') {
+        Some(("backoff", backoff)) => policy.backoff = parse_backoff(backoff)?,
+        _ => return Err(format!("Read retry option '{part}' is not recognized. The only option is backoff=DURATION")),
+      }
+    }
+
+    Ok(policy)
+  }
+
+  pub fn describe(&self) -> String {
+    if self.retries == 0 {
+      return "never".to_string();
+    }
+
+    format!("up to {} times, {}ms apart and doubling", self.retries, self.backoff.as_millis())
+  }
+}
+
+/**
+ * Parse a wait like 200ms or 2s
+ */
+fn parse_backoff(raw: &str) -> Result<Duration, String> {
+  let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+  let (number, unit) = raw.split_at(split);
+
+  let number: u64 = number.parse()
+    .map_err(|_| format!("Read retry backoff must be a number followed by ms or s, but was '{raw}'"))?;
+
+  match unit {
+    "ms" => Ok(Duration::from_millis(number)),
+    "s" => Ok(Duration::from_secs(number)),
+    _ => Err(format!("Read retry backoff must be a number followed by ms or s, but was '{raw}'")),
+  }
+}
+
+/**
+ * Set the policy for every read after this
+ */
+pub fn set_policy(policy: RetryPolicy) {
+  *POLICY.write().expect("Read retry policy was poisoned") = policy;
+}
+
+/**
+ * Read from FILE, which has had OFFSET bytes read from it, retrying if the read fails for a moment. FILE is replaced
+ * with the file opened again when it is, as long as PATH is still the same file as ID
+ */
+pub fn read(file: &mut File, path: &Path, id: Option<(u64, u64)>, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+  let policy = *POLICY.read().expect("Read retry policy was poisoned");
+  let mut wait = policy.backoff;
+  let mut retries = 0;
+
+  loop {
+    match file.read(buf) {
+      Ok(read) => return Ok(read),
+      Err(err) if retries < policy.retries && is_transient(&err) => {
+        retries += 1;
+
+        eprintln!(
+          "Warning: failed to read file '{}' at byte {offset}, trying again in {}ms ({retries} of {}): {err}",
+          path.display(), wait.as_millis(), policy.retries,
+        );
+
+        thread::sleep(wait);
+        wait *= 2;
+
+        // a handle that went stale is no good, but if the server is still gone the old one is tried again
+        if let Some(reopened) = reopen(path, id, offset) {
+          *file = reopened;
+        }
+      }
+      Err(err) => return Err(err),
+    }
+  }
+}
+
+fn reopen(path: &Path, id: Option<(u64, u64)>, offset: u64) -> Option<File> {
+  let mut file = File::open(path).ok()?;
+
+  // a file rotated into its place in the meantime isn't the one being read
+  if id.is_some() && identity(&file) != id {
+    return None;
+  }
+
+  file.seek(SeekFrom::Start(offset)).ok()?;
+  Some(file)
+}
+
+/**
+ * What tells a file apart from another put at the same path, where there is something
+ */
+#[cfg(unix)]
+pub fn identity(file: &File) -> Option<(u64, u64)> {
+  use std::os::unix::fs::MetadataExt;
+
+  file.metadata().ok().map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn identity(_file: &File) -> Option<(u64, u64)> {
+  None
+}
+
+#[cfg(unix)]
+fn is_transient(err: &io::Error) -> bool {
+  matches!(err.raw_os_error(), Some(libc::EIO | libc::ESTALE | libc::ETIMEDOUT | libc::ECONNRESET | libc::EAGAIN))
+}
+
+#[cfg(not(unix))]
+fn is_transient(err: &io::Error) -> bool {
+  matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::ConnectionReset)
+}
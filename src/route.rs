@@ -0,0 +1,126 @@
+use std::fmt;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use datetime::LocalDateTime;
+use serde_json::{Map, Value};
+
+use crate::checksum::{Checksum, OutputFile};
+use crate::chunk::LogWriter;
+use crate::codec::CompressionRoutes;
+use crate::exists::OnExists;
+use crate::filter::{Expression, FilterSet};
+
+/// separates a route's filter from where it goes
+const ARROW: &str = "=>";
+
+/**
+ * Where events matching a filter are written instead of the output, from --route "FILTER => PATH". FILTER is
+ * anything --filter takes, including expressions
+ */
+#[derive(Debug)]
+pub struct Route {
+  condition: Expression,
+  pub path: PathBuf,
+}
+
+impl fmt::Display for Route {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} => {}", self.condition, self.path.display())
+  }
+}
+
+impl Route {
+  pub fn parse(raw: &str) -> Result<Route, String> {
+    let (filter, path) = raw.rsplit_once(ARROW)
+      .ok_or_else(|| format!("Route '{raw}' must be in the form \"FILTER => PATH\""))?;
+
+    let path = path.trim();
+
+    if path.is_empty() {
+      return Err(format!("Route '{raw}' must name a file to write to after =>"));
+    }
+
+    Ok(Route { condition: FilterSet::parse_expression(filter.trim())?, path: PathBuf::from(path) })
+  }
+
+  pub fn matches(&self, values: &Map<String, Value>) -> bool {
+    self.condition.matches(values)
+  }
+}
+
+/**
+ * Writes each event to the file of the first route it matches, or to the output if it matches none, so events can
+ * be separated into several files in one pass, like those with a stack from the rest
+ */
+pub struct RouteWriter {
+  routes: Vec<(Route, Box<dyn LogWriter>)>,
+  default: Box<dyn LogWriter>,
+  /// the route the event being written goes to, or None for the output
+  current: Option<usize>,
+}
+
+/**
+ * Open the file of every route, before the output is, so a file that's already there stops saw before anything is
+ * written
+ */
+pub fn open(
+  routes: Vec<Route>,
+  compression: &CompressionRoutes,
+  on_exists: OnExists,
+  checksum: Option<Checksum>,
+) -> Vec<(Route, Box<dyn LogWriter>)> {
+  on_exists.check(routes.iter().map(|route| route.path.clone()));
+
+  routes.into_iter()
+    .map(|route| {
+      let codec = compression.codec_for(route.path.to_str().unwrap_or(""));
+      let file = on_exists.create(&route.path);
+      let writer = codec.wrap(BufWriter::new(OutputFile::new(file, &route.path, checksum)));
+
+      (route, writer)
+    })
+    .collect()
+}
+
+impl RouteWriter {
+  pub fn new(default: Box<dyn LogWriter>, routes: Vec<(Route, Box<dyn LogWriter>)>) -> RouteWriter {
+    RouteWriter { routes, default, current: None }
+  }
+
+  fn target(&mut self) -> &mut Box<dyn LogWriter> {
+    match self.current {
+      Some(index) => &mut self.routes[index].1,
+      None => &mut self.default,
+    }
+  }
+}
+
+impl Write for RouteWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.target().write(buf)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.routes.iter_mut().try_for_each(|(_, writer)| writer.flush())?;
+    self.default.flush()
+  }
+}
+
+impl LogWriter for RouteWriter {
+  fn route_event(&mut self, values: &Map<String, Value>) {
+    self.current = self.routes.iter().position(|(route, _)| route.matches(values));
+  }
+
+  fn start_event(&mut self, time: Option<LocalDateTime>) {
+    self.target().start_event(time);
+  }
+
+  fn end_line(&mut self) {
+    self.target().end_line();
+  }
+
+  fn end_frame(&mut self) {
+    self.target().end_frame();
+  }
+}
@@ -0,0 +1,213 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use regex::{Captures, Regex, RegexSet};
+use serde_json::Value;
+
+use crate::log::{FileSource, Line};
+
+/// the exit code when --scan-secrets fail finds anything, EX_DATAERR from sysexits.h
+pub const SECRETS_EXIT_CODE: i32 = 65;
+
+/**
+ * What --scan-secrets does with an event holding a secret
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecretAction {
+  /// write it as it is, and list what was found once done
+  Report,
+  /// replace each secret with [REDACTED:KIND]
+  Redact,
+  /// leave the event out, and exit with an error once done
+  Fail,
+}
+
+/// (name, pattern) of every kind of secret found, in the order they're reported
+const KINDS: [(&str, &str); 7] = [
+  ("aws-access-key", r"\b(?:AKIA|ASIA|AGPA|AIDA|AROA|ANPA|ANVA|AIPA)[0-9A-Z]{16}\b"),
+  ("aws-secret-key", r#"(?i)\baws_?secret_?access_?key\b["']?\s*[:=]\s*["']?[A-Za-z0-9/+]{40}"#),
+  ("jwt", r"\beyJ[A-Za-z0-9_-]{5,}\.eyJ[A-Za-z0-9_-]{5,}\.[A-Za-z0-9_-]{10,}"),
+  ("bearer-token", r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]{8,}=*"),
+  ("private-key", r"-----BEGIN (?:[A-Z]+ )?PRIVATE KEY-----"),
+  ("github-token", r"\bgh[pousr]_[A-Za-z0-9]{36}\b"),
+  // only counted when the digits pass the Luhn check, so ids and timestamps aren't taken for cards
+  ("credit-card", r"\b\d(?:[ -]?\d){12,18}\b"),
+];
+
+const CARD: usize = 6;
+
+lazy_static! {
+  static ref ANY: RegexSet = RegexSet::new(KINDS.iter().map(|(_, pattern)| pattern)).unwrap();
+  static ref EACH: Vec<Regex> = KINDS.iter().map(|(_, pattern)| Regex::new(pattern).unwrap()).collect();
+}
+
+/**
+ * How many of each kind were found and where the first one was, shared with the scanner, which may be running on
+ * another thread with --daily
+ */
+#[derive(Debug, Default)]
+pub struct Findings {
+  counts: [AtomicU64; KINDS.len()],
+  first: Mutex<[Option<String>; KINDS.len()]>,
+  /// events left out by fail
+  dropped: AtomicU64,
+}
+
+/**
+ * Looks for common secrets in every string of every event, from --scan-secrets
+ */
+#[derive(Debug)]
+pub struct SecretScanner {
+  action: SecretAction,
+  findings: Arc<Findings>,
+}
+
+impl SecretAction {
+  pub fn parse(raw: &str) -> Result<SecretAction, String> {
+    match raw.trim().to_lowercase().as_str() {
+      "report" => Ok(SecretAction::Report),
+      "redact" => Ok(SecretAction::Redact),
+      "fail" => Ok(SecretAction::Fail),
+      _ => Err(format!("Argument --scan-secrets '{raw}' is not recognized. Valid options are report, redact, fail")),
+    }
+  }
+
+  pub fn describe(&self) -> &'static str {
+    match self {
+      SecretAction::Report => "report secrets found",
+      SecretAction::Redact => "redact secrets",
+      SecretAction::Fail => "leave out events with secrets and fail",
+    }
+  }
+}
+
+impl SecretScanner {
+  pub fn new(action: SecretAction) -> SecretScanner {
+    SecretScanner { action, findings: Arc::default() }
+  }
+
+  pub fn action(&self) -> SecretAction {
+    self.action
+  }
+
+  pub fn findings(&self) -> Arc<Findings> {
+    self.findings.clone()
+  }
+
+  /**
+   * Scan the event, redacting it if asked to. Returns false if it must be left out
+   */
+  pub fn apply(&self, line: &mut Line) -> bool {
+    let mut found = false;
+
+    for (key, value) in line.value.iter_mut() {
+      // the path to a field is only worked out for what's found
+      found |= self.scan(value, &line.src, &|| key.clone());
+    }
+
+    if found && self.action == SecretAction::Fail {
+      self.findings.dropped.fetch_add(1, Ordering::Relaxed);
+      return false;
+    }
+
+    true
+  }
+
+  fn scan(&self, value: &mut Value, src: &FileSource, path: &dyn Fn() -> String) -> bool {
+    match value {
+      Value::String(text) => self.scan_text(text, src, path),
+      Value::Array(items) => items.iter_mut().enumerate()
+        .fold(false, |found, (index, item)| self.scan(item, src, &|| format!("{}.{index}", path())) | found),
+      Value::Object(fields) => fields.iter_mut()
+        .fold(false, |found, (key, item)| self.scan(item, src, &|| format!("{}.{key}", path())) | found),
+      _ => false,
+    }
+  }
+
+  fn scan_text(&self, text: &mut String, src: &FileSource, path: &dyn Fn() -> String) -> bool {
+    let mut found = false;
+
+    for kind in ANY.matches(text).iter() {
+      let mut count = 0;
+
+      let redacted = EACH[kind].replace_all(text, |captures: &Captures| {
+        let secret = &captures[0];
+
+        if kind == CARD && !passes_luhn(secret) {
+          return secret.to_string();
+        }
+
+        count += 1;
+        format!("[REDACTED:{}]", KINDS[kind].0)
+      });
+
+      if count == 0 {
+        continue;
+      }
+
+      found = true;
+      self.findings.record(kind, count, &|| format!("'{}' line {}, field '{}'", src.file, src.line, path()));
+
+      if self.action == SecretAction::Redact {
+        *text = redacted.into_owned();
+      }
+    }
+
+    found
+  }
+}
+
+fn passes_luhn(raw: &str) -> bool {
+  let digits: Vec<u32> = raw.chars().filter_map(|c| c.to_digit(10)).collect();
+
+  // every second digit from the right is doubled, and its digits summed
+  let sum: u32 = digits.iter().rev().enumerate()
+    .map(|(index, digit)| match (index % 2, digit * 2) {
+      (1, doubled) if doubled > 9 => doubled - 9,
+      (1, doubled) => doubled,
+      _ => *digit,
+    })
+    .sum();
+
+  sum.is_multiple_of(10)
+}
+
+impl Findings {
+  fn record(&self, kind: usize, count: u64, location: &dyn Fn() -> String) {
+    self.counts[kind].fetch_add(count, Ordering::Relaxed);
+
+    let mut first = self.first.lock().expect("Secret findings were poisoned");
+    first[kind].get_or_insert_with(location);
+  }
+
+  pub fn total(&self) -> u64 {
+    self.counts.iter().map(|count| count.load(Ordering::Relaxed)).sum()
+  }
+
+  /**
+   * List what was found on stderr, if anything was
+   */
+  pub fn print(&self, action: SecretAction) {
+    if self.total() == 0 {
+      return;
+    }
+
+    let verb = if action == SecretAction::Redact { "Redacted" } else { "Found" };
+
+    eprintln!("{verb} {} secrets:", self.total());
+
+    let first = self.first.lock().expect("Secret findings were poisoned");
+
+    for (index, (name, _)) in KINDS.iter().enumerate() {
+      let count = self.counts[index].load(Ordering::Relaxed);
+
+      if count > 0 {
+        eprintln!("  {name}: {count}, first in {}", first[index].as_deref().unwrap_or("an unknown place"));
+      }
+    }
+
+    if action == SecretAction::Fail {
+      eprintln!("Left out {} events holding secrets", self.dropped.load(Ordering::Relaxed));
+    }
+  }
+}
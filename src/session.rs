@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use datetime::LocalDateTime;
+use serde_json::{Map, Value};
+
+use crate::pretty::PrettyDescriptor;
+
+pub const SESSION_FIELD: &str = "_session";
+
+/// how many keys are kept before those that can't continue a session any more are forgotten
+const PRUNE_AT: usize = 100_000;
+
+/**
+ * Numbers sessions from --sessionize by FIELD gap GAP. Events with the same FIELD belong to one session until one
+ * comes GAP or more after the one before it, which starts a new session. Every session gets its own number in
+ * '_session', counting from 1 in the order they start, so sessions of different keys never share one.
+ *
+ * Events must come in time order, as they do when merged, and events without the field or a time are left alone.
+ */
+#[derive(Debug)]
+pub struct Sessionizer {
+  field: String,
+  gap: String,
+  gap_ms: i64,
+  /// each key, to the time of its last event and the number of its session
+  open: HashMap<String, (i64, u64)>,
+  sessions: u64,
+  latest: i64,
+}
+
+impl Sessionizer {
+  pub fn new(field: String, raw_gap: &str) -> Result<Sessionizer, String> {
+    if field.trim().is_empty() {
+      return Err("Argument --sessionize must be given a FIELD after 'by'".to_string());
+    }
+
+    Ok(Sessionizer {
+      field,
+      gap: raw_gap.trim().to_string(),
+      gap_ms: parse_duration(raw_gap, "--sessionize gap")?,
+      open: HashMap::new(),
+      sessions: 0,
+      latest: i64::MIN,
+    })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{SESSION_FIELD} by '{}', ended by a gap of {}", self.field, self.gap)
+  }
+
+  pub fn apply(&mut self, values: &mut Map<String, Value>, time: Option<LocalDateTime>) {
+    let (Some(value), Some(time)) = (values.get(&self.field), time) else {
+      return;
+    };
+
+    let key = PrettyDescriptor::pretty_value(value);
+    let now = millis(time);
+    self.latest = self.latest.max(now);
+
+    let session = match self.open.get_mut(&key) {
+      Some((last, session)) if now - *last < self.gap_ms => {
+        *last = now;
+        *session
+      }
+      _ => {
+        self.sessions += 1;
+
+        if self.open.len() >= PRUNE_AT {
+          self.prune();
+        }
+
+        self.open.insert(key, (now, self.sessions));
+        self.sessions
+      }
+    };
+
+    values.insert(SESSION_FIELD.to_string(), Value::from(session));
+  }
+
+  /// forget every key whose session has already ended, so the keys of a long run don't all stay in memory
+  fn prune(&mut self) {
+    let cutoff = self.latest.saturating_sub(self.gap_ms);
+
+    self.open.retain(|_, (last, _)| *last > cutoff);
+  }
+}
+
+/**
+ * Parse a duration like 90s, 30m, 2h or 1d into milliseconds. 'what' names the option in the error
+ */
+pub fn parse_duration(raw: &str, what: &str) -> Result<i64, String> {
+  let trimmed = raw.trim();
+  let split = trimmed.find(|c: char| !c.is_ascii_digit()).unwrap_or(trimmed.len());
+  let (number, unit) = trimmed.split_at(split);
+
+  let number: i64 = number.parse().ok()
+    .filter(|number| *number > 0)
+    .ok_or_else(|| format!("Argument {what} must be a whole number greater than 0 followed by s, m, h or d, but was '{raw}'"))?;
+
+  let unit_ms = match unit {
+    "s" => 1000,
+    "m" => 60 * 1000,
+    "h" => 60 * 60 * 1000,
+    "d" => 24 * 60 * 60 * 1000,
+    _ => return Err(format!("Argument {what} must be a whole number greater than 0 followed by s, m, h or d, but was '{raw}'")),
+  };
+
+  number.checked_mul(unit_ms).ok_or_else(|| format!("Argument {what} '{raw}' is too large"))
+}
+
+pub fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
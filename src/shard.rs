@@ -0,0 +1,89 @@
+use serde_json::{Map, Value};
+
+use crate::fingerprint::fnv;
+use crate::pretty::PrettyDescriptor;
+
+/**
+ * Which part of the work this process does when several split it between them, from --shard K/N. By default each
+ * source belongs to one shard picked by hashing its name, with key=FIELD every process reads every source and keeps
+ * the events whose FIELD hashes to its shard. Either way every process must be given the same sources, and each one
+ * is written by exactly one shard.
+ */
+#[derive(Debug, Clone)]
+pub struct Shard {
+  /// counted from 0, while K is counted from 1
+  index: u64,
+  count: u64,
+  key: Option<String>,
+}
+
+impl Shard {
+  /**
+   * Parse K/N[,key=FIELD], for example 2/8 or 2/8,key=request_id
+   */
+  pub fn parse(raw: &str) -> Result<Shard, String> {
+    let mut parts = raw.split(',');
+
+    let (shard, count) = parts.next().unwrap_or("").split_once('/')
+      .ok_or_else(|| format!("Shard '{raw}' must be in the form K/N, like 2/8"))?;
+
+    let shard: u64 = shard.trim().parse().map_err(|_| format!("Shard '{raw}' must start with a number"))?;
+    let count: u64 = count.trim().parse().map_err(|_| format!("Shard '{raw}' must have a number of shards after the /"))?;
+
+    if count == 0 || shard == 0 || shard > count {
+      return Err(format!("Shard '{raw}' must be between 1/N and N/N"));
+    }
+
+    let mut key = None;
+
+    for part in parts {
+      let field = part.trim().strip_prefix("key=")
+        .ok_or_else(|| format!("Shard option '{part}' is not recognized. The only option is key=FIELD"))?;
+
+      if field.is_empty() {
+        return Err("Shard option 'key=' must be followed by a FIELD".to_string());
+      }
+
+      key = Some(field.to_string());
+    }
+
+    Ok(Shard { index: shard - 1, count, key })
+  }
+
+  pub fn describe(&self) -> String {
+    match &self.key {
+      Some(key) => format!("{} of {}, by the hash of '{key}'", self.index + 1, self.count),
+      None => format!("{} of {}, by source", self.index + 1, self.count),
+    }
+  }
+
+  pub fn by_key(&self) -> bool {
+    self.key.is_some()
+  }
+
+  fn owns(&self, raw: &str) -> bool {
+    fnv(raw.bytes()) % self.count == self.index
+  }
+
+  /**
+   * Is this source read by this shard? Always true when sharding by key
+   */
+  pub fn owns_source(&self, name: &str) -> bool {
+    self.key.is_some() || self.owns(name)
+  }
+
+  /**
+   * Is this event written by this shard? Events without the key all go to the first shard. Always true when sharding
+   * by source
+   */
+  pub fn owns_event(&self, values: &Map<String, Value>) -> bool {
+    let Some(key) = &self.key else {
+      return true;
+    };
+
+    match values.get(key) {
+      Some(value) => self.owns(&PrettyDescriptor::pretty_value(value)),
+      None => self.index == 0,
+    }
+  }
+}
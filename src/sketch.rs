@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+/**
+ * A DDSketch, which estimates quantiles to within a fixed relative error using memory that only grows
+ * with the log of the range of values seen, no matter how many values there are.
+ */
+#[derive(Debug, Clone)]
+pub struct Sketch {
+  gamma: f64,
+  log_gamma: f64,
+  positive: BTreeMap<i32, u64>,
+  negative: BTreeMap<i32, u64>,
+  zero: u64,
+  count: u64,
+  min: f64,
+  max: f64,
+}
+
+// results are within 1% of the true value
+const RELATIVE_ACCURACY: f64 = 0.01;
+
+// values closer to zero than this are counted as zero
+const MIN_MAGNITUDE: f64 = 1e-9;
+
+impl Sketch {
+  pub fn new() -> Sketch {
+    let gamma = (1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY);
+
+    Sketch {
+      gamma,
+      log_gamma: gamma.ln(),
+      positive: BTreeMap::new(),
+      negative: BTreeMap::new(),
+      zero: 0,
+      count: 0,
+      min: f64::INFINITY,
+      max: f64::NEG_INFINITY,
+    }
+  }
+
+  pub fn count(&self) -> u64 {
+    self.count
+  }
+
+  pub fn add(&mut self, value: f64) {
+    if value.is_nan() {
+      return;
+    }
+
+    if value > MIN_MAGNITUDE {
+      *self.positive.entry(self.index(value)).or_insert(0) += 1;
+    } else if value < -MIN_MAGNITUDE {
+      *self.negative.entry(self.index(-value)).or_insert(0) += 1;
+    } else {
+      self.zero += 1;
+    }
+
+    self.count += 1;
+    self.min = self.min.min(value);
+    self.max = self.max.max(value);
+  }
+
+  /**
+   * Estimate the value at quantile q, which should be between 0 and 1. Returns None if the sketch is empty.
+   */
+  pub fn quantile(&self, q: f64) -> Option<f64> {
+    if self.count == 0 {
+      return None;
+    }
+
+    if q <= 0.0 {
+      return Some(self.min);
+    }
+
+    if q >= 1.0 {
+      return Some(self.max);
+    }
+
+    let rank = (q * (self.count - 1) as f64) as u64;
+    let mut seen = 0u64;
+
+    // negative values, from the largest magnitude down
+    for (index, count) in self.negative.iter().rev() {
+      seen += count;
+
+      if seen > rank {
+        return Some(-self.value(*index));
+      }
+    }
+
+    seen += self.zero;
+
+    if seen > rank {
+      return Some(0.0);
+    }
+
+    for (index, count) in &self.positive {
+      seen += count;
+
+      if seen > rank {
+        return Some(self.value(*index).clamp(self.min, self.max));
+      }
+    }
+
+    Some(self.max)
+  }
+
+  fn index(&self, value: f64) -> i32 {
+    (value.ln() / self.log_gamma).ceil() as i32
+  }
+
+  fn value(&self, index: i32) -> f64 {
+    2.0 * self.gamma.powi(index) / (self.gamma + 1.0)
+  }
+}
@@ -0,0 +1,78 @@
+use std::time::{Duration, Instant};
+
+/**
+ * How often --stats and --group-by write what they have so far, from --snapshot-every, for sources that never end
+ * like --follow, where the report that's written once every event has been read never comes
+ */
+#[derive(Debug, Clone, Copy)]
+pub enum SnapshotEvery {
+  /// after this many events
+  Events(u64),
+  /// once this long has passed, checked as each event arrives, since nothing has changed while none do
+  Interval(Duration),
+}
+
+impl SnapshotEvery {
+  /**
+   * Parse a number of events like 1000, or an interval like 500ms, 10s, 5m or 1h
+   */
+  pub fn parse(raw: &str) -> Result<SnapshotEvery, String> {
+    let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(split);
+
+    let number: u64 = number.parse().ok()
+      .filter(|number| *number > 0)
+      .ok_or_else(|| format!("Argument --snapshot-every must be a number of events or a number followed by ms, s, m or h, greater than 0, but was '{raw}'"))?;
+
+    match unit {
+      "" => Ok(SnapshotEvery::Events(number)),
+      "ms" => Ok(SnapshotEvery::Interval(Duration::from_millis(number))),
+      "s" => Ok(SnapshotEvery::Interval(Duration::from_secs(number))),
+      "m" => Ok(SnapshotEvery::Interval(Duration::from_secs(number * 60))),
+      "h" => Ok(SnapshotEvery::Interval(Duration::from_secs(number * 60 * 60))),
+      _ => Err(format!("Argument --snapshot-every must be a number of events or a number followed by ms, s, m or h, but was '{raw}'")),
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      SnapshotEvery::Events(events) => format!("every {events} events"),
+      SnapshotEvery::Interval(interval) => format!("every {}ms", interval.as_millis()),
+    }
+  }
+}
+
+/**
+ * Counts events toward the next snapshot
+ */
+#[derive(Debug)]
+pub struct Snapshots {
+  every: SnapshotEvery,
+  events: u64,
+  last: Instant,
+}
+
+impl Snapshots {
+  pub fn new(every: SnapshotEvery) -> Snapshots {
+    Snapshots { every, events: 0, last: Instant::now() }
+  }
+
+  /**
+   * Count one more event, true if a snapshot is due after it
+   */
+  pub fn due(&mut self) -> bool {
+    self.events += 1;
+
+    let due = match self.every {
+      SnapshotEvery::Events(events) => self.events >= events,
+      SnapshotEvery::Interval(interval) => self.last.elapsed() >= interval,
+    };
+
+    if due {
+      self.events = 0;
+      self.last = Instant::now();
+    }
+
+    due
+  }
+}
@@ -0,0 +1,108 @@
+use std::cmp::Ordering;
+
+use datetime::LocalDateTime;
+use serde_json::{Map, Value};
+
+/// the field that holds each event's parsed time
+const TIME_FIELD: &str = "time";
+
+/**
+ * The fields sources are merged by in place of 'time', from --sort-by. Each field is compared in turn, with numbers
+ * compared as numbers, and 'time' compared as a time
+ */
+#[derive(Debug)]
+pub struct SortKey {
+  fields: Vec<String>,
+}
+
+/**
+ * One field of a sort key. Events missing the field come first, and numbers come before times, which come before text
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortValue {
+  Missing,
+  Number(f64),
+  Time(LocalDateTime),
+  Text(String),
+}
+
+impl SortValue {
+  fn rank(&self) -> u8 {
+    match self {
+      SortValue::Missing => 0,
+      SortValue::Number(_) => 1,
+      SortValue::Time(_) => 2,
+      SortValue::Text(_) => 3,
+    }
+  }
+}
+
+impl Eq for SortValue {}
+
+impl PartialOrd for SortValue {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for SortValue {
+  fn cmp(&self, other: &Self) -> Ordering {
+    match (self, other) {
+      (SortValue::Number(left), SortValue::Number(right)) => left.total_cmp(right),
+      (SortValue::Time(left), SortValue::Time(right)) => left.cmp(right),
+      (SortValue::Text(left), SortValue::Text(right)) => left.cmp(right),
+      _ => self.rank().cmp(&other.rank()),
+    }
+  }
+}
+
+impl SortKey {
+  /**
+   * Parse comma separated FIELDS, like seq or time,seq
+   */
+  pub fn parse(raw: &str) -> Result<SortKey, String> {
+    let fields: Vec<String> = raw.split(',').map(str::trim).map(String::from).collect();
+
+    if fields.iter().any(String::is_empty) {
+      return Err(format!("Argument --sort-by contains an empty field name in '{raw}'"));
+    }
+
+    Ok(SortKey { fields })
+  }
+
+  pub fn describe(&self) -> String {
+    self.fields.join(", ")
+  }
+
+  pub fn fields(&self) -> &[String] {
+    &self.fields
+  }
+
+  /**
+   * Is an event's time needed to merge it?
+   */
+  pub fn uses_time(&self) -> bool {
+    self.fields.iter().any(|field| field == TIME_FIELD)
+  }
+
+  pub fn key(&self, values: &Map<String, Value>, time: Option<LocalDateTime>) -> Vec<SortValue> {
+    self.fields.iter()
+      .map(|field| {
+        if field == TIME_FIELD {
+          return time.map_or(SortValue::Missing, SortValue::Time);
+        }
+
+        match values.get(field) {
+          Some(Value::Number(num)) => num.as_f64().map_or(SortValue::Missing, SortValue::Number),
+          // sequence numbers are often written as strings, and must still sort 9 before 10
+          Some(Value::String(text)) => match text.trim().parse::<f64>() {
+            Ok(num) if num.is_finite() => SortValue::Number(num),
+            _ => SortValue::Text(text.clone()),
+          },
+          Some(Value::Null) | None => SortValue::Missing,
+          Some(other) => SortValue::Text(other.to_string()),
+        }
+      })
+      .collect()
+  }
+}
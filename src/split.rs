@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use datetime::{DatePiece, LocalDateTime, TimePiece};
+
+use crate::checksum::{Checksum, OutputFile};
+use crate::chunk::LogWriter;
+use crate::codec::CompressionRoutes;
+use crate::exists::OnExists;
+use crate::zone::TimeZone;
+
+const MINUTE_MS: i64 = 60_000;
+const HOUR_MS: i64 = 60 * MINUTE_MS;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+/// most buckets kept open at once. Merged events mostly go forward in time, so only a few are ever written to together
+const MAX_OPEN: usize = 16;
+
+/// where events without a time go, next to the year directories
+const UNTIMED: &str = "untimed";
+
+/**
+ * How long each bucket of --split-by-time is, like 1h or 15m. Buckets start on whole multiples of it since the epoch
+ * on the wall clock of --timezone, so they line up with the hours and days of the events' own times there
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct SplitInterval {
+  ms: i64,
+}
+
+impl SplitInterval {
+  /**
+   * Parse a number and one of m, h or d. Anything under a day must divide a day evenly, so every day has the same
+   * buckets
+   */
+  pub fn parse(raw: &str) -> Result<SplitInterval, String> {
+    let trimmed = raw.trim();
+    let split = trimmed.find(|c: char| !c.is_ascii_digit()).unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let count: i64 = number.parse().ok()
+      .filter(|count| *count > 0)
+      .ok_or_else(|| format!("Split interval '{raw}' must start with a whole number greater than 0"))?;
+
+    let unit_ms = match unit.trim().to_lowercase().as_str() {
+      "m" => MINUTE_MS,
+      "h" => HOUR_MS,
+      "d" => DAY_MS,
+      other => return Err(format!("Split interval unit '{other}' in '{raw}' is not recognized. Valid options are m, h, d")),
+    };
+
+    let ms = count.checked_mul(unit_ms).ok_or_else(|| format!("Split interval '{raw}' is too large"))?;
+
+    if ms < DAY_MS && DAY_MS % ms != 0 {
+      return Err(format!("Split interval '{raw}' must divide a day evenly, like 15m, 2h or 6h"));
+    }
+
+    Ok(SplitInterval { ms })
+  }
+
+  fn bucket(&self, time: LocalDateTime, zone: &TimeZone) -> i64 {
+    // an hour that happens twice when the clocks go back shares a bucket
+    let ms = millis(zone.to_local(time));
+
+    ms - ms.rem_euclid(self.ms)
+  }
+
+  /**
+   * The path of a bucket under the output directory, without an extension. Days are always directories, and each
+   * bucket goes as deep as its interval needs, so 1d gives 2024/06/01, 1h gives 2024/06/01/13 and 15m gives
+   * 2024/06/01/13/45
+   */
+  fn path(&self, bucket: i64) -> PathBuf {
+    let start = LocalDateTime::at(bucket.div_euclid(1000));
+
+    let mut path = PathBuf::from(format!("{:04}", start.year()));
+    path.push(format!("{:02}", start.month().months_from_january() + 1));
+    path.push(format!("{:02}", start.day()));
+
+    if self.ms % DAY_MS != 0 {
+      path.push(format!("{:02}", start.hour()));
+    }
+
+    if self.ms % HOUR_MS != 0 {
+      path.push(format!("{:02}", start.minute()));
+    }
+
+    path
+  }
+}
+
+impl fmt::Display for SplitInterval {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.ms % DAY_MS == 0 {
+      write!(f, "{}d", self.ms / DAY_MS)
+    } else if self.ms % HOUR_MS == 0 {
+      write!(f, "{}h", self.ms / HOUR_MS)
+    } else {
+      write!(f, "{}m", self.ms / MINUTE_MS)
+    }
+  }
+}
+
+/**
+ * Writes each event into a file for the bucket its own time falls in, under the output directory. A writer is kept
+ * open for every bucket in use, and the least recently used is closed when too many are. A bucket that is written to
+ * again after being closed is appended to, which gzip and zstd both allow, since each write adds a new frame.
+ */
+pub struct SplitWriter {
+  base: PathBuf,
+  interval: SplitInterval,
+  zone: TimeZone,
+  compression: CompressionRoutes,
+  on_exists: OnExists,
+  checksum: Option<Checksum>,
+  /// open buckets, least recently used first. The last one is where writes go. None is the bucket of untimed events
+  open: Vec<(Option<i64>, Box<dyn LogWriter>)>,
+  /// every file written by this run, which are appended to when opened again. --on-exists only decides what happens
+  /// to files from before this run
+  created: HashSet<PathBuf>,
+}
+
+impl SplitWriter {
+  pub fn new(base: PathBuf, interval: SplitInterval, zone: TimeZone, compression: CompressionRoutes, on_exists: OnExists, checksum: Option<Checksum>) -> SplitWriter {
+    // which buckets will be written isn't known until they are, so anything already in the directory counts
+    on_exists.check(fs::read_dir(&base).into_iter().flatten().flatten().map(|entry| entry.path()));
+
+    SplitWriter { base, interval, zone, compression, on_exists, checksum, open: vec![], created: HashSet::new() }
+  }
+
+  fn select(&mut self, bucket: Option<i64>) {
+    if self.open.last().is_some_and(|(current, _)| *current == bucket) {
+      return;
+    }
+
+    if let Some(index) = self.open.iter().position(|(current, _)| *current == bucket) {
+      let found = self.open.remove(index);
+      self.open.push(found);
+      return;
+    }
+
+    if self.open.len() >= MAX_OPEN {
+      // dropping it finishes whatever compression it had
+      self.open.remove(0);
+    }
+
+    let writer = self.create(bucket);
+    self.open.push((bucket, writer));
+  }
+
+  fn create(&mut self, bucket: Option<i64>) -> Box<dyn LogWriter> {
+    let relative = bucket.map_or(PathBuf::from(UNTIMED), |bucket| self.interval.path(bucket));
+    let stem = self.base.join(relative);
+
+    // routes are matched against the bucket without its extensions, since the extension depends on the route
+    let codec = self.compression.codec_for(stem.to_str().unwrap_or(""));
+    let file_path = match codec.extension() {
+      Some(ext) => stem.with_extension(format!("log.{ext}")),
+      None => stem.with_extension("log"),
+    };
+
+    if let Some(parent) = file_path.parent() {
+      fs::create_dir_all(parent).unwrap_or_else(|_| panic!("Failed to create directory '{}'", parent.display()));
+    }
+
+    let file = if self.created.insert(file_path.clone()) {
+      self.on_exists.create(&file_path)
+    } else {
+      OpenOptions::new().append(true).open(&file_path).unwrap_or_else(|_| panic!("Failed to open file '{}'", file_path.display()))
+    };
+
+    codec.wrap(BufWriter::new(OutputFile::new(file, &file_path, self.checksum)))
+  }
+
+  fn current(&mut self) -> &mut Box<dyn LogWriter> {
+    // anything written before the first event, which nothing does yet, goes with the untimed events
+    if self.open.is_empty() {
+      self.select(None);
+    }
+
+    &mut self.open.last_mut().unwrap().1
+  }
+}
+
+impl Write for SplitWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.current().write(buf)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.open.iter_mut().try_for_each(|(_, writer)| writer.flush())
+  }
+}
+
+impl LogWriter for SplitWriter {
+  fn start_event(&mut self, time: Option<LocalDateTime>) {
+    let bucket = time.map(|time| self.interval.bucket(time, &self.zone));
+
+    self.select(bucket);
+  }
+
+  fn end_line(&mut self) {
+    self.current().end_line();
+  }
+
+  fn end_frame(&mut self) {
+    self.current().end_frame();
+  }
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
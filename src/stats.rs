@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use datetime::{ISO, LocalDateTime};
+
+use crate::width;
+
+/// how far a source's last event must be from the others' before it is called out, in milliseconds
+const SKEW_THRESHOLD_MS: i64 = 60_000;
+
+/**
+ * What was read from one source, for --source-stats. Shared with the source, which may be on another thread
+ */
+#[derive(Debug, Default)]
+pub struct SourceStats {
+  pub name: String,
+  pub path: Option<PathBuf>,
+  pub lines: u64,
+  /// bytes read after decompressing
+  pub bytes: u64,
+  /// lines dropped for not being JSON or not having a valid time
+  pub errors: u64,
+  pub first: Option<LocalDateTime>,
+  pub last: Option<LocalDateTime>,
+  /// events with an earlier time than the one before them in the same source
+  pub backwards: u64,
+  /// the furthest any event went back, in milliseconds
+  pub max_backwards_ms: i64,
+  /// time of the latest event so far
+  latest: Option<LocalDateTime>,
+}
+
+impl SourceStats {
+  pub fn new(name: &str, path: Option<&PathBuf>) -> Arc<Mutex<SourceStats>> {
+    Arc::new(Mutex::new(SourceStats {
+      name: name.to_string(),
+      path: path.cloned(),
+      ..SourceStats::default()
+    }))
+  }
+
+  pub fn add_event(&mut self, time: Option<LocalDateTime>) {
+    let Some(time) = time else {
+      return;
+    };
+
+    self.first = Some(self.first.map_or(time, |first| first.min(time)));
+    self.last = Some(self.last.map_or(time, |last| last.max(time)));
+
+    if let Some(latest) = self.latest {
+      if time < latest {
+        self.backwards += 1;
+        self.max_backwards_ms = self.max_backwards_ms.max(millis(latest) - millis(time));
+      }
+    }
+
+    self.latest = Some(self.latest.map_or(time, |latest| latest.max(time)));
+  }
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
+
+fn describe_ms(ms: i64) -> String {
+  let sign = if ms < 0 { "-" } else { "+" };
+  let secs = ms.abs() / 1000;
+
+  match secs {
+    0..=59 => format!("{sign}{:.1}s", ms.abs() as f64 / 1000.0),
+    60..=3599 => format!("{sign}{}m{:02}s", secs / 60, secs % 60),
+    _ => format!("{sign}{}h{:02}m", secs / 3600, (secs % 3600) / 60),
+  }
+}
+
+/**
+ * Print a table of every source to stderr. Skew is how far a source's last event is from the median last event of all
+ * sources, since sources collected at the same moment should end at about the same time. A host with a wrong clock
+ * stands out as the one far from zero.
+ */
+pub fn print(stats: &[Arc<Mutex<SourceStats>>]) {
+  let stats: Vec<_> = stats.iter().map(|stats| stats.lock().expect("Source stats were poisoned")).collect();
+
+  let mut ends: Vec<i64> = stats.iter().filter_map(|stats| stats.last.map(millis)).collect();
+  ends.sort();
+  let median = ends.get(ends.len() / 2).copied();
+
+  let rows: Vec<[String; 10]> = stats.iter()
+    .map(|stats| {
+      let compressed = stats.path.as_ref()
+        .and_then(|path| path.metadata().ok())
+        .map(|metadata| metadata.len());
+
+      let ratio = match compressed {
+        Some(compressed) if compressed > 0 => format!("{:.1}x", stats.bytes as f64 / compressed as f64),
+        _ => "-".to_string(),
+      };
+
+      let skew = match (stats.last, median) {
+        (Some(last), Some(median)) if (millis(last) - median).abs() >= SKEW_THRESHOLD_MS => describe_ms(millis(last) - median),
+        (Some(_), Some(_)) => "0".to_string(),
+        _ => "-".to_string(),
+      };
+
+      let backwards = if stats.backwards == 0 {
+        "0".to_string()
+      } else {
+        format!("{} (up to {})", stats.backwards, describe_ms(-stats.max_backwards_ms))
+      };
+
+      [
+        stats.name.clone(),
+        compressed.map_or("-".to_string(), |compressed| compressed.to_string()),
+        stats.bytes.to_string(),
+        ratio,
+        stats.lines.to_string(),
+        stats.errors.to_string(),
+        stats.first.map_or("-".to_string(), |time| time.iso().to_string()),
+        stats.last.map_or("-".to_string(), |time| time.iso().to_string()),
+        skew,
+        backwards,
+      ]
+    })
+    .collect();
+
+  let header = ["source", "compressed", "bytes", "ratio", "lines", "errors", "first", "last", "skew", "backwards"].map(String::from);
+
+  let widths: Vec<usize> = (0..header.len())
+    .map(|column| rows.iter().chain([&header]).map(|row| width::width(&row[column])).max().unwrap_or(0))
+    .collect();
+
+  for row in [&header].into_iter().chain(&rows) {
+    let line: Vec<String> = row.iter().zip(&widths).enumerate()
+      // names and times read best on the left, numbers on the right
+      .map(|(column, (cell, columns))| width::pad(cell, *columns, !matches!(column, 0 | 6 | 7 | 9)))
+      .collect();
+
+    eprintln!("{}", line.join("  ").trim_end());
+  }
+}
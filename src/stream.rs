@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+use datetime::LocalDateTime;
+
+use crate::aggregate::StatsMode;
+use crate::args::Arguments;
+
+/**
+ * A single source has nothing to be merged with, so if nothing after the merge looks at the time of an event either,
+ * its lines can go straight from the file to the filters without their times being parsed. Pretty printing one file
+ * is the most common way saw is run, and the merge is all cost for it.
+ *
+ * Lines still need a valid 'time' to be kept, as they would when merged, but the common shapes of time are only checked
+ * rather than parsed. See is_valid_time.
+ */
+pub fn can_stream(args: &Arguments) -> bool {
+  args.sources.len() == 1
+    && args.range == (None, None)
+    && !args.daily
+    && !args.follow
+    && !args.reverse
+    && args.tail.is_none()
+    && args.sort_by.is_none()
+    && args.external_sort.is_none()
+    // invalid lines are placed at the time of the line before them
+    && !args.passthrough_invalid
+    && args.sessionize.is_none()
+    && args.anomalies.is_none()
+    && args.correlate.is_none()
+    && args.deadline.is_none()
+    && args.replay.is_none()
+    && args.verify_sorted.is_none()
+    && args.summary_by.is_none()
+    && !args.stats.as_ref().is_some_and(|stats| matches!(stats.mode(), StatsMode::Histogram(..)))
+    && args.html.is_none()
+    && args.group.is_none()
+    && args.chunked.is_none()
+    && args.split_by_time.is_none()
+    && args.control_socket.is_none()
+    && !args.provenance
+    && !args.source_stats
+    && args.normalize_time.is_none()
+}
+
+/**
+ * Would this time be read by a merge? Times like 2024-01-31T12:00:00.123Z are checked by hand, which is far quicker than
+ * parsing them, and anything else, like an offset or a comma before the fraction, is parsed to be sure. Only shapes that
+ * parse every time are checked by hand: more than 6 digits of fraction can overflow when parsed
+ */
+pub fn is_valid_time(time: &str) -> bool {
+  is_plain_utc(time.as_bytes()) || LocalDateTime::from_str(time).is_ok()
+}
+
+fn is_plain_utc(time: &[u8]) -> bool {
+  let digits = |range: std::ops::Range<usize>| -> Option<u32> {
+    time.get(range)?.iter().try_fold(0, |total, digit| digit.is_ascii_digit().then(|| total * 10 + (digit - b'0') as u32))
+  };
+
+  let separated = time.len() >= 19
+    && time[4] == b'-' && time[7] == b'-' && time[10] == b'T' && time[13] == b':' && time[16] == b':';
+
+  let (Some(year), Some(month), Some(day), Some(hour), Some(minute), Some(second)) =
+    (digits(0..4), digits(5..7), digits(8..10), digits(11..13), digits(14..16), digits(17..19)) else {
+    return false;
+  };
+
+  let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+  let days = match month {
+    2 if leap => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  };
+
+  let rest = &time[19..];
+  let rest = rest.strip_suffix(b"Z").unwrap_or(rest);
+  let fraction = match rest {
+    [] => true,
+    [b'.', digits @ ..] => (1..=6).contains(&digits.len()) && digits.iter().all(u8::is_ascii_digit),
+    _ => false,
+  };
+
+  separated && fraction && (1..=12).contains(&month) && (1..=days).contains(&day) && hour < 24 && minute < 60 && second < 60
+}
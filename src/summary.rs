@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use datetime::{ISO, LocalDateTime, TimePiece};
+
+use crate::levels;
+use crate::log::Line;
+use crate::width;
+use crate::zone::TimeZone;
+
+#[derive(Debug, Clone, Copy)]
+pub enum SummaryBy {
+  Day,
+  Hour,
+}
+
+/**
+ * Counts of events per level in each day or hour, printed as a table once everything has been written
+ */
+#[derive(Debug)]
+pub struct Summary {
+  by: SummaryBy,
+  /// where days and hours start
+  zone: TimeZone,
+  counts: BTreeMap<String, BTreeMap<String, u64>>,
+  levels: BTreeSet<String>,
+}
+
+impl SummaryBy {
+  pub fn parse(raw: &str) -> Result<SummaryBy, String> {
+    match raw {
+      "day" => Ok(SummaryBy::Day),
+      "hour" => Ok(SummaryBy::Hour),
+      _ => Err("Argument --summary-by must be followed by 'day' or 'hour'".to_string()),
+    }
+  }
+
+  fn bucket(&self, time: LocalDateTime) -> String {
+    match self {
+      SummaryBy::Day => time.date().iso().to_string(),
+      SummaryBy::Hour => format!("{}T{:02}", time.date().iso(), time.hour()),
+    }
+  }
+}
+
+impl Summary {
+  pub fn new(by: SummaryBy, zone: TimeZone) -> Summary {
+    Summary {
+      by,
+      zone,
+      counts: BTreeMap::new(),
+      levels: BTreeSet::new(),
+    }
+  }
+
+  pub fn add(&mut self, line: &Line) {
+    let bucket = line.time.map_or("-".to_string(), |time| self.by.bucket(self.zone.to_local(time)));
+    let level = levels::named(&line.value)
+      .or_else(|| line.value.get("level").and_then(|level| level.as_str()))
+      .unwrap_or("-")
+      .to_string();
+
+    *self.counts.entry(bucket).or_default().entry(level.clone()).or_insert(0) += 1;
+    self.levels.insert(level);
+  }
+
+  pub fn print(&self) {
+    let bucket_width = self.counts.keys().map(|key| key.len()).max().unwrap_or(0).max("time".len());
+    let widths: Vec<usize> = self.levels.iter().map(|level| width::width(level).max(6)).collect();
+
+    let mut header = format!("{:bucket_width$}", "time");
+    for (level, columns) in self.levels.iter().zip(&widths) {
+      header.push_str(&format!("  {}", width::pad(level, *columns, true)));
+    }
+    header.push_str(&format!("  {:>6}", "total"));
+    eprintln!("{header}");
+
+    for (bucket, counts) in &self.counts {
+      let mut row = format!("{bucket:bucket_width$}");
+
+      for (level, width) in self.levels.iter().zip(&widths) {
+        row.push_str(&format!("  {:>width$}", counts.get(level).unwrap_or(&0)));
+      }
+
+      row.push_str(&format!("  {:>6}", counts.values().sum::<u64>()));
+      eprintln!("{row}");
+    }
+  }
+}
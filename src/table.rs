@@ -0,0 +1,111 @@
+use serde_json::{Map, Value};
+
+use crate::chunk::LogWriter;
+use crate::pretty::PrettyDescriptor;
+use crate::width;
+
+const DEFAULT_EVERY: usize = 100;
+
+/// space between columns
+const GAP: &str = "  ";
+
+/**
+ * Writes chosen fields as aligned columns under a header. Rows are held back in batches so each batch can be sized
+ * to fit, and the header is written again whenever the widths change.
+ */
+#[derive(Debug)]
+pub struct Table {
+  fields: Vec<String>,
+  every: usize,
+  rows: Vec<Vec<String>>,
+  /// widths of the last header written, if any
+  widths: Option<Vec<usize>>,
+}
+
+impl Table {
+  /**
+   * Parse FIELDS[,every=N], for example time,level,status,path,every=500
+   */
+  pub fn parse(raw: &str) -> Result<Table, String> {
+    let mut fields = vec![];
+    let mut every = DEFAULT_EVERY;
+
+    for part in raw.split(',').map(str::trim) {
+      if let Some(size) = part.strip_prefix("every=") {
+        every = size.parse().ok().filter(|size| *size > 0)
+          .ok_or_else(|| format!("Argument --table 'every={size}' must be a number of lines greater than 0"))?;
+      } else if part.is_empty() {
+        return Err(format!("Argument --table contains an empty field name in '{raw}'"));
+      } else {
+        fields.push(part.to_string());
+      }
+    }
+
+    if fields.is_empty() {
+      return Err(format!("Argument --table needs at least one field in '{raw}'"));
+    }
+
+    Ok(Table { fields, every, rows: vec![], widths: None })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{}, sized every {} lines", self.fields.join(", "), self.every)
+  }
+
+  pub fn add(&mut self, values: &Map<String, Value>, target: &mut Box<dyn LogWriter>) {
+    let row = self.fields.iter()
+      .map(|field| {
+        let value = values.get(field).map(PrettyDescriptor::pretty_value).unwrap_or_default();
+
+        // a cell must stay on one line or the columns fall apart
+        value.replace(['\r', '\n', '\t'], " ")
+      })
+      .collect();
+
+    self.rows.push(row);
+
+    if self.rows.len() >= self.every {
+      self.flush(target);
+    }
+  }
+
+  /**
+   * Write every row held back, with a header first if the widths have changed
+   */
+  pub fn flush(&mut self, target: &mut Box<dyn LogWriter>) {
+    if self.rows.is_empty() {
+      return;
+    }
+
+    let widths: Vec<usize> = self.fields.iter().enumerate()
+      .map(|(index, field)| {
+        self.rows.iter().map(|row| width::width(&row[index])).fold(width::width(field), usize::max)
+      })
+      .collect();
+
+    if self.widths.as_ref() != Some(&widths) {
+      let header: Vec<String> = self.fields.iter().map(|field| field.to_uppercase()).collect();
+
+      Table::write_row(&header, &widths, target);
+      self.widths = Some(widths.clone());
+    }
+
+    for row in self.rows.drain(..) {
+      Table::write_row(&row, &widths, target);
+    }
+  }
+
+  fn write_row(cells: &[String], widths: &[usize], target: &mut Box<dyn LogWriter>) {
+    let last = cells.len() - 1;
+
+    let line: String = cells.iter().zip(widths).enumerate()
+      .map(|(index, (cell, columns))| {
+        // padding the last column would only leave trailing spaces
+        if index == last { cell.clone() } else { format!("{}{GAP}", width::pad(cell, *columns, false)) }
+      })
+      .collect();
+
+    target.write_all(line.as_bytes()).expect("Failed to write");
+    target.end_line();
+  }
+}
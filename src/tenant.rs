@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use regex::{Regex, RegexBuilder};
+use serde_json::{Map, Value};
+
+use crate::patterns;
+use crate::pretty::PrettyDescriptor;
+
+/// the field that holds the tenant, unless --tenant is given field=FIELD
+const DEFAULT_FIELD: &str = "tenant";
+
+const REDACTED: &str = "[REDACTED:tenant]";
+
+/// how many tenants seen too late to be redacted from everything are named when done, the rest are only counted
+const LATE_LISTED: usize = 10;
+
+/**
+ * An extract for one customer, from --tenant NAME[,field=FIELD]. Only events whose FIELD is NAME are kept, and in
+ * those every other tenant is redacted: a FIELD at any depth holding another tenant, and any other tenant named as a
+ * whole word in a string, like "moved from globex". Other tenants are learned from the FIELD of every event read,
+ * including the ones left out, so one that only shows up after events naming it were written can't be redacted from
+ * them. Those are listed when done, along with what was kept, left out and redacted.
+ */
+#[derive(Debug)]
+pub struct Tenant {
+  name: String,
+  field: String,
+  /// each other tenant seen, to how many events had been written when it was first seen
+  others: BTreeMap<String, u64>,
+  /// matches any other tenant, rebuilt when a new one is seen
+  matcher: Option<Regex>,
+  stale: bool,
+  kept: u64,
+  dropped: u64,
+  redacted: u64,
+}
+
+impl Tenant {
+  /**
+   * Parse NAME[,field=FIELD], for example acme or acme,field=org_id
+   */
+  pub fn parse(raw: &str) -> Result<Tenant, String> {
+    let mut parts = raw.split(',');
+
+    let name = parts.next().unwrap_or("").trim().to_string();
+
+    if name.is_empty() {
+      return Err("Argument --tenant must be followed by a NAME, like acme or acme,field=org_id".to_string());
+    }
+
+    let mut field = DEFAULT_FIELD.to_string();
+
+    for part in parts {
+      field = part.trim().strip_prefix("field=")
+        .filter(|field| !field.is_empty())
+        .ok_or_else(|| format!("Tenant option '{part}' is not recognized. The only option is field=FIELD"))?
+        .to_string();
+    }
+
+    Ok(Tenant { name, field, others: BTreeMap::new(), matcher: None, stale: false, kept: 0, dropped: 0, redacted: 0 })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("'{}' by '{}', with every other tenant redacted", self.name, self.field)
+  }
+
+  /**
+   * Keep the event if it belongs to the tenant, with every other tenant redacted from it. Returns false if it must
+   * be left out
+   */
+  pub fn apply(&mut self, values: &mut Map<String, Value>) -> bool {
+    self.learn(values);
+
+    let owned = values.get(&self.field).is_some_and(|value| PrettyDescriptor::pretty_value(value) == self.name);
+
+    if !owned {
+      self.dropped += 1;
+      return false;
+    }
+
+    if self.stale {
+      self.matcher = Some(self.build_matcher());
+      self.stale = false;
+    }
+
+    for (key, value) in values.iter_mut() {
+      // the event's own tenant is the one being kept, so it's the only field not looked at as a tenant
+      if *key != self.field {
+        self.redact(value);
+      }
+    }
+
+    self.kept += 1;
+    true
+  }
+
+  /**
+   * Print what was kept, left out and redacted to stderr, and every tenant that showed up too late to be redacted
+   * from everything before it
+   */
+  pub fn report(&self) {
+    eprintln!(
+      "Tenant '{}': kept {} events, left out {} of other tenants or without '{}', redacted {} mentions of {} other tenants",
+      self.name, self.kept, self.dropped, self.field, self.redacted, self.others.len(),
+    );
+
+    let late: Vec<(&String, &u64)> = self.others.iter().filter(|(_, written)| **written > 0).collect();
+
+    for (other, written) in late.iter().take(LATE_LISTED) {
+      eprintln!("  '{other}' was first seen after {written} events had been written, which weren't checked for it");
+    }
+
+    if late.len() > LATE_LISTED {
+      eprintln!("  and {} more tenants first seen after events had been written", late.len() - LATE_LISTED);
+    }
+  }
+
+  fn learn(&mut self, values: &Map<String, Value>) {
+    for (key, value) in values {
+      match value {
+        Value::Object(fields) => self.learn(fields),
+        Value::Array(items) => items.iter()
+          .filter_map(Value::as_object)
+          .for_each(|fields| self.learn(fields)),
+        Value::String(_) | Value::Number(_) if *key == self.field => {
+          let tenant = PrettyDescriptor::pretty_value(value);
+
+          if tenant != self.name && !self.others.contains_key(&tenant) {
+            self.others.insert(tenant, self.kept);
+            self.stale = true;
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+
+  fn build_matcher(&self) -> Regex {
+    // longest first, so a tenant that starts with the name of another is matched whole
+    let mut others: Vec<&String> = self.others.keys().collect();
+    others.sort_by_key(|other| std::cmp::Reverse(other.len()));
+
+    let alternatives: Vec<String> = others.iter().map(|other| regex::escape(other)).collect();
+
+    // not cached like other patterns, since it's replaced each time another tenant is seen
+    let mut builder = RegexBuilder::new(&alternatives.join("|"));
+
+    if let Some(size) = patterns::options().size_limit() {
+      builder.size_limit(size);
+    }
+
+    builder.build().expect("Too many tenants to redact, raise the limit with --regex-options size=SIZE")
+  }
+
+  fn redact(&mut self, value: &mut Value) {
+    match value {
+      Value::Object(fields) => {
+        for (key, item) in fields.iter_mut() {
+          if *key == self.field && !item.is_object() && !item.is_array() {
+            if PrettyDescriptor::pretty_value(item) != self.name {
+              *item = Value::String(REDACTED.to_string());
+              self.redacted += 1;
+            }
+          } else {
+            self.redact(item);
+          }
+        }
+      }
+      Value::Array(items) => items.iter_mut().for_each(|item| self.redact(item)),
+      Value::String(text) => {
+        if let Some(redacted) = self.redact_text(text) {
+          *text = redacted;
+        }
+      }
+      _ => {}
+    }
+  }
+
+  /**
+   * The text with every other tenant that is a whole word in it redacted, or None if there were none
+   */
+  fn redact_text(&mut self, text: &str) -> Option<String> {
+    let matcher = self.matcher.as_ref()?;
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut redacted = String::new();
+    let mut copied = 0;
+
+    for found in matcher.find_iter(text) {
+      let before = text[..found.start()].chars().next_back();
+      let after = text[found.end()..].chars().next();
+
+      if before.is_some_and(is_word) || after.is_some_and(is_word) {
+        continue;
+      }
+
+      redacted.push_str(&text[copied..found.start()]);
+      redacted.push_str(REDACTED);
+      copied = found.end();
+      self.redacted += 1;
+    }
+
+    if copied == 0 {
+      return None;
+    }
+
+    redacted.push_str(&text[copied..]);
+    Some(redacted)
+  }
+}
@@ -0,0 +1,54 @@
+use datetime::{ISO, LocalDateTime};
+use serde_json::{Map, Value};
+
+/**
+ * How 'time' is written, from --normalize-time, whatever way each source wrote it. Times are already in UTC once
+ * they're read, so every event comes out the same way
+ */
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeOutput {
+  /// milliseconds since the epoch, as a number
+  EpochMillis,
+  /// whole seconds since the epoch, as a number
+  Epoch,
+  /// like 2024-06-01T10:00:00.000Z
+  Rfc3339,
+}
+
+impl TimeOutput {
+  pub fn parse(raw: &str) -> Result<TimeOutput, String> {
+    match raw {
+      "epoch-millis" => Ok(TimeOutput::EpochMillis),
+      "epoch" => Ok(TimeOutput::Epoch),
+      "rfc3339" => Ok(TimeOutput::Rfc3339),
+      _ => Err(format!("Argument --normalize-time '{raw}' is not recognized. Use epoch-millis, epoch or rfc3339")),
+    }
+  }
+
+  pub fn describe(&self) -> &'static str {
+    match self {
+      TimeOutput::EpochMillis => "milliseconds since the epoch",
+      TimeOutput::Epoch => "seconds since the epoch",
+      TimeOutput::Rfc3339 => "RFC 3339 in UTC",
+    }
+  }
+
+  /**
+   * Write TIME into 'time'. Events without a time are left as they are
+   */
+  pub fn apply(&self, values: &mut Map<String, Value>, time: Option<LocalDateTime>) {
+    let Some(time) = time else {
+      return;
+    };
+
+    let instant = time.to_instant();
+
+    let written = match self {
+      TimeOutput::EpochMillis => Value::from(instant.seconds() * 1000 + instant.milliseconds() as i64),
+      TimeOutput::Epoch => Value::from(instant.seconds()),
+      TimeOutput::Rfc3339 => Value::String(format!("{}Z", time.iso())),
+    };
+
+    values.insert("time".to_string(), written);
+  }
+}
@@ -0,0 +1,122 @@
+use serde_json::{Map, Value};
+
+pub const TRACE_FIELD: &str = "trace_id";
+pub const SPAN_FIELD: &str = "span_id";
+
+/// the W3C header, which holds both ids as 'version-trace-span-flags'
+const TRACEPARENT_FIELDS: [&str; 2] = ["traceparent", "traceParent"];
+
+/// names used by common tracing libraries and vendors, checked in order after traceparent
+const TRACE_FIELDS: [&str; 8] = [TRACE_FIELD, "traceId", "traceID", "trace.id", "otelTraceID", "dd.trace_id", "X-B3-TraceId", "x-b3-traceid"];
+const SPAN_FIELDS: [&str; 8] = [SPAN_FIELD, "spanId", "spanID", "span.id", "otelSpanID", "dd.span_id", "X-B3-SpanId", "x-b3-spanid"];
+
+/// ids are hex, 32 digits for a trace and 16 for a span
+const TRACE_DIGITS: usize = 32;
+const SPAN_DIGITS: usize = 16;
+
+/**
+ * Finds trace and span ids under whatever name an event uses and writes them as 'trace_id' and 'span_id',
+ * optionally dropping every event that isn't part of one trace
+ */
+#[derive(Debug, Default)]
+pub struct TraceIds {
+  only: Option<String>,
+}
+
+impl TraceIds {
+  /**
+   * Only keep events from this trace, given in any form an event could hold it
+   */
+  pub fn only(raw: &str) -> Result<TraceIds, String> {
+    let id = normalize(&Value::String(raw.to_string()), TRACE_DIGITS)
+      .ok_or_else(|| format!("Argument --trace '{raw}' is not a trace id. It must be up to 32 hex digits"))?;
+
+    Ok(TraceIds { only: Some(id) })
+  }
+
+  pub fn describe(&self) -> String {
+    match &self.only {
+      Some(id) => format!("only {id}, ids copied to {TRACE_FIELD} and {SPAN_FIELD}"),
+      None => format!("ids copied to {TRACE_FIELD} and {SPAN_FIELD}"),
+    }
+  }
+
+  /**
+   * Add the canonical fields to an event, returning false if it should be dropped by --trace.
+   * Original fields are left as they were, and ids that aren't valid hex are ignored.
+   */
+  pub fn apply(&self, values: &mut Map<String, Value>) -> bool {
+    let (trace, span) = extract(values);
+
+    if let Some(trace) = &trace {
+      values.insert(TRACE_FIELD.to_string(), Value::String(trace.clone()));
+    }
+
+    if let Some(span) = span {
+      values.insert(SPAN_FIELD.to_string(), Value::String(span));
+    }
+
+    self.only.as_ref().is_none_or(|only| trace.as_ref() == Some(only))
+  }
+}
+
+fn extract(values: &Map<String, Value>) -> (Option<String>, Option<String>) {
+  let parent = TRACEPARENT_FIELDS.iter()
+    .find_map(|field| values.get(*field)?.as_str().and_then(parse_traceparent));
+
+  if let Some((trace, span)) = parent {
+    return (Some(trace), Some(span));
+  }
+
+  let find = |fields: &[&str], digits: usize| fields.iter().find_map(|field| {
+    let value = values.get(*field)?;
+
+    // Datadog writes its ids in decimal, even when they are strings
+    match value.as_str() {
+      Some(raw) if field.starts_with("dd.") => normalize(&Value::from(raw.trim().parse::<u64>().ok()?), digits),
+      _ => normalize(value, digits),
+    }
+  });
+
+  (find(&TRACE_FIELDS, TRACE_DIGITS), find(&SPAN_FIELDS, SPAN_DIGITS))
+}
+
+/**
+ * Split a W3C traceparent like '00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01' into its trace and span
+ */
+fn parse_traceparent(raw: &str) -> Option<(String, String)> {
+  let mut parts = raw.trim().split('-');
+
+  let _version = parts.next()?;
+  let trace = normalize(&Value::String(parts.next()?.to_string()), TRACE_DIGITS)?;
+  let span = normalize(&Value::String(parts.next()?.to_string()), SPAN_DIGITS)?;
+
+  Some((trace, span))
+}
+
+/**
+ * Lower case hex padded to the full width, so ids from every library compare equal. Shorter hex ids, like the
+ * 64 bit trace ids of B3, are padded with zeros the way W3C says to, and numbers, like Datadog's, become hex.
+ * All zeros means no id.
+ */
+fn normalize(value: &Value, digits: usize) -> Option<String> {
+  let hex = match value {
+    Value::String(raw) => {
+      let raw = raw.trim();
+
+      if raw.is_empty() || raw.len() > digits || !raw.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+      }
+
+      raw.to_ascii_lowercase()
+    }
+    Value::Number(num) => format!("{:x}", num.as_u64()?),
+    _ => return None,
+  };
+
+  if hex.bytes().all(|byte| byte == b'0') {
+    return None;
+  }
+
+  Some(format!("{hex:0>digits$}"))
+}
@@ -1,28 +1,86 @@
+use std::fmt;
+use std::sync::Arc;
+
 use serde_json::{Map, Value};
+use crate::audit::RuleCounts;
+use crate::filter::{Filter, FilterSet};
+use crate::patterns;
 use crate::PrettyDescriptor;
 
 #[derive(Debug)]
 pub struct Translation {
   output: String,
+  raw: String,
   pattern: PrettyDescriptor,
+  /// only translate events matching this filter, from --translate-if
+  condition: Option<Filter>,
+  counts: Arc<RuleCounts>,
+}
+
+impl fmt::Display for Translation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} = \"{}\"", self.output, self.raw)?;
+
+    if let Some(condition) = &self.condition {
+      write!(f, " if {condition}")?;
+    }
+
+    Ok(())
+  }
 }
 
 impl Translation {
 
-  pub fn parse(output: String, raw: &str) -> Translation {
-    Translation {
+  pub fn parse(output: String, raw: &str) -> Result<Translation, String> {
+    Ok(Translation {
       output,
-      pattern: PrettyDescriptor::parse(raw),
-    }
+      raw: raw.to_string(),
+      pattern: patterns::pretty(raw)?,
+      condition: None,
+      counts: Arc::default(),
+    })
+  }
+
+  pub fn parse_if(filter: &str, output: String, raw: &str) -> Result<Translation, String> {
+    Ok(Translation {
+      condition: Some(FilterSet::parse(filter)?),
+      ..Translation::parse(output, raw)?
+    })
+  }
+
+  /**
+   * Every field the translation reads, in its pattern and its condition
+   */
+  pub fn variables(&self) -> Vec<String> {
+    let mut names = self.pattern.variables();
+    names.extend(self.condition.iter().map(|condition| condition.key().to_string()));
+    names
+  }
+
+  pub fn counts(&self) -> Arc<RuleCounts> {
+    self.counts.clone()
   }
 
   pub fn translate(&self, values: &mut Map<String, Value>) {
+    if let Some(condition) = &self.condition {
+      if !condition.matches(values) {
+        return;
+      }
+    }
+
     let result = self.pattern.print_to_string(values);
 
     if result.trim().is_empty() {
-      values.remove(&self.output);
+      let removed = values.remove(&self.output).is_some();
+
+      self.counts.record(removed, removed);
     } else {
-      values.insert(self.output.clone(), Value::String(result));
+      let result = Value::String(result);
+      let changed = values.get(&self.output) != Some(&result);
+
+      values.insert(self.output.clone(), result);
+
+      self.counts.record(changed, false);
     }
   }
 }
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{Map, Value};
+
+use crate::chunk::{ChunkInfo, ChunkUnit};
+
+/// where the fields that were cut short are listed, each with how many bytes it had
+pub const TRUNCATED_KEY: &str = "_truncated";
+
+/**
+ * Cuts a field down to a number of bytes, for --truncate-field FIELD=SIZE, so one huge stack can't get a whole
+ * archive rejected by a store with a limit on the size of a document. Strings are cut at the last whole character
+ * that fits, and objects and arrays too big are written as JSON and cut the same way. Every field cut short is
+ * listed in '_truncated' with the size it had, so it's clear something is missing
+ */
+#[derive(Debug)]
+pub struct Truncation {
+  field: String,
+  raw_limit: String,
+  limit: usize,
+  truncated: Arc<AtomicU64>,
+}
+
+impl Truncation {
+  /**
+   * Parse FIELD=SIZE, like stack=4kb
+   */
+  pub fn parse(raw: &str) -> Result<Truncation, String> {
+    let (field, size) = raw.split_once('=')
+      .map(|(field, size)| (field.trim(), size.trim()))
+      .filter(|(field, size)| !field.is_empty() && !size.is_empty())
+      .ok_or_else(|| format!("Argument --truncate-field '{raw}' must be in the form FIELD=SIZE, like stack=4kb"))?;
+
+    let limit = ChunkInfo::parse(size)?.limits.iter()
+      .find(|limit| limit.unit == ChunkUnit::Bytes)
+      .map(|limit| limit.value)
+      .ok_or_else(|| format!("Argument --truncate-field '{raw}' must give a size in bytes, like 4kb or 512b"))?;
+
+    Ok(Truncation { field: field.to_string(), raw_limit: size.to_string(), limit, truncated: Arc::default() })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("'{}' to {}", self.field, self.raw_limit)
+  }
+
+  /// how many values were cut short, shared so it can be read once the truncation has been moved to another thread
+  pub fn truncated(&self) -> Arc<AtomicU64> {
+    self.truncated.clone()
+  }
+
+  pub fn field(&self) -> &str {
+    &self.field
+  }
+
+  /**
+   * Cut the field down if it's over the limit
+   */
+  pub fn apply(&self, values: &mut Map<String, Value>) {
+    let Some(value) = values.get_mut(&self.field) else {
+      return;
+    };
+
+    let size = match value {
+      Value::String(text) => text.len(),
+      Value::Array(_) | Value::Object(_) => value.to_string().len(),
+      _ => return,
+    };
+
+    if size <= self.limit {
+      return;
+    }
+
+    let text = match value {
+      Value::String(text) => std::mem::take(text),
+      _ => value.to_string(),
+    };
+
+    let mut end = self.limit;
+
+    while !text.is_char_boundary(end) {
+      end -= 1;
+    }
+
+    *value = Value::String(text[..end].to_string());
+    self.truncated.fetch_add(1, Ordering::Relaxed);
+
+    let marker = values.entry(TRUNCATED_KEY).or_insert_with(|| Value::Object(Map::new()));
+
+    if !marker.is_object() {
+      *marker = Value::Object(Map::new());
+    }
+
+    if let Value::Object(marker) = marker {
+      marker.insert(self.field.clone(), Value::from(size));
+    }
+  }
+}
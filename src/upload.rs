@@ -0,0 +1,246 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::checksum::{Checksum, OutputFile};
+use crate::codec::Codec;
+use crate::exists::OnExists;
+
+/// exit code when any chunk couldn't be compressed or uploaded, EX_IOERR from sysexits
+pub const CHUNK_EXIT_CODE: i32 = 74;
+
+/// how long to wait before the first retry of an upload, doubling with each one after
+const UPLOAD_BACKOFF: Duration = Duration::from_millis(500);
+
+/// the environment variable an --upload-chunk command finds the chunk's path in
+const CHUNK_VARIABLE: &str = "SAW_CHUNK";
+
+/**
+ * How finished chunks of --chunked output are compressed, and uploaded with --upload-chunk, from --chunk-workers
+ * N[,retries=R]. Each chunk is written plain to CHUNK.partial, then handed to one of N workers while the next chunk
+ * is written, so compressing one chunk or waiting on the network for it doesn't hold up the rest. At most N chunks
+ * wait for a worker, after which writing waits for one to finish rather than filling the disk.
+ */
+#[derive(Debug, Clone)]
+pub struct ChunkWorkers {
+  workers: usize,
+  retries: u32,
+  /// run with sh for each chunk once it's compressed, with its path in SAW_CHUNK
+  pub upload: Option<String>,
+  outcome: Arc<ChunkOutcome>,
+}
+
+/**
+ * What happened to the chunks, counted by the workers
+ */
+#[derive(Debug, Default)]
+pub struct ChunkOutcome {
+  finished: AtomicUsize,
+  retries: AtomicUsize,
+  failed: AtomicUsize,
+}
+
+struct Job {
+  staging: PathBuf,
+  target: PathBuf,
+  codec: Codec,
+}
+
+impl Default for ChunkWorkers {
+  fn default() -> ChunkWorkers {
+    ChunkWorkers { workers: 1, retries: 3, upload: None, outcome: Arc::default() }
+  }
+}
+
+impl ChunkWorkers {
+  /**
+   * Parse N[,retries=R], like 4,retries=5
+   */
+  pub fn parse(raw: &str) -> Result<ChunkWorkers, String> {
+    let mut parts = raw.split(',').map(str::trim);
+
+    let workers = parts.next()
+      .and_then(|workers| workers.parse().ok())
+      .filter(|workers| *workers > 0)
+      .ok_or_else(|| format!("Argument --chunk-workers '{raw}' must start with how many workers to use, greater than 0"))?;
+
+    let mut settings = ChunkWorkers { workers, ..ChunkWorkers::default() };
+
+    for part in parts {
+      match part.split_once('=') {
+        Some(("retries", retries)) => {
+          settings.retries = retries.parse()
+            .map_err(|_| format!("Chunk worker retries must be a whole number, but was '{retries}'"))?;
+        }
+        _ => return Err(format!("Chunk worker option '{part}' is not recognized. The only option is retries=R")),
+      }
+    }
+
+    Ok(settings)
+  }
+
+  pub fn describe(&self) -> String {
+    let retries = match &self.upload {
+      Some(upload) => format!(", uploaded with '{upload}' and retried up to {} times", self.retries),
+      None => String::new(),
+    };
+
+    format!("{} workers{retries}", self.workers)
+  }
+
+  pub fn outcome(&self) -> Arc<ChunkOutcome> {
+    self.outcome.clone()
+  }
+
+  pub fn start(&self, on_exists: OnExists, checksum: Option<Checksum>) -> ChunkQueue {
+    let (sender, receiver) = sync_channel::<Job>(self.workers);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let workers = (0..self.workers)
+      .map(|_| {
+        let receiver = receiver.clone();
+        let settings = self.clone();
+
+        thread::spawn(move || settings.work(&receiver, on_exists, checksum))
+      })
+      .collect();
+
+    ChunkQueue { sender: Some(sender), workers, settings: self.clone() }
+  }
+
+  fn work(&self, receiver: &Mutex<Receiver<Job>>, on_exists: OnExists, checksum: Option<Checksum>) {
+    loop {
+      // the lock is only held while waiting, so the others can take the next job while this one works
+      let next = receiver.lock().expect("Chunk queue was poisoned").recv();
+
+      let Ok(job) = next else {
+        return;
+      };
+
+      if self.finish(&job, on_exists, checksum) {
+        self.outcome.finished.fetch_add(1, Ordering::Relaxed);
+      } else {
+        self.outcome.failed.fetch_add(1, Ordering::Relaxed);
+      }
+    }
+  }
+
+  /// compress and upload one chunk, true if it was
+  fn finish(&self, job: &Job, on_exists: OnExists, checksum: Option<Checksum>) -> bool {
+    let compressed = catch_unwind(AssertUnwindSafe(|| compress(job, on_exists, checksum)));
+
+    match compressed {
+      Ok(Ok(())) => {}
+      Ok(Err(err)) => {
+        eprintln!("Error: failed to compress chunk '{}', it was left at '{}': {err}", job.target.display(), job.staging.display());
+        return false;
+      }
+      // the panic has already been printed
+      Err(_) => return false,
+    }
+
+    let Some(upload) = &self.upload else {
+      return true;
+    };
+
+    let mut wait = UPLOAD_BACKOFF;
+
+    for attempt in 0..=self.retries {
+      let status = Command::new("sh")
+        .args(["-c", upload])
+        .env(CHUNK_VARIABLE, &job.target)
+        .status();
+
+      let err = match status {
+        Ok(status) if status.success() => return true,
+        Ok(status) => status.to_string(),
+        Err(err) => err.to_string(),
+      };
+
+      if attempt == self.retries {
+        eprintln!("Error: failed to upload chunk '{}' after {} retries: {err}", job.target.display(), self.retries);
+        break;
+      }
+
+      eprintln!(
+        "Warning: failed to upload chunk '{}', trying again in {}ms ({} of {}): {err}",
+        job.target.display(), wait.as_millis(), attempt + 1, self.retries,
+      );
+
+      self.outcome.retries.fetch_add(1, Ordering::Relaxed);
+      thread::sleep(wait);
+      wait *= 2;
+    }
+
+    false
+  }
+}
+
+fn compress(job: &Job, on_exists: OnExists, checksum: Option<Checksum>) -> io::Result<()> {
+  let mut plain = File::open(&job.staging)?;
+  let target = on_exists.create(&job.target);
+
+  let mut compressed = job.codec.wrap(BufWriter::new(OutputFile::new(target, &job.target, checksum)));
+  io::copy(&mut plain, &mut compressed)?;
+  compressed.flush()?;
+
+  // compressing is only finished once the encoder is dropped
+  drop(compressed);
+
+  fs::remove_file(&job.staging)
+}
+
+impl ChunkOutcome {
+  pub fn failed(&self) -> usize {
+    self.failed.load(Ordering::Relaxed)
+  }
+}
+
+/**
+ * The chunks waiting for a worker. Dropping it waits for every chunk to be finished
+ */
+pub struct ChunkQueue {
+  sender: Option<SyncSender<Job>>,
+  workers: Vec<JoinHandle<()>>,
+  settings: ChunkWorkers,
+}
+
+impl ChunkQueue {
+  /**
+   * Hand over the chunk written plain to STAGING, to be compressed with CODEC into TARGET. Waits while the queue is full
+   */
+  pub fn submit(&self, staging: PathBuf, target: PathBuf, codec: Codec) {
+    if let Some(sender) = &self.sender {
+      sender.send(Job { staging, target, codec }).expect("Every chunk worker stopped");
+    }
+  }
+}
+
+impl Drop for ChunkQueue {
+  fn drop(&mut self) {
+    // the workers stop once the queue is closed and empty
+    self.sender = None;
+
+    for worker in self.workers.drain(..) {
+      let _ = worker.join();
+    }
+
+    let outcome = &self.settings.outcome;
+    let finished = outcome.finished.load(Ordering::Relaxed);
+    let retries = outcome.retries.load(Ordering::Relaxed);
+    let failed = outcome.failed.load(Ordering::Relaxed);
+
+    if self.settings.upload.is_some() {
+      eprintln!("Uploaded {finished} chunks, with {retries} retries, and {failed} failed");
+    } else if failed > 0 {
+      eprintln!("Compressed {finished} chunks, and {failed} failed");
+    }
+  }
+}
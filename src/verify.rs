@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use datetime::{ISO, LocalDateTime};
+
+use crate::log::Line;
+
+/// the exit code used when events were written out of order with --verify-sorted-output fail, EX_DATAERR from sysexits.h
+pub const UNSORTED_EXIT_CODE: i32 = 65;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VerifyAction {
+  /// stop writing at the first event out of order, and exit with an error once what came before is written
+  Fail,
+  /// write everything, and report how many were out of order once done
+  Warn,
+}
+
+impl VerifyAction {
+  pub fn parse(raw: &str) -> Result<VerifyAction, String> {
+    match raw {
+      "fail" => Ok(VerifyAction::Fail),
+      "warn" => Ok(VerifyAction::Warn),
+      _ => Err(format!("Argument --verify-sorted-output '{raw}' is not recognized. Use fail or warn")),
+    }
+  }
+}
+
+/**
+ * Checks that events are written in time order, for --verify-sorted-output, so a bug in merging can't quietly
+ * write an archive that tools which binary search it by time would read wrong. Order is from oldest to newest,
+ * or newest to oldest with --reverse, and events with the same time can come in any order
+ */
+#[derive(Debug)]
+pub struct SortCheck {
+  action: VerifyAction,
+  reverse: bool,
+  /// the time of the last event written, and the source and line it came from
+  last: Option<(LocalDateTime, Arc<str>, u64)>,
+  out_of_order: u64,
+  first: Option<String>,
+}
+
+impl SortCheck {
+  pub fn new(action: VerifyAction, reverse: bool) -> SortCheck {
+    SortCheck { action, reverse, last: None, out_of_order: 0, first: None }
+  }
+
+  pub fn describe(&self) -> String {
+    let order = if self.reverse { "newest to oldest" } else { "oldest to newest" };
+
+    match self.action {
+      VerifyAction::Fail => format!("{order}, stopping at the first event out of order"),
+      VerifyAction::Warn => format!("{order}, warning about events out of order"),
+    }
+  }
+
+  /**
+   * Should this line still be written? Once this returns false it must not be called again
+   */
+  pub fn allow(&mut self, line: &Line) -> bool {
+    let Some(time) = line.time else {
+      return true;
+    };
+
+    if let Some((last, last_file, last_line)) = &self.last {
+      let in_order = if self.reverse { time <= *last } else { time >= *last };
+
+      if !in_order {
+        self.out_of_order += 1;
+
+        if self.first.is_none() {
+          self.first = Some(format!(
+            "{} from '{}' line {} came after {} from '{}' line {}",
+            time.iso(), line.src.file, line.src.line, last.iso(), last_file, last_line,
+          ));
+        }
+
+        if self.action == VerifyAction::Fail {
+          return false;
+        }
+      }
+    }
+
+    self.last = Some((time, line.src.file.clone(), line.src.line));
+    true
+  }
+
+  /**
+   * Print what was out of order to stderr, if anything was. Returns true if saw must fail
+   */
+  pub fn report(&self) -> bool {
+    let Some(first) = &self.first else {
+      return false;
+    };
+
+    match self.action {
+      VerifyAction::Fail => {
+        eprintln!("Output is not in time order, {first}. Stopped writing before it");
+        true
+      }
+      VerifyAction::Warn => {
+        eprintln!("Wrote {} events out of time order, the first was {first}", self.out_of_order);
+        false
+      }
+    }
+  }
+}
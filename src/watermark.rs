@@ -0,0 +1,68 @@
+use datetime::LocalDateTime;
+
+use crate::log::Line;
+use crate::session::parse_duration;
+
+/**
+ * Drops events that are too far behind the stream, for --drop-older-than DURATION with --follow. The watermark is
+ * the newest time of any event read so far less DURATION, so a backlog that's read again, or a source that's caught
+ * up with from far behind, doesn't flood what's reading the live events. Events without a time are always kept, as
+ * there's nothing to tell how old they are.
+ */
+#[derive(Debug)]
+pub struct Watermark {
+  raw: String,
+  age: i64,
+  /// the time of the newest event read, in milliseconds
+  head: Option<i64>,
+  dropped: u64,
+}
+
+impl Watermark {
+  pub fn parse(raw: &str) -> Result<Watermark, String> {
+    Ok(Watermark {
+      raw: raw.trim().to_string(),
+      age: parse_duration(raw, "--drop-older-than")?,
+      head: None,
+      dropped: 0,
+    })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("events more than {} older than the newest one read", self.raw)
+  }
+
+  /// why an event was dropped, for --why
+  pub fn reason(&self) -> String {
+    format!("more than {} older than the newest event", self.raw)
+  }
+
+  /**
+   * Should this event be kept? Moves the watermark along if it's the newest yet, and counts it if not kept
+   */
+  pub fn admit(&mut self, line: &Line) -> bool {
+    let Some(time) = line.time.map(millis) else {
+      return true;
+    };
+
+    let head = self.head.map_or(time, |head| head.max(time));
+    self.head = Some(head);
+
+    if time < head - self.age {
+      self.dropped += 1;
+      return false;
+    }
+
+    true
+  }
+
+  pub fn dropped(&self) -> u64 {
+    self.dropped
+  }
+}
+
+fn millis(time: LocalDateTime) -> i64 {
+  let instant = time.to_instant();
+
+  instant.seconds() * 1000 + instant.milliseconds() as i64
+}
@@ -0,0 +1,53 @@
+use serde_json::Value;
+
+use crate::filter::FilterSet;
+use crate::log::Line;
+
+/// the field --why lists the filters that accepted an event in
+pub const MATCHED_BY_KEY: &str = "_matched_by";
+
+/**
+ * How much --why explains. Every written event says which filters accepted it, and with drops every event left out
+ * by a time range, trace, shard or filter is also written to stderr with what left it out
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Why {
+  Matched,
+  Drops,
+}
+
+impl Why {
+  pub fn drops(&self) -> bool {
+    *self == Why::Drops
+  }
+}
+
+/**
+ * Note on stderr that a stage left out this event, and why
+ */
+pub fn dropped(stage: &str, reason: &str, line: &Line) {
+  let event = serde_json::to_string(&line.value).unwrap_or_default();
+
+  eprintln!("Dropped by {stage} ({reason}): {event}");
+}
+
+/**
+ * Check the filter, listing what accepted the event in its _matched_by field, or noting what didn't with drops
+ */
+pub fn filter(filter: &FilterSet, why: Why, mut line: Line) -> Option<Line> {
+  match filter.explain(&line.value) {
+    Ok(reasons) => {
+      let reasons = reasons.into_iter().map(Value::String).collect();
+
+      line.value.insert(MATCHED_BY_KEY.to_string(), Value::Array(reasons));
+      Some(line)
+    }
+    Err(reason) => {
+      if why.drops() {
+        dropped("--filter", &reason, &line);
+      }
+
+      None
+    }
+  }
+}
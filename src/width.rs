@@ -0,0 +1,109 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// written in place of what %truncate cuts off
+const ELLIPSIS: char = '…';
+
+/**
+ * How many columns TEXT takes up in a terminal, which is neither its bytes nor its chars: CJK and emoji take two,
+ * and combining accents and zero width joiners take none
+ */
+pub fn width(text: &str) -> usize {
+  UnicodeWidthStr::width(text)
+}
+
+fn char_width(c: char) -> usize {
+  UnicodeWidthChar::width(c).unwrap_or(0)
+}
+
+/**
+ * The longest start of TEXT that fits in COLUMNS, never splitting a char or leaving an accent without the letter
+ * it goes on
+ */
+fn fit(text: &str, columns: usize) -> &str {
+  let mut used = 0;
+
+  for (index, c) in text.char_indices() {
+    used += char_width(c);
+
+    if used > columns {
+      return &text[..index];
+    }
+  }
+
+  text
+}
+
+/**
+ * TEXT cut down to COLUMNS, ending in an ellipsis when anything was cut
+ */
+pub fn truncate(text: &str, columns: usize) -> String {
+  if width(text) <= columns {
+    return text.to_string();
+  }
+
+  if columns == 0 {
+    return String::new();
+  }
+
+  let mut cut = fit(text, columns - 1).to_string();
+  cut.push(ELLIPSIS);
+  cut
+}
+
+/**
+ * TEXT with spaces after it, or before it when RIGHT, to fill COLUMNS. Text already as wide is left as it is
+ */
+pub fn pad(text: &str, columns: usize, right: bool) -> String {
+  let spaces = " ".repeat(columns.saturating_sub(width(text)));
+
+  if right { format!("{spaces}{text}") } else { format!("{text}{spaces}") }
+}
+
+/**
+ * TEXT broken into lines of at most COLUMNS, between words where it can be and within a word that's wider than a
+ * whole line. Newlines already in TEXT are kept
+ */
+pub fn wrap(text: &str, columns: usize) -> String {
+  let columns = columns.max(1);
+  let mut lines: Vec<String> = vec![];
+
+  for paragraph in text.split('\n') {
+    let mut line = String::new();
+
+    for word in paragraph.split(' ').filter(|word| !word.is_empty()) {
+      let needed = if line.is_empty() { width(word) } else { width(&line) + 1 + width(word) };
+
+      if needed <= columns {
+        if !line.is_empty() {
+          line.push(' ');
+        }
+
+        line.push_str(word);
+        continue;
+      }
+
+      if !line.is_empty() {
+        lines.push(std::mem::take(&mut line));
+      }
+
+      let mut rest = word;
+
+      while width(rest) > columns {
+        // a char wider than a whole line still has to go somewhere
+        let piece = match fit(rest, columns) {
+          "" => &rest[..rest.chars().next().map_or(0, char::len_utf8)],
+          piece => piece,
+        };
+
+        lines.push(piece.to_string());
+        rest = &rest[piece.len()..];
+      }
+
+      line.push_str(rest);
+    }
+
+    lines.push(line);
+  }
+
+  lines.join("\n")
+}
@@ -0,0 +1,382 @@
+use std::fs;
+use std::path::PathBuf;
+
+use datetime::{DatePiece, LocalDate, LocalDateTime, LocalTime, Month, Weekday, Year};
+
+/// where zones are read from unless TZDIR says otherwise
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+/**
+ * The zone days and hours start in for --daily, --split-by-time and --summary-by, from --timezone. Times are read as
+ * UTC, or moved to it when they have an offset, so without a zone every day starts at midnight UTC. A named zone
+ * like Europe/Berlin is read from the system's zoneinfo, with every change of offset it has had, and the rule it
+ * gives for daylight saving past the last one
+ */
+#[derive(Debug, Clone)]
+pub struct TimeZone {
+  name: String,
+  /// seconds east of UTC before the first transition
+  initial: i64,
+  /// (UTC seconds, seconds east of UTC from then on), in order
+  transitions: Vec<(i64, i64)>,
+  /// how the offset changes after the last transition
+  rule: Option<Rule>,
+}
+
+/**
+ * A TZ string like 'CET-1CEST,M3.5.0,M10.5.0/3', from the end of a zoneinfo file
+ */
+#[derive(Debug, Clone)]
+struct Rule {
+  standard: i64,
+  daylight: Option<(i64, Change, Change)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Day {
+  /// Mm.w.d, day d (0 is Sunday) of week w of month m, where week 5 is the last
+  Weekday { month: i8, week: i64, weekday: i64 },
+  /// Jn, day n from 1 to 365, never counting February 29
+  Julian(i64),
+  /// n, day n from 0 to 365, counting February 29
+  Ordinal(i64),
+}
+
+/// a day and the seconds after its midnight, in the time that was in effect before
+#[derive(Debug, Clone, Copy)]
+struct Change {
+  day: Day,
+  time: i64,
+}
+
+impl TimeZone {
+  pub fn utc() -> TimeZone {
+    TimeZone::fixed("UTC", 0)
+  }
+
+  /**
+   * Parse UTC, an offset like +05:30 or -0800, local for the zone of this machine, or a name from the zoneinfo
+   * database like America/New_York
+   */
+  pub fn parse(raw: &str) -> Result<TimeZone, String> {
+    let raw = raw.trim();
+
+    if raw.eq_ignore_ascii_case("utc") || raw == "Z" {
+      return Ok(TimeZone::utc());
+    }
+
+    if raw.starts_with(['+', '-']) {
+      let offset = parse_offset(&raw[1..])
+        .ok_or_else(|| format!("Time zone '{raw}' must be an offset like +02:00 or -0530, or a name like Europe/Berlin"))?;
+
+      return Ok(TimeZone::fixed(raw, if raw.starts_with('-') { -offset } else { offset }));
+    }
+
+    let path = if raw == "local" {
+      PathBuf::from("/etc/localtime")
+    } else {
+      if raw.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+        return Err(format!("Time zone '{raw}' is not a valid zone name, like Europe/Berlin"));
+      }
+
+      std::env::var_os("TZDIR").map_or_else(|| PathBuf::from(ZONEINFO), PathBuf::from).join(raw)
+    };
+
+    let bytes = fs::read(&path).map_err(|err| format!("Time zone '{raw}' could not be read from {}: {err}", path.display()))?;
+
+    TimeZone::from_tzif(raw, &bytes).ok_or_else(|| format!("Time zone file {} is not a valid zoneinfo file", path.display()))
+  }
+
+  fn fixed(name: &str, offset: i64) -> TimeZone {
+    TimeZone { name: name.to_string(), initial: offset, transitions: vec![], rule: None }
+  }
+
+  pub fn is_utc(&self) -> bool {
+    self.initial == 0 && self.transitions.is_empty() && self.rule.is_none()
+  }
+
+  pub fn describe(&self) -> String {
+    if self.is_utc() {
+      "UTC".to_string()
+    } else {
+      self.name.clone()
+    }
+  }
+
+  /**
+   * The same moment as the wall clock in this zone shows it
+   */
+  pub fn to_local(&self, time: LocalDateTime) -> LocalDateTime {
+    time.add_seconds(self.offset_at(time.to_instant().seconds()))
+  }
+
+  /// seconds east of UTC at this many seconds since the epoch
+  fn offset_at(&self, seconds: i64) -> i64 {
+    let passed = self.transitions.partition_point(|(at, _)| *at <= seconds);
+
+    if passed == self.transitions.len() {
+      if let Some(rule) = &self.rule {
+        return rule.offset_at(seconds);
+      }
+    }
+
+    match passed {
+      0 => self.initial,
+      _ => self.transitions[passed - 1].1,
+    }
+  }
+
+  /**
+   * Read a compiled zoneinfo file, version 1 or the 64 bit data of version 2 and later
+   */
+  fn from_tzif(name: &str, bytes: &[u8]) -> Option<TimeZone> {
+    let (version, counts) = tzif_header(bytes)?;
+
+    if version < b'2' {
+      return TimeZone::from_tzif_data(name, &bytes[44..], counts, 4, None);
+    }
+
+    // skip the 32 bit data, which version 2 repeats with 64 bit times after a second header
+    let rest = bytes.get(44 + data_len(counts, 4)..)?;
+    let (_, counts) = tzif_header(rest)?;
+    let data = &rest[44..];
+
+    let footer = data.get(data_len(counts, 8)..)
+      .and_then(|footer| std::str::from_utf8(footer).ok())
+      .map(|footer| footer.trim_matches('\n'))
+      .filter(|footer| !footer.is_empty())
+      .and_then(Rule::parse);
+
+    TimeZone::from_tzif_data(name, data, counts, 8, footer)
+  }
+
+  fn from_tzif_data(name: &str, data: &[u8], counts: [usize; 6], time_size: usize, rule: Option<Rule>) -> Option<TimeZone> {
+    let [_, _, _, time_count, type_count, _] = counts;
+
+    if data.len() < data_len(counts, time_size) || type_count == 0 {
+      return None;
+    }
+
+    let times = &data[..time_count * time_size];
+    let indexes = &data[time_count * time_size..time_count * (time_size + 1)];
+    let types = &data[time_count * (time_size + 1)..time_count * (time_size + 1) + type_count * 6];
+
+    let offset = |index: usize| -> Option<i64> {
+      let info = types.get(index * 6..index * 6 + 4)?;
+
+      Some(i32::from_be_bytes(info.try_into().ok()?) as i64)
+    };
+
+    let transitions = times.chunks(time_size).zip(indexes)
+      .map(|(time, index)| {
+        let at = match time_size {
+          4 => i32::from_be_bytes(time.try_into().ok()?) as i64,
+          _ => i64::from_be_bytes(time.try_into().ok()?),
+        };
+
+        Some((at, offset(*index as usize)?))
+      })
+      .collect::<Option<Vec<_>>>()?;
+
+    // before the first transition the first type that isn't daylight saving applies, or the first one if all are
+    let initial = (0..type_count).find(|index| types[index * 6 + 4] == 0).unwrap_or(0);
+
+    Some(TimeZone { name: name.to_string(), initial: offset(initial)?, transitions, rule })
+  }
+}
+
+/// the version and the six counts of a zoneinfo header
+fn tzif_header(bytes: &[u8]) -> Option<(u8, [usize; 6])> {
+  if bytes.len() < 44 || &bytes[..4] != b"TZif" {
+    return None;
+  }
+
+  let mut counts = [0; 6];
+
+  for (index, count) in counts.iter_mut().enumerate() {
+    let start = 20 + index * 4;
+    *count = u32::from_be_bytes(bytes[start..start + 4].try_into().ok()?) as usize;
+  }
+
+  Some((bytes[4], counts))
+}
+
+/// bytes of data after a header, from its counts of utc flags, standard flags, leap seconds, transitions, types and
+/// characters of abbreviations
+fn data_len(counts: [usize; 6], time_size: usize) -> usize {
+  let [utc_count, std_count, leap_count, time_count, type_count, char_count] = counts;
+
+  time_count * (time_size + 1) + type_count * 6 + char_count + leap_count * (time_size + 4) + std_count + utc_count
+}
+
+impl Rule {
+  fn parse(raw: &str) -> Option<Rule> {
+    let mut rest = raw;
+
+    skip_name(&mut rest)?;
+    // TZ strings count west of UTC as positive
+    let standard = -take_offset(&mut rest)?;
+
+    if rest.is_empty() {
+      return Some(Rule { standard, daylight: None });
+    }
+
+    skip_name(&mut rest)?;
+
+    let daylight = if rest.starts_with(',') {
+      standard + 3600
+    } else {
+      -take_offset(&mut rest)?
+    };
+
+    let (start, end) = rest.strip_prefix(',')?.split_once(',')?;
+
+    Some(Rule { standard, daylight: Some((daylight, Change::parse(start)?, Change::parse(end)?)) })
+  }
+
+  fn offset_at(&self, seconds: i64) -> i64 {
+    let Some((daylight, start, end)) = self.daylight else {
+      return self.standard;
+    };
+
+    let year = LocalDateTime::at(seconds + self.standard).year();
+
+    // the start is given in standard time and the end in daylight saving time
+    let starts = start.local_seconds(year) - self.standard;
+    let ends = end.local_seconds(year) - daylight;
+
+    let in_daylight = if starts < ends {
+      starts <= seconds && seconds < ends
+    } else {
+      // south of the equator daylight saving runs over the new year
+      !(ends <= seconds && seconds < starts)
+    };
+
+    if in_daylight { daylight } else { self.standard }
+  }
+}
+
+impl Change {
+  fn parse(raw: &str) -> Option<Change> {
+    let (day, time) = match raw.split_once('/') {
+      Some((day, time)) => (day, signed_offset(time)?),
+      None => (raw, 2 * 3600),
+    };
+
+    let day = if let Some(rest) = day.strip_prefix('M') {
+      let mut parts = rest.split('.').map(|part| part.parse::<i64>().ok());
+      let (month, week, weekday) = (parts.next()??, parts.next()??, parts.next()??);
+
+      if !(1..=12).contains(&month) || !(1..=5).contains(&week) || !(0..=6).contains(&weekday) || parts.next().is_some() {
+        return None;
+      }
+
+      Day::Weekday { month: month as i8, week, weekday }
+    } else if let Some(rest) = day.strip_prefix('J') {
+      Day::Julian(rest.parse().ok().filter(|day| (1..=365).contains(day))?)
+    } else {
+      Day::Ordinal(day.parse().ok().filter(|day| (0..=365).contains(day))?)
+    };
+
+    Some(Change { day, time })
+  }
+
+  /// when this change happens in a year, as seconds since the epoch on the wall clock
+  fn local_seconds(&self, year: i64) -> i64 {
+    let date = match self.day {
+      Day::Weekday { month, week, weekday } => {
+        let month = Month::from_one(month).unwrap();
+        let first = LocalDate::ymd(year, month, 1).unwrap();
+        let first_weekday = weekday_number(first.weekday());
+        let days = month.days_in_month(Year(year).is_leap_year());
+
+        let mut day = 1 + (weekday - first_weekday).rem_euclid(7) + 7 * (week - 1);
+
+        // week 5 is the last one, which might only have four
+        while day > days as i64 {
+          day -= 7;
+        }
+
+        LocalDate::ymd(year, month, day as i8).unwrap()
+      }
+      Day::Julian(day) => {
+        LocalDate::yd(year, if Year(year).is_leap_year() && day >= 60 { day + 1 } else { day }).unwrap()
+      }
+      Day::Ordinal(day) => LocalDate::yd(year, day + 1).unwrap(),
+    };
+
+    LocalDateTime::new(date, LocalTime::midnight()).add_seconds(self.time).to_instant().seconds()
+  }
+}
+
+fn weekday_number(weekday: Weekday) -> i64 {
+  match weekday {
+    Weekday::Sunday => 0,
+    Weekday::Monday => 1,
+    Weekday::Tuesday => 2,
+    Weekday::Wednesday => 3,
+    Weekday::Thursday => 4,
+    Weekday::Friday => 5,
+    Weekday::Saturday => 6,
+  }
+}
+
+/// skip an abbreviation like CET or <+0530>
+fn skip_name(rest: &mut &str) -> Option<()> {
+  let len = if rest.starts_with('<') {
+    rest.find('>')? + 1
+  } else {
+    rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len())
+  };
+
+  if len < 3 {
+    return None;
+  }
+
+  *rest = &rest[len..];
+  Some(())
+}
+
+/// take a signed offset like -1 or 5:30 off the front
+fn take_offset(rest: &mut &str) -> Option<i64> {
+  let len = rest.find(|c: char| !(c.is_ascii_digit() || matches!(c, '+' | '-' | ':'))).unwrap_or(rest.len());
+  let offset = signed_offset(&rest[..len])?;
+
+  *rest = &rest[len..];
+  Some(offset)
+}
+
+fn signed_offset(raw: &str) -> Option<i64> {
+  match raw.strip_prefix('-') {
+    Some(rest) => Some(-hms(rest.strip_prefix('+').unwrap_or(rest))?),
+    None => hms(raw.strip_prefix('+').unwrap_or(raw)),
+  }
+}
+
+/// hh[:mm[:ss]] as seconds
+fn hms(raw: &str) -> Option<i64> {
+  let mut seconds = 0;
+  let mut parts = 0;
+
+  for (index, part) in raw.split(':').enumerate() {
+    if index > 2 || part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+      return None;
+    }
+
+    seconds += part.parse::<i64>().ok()? * [3600, 60, 1][index];
+    parts += 1;
+  }
+
+  (parts > 0).then_some(seconds)
+}
+
+/// hh:mm or hhmm, as written after the sign of --timezone
+fn parse_offset(raw: &str) -> Option<i64> {
+  let digits: String = raw.chars().filter(|c| *c != ':').collect();
+
+  if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+
+  Some(digits[..2].parse::<i64>().ok()? * 3600 + digits[2..].parse::<i64>().ok()? * 60)
+}
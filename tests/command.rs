@@ -0,0 +1,89 @@
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// far more than a pipe holds, so a merge that stops reading stdout while it waits on stderr would hang
+const LINES: usize = 20_000;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/**
+ * Run saw with ARGS, killing it if it hasn't finished within TIMEOUT, and return how many lines it wrote
+ */
+fn count_lines(args: &[&str]) -> usize {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(args)
+    .args(["--zip", "false", "--json", "true"])
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .expect("Failed to run saw");
+
+  let mut stdout = child.stdout.take().unwrap();
+  let reader = std::thread::spawn(move || {
+    let mut out = String::new();
+    stdout.read_to_string(&mut out).unwrap();
+    out
+  });
+
+  let start = Instant::now();
+
+  while child.try_wait().unwrap().is_none() {
+    if start.elapsed() > TIMEOUT {
+      let _ = child.kill();
+      panic!("saw {args:?} did not finish within {TIMEOUT:?}");
+    }
+
+    sleep(Duration::from_millis(50));
+  }
+
+  reader.join().unwrap().lines().count()
+}
+
+fn events(to_stderr: bool) -> String {
+  let tee = if to_stderr { " | tee /dev/stderr" } else { "" };
+
+  format!(r#"seq 1 {LINES} | sed 's/.*/{{"time":"2024-01-01T00:00:00.000Z","message":"&"}}/'{tee}"#)
+}
+
+#[test]
+fn command_with_large_stdout_does_not_hang() {
+  assert_eq!(count_lines(&["--command", &events(false)]), LINES);
+}
+
+#[test]
+fn command_with_stderr_with_large_output_does_not_hang() {
+  assert_eq!(count_lines(&["--command-with-stderr", &events(true)]), LINES * 2);
+}
+
+#[test]
+fn bad_arguments_are_reported_without_panicking() {
+  let output = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(["--command", "true", "--shard", "9/8", "--rate-limit", "fast"])
+    .stdin(Stdio::null())
+    .output()
+    .expect("Failed to run saw");
+
+  let stderr = String::from_utf8_lossy(&output.stderr);
+
+  assert_eq!(output.status.code(), Some(2));
+  assert!(stderr.starts_with("Found 2 problems with the arguments:"), "{stderr}");
+  assert!(!stderr.contains("panicked"), "{stderr}");
+}
+
+#[test]
+fn argument_errors_only_name_arguments_that_were_passed() {
+  let output = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(["--command", "true", "--group-by", "level", "--json", "true"])
+    .stdin(Stdio::null())
+    .output()
+    .expect("Failed to run saw");
+
+  let stderr = String::from_utf8_lossy(&output.stderr);
+
+  assert_eq!(output.status.code(), Some(2));
+  assert!(stderr.contains("--group-by"), "{stderr}");
+  assert!(!stderr.contains("--pretty"), "{stderr}");
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::process::{Command, Stdio};
+
+/**
+ * Run saw on FILES written to a scratch directory, plus ARGS, and return what it wrote
+ */
+fn run(name: &str, files: &[&str], args: &[&str]) -> String {
+  let dir = std::env::temp_dir().join(format!("saw-concat-{}-{name}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+
+  let paths: Vec<_> = files.iter().enumerate().map(|(index, contents)| {
+    let path = dir.join(format!("{index}.log"));
+    fs::write(&path, contents).unwrap();
+    path
+  }).collect();
+
+  let output = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(&paths)
+    .args(args)
+    .args(["--zip", "false", "--json", "true"])
+    .stdin(Stdio::null())
+    .stderr(Stdio::null())
+    .output()
+    .expect("Failed to run saw");
+
+  let _ = fs::remove_dir_all(&dir);
+
+  String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn keep_order_does_not_copy_lines_a_merge_would_drop_or_reformat() {
+  let file = "{\"message\":\"a\",\"time\":\"2024-01-01T00:00:00Z\"}\nnot json\n{ \"message\" : \"b\" }\n";
+
+  let fast = run("keep-order", &[file, file], &["--keep-order-of-input"]);
+  let merged = run("keep-order-compact", &[file, file], &["--keep-order-of-input", "--compact"]);
+
+  assert_eq!(fast, merged);
+  assert!(!fast.contains("not json"), "{fast}");
+}
+
+#[test]
+fn sources_that_do_not_overlap_are_written_as_a_merge_would() {
+  let early = "{\"message\":\"a\",\"time\":\"2024-01-01T00:00:00Z\"}\n{\"message\":\"b\",\"time\":\"2024-01-01T00:00:01Z\"}\n";
+  let late = "{\"message\":\"c\",\"time\":\"2024-01-02T00:00:00Z\"}\n";
+
+  // given latest first, so they're only in order if the files are put in order by their times
+  let fast = run("apart", &[late, early], &[]);
+  let merged = run("apart-compact", &[late, early], &["--compact"]);
+
+  assert_eq!(fast, merged);
+  assert_eq!(fast, format!("{early}{late}"));
+}
+
+#[test]
+fn sources_that_overlap_are_merged() {
+  let first = "{\"message\":\"a\",\"time\":\"2024-01-01T00:00:00Z\"}\n{\"message\":\"c\",\"time\":\"2024-01-01T00:00:02Z\"}\n";
+  let second = "{\"message\":\"b\",\"time\":\"2024-01-01T00:00:01Z\"}\n";
+
+  let messages: Vec<String> = run("overlap", &[first, second], &[]).lines()
+    .map(|line| line.split('"').nth(3).unwrap_or_default().to_string())
+    .collect();
+
+  assert_eq!(messages, ["a", "b", "c"]);
+}
@@ -0,0 +1,71 @@
+#![cfg(unix)]
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// enough that reading them all takes far longer than stopping should
+const LINES: u64 = 500_000;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[test]
+fn stop_ends_a_run_whose_filter_leaves_out_everything() {
+  let dir = std::env::temp_dir().join(format!("saw-control-{}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+
+  let mut events = String::new();
+
+  for i in 0..LINES {
+    let _ = writeln!(events, r#"{{"time":"2024-01-01T{:02}:{:02}:{:02}.{:03}Z","message":"{i}"}}"#,
+      i / 3_600_000, i / 60_000 % 60, i / 1000 % 60, i % 1000);
+  }
+
+  let input = dir.join("input.log");
+  let socket = dir.join("saw.sock");
+  fs::write(&input, events).unwrap();
+
+  let mut child = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .arg(&input)
+    .args(["-f", "message=nothing", "--zip", "false", "--json", "true", "--control-socket"])
+    .arg(&socket)
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()
+    .expect("Failed to run saw");
+
+  let start = Instant::now();
+
+  let mut stream = loop {
+    match UnixStream::connect(&socket) {
+      Ok(stream) => break stream,
+      Err(_) if start.elapsed() < TIMEOUT => sleep(Duration::from_millis(10)),
+      Err(err) => panic!("Failed to connect to the control socket: {err}"),
+    }
+  };
+
+  stream.write_all(b"stop\n").unwrap();
+
+  let mut answer = String::new();
+  BufReader::new(&stream).read_line(&mut answer).unwrap();
+
+  let stopped = Instant::now();
+
+  while child.try_wait().unwrap().is_none() && stopped.elapsed() < TIMEOUT {
+    sleep(Duration::from_millis(10));
+  }
+
+  let finished = child.try_wait().unwrap();
+  let _ = child.kill();
+  let _ = fs::remove_dir_all(&dir);
+
+  assert!(answer.contains(r#""stopping":true"#), "{answer}");
+  assert!(finished.is_some_and(|status| status.success()), "saw was still running {TIMEOUT:?} after being stopped");
+  assert!(stopped.elapsed() < Duration::from_secs(2), "saw took {:?} to stop", stopped.elapsed());
+}
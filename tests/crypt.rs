@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/// 32 zero bytes as base64
+const KEY: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+
+fn saw(sources: &[&Path], args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(sources)
+    .args(args)
+    .args(["--zip", "false"])
+    .env("SAW_FIELD_KEY", KEY)
+    .stdin(Stdio::null())
+    .output()
+    .expect("Failed to run saw")
+}
+
+#[test]
+fn decrypted_fields_are_only_written_to_pretty_output_unless_asked() {
+  let dir = std::env::temp_dir().join(format!("saw-crypt-{}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+
+  let plain = dir.join("plain.log");
+  let encrypted = dir.join("encrypted.log");
+  fs::write(&plain, "{\"time\":\"2024-01-01T00:00:00Z\",\"message\":\"secret\"}\n").unwrap();
+  fs::write(&encrypted, saw(&[&plain], &["--encrypt-field", "message", "--json", "true"]).stdout).unwrap();
+
+  let json = saw(&[&encrypted], &["--decrypt-field", "message", "--json", "true"]);
+  let pretty = saw(&[&encrypted], &["--decrypt-field", "message", "--pretty", "%message"]);
+  let written = saw(&[&encrypted], &["--decrypt-field", "message", "--json", "true", "--write-decrypted"]);
+  let _ = fs::remove_dir_all(&dir);
+
+  assert_eq!(json.status.code(), Some(2));
+  assert!(!String::from_utf8_lossy(&json.stdout).contains("secret"));
+  assert!(String::from_utf8_lossy(&json.stderr).contains("--write-decrypted"));
+
+  assert!(pretty.status.success(), "{}", String::from_utf8_lossy(&pretty.stderr));
+  assert!(String::from_utf8_lossy(&pretty.stdout).contains("secret"));
+
+  assert!(written.status.success(), "{}", String::from_utf8_lossy(&written.stderr));
+  assert!(String::from_utf8_lossy(&written.stdout).contains(r#""message":"secret""#));
+}
@@ -0,0 +1,39 @@
+use std::fmt::Write;
+use std::fs;
+use std::process::{Command, Stdio};
+
+/// far more than saw reads in the budget below, even in a release build
+const LINES: u64 = 500_000;
+
+#[test]
+fn deadline_stops_a_run_whose_filter_leaves_out_everything() {
+  let dir = std::env::temp_dir().join(format!("saw-deadline-{}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+
+  let mut events = String::new();
+
+  for i in 0..LINES {
+    let _ = writeln!(events, r#"{{"time":"2024-01-01T{:02}:{:02}:{:02}.{:03}Z","message":"{i}"}}"#,
+      i / 3_600_000, i / 60_000 % 60, i / 1000 % 60, i % 1000);
+  }
+
+  let input = dir.join("input.log");
+  let output = dir.join("output.log");
+  fs::write(&input, events).unwrap();
+
+  let status = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .arg(&input)
+    .args(["--deadline", "50ms", "-f", "message=nothing", "--zip", "false", "--json", "true", "-o"])
+    .arg(&output)
+    .stdin(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .expect("Failed to run saw");
+
+  let checkpoint = fs::read_to_string(dir.join("output.log.checkpoint"));
+  let _ = fs::remove_dir_all(&dir);
+
+  assert_eq!(status.code(), Some(75));
+  assert!(checkpoint.expect("No checkpoint was written").contains(r#""resume_from":"2024-01-01T"#));
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn scratch(name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("saw-deterministic-{}-{name}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+fn saw(sources: &[&Path], args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(sources)
+    .args(args)
+    .env("SOURCE_DATE_EPOCH", "1700000000")
+    .env("SAW_FIELD_KEY", "00000000000000000000000000000000")
+    .stdin(Stdio::null())
+    .output()
+    .expect("Failed to run saw")
+}
+
+#[test]
+fn two_runs_write_the_same_bytes() {
+  let dir = scratch("runs");
+  let first = dir.join("first.log");
+  let second = dir.join("second.log");
+
+  fs::write(&first, concat!(
+    r#"{"time":"2024-01-01T00:00:00Z","message":"first"}"#, "\n",
+    r#"{"time":"2024-01-01T00:00:01Z","message":"tie from first"}"#, "\n",
+  )).unwrap();
+  fs::write(&second, concat!(
+    r#"{"time":"2024-01-01T00:00:01Z","message":"tie from second"}"#, "\n",
+    r#"{"time":"2024-01-01T00:00:02Z","message":"second"}"#, "\n",
+  )).unwrap();
+
+  let mut written = vec![];
+
+  for (index, sources) in [[&first, &second], [&second, &first]].iter().enumerate() {
+    let output = dir.join(format!("{index}.log.gz"));
+    let sources: Vec<&Path> = sources.iter().map(|source| source.as_path()).collect();
+    let run = saw(&sources, &["--deterministic", "--json", "true", "-o", output.to_str().unwrap()]);
+
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    written.push(fs::read(&output).unwrap());
+  }
+
+  let _ = fs::remove_dir_all(&dir);
+
+  assert!(!written[0].is_empty());
+  assert_eq!(written[0], written[1]);
+}
+
+#[test]
+fn options_that_change_between_runs_are_refused() {
+  let dir = scratch("refused");
+  let source = dir.join("source.log");
+  fs::write(&source, "{\"time\":\"2024-01-01T00:00:00Z\",\"message\":\"secret\"}\n").unwrap();
+
+  let encrypted = saw(&[&source], &["--deterministic", "--encrypt-field", "message"]);
+  let yearless = saw(&[&source], &["--deterministic", "--time-format", "%b %d %H:%M:%S"]);
+
+  let _ = fs::remove_dir_all(&dir);
+
+  assert_eq!(encrypted.status.code(), Some(2));
+  assert!(String::from_utf8_lossy(&encrypted.stderr).contains("--encrypt-field"));
+  assert_eq!(yearless.status.code(), Some(2));
+  assert!(String::from_utf8_lossy(&yearless.stderr).contains("--time-format"));
+}
@@ -0,0 +1,95 @@
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// the 22 lines take over ten seconds when each one waits out the grace for the quiet source
+const WITHIN: Duration = Duration::from_secs(3);
+
+fn event(second: u32, message: &str) -> String {
+  format!(r#"{{"time":"2024-01-01T00:{:02}:{:02}Z","message":"{message}"}}"#, second / 60, second % 60) + "\n"
+}
+
+fn scratch(name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("saw-follow-{}-{name}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+#[test]
+fn quiet_source_does_not_slow_down_the_others() {
+  let dir = scratch("quiet");
+  let quiet = dir.join("quiet.log");
+  let busy = dir.join("busy.log");
+
+  fs::write(&quiet, event(0, "quiet")).unwrap();
+  fs::write(&busy, event(1, "busy")).unwrap();
+
+  let mut child = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .arg(&quiet)
+    .arg(&busy)
+    .args(["--follow", "--zip", "false", "--json", "true"])
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .expect("Failed to run saw");
+
+  let (lines, received) = mpsc::channel();
+  let stdout = child.stdout.take().unwrap();
+
+  std::thread::spawn(move || {
+    for line in BufReader::new(stdout).lines() {
+      if lines.send(line.unwrap()).is_err() {
+        break;
+      }
+    }
+  });
+
+  sleep(Duration::from_millis(500));
+
+  let mut file = OpenOptions::new().append(true).open(&busy).unwrap();
+
+  for second in 2..22 {
+    file.write_all(event(second, "more").as_bytes()).unwrap();
+  }
+
+  file.flush().unwrap();
+
+  let start = Instant::now();
+  let mut count = 0;
+
+  while count < 22 && start.elapsed() < WITHIN {
+    if received.recv_timeout(Duration::from_millis(100)).is_ok() {
+      count += 1;
+    }
+  }
+
+  let _ = child.kill();
+  let _ = child.wait();
+  let _ = fs::remove_dir_all(&dir);
+
+  assert_eq!(count, 22, "only {count} lines were written within {WITHIN:?}");
+}
+
+#[test]
+fn poll_interval_needs_follow_and_a_unit() {
+  let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(["--command", "true"])
+    .args(args)
+    .stdin(Stdio::null())
+    .output()
+    .expect("Failed to run saw");
+
+  let without_follow = run(&["--poll-interval", "2s"]);
+  let without_unit = run(&["--follow", "--poll-interval", "2"]);
+
+  assert_eq!(without_follow.status.code(), Some(2));
+  assert!(String::from_utf8_lossy(&without_follow.stderr).contains("only valid with --follow"));
+  assert_eq!(without_unit.status.code(), Some(2));
+  assert!(String::from_utf8_lossy(&without_unit.stderr).contains("followed by ms or s"));
+}
@@ -0,0 +1,32 @@
+use std::process::{Command, Stdio};
+
+/**
+ * Run saw with --rate-limit RATE and return its exit code and what it wrote to stderr
+ */
+fn rate_limit(rate: &str) -> (Option<i32>, String) {
+  let output = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(["--command", "true", "--rate-limit", rate])
+    .stdin(Stdio::null())
+    .output()
+    .expect("Failed to run saw");
+
+  (output.status.code(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+#[test]
+fn rates_that_are_not_finite_or_too_slow_are_refused() {
+  for rate in ["nan/s", "inf/s", "1e-300/s", "0.5/h", "0/s", "-1/s", "10/s,burst=nan", "10/s,burst=inf"] {
+    let (code, stderr) = rate_limit(rate);
+
+    assert_eq!(code, Some(2), "{rate}: {stderr}");
+    assert!(stderr.contains("Rate limit"), "{rate}: {stderr}");
+    assert!(!stderr.contains("panicked"), "{rate}: {stderr}");
+  }
+}
+
+#[test]
+fn slowest_rate_is_accepted() {
+  let (code, stderr) = rate_limit("1/h");
+
+  assert_eq!(code, Some(0), "{stderr}");
+}
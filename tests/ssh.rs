@@ -0,0 +1,108 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+/// stands in for ssh: drops the host and runs the rest with sh, which is what the remote end does with it
+const FAKE_SSH: &str = "#!/bin/sh\nshift\necho \"$*\" >> \"$SSH_LOG\"\nexec sh -c \"$*\"\n";
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/**
+ * A scratch directory holding a fake ssh, and the log of every command it was asked to run
+ */
+fn scratch(name: &str) -> (PathBuf, PathBuf) {
+  let dir = std::env::temp_dir().join(format!("saw-ssh-{}-{name}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(dir.join("bin")).unwrap();
+
+  let ssh = dir.join("bin").join("ssh");
+  fs::write(&ssh, FAKE_SSH).unwrap();
+  fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+
+  (dir.clone(), dir.join("ssh.log"))
+}
+
+/**
+ * Run saw with the fake ssh first on the PATH, killing it if it hasn't finished within TIMEOUT
+ */
+fn saw(dir: &Path, log: &Path, args: &[&str]) -> Output {
+  let path = format!("{}:{}", dir.join("bin").display(), std::env::var("PATH").unwrap_or_default());
+
+  let child = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(args)
+    .args(["--zip", "false", "--json", "true"])
+    .env("PATH", path)
+    .env("SSH_LOG", log)
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .expect("Failed to run saw");
+
+  let pid = child.id();
+  let output = spawn(move || child.wait_with_output().expect("Failed to run saw"));
+  let start = Instant::now();
+
+  while !output.is_finished() {
+    if start.elapsed() > TIMEOUT {
+      let _ = Command::new("kill").arg(pid.to_string()).status();
+      panic!("saw {args:?} did not finish within {TIMEOUT:?}");
+    }
+
+    sleep(Duration::from_millis(50));
+  }
+
+  output.join().unwrap()
+}
+
+#[test]
+fn remote_paths_are_quoted_apart_from_globs() {
+  let (dir, log) = scratch("quoted");
+  let logs = dir.join("my logs; touch pwned");
+  fs::create_dir_all(&logs).unwrap();
+  fs::write(logs.join("app.log"), "{\"time\":\"2024-01-01T00:00:00Z\",\"message\":\"remote\"}\n").unwrap();
+
+  let remote = format!("web1:{}/*.log", logs.display());
+  let output = saw(&dir, &log, &["--ssh", &remote]);
+  let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+  let pwned = Path::new("pwned").exists() || dir.join("pwned").exists();
+  let _ = fs::remove_dir_all(&dir);
+
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(stdout.contains(r#""_host":"web1""#), "{stdout}");
+  assert!(stdout.contains(r#""message":"remote""#), "{stdout}");
+  assert!(!pwned, "the remote shell ran part of the path");
+}
+
+#[test]
+fn remote_files_are_only_streamed_once_read() {
+  let (dir, log) = scratch("lazy");
+  fs::write(dir.join("app.log"), "{\"time\":\"2024-01-01T00:00:00Z\",\"message\":\"remote\"}\n").unwrap();
+
+  let remote = format!("web1:{}/*.log", dir.display());
+  let output = saw(&dir, &log, &["--ssh", &remote, "--dry-run"]);
+  let commands = fs::read_to_string(&log).unwrap_or_default();
+  let _ = fs::remove_dir_all(&dir);
+
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(commands.contains("ls -1d"), "{commands}");
+  assert!(!commands.contains("gzip"), "{commands}");
+}
+
+#[test]
+fn follow_is_refused_with_ssh() {
+  let (dir, log) = scratch("follow");
+  fs::write(dir.join("app.log"), "{\"time\":\"2024-01-01T00:00:00Z\",\"message\":\"remote\"}\n").unwrap();
+
+  let remote = format!("web1:{}/*.log", dir.display());
+  let output = saw(&dir, &log, &["--ssh", &remote, "--follow"]);
+  let _ = fs::remove_dir_all(&dir);
+
+  assert_eq!(output.status.code(), Some(2));
+  assert!(String::from_utf8_lossy(&output.stderr).contains("--follow with --ssh"));
+}
@@ -0,0 +1,43 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const EVENTS: &str = r#"{"time":"2024-01-01T00:00:00Z","message":"kept"}
+{"time":"bad","message":"bad"}
+{"time":"2024-02-30T00:00:00Z","message":"no such day"}
+{"time":"2024-01-01T23:59:60.5Z","message":"leap second"}
+{"time":"2024-01-01T00:00:00+01:00","message":"offset"}
+{"time":"2024-01-01T00:00:00.123456Z","message":"micros"}
+"#;
+
+fn run(sources: &[&Path]) -> String {
+  let output = Command::new(env!("CARGO_BIN_EXE_saw"))
+    .args(sources)
+    .args(["--zip", "false", "--json", "true"])
+    .stdin(Stdio::null())
+    .stderr(Stdio::null())
+    .output()
+    .expect("Failed to run saw");
+
+  String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn single_source_drops_the_same_bad_times_as_a_merge() {
+  let dir = std::env::temp_dir().join(format!("saw-stream-{}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+
+  let events = dir.join("events.log");
+  let empty = dir.join("empty.log");
+  fs::write(&events, EVENTS).unwrap();
+  fs::write(&empty, "").unwrap();
+
+  let streamed = run(&[&events]);
+  let merged = run(&[&events, &empty]);
+  let _ = fs::remove_dir_all(&dir);
+
+  assert_eq!(streamed, merged);
+  assert!(!streamed.contains(r#""bad""#), "{streamed}");
+  assert_eq!(streamed.lines().count(), 3, "{streamed}");
+}